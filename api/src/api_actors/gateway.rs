@@ -0,0 +1,154 @@
+use crate::api_actors::session::ClientSession;
+use crate::messages::internal_messages::{
+    FetchNearbyRestaurants, FetchOrderStatus, GetOrder, ListRestaurants, PlaceOrder, SubmitOrder,
+};
+use actix::prelude::*;
+use colored::Color;
+use common::logger::Logger;
+use common::types::dtos::OrderDTO;
+use common::types::restaurant_info::RestaurantInfo;
+use common::utils::get_rand_f32_tuple;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+/// The `ApiGateway` actor bridges the HTTP routes and the cluster protocol.
+///
+/// ## Responsibilities
+/// - Keeps one [`ClientSession`] per client ID, creating it on first use.
+/// - Forwards HTTP requests to the matching session.
+pub struct ApiGateway {
+    /// List of server socket addresses to connect to.
+    pub servers: Vec<SocketAddr>,
+    /// Active sessions, keyed by client ID.
+    pub sessions: HashMap<String, Addr<ClientSession>>,
+    /// Logger for gateway events.
+    pub logger: Logger,
+}
+
+impl ApiGateway {
+    /// Creates a new `ApiGateway` for the given cluster.
+    ///
+    /// ## Arguments
+    /// * `servers` - A vector of server socket addresses.
+    pub fn new(servers: Vec<SocketAddr>) -> Self {
+        Self {
+            servers,
+            sessions: HashMap::new(),
+            logger: Logger::new("API Gateway", Color::BrightMagenta),
+        }
+    }
+
+    /// Returns a future resolving to the session of `client_id`, starting one if needed.
+    ///
+    /// ## Arguments
+    /// * `client_id` - The client the session acts for.
+    /// * `position` - The position used if a new session has to be created.
+    fn session_for(
+        &mut self,
+        client_id: String,
+        position: (f32, f32),
+    ) -> ResponseActFuture<Self, Result<Addr<ClientSession>, String>> {
+        if let Some(session) = self.sessions.get(&client_id).filter(|s| s.connected()) {
+            let session = session.clone();
+            return Box::pin(async move { Ok(session) }.into_actor(self));
+        }
+
+        let servers = self.servers.clone();
+        let id = client_id.clone();
+        Box::pin(
+            async move { ClientSession::new(servers, id, position).await }
+                .into_actor(self)
+                .map(move |session, actor, _ctx| {
+                    // Otro pedido pudo haber creado la sesión mientras conectábamos
+                    if let Some(existing) = actor.sessions.get(&client_id).filter(|s| s.connected())
+                    {
+                        return Ok(existing.clone());
+                    }
+                    let session = session
+                        .ok_or_else(|| "Could not reach any server".to_string())?
+                        .start();
+                    actor.logger.info(format!("New session for {}", client_id));
+                    actor.sessions.insert(client_id, session.clone());
+                    Ok(session)
+                }),
+        )
+    }
+}
+
+impl Actor for ApiGateway {
+    type Context = Context<Self>;
+}
+
+/// Handles [`PlaceOrder`] messages.
+///
+/// Submits the order through the client's session.
+impl Handler<PlaceOrder> for ApiGateway {
+    type Result = ResponseActFuture<Self, Result<OrderDTO, String>>;
+
+    fn handle(&mut self, msg: PlaceOrder, _ctx: &mut Self::Context) -> Self::Result {
+        Box::pin(
+            self.session_for(msg.client_id, msg.position)
+                .then(move |session, actor, _ctx| {
+                    async move {
+                        let session = session?;
+                        session
+                            .send(SubmitOrder {
+                                restaurant_id: msg.restaurant_id,
+                                items: msg.items,
+                                priority: msg.priority,
+                                promo_code: msg.promo_code,
+                            })
+                            .await
+                            .map_err(|e| e.to_string())?
+                    }
+                    .into_actor(actor)
+                }),
+        )
+    }
+}
+
+/// Handles [`GetOrder`] messages.
+///
+/// Asks the cluster for the order through the session of its client, so orders placed
+/// through other gateways or clients are found too.
+impl Handler<GetOrder> for ApiGateway {
+    type Result = ResponseActFuture<Self, Result<Option<OrderDTO>, String>>;
+
+    fn handle(&mut self, msg: GetOrder, _ctx: &mut Self::Context) -> Self::Result {
+        let order_id = msg.order_id;
+        Box::pin(self.session_for(msg.client_id, get_rand_f32_tuple()).then(
+            move |session, actor, _ctx| {
+                async move {
+                    session?
+                        .send(FetchOrderStatus { order_id })
+                        .await
+                        .map_err(|e| e.to_string())?
+                }
+                .into_actor(actor)
+            },
+        ))
+    }
+}
+
+/// Handles [`ListRestaurants`] messages.
+///
+/// Asks the client's session for the restaurants near its position.
+impl Handler<ListRestaurants> for ApiGateway {
+    type Result = ResponseActFuture<Self, Result<Vec<RestaurantInfo>, String>>;
+
+    fn handle(&mut self, msg: ListRestaurants, _ctx: &mut Self::Context) -> Self::Result {
+        let filter = msg.filter;
+        Box::pin(
+            self.session_for(msg.client_id, msg.position)
+                .then(|session, actor, _ctx| {
+                    async move {
+                        session?
+                            .send(FetchNearbyRestaurants { filter })
+                            .await
+                            .map_err(|e| e.to_string())?
+                    }
+                    .into_actor(actor)
+                }),
+        )
+    }
+}
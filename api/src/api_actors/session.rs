@@ -0,0 +1,543 @@
+use crate::messages::internal_messages::{
+    CancelTrackedOrder, FetchNearbyRestaurants, FetchOrderStatus, SubmitOrder,
+};
+use actix::fut::wrap_future;
+use actix::prelude::*;
+use colored::Color;
+use common::constants::{
+    API_REQUEST_TIMEOUT, DELAY_SECONDS_TO_START_RECONNECT, UNASSIGNED_ORDER_ID,
+};
+use common::logger::Logger;
+use common::messages::client_messages::*;
+use common::messages::shared_messages::*;
+use common::network::communicator::Communicator;
+use common::network::connections::{connect_one, connect_some, reconnect};
+use common::network::peer_types::{PeerIdentity, PeerType};
+use common::network::stream::PeerStream;
+use common::types::dtos::{ClientDTO, OrderDTO, UserDTO};
+use common::types::hlc::HlcTimestamp;
+use common::types::order_status::OrderStatus;
+use common::types::restaurant_info::RestaurantInfo;
+use common::types::tenant::TenantId;
+use rand::Rng;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// The `ClientSession` actor speaks the cluster protocol on behalf of a single HTTP client.
+///
+/// ## Responsibilities
+/// - Connects to the cluster as a `ClientType` peer and follows the leader.
+/// - Registers the client and queues outgoing messages until registration completes.
+/// - Tracks the unfinished orders placed through it, updating them on `NotifyOrderUpdated`
+///   and forgetting them once they finish.
+/// - Resolves pending nearby-restaurant and order status lookups.
+pub struct ClientSession {
+    /// List of server socket addresses to connect to.
+    pub servers: Vec<SocketAddr>,
+    /// ID of the client this session acts for.
+    pub client_id: String,
+    /// Position of the client in 2D coordinates.
+    pub client_position: (f32, f32),
+    /// Marketplace the client belongs to, the one of the gateway.
+    pub tenant_id: TenantId,
+    /// Unfinished orders placed through this session, keyed by order ID.
+    pub orders: HashMap<u64, OrderDTO>,
+    /// Communicator for network interactions with the leader.
+    pub communicator: Option<Communicator<ClientSession>>,
+    /// Pending TCP stream before the actor starts.
+    pub pending_stream: Option<PeerStream>,
+    /// Messages waiting for the registration to complete.
+    pub outbox: Vec<NetworkMessage>,
+    /// Pending nearby-restaurant lookups.
+    pub restaurant_waiters: Vec<oneshot::Sender<Vec<RestaurantInfo>>>,
+    /// Orders waiting for the leader to give them an ID, by the `request_id` they were
+    /// placed with.
+    pub order_waiters: HashMap<u64, oneshot::Sender<OrderDTO>>,
+    /// Pending order status lookups, by order ID, answered with `None` if the cluster does
+    /// not know the order.
+    pub status_waiters: HashMap<u64, Vec<oneshot::Sender<Option<OrderDTO>>>>,
+    /// Whether the leader has already answered the `RegisterUser`.
+    pub registered: bool,
+    /// Logger for session events.
+    pub logger: Logger,
+    /// Timer for waiting reconnection attempts after a connection is closed.
+    waiting_reconnection_timer: Option<SpawnHandle>,
+}
+
+impl ClientSession {
+    /// Creates a new `ClientSession`, connecting to one of the available servers.
+    ///
+    /// ## Arguments
+    /// * `servers` - A vector of server socket addresses.
+    /// * `client_id` - The client this session acts for.
+    /// * `client_position` - The position of the client.
+    ///
+    /// ## Returns
+    /// `None` if no server could be reached.
+    pub async fn new(
+        servers: Vec<SocketAddr>,
+        client_id: String,
+        client_position: (f32, f32),
+    ) -> Option<Self> {
+        let logger = Logger::new(format!("Session {}", &client_id), Color::Cyan);
+        let pending_stream = connect_some(
+            servers.clone(),
+            &PeerIdentity::new(PeerType::ClientType, client_id.clone()),
+        )
+        .await;
+
+        if pending_stream.is_none() {
+            logger.error("Failed to connect to any server.");
+            return None;
+        }
+
+        Some(Self {
+            servers,
+            client_id,
+            client_position,
+            tenant_id: TenantId::from_env(),
+            orders: HashMap::new(),
+            communicator: None,
+            pending_stream,
+            outbox: Vec::new(),
+            restaurant_waiters: Vec::new(),
+            order_waiters: HashMap::new(),
+            status_waiters: HashMap::new(),
+            registered: false,
+            logger,
+            waiting_reconnection_timer: None,
+        })
+    }
+
+    /// Sends a network message to the leader, or queues it until the session is registered.
+    ///
+    /// ## Arguments
+    /// * `message` - The network message to send.
+    pub fn send_or_queue(&mut self, message: NetworkMessage) {
+        if !self.registered {
+            self.outbox.push(message);
+            return;
+        }
+        self.send_network_message(message);
+    }
+
+    /// Returns the identity announced to the servers when connecting.
+    fn identity(&self) -> PeerIdentity {
+        PeerIdentity::new(PeerType::ClientType, self.client_id.clone())
+    }
+
+    /// Sends a network message to the connected server via the communicator.
+    ///
+    /// ## Arguments
+    /// * `message` - The network message to send.
+    pub fn send_network_message(&self, message: NetworkMessage) {
+        if let Some(communicator) = &self.communicator {
+            if let Some(sender) = &communicator.sender {
+                sender.do_send(message);
+            } else {
+                self.logger.error("Sender not initialized in communicator");
+            }
+        } else {
+            self.logger.error("Communicator not found!");
+        }
+    }
+
+    /// Starts the handshake by asking the server who the current leader is.
+    pub fn start_running(&self) {
+        if let Some(communicator) = &self.communicator {
+            self.send_network_message(NetworkMessage::WhoIsLeader(WhoIsLeader {
+                origin_addr: communicator.local_address,
+                user_id: self.client_id.clone(),
+            }));
+        }
+    }
+
+    /// Marks the session as registered and flushes every queued message.
+    fn on_registered(&mut self) {
+        self.registered = true;
+        for message in std::mem::take(&mut self.outbox) {
+            self.send_network_message(message);
+        }
+    }
+
+    /// Keeps the last known state of `order`, forgetting it once it finished.
+    ///
+    /// ## Arguments
+    /// * `order` - The order as the leader last reported it.
+    fn track_order(&mut self, order: OrderDTO) {
+        if order.status.is_final() {
+            self.orders.remove(&order.order_id);
+        } else {
+            self.orders.insert(order.order_id, order);
+        }
+    }
+
+    /// Builds the [`ClientDTO`] describing this session's client.
+    fn client_dto(&self) -> ClientDTO {
+        ClientDTO {
+            client_position: self.client_position,
+            client_id: self.client_id.clone(),
+            client_order: None,
+            tenant_id: self.tenant_id.clone(),
+            time_stamp: HlcTimestamp::now(),
+        }
+    }
+}
+
+impl Actor for ClientSession {
+    type Context = Context<Self>;
+
+    /// Initializes the communicator and asks for the current leader.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let communicator = Communicator::new(
+            self.pending_stream
+                .take()
+                .expect("Pending stream should be set"),
+            ctx.address(),
+            PeerType::ClientType,
+        );
+        self.communicator = Some(communicator);
+
+        let handler = ctx.run_later(Duration::from_millis(100), move |_, ctx| {
+            ctx.address().do_send(StartRunning);
+        });
+        self.waiting_reconnection_timer = Some(handler);
+    }
+}
+
+impl Handler<StartRunning> for ClientSession {
+    type Result = ();
+    fn handle(&mut self, _msg: StartRunning, _ctx: &mut Self::Context) -> Self::Result {
+        self.start_running();
+    }
+}
+
+/// Handles [`SubmitOrder`] messages.
+///
+/// Sends a new order of the client to the leader and waits for the ID the leader gives it,
+/// failing after [`API_REQUEST_TIMEOUT`]. From then on the order is tracked by that ID.
+impl Handler<SubmitOrder> for ClientSession {
+    type Result = ResponseFuture<Result<OrderDTO, String>>;
+
+    fn handle(&mut self, msg: SubmitOrder, _ctx: &mut Self::Context) -> Self::Result {
+        let request_id: u64 = rand::thread_rng().gen_range(1..=u64::MAX);
+        let order = OrderDTO {
+            order_id: UNASSIGNED_ORDER_ID,
+            client_id: self.client_id.clone(),
+            restaurant_id: msg.restaurant_id,
+            items: msg.items,
+            status: OrderStatus::Pending,
+            delivery_id: None,
+            time_stamp: HlcTimestamp::now(),
+            tenant_id: self.tenant_id.clone(),
+            client_position: self.client_position,
+            expected_delivery_time: 0,
+            expected_preparation_time: 0,
+            priority: msg.priority,
+            discount: 0,
+        };
+        self.logger.info(format!(
+            "Placing order at restaurant {} (request {})",
+            order.restaurant_id, request_id
+        ));
+        let (tx, rx) = oneshot::channel();
+        self.order_waiters.insert(request_id, tx);
+        self.send_or_queue(NetworkMessage::RequestThisOrder(RequestThisOrder {
+            order,
+            promo_code: msg.promo_code,
+            request_id,
+        }));
+        Box::pin(async move {
+            match tokio::time::timeout(API_REQUEST_TIMEOUT, rx).await {
+                Ok(Ok(order)) => Ok(order),
+                Ok(Err(_)) => Err("Session closed before the order was placed".to_string()),
+                Err(_) => Err("Timed out waiting for the order to be placed".to_string()),
+            }
+        })
+    }
+}
+
+/// Handles [`CancelTrackedOrder`] messages.
+///
+/// Asks the leader to cancel an order placed through this session.
+impl Handler<CancelTrackedOrder> for ClientSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: CancelTrackedOrder, _ctx: &mut Self::Context) -> Self::Result {
+        if !self.orders.contains_key(&msg.order_id) {
+            self.logger.warn(format!(
+                "Order {} is not tracked, cannot cancel it",
+                msg.order_id
+            ));
+            return;
+        }
+        self.logger
+            .info(format!("Cancelling order {}", msg.order_id));
+        self.send_or_queue(NetworkMessage::CancelMyOrder(CancelMyOrder {
+            client_id: self.client_id.clone(),
+            order_id: msg.order_id,
+            waive_fee: false,
+        }));
+    }
+}
+
+/// Handles [`FetchOrderStatus`] messages.
+///
+/// Asks the cluster for the stored state of an order of the client and waits for the answer,
+/// failing after [`API_REQUEST_TIMEOUT`]. Recently finished or cancelled orders are answered
+/// with their last state, and orders the cluster does not know for the client with `None`.
+impl Handler<FetchOrderStatus> for ClientSession {
+    type Result = ResponseFuture<Result<Option<OrderDTO>, String>>;
+
+    fn handle(&mut self, msg: FetchOrderStatus, _ctx: &mut Self::Context) -> Self::Result {
+        let (tx, rx) = oneshot::channel();
+        self.status_waiters
+            .entry(msg.order_id)
+            .or_default()
+            .push(tx);
+        self.send_or_queue(NetworkMessage::RequestOrderStatus(RequestOrderStatus {
+            client_id: self.client_id.clone(),
+            order_id: msg.order_id,
+        }));
+        Box::pin(async move {
+            match tokio::time::timeout(API_REQUEST_TIMEOUT, rx).await {
+                Ok(Ok(order)) => Ok(order),
+                Ok(Err(_)) => Err("Session closed before the answer arrived".to_string()),
+                Err(_) => Err("Timed out waiting for the order status".to_string()),
+            }
+        })
+    }
+}
+
+/// Handles [`FetchNearbyRestaurants`] messages.
+///
+/// Asks the leader for the restaurants near the client and waits for the answer,
+/// failing after [`API_REQUEST_TIMEOUT`].
+impl Handler<FetchNearbyRestaurants> for ClientSession {
+    type Result = ResponseFuture<Result<Vec<RestaurantInfo>, String>>;
+
+    fn handle(&mut self, msg: FetchNearbyRestaurants, _ctx: &mut Self::Context) -> Self::Result {
+        let (tx, rx) = oneshot::channel();
+        self.restaurant_waiters.push(tx);
+        let client = self.client_dto();
+        self.send_or_queue(NetworkMessage::RequestNearbyRestaurants(
+            RequestNearbyRestaurants {
+                client,
+                radius: None,
+                filter: msg.filter,
+            },
+        ));
+        Box::pin(async move {
+            match tokio::time::timeout(API_REQUEST_TIMEOUT, rx).await {
+                Ok(Ok(restaurants)) => Ok(restaurants),
+                Ok(Err(_)) => Err("Session closed before the answer arrived".to_string()),
+                Err(_) => Err("Timed out waiting for nearby restaurants".to_string()),
+            }
+        })
+    }
+}
+
+/// Handles [`LeaderIs`] messages.
+///
+/// Registers the client if already connected to the leader; otherwise reconnects to it.
+impl Handler<LeaderIs> for ClientSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: LeaderIs, ctx: &mut Self::Context) -> Self::Result {
+        let leader_addr = msg.coord_addr;
+        let self_addr = ctx.address();
+        let logger = self.logger.clone();
+
+        if let Some(handler) = self.waiting_reconnection_timer.take() {
+            ctx.cancel_future(handler);
+        }
+
+        let communicator_opt = self.communicator.as_ref().map(|c| c.peer_address);
+
+        if Some(leader_addr) == communicator_opt {
+            let local_address = self
+                .communicator
+                .as_ref()
+                .map(|c| c.local_address)
+                .expect("Socket address not set");
+            self.send_network_message(NetworkMessage::RegisterUser(RegisterUser {
+                origin_addr: local_address,
+                user_id: self.client_id.clone(),
+                position: self.client_position,
+                secret: None,
+                tags: Vec::new(),
+                vehicle: None,
+                tenant_id: self.tenant_id.clone(),
+            }));
+            return;
+        }
+
+        if let Some(comm) = self.communicator.as_mut() {
+            comm.shutdown();
+        }
+        self.communicator = None;
+
+        let identity = self.identity();
+        ctx.spawn(
+            wrap_future(async move {
+                if let Some(new_stream) = connect_one(leader_addr, &identity).await {
+                    Some(Communicator::new(
+                        new_stream,
+                        self_addr,
+                        PeerType::ClientType,
+                    ))
+                } else {
+                    logger.error(format!(
+                        "Failed to connect to the new leader at {}",
+                        leader_addr
+                    ));
+                    None
+                }
+            })
+            .map(move |maybe_communicator, actor: &mut Self, ctx| {
+                if let Some(new_communicator) = maybe_communicator {
+                    actor.communicator = Some(new_communicator);
+                    let handler = ctx.run_later(Duration::from_millis(100), move |_, ctx| {
+                        ctx.address().do_send(StartRunning);
+                    });
+                    actor.waiting_reconnection_timer = Some(handler);
+                }
+            }),
+        );
+    }
+}
+
+/// Handles [`ConnectionClosed`] messages.
+///
+/// Attempts to reconnect to one of the known servers. If no server can be reached,
+/// the session is stopped and every pending lookup fails.
+impl Handler<ConnectionClosed> for ClientSession {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ConnectionClosed, ctx: &mut Self::Context) -> Self::Result {
+        if self.communicator.is_some() {
+            return;
+        }
+
+        let servers = self.servers.clone();
+        let identity = self.identity();
+        let fut = async move { reconnect(servers, &identity).await };
+
+        let fut = wrap_future::<_, Self>(fut).map(|result, actor: &mut Self, ctx| match result {
+            Some(stream) => {
+                actor.communicator = Some(Communicator::new(
+                    stream,
+                    ctx.address(),
+                    PeerType::ClientType,
+                ));
+                actor.logger.info("Reconnected successfully.");
+                let handler = ctx.run_later(Duration::from_millis(100), move |_, ctx| {
+                    ctx.address().do_send(StartRunning);
+                });
+                actor.waiting_reconnection_timer = Some(handler);
+            }
+            None => {
+                actor
+                    .logger
+                    .error("Failed to reconnect to any server after closed connection");
+                ctx.stop();
+            }
+        });
+        ctx.spawn(fut);
+    }
+}
+
+/// Handles [`NetworkMessage`] messages.
+///
+/// Follows the registration handshake, resolves nearby-restaurant lookups and keeps
+/// the tracked orders up to date.
+impl Handler<NetworkMessage> for ClientSession {
+    type Result = ();
+
+    fn handle(&mut self, msg: NetworkMessage, ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            NetworkMessage::RetryLater(_msg_data) => {
+                let handler = ctx.run_later(DELAY_SECONDS_TO_START_RECONNECT, |_, ctx| {
+                    ctx.address().do_send(StartRunning);
+                });
+                self.waiting_reconnection_timer = Some(handler);
+            }
+            NetworkMessage::LeaderIs(msg_data) => ctx.address().do_send(msg_data),
+            NetworkMessage::RecoveredInfo(UserDTO::Client(client_dto)) => {
+                if let Some(order) = client_dto.client_order {
+                    self.track_order(order);
+                }
+                self.on_registered();
+            }
+            NetworkMessage::RecoveredInfo(_) | NetworkMessage::NoRecoveredInfo => {
+                self.on_registered();
+            }
+            NetworkMessage::IdentityRejected(msg_data) => {
+                // El ID es de un cliente con archivo de identidad: la sesión no puede usarlo
+                self.logger.error(format!(
+                    "Client {} belongs to another user, closing the session",
+                    msg_data.user_id
+                ));
+                ctx.stop();
+            }
+            NetworkMessage::NearbyRestaurants(msg_data) => {
+                for waiter in self.restaurant_waiters.drain(..) {
+                    let _ = waiter.send(msg_data.restaurants.clone());
+                }
+            }
+            NetworkMessage::NotifyOrderUpdated(msg_data) => {
+                self.logger.info(format!(
+                    "Order {} is now {}",
+                    msg_data.order.order_id, msg_data.order.status
+                ));
+                if msg_data.update_id != 0 {
+                    self.send_or_queue(NetworkMessage::AckOrderUpdate(AckOrderUpdate {
+                        client_id: self.client_id.clone(),
+                        update_id: msg_data.update_id,
+                    }));
+                }
+                for waiter in self
+                    .status_waiters
+                    .remove(&msg_data.order.order_id)
+                    .unwrap_or_default()
+                {
+                    let _ = waiter.send(Some(msg_data.order.clone()));
+                }
+                self.track_order(msg_data.order.clone());
+                if let Some(waiter) = self.order_waiters.remove(&msg_data.request_id) {
+                    let _ = waiter.send(msg_data.order);
+                }
+            }
+            NetworkMessage::CancelOrder(msg_data) => {
+                self.orders.remove(&msg_data.order.order_id);
+            }
+            NetworkMessage::OrderNotFound(msg_data) => {
+                for waiter in self
+                    .status_waiters
+                    .remove(&msg_data.order_id)
+                    .unwrap_or_default()
+                {
+                    let _ = waiter.send(None);
+                }
+            }
+            NetworkMessage::ConnectionClosed(msg_data) => {
+                self.logger.warn(format!(
+                    "Connection closed with address: {}",
+                    msg_data.remote_addr
+                ));
+                self.communicator = None;
+                self.registered = false;
+                let handle = ctx.run_later(DELAY_SECONDS_TO_START_RECONNECT, move |_, ctx| {
+                    ctx.address().do_send(msg_data.clone());
+                });
+                self.waiting_reconnection_timer = Some(handle);
+            }
+            _ => {
+                self.logger
+                    .info(format!("NetworkMessage ignored: {:?}", msg));
+            }
+        }
+    }
+}
@@ -0,0 +1,3 @@
+pub mod api_actors;
+pub mod messages;
+pub mod routes;
@@ -0,0 +1,36 @@
+use actix::prelude::*;
+use actix_web::{App, HttpServer, web};
+use api::api_actors::gateway::ApiGateway;
+use api::routes;
+use common::constants::{API_GATEWAY_PORT, SERVER_IP_ADDRESS};
+use common::network::connections::coordinator_addresses;
+use common::utils::print_welcome_message;
+use std::env;
+
+#[actix::main]
+async fn main() -> std::io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let port = args
+        .get(1)
+        .and_then(|p| p.parse::<u16>().ok())
+        .unwrap_or(API_GATEWAY_PORT);
+
+    let servers = coordinator_addresses();
+
+    print_welcome_message();
+    println!(
+        "REST API listening on http://{}:{}",
+        SERVER_IP_ADDRESS, port
+    );
+
+    let gateway = web::Data::new(ApiGateway::new(servers).start());
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(gateway.clone())
+            .configure(routes::configure)
+    })
+    .bind((SERVER_IP_ADDRESS, port))?
+    .run()
+    .await
+}
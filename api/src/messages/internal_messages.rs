@@ -0,0 +1,117 @@
+use actix::Message;
+use common::types::dtos::{OrderDTO, OrderItem};
+use common::types::order_priority::OrderPriority;
+use common::types::restaurant_filter::RestaurantFilter;
+use common::types::restaurant_info::RestaurantInfo;
+
+/////////////////////////////////////////////////////////////////////
+// Mensajes de las rutas HTTP al ApiGateway
+/////////////////////////////////////////////////////////////////////
+
+/// Message sent by the HTTP layer to place a new order on behalf of a client.
+///
+/// ## Purpose
+/// Creates (or reuses) the client session and forwards a `RequestThisOrder` to the leader.
+///
+/// ## Contents
+/// - `client_id`: The ID of the client placing the order.
+/// - `position`: The (x, y) position of the client.
+/// - `restaurant_id`: The restaurant the order is addressed to.
+/// - `items`: The dishes being ordered.
+/// - `priority`: Whether the order is express or standard.
+/// - `promo_code`: The promo code to use, if any.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<OrderDTO, String>")]
+pub struct PlaceOrder {
+    pub client_id: String,
+    pub position: (f32, f32),
+    pub restaurant_id: String,
+    pub items: Vec<OrderItem>,
+    pub priority: OrderPriority,
+    pub promo_code: Option<String>,
+}
+
+/// Message sent by the HTTP layer to query the current state of an order.
+///
+/// ## Purpose
+/// Asks the cluster, through the session of the client that placed the order, for its
+/// stored [`OrderDTO`].
+///
+/// ## Contents
+/// - `client_id`: The ID of the client that placed the order.
+/// - `order_id`: The ID of the order being queried.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<Option<OrderDTO>, String>")]
+pub struct GetOrder {
+    pub client_id: String,
+    pub order_id: u64,
+}
+
+/// Message sent by the HTTP layer to list the restaurants near a client.
+///
+/// ## Purpose
+/// Forwards a `RequestNearbyRestaurants` to the leader and waits for the answer.
+///
+/// ## Contents
+/// - `client_id`: The ID of the client asking for restaurants.
+/// - `position`: The (x, y) position of the client.
+/// - `filter`: Which restaurants to list, and in which order.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<Vec<RestaurantInfo>, String>")]
+pub struct ListRestaurants {
+    pub client_id: String,
+    pub position: (f32, f32),
+    pub filter: RestaurantFilter,
+}
+
+/////////////////////////////////////////////////////////////////////
+// Mensajes del ApiGateway a las ClientSession
+/////////////////////////////////////////////////////////////////////
+
+/// Message sent to a session to ask the cluster for the current state of one of the orders
+/// of its client, answered with `None` if the cluster does not know it.
+///
+/// ## Contents
+/// - `order_id`: The ID of the order being queried.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<Option<OrderDTO>, String>")]
+pub struct FetchOrderStatus {
+    pub order_id: u64,
+}
+
+/// Message sent to a session to place an order for its client, answered with the order and
+/// the ID the leader gave it.
+///
+/// ## Contents
+/// - `restaurant_id`: The restaurant the order is addressed to.
+/// - `items`: The dishes being ordered.
+/// - `priority`: Whether the order is express or standard.
+/// - `promo_code`: The promo code to use, if any.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<OrderDTO, String>")]
+pub struct SubmitOrder {
+    pub restaurant_id: String,
+    pub items: Vec<OrderItem>,
+    pub priority: OrderPriority,
+    pub promo_code: Option<String>,
+}
+
+/// Message sent to a session to fetch the restaurants near its client.
+///
+/// ## Contents
+/// - `filter`: Which restaurants to list, and in which order.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<Vec<RestaurantInfo>, String>")]
+pub struct FetchNearbyRestaurants {
+    pub filter: RestaurantFilter,
+}
+
+/// Message sent to a session to cancel an order it is tracking.
+///
+/// ## Contents
+/// - `order_id`: The ID of the order to cancel.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct CancelTrackedOrder {
+    pub order_id: u64,
+}
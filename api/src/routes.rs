@@ -0,0 +1,225 @@
+use crate::api_actors::gateway::ApiGateway;
+use crate::messages::internal_messages::{GetOrder, ListRestaurants, PlaceOrder};
+use actix::Addr;
+use actix_web::{HttpResponse, Responder, get, post, web};
+use common::types::dtos::OrderItem;
+use common::types::map::Map;
+use common::types::order_priority::OrderPriority;
+use common::types::restaurant_filter::{RestaurantFilter, RestaurantSort};
+use common::utils::get_rand_f32_tuple;
+use serde::Deserialize;
+
+/// Body of a `POST /orders` request.
+///
+/// If `position` is omitted, a random one is assigned, as the terminal client does; if not,
+/// it must be inside the city [`Map`].
+/// Each item of the cart has a `dish` and a `quantity`; its price is set by the server.
+/// Orders are `Standard` unless `priority` says otherwise, and `promo_code` is optional.
+#[derive(Debug, Deserialize)]
+pub struct PlaceOrderRequest {
+    pub client_id: String,
+    pub position: Option<(f32, f32)>,
+    pub restaurant_id: String,
+    pub items: Vec<OrderItem>,
+    #[serde(default)]
+    pub priority: OrderPriority,
+    #[serde(default)]
+    pub promo_code: Option<String>,
+}
+
+/// Query string of a `GET /orders/{id}` request.
+#[derive(Debug, Deserialize)]
+pub struct OrderQuery {
+    /// The client that placed the order.
+    pub client_id: String,
+}
+
+/// Query string of a `GET /restaurants` request.
+#[derive(Debug, Deserialize)]
+pub struct RestaurantsQuery {
+    pub client_id: String,
+    pub x: Option<f32>,
+    pub y: Option<f32>,
+    /// Text that the ID of the restaurants must contain.
+    pub q: Option<String>,
+    /// Tag that the restaurants must have, e.g. `pizza`.
+    pub tag: Option<String>,
+    /// `distance` (default) or `name`.
+    #[serde(default)]
+    pub sort: RestaurantSort,
+    /// Whether the closed restaurants are also listed.
+    #[serde(default)]
+    pub closed: bool,
+}
+
+/// `POST /orders`: places a new order and returns it with the ID the leader gave it and status
+/// `Requested`.
+#[post("/orders")]
+pub async fn place_order(
+    gateway: web::Data<Addr<ApiGateway>>,
+    body: web::Json<PlaceOrderRequest>,
+) -> impl Responder {
+    let body = body.into_inner();
+    if body.items.is_empty() || body.items.iter().any(|item| item.quantity == 0) {
+        return HttpResponse::BadRequest().body("The order needs at least one unit of a dish");
+    }
+    let position = match body.position.map(|position| Map::default().check(position)) {
+        Some(Ok(position)) => position,
+        Some(Err(e)) => return HttpResponse::BadRequest().body(e.to_string()),
+        None => get_rand_f32_tuple(),
+    };
+    let msg = PlaceOrder {
+        client_id: body.client_id,
+        position,
+        restaurant_id: body.restaurant_id,
+        items: body.items,
+        priority: body.priority,
+        promo_code: body.promo_code,
+    };
+    match gateway.send(msg).await {
+        Ok(Ok(order)) => HttpResponse::Accepted().json(order),
+        Ok(Err(e)) => HttpResponse::ServiceUnavailable().body(e),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// `GET /orders/{id}?client_id=..`: returns the state of an order of the client as the cluster
+/// stores it, wherever the order was placed, or its last state if it finished recently.
+/// Answers `404` if the cluster does not know the order and `503` if it does not answer.
+#[get("/orders/{id}")]
+pub async fn get_order(
+    gateway: web::Data<Addr<ApiGateway>>,
+    path: web::Path<u64>,
+    query: web::Query<OrderQuery>,
+) -> impl Responder {
+    match gateway
+        .send(GetOrder {
+            client_id: query.into_inner().client_id,
+            order_id: path.into_inner(),
+        })
+        .await
+    {
+        Ok(Ok(Some(order))) => HttpResponse::Ok().json(order),
+        Ok(Ok(None)) => HttpResponse::NotFound().finish(),
+        Ok(Err(e)) => HttpResponse::ServiceUnavailable().body(e),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// `GET /restaurants?client_id=..&x=..&y=..&q=..&tag=..&sort=..&closed=..`: lists the
+/// restaurants near the client, those whose ID contains `q` and with the tag `tag` if given,
+/// sorted by `distance` or `name`.
+#[get("/restaurants")]
+pub async fn list_restaurants(
+    gateway: web::Data<Addr<ApiGateway>>,
+    query: web::Query<RestaurantsQuery>,
+) -> impl Responder {
+    let query = query.into_inner();
+    let position = match (query.x, query.y) {
+        (Some(x), Some(y)) => match Map::default().check((x, y)) {
+            Ok(position) => position,
+            Err(e) => return HttpResponse::BadRequest().body(e.to_string()),
+        },
+        _ => get_rand_f32_tuple(),
+    };
+    match gateway
+        .send(ListRestaurants {
+            client_id: query.client_id,
+            position,
+            filter: RestaurantFilter {
+                query: query.q.filter(|q| !q.trim().is_empty()),
+                tag: query.tag.filter(|tag| !tag.trim().is_empty()),
+                include_closed: query.closed,
+                sort: query.sort,
+            },
+        })
+        .await
+    {
+        Ok(Ok(restaurants)) => HttpResponse::Ok().json(restaurants),
+        Ok(Err(e)) => HttpResponse::GatewayTimeout().body(e),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Registers every API route in the given service config.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(place_order)
+        .service(get_order)
+        .service(list_restaurants);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix::Actor;
+    use actix_web::http::StatusCode;
+    use actix_web::{App, test};
+    use serde_json::json;
+
+    /// Starts a gateway whose only server is an address nobody listens on.
+    fn unreachable_gateway() -> web::Data<Addr<ApiGateway>> {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        web::Data::new(ApiGateway::new(vec![addr]).start())
+    }
+
+    async fn status_of(req: test::TestRequest) -> StatusCode {
+        let app = test::init_service(
+            App::new()
+                .app_data(unreachable_gateway())
+                .configure(configure),
+        )
+        .await;
+        test::call_service(&app, req.to_request()).await.status()
+    }
+
+    #[actix_web::test]
+    async fn rejects_orders_without_dishes() {
+        for items in [json!([]), json!([{"dish": "Pizza", "quantity": 0}])] {
+            let req = test::TestRequest::post().uri("/orders").set_json(json!({
+                "client_id": "cliente_1",
+                "restaurant_id": "resto_1",
+                "items": items,
+            }));
+            assert_eq!(status_of(req).await, StatusCode::BAD_REQUEST);
+        }
+    }
+
+    #[actix_web::test]
+    async fn rejects_positions_outside_the_map() {
+        let req = test::TestRequest::post().uri("/orders").set_json(json!({
+            "client_id": "cliente_1",
+            "restaurant_id": "resto_1",
+            "items": [{"dish": "Pizza", "quantity": 1}],
+            "position": [-5.0, 1000.0],
+        }));
+        assert_eq!(status_of(req).await, StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::get().uri("/restaurants?client_id=cliente_1&x=-5&y=1000");
+        assert_eq!(status_of(req).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn orders_are_queried_for_a_client() {
+        let req = test::TestRequest::get().uri("/orders/7");
+        assert_eq!(status_of(req).await, StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_web::test]
+    async fn reports_an_unreachable_cluster() {
+        let req = test::TestRequest::post().uri("/orders").set_json(json!({
+            "client_id": "cliente_1",
+            "restaurant_id": "resto_1",
+            "items": [{"dish": "Pizza", "quantity": 1}],
+        }));
+        assert_eq!(status_of(req).await, StatusCode::SERVICE_UNAVAILABLE);
+
+        let req = test::TestRequest::get().uri("/orders/7?client_id=cliente_1");
+        assert_eq!(status_of(req).await, StatusCode::SERVICE_UNAVAILABLE);
+
+        let req = test::TestRequest::get().uri("/restaurants?client_id=cliente_1");
+        assert_eq!(status_of(req).await, StatusCode::GATEWAY_TIMEOUT);
+    }
+}
@@ -0,0 +1,80 @@
+use common::types::dtos::OrderItem;
+use std::fmt;
+
+/// The dishes the user picked for the next order, built one line at a time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Cart {
+    /// The dishes picked, in the order they were first added.
+    items: Vec<OrderItem>,
+}
+
+impl Cart {
+    /// Adds the dish typed by the user: its name, optionally followed by the units to add,
+    /// e.g. `Pizza` or `Papas fritas 2`. Adding a dish already in the cart adds up its units.
+    ///
+    /// ## Returns
+    /// An error to show the user if the line has no dish or the units are zero.
+    pub fn add(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        let (dish, quantity) = match line.rsplit_once(' ') {
+            Some((dish, units)) => match units.parse::<u32>() {
+                Ok(units) => (dish.trim(), units),
+                Err(_) => (line, 1),
+            },
+            None => (line, 1),
+        };
+        if dish.is_empty() {
+            return Err("Dish name cannot be empty.".to_string());
+        }
+        if quantity == 0 {
+            return Err("Quantity must be at least 1.".to_string());
+        }
+        match self.items.iter_mut().find(|item| item.dish == dish) {
+            Some(item) => item.quantity = item.quantity.saturating_add(quantity),
+            None => self.items.push(OrderItem::new(dish, quantity)),
+        }
+        Ok(())
+    }
+
+    /// Returns whether no dish was picked.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns the dishes picked.
+    pub fn items(&self) -> &[OrderItem] {
+        &self.items
+    }
+
+    /// Returns the price of the dishes picked, in cents, without surcharges or discounts.
+    pub fn total(&self) -> u64 {
+        self.items.iter().map(OrderItem::total).sum()
+    }
+}
+
+impl fmt::Display for Cart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items: Vec<String> = self.items.iter().map(OrderItem::to_string).collect();
+        write!(f, "{}", items.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::constants::ORDER_PRICE_CENTS;
+
+    #[test]
+    fn adds_dishes_with_their_units() {
+        let mut cart = Cart::default();
+        assert!(cart.add("Pizza").is_ok());
+        assert!(cart.add(" Papas fritas 2 ").is_ok());
+        assert!(cart.add("Pizza 3").is_ok());
+        assert_eq!(cart.to_string(), "4x Pizza, 2x Papas fritas");
+        assert_eq!(cart.total(), 6 * ORDER_PRICE_CENTS);
+
+        assert!(cart.add("").is_err());
+        assert!(cart.add("Pizza 0").is_err());
+        assert_eq!(cart.items().len(), 2);
+    }
+}
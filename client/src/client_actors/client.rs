@@ -3,26 +3,28 @@ use crate::messages::internal_messages::*;
 use actix::fut::wrap_future;
 use actix::prelude::*;
 use colored::Color;
-use common::constants::BASE_DELAY_MILLIS;
-use common::constants::DELAY_SECONDS_TO_START_RECONNECT;
+use common::constants::{DEREGISTER_WAIT, UNASSIGNED_ORDER_ID};
 use common::logger::Logger;
 use common::messages::NearbyRestaurants;
 use common::messages::OrderDelivered;
 use common::messages::client_messages::*;
 use common::messages::shared_messages::*;
 use common::network::communicator::Communicator;
-use common::network::connections::connect_one;
 use common::network::connections::connect_some;
-use common::network::connections::reconnect;
-use common::network::peer_types::PeerType;
+use common::network::peer_types::{PeerIdentity, PeerType};
+use common::peer::{Peer, PeerCore};
+use common::types::decline_reason::DeclineReason;
 use common::types::dtos::ClientDTO;
 use common::types::dtos::OrderDTO;
 use common::types::dtos::UserDTO;
+use common::types::hlc::HlcTimestamp;
 use common::types::order_status::OrderStatus;
+use common::types::rejection_reason::RejectionReason;
+use common::types::restaurant_filter::RestaurantFilter;
+use common::utils::TravelModel;
 use rand::Rng;
 use std::net::SocketAddr;
 use std::process;
-use tokio::net::TcpStream;
 
 /// Represents a client actor in the restaurant ordering system.
 ///
@@ -31,8 +33,6 @@ use tokio::net::TcpStream;
 /// It is responsible for sending and receiving network messages, tracking the current order,
 /// and managing delivery timers.
 pub struct Client {
-    /// List of server socket addresses to connect to.
-    pub servers: Vec<SocketAddr>,
     /// Unique identifier for the client.
     pub client_id: String,
     /// Current position of the client in 2D coordinates.
@@ -41,18 +41,26 @@ pub struct Client {
     pub client_order: Option<OrderDTO>,
     /// Address of the UI handler actor.
     pub ui_handler: Option<Addr<UIHandler>>,
-    /// Communicator for network interactions with the server.
-    pub communicator: Option<Communicator<Client>>,
-    /// Pending TCP stream before the actor starts.
-    pub pending_stream: Option<TcpStream>,
+    /// Connection to the leader of the servers.
+    pub peer: PeerCore<Client>,
+    /// Read-only connection to a follower, used for queries that a replica can answer.
+    pub read_replica: Option<Communicator<Client>>,
     /// Logger for client events.
     pub logger: Logger,
     /// Handle for the delivery timer, if active.
     delivery_timer: Option<actix::SpawnHandle>,
-    /// Timer for waiting reconnection attempts after a connection is closed.
-    waiting_reconnection_timer: Option<actix::SpawnHandle>,
-    /// Flag to indicate if the client is already connected and waiting for reconnection.
-    already_connected: bool,
+    /// Flag to indicate if the user asked to cancel the current order.
+    cancel_requested: bool,
+    /// Position of the restaurant of the current order, if known.
+    restaurant_position: Option<(f32, f32)>,
+    /// Maximum distance to the restaurants offered, in blocks. The server decides if `None`.
+    search_radius: Option<f32>,
+    /// Which restaurants are offered, and in which order.
+    restaurant_filter: RestaurantFilter,
+    /// How long the trips of the couriers take, to estimate when the order arrives.
+    travel: TravelModel,
+    /// Requests made while disconnected, sent once the client registers again.
+    pending_requests: Vec<NetworkMessage>,
 }
 
 impl Client {
@@ -74,72 +82,208 @@ impl Client {
     ) -> Self {
         let logger = Logger::new(format!("Client {}", &client_id), Color::Cyan);
         logger.info(format!("Hello, {}!", client_id));
-        let pending_stream = connect_some(servers.clone(), PeerType::ClientType).await;
-
-        if pending_stream.is_none() {
-            logger.error("Failed to connect to any server. Exiting.");
+        let identity = PeerIdentity::new(PeerType::ClientType, client_id.clone());
+        let Some(peer) = PeerCore::connect(servers, identity, logger.clone()).await else {
             std::process::exit(1);
-        }
+        };
 
         Self {
-            servers,
             client_id,
             client_position,
             client_order: None, // Inicializamos el pedido como None
             ui_handler: None,   // Inicializamos el canal de envío hacia UIHandler como None
-            communicator: None,
-            pending_stream, // Guarda el stream hasta que arranque
+            peer,
+            read_replica: None,
             logger,
             delivery_timer: None, // Inicializamos el temporizador de entrega como None
-            waiting_reconnection_timer: None, // Timer for reconnection attempts
-            already_connected: false, // Flag to indicate if waiting for reconnection
+            cancel_requested: false,
+            restaurant_position: None,
+            search_radius: None,
+            restaurant_filter: RestaurantFilter::default(),
+            travel: TravelModel::from_env(),
+            pending_requests: Vec::new(),
         }
     }
 
+    /// Registers with `secret`, the secret of the identity of the client.
+    pub fn with_secret(mut self, secret: Option<String>) -> Self {
+        self.peer.set_secret(secret);
+        self
+    }
+
+    /// Estimates the travel time of the orders with `travel`, which should be the model the
+    /// deliveries use.
+    pub fn with_travel_model(mut self, travel: TravelModel) -> Self {
+        self.travel = travel;
+        self
+    }
+
     /// Sends a network message to the connected server via the communicator.
     ///
     /// ## Arguments
     ///
     /// * `message` - The network message to send.
     pub fn send_network_message(&self, message: NetworkMessage) {
-        if let Some(communicator) = &self.communicator {
-            if let Some(sender) = &communicator.sender {
-                sender.do_send(message);
-            } else {
-                self.logger.error("Sender not initialized in communicator");
-            }
-        } else {
-            self.logger.error("Communicator not found!");
+        self.peer.send(message);
+    }
+
+    /// Sends a read-only query through the read replica, falling back to the leader if there
+    /// is no replica connection.
+    ///
+    /// ## Arguments
+    ///
+    /// * `message` - The network message to send.
+    pub fn send_read_message(&self, message: NetworkMessage) {
+        match self.read_replica.as_ref().and_then(|c| c.sender.as_ref()) {
+            Some(sender) => sender.do_send(message),
+            None => self.send_network_message(message),
         }
     }
 
-    /// Starts the client logic by requesting the current leader from the server
-    /// (Sends a WhoIsLeader message).
+    /// Sends a network message to the leader, or queues it until the client registers again
+    /// if it is disconnected.
     ///
     /// ## Arguments
     ///
-    /// * `_ctx` - The Actix actor context.
-    pub fn start_running(&self, _ctx: &mut Context<Self>) {
-        let actual_socket_addr = self
-            .communicator
-            .as_ref()
-            .map(|c| c.local_address)
-            .expect("Socket address not initialized");
+    /// * `message` - The network message to send.
+    fn send_or_queue(&mut self, message: NetworkMessage) {
+        if self.peer.communicator.is_some() {
+            self.send_network_message(message);
+            return;
+        }
+        self.logger
+            .warn("Not connected to the server, the request will be sent after reconnecting");
+        // Sólo importa la última búsqueda de restaurantes
+        if matches!(message, NetworkMessage::RequestNearbyRestaurants(_)) {
+            self.pending_requests
+                .retain(|m| !matches!(m, NetworkMessage::RequestNearbyRestaurants(_)));
+        }
+        self.pending_requests.push(message);
+    }
+
+    /// Sends the requests made while the client was disconnected, in the order they were made.
+    fn flush_pending_requests(&mut self) {
+        if self.pending_requests.is_empty() {
+            return;
+        }
         self.logger.info(format!(
-            "Starting Client actor with ID: {} at position: {:?}",
-            self.client_id, self.client_position
+            "Sending {} requests made while disconnected",
+            self.pending_requests.len()
         ));
+        for message in std::mem::take(&mut self.pending_requests) {
+            self.send_network_message(message);
+        }
+    }
 
-        self.send_network_message(NetworkMessage::WhoIsLeader(WhoIsLeader {
-            origin_addr: actual_socket_addr,
-            user_id: self.client_id.clone(),
-        }));
+    /// Asks the server for the restaurants near the client, within the chosen search radius
+    /// and filtered as the user chose.
+    fn request_nearby_restaurants(&mut self) {
+        let client = ClientDTO {
+            client_position: self.client_position,
+            client_id: self.client_id.clone(),
+            client_order: None, // No hay orden activa
+            time_stamp: HlcTimestamp::now(),
+            tenant_id: self.peer.tenant_id().clone(),
+        };
+        let message = NetworkMessage::RequestNearbyRestaurants(RequestNearbyRestaurants {
+            client,
+            radius: self.search_radius,
+            filter: self.restaurant_filter.clone(),
+        });
+        if self.read_replica.is_some() {
+            self.send_read_message(message);
+        } else {
+            self.send_or_queue(message);
+        }
+    }
+
+    /// Forgets the current order and lets the user choose what to do next.
+    ///
+    /// ## Arguments
+    ///
+    /// * `ctx` - The Actix actor context.
+    fn finish_order(&mut self, ctx: &mut actix::Context<Self>) {
+        self.forget_order(ctx);
+        if let Some(ui_handler) = &self.ui_handler {
+            ui_handler.do_send(OrderFinished);
+        } else {
+            self.logger.error("UIHandler not initialized");
+        }
+    }
+
+    /// Forgets the current order, whose payment was declined, and lets the user retry the
+    /// payment, pick another restaurant or quit.
+    ///
+    /// ## Arguments
+    ///
+    /// * `decline` - Why the payment was declined, if the server said so.
+    /// * `ctx` - The Actix actor context.
+    fn decline_order(&mut self, decline: Option<DeclineReason>, ctx: &mut actix::Context<Self>) {
+        self.forget_order(ctx);
+        if let Some(ui_handler) = &self.ui_handler {
+            ui_handler.do_send(PaymentDeclined { decline });
+        } else {
+            self.logger.error("UIHandler not initialized");
+        }
+    }
+
+    /// Forgets the current order and stops waiting for its delivery.
+    fn forget_order(&mut self, ctx: &mut actix::Context<Self>) {
+        if let Some(handle) = self.delivery_timer.take() {
+            ctx.cancel_future(handle);
+        }
+        self.client_order = None;
+        self.restaurant_position = None;
+        self.cancel_requested = false;
+    }
+
+    /// Opens a read-only connection to one of the followers of `leader_addr`.
+    ///
+    /// ## Arguments
+    ///
+    /// * `leader_addr` - The address of the current leader.
+    /// * `ctx` - The Actix actor context.
+    pub fn open_read_replica(&mut self, leader_addr: SocketAddr, ctx: &mut Context<Self>) {
+        if let Some(mut replica) = self.read_replica.take() {
+            replica.shutdown();
+        }
+        let followers: Vec<SocketAddr> = self
+            .peer
+            .servers
+            .iter()
+            .copied()
+            .filter(|addr| *addr != leader_addr)
+            .collect();
+        if followers.is_empty() {
+            return;
+        }
+
+        let identity = self.peer.identity().clone();
+        ctx.spawn(
+            wrap_future(async move { connect_some(followers, &identity).await }).map(
+                |maybe_stream, actor: &mut Self, ctx| {
+                    let Some(stream) = maybe_stream else {
+                        actor
+                            .logger
+                            .warn("No follower available, queries will go to the leader");
+                        return;
+                    };
+                    let replica = Communicator::new(stream, ctx.address(), PeerType::ClientType);
+                    actor.logger.info(format!(
+                        "Using follower {} as read replica",
+                        replica.peer_address
+                    ));
+                    actor.read_replica = Some(replica);
+                },
+            ),
+        );
     }
 
     /// Manages the delivery timer for the current order.
     ///
     /// If the order is in the `Delivering` state, a timer is started to track the expected delivery time.
     /// If the order is delivered or cancelled, the timer is cancelled.
+    /// While the order is pending or being prepared, the estimated arrival time is shown instead.
     ///
     /// ## Arguments
     ///
@@ -165,7 +309,8 @@ impl Client {
                 ctx.cancel_future(handle);
             }
 
-            let delivery_time = (order.expected_delivery_time + BASE_DELAY_MILLIS) / 1000;
+            let delivery_time =
+                (order.expected_delivery_time + self.travel.base_delay_millis) / 1000;
             let handle = ctx.run_later(
                 std::time::Duration::from_secs(delivery_time),
                 move |act, _ctx| {
@@ -201,64 +346,73 @@ impl Client {
             if let Some(handle) = self.delivery_timer.take() {
                 ctx.cancel_future(handle);
             }
+            if matches!(order.status, OrderStatus::Pending | OrderStatus::Preparing)
+                && order.expected_preparation_time > 0
+            {
+                self.show_estimated_arrival(order);
+            }
         }
     }
-}
-
-/// Handles [`ConnectionClosed`] messages.
-///
-/// This handler is triggered when the connection to the server is lost.
-/// It attempts to reconnect to one of the known servers. If reconnection is successful,
-/// it reinitializes the communicator and restarts the actor. If not, the actor is stopped.
-impl Handler<ConnectionClosed> for Client {
-    type Result = ();
 
-    fn handle(&mut self, _msg: ConnectionClosed, ctx: &mut Self::Context) -> Self::Result {
-        // Llama a la función async y usa wrap_future para obtener el resultado
-        // Antes de reconectar o crear communicator:
-
-        if let Some(handle) = self.delivery_timer.take() {
-            ctx.cancel_future(handle);
-            self.delivery_timer = None;
+    /// Shows when the order should arrive: the preparation time estimated by the restaurant
+    /// plus the travel time from the restaurant, if its position is known.
+    ///
+    /// The travel time does not include the courier's way to the restaurant, since the
+    /// courier is not chosen yet.
+    fn show_estimated_arrival(&self, order: &OrderDTO) {
+        let preparation = order.expected_preparation_time as f64 / 1000.0;
+        match self.restaurant_position {
+            Some(restaurant_position) => {
+                let travel = self
+                    .travel
+                    .trip_millis(&[restaurant_position, order.client_position])
+                    as f64
+                    / 1000.0;
+                self.logger.info(format!(
+                    "Your order will be ready in about {:.2} seconds and should arrive in about {:.2} seconds.",
+                    preparation,
+                    preparation + travel
+                ));
+            }
+            None => {
+                self.logger.info(format!(
+                    "Your order will be ready in about {:.2} seconds.",
+                    preparation
+                ));
+            }
         }
+    }
+}
 
-        if self.communicator.is_some() {
-            self.logger
-                .info("Already connected, skipping reconnection.");
-            return;
-        }
+/// Registers the client with the leader of the servers and recovers its state.
+impl Peer for Client {
+    fn peer(&self) -> &PeerCore<Self> {
+        &self.peer
+    }
 
-        let servers = self.servers.clone();
-        let fut = async move { reconnect(servers, PeerType::ClientType).await };
+    fn peer_mut(&mut self) -> &mut PeerCore<Self> {
+        &mut self.peer
+    }
 
-        let fut = wrap_future::<_, Self>(fut).map(|result, actor: &mut Self, ctx| match result {
-            Some(stream) => {
-                let communicator = Communicator::new(stream, ctx.address(), PeerType::ClientType);
-                actor.communicator = Some(communicator);
+    fn position(&self) -> (f32, f32) {
+        self.client_position
+    }
 
-                actor
-                    .logger
-                    .info("Reconnected successfully. Restarting actor...");
+    fn on_recovered(&mut self, user: UserDTO, ctx: &mut Context<Self>) {
+        ctx.address().do_send(RecoverProcedure { user_info: user });
+    }
 
-                if let Some(handler) = actor.waiting_reconnection_timer.take() {
-                    ctx.cancel_future(handler);
-                    actor.waiting_reconnection_timer = None;
-                }
-                // Esperar 100ms antes de enviar WhoIsLeader tras reconexión
-                let addr = ctx.address();
-                let handler = ctx.run_later(std::time::Duration::from_millis(100), move |_, _| {
-                    addr.do_send(StartRunning);
-                });
-                actor.waiting_reconnection_timer = Some(handler);
-            }
-            None => {
-                actor
-                    .logger
-                    .error("Failed to reconnect to any server after closed connection");
-                ctx.stop();
-            }
-        });
-        ctx.spawn(fut);
+    fn on_registered(&mut self, recovered: bool, _ctx: &mut Context<Self>) {
+        // Si el usuario ya eligió qué hacer mientras estaba desconectado, se envía eso
+        if recovered || !self.pending_requests.is_empty() {
+            self.flush_pending_requests();
+        } else {
+            self.request_nearby_restaurants();
+        }
+    }
+
+    fn on_leader_found(&mut self, leader_addr: SocketAddr, ctx: &mut Context<Self>) {
+        self.open_read_replica(leader_addr, ctx);
     }
 }
 
@@ -274,116 +428,21 @@ impl Actor for Client {
     ///
     /// * `ctx` - The Actix actor context.
     fn started(&mut self, ctx: &mut Self::Context) {
-        let communicator = Communicator::new(
-            self.pending_stream
-                .take()
-                .expect("Pending stream should be set"),
-            ctx.address(),
-            PeerType::ClientType,
-        );
-        self.communicator = Some(communicator);
-
         let ui_handler = UIHandler::new(ctx.address(), self.logger.clone());
         self.ui_handler = Some(ui_handler.start());
-
-        // Esperar 100ms antes de enviar WhoIsLeader
-        let addr = ctx.address();
-        let handler = ctx.run_later(std::time::Duration::from_millis(100), move |_, _| {
-            addr.do_send(StartRunning);
-        });
-        self.waiting_reconnection_timer = Some(handler);
+        self.start_peer(ctx);
     }
 }
 
-impl Handler<StartRunning> for Client {
-    type Result = ();
-    fn handle(&mut self, _msg: StartRunning, ctx: &mut Self::Context) -> Self::Result {
-        self.start_running(ctx);
-    }
-}
-
-/// Handler for the `LeaderIs` message.
+/// Handler for the `Deregister` message.
 ///
-/// Handles notification of the current leader's address. If not already connected to the leader,
-/// attempts to establish a new connection and updates the communicator.
-impl Handler<LeaderIs> for Client {
+/// Tells the leader the client leaves before shutting down, so that the orders the
+/// restaurant did not start yet are cancelled.
+impl Handler<Deregister> for Client {
     type Result = ();
 
-    fn handle(&mut self, msg: LeaderIs, ctx: &mut Self::Context) -> Self::Result {
-        self.logger
-            .info(format!("Received new leader address: {}", msg.coord_addr));
-        let leader_addr = msg.coord_addr;
-        let self_addr = ctx.address();
-        let logger = self.logger.clone();
-
-        if let Some(handler) = self.waiting_reconnection_timer.take() {
-            ctx.cancel_future(handler);
-            self.waiting_reconnection_timer = None;
-        }
-
-        let communicator_opt = self.communicator.as_ref().map(|c| c.peer_address);
-
-        // Si ya estamos conectados al líder, no hacemos nada
-        if Some(leader_addr) == communicator_opt {
-            self.logger.info(format!(
-                "Already connected to the leader at address: {}",
-                leader_addr.clone()
-            ));
-
-            let local_address = self
-                .communicator
-                .as_ref()
-                .map(|c| c.local_address)
-                .expect("Socket address not set");
-            self.send_network_message(NetworkMessage::RegisterUser(RegisterUser {
-                origin_addr: local_address,
-                user_id: self.client_id.clone(),
-                position: self.client_position,
-            }));
-            return;
-        }
-
-        // Si no estoy conectado al líder, cierro el communicator anterior y conecto al nuevo líder
-        if let Some(comm) = self.communicator.as_mut() {
-            comm.shutdown();
-        }
-        self.communicator = None;
-
-        ctx.spawn(
-            wrap_future(async move {
-                logger.info(format!(
-                    "Attempting to connect to the new leader at address: {}",
-                    leader_addr
-                ));
-                if let Some(new_stream) = connect_one(leader_addr, PeerType::ClientType).await {
-                    let new_communicator =
-                        Communicator::new(new_stream, self_addr.clone(), PeerType::ClientType);
-                    Some(new_communicator)
-                } else {
-                    logger.error(format!(
-                        "Failed to connect to the new leader at {}",
-                        leader_addr
-                    ));
-                    None
-                }
-            })
-            .map(move |maybe_communicator, actor: &mut Self, ctx| {
-                if let Some(new_communicator) = maybe_communicator {
-                    actor.logger.info(format!(
-                        "Communicator updated with new peer address: {}",
-                        new_communicator.peer_address
-                    ));
-                    actor.communicator = Some(new_communicator);
-
-                    // Usar ctx.address() directamente
-                    let handler =
-                        ctx.run_later(std::time::Duration::from_millis(100), move |_, ctx| {
-                            ctx.address().do_send(StartRunning);
-                        });
-                    actor.waiting_reconnection_timer = Some(handler);
-                }
-            }),
-        );
+    fn handle(&mut self, _msg: Deregister, _ctx: &mut Self::Context) -> Self::Result {
+        self.deregister();
     }
 }
 
@@ -395,7 +454,7 @@ impl Handler<RecoverProcedure> for Client {
     type Result = ();
 
     fn handle(&mut self, msg: RecoverProcedure, ctx: &mut Self::Context) -> Self::Result {
-        if self.already_connected {
+        if self.peer.already_connected {
             self.logger
                 .info("Already connected, skipping recovery procedure.");
             return;
@@ -420,24 +479,24 @@ impl Handler<RecoverProcedure> for Client {
 
                         // Imprime los posibles estados del pedido
                         match order_cloned.status {
-                            OrderStatus::Cancelled => {
+                            OrderStatus::Cancelled | OrderStatus::OutOfStock => {
                                 self.logger.warn(format!(
-                                    "Your order has been cancelled: {}. Try again later.",
-                                    order_cloned.dish_name
+                                    "Your order has been cancelled: {}.",
+                                    order_cloned.summary()
                                 ));
-                                ctx.stop();
+                                self.finish_order(ctx);
                             }
                             OrderStatus::Delivered => {
                                 self.logger.info(format!(
                                     "Your order has already been delivered: {}. Enjoy your meal!",
-                                    order_cloned.dish_name
+                                    order_cloned.summary()
                                 ));
-                                ctx.stop();
+                                self.finish_order(ctx);
                             }
                             OrderStatus::Delivering => {
                                 self.logger.info(format!(
                                     "Your order is out for delivery: {}.",
-                                    order_cloned.dish_name
+                                    order_cloned.summary()
                                 ));
                                 self.manage_delivery_time(&order_cloned, ctx);
                             }
@@ -454,19 +513,9 @@ impl Handler<RecoverProcedure> for Client {
                             "Client ID={} has no active order, requesting nearby restaurants.",
                             self.client_id
                         ));
-                        let new_client_dto = ClientDTO {
-                            client_position: self.client_position,
-                            client_id: self.client_id.clone(),
-                            client_order: None, // No hay orden activa
-                            time_stamp: std::time::SystemTime::now(),
-                        };
-                        self.send_network_message(NetworkMessage::RequestNearbyRestaurants(
-                            RequestNearbyRestaurants {
-                                client: new_client_dto.clone(),
-                            },
-                        ));
+                        self.request_nearby_restaurants();
                     }
-                    self.already_connected = true;
+                    self.peer.already_connected = true;
                 } else {
                     self.logger.warn(format!(
                         "Received recovered info for a different client ({}), ignoring",
@@ -493,30 +542,173 @@ impl Handler<SendThisOrder> for Client {
     fn handle(&mut self, msg: SendThisOrder, _ctx: &mut Self::Context) -> Self::Result {
         self.logger.info(format!(
             "Sending order to restaurant {}: {}",
-            msg.selected_restaurant, msg.selected_dish
+            msg.selected_restaurant,
+            msg.selected_items
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
         ));
 
         let mut rng = rand::thread_rng();
-        let order_id: u64 = rng.gen_range(1..=u64::MAX);
+        let request_id: u64 = rng.gen_range(1..=u64::MAX);
 
         let order = OrderDTO {
-            order_id,
+            order_id: UNASSIGNED_ORDER_ID, // Lo asigna el líder
             client_id: self.client_id.clone(),
             restaurant_id: msg.selected_restaurant,
-            dish_name: msg.selected_dish,
-            status: OrderStatus::Pending, // Estado inicial del pedido
-            delivery_id: None,            // No hay delivery asignado aún
-            time_stamp: std::time::SystemTime::now(), // Marca de tiempo actual
+            items: msg.selected_items,
+            status: OrderStatus::Pending,    // Estado inicial del pedido
+            delivery_id: None,               // No hay delivery asignado aún
+            time_stamp: HlcTimestamp::now(), // Marca de tiempo actual
             client_position: self.client_position, // Posición del cliente
-            expected_delivery_time: 0,    // Tiempo de entrega inicial
+            expected_delivery_time: 0,       // Tiempo de entrega inicial
+            expected_preparation_time: 0,    // Lo estima la cocina del restaurante
+            priority: msg.priority,          // Prioridad elegida por el usuario
+            discount: 0,                     // Lo aplica el servidor con el código promocional
+            tenant_id: self.peer.tenant_id().clone(), // Lo confirma el servidor
         };
 
         // Enviar el pedido al servidor
-        let network_message = NetworkMessage::RequestThisOrder(RequestThisOrder { order });
+        if let Some(ui_handler) = &self.ui_handler {
+            ui_handler.do_send(OrderStatusChanged {
+                order_id: UNASSIGNED_ORDER_ID,
+                status: OrderStatus::Requested,
+            });
+        }
+        self.client_order = Some(order.clone());
+        self.restaurant_position = Some(msg.selected_restaurant_position);
+        self.cancel_requested = false;
+        let network_message = NetworkMessage::RequestThisOrder(RequestThisOrder {
+            order,
+            promo_code: msg.promo_code,
+            request_id,
+        });
+        self.send_or_queue(network_message);
+    }
+}
+
+/// Handler for the `CancelCurrentOrder` message.
+///
+/// Asks the leader to cancel the current order, unless it is already on its way.
+impl Handler<CancelCurrentOrder> for Client {
+    type Result = ();
+
+    fn handle(&mut self, _msg: CancelCurrentOrder, _ctx: &mut Self::Context) -> Self::Result {
+        let Some(order) = &self.client_order else {
+            self.logger.warn("You have no order to cancel.");
+            return;
+        };
+        if !order.status.is_cancellable() {
+            self.logger.warn(format!(
+                "Your order is already {}, it can no longer be cancelled.",
+                order.status.to_string().to_uppercase()
+            ));
+            return;
+        }
+        if order.order_id == UNASSIGNED_ORDER_ID {
+            self.logger
+                .warn("Your order has not reached the server yet, try again in a moment.");
+            return;
+        }
+        self.logger
+            .info(format!("Cancelling order {}...", order.order_id));
+        self.cancel_requested = true;
+        let network_message = NetworkMessage::CancelMyOrder(CancelMyOrder {
+            client_id: self.client_id.clone(),
+            order_id: order.order_id,
+            waive_fee: false,
+        });
         self.send_network_message(network_message);
     }
 }
 
+/// Handler for the `RefreshOrderStatus` message.
+///
+/// Asks for the current status of the order through the read replica, which answers with a
+/// `NotifyOrderUpdated` like the leader.
+impl Handler<RefreshOrderStatus> for Client {
+    type Result = ();
+
+    fn handle(&mut self, _msg: RefreshOrderStatus, _ctx: &mut Self::Context) -> Self::Result {
+        let Some(order) = &self.client_order else {
+            return;
+        };
+        if order.order_id == UNASSIGNED_ORDER_ID {
+            return;
+        }
+        self.send_read_message(NetworkMessage::RequestOrderStatus(RequestOrderStatus {
+            client_id: self.client_id.clone(),
+            order_id: order.order_id,
+        }));
+    }
+}
+
+/// Handler for the `ChangeSearchRadius` message.
+///
+/// Asks the server again for the restaurants, within the new radius.
+impl Handler<ChangeSearchRadius> for Client {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChangeSearchRadius, _ctx: &mut Self::Context) -> Self::Result {
+        self.logger.info(format!(
+            "Searching for restaurants within {} blocks...",
+            msg.radius
+        ));
+        self.search_radius = Some(msg.radius);
+        self.request_nearby_restaurants();
+    }
+}
+
+/// Handler for the `ChangeRestaurantFilter` message.
+///
+/// Asks the server again for the restaurants, with the new search.
+impl Handler<ChangeRestaurantFilter> for Client {
+    type Result = ();
+
+    fn handle(&mut self, msg: ChangeRestaurantFilter, _ctx: &mut Self::Context) -> Self::Result {
+        let mut search = Vec::new();
+        if let Some(query) = &msg.filter.query {
+            search.push(format!("matching '{}'", query));
+        }
+        if let Some(tag) = &msg.filter.tag {
+            search.push(format!("tagged '{}'", tag));
+        }
+        search.push(format!("by {}", msg.filter.sort));
+        self.logger.info(format!(
+            "Searching for restaurants {}...",
+            search.join(", ")
+        ));
+        self.restaurant_filter = msg.filter;
+        self.request_nearby_restaurants();
+    }
+}
+
+/// Handler for the `BrowseRestaurants` message.
+///
+/// Asks the server again for the nearby restaurants, after an order finished.
+impl Handler<BrowseRestaurants> for Client {
+    type Result = ();
+
+    fn handle(&mut self, _msg: BrowseRestaurants, _ctx: &mut Self::Context) -> Self::Result {
+        self.request_nearby_restaurants();
+    }
+}
+
+/// Handler for the `QuitClient` message.
+///
+/// Stops the client when the user quits.
+impl Handler<QuitClient> for Client {
+    type Result = ();
+
+    fn handle(&mut self, _msg: QuitClient, ctx: &mut Self::Context) -> Self::Result {
+        self.logger.info("Goodbye! Thanks for using our service!");
+        self.deregister();
+        // Se espera a que el DeregisterUser salga antes de cerrar la conexión
+        ctx.run_later(DEREGISTER_WAIT, |_, ctx| ctx.stop());
+    }
+}
+
 /// Handler for the `NearbyRestaurants` message.
 ///
 /// Forwards the list of nearby restaurants to the UI handler for user selection.
@@ -527,6 +719,8 @@ impl Handler<NearbyRestaurants> for Client {
         if let Some(ui_handler) = &self.ui_handler {
             ui_handler.do_send(SelectNearbyRestaurants {
                 nearby_restaurants: msg.restaurants,
+                distances: msg.distances,
+                closed: msg.closed,
             });
         } else {
             self.logger.error("UIHandler not initialized");
@@ -542,56 +736,10 @@ impl Handler<NearbyRestaurants> for Client {
 impl Handler<NetworkMessage> for Client {
     type Result = ();
     fn handle(&mut self, msg: NetworkMessage, ctx: &mut Self::Context) -> Self::Result {
+        let Some(msg) = self.handle_peer_message(msg, ctx) else {
+            return;
+        };
         match msg {
-            NetworkMessage::RetryLater(_msg_data) => {
-                self.logger.info("Retrying to connect in some seconds");
-            }
-            // All Users messages
-            NetworkMessage::LeaderIs(msg_data) => {
-                self.logger.info(format!(
-                    "Received LeaderIs message with addr: {}",
-                    msg_data.coord_addr
-                ));
-                ctx.address().do_send(msg_data)
-            }
-            NetworkMessage::RecoveredInfo(user_dto) => match user_dto {
-                UserDTO::Client(client_dto) => {
-                    if client_dto.client_id == self.client_id {
-                        ctx.address().do_send(RecoverProcedure {
-                            user_info: UserDTO::Client(client_dto.clone()),
-                        });
-                    } else {
-                        self.logger.warn(format!(
-                            "Received recovered info for a different client ({}), ignoring",
-                            client_dto.client_id
-                        ));
-                    }
-                }
-                other => {
-                    self.logger.warn(format!(
-                        "Received recovered info of type {:?}, but I'm Client. Ignoring.",
-                        other
-                    ));
-                }
-            },
-            NetworkMessage::NoRecoveredInfo => {
-                self.logger
-                    .info("No recovered info received, proceeding with normal flow");
-                // Aquí podrías enviar un mensaje para solicitar restaurantes cercanos
-                self.already_connected = true;
-                let new_client_dto = ClientDTO {
-                    client_position: self.client_position,
-                    client_id: self.client_id.clone(),
-                    client_order: None, // No hay orden activa
-                    time_stamp: std::time::SystemTime::now(),
-                };
-                self.send_network_message(NetworkMessage::RequestNearbyRestaurants(
-                    RequestNearbyRestaurants {
-                        client: new_client_dto,
-                    },
-                ));
-            }
-
             // Client messages
             NetworkMessage::NearbyRestaurants(msg_data) => {
                 self.logger.info(format!(
@@ -605,6 +753,32 @@ impl Handler<NetworkMessage> for Client {
                 if let Some(order) = &self.client_order {
                     if order.order_id == msg_data.order.order_id {
                         match order.status {
+                            _ if msg_data.order.status == OrderStatus::OutOfStock
+                                || msg_data.reason == Some(RejectionReason::OutOfStock) =>
+                            {
+                                self.logger.info(format!(
+                                    "Your order has been cancelled. The restaurant {} ran out of a dish of {}. Try other dishes.",
+                                    order.restaurant_id,
+                                    order.summary()
+                                ));
+                            }
+                            _ if msg_data.reason == Some(RejectionReason::Closed) => {
+                                self.logger.info(format!(
+                                    "Your order has been cancelled. The restaurant {} is closed. Try another restaurant.",
+                                    order.restaurant_id
+                                ));
+                            }
+                            _ if msg_data.reason == Some(RejectionReason::TooBusy) => {
+                                self.logger.info(format!(
+                                    "Your order has been cancelled. The restaurant {} is too busy. Try again later.",
+                                    order.restaurant_id
+                                ));
+                            }
+                            _ if self.cancel_requested => {
+                                self.logger.info(
+                                    "Your order has been cancelled as requested. The payment will be refunded.",
+                                );
+                            }
                             OrderStatus::Authorized => {
                                 self.logger.info(format!(
                                     "Your order has been cancelled. The restaurant {} rejected the order. Try again later.",
@@ -621,8 +795,8 @@ impl Handler<NetworkMessage> for Client {
                                     .warn("Your order has been cancelled. Try again later.");
                             }
                         }
-                        self.client_order = None; // Limpiamos el pedido actual
-                        ctx.stop();
+                        // Limpiamos el pedido actual
+                        self.finish_order(ctx);
                     } else {
                         self.logger.error(format!(
                             "Received cancel request for order {}, but I have order {}",
@@ -632,38 +806,123 @@ impl Handler<NetworkMessage> for Client {
                 } else {
                     self.logger
                         .warn("No restaurants found for the order. Try again later.");
-                    ctx.stop();
+                    self.finish_order(ctx);
                 }
             }
 
+            NetworkMessage::StillLookingForDelivery(msg_data) => {
+                self.logger.info(format!(
+                    "No delivery took your order {} yet, still looking for one (attempt {} of {}).",
+                    msg_data.order.order_id, msg_data.attempt, msg_data.max_attempts
+                ));
+            }
+
+            NetworkMessage::CancellationCharged(msg_data) => {
+                let fee = msg_data.fee;
+                self.logger.info(format!(
+                    "Order {} cancelled while {}: paid {} cents, {} kept as cancellation fee, {} refunded.",
+                    fee.order_id,
+                    fee.stage,
+                    fee.price,
+                    fee.fee,
+                    fee.refunded()
+                ));
+            }
+
             NetworkMessage::NotifyOrderUpdated(msg_data) => {
+                // Una consulta del estado (sin update_id) puede no traer nada nuevo
+                if msg_data.update_id == 0
+                    && self.client_order.as_ref().is_some_and(|order| {
+                        order.order_id == msg_data.order.order_id
+                            && order.status == msg_data.order.status
+                    })
+                {
+                    return;
+                }
                 self.logger.info(format!(
                     "Your order is now: {:?}",
                     msg_data.order.status.to_string().to_uppercase()
                 ));
+                // Se confirma la actualización para que el servidor no la vuelva a enviar
+                if msg_data.update_id != 0 {
+                    self.send_network_message(NetworkMessage::AckOrderUpdate(AckOrderUpdate {
+                        client_id: self.client_id.clone(),
+                        update_id: msg_data.update_id,
+                    }));
+                }
                 self.client_order = Some(msg_data.order.clone());
+                if let Some(ui_handler) = &self.ui_handler {
+                    ui_handler.do_send(OrderStatusChanged {
+                        order_id: msg_data.order.order_id,
+                        status: msg_data.order.status.clone(),
+                    });
+                }
                 match msg_data.order.status {
                     OrderStatus::Delivered => {
                         self.logger
                             .info("Your order has been delivered. Thanks for using our service!");
-                        ctx.stop();
+                        self.finish_order(ctx);
                     }
                     OrderStatus::Unauthorized => {
-                        self.logger
-                            .info("Your order has been unauthorized. Please try again later.");
-                        ctx.stop();
+                        self.logger.info(match msg_data.decline {
+                            Some(reason) if reason.is_transient() => format!(
+                                "Your order has been unauthorized ({}). Please try again later.",
+                                reason
+                            ),
+                            Some(reason) => format!(
+                                "Your order has been unauthorized ({}). Trying again will not help.",
+                                reason
+                            ),
+                            None => {
+                                "Your order has been unauthorized. Please try again later.".into()
+                            }
+                        });
+                        self.decline_order(msg_data.decline, ctx);
                     }
                     OrderStatus::Cancelled => {
                         self.logger.info(format!(
-                            "Order {} has been cancelled. Please try again later.",
+                            "Order {} has been cancelled.",
                             msg_data.order.order_id
                         ));
-                        ctx.stop();
+                        self.finish_order(ctx);
+                    }
+                    OrderStatus::OutOfStock => {
+                        self.logger.info(format!(
+                            "The restaurant ran out of a dish of {}. Please try other dishes.",
+                            msg_data.order.summary()
+                        ));
+                        self.finish_order(ctx);
+                    }
+                    OrderStatus::OutOfZone => {
+                        self.logger.info(format!(
+                            "The restaurant {} does not deliver to your position. Please choose another restaurant.",
+                            msg_data.order.restaurant_id
+                        ));
+                        self.finish_order(ctx);
+                    }
+                    OrderStatus::InvalidPromoCode => {
+                        self.logger.info(
+                            "Your promo code cannot be used for this order. Please try again without it.",
+                        );
+                        self.finish_order(ctx);
+                    }
+                    OrderStatus::RestaurantClosed => {
+                        self.logger.info(format!(
+                            "The restaurant {} is closed now. Please choose another restaurant.",
+                            msg_data.order.restaurant_id
+                        ));
+                        self.finish_order(ctx);
+                    }
+                    OrderStatus::OtherTenant => {
+                        self.logger.info(format!(
+                            "The restaurant {} belongs to another marketplace. Please choose another restaurant.",
+                            msg_data.order.restaurant_id
+                        ));
+                        self.finish_order(ctx);
                     }
 
-                    _ => {}
+                    _ => self.manage_delivery_time(&msg_data.order, ctx),
                 }
-                self.manage_delivery_time(&msg_data.order, ctx);
             }
 
             NetworkMessage::ConnectionClosed(msg_data) => {
@@ -672,32 +931,23 @@ impl Handler<NetworkMessage> for Client {
                     msg_data.remote_addr
                 ));
 
+                // Si se cayó la réplica de lectura, las consultas vuelven a ir al líder
+                if self
+                    .read_replica
+                    .as_ref()
+                    .is_some_and(|replica| replica.peer_address == msg_data.remote_addr)
+                {
+                    self.read_replica = None;
+                    return;
+                }
+
                 // CANCELA EL TIMER DE DELIVERY SI EXISTE
                 if let Some(handle) = self.delivery_timer.take() {
                     ctx.cancel_future(handle);
                     self.delivery_timer = None;
                 }
 
-                // Si el comunicador actual posee una peer_address que coincide con la dirección cerrada,
-                // se elimina el comunicador actual. Si no, se ignora.
-
-                self.logger.info(format!(
-                    "Removing communicator for address: {}",
-                    msg_data.remote_addr
-                ));
-                self.communicator = None;
-                self.logger.warn("Retrying to reconnect to the server ...");
-
-                let msg_data_cloned = msg_data.clone();
-
-                // Inicia un temporizador para reconectar después de un tiempo
-                let handle = ctx.run_later(DELAY_SECONDS_TO_START_RECONNECT, move |_, ctx| {
-                    ctx.address().do_send(ConnectionClosed {
-                        remote_addr: msg_data_cloned.remote_addr,
-                    });
-                });
-
-                self.waiting_reconnection_timer = Some(handle);
+                self.peer_connection_closed(ctx);
             }
 
             _ => {
@@ -1,18 +1,66 @@
+use crate::cart::Cart;
 use crate::client_actors::client::Client;
-use crate::messages::internal_messages::{SelectNearbyRestaurants, SendThisOrder};
+use crate::messages::internal_messages::{
+    BrowseRestaurants, CancelCurrentOrder, ChangeRestaurantFilter, ChangeSearchRadius,
+    OrderFinished, OrderStatusChanged, PaymentDeclined, QuitClient, RefreshOrderStatus,
+    SelectNearbyRestaurants, SendThisOrder, UserInput,
+};
+use crate::order_timeline::OrderTimeline;
 use actix::prelude::*;
+use common::constants::{MAX_NEARBY_RADIUS, UNASSIGNED_ORDER_ID};
 use common::logger::Logger;
+use common::types::decline_reason::DeclineReason;
+use common::types::order_priority::OrderPriority;
+use common::types::restaurant_filter::{RestaurantFilter, RestaurantSort};
 use common::types::restaurant_info::RestaurantInfo;
 use std::io::Write;
+use std::time::Instant;
+
+/// What the user is being asked for.
+enum Prompt {
+    /// Nothing: waiting for the server.
+    Idle,
+    /// A restaurant of the list, in the order chosen, with its distance and whether it is
+    /// closed, or another search.
+    Restaurant(Vec<(RestaurantInfo, Option<f32>, bool)>),
+    /// The dishes to add to the cart at the chosen restaurant, until the user types `done`.
+    Dish(RestaurantInfo, Cart),
+    /// Whether the order is express.
+    Express(RestaurantInfo, Cart),
+    /// The promo code to use, if any.
+    PromoCode(RestaurantInfo, Cart, OrderPriority),
+    /// `cancel` or `status`, while the order is in progress.
+    Cancel,
+    /// What to do once the order finished.
+    AfterOrder,
+    /// What to do once the payment of the order was declined, and why it was, if known.
+    AfterDecline(Option<DeclineReason>),
+}
 
 /// The `UIHandler` actor is responsible for managing the user interface interactions
-/// in the client application. It prompts the user to select a restaurant and dish,
-/// and communicates the user's choices to the `Client` actor.
+/// in the client application. It prompts the user to select a restaurant and to fill a cart
+/// with its dishes, and communicates the user's choices to the `Client` actor. Once the order is placed,
+/// the timeline of its stages is shown every time its status changes, and the user can type
+/// `cancel` to cancel it or `status` to see the timeline again and ask the server for its
+/// current status. Once it finishes, the user can order the same cart again, browse the
+/// restaurants again or quit. If its payment is declined, the user can retry the payment,
+/// pick another restaurant or quit.
+///
+/// Every line typed by the user is read by a single thread and handled according to what
+/// the user is being asked for at that moment.
 pub struct UIHandler {
     /// Address of the `Client` actor to send user selections to.
     pub client: Addr<Client>,
     /// Logger for UI-related messages and errors.
     pub logger: Logger,
+    /// What the user is being asked for.
+    prompt: Prompt,
+    /// The last order placed by the user, to order it again.
+    last_order: Option<UserOrderResult>,
+    /// Timeline of the last order placed.
+    timeline: Option<OrderTimeline>,
+    /// Which restaurants are listed, and in which order.
+    filter: RestaurantFilter,
 }
 
 impl UIHandler {
@@ -23,127 +71,450 @@ impl UIHandler {
     /// * `client` - Address of the `Client` actor.
     /// * `logger` - Logger instance for UI messages.
     pub fn new(client: Addr<Client>, logger: Logger) -> Self {
-        UIHandler { client, logger }
+        UIHandler {
+            client,
+            logger,
+            prompt: Prompt::Idle,
+            last_order: None,
+            timeline: None,
+            filter: RestaurantFilter::default(),
+        }
+    }
+
+    /// Shows the timeline of the last order placed, with the time spent in each stage.
+    fn show_timeline(&self) {
+        match &self.timeline {
+            Some(timeline) => {
+                for line in timeline.render(Instant::now()) {
+                    self.logger.info(line);
+                }
+            }
+            None => self.logger.warn("Your order was not placed yet."),
+        }
+    }
+
+    /// Shows the user what is being asked for.
+    fn show_prompt(&self) {
+        match &self.prompt {
+            Prompt::Idle => {}
+            Prompt::Restaurant(restaurants) => {
+                self.logger.info("Select a restaurant by number:");
+                for (i, (restaurant, distance, closed)) in restaurants.iter().enumerate() {
+                    let mut details = Vec::new();
+                    if !restaurant.tags.is_empty() {
+                        details.push(restaurant.tags.join(", "));
+                    }
+                    if let Some(distance) = distance {
+                        details.push(format!("{} blocks", distance));
+                    }
+                    if *closed {
+                        details.push("closed".to_string());
+                    }
+                    if details.is_empty() {
+                        self.logger.info(format!("{}: {}", i + 1, restaurant.id));
+                    } else {
+                        self.logger.info(format!(
+                            "{}: {} ({})",
+                            i + 1,
+                            restaurant.id,
+                            details.join(", ")
+                        ));
+                    }
+                }
+                self.logger.info(format!(
+                    "Or type 'radius <blocks>' to search within another distance (at most {}).",
+                    MAX_NEARBY_RADIUS
+                ));
+                self.logger.info(format!(
+                    "Or type 'search <name>' to search by name, 'tag <tag>' to search by cuisine (e.g. 'tag pizza'), either alone to see them all, 'sort <distance|name>' to change the order (now by {}), or 'closed' to {} the closed restaurants.",
+                    self.filter.sort,
+                    if self.filter.include_closed { "hide" } else { "show" }
+                ));
+            }
+            Prompt::Dish(_, cart) => {
+                if !cart.is_empty() {
+                    self.logger.info(format!(
+                        "Your cart: {} (${:.2})",
+                        cart,
+                        cart.total() as f64 / 100.0
+                    ));
+                }
+                self.logger.info(
+                    "Enter a dish to add to your cart, optionally followed by the units (e.g. 'Pizza 2'), or 'done' to finish:",
+                );
+            }
+            Prompt::Express(..) => {
+                self.logger
+                    .info("Do you want express delivery for an extra charge? (y/N):");
+            }
+            Prompt::PromoCode(..) => {
+                self.logger
+                    .info("Enter a promo code, or leave it empty if you have none:");
+            }
+            Prompt::Cancel => {
+                self.logger.info(
+                    "Type 'cancel' at any moment to cancel your order, or 'status' to see its timeline.",
+                );
+            }
+            Prompt::AfterOrder => {
+                self.logger.info("What do you want to do now?");
+                if let Some(order) = &self.last_order {
+                    self.logger.info(format!(
+                        "1: Order {} again at {}",
+                        order.cart, order.restaurant_id
+                    ));
+                }
+                self.logger.info("2: Browse the restaurants again");
+                self.logger.info("3: Quit");
+            }
+            Prompt::AfterDecline(decline) => {
+                self.logger
+                    .info("Your payment was declined. What do you want to do now?");
+                if let Some(order) = &self.last_order {
+                    let hint = match decline {
+                        Some(reason) if !reason.is_transient() => {
+                            " (it will likely be declined again)"
+                        }
+                        _ => "",
+                    };
+                    self.logger.info(format!(
+                        "1: Retry the payment of {} at {}{}",
+                        order.cart, order.restaurant_id, hint
+                    ));
+                }
+                self.logger.info("2: Pick another restaurant");
+                self.logger.info("3: Quit");
+            }
+        }
+        std::io::stdout().flush().unwrap();
+    }
+
+    /// Sends the order to the `Client` actor and waits for a cancellation from the user.
+    fn place_order(&mut self, order: UserOrderResult) {
+        self.logger.info(format!(
+            "You selected restaurant: {} and dishes: {} ({})",
+            order.restaurant_id, order.cart, order.priority
+        ));
+        self.client.do_send(SendThisOrder {
+            selected_restaurant: order.restaurant_id.clone(),
+            selected_restaurant_position: order.restaurant_position,
+            selected_items: order.cart.items().to_vec(),
+            priority: order.priority,
+            promo_code: order.promo_code.clone(),
+        });
+        self.last_order = Some(order);
+        self.prompt = Prompt::Cancel;
+        self.show_prompt();
+    }
+
+    /// Asks the `Client` actor for the restaurants again, with the search chosen by the user.
+    fn change_filter(&self) -> Prompt {
+        self.client.do_send(ChangeRestaurantFilter {
+            filter: self.filter.clone(),
+        });
+        Prompt::Idle
+    }
+
+    /// Handles a line typed by the user, according to what is being asked for.
+    ///
+    /// ## Returns
+    /// What to ask for next.
+    fn handle_input(&mut self, input: &str, prompt: Prompt) -> Prompt {
+        match prompt {
+            Prompt::Idle => {
+                self.logger.warn("Please wait for the server to answer.");
+                Prompt::Idle
+            }
+            Prompt::Restaurant(restaurants) => {
+                if let Some(radius) = input.strip_prefix("radius") {
+                    return match radius.trim().parse::<f32>() {
+                        Ok(radius) if radius > 0.0 && radius <= MAX_NEARBY_RADIUS => {
+                            self.client.do_send(ChangeSearchRadius { radius });
+                            Prompt::Idle
+                        }
+                        _ => {
+                            self.logger.warn(format!(
+                                "Invalid radius. Please enter a number of blocks between 0 and {}.",
+                                MAX_NEARBY_RADIUS
+                            ));
+                            Prompt::Restaurant(restaurants)
+                        }
+                    };
+                }
+                if let Some(query) = input.strip_prefix("search") {
+                    let query = query.trim();
+                    self.filter.query = (!query.is_empty()).then(|| query.to_string());
+                    return self.change_filter();
+                }
+                if let Some(tag) = input.strip_prefix("tag") {
+                    let tag = tag.trim();
+                    self.filter.tag = (!tag.is_empty()).then(|| tag.to_lowercase());
+                    return self.change_filter();
+                }
+                if let Some(sort) = input.strip_prefix("sort") {
+                    return match sort.parse::<RestaurantSort>() {
+                        Ok(sort) => {
+                            self.filter.sort = sort;
+                            self.change_filter()
+                        }
+                        Err(e) => {
+                            self.logger.warn(e);
+                            Prompt::Restaurant(restaurants)
+                        }
+                    };
+                }
+                if input.eq_ignore_ascii_case("closed") {
+                    self.filter.include_closed = !self.filter.include_closed;
+                    return self.change_filter();
+                }
+                match input.parse::<usize>() {
+                    Ok(num) if num >= 1 && num <= restaurants.len() => {
+                        let (restaurant, _, _) = restaurants[num - 1].clone();
+                        Prompt::Dish(restaurant, Cart::default())
+                    }
+                    _ => {
+                        self.logger.warn(
+                            "Invalid selection. Please enter a number corresponding to a restaurant.",
+                        );
+                        Prompt::Restaurant(restaurants)
+                    }
+                }
+            }
+            Prompt::Dish(restaurant, mut cart) => {
+                if input.eq_ignore_ascii_case("done") {
+                    if cart.is_empty() {
+                        self.logger
+                            .warn("Your cart is empty. Please add at least one dish.");
+                        return Prompt::Dish(restaurant, cart);
+                    }
+                    return Prompt::Express(restaurant, cart);
+                }
+                if let Err(e) = cart.add(input) {
+                    self.logger.warn(e);
+                }
+                Prompt::Dish(restaurant, cart)
+            }
+            Prompt::Express(restaurant, cart) => {
+                // Entrega express, con un recargo
+                let priority = if input.eq_ignore_ascii_case("y") {
+                    OrderPriority::Express
+                } else {
+                    OrderPriority::Standard
+                };
+                Prompt::PromoCode(restaurant, cart, priority)
+            }
+            Prompt::PromoCode(restaurant, cart, priority) => {
+                self.place_order(UserOrderResult {
+                    restaurant_id: restaurant.id,
+                    restaurant_position: restaurant.position,
+                    cart,
+                    priority,
+                    promo_code: (!input.is_empty()).then(|| input.to_string()),
+                });
+                Prompt::Cancel
+            }
+            Prompt::Cancel => {
+                if input.eq_ignore_ascii_case("cancel") {
+                    self.client.do_send(CancelCurrentOrder);
+                } else if input.eq_ignore_ascii_case("status") {
+                    self.show_timeline();
+                    // Si el estado cambió, la respuesta actualiza el timeline
+                    self.client.do_send(RefreshOrderStatus);
+                } else {
+                    self.logger.warn(
+                        "Unknown command. Type 'cancel' to cancel your order or 'status' to see it.",
+                    );
+                }
+                Prompt::Cancel
+            }
+            Prompt::AfterOrder => match input {
+                "1" if self.last_order.is_some() => {
+                    if let Some(mut order) = self.last_order.clone() {
+                        // Cada código promocional se usa una sola vez
+                        order.promo_code = None;
+                        self.place_order(order);
+                    }
+                    Prompt::Cancel
+                }
+                "2" => {
+                    self.client.do_send(BrowseRestaurants);
+                    Prompt::Idle
+                }
+                "3" => {
+                    self.client.do_send(QuitClient);
+                    Prompt::Idle
+                }
+                _ => {
+                    self.logger
+                        .warn("Invalid option. Please enter one of the numbers shown.");
+                    Prompt::AfterOrder
+                }
+            },
+            Prompt::AfterDecline(decline) => match input {
+                "1" if self.last_order.is_some() => {
+                    // El servidor libera el código promocional de un pedido no autorizado
+                    if let Some(order) = self.last_order.clone() {
+                        self.place_order(order);
+                    }
+                    Prompt::Cancel
+                }
+                "2" => {
+                    self.client.do_send(BrowseRestaurants);
+                    Prompt::Idle
+                }
+                "3" => {
+                    self.client.do_send(QuitClient);
+                    Prompt::Idle
+                }
+                _ => {
+                    self.logger
+                        .warn("Invalid option. Please enter one of the numbers shown.");
+                    Prompt::AfterDecline(decline)
+                }
+            },
+        }
     }
 }
 
 impl Actor for UIHandler {
     type Context = Context<Self>;
+
+    /// Starts the thread that reads the lines typed by the user.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let addr = ctx.address();
+        let logger = self.logger.clone();
+        // Un único hilo lee la entrada, para que ninguna línea se pierda entre dos lectores
+        std::thread::spawn(move || {
+            loop {
+                let mut input = String::new();
+                match std::io::stdin().read_line(&mut input) {
+                    Ok(0) => break,
+                    Ok(_) => addr.do_send(UserInput {
+                        line: input.trim().to_string(),
+                    }),
+                    Err(e) => {
+                        logger.error(format!("Error while reading input: {}", e));
+                        break;
+                    }
+                }
+            }
+        });
+    }
 }
 
 /// Handles the `SelectNearbyRestaurants` message.
 ///
-/// Extracts the list of nearby restaurants and dishes.
+/// Extracts the list of nearby restaurants, in the order the server sorted them.
 ///
 /// Prompts the user to select a restaurant and dish, then sending the
-/// selection to the `Client` actor.
+/// selection to the `Client` actor. The user can also search again within another radius,
+/// by name or in another order.
 impl Handler<SelectNearbyRestaurants> for UIHandler {
     type Result = ();
 
-    fn handle(&mut self, msg: SelectNearbyRestaurants, ctx: &mut Self::Context) {
+    fn handle(&mut self, msg: SelectNearbyRestaurants, _ctx: &mut Self::Context) {
         if msg.nearby_restaurants.is_empty() {
-            self.logger
-                .warn("No nearby restaurants found. Please try again later.");
-            return;
+            match (&self.filter.query, &self.filter.tag) {
+                (None, None) => self
+                    .logger
+                    .warn("No nearby restaurants found. Try searching within a larger radius."),
+                (query, tag) => self.logger.warn(format!(
+                    "No restaurants match '{}'. Try another search.",
+                    query
+                        .iter()
+                        .chain(tag)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                )),
+            }
         }
-        let logger = self.logger.clone();
-        let restaurants = msg.nearby_restaurants.clone();
-        let addr = ctx.address();
-
-        // Spawn blocking para no trabar el actor
-        actix::spawn(async move {
-            let (restaurant_id, dish_name) =
-                tokio::task::spawn_blocking(move || ask_user_order_blocking(&logger, restaurants))
-                    .await
-                    .unwrap();
-
-            addr.do_send(UserOrderResult {
-                restaurant_id,
-                dish_name,
-            });
-        });
+        // Los restaurantes ya vienen ordenados por el servidor
+        let restaurants = msg
+            .nearby_restaurants
+            .into_iter()
+            .map(|restaurant| {
+                let distance = msg.distances.get(&restaurant.id).copied();
+                let closed = msg.closed.contains(&restaurant.id);
+                (restaurant, distance, closed)
+            })
+            .collect();
+        self.prompt = Prompt::Restaurant(restaurants);
+        self.show_prompt();
     }
 }
 
+/// The order chosen by the user.
+#[derive(Debug, Clone)]
 pub struct UserOrderResult {
     pub restaurant_id: String,
-    pub dish_name: String,
+    pub restaurant_position: (f32, f32),
+    pub cart: Cart,
+    pub priority: OrderPriority,
+    pub promo_code: Option<String>,
 }
 
-impl Message for UserOrderResult {
+/// Handles the `OrderFinished` message.
+///
+/// Asks the user whether to order the same cart again, browse the restaurants again or quit.
+impl Handler<OrderFinished> for UIHandler {
     type Result = ();
-}
 
-fn ask_user_order_blocking(
-    logger: &Logger,
-    possible_restaurants: Vec<RestaurantInfo>,
-) -> (String, String) {
-    let selected_index = loop {
-        logger.info("Select a restaurant by number:");
-        for (i, restaurant) in possible_restaurants.iter().enumerate() {
-            logger.info(format!("{}: {}", i + 1, restaurant.id));
-        }
-        std::io::stdout().flush().unwrap();
-
-        let mut input = String::new();
-        if let Err(e) = std::io::stdin().read_line(&mut input) {
-            logger.error(format!(
-                "Error while reading input: {}. Please try again.",
-                e
-            ));
-            continue;
-        }
-
-        match input.trim().parse::<usize>() {
-            Ok(num) if num >= 1 && num <= possible_restaurants.len() => break num - 1,
-            _ => {
-                logger.warn(
-                    "Invalid selection. Please enter a number corresponding to a restaurant.",
-                );
-                continue;
-            }
-        }
-    };
+    fn handle(&mut self, _msg: OrderFinished, _ctx: &mut Self::Context) {
+        self.prompt = Prompt::AfterOrder;
+        self.show_prompt();
+    }
+}
 
-    let selected_restaurant = &possible_restaurants[selected_index];
+/// Handles the `PaymentDeclined` message.
+///
+/// Asks the user whether to retry the payment of the same cart, pick another restaurant or
+/// quit.
+impl Handler<PaymentDeclined> for UIHandler {
+    type Result = ();
 
-    // Ingreso del nombre del plato
-    let dish_name = loop {
-        logger.info("Please enter the name of the dish you want to order:");
-        std::io::stdout().flush().unwrap();
+    fn handle(&mut self, msg: PaymentDeclined, _ctx: &mut Self::Context) {
+        self.prompt = Prompt::AfterDecline(msg.decline);
+        self.show_prompt();
+    }
+}
 
-        let mut dish_input = String::new();
-        if let Err(e) = std::io::stdin().read_line(&mut dish_input) {
-            logger.error(format!(
-                "Error while reading dish input: {}. Please try again.",
-                e
-            ));
-            continue;
-        }
+/// Handles the `OrderStatusChanged` message.
+///
+/// Starts the timeline of a new order, or moves the current one forward and shows it.
+impl Handler<OrderStatusChanged> for UIHandler {
+    type Result = ();
 
-        let dish = dish_input.trim();
-        if !dish.is_empty() {
-            break dish.to_string();
-        } else {
-            logger.warn("Dish name cannot be empty. Please enter a valid dish name.");
-            continue;
+    fn handle(&mut self, msg: OrderStatusChanged, _ctx: &mut Self::Context) {
+        let now = Instant::now();
+        let timeline = match &mut self.timeline {
+            Some(timeline) if timeline.order_id == msg.order_id => timeline,
+            // El pedido recién hecho recibe el ID que le asignó el líder
+            Some(timeline)
+                if timeline.order_id == UNASSIGNED_ORDER_ID && !timeline.is_finished() =>
+            {
+                timeline.order_id = msg.order_id;
+                timeline
+            }
+            timeline => timeline.insert(OrderTimeline::new(msg.order_id, now)),
+        };
+        if timeline.advance(&msg.status, now) {
+            self.show_timeline();
         }
-    };
-
-    logger.info(format!(
-        "You selected restaurant: {} and dish: {}",
-        selected_restaurant.id, dish_name
-    ));
-    (selected_restaurant.id.clone(), dish_name)
+    }
 }
 
-impl Handler<UserOrderResult> for UIHandler {
+/// Handles the `UserInput` message.
+///
+/// Answers what the user is being asked for and asks for the next thing.
+impl Handler<UserInput> for UIHandler {
     type Result = ();
 
-    fn handle(&mut self, msg: UserOrderResult, _ctx: &mut Self::Context) {
-        self.client.do_send(SendThisOrder {
-            selected_restaurant: msg.restaurant_id,
-            selected_dish: msg.dish_name,
-        });
+    fn handle(&mut self, msg: UserInput, _ctx: &mut Self::Context) {
+        let prompt = std::mem::replace(&mut self.prompt, Prompt::Idle);
+        self.prompt = self.handle_input(&msg.line, prompt);
+        if !matches!(self.prompt, Prompt::Cancel | Prompt::Idle) {
+            self.show_prompt();
+        }
     }
 }
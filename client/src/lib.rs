@@ -1,2 +1,4 @@
+pub mod cart;
 pub mod client_actors;
 pub mod messages;
+pub mod order_timeline;
@@ -1,37 +1,45 @@
 use actix::Actor;
+use clap::Parser;
 use client::client_actors::client::Client;
-use common::constants::{BASE_PORT, NUM_COORDINATORS, SERVER_IP_ADDRESS};
-use common::utils::{get_rand_f32_tuple, print_welcome_message};
-use std::env;
-use std::net::SocketAddr;
+use common::cli::UserArgs;
+use common::constants::DEREGISTER_WAIT;
+use common::messages::Deregister;
+use common::utils::print_welcome_message;
 use tokio::signal::ctrl_c;
 
+/// Client of PedidosRust: orders food from the nearby restaurants.
+#[derive(Parser)]
+struct Args {
+    /// ID of the client (same as --id)
+    #[arg(value_name = "CLIENT_ID", conflicts_with = "id")]
+    client_id: Option<String>,
+    #[command(flatten)]
+    user: UserArgs,
+}
+
 #[actix::main]
 async fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <client_id>", args[0]);
-        std::process::exit(1);
-    }
+    let args = Args::parse();
 
-    let id = args[1].clone();
-    let servers: Vec<SocketAddr> = (0..NUM_COORDINATORS)
-        .map(|i| {
-            format!("{}:{}", SERVER_IP_ADDRESS, BASE_PORT + i)
-                .parse()
-                .expect("Invalid IP address")
-        })
-        .collect();
+    let identity = args.user.identity(args.client_id, "client")?;
+    let servers = args.user.servers();
+    args.user.apply_bind();
 
     print_welcome_message();
 
-    let position = get_rand_f32_tuple();
+    let position = args.user.position();
 
-    let client = Client::new(servers.clone(), id, position).await;
-    client.start();
+    let client = Client::new(servers, identity.user_id, position)
+        .await
+        .with_secret(identity.secret);
+    let client = client.start();
 
     tokio::select! {
         _ = ctrl_c() => {
+            // Se avisa a los servidores antes de cerrar la conexión
+            if client.send(Deregister).await.is_ok() {
+                tokio::time::sleep(DEREGISTER_WAIT).await;
+            }
             actix::System::current().stop();
         }
     }
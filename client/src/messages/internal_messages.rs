@@ -1,5 +1,11 @@
 use actix::Message;
+use common::types::decline_reason::DeclineReason;
+use common::types::dtos::OrderItem;
+use common::types::order_priority::OrderPriority;
+use common::types::order_status::OrderStatus;
+use common::types::restaurant_filter::RestaurantFilter;
 use common::types::restaurant_info::RestaurantInfo;
+use std::collections::HashMap;
 
 /// Request message to fetch nearby restaurants.
 ///
@@ -7,10 +13,38 @@ use common::types::restaurant_info::RestaurantInfo;
 ///
 /// Content:
 /// - `nearby_restaurants`: A vector of `RestaurantInfo` containing details about each restaurant.
+/// - `distances`: The distance to each restaurant, in blocks, by restaurant ID.
+/// - `closed`: The IDs of the restaurants listed that are outside their opening hours.
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct SelectNearbyRestaurants {
     pub nearby_restaurants: Vec<RestaurantInfo>,
+    pub distances: HashMap<String, f32>,
+    pub closed: Vec<String>,
+}
+
+/// Request message to search for restaurants within another distance.
+///
+/// This message is sent by the UI when the user changes the search radius.
+///
+/// Content:
+/// - `radius`: The maximum distance to the restaurants, in blocks.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChangeSearchRadius {
+    pub radius: f32,
+}
+
+/// Request message to search the restaurants by name, or list them in another order.
+///
+/// This message is sent by the UI when the user changes the search.
+///
+/// Content:
+/// - `filter`: The [`RestaurantFilter`] to ask the server for.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct ChangeRestaurantFilter {
+    pub filter: RestaurantFilter,
 }
 
 /// Request message to send an order to the selected restaurant.
@@ -19,14 +53,34 @@ pub struct SelectNearbyRestaurants {
 ///
 /// Content:
 /// - `selected_restaurant`: The name of the restaurant where the order is placed.
-/// - `selected_dish`: The name of the dish that the user has selected to order.
+/// - `selected_restaurant_position`: The position of that restaurant, used to estimate the arrival time.
+/// - `selected_items`: The dishes in the cart of the user, with their units.
+/// - `priority`: Whether the user asked for express delivery.
+/// - `promo_code`: The promo code typed by the user, if any.
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct SendThisOrder {
     pub selected_restaurant: String,
-    pub selected_dish: String,
+    pub selected_restaurant_position: (f32, f32),
+    pub selected_items: Vec<OrderItem>,
+    pub priority: OrderPriority,
+    pub promo_code: Option<String>,
 }
 
+/// Request message to cancel the current order.
+///
+/// This message is sent by the UI when the user asks to cancel the order they placed.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct CancelCurrentOrder;
+
+/// Request message to ask the server for the current status of the order.
+///
+/// This message is sent by the UI when the user asks to see the order they placed.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct RefreshOrderStatus;
+
 /// Enum: UIMessage
 /// This enum defines the messages that the UIHandler can receive.
 #[derive(Message)]
@@ -35,3 +89,56 @@ pub enum UIMessage {
     ShowMessage(String),
     ShowOrderStatus(String),
 }
+
+/// Message sent to the UI when the current order is placed or changes its status.
+///
+/// The UI keeps the timeline of the order up to date and shows it.
+///
+/// Content:
+/// - `order_id`: The ID of the order.
+/// - `status`: The new status of the order, `Requested` when it is placed.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct OrderStatusChanged {
+    pub order_id: u64,
+    pub status: OrderStatus,
+}
+
+/// Message sent to the UI when the current order finished, either delivered or cancelled.
+///
+/// The UI asks the user whether to order again, browse the restaurants or quit.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct OrderFinished;
+
+/// Message sent to the UI when the payment of the current order was declined.
+///
+/// The UI asks the user whether to retry the payment, pick another restaurant or quit.
+///
+/// Content:
+/// - `decline`: Why the payment was declined, if the server said so.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct PaymentDeclined {
+    pub decline: Option<DeclineReason>,
+}
+
+/// Request message to fetch the nearby restaurants again, after an order finished.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct BrowseRestaurants;
+
+/// Request message to stop the client, sent by the UI when the user quits.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct QuitClient;
+
+/// A line typed by the user.
+///
+/// Content:
+/// - `line`: The line, without surrounding whitespace.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct UserInput {
+    pub line: String,
+}
@@ -0,0 +1,161 @@
+use common::types::order_status::OrderStatus;
+use std::time::{Duration, Instant};
+
+/// The stages an order goes through until it is delivered, in order.
+pub const STAGES: [OrderStatus; 5] = [
+    OrderStatus::Authorized,
+    OrderStatus::Preparing,
+    OrderStatus::ReadyForDelivery,
+    OrderStatus::Delivering,
+    OrderStatus::Delivered,
+];
+
+/// Returns the position in [`STAGES`] of the stage an order in `status` is at, or `None` if
+/// the status is not part of the way to the client.
+fn stage_of(status: &OrderStatus) -> Option<usize> {
+    match status {
+        // Un pedido pendiente ya está autorizado y espera a la cocina
+        OrderStatus::Pending => Some(0),
+        status => STAGES.iter().position(|stage| stage == status),
+    }
+}
+
+/// Formats a duration as seconds with one decimal.
+fn seconds(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f32())
+}
+
+/// The stages an order reached and when, shown to the client while it waits for the order.
+#[derive(Debug, Clone)]
+pub struct OrderTimeline {
+    /// ID of the order.
+    pub order_id: u64,
+    /// When the order was placed.
+    placed_at: Instant,
+    /// When the order reached each stage of [`STAGES`], if it did.
+    reached_at: [Option<Instant>; STAGES.len()],
+    /// The status that ended the order without delivering it, and when.
+    ended: Option<(OrderStatus, Instant)>,
+}
+
+impl OrderTimeline {
+    /// Creates the timeline of an order placed at `now`.
+    pub fn new(order_id: u64, now: Instant) -> Self {
+        Self {
+            order_id,
+            placed_at: now,
+            reached_at: [None; STAGES.len()],
+            ended: None,
+        }
+    }
+
+    /// Records that the order changed to `status` at `now`.
+    ///
+    /// Stages skipped on the way (e.g. an update that never arrived) are considered reached
+    /// at `now`. Updates of a stage already reached change nothing.
+    ///
+    /// ## Returns
+    /// `true` if the timeline changed.
+    pub fn advance(&mut self, status: &OrderStatus, now: Instant) -> bool {
+        if self.is_finished() {
+            return false;
+        }
+        let Some(stage) = stage_of(status) else {
+            if matches!(status, OrderStatus::Requested) {
+                return false;
+            }
+            self.ended = Some((status.clone(), now));
+            return true;
+        };
+        let mut changed = false;
+        for reached in self.reached_at.iter_mut().take(stage + 1) {
+            if reached.is_none() {
+                *reached = Some(now);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Returns `true` once the order was delivered or ended without being delivered.
+    pub fn is_finished(&self) -> bool {
+        self.ended.is_some() || self.reached_at[STAGES.len() - 1].is_some()
+    }
+
+    /// Returns the lines of the timeline at `now`: every stage, whether it was reached and
+    /// how long the order spent in it.
+    pub fn render(&self, now: Instant) -> Vec<String> {
+        let mut lines = vec![format!("Order {} timeline:", self.order_id)];
+        let last = STAGES.len() - 1;
+        let end = self.ended.as_ref().map(|(_, at)| *at);
+        for (i, stage) in STAGES.iter().enumerate() {
+            let line = match self.reached_at[i] {
+                Some(_) if i == last => "[x] Delivered".to_string(),
+                Some(at) => {
+                    let next = self.reached_at[i + 1];
+                    let left = next.or(end).unwrap_or(now);
+                    let (mark, note) = match next {
+                        Some(_) => ("x", ""),
+                        None if end.is_some() => ("!", ""),
+                        None => (">", " (now)"),
+                    };
+                    format!(
+                        "[{}] {:<18} {:>7}{}",
+                        mark,
+                        stage.to_string(),
+                        seconds(left.saturating_duration_since(at)),
+                        note
+                    )
+                }
+                None => format!("[ ] {}", stage),
+            };
+            lines.push(format!("  {}", line));
+        }
+        let total_until = self.reached_at[last].or(end).unwrap_or(now);
+        let total = seconds(total_until.saturating_duration_since(self.placed_at));
+        match &self.ended {
+            Some((status, _)) => lines.push(format!("  Ended as {:?} after {}", status, total)),
+            None if self.reached_at[last].is_some() => {
+                lines.push(format!("  Delivered in {}", total))
+            }
+            None => lines.push(format!("  Waiting for {}", total)),
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marks_the_skipped_stages_as_reached() {
+        let placed = Instant::now();
+        let mut timeline = OrderTimeline::new(7, placed);
+        assert!(timeline.advance(&OrderStatus::Authorized, placed));
+        assert!(timeline.advance(&OrderStatus::Delivering, placed + Duration::from_secs(4)));
+        assert!(!timeline.advance(&OrderStatus::Preparing, placed + Duration::from_secs(5)));
+
+        let lines = timeline.render(placed + Duration::from_secs(6));
+        assert_eq!(lines[1], "  [x] Authorized            4.0s");
+        assert_eq!(lines[2], "  [x] Preparing             0.0s");
+        assert_eq!(lines[4], "  [>] Delivering            2.0s (now)");
+        assert_eq!(lines[5], "  [ ] Delivered");
+        assert_eq!(lines[6], "  Waiting for 6.0s");
+    }
+
+    #[test]
+    fn stops_at_a_cancellation() {
+        let placed = Instant::now();
+        let mut timeline = OrderTimeline::new(7, placed);
+        timeline.advance(&OrderStatus::Pending, placed);
+        timeline.advance(&OrderStatus::Cancelled, placed + Duration::from_secs(3));
+        assert!(timeline.is_finished());
+        assert!(!timeline.advance(&OrderStatus::Preparing, placed + Duration::from_secs(4)));
+
+        let lines = timeline.render(placed + Duration::from_secs(10));
+        assert_eq!(lines[1], "  [!] Authorized            3.0s");
+        assert_eq!(lines[2], "  [ ] Preparing");
+        assert_eq!(lines[6], "  Ended as Cancelled after 3.0s");
+    }
+}
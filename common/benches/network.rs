@@ -0,0 +1,239 @@
+//! Benchmarks of the replication path on the wire: serializing and deserializing
+//! [`NetworkMessage`]s, and round trips between two [`Communicator`]s over loopback.
+//!
+//! Run them with `cargo bench -p common`.
+
+use actix::prelude::*;
+use common::messages::coordinator_messages::NewOrder;
+use common::messages::coordinatormanager_messages::{Ping, Pong, StorageUpdates};
+use common::messages::internal_messages::{AddOrder, SetOrderStatus, StorageLogMessage};
+use common::messages::shared_messages::NetworkMessage;
+use common::network::codec::{
+    Compression, Frame, WireFormat, WireSettings, encode_frames, read_frame,
+};
+use common::network::communicator::Communicator;
+use common::network::peer_types::PeerType;
+use common::network::tcp_sender::TCPSender;
+use common::types::dtos::{OrderDTO, OrderItem};
+use common::types::order_status::OrderStatus;
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// Entries of the batch of storage updates, as the leader replicates them.
+const STORAGE_UPDATES: u64 = 500;
+/// Messages sent before waiting for the answers, to measure the throughput of a connection.
+const PIPELINED: u64 = 100;
+
+fn order(order_id: u64) -> OrderDTO {
+    OrderDTO {
+        client_position: (3.0, 7.0),
+        ..OrderDTO::new(
+            order_id,
+            &format!("client_{}", order_id % 100),
+            &format!("restaurant_{}", order_id % 10),
+            vec![OrderItem::new("Pizza", 2), OrderItem::new("Empanadas", 12)],
+        )
+    }
+}
+
+/// Returns the messages to benchmark, by name: a small one sent to users and a large one
+/// sent between coordinators.
+fn messages() -> Vec<(&'static str, NetworkMessage)> {
+    let updates = (1..=STORAGE_UPDATES)
+        .map(|index| {
+            let update = if index % 2 == 0 {
+                StorageLogMessage::AddOrder(AddOrder {
+                    order: order(index),
+                    event: None,
+                })
+            } else {
+                StorageLogMessage::SetOrderStatus(SetOrderStatus {
+                    order: order(index),
+                    order_status: OrderStatus::Preparing,
+                    event: None,
+                })
+            };
+            (index, update)
+        })
+        .collect::<HashMap<_, _>>();
+    vec![
+        (
+            "new_order",
+            NetworkMessage::NewOrder(NewOrder { order: order(1) }),
+        ),
+        (
+            "storage_updates",
+            NetworkMessage::StorageUpdates(StorageUpdates {
+                updates,
+                coordinator_id: "server_0".to_string(),
+                epoch: 1,
+            }),
+        ),
+    ]
+}
+
+/// Returns the wire settings to benchmark, by name.
+fn wire_settings() -> Vec<(&'static str, WireSettings)> {
+    let mut settings = vec![
+        ("json", WireSettings::default()),
+        (
+            "json_lz4",
+            WireSettings {
+                format: WireFormat::Json,
+                compression: Compression::Lz4,
+            },
+        ),
+    ];
+    if cfg!(feature = "protobuf") {
+        settings.push((
+            "protobuf_lz4",
+            WireSettings {
+                format: WireFormat::Protobuf,
+                compression: Compression::Lz4,
+            },
+        ));
+    }
+    settings
+}
+
+fn serialization(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("serialization");
+    for (message_name, message) in messages() {
+        for (settings_name, settings) in wire_settings() {
+            let frames = encode_frames(settings, &message).unwrap();
+            let bytes = frames.concat();
+            group.throughput(Throughput::Bytes(bytes.len() as u64));
+            let id = format!("{}/{}", message_name, settings_name);
+            group.bench_function(BenchmarkId::new("serialize", &id), |b| {
+                b.iter(|| encode_frames(settings, &message).unwrap())
+            });
+            group.bench_function(BenchmarkId::new("deserialize", &id), |b| {
+                b.iter(|| {
+                    let mut reader = bytes.as_slice();
+                    match runtime.block_on(read_frame(&mut reader)) {
+                        Ok(Some(Frame::Message(message))) => message,
+                        other => panic!("Cannot read the frame back: {:?}", other),
+                    }
+                })
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Answers every [`Ping`] with a [`Pong`] over its connection.
+struct Echo {
+    communicator: Communicator<Echo>,
+}
+
+impl Actor for Echo {
+    type Context = Context<Self>;
+}
+
+impl Handler<NetworkMessage> for Echo {
+    type Result = ();
+
+    fn handle(&mut self, msg: NetworkMessage, _ctx: &mut Self::Context) -> Self::Result {
+        if let NetworkMessage::Ping(ping) = msg
+            && let Some(sender) = &self.communicator.sender
+        {
+            sender.do_send(NetworkMessage::Pong(Pong { from: ping.from }));
+        }
+    }
+}
+
+/// Counts every [`Pong`] received over its connection.
+struct Probe {
+    _communicator: Communicator<Probe>,
+    pongs: mpsc::UnboundedSender<()>,
+}
+
+impl Actor for Probe {
+    type Context = Context<Self>;
+}
+
+impl Handler<NetworkMessage> for Probe {
+    type Result = ();
+
+    fn handle(&mut self, msg: NetworkMessage, _ctx: &mut Self::Context) -> Self::Result {
+        if let NetworkMessage::Pong(_) = msg {
+            let _ = self.pongs.send(());
+        }
+    }
+}
+
+/// Connects a [`Probe`] to an [`Echo`] over loopback, and returns the sender of the probe and
+/// where its pongs arrive.
+async fn connect() -> (Addr<TCPSender>, SocketAddr, mpsc::UnboundedReceiver<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (stream, accepted) = tokio::join!(TcpStream::connect(addr), listener.accept());
+    let (stream, (accepted, _)) = (stream.unwrap(), accepted.unwrap());
+
+    Echo::create(|ctx| Echo {
+        communicator: Communicator::new(accepted, ctx.address(), PeerType::CoordinatorType),
+    });
+    let (pongs_tx, pongs) = mpsc::unbounded_channel();
+    let mut sender = None;
+    Probe::create(|ctx| {
+        let communicator = Communicator::new(stream, ctx.address(), PeerType::CoordinatorType);
+        sender = communicator.sender.as_deref().cloned();
+        Probe {
+            _communicator: communicator,
+            pongs: pongs_tx,
+        }
+    });
+    (sender.unwrap(), addr, pongs)
+}
+
+/// Sends `iterations` rounds of `batch` pings and waits for all their pongs, returning how
+/// long it took.
+async fn ping_pong(
+    sender: &Addr<TCPSender>,
+    from: SocketAddr,
+    pongs: &mut mpsc::UnboundedReceiver<()>,
+    iterations: u64,
+    batch: u64,
+) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        for _ in 0..batch {
+            sender.do_send(NetworkMessage::Ping(Ping { from }));
+        }
+        for _ in 0..batch {
+            pongs.recv().await.expect("The echo closed the connection");
+        }
+    }
+    start.elapsed()
+}
+
+fn communicator(c: &mut Criterion) {
+    let system = System::new();
+    let (sender, from, mut pongs) = system.block_on(connect());
+    // La primera ronda espera al handshake de ambos extremos
+    system.block_on(ping_pong(&sender, from, &mut pongs, 1, 1));
+
+    let mut group = c.benchmark_group("communicator");
+    group.bench_function("round_trip", |b| {
+        b.iter_custom(|iterations| {
+            system.block_on(ping_pong(&sender, from, &mut pongs, iterations, 1))
+        })
+    });
+    group.throughput(Throughput::Elements(PIPELINED));
+    group.bench_function(format!("pipelined_{}", PIPELINED), |b| {
+        b.iter_custom(|iterations| {
+            system.block_on(ping_pong(&sender, from, &mut pongs, iterations, PIPELINED))
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, serialization, communicator);
+criterion_main!(benches);
@@ -0,0 +1,18 @@
+fn main() {
+    #[cfg(feature = "protobuf")]
+    {
+        // Usar el protoc incluido para no depender de una instalación del sistema
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc binary not available");
+        // SAFETY: el build script es single-threaded en este punto
+        unsafe {
+            std::env::set_var("PROTOC", protoc);
+        }
+        println!("cargo:rerun-if-changed=proto/coordinator.proto");
+        // El cliente y el servidor gRPC solo se generan con la feature `grpc`
+        tonic_build::configure()
+            .build_client(cfg!(feature = "grpc"))
+            .build_server(cfg!(feature = "grpc"))
+            .compile_protos(&["proto/coordinator.proto"], &["proto"])
+            .expect("Failed to compile coordinator.proto");
+    }
+}
@@ -0,0 +1,198 @@
+use crate::identity::{self, Identity};
+use crate::network::connections::{coordinator_addresses, parse_addr};
+use crate::network::stream::bind_outgoing;
+use crate::types::map::Map;
+use clap::Args;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Command line arguments shared by the users (clients, restaurants and deliveries).
+#[derive(Debug, Clone, Default, Args)]
+pub struct UserArgs {
+    /// ID of the user [default: a new one, from the process ID]
+    #[arg(long)]
+    pub id: Option<String>,
+    /// Comma-separated addresses of the coordinators, as host:port [default: the local
+    /// cluster, starting at BASE_PORT]
+    #[arg(long, value_delimiter = ',', value_name = "ADDR,...", value_parser = parse_addr)]
+    pub servers: Vec<SocketAddr>,
+    /// Local address to connect to the servers from (port 0 for any port)
+    #[arg(long, value_name = "ADDR")]
+    pub bind: Option<SocketAddr>,
+    /// Position of the user, as `x,y`, inside the map [default: a random one]
+    #[arg(long, value_name = "X,Y", value_parser = parse_position)]
+    pub position: Option<(f32, f32)>,
+    /// District of the map to place the user at random in: centro, norte, sur, este or oeste
+    #[arg(long, value_name = "NAME", value_parser = parse_district, conflicts_with = "position")]
+    pub district: Option<String>,
+    /// File keeping the ID and secret of the user, created on the first run [default: the ID,
+    /// or the kind of user if no ID is given, in IDENTITY_DIR]
+    #[arg(long, value_name = "FILE")]
+    pub identity: Option<PathBuf>,
+}
+
+impl UserArgs {
+    /// Returns the ID of the user, given as `positional` (the ID without `--id`) or with
+    /// `--id`, or one made of `prefix` and the process ID if none was given.
+    pub fn id(&self, positional: Option<String>, prefix: &str) -> String {
+        positional
+            .or_else(|| self.id.clone())
+            .unwrap_or_else(|| format!("{}_{}", prefix, std::process::id()))
+    }
+
+    /// Returns the identity of the user, kept in its identity file so that every run
+    /// registers as the same user. The ID is the one given, as in [`UserArgs::id`], or the one
+    /// of the first run.
+    ///
+    /// If `IDENTITY_DIR` is `off` and no `--identity` is given, the identity has no secret.
+    pub fn identity(&self, positional: Option<String>, prefix: &str) -> io::Result<Identity> {
+        let given = positional.or_else(|| self.id.clone());
+        let path = match (&self.identity, identity::dir_from_env()) {
+            (Some(path), _) => path.clone(),
+            (None, Some(dir)) => dir.join(format!("{}.json", given.as_deref().unwrap_or(prefix))),
+            (None, None) => return Ok(Identity::anonymous(self.id(given, prefix))),
+        };
+        Identity::load_or_create(&path, given.as_deref(), || self.id(None, prefix))
+    }
+
+    /// Returns the addresses of the coordinators to connect to.
+    pub fn servers(&self) -> Vec<SocketAddr> {
+        if self.servers.is_empty() {
+            coordinator_addresses()
+        } else {
+            self.servers.clone()
+        }
+    }
+
+    /// Returns the position of the user: the given one, a random one in the given district,
+    /// or a random one in the whole map.
+    pub fn position(&self) -> (f32, f32) {
+        let map = Map::default();
+        match (self.position, &self.district) {
+            (Some(position), _) => position,
+            (None, Some(name)) => map
+                .district(name)
+                .map(|district| district.random_position())
+                .unwrap_or_else(|_| map.random_position()),
+            (None, None) => map.random_position(),
+        }
+    }
+
+    /// Opens the connections to the servers from `--bind`, if it was given.
+    pub fn apply_bind(&self) {
+        if let Some(addr) = self.bind {
+            bind_outgoing(addr);
+        }
+    }
+}
+
+/// Parses a position written as `x,y`, which must be inside the map.
+pub fn parse_position(value: &str) -> Result<(f32, f32), String> {
+    let (x, y) = value
+        .split_once(',')
+        .ok_or_else(|| format!("Invalid position, expected x,y: {}", value))?;
+    let coordinate = |c: &str| {
+        c.trim()
+            .parse::<f32>()
+            .ok()
+            .filter(|c| c.is_finite())
+            .ok_or_else(|| format!("Invalid coordinate: {}", c))
+    };
+    Map::default()
+        .check((coordinate(x)?, coordinate(y)?))
+        .map_err(|e| e.to_string())
+}
+
+/// Parses the name of a district of the map.
+fn parse_district(value: &str) -> Result<String, String> {
+    Map::default()
+        .district(value)
+        .map(|district| district.name.to_string())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[derive(Parser)]
+    struct TestArgs {
+        #[arg(value_name = "ID", conflicts_with = "id")]
+        user_id: Option<String>,
+        #[command(flatten)]
+        user: UserArgs,
+    }
+
+    #[test]
+    fn parses_the_flags_shared_by_the_users() {
+        let args = TestArgs::parse_from([
+            "client",
+            "--servers",
+            "10.0.0.1:8081,10.0.0.2:8082",
+            "--bind",
+            "10.0.0.9:0",
+            "--position",
+            "2.5,3",
+        ]);
+
+        assert_eq!(
+            args.user.servers(),
+            vec![
+                "10.0.0.1:8081".parse::<SocketAddr>().unwrap(),
+                "10.0.0.2:8082".parse().unwrap()
+            ]
+        );
+        assert_eq!(args.user.bind, Some("10.0.0.9:0".parse().unwrap()));
+        assert_eq!(args.user.position(), (2.5, 3.0));
+        assert_eq!(
+            args.user.id(args.user_id, "client"),
+            format!("client_{}", std::process::id())
+        );
+    }
+
+    #[test]
+    fn resolves_the_host_names_of_the_servers() {
+        let args = TestArgs::parse_from(["client", "--servers", "localhost:8081,10.0.0.2:8082"]);
+        let servers = args.user.servers();
+        assert!(servers[0].ip().is_loopback());
+        assert_eq!(servers[0].port(), 8081);
+        assert_eq!(servers[1], "10.0.0.2:8082".parse().unwrap());
+
+        assert!(TestArgs::try_parse_from(["client", "--servers", "localhost"]).is_err());
+    }
+
+    #[test]
+    fn the_id_is_given_with_or_without_the_flag() {
+        let args = TestArgs::parse_from(["client", "cliente_1"]);
+        assert_eq!(args.user.id(args.user_id, "client"), "cliente_1");
+
+        let args = TestArgs::parse_from(["client", "--id", "cliente_2"]);
+        assert_eq!(args.user.id(args.user_id, "client"), "cliente_2");
+
+        assert!(TestArgs::try_parse_from(["client", "cliente_1", "--id", "cliente_2"]).is_err());
+    }
+
+    #[test]
+    fn rejects_invalid_positions() {
+        assert!(parse_position("1,2").is_ok());
+        assert!(parse_position("1").is_err());
+        assert!(parse_position("a,2").is_err());
+        assert!(parse_position("1,NaN").is_err());
+        assert!(parse_position("1,-2").is_err());
+    }
+
+    #[test]
+    fn places_the_user_in_the_given_district() {
+        let args = TestArgs::parse_from(["client", "--district", "Norte"]);
+        let map = Map::default();
+        let norte = map.district("norte").unwrap();
+        assert!(norte.contains(args.user.position()));
+
+        assert!(TestArgs::try_parse_from(["client", "--district", "afueras"]).is_err());
+        assert!(
+            TestArgs::try_parse_from(["client", "--district", "sur", "--position", "1,1"]).is_err()
+        );
+    }
+}
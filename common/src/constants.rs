@@ -3,7 +3,7 @@ use tokio::time::Duration;
 const DELAY_SECONDS: u64 = 2;
 pub const COORDINATE_SCALE: f32 = 10.0;
 pub const NEARBY_RADIUS: f32 = 8.0; // blocks
-pub const PAYMENT_SUCCESS_PROBABILITY: f32 = 0.95;
+pub const MAX_NEARBY_RADIUS: f32 = 2.0 * COORDINATE_SCALE; // blocks
 pub const RESTAURANT_SUCCESS_PROBABILITY: f32 = 0.9;
 pub const DELIVERY_SUCCESS_PROBABILITY: f32 = 0.8;
 pub const NUM_COORDINATORS: u16 = 4;
@@ -19,4 +19,132 @@ pub const TIMEOUT_LEADER_RESPONSE: Duration = Duration::from_secs(5);
 pub const NUMBER_OF_CHEFS: usize = 4;
 pub const DEFAULT_TIME_TO_COOK: u64 = 8;
 pub const DELAY_SECONDS_TO_START_RECONNECT: Duration = Duration::from_secs(3);
+pub const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+pub const RECONNECT_MAX_ATTEMPTS: u32 = 5;
+pub const RECONNECT_JITTER: f64 = 0.2;
+/// Wait a user is asked for when it looks for the leader during an election.
+pub const RETRY_LATER_WHILE_ELECTING: Duration = Duration::from_secs(2);
+/// First wait of a user before asking for the leader again after a `RetryLater`.
+pub const RETRY_LATER_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Longest wait of a user between questions for the leader, unless the server asks for more.
+pub const RETRY_LATER_MAX_DELAY: Duration = Duration::from_secs(15);
+/// Questions for the leader answered with a `RetryLater` before trying another server.
+pub const RETRY_LATER_MAX_ATTEMPTS: u32 = 6;
 pub const REAP_TIMEOUT: Duration = Duration::from_secs(10);
+pub const DELIVERY_GRACE_PERIOD: Duration = Duration::from_secs(5);
+pub const ORDER_REAPER_INTERVAL: Duration = Duration::from_secs(10);
+pub const ORDER_REAPER_MAX_RETRIES: u32 = 2;
+/// How often the leader records the connected users and collects the stale ones.
+pub const USER_COLLECTOR_INTERVAL: Duration = Duration::from_secs(60);
+/// How long a client or delivery can go unseen before it is evicted from storage.
+pub const USER_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+pub const PAYMENT_GATEWAY_RETRY_BASE: Duration = Duration::from_secs(1);
+pub const PAYMENT_GATEWAY_RETRY_MAX: Duration = Duration::from_secs(30);
+pub const PAYMENT_GATEWAY_MAX_PENDING: usize = 1000;
+pub const PAYMENT_GATEWAY_HEALTH_INTERVAL: Duration = Duration::from_secs(2);
+pub const PAYMENT_GATEWAY_HEALTH_TIMEOUT: Duration = Duration::from_secs(6);
+pub const STUCK_PAYMENT_TIMEOUT: Duration = Duration::from_secs(60);
+pub const STUCK_KITCHEN_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+pub const STUCK_DELIVERY_TIMEOUT: Duration = Duration::from_secs(60);
+pub const API_GATEWAY_PORT: u16 = PAYMENT_GATEWAY_PORT + 1;
+pub const API_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+pub const WS_NOTIFICATIONS_PORT_OFFSET: u16 = 1000;
+/// Port offset of the TCP subscribers of the order lifecycle events.
+pub const EVENT_SUBSCRIBERS_PORT_OFFSET: u16 = 6000;
+pub const GRPC_PORT_OFFSET: u16 = 2000;
+pub const PROTOCOL_VERSION: u32 = 1;
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+pub const SNAPSHOT_CHUNK_SIZE: usize = 32 * 1024;
+pub const SNAPSHOT_CHUNK_TIMEOUT: Duration = Duration::from_secs(3);
+pub const SNAPSHOT_CACHE_TTL: Duration = Duration::from_secs(60);
+/// How often each follower compares its storage with the one of another random follower.
+pub const ANTI_ENTROPY_INTERVAL: Duration = Duration::from_secs(15);
+pub const DEFAULT_STORAGE_LOG_DIR: &str = "storage_logs";
+pub const DEFAULT_PAYMENT_LEDGER_DIR: &str = "payment_ledgers";
+pub const DEFAULT_IDENTITY_DIR: &str = "identities";
+pub const LOG_COMPACTION_INTERVAL: Duration = Duration::from_secs(30);
+pub const LOG_COMPACTION_FOLLOWER_TIMEOUT: Duration = Duration::from_secs(60);
+pub const LOG_COMPACTION_MIN_ENTRIES: u64 = 1000;
+pub const QUORUM_WRITE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Longest wait of a leader handing the leadership off for its successor to apply the log.
+pub const LEADERSHIP_HANDOFF_TIMEOUT: Duration = Duration::from_secs(10);
+/// Wait of a drained coordinator before stopping, so that its users get the new leader.
+pub const DRAIN_GRACE_PERIOD: Duration = Duration::from_secs(1);
+pub const REPLICA_MAX_STALENESS: Duration = Duration::from_secs(5);
+pub const FAULT_REORDER_WINDOW: Duration = Duration::from_millis(500);
+pub const DASHBOARD_PORT_OFFSET: u16 = 3000;
+pub const DASHBOARD_LEADER_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+pub const HEALTH_PORT_OFFSET: u16 = 5000;
+pub const HEALTH_REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+pub const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+pub const HEALTH_WAIT_TIMEOUT: Duration = Duration::from_secs(15);
+pub const MAX_RECENT_ELECTIONS: usize = 10;
+pub const MAX_REMOVED_ORDER_TIMELINES: usize = 500;
+pub const MAX_TOMBSTONES: usize = 5_000;
+pub const MAX_PENDING_ORDER_UPDATES: usize = 100;
+pub const MAX_ORDER_REQUESTS_PER_CLIENT: usize = 16;
+pub const MAX_COMPLETED_PAYMENTS: usize = 500;
+pub const DELIVERY_OFFER_TIMEOUT: Duration = Duration::from_secs(6);
+pub const EXPRESS_DELIVERY_OFFER_TIMEOUT: Duration = Duration::from_secs(3);
+pub const DELIVERY_REOFFER_ATTEMPTS: u32 = 3;
+pub const DELIVERY_REOFFER_BACKOFF: Duration = Duration::from_secs(2);
+pub const ORDER_PRICE_CENTS: u64 = 1000;
+pub const EXPRESS_SURCHARGE_CENTS: u64 = 500;
+pub const DEFAULT_CLIENT_BALANCE: u64 = 5 * ORDER_PRICE_CENTS; // cents
+pub const DELIVERY_BASE_FEE_CENTS: u64 = 200;
+pub const DELIVERY_FEE_PER_BLOCK_CENTS: u64 = 25;
+pub const EARNINGS_REPORT_TIMEOUT: Duration = Duration::from_secs(3);
+pub const DEAD_LETTER_TTL: Duration = Duration::from_secs(30);
+pub const DEAD_LETTER_MAX_PER_USER: usize = 100;
+pub const DEAD_LETTER_PURGE_INTERVAL: Duration = Duration::from_secs(10);
+pub const RATE_LIMIT_BURST: u32 = 10;
+pub const RATE_LIMIT_PER_SECOND: f64 = 2.0;
+pub const RATE_LIMIT_MAX_STRIKES: u32 = 20;
+pub const OUTBOUND_QUEUE_CAPACITY: usize = 1024;
+/// Connections of users a server keeps open at the same time before refusing new ones.
+pub const MAX_CONNECTIONS: usize = 4096;
+/// Connections waiting in the listen queue of a server before the system refuses new ones.
+pub const ACCEPT_BACKLOG: u32 = 1024;
+/// Time from accepting a connection to registering it above which a warning is logged.
+pub const SLOW_ACCEPT_THRESHOLD: Duration = Duration::from_millis(500);
+/// Wait a user refused by a full server is asked for before connecting again.
+pub const CONNECTION_REFUSED_RETRY_AFTER: Duration = Duration::from_secs(5);
+pub const OUTBOUND_BLOCK_TIMEOUT: Duration = Duration::from_secs(2);
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(2);
+pub const KEEPALIVE_TIMEOUT: Duration = Duration::from_secs(6);
+pub const RELIABLE_RESEND_INTERVAL: Duration = Duration::from_secs(2);
+pub const RELIABLE_MAX_ATTEMPTS: u32 = 5;
+pub const RELIABLE_SEEN_IDS: usize = 1024;
+pub const USER_SHARD_VIRTUAL_NODES: usize = 64;
+pub const ORDER_SERVICE_SHARDS: usize = 4;
+pub const MAX_OPEN_DELIVERY_OFFERS: usize = 8;
+pub const GOSSIP_PORT_OFFSET: u16 = 4000;
+pub const GOSSIP_INTERVAL: Duration = Duration::from_secs(1);
+pub const GOSSIP_ACK_TIMEOUT: Duration = Duration::from_millis(400);
+pub const GOSSIP_INDIRECT_PROBES: usize = 2;
+pub const GOSSIP_SUSPICION_TIMEOUT: Duration = Duration::from_secs(3);
+pub const GOSSIP_RETRANSMISSIONS: u32 = 4;
+pub const ELECTION_ANSWER_TIMEOUT: Duration = Duration::from_secs(2);
+pub const ELECTION_ANNOUNCEMENT_TIMEOUT: Duration = Duration::from_secs(5);
+pub const MAX_STORED_ELECTIONS: usize = 100;
+pub const TUI_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+pub const MANUAL_OFFER_TIMEOUT: Duration = Duration::from_secs(5);
+pub const CANCELLATION_FEE_PREPARING_PERCENT: u64 = 50;
+pub const CANCELLATION_FEE_READY_PERCENT: u64 = 80;
+pub const DEREGISTER_WAIT: Duration = Duration::from_millis(200);
+pub const COURIER_BLOCKS_PER_SECOND: f32 = 1.0;
+/// Speed of a bike, relative to [`COURIER_BLOCKS_PER_SECOND`] (the speed of a moto).
+pub const BIKE_SPEED_FACTOR: f32 = 0.6;
+/// Speed of a car, relative to [`COURIER_BLOCKS_PER_SECOND`] (the speed of a moto).
+pub const CAR_SPEED_FACTOR: f32 = 1.3;
+/// Units of dishes a courier carries in each trip, by vehicle.
+pub const BIKE_MAX_UNITS: u32 = 4;
+pub const MOTO_MAX_UNITS: u32 = 10;
+pub const CAR_MAX_UNITS: u32 = 40;
+/// Longest trips, in blocks, for which a bike or a moto is the best suited vehicle. Longer
+/// trips suit a car.
+pub const BIKE_MAX_TRIP_BLOCKS: f32 = 6.0;
+pub const MOTO_MAX_TRIP_BLOCKS: f32 = 14.0;
+pub const TRAFFIC_MULTIPLIER: f32 = 1.0;
+pub const UNASSIGNED_ORDER_ID: u64 = 0;
+pub const HLC_ID_LOGICAL_BITS: u32 = 20;
@@ -0,0 +1,263 @@
+use crate::constants::{HEALTH_POLL_INTERVAL, HEALTH_PORT_OFFSET, HEALTH_REQUEST_TIMEOUT};
+use actix::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{Instant, sleep, timeout};
+
+/// Environment variable with the address of the health endpoint of a process, or `off` to
+/// disable it. By default it listens on the address of the process, [`HEALTH_PORT_OFFSET`]
+/// ports above.
+pub const HEALTH_ENV_VAR: &str = "HEALTH_ENDPOINT";
+
+/// Returns the address the health endpoint of a process listening on `addr` listens on, or
+/// `None` if [`HEALTH_ENV_VAR`] disables it.
+pub fn addr_from_env(addr: SocketAddr) -> Option<SocketAddr> {
+    match std::env::var(HEALTH_ENV_VAR) {
+        Ok(value) if value.trim().eq_ignore_ascii_case("off") => None,
+        Ok(value) if !value.trim().is_empty() => match value.trim().parse() {
+            Ok(health_addr) => Some(health_addr),
+            Err(_) => {
+                eprintln!("[Health] Ignoring invalid {}: '{}'", HEALTH_ENV_VAR, value);
+                Some(default_addr(addr))
+            }
+        },
+        _ => Some(default_addr(addr)),
+    }
+}
+
+/// Returns the default address of the health endpoint of a process listening on `addr`.
+pub fn default_addr(addr: SocketAddr) -> SocketAddr {
+    let mut health_addr = addr;
+    health_addr.set_port(addr.port() + HEALTH_PORT_OFFSET);
+    health_addr
+}
+
+/// What the health endpoint of a process answers.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct HealthReport {
+    /// Kind of process: `server` or `payment`.
+    pub service: String,
+    /// ID of the process, e.g. the ID of the coordinator.
+    pub node_id: String,
+    /// Whether the process can take requests. A live process that is not ready answers with
+    /// `503 Service Unavailable`.
+    pub ready: bool,
+    /// Role of the process: `leader`, `follower` or `electing` for a server.
+    pub role: String,
+    /// Index of the last entry of the storage log, if the process keeps one.
+    #[serde(default)]
+    pub log_index: Option<u64>,
+    /// On a leader, entries of the log the slowest follower has not applied yet.
+    #[serde(default)]
+    pub log_lag: Option<u64>,
+    /// On a follower, milliseconds since its replica last caught up with the ring.
+    #[serde(default)]
+    pub replica_age_millis: Option<u64>,
+    /// Number of open connections, by kind of peer.
+    #[serde(default)]
+    pub connections: BTreeMap<String, usize>,
+    /// Connections accepted, refused and still open since the process started, by kind of
+    /// peer, if the process accepts connections.
+    #[serde(default)]
+    pub accepted: BTreeMap<String, AcceptStats>,
+}
+
+/// Counters of the connections a process accepted from one kind of peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct AcceptStats {
+    /// Connections accepted.
+    pub accepted: u64,
+    /// Connections refused because the process had too many open.
+    pub rejected: u64,
+    /// Accepted connections still open.
+    pub active: usize,
+}
+
+/// Message to get the [`HealthReport`] of a process.
+///
+/// ## Returns
+/// - [`HealthReport`]: The current health of the process.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "HealthReport")]
+pub struct GetHealth;
+
+/// Serves the health endpoint on `addr` until the actix system stops.
+///
+/// - `GET /health/live` always answers `200 OK`: the process is running.
+/// - `GET /health` answers `200 OK` if the process is ready and `503 Service Unavailable`
+///   otherwise.
+///
+/// Both return the [`HealthReport`] built by `report` as JSON.
+///
+/// ## Arguments
+/// * `addr` - The address to listen on.
+/// * `report` - Builds the report of each request.
+pub async fn serve<F, Fut>(addr: SocketAddr, report: F) -> io::Result<()>
+where
+    F: Fn() -> Fut + 'static,
+    Fut: Future<Output = HealthReport> + 'static,
+{
+    let listener = TcpListener::bind(addr).await?;
+    actix::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            // Cada pedido arma su propio reporte, sin bloquear al resto
+            actix::spawn(answer(stream, report()));
+        }
+    });
+    Ok(())
+}
+
+/// Answers one request of the health endpoint.
+async fn answer(mut stream: TcpStream, report: impl Future<Output = HealthReport>) {
+    let Ok(Some(path)) = timeout(HEALTH_REQUEST_TIMEOUT, read_path(&mut stream)).await else {
+        return;
+    };
+    let (status, body) = match path.as_str() {
+        "/health" | "/health/live" => {
+            let report = report.await;
+            let status = if report.ready || path == "/health/live" {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            (status, serde_json::to_string(&report).unwrap_or_default())
+        }
+        _ => ("404 Not Found", String::new()),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Reads the head of an HTTP request and returns its path, if it is a `GET`.
+async fn read_path(stream: &mut TcpStream) -> Option<String> {
+    let mut head = Vec::new();
+    let mut buffer = [0u8; 512];
+    // Sólo interesa la primera línea, pero se lee la cabecera entera antes de responder
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < 8192 {
+        let read = stream.read(&mut buffer).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buffer[..read]);
+    }
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next()?.split_whitespace();
+    match (request_line.next()?, request_line.next()?) {
+        ("GET", path) => Some(path.split('?').next().unwrap_or(path).to_string()),
+        _ => None,
+    }
+}
+
+/// Asks the health endpoint at `addr` for its report, ready or not.
+pub async fn check(addr: SocketAddr) -> io::Result<HealthReport> {
+    let request = async {
+        let mut stream = TcpStream::connect(addr).await?;
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        let (_, body) = response
+            .split_once("\r\n\r\n")
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Invalid response"))?;
+        serde_json::from_str(body).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    };
+    timeout(HEALTH_REQUEST_TIMEOUT, request)
+        .await
+        .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "Health check timed out"))?
+}
+
+/// Waits until the health endpoint of one of `addrs` reports a ready process accepted by
+/// `accept`, e.g. a ready leader.
+///
+/// ## Returns
+/// The first report accepted, or an error if none was within `wait`.
+pub async fn wait_until(
+    addrs: &[SocketAddr],
+    wait: Duration,
+    accept: impl Fn(&HealthReport) -> bool,
+) -> io::Result<HealthReport> {
+    let deadline = Instant::now() + wait;
+    loop {
+        for addr in addrs {
+            if let Ok(report) = check(*addr).await
+                && report.ready
+                && accept(&report)
+            {
+                return Ok(report);
+            }
+        }
+        if Instant::now() >= deadline {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "No process became ready in time",
+            ));
+        }
+        sleep(HEALTH_POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    fn free_addr() -> SocketAddr {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn answers_whether_the_process_is_ready() {
+        let addr = free_addr();
+        let ready = Rc::new(Cell::new(false));
+        let flag = ready.clone();
+        serve(addr, move || {
+            let ready = flag.get();
+            async move {
+                HealthReport {
+                    service: "server".to_string(),
+                    node_id: "server_0".to_string(),
+                    ready,
+                    role: "leader".to_string(),
+                    log_index: Some(3),
+                    ..HealthReport::default()
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        let report = check(addr).await.unwrap();
+        assert!(!report.ready);
+        assert_eq!(report.log_index, Some(3));
+        assert!(
+            wait_until(&[addr], Duration::from_millis(300), |_| true)
+                .await
+                .is_err()
+        );
+
+        ready.set(true);
+        let report = wait_until(&[addr], Duration::from_secs(2), |report| {
+            report.role == "leader"
+        })
+        .await
+        .unwrap();
+        assert_eq!(report.node_id, "server_0");
+    }
+}
@@ -0,0 +1,124 @@
+use crate::constants::DEFAULT_IDENTITY_DIR;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Environment variable with the directory where the users keep their identity files.
+/// [`DEFAULT_IDENTITY_DIR`] is used if it is not set, and `off` makes the users register
+/// without a secret, as anyone can.
+pub const IDENTITY_DIR_ENV_VAR: &str = "IDENTITY_DIR";
+
+/// Returns the directory selected by [`IDENTITY_DIR_ENV_VAR`], or `None` if the identities
+/// are not kept.
+pub fn dir_from_env() -> Option<PathBuf> {
+    match std::env::var(IDENTITY_DIR_ENV_VAR) {
+        Ok(value) if value.trim().eq_ignore_ascii_case("off") => None,
+        Ok(value) if !value.trim().is_empty() => Some(PathBuf::from(value.trim())),
+        _ => Some(PathBuf::from(DEFAULT_IDENTITY_DIR)),
+    }
+}
+
+/// The identity a client, restaurant or delivery registers with.
+///
+/// It is kept in a JSON file created on the first run, so a restarted binary registers as the
+/// same user and recovers its state. The leader remembers the secret of the first
+/// registration of each ID and rejects any later one without it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Identity {
+    /// ID of the user.
+    pub user_id: String,
+    /// Secret proving the ownership of the ID, or `None` if the identity is not kept.
+    pub secret: Option<String>,
+}
+
+impl Identity {
+    /// Creates an identity without a secret, which is not kept anywhere.
+    pub fn anonymous(user_id: String) -> Self {
+        Self {
+            user_id,
+            secret: None,
+        }
+    }
+
+    /// Reads the identity kept at `path`, or creates a new one with a random secret and keeps
+    /// it there.
+    ///
+    /// ## Arguments
+    /// * `path` - The identity file.
+    /// * `user_id` - The ID asked for, if any. It must be the one in the file, if it exists.
+    /// * `new_id` - Returns the ID of a new identity if none was asked for.
+    pub fn load_or_create(
+        path: &Path,
+        user_id: Option<&str>,
+        new_id: impl FnOnce() -> String,
+    ) -> io::Result<Self> {
+        if path.exists() {
+            let identity: Identity = serde_json::from_str(&fs::read_to_string(path)?)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+            if let Some(user_id) = user_id
+                && user_id != identity.user_id
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "{} is the identity of {}, not of {}",
+                        path.display(),
+                        identity.user_id,
+                        user_id
+                    ),
+                ));
+            }
+            return Ok(identity);
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let identity = Identity {
+            user_id: user_id.map_or_else(new_id, str::to_string),
+            secret: Some(format!("{:032x}", rand::random::<u128>())),
+        };
+        let mut options = OpenOptions::new();
+        options.write(true).create_new(true);
+        // Sólo el dueño puede leer el secreto
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let mut file = options.open(path)?;
+        file.write_all(serde_json::to_string_pretty(&identity)?.as_bytes())?;
+        Ok(identity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("identity_{}_{}", name, rand::random::<u64>()))
+            .join("user.json")
+    }
+
+    #[test]
+    fn keeps_the_identity_between_runs() {
+        let path = temp_file("runs");
+        let first = Identity::load_or_create(&path, None, || "client_1".to_string()).unwrap();
+        assert_eq!(first.user_id, "client_1");
+        assert!(first.secret.is_some());
+
+        let again = Identity::load_or_create(&path, None, || "client_2".to_string()).unwrap();
+        assert_eq!(again, first);
+        let by_id = Identity::load_or_create(&path, Some("client_1"), String::new).unwrap();
+        assert_eq!(by_id, first);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn rejects_the_identity_file_of_another_user() {
+        let path = temp_file("other");
+        Identity::load_or_create(&path, Some("resto_1"), String::new).unwrap();
+        let other = Identity::load_or_create(&path, Some("resto_2"), String::new);
+        assert_eq!(other.unwrap_err().kind(), io::ErrorKind::InvalidData);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
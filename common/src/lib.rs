@@ -1,7 +1,13 @@
 pub mod bimap;
+pub mod cli;
 pub mod constants;
+pub mod health;
+pub mod identity;
 pub mod logger;
 pub mod messages;
 pub mod network;
+pub mod peer;
+pub mod spatial_index;
+pub mod telemetry;
 pub mod types;
 pub mod utils;
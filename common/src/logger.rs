@@ -1,5 +1,12 @@
 use chrono::Local;
 use colored::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Whether every logger of the process is silenced, e.g. while a full-screen UI is shown.
+static MUTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the loggers log through `tracing`, inside the span of the message being handled.
+static TRACING: AtomicBool = AtomicBool::new(false);
 
 #[derive(Debug, Clone)]
 pub struct Logger {
@@ -15,11 +22,37 @@ impl Logger {
         }
     }
 
+    /// Silences (or restores) every logger of the process.
+    pub fn set_muted(muted: bool) {
+        MUTED.store(muted, Ordering::Relaxed);
+    }
+
+    fn is_muted() -> bool {
+        MUTED.load(Ordering::Relaxed)
+    }
+
+    /// Makes every logger of the process log through `tracing` (or print again), see
+    /// [`crate::telemetry::init`].
+    pub fn set_tracing(tracing: bool) {
+        TRACING.store(tracing, Ordering::Relaxed);
+    }
+
+    fn is_tracing() -> bool {
+        TRACING.load(Ordering::Relaxed)
+    }
+
     fn timestamp() -> String {
         Local::now().format("%H:%M:%S").to_string()
     }
 
     pub fn info(&self, msg: impl AsRef<str>) {
+        if Self::is_muted() {
+            return;
+        }
+        if Self::is_tracing() {
+            tracing::info!(logger = %self.name, "{}", msg.as_ref());
+            return;
+        }
         println!(
             "{} {} {}",
             format!("[{}][INFO][{}]", Self::timestamp(), self.name)
@@ -31,6 +64,13 @@ impl Logger {
     }
 
     pub fn warn(&self, msg: impl AsRef<str>) {
+        if Self::is_muted() {
+            return;
+        }
+        if Self::is_tracing() {
+            tracing::warn!(logger = %self.name, "{}", msg.as_ref());
+            return;
+        }
         println!(
             "{} {} {}",
             format!("[{}][WARN][{}]", Self::timestamp(), self.name)
@@ -42,6 +82,13 @@ impl Logger {
     }
 
     pub fn error(&self, msg: impl AsRef<str>) {
+        if Self::is_muted() {
+            return;
+        }
+        if Self::is_tracing() {
+            tracing::error!(logger = %self.name, "{}", msg.as_ref());
+            return;
+        }
         eprintln!(
             "{} {} {}",
             format!("[{}][ERROR][{}]", Self::timestamp(), self.name)
@@ -1,4 +1,5 @@
-use crate::types::dtos::{ClientDTO, OrderDTO};
+use crate::types::dtos::{CancellationFee, ClientDTO, OrderDTO};
+use crate::types::restaurant_filter::RestaurantFilter;
 use actix::Message;
 use serde::{Deserialize, Serialize};
 
@@ -10,10 +11,19 @@ use serde::{Deserialize, Serialize};
 ///
 /// ## Contents
 /// - `order`: The [`OrderDTO`] containing all relevant information about the order being placed.
+/// - `promo_code`: The promo code the client wants to use, if any. An order with a code that
+///   cannot be used is rejected with the `InvalidPromoCode` status.
+/// - `request_id`: A number chosen by the client, which comes back in the first
+///   [`NotifyOrderUpdated`](crate::messages::NotifyOrderUpdated) of the order so the client can
+///   learn the ID the leader gave it. The `order_id` sent by the client is ignored.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RequestThisOrder {
     pub order: OrderDTO,
+    #[serde(default)]
+    pub promo_code: Option<String>,
+    #[serde(default)]
+    pub request_id: u64,
 }
 
 /// Message sent by a client to request a list of nearby restaurants.
@@ -24,10 +34,95 @@ pub struct RequestThisOrder {
 ///
 /// ## Contents
 /// - `client`: The [`ClientDTO`] containing the client's information and location.
+/// - `radius`: The maximum distance to the restaurants, in blocks. If `None`, the default
+///   radius is used and every restaurant is returned when none is that close.
+/// - `filter`: The [`RestaurantFilter`] choosing which restaurants are listed and in which
+///   order. By default, the open ones, closest first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RequestNearbyRestaurants {
     pub client: ClientDTO,
+    #[serde(default)]
+    pub radius: Option<f32>,
+    #[serde(default)]
+    pub filter: RestaurantFilter,
+}
+
+/// Message sent by a client to ask for the current state of one of its orders.
+///
+/// ## Purpose
+/// Read-only query that a follower coordinator can answer from its replicated storage,
+/// replying with a [`NotifyOrderUpdated`](crate::messages::NotifyOrderUpdated), also for
+/// recently finished or cancelled orders, or with an
+/// [`OrderNotFound`](crate::messages::OrderNotFound).
+///
+/// ## Contents
+/// - `client_id`: The ID of the client that placed the order.
+/// - `order_id`: The ID of the order.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct RequestOrderStatus {
+    pub client_id: String,
+    pub order_id: u64,
+}
+
+/// Message sent by a client to cancel one of its orders.
+///
+/// ## Purpose
+/// Lets the user back out of an order while it has not left the restaurant. The leader
+/// checks the stored status of the order: if it is still before
+/// [`Delivering`](crate::types::order_status::OrderStatus::Delivering) the order is cancelled,
+/// the payment refunded and the restaurant and courier notified. Otherwise the client gets
+/// a [`NotifyOrderUpdated`](crate::messages::NotifyOrderUpdated) with the current status.
+///
+/// Once the kitchen started the order, part of the price is kept as a
+/// [`CancellationFee`] and the client is told the breakdown with a [`CancellationCharged`].
+///
+/// ## Contents
+/// - `client_id`: The ID of the client that placed the order.
+/// - `order_id`: The ID of the order to cancel.
+/// - `waive_fee`: Whether the whole price is refunded regardless of the status, set when
+///   the cluster cancels an order it got stuck with.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct CancelMyOrder {
+    pub client_id: String,
+    pub order_id: u64,
+    #[serde(default)]
+    pub waive_fee: bool,
+}
+
+/// Message sent to tell the client how much of a cancelled order is refunded.
+///
+/// ## Purpose
+/// Answers a [`CancelMyOrder`] with the fee kept for the restaurant, which depends on how far
+/// the kitchen got with the order.
+///
+/// ## Contents
+/// - `client_id`: The ID of the client that cancelled the order.
+/// - `fee`: The [`CancellationFee`] with the price, the fee and the stage of the order.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct CancellationCharged {
+    pub client_id: String,
+    pub fee: CancellationFee,
+}
+
+/// Message sent by a client to acknowledge the order updates it received.
+///
+/// ## Purpose
+/// The storage keeps every [`NotifyOrderUpdated`](crate::messages::NotifyOrderUpdated) sent
+/// to a client in its outbox until the client acknowledges it, and replays the ones left
+/// when the client registers again (e.g. after a leader failover).
+///
+/// ## Contents
+/// - `client_id`: The ID of the client.
+/// - `update_id`: The ID of the last update received. The older ones are acknowledged too.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct AckOrderUpdate {
+    pub client_id: String,
+    pub update_id: u64,
 }
 
 /// Message sent to notify the client that their order has been finalized.
@@ -59,3 +154,22 @@ pub struct DeliveryExpectedTime {
     pub order: OrderDTO,
     pub expected_time: u64, // in seconds
 }
+
+/// Message sent to tell the client that no delivery took its order yet and that the server is
+/// offering it again.
+///
+/// ## Purpose
+/// Keeps the client informed while its order waits for a delivery, instead of leaving it
+/// waiting until the order is delivered or cancelled.
+///
+/// ## Contents
+/// - `order`: The [`OrderDTO`] waiting for a delivery.
+/// - `attempt`: How many times the order was offered again, including this one.
+/// - `max_attempts`: How many times the order is offered again before it is cancelled.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct StillLookingForDelivery {
+    pub order: OrderDTO,
+    pub attempt: u32,
+    pub max_attempts: u32,
+}
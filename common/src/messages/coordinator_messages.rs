@@ -1,7 +1,10 @@
-use crate::types::dtos::{ClientDTO, DeliveryDTO, OrderDTO};
+use crate::types::decline_reason::DeclineReason;
+use crate::types::dtos::{ClientDTO, DeliveryDTO, EarningsReport, OfferDetails, OrderDTO};
+use crate::types::order_status::OrderStatus;
 use crate::types::restaurant_info::RestaurantInfo;
 use actix::Message;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 /// Message sent to a client with a list of nearby restaurants.
 ///
@@ -10,12 +13,20 @@ use serde::{Deserialize, Serialize};
 ///
 /// ## Contents
 /// - `client`: The [`ClientDTO`] representing the client who made the request.
-/// - `restaurants`: A list of [`RestaurantInfo`] objects with details of each nearby restaurant.
+/// - `restaurants`: A list of [`RestaurantInfo`] objects with details of each nearby restaurant,
+///   in the order asked by the client.
+/// - `distances`: The distance from the client to each restaurant, in blocks, by restaurant ID.
+/// - `closed`: The IDs of the restaurants listed that are outside their opening hours, only
+///   listed if the client asked for them.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct NearbyRestaurants {
     pub client: ClientDTO,
     pub restaurants: Vec<RestaurantInfo>,
+    #[serde(default)]
+    pub distances: HashMap<String, f32>,
+    #[serde(default)]
+    pub closed: Vec<String>,
 }
 
 /// Message sent to notify a peer (client, restaurant, or delivery) that an order has been updated.
@@ -26,11 +37,60 @@ pub struct NearbyRestaurants {
 /// ## Contents
 /// - `peer_id`: The ID of the peer to notify.
 /// - `order`: The updated [`OrderDTO`] for the order.
+/// - `update_id`: The ID of the update in the outbox of the peer, which the peer acknowledges
+///   with an [`AckOrderUpdate`](crate::messages::AckOrderUpdate), or `0` if the update is not
+///   kept (e.g. the answer to a [`RequestOrderStatus`](crate::messages::RequestOrderStatus)).
+/// - `request_id`: In the first update of an order, the `request_id` of the
+///   [`RequestThisOrder`](crate::messages::RequestThisOrder) that placed it, which tells the
+///   client the ID the leader gave the order; `0` in every other update.
+/// - `decline`: If the order became `Unauthorized`, why its payment was declined, so the
+///   client can tell whether placing it again makes sense; `None` in every other update.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct NotifyOrderUpdated {
     pub peer_id: String,
     pub order: OrderDTO,
+    #[serde(default)]
+    pub update_id: u64,
+    #[serde(default)]
+    pub request_id: u64,
+    #[serde(default)]
+    pub decline: Option<DeclineReason>,
+}
+
+/// Message sent to a client that asked for the state of an order the servers do not know.
+///
+/// ## Purpose
+/// Answers a [`RequestOrderStatus`](crate::messages::RequestOrderStatus) for an order that
+/// does not exist, belongs to another client, or was removed so long ago that its last state
+/// is no longer kept, so the client does not have to wait for an answer that never comes.
+///
+/// ## Contents
+/// - `client_id`: The ID of the client that asked.
+/// - `order_id`: The ID of the order it asked for.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct OrderNotFound {
+    pub client_id: String,
+    pub order_id: u64,
+}
+
+/// Message sent to a restaurant or delivery whose status update was rejected.
+///
+/// ## Purpose
+/// Used by the coordinator when a peer reports a status the order cannot move to from the one
+/// it is in (e.g. `Delivered` before `Preparing`), so the peer can catch up with the order.
+///
+/// ## Contents
+/// - `peer_id`: The ID of the peer that sent the update.
+/// - `order`: The [`OrderDTO`] as the servers know it.
+/// - `rejected_status`: The status the peer sent.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct StatusRejected {
+    pub peer_id: String,
+    pub order: OrderDTO,
+    pub rejected_status: OrderStatus,
 }
 
 /// Message sent to a restaurant to notify about a new order.
@@ -68,10 +128,13 @@ pub struct DeliveryAvailable {
 ///
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order to be delivered.
+/// - `details`: Where the order is picked up, how far the trip is and what it pays, if known.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct NewOfferToDeliver {
     pub order: OrderDTO,
+    #[serde(default)]
+    pub details: Option<OfferDetails>,
 }
 
 /// Message sent to a delivery agent to indicate their services are not needed for an order.
@@ -96,10 +159,24 @@ pub struct DeliveryNoNeeded {
 ///
 /// ## Contents
 /// - `order`: The [`OrderDTO`] for which delivery agents are being proposed.
-/// - `deliveries`: A list of [`DeliveryDTO`] objects representing available delivery agents.
+/// - `deliveries`: The available delivery agents, each with the [`OfferDetails`] of the order
+///   computed from its position.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct NearbyDeliveries {
     pub order: OrderDTO,
-    pub deliveries: Vec<DeliveryDTO>,
+    pub deliveries: Vec<(DeliveryDTO, OfferDetails)>,
+}
+
+/// Message sent to a delivery agent with what it earned.
+///
+/// ## Purpose
+/// Used by the coordinator to answer a [`GetMyEarnings`](crate::messages::GetMyEarnings).
+///
+/// ## Contents
+/// - `report`: The [`EarningsReport`] of the delivery agent.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct MyEarnings {
+    pub report: EarningsReport,
 }
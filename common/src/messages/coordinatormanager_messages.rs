@@ -1,5 +1,8 @@
 use crate::messages::StorageLogMessage;
+use crate::messages::shared_messages::NetworkMessage;
+use crate::network::peer_types::PeerType;
 use crate::types::dtos::Snapshot;
+use crate::types::storage_entity::{StorageDigest, StorageEntity};
 use actix::Message;
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
@@ -14,11 +17,15 @@ use std::net::SocketAddr;
 /// ## Contents
 /// - `coordinator_id`: The ID of the requesting coordinator.
 /// - `start_index`: The index from which to start sending updates.
+/// - `applied_index`: The last index applied by the requesting coordinator, confirming every
+///   earlier entry so that it can be compacted. `None` if the requester does not report it.
 #[derive(Serialize, Deserialize, Debug, Message, Clone)]
 #[rtype(result = "()")]
 pub struct RequestNewStorageUpdates {
     pub coordinator_id: String,
     pub start_index: u64,
+    #[serde(default)]
+    pub applied_index: Option<u64>,
 }
 
 /// Message sent to provide storage updates to a coordinator.
@@ -28,25 +35,89 @@ pub struct RequestNewStorageUpdates {
 ///
 /// ## Contents
 /// - `updates`: A map of log indices to [`StorageLogMessage`]s.
+/// - `coordinator_id`: The ID of the coordinator sending the updates.
+/// - `epoch`: The leader epoch of the sender, used to fence stale leaders.
 #[serde_as]
 #[derive(Serialize, Deserialize, Debug, Message, Clone)]
 #[rtype(result = "()")]
 pub struct StorageUpdates {
     #[serde_as(as = "HashMap<DisplayFromStr, _>")]
     pub updates: HashMap<u64, StorageLogMessage>,
+    #[serde(default)]
+    pub coordinator_id: String,
+    #[serde(default)]
+    pub epoch: u64,
 }
 
 /// Message sent to request all storage data from the coordinator manager.
 ///
 /// ## Purpose
-/// Used by a coordinator to request a full snapshot of the storage.
+/// Used by a coordinator to request a full snapshot of the storage, or to resume an
+/// interrupted snapshot transfer.
 ///
 /// ## Contents
 /// - `coordinator_id`: The ID of the requesting coordinator.
+/// - `snapshot_id`: The snapshot being resumed, if any. A fresh snapshot is sent if the
+///   leader no longer has it.
+/// - `from_seq`: The first [`SnapshotChunk`] still missing.
 #[derive(Serialize, Deserialize, Debug, Message, Clone)]
 #[rtype(result = "()")]
 pub struct RequestAllStorage {
     pub coordinator_id: String,
+    #[serde(default)]
+    pub snapshot_id: Option<u64>,
+    #[serde(default)]
+    pub from_seq: u32,
+}
+
+/// Message sent to request only some kinds of entities of the storage.
+///
+/// ## Purpose
+/// Used by a coordinator that only needs to recover part of its storage, e.g. its orders, so
+/// that the leader does not transfer the whole of it. The snapshot is sent in
+/// [`SnapshotChunk`]s like a full one, and merged the same way.
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the requesting coordinator.
+/// - `entities`: The kinds of entities to transfer.
+/// - `snapshot_id`: The snapshot being resumed, if any. A fresh snapshot is sent if the
+///   leader no longer has it.
+/// - `from_seq`: The first [`SnapshotChunk`] still missing.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct RequestStorageSubset {
+    pub coordinator_id: String,
+    pub entities: Vec<StorageEntity>,
+    #[serde(default)]
+    pub snapshot_id: Option<u64>,
+    #[serde(default)]
+    pub from_seq: u32,
+}
+
+/// Message sent with one piece of a storage snapshot.
+///
+/// ## Purpose
+/// Used by the leader to stream a [`Snapshot`] in pieces, so that an interrupted transfer
+/// can be resumed instead of restarted.
+///
+/// ## Contents
+/// - `snapshot_id`: Identifies the snapshot the chunk belongs to.
+/// - `seq`: Position of the chunk, starting at 0.
+/// - `total`: Number of chunks of the snapshot.
+/// - `payload`: A slice of the snapshot serialized as JSON.
+/// - `coordinator_id`: The ID of the coordinator sending the snapshot.
+/// - `epoch`: The leader epoch of the sender, used to fence stale leaders.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct SnapshotChunk {
+    pub snapshot_id: u64,
+    pub seq: u32,
+    pub total: u32,
+    pub payload: String,
+    #[serde(default)]
+    pub coordinator_id: String,
+    #[serde(default)]
+    pub epoch: u64,
 }
 
 /// Message sent to provide a snapshot of storage.
@@ -56,10 +127,59 @@ pub struct RequestAllStorage {
 ///
 /// ## Contents
 /// - `snapshot`: The [`Snapshot`] representing the current storage state.
+/// - `coordinator_id`: The ID of the coordinator sending the snapshot.
+/// - `epoch`: The leader epoch of the sender, used to fence stale leaders.
 #[derive(Serialize, Deserialize, Debug, Message, Clone)]
 #[rtype(result = "()")]
 pub struct StorageSnapshot {
-    pub snapshot: Snapshot,
+    pub snapshot: Box<Snapshot>,
+    #[serde(default)]
+    pub coordinator_id: String,
+    #[serde(default)]
+    pub epoch: u64,
+}
+
+/// Message sent by a follower to another random follower, to check that their storages did
+/// not diverge.
+///
+/// ## Purpose
+/// Followers pull the log from their predecessor in the ring, so a change lost on the way
+/// would only be noticed along that chain. Comparing digests between any two followers
+/// finds the divergence regardless of their position in the ring.
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the follower sending the digest.
+/// - `epoch`: The leader epoch known by the sender, used to fence stale coordinators.
+/// - `digest`: The [`StorageDigest`] of the storage of the sender.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct AntiEntropyDigest {
+    pub coordinator_id: String,
+    pub epoch: u64,
+    pub digest: StorageDigest,
+}
+
+/// Message sent between two followers whose digests differ, with the kinds of entities in
+/// which they diverged.
+///
+/// ## Purpose
+/// Each follower merges the entries of the other one, keeping the ones changed last, so
+/// that both end up with the same entries.
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the follower sending the entries.
+/// - `epoch`: The leader epoch known by the sender, used to fence stale coordinators.
+/// - `snapshot`: A [`Snapshot`] with only the kinds of entities that differ.
+/// - `entities`: The kinds of entities in the snapshot.
+/// - `reply`: Whether the receiver must answer with its own entries of those kinds.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct AntiEntropyRepair {
+    pub coordinator_id: String,
+    pub epoch: u64,
+    pub snapshot: Box<Snapshot>,
+    pub entities: Vec<StorageEntity>,
+    pub reply: bool,
 }
 
 /// Message sent to request recovery of storage operations.
@@ -130,14 +250,143 @@ pub struct Pong {
 /// ## Contents
 /// - `initiator`: The ID of the node initiating the election.
 /// - `candidates`: A list of candidate node IDs.
+/// - `epoch`: The highest leader epoch known by the nodes the election went through. The
+///   elected leader gets the next one.
 #[derive(Serialize, Deserialize, Debug, Message, Clone)]
 #[rtype(result = "()")]
 pub struct LeaderElection {
     pub initiator: String,
     pub candidates: Vec<String>,
+    #[serde(default)]
+    pub epoch: u64,
+}
+
+/// Message of the bully leader election.
+///
+/// ## Purpose
+/// Used by coordinators to elect the most senior coordinator reachable (the one with the
+/// lowest ID) when the bully election strategy is selected.
+///
+/// ## Variants
+/// - `Election`: `from` asks the coordinators senior to it whether they are alive, and
+///   becomes the leader if none answers. `epoch` is the highest leader epoch it knows.
+/// - `Alive`: `from`, senior to the receiver, is alive and takes the election over.
+#[derive(Serialize, Deserialize, Debug, Message, Clone, PartialEq, Eq)]
+#[rtype(result = "()")]
+pub enum BullyElection {
+    Election { from: String, epoch: u64 },
+    Alive { from: String },
 }
 
 /// Message sent to check for pong timeout (internal use).
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct CheckPongTimeout;
+
+/// Message sent by the leader to the coordinator it hands the leadership off to.
+///
+/// ## Purpose
+/// The coordinator pulls the log from the leader instead of from the previous coordinator of
+/// the ring, so that the leader sees how far it applied it, until a new leader is announced.
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the leader.
+/// - `epoch`: The leader epoch of the sender, used to fence stale leaders.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct PrepareLeadership {
+    pub coordinator_id: String,
+    pub epoch: u64,
+}
+
+/// Message sent by the leader to replicate a storage write before acknowledging it.
+///
+/// ## Purpose
+/// Used in quorum write mode: each follower applies the write and answers with a [`WriteAck`].
+///
+/// ## Contents
+/// - `write_id`: Identifies the write on the leader.
+/// - `coordinator_id`: The ID of the leader sending the write.
+/// - `index`: The index of the write in the log of the leader, where followers log it too.
+/// - `update`: The [`StorageLogMessage`] to apply.
+/// - `epoch`: The leader epoch of the sender, used to fence stale leaders.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct ReplicateWrite {
+    pub write_id: u64,
+    pub coordinator_id: String,
+    pub index: u64,
+    pub update: StorageLogMessage,
+    #[serde(default)]
+    pub epoch: u64,
+}
+
+/// Message sent by a follower to confirm a replicated write.
+///
+/// ## Purpose
+/// Counts towards the quorum the leader waits for before acknowledging a write.
+///
+/// ## Contents
+/// - `write_id`: The write being confirmed.
+/// - `coordinator_id`: The ID of the confirming coordinator.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct WriteAck {
+    pub write_id: u64,
+    pub coordinator_id: String,
+}
+
+/// Message sent by a follower to hand a user message over to the leader.
+///
+/// ## Purpose
+/// Followers only answer read-only queries; mutations (and reads on a stale replica) are
+/// forwarded to the leader, which answers over its own connection with the user.
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the follower forwarding the message.
+/// - `message`: The original [`NetworkMessage`].
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct ForwardToLeader {
+    pub coordinator_id: String,
+    pub message: Box<NetworkMessage>,
+}
+
+/// Message sent by the leader to hand a message over to a user connected to a follower.
+///
+/// ## Purpose
+/// With user proxying, users stay connected to any coordinator. The leader sends what it has
+/// for them to the follower they are connected to, which passes it on over its connection.
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the follower the user is connected to.
+/// - `user_id`: The ID of the user.
+/// - `message`: The [`NetworkMessage`] for the user.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct ForwardToUser {
+    pub coordinator_id: String,
+    pub user_id: String,
+    pub message: Box<NetworkMessage>,
+}
+
+/// Message sent by a follower to tell the leader which users are connected through it.
+///
+/// ## Purpose
+/// Lets the leader route the messages for a user to the follower it is connected to. The
+/// follower sends it before forwarding the registration of a user, for every user connected
+/// through it when a new leader is elected, and when a user disconnects.
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the follower.
+/// - `user_id`: The ID of the user.
+/// - `peer_type`: Whether the user is a client, a restaurant or a delivery.
+/// - `connected`: `false` if the user disconnected from the follower.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct ProxyUser {
+    pub coordinator_id: String,
+    pub user_id: String,
+    pub peer_type: PeerType,
+    pub connected: bool,
+}
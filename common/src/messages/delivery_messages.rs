@@ -77,3 +77,17 @@ pub struct IAmDelivering {
     pub expected_delivery_time: u64,
     pub order: OrderDTO,
 }
+
+/// Message sent by a delivery agent to ask for what it earned.
+///
+/// # Purpose
+/// Read-only query that a follower coordinator can answer from its replicated storage,
+/// replying with a [`MyEarnings`](crate::messages::MyEarnings).
+///
+/// # Contents
+/// - `delivery_id`: The ID of the delivery agent.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct GetMyEarnings {
+    pub delivery_id: String,
+}
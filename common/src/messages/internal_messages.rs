@@ -1,13 +1,25 @@
+use crate::messages::coordinator_messages::{DeliveryAvailable, NotifyOrderUpdated};
 use crate::types::delivery_status::DeliveryStatus;
+use crate::types::dtos::CancellationFee;
 use crate::types::dtos::ClientDTO;
 use crate::types::dtos::DeliveryDTO;
+use crate::types::dtos::EarningDTO;
+use crate::types::dtos::EarningsReport;
+use crate::types::dtos::ElectionRecord;
 use crate::types::dtos::OrderDTO;
+use crate::types::dtos::OrderRequest;
+use crate::types::dtos::PromoRedemption;
 use crate::types::dtos::RestaurantDTO;
+use crate::types::hlc::HlcTimestamp;
+use crate::types::order_event::OrderEvent;
 use crate::types::order_status::OrderStatus;
 use crate::types::restaurant_info::RestaurantInfo;
+use crate::types::secret_hash::SecretHash;
+use crate::types::tenant::TenantId;
 use actix::Message;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use strum::IntoStaticStr;
 
 /////////////////////////////////////////////////////////////////////
 // Mensajes del storage
@@ -48,7 +60,7 @@ pub struct SetStorageUpdatesLog {
 ///
 /// ## Contents
 /// Each variant wraps a specific message struct related to storage operations.
-#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[derive(Serialize, Deserialize, Debug, Message, Clone, IntoStaticStr)]
 #[serde(tag = "storage_message")]
 #[rtype(result = "()")]
 pub enum StorageLogMessage {
@@ -73,9 +85,59 @@ pub enum StorageLogMessage {
     SetDeliveryToOrder(SetDeliveryToOrder),
     SetOrderStatus(SetOrderStatus),
     SetOrderExpectedTime(SetOrderExpectedTime),
+    SetOrderPreparationTime(SetOrderPreparationTime),
 
     InsertAcceptedDelivery(InsertAcceptedDelivery),
     RemoveAcceptedDeliveries(RemoveAcceptedDeliveries),
+    AddDeliveryEarning(AddDeliveryEarning),
+    SetEarningPaidOut(SetEarningPaidOut),
+    AddCancellationFee(AddCancellationFee),
+    AddPromoRedemption(AddPromoRedemption),
+    AddOutboxPayment(AddOutboxPayment),
+    CompleteOutboxPayment(CompleteOutboxPayment),
+    SetRestaurantOpen(SetRestaurantOpen),
+    SetRestaurantTags(SetRestaurantTags),
+    SetUserOffline(SetUserOffline),
+    TouchUsers(TouchUsers),
+    ClaimUserId(ClaimUserId),
+    ClaimOrderRequest(ClaimOrderRequest),
+    RecordElection(RecordElection),
+
+    /// mensajes con los usuarios
+    QueueOrderUpdate(QueueOrderUpdate),
+    AckOrderUpdates(AckOrderUpdates),
+}
+
+impl StorageLogMessage {
+    /// Returns the name of the operation, as in its `storage_message` tag.
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
+
+    /// Returns the ID of the order the operation changes, if any.
+    pub fn order_id(&self) -> Option<u64> {
+        match self {
+            StorageLogMessage::AddOrder(msg) => Some(msg.order.order_id),
+            StorageLogMessage::RemoveOrder(msg) => Some(msg.order.order_id),
+            StorageLogMessage::AddAuthorizedOrderToRestaurant(msg) => Some(msg.order.order_id),
+            StorageLogMessage::AddPendingOrderToRestaurant(msg) => Some(msg.order.order_id),
+            StorageLogMessage::RemoveAuthorizedOrderToRestaurant(msg) => Some(msg.order.order_id),
+            StorageLogMessage::RemovePendingOrderToRestaurant(msg) => Some(msg.order.order_id),
+            StorageLogMessage::SetCurrentOrderToDelivery(msg) => Some(msg.order.order_id),
+            StorageLogMessage::SetDeliveryToOrder(msg) => Some(msg.order.order_id),
+            StorageLogMessage::SetOrderStatus(msg) => Some(msg.order.order_id),
+            StorageLogMessage::AddDeliveryEarning(msg) => Some(msg.order.order_id),
+            StorageLogMessage::SetOrderExpectedTime(msg) => Some(msg.order_id),
+            StorageLogMessage::SetOrderPreparationTime(msg) => Some(msg.order_id),
+            StorageLogMessage::SetEarningPaidOut(msg) => Some(msg.order_id),
+            StorageLogMessage::InsertAcceptedDelivery(msg) => Some(msg.order_id),
+            StorageLogMessage::RemoveAcceptedDeliveries(msg) => Some(msg.order_id),
+            StorageLogMessage::AddOutboxPayment(msg) => Some(msg.order.order_id),
+            StorageLogMessage::CompleteOutboxPayment(msg) => Some(msg.order.order_id),
+            StorageLogMessage::ClaimOrderRequest(msg) => Some(msg.request.order_id),
+            _ => None,
+        }
+    }
 }
 
 /// Message to add a new client to storage.
@@ -124,10 +186,13 @@ pub struct AddDelivery {
 ///
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order.
+/// - `event`: The [`OrderEvent`] of the change, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct AddOrder {
     pub order: OrderDTO,
+    #[serde(default)]
+    pub event: Option<OrderEvent>,
 }
 
 /// Message to get a client by ID from storage.
@@ -182,6 +247,122 @@ pub struct GetOrder {
     pub order_id: u64,
 }
 
+/// Message to get an order by ID from storage, also if it was removed recently.
+///
+/// ## Purpose
+/// Used to answer what happened to an order, returning the last state of a finished or
+/// cancelled order while its timeline is still kept.
+///
+/// ## Contents
+/// - `order_id`: The ID of the order to retrieve.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Option<OrderDTO>")]
+pub struct GetLastKnownOrder {
+    pub order_id: u64,
+}
+
+/// Message to get the timeline of an order from storage.
+///
+/// ## Purpose
+/// Used to see every change of an order, oldest first, to find where it stalled. The
+/// timeline of a finished order is kept for a while after it is removed.
+///
+/// ## Contents
+/// - `order_id`: The ID of the order.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<OrderEvent>")]
+pub struct GetOrderTimeline {
+    pub order_id: u64,
+}
+
+/// Message to keep an order update in the outbox of its user until it is acknowledged.
+///
+/// ## Purpose
+/// Used so the users get the updates they missed while disconnected when they register
+/// again, even with another leader.
+///
+/// ## Contents
+/// - `update`: The [`NotifyOrderUpdated`] to keep, with the ID set by the storage that keeps
+///   it first.
+///
+/// ## Returns
+/// - [`NotifyOrderUpdated`]: The update with its ID.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "NotifyOrderUpdated")]
+pub struct QueueOrderUpdate {
+    pub update: NotifyOrderUpdated,
+}
+
+/// Message to drop the order updates a user acknowledged from its outbox.
+///
+/// ## Purpose
+/// Used when a user acknowledges the updates it received.
+///
+/// ## Contents
+/// - `user_id`: The ID of the user.
+/// - `update_id`: The ID of the last update acknowledged. The older ones are dropped too.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct AckOrderUpdates {
+    pub user_id: String,
+    pub update_id: u64,
+}
+
+/// Message to get the order updates a user has not acknowledged yet from storage.
+///
+/// ## Purpose
+/// Used to replay the updates a user missed when it registers again.
+///
+/// ## Contents
+/// - `user_id`: The ID of the user.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<NotifyOrderUpdated>")]
+pub struct GetPendingOrderUpdates {
+    pub user_id: String,
+}
+
+/// Message to get the delivery agent that accepted an order from storage.
+///
+/// ## Purpose
+/// Used to notify the delivery agent assigned to an order that is cancelled before it is
+/// picked up.
+///
+/// ## Contents
+/// - `order_id`: The ID of the order.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Option<DeliveryDTO>")]
+pub struct GetAcceptedDelivery {
+    pub order_id: u64,
+}
+
+/// Message to get the orders a delivery agent is carrying or was accepted for.
+///
+/// ## Purpose
+/// Used to reassign the orders of a delivery agent that disconnected.
+///
+/// ## Contents
+/// - `delivery_id`: The ID of the delivery agent.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<OrderDTO>")]
+pub struct GetDeliveryOrders {
+    pub delivery_id: String,
+}
+
+/// Message to get the delivery acceptances whose assignment was not finished yet.
+///
+/// ## Purpose
+/// Used by a new leader to resume the assignments decided by the leaders before it.
+///
+/// ## Contents
+/// - `restaurant_id`: Only the orders of this restaurant, or every order if `None`.
+/// - `before_epoch`: Only the acceptances decided in an older leader epoch.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<DeliveryAvailable>")]
+pub struct GetPendingAssignments {
+    pub restaurant_id: Option<String>,
+    pub before_epoch: u64,
+}
+
 /// Message to remove a user from storage.
 ///
 /// ## Purpose
@@ -202,10 +383,13 @@ pub struct RemoveUser {
 ///
 /// ## Contents
 /// - `client_id`: The ID of the client to remove.
+/// - `time_stamp`: When it was removed, set by the storage that removes it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RemoveClient {
     pub client_id: String,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to remove a restaurant from storage.
@@ -215,10 +399,13 @@ pub struct RemoveClient {
 ///
 /// ## Contents
 /// - `restaurant_id`: The ID of the restaurant to remove.
+/// - `time_stamp`: When it was removed, set by the storage that removes it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RemoveRestaurant {
     pub restaurant_id: String,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to remove a delivery agent from storage.
@@ -228,10 +415,13 @@ pub struct RemoveRestaurant {
 ///
 /// ## Contents
 /// - `delivery_id`: The ID of the delivery agent to remove.
+/// - `time_stamp`: When it was removed, set by the storage that removes it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RemoveDelivery {
     pub delivery_id: String,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to remove an order from storage.
@@ -241,10 +431,13 @@ pub struct RemoveDelivery {
 ///
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order to remove.
+/// - `event`: The [`OrderEvent`] of the change, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RemoveOrder {
     pub order: OrderDTO,
+    #[serde(default)]
+    pub event: Option<OrderEvent>,
 }
 
 /// Message to add an authorized order to a restaurant.
@@ -255,11 +448,14 @@ pub struct RemoveOrder {
 /// ## Contents
 /// - `restaurant_id`: The ID of the restaurant.
 /// - `order`: The [`OrderDTO`] representing the order.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct AddAuthorizedOrderToRestaurant {
     pub restaurant_id: String,
     pub order: OrderDTO,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to add a pending order to a restaurant.
@@ -270,11 +466,14 @@ pub struct AddAuthorizedOrderToRestaurant {
 /// ## Contents
 /// - `restaurant_id`: The ID of the restaurant.
 /// - `order`: The [`OrderDTO`] representing the order.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct AddPendingOrderToRestaurant {
     pub restaurant_id: String,
     pub order: OrderDTO,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to remove an authorized order from a restaurant.
@@ -285,11 +484,14 @@ pub struct AddPendingOrderToRestaurant {
 /// ## Contents
 /// - `restaurant_id`: The ID of the restaurant.
 /// - `order`: The [`OrderDTO`] representing the order.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RemoveAuthorizedOrderToRestaurant {
     pub restaurant_id: String,
     pub order: OrderDTO,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to remove a pending order from a restaurant.
@@ -300,11 +502,14 @@ pub struct RemoveAuthorizedOrderToRestaurant {
 /// ## Contents
 /// - `restaurant_id`: The ID of the restaurant.
 /// - `order`: The [`OrderDTO`] representing the order.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RemovePendingOrderToRestaurant {
     pub restaurant_id: String,
     pub order: OrderDTO,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to get all restaurants from storage.
@@ -323,6 +528,22 @@ pub struct GetRestaurants;
 #[rtype(result = "Vec<RestaurantInfo>")]
 pub struct GetAllRestaurantsInfo;
 
+/// Message to get the restaurants near a position from storage.
+///
+/// ## Purpose
+/// Used to retrieve the restaurants close to a client, looking only at the part of the
+/// spatial index around it.
+///
+/// ## Contents
+/// - `position`: The position to search around.
+/// - `radius`: The maximum distance to `position`.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<RestaurantInfo>")]
+pub struct GetNearbyRestaurants {
+    pub position: (f32, f32),
+    pub radius: f32,
+}
+
 /// Message to set the position of a delivery agent.
 ///
 /// ## Purpose
@@ -331,11 +552,14 @@ pub struct GetAllRestaurantsInfo;
 /// ## Contents
 /// - `delivery_id`: The ID of the delivery agent.
 /// - `position`: The (x, y) position.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct SetDeliveryPosition {
     pub delivery_id: String,
     pub position: (f32, f32),
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to set the current client assigned to a delivery agent.
@@ -346,11 +570,14 @@ pub struct SetDeliveryPosition {
 /// ## Contents
 /// - `delivery_id`: The ID of the delivery agent.
 /// - `client_id`: The ID of the client.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct SetCurrentClientToDelivery {
     pub delivery_id: String,
     pub client_id: String,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to set the current order assigned to a delivery agent.
@@ -361,11 +588,14 @@ pub struct SetCurrentClientToDelivery {
 /// ## Contents
 /// - `delivery_id`: The ID of the delivery agent.
 /// - `order`: The [`OrderDTO`] representing the order.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct SetCurrentOrderToDelivery {
     pub delivery_id: String,
     pub order: OrderDTO,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to set the status of a delivery agent.
@@ -376,11 +606,14 @@ pub struct SetCurrentOrderToDelivery {
 /// ## Contents
 /// - `delivery_id`: The ID of the delivery agent.
 /// - `delivery_status`: The [`DeliveryStatus`] to set.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct SetDeliveryStatus {
     pub delivery_id: String,
     pub delivery_status: DeliveryStatus,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
 /// Message to set the delivery agent assigned to an order.
@@ -391,11 +624,14 @@ pub struct SetDeliveryStatus {
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order.
 /// - `delivery_id`: The ID of the delivery agent.
+/// - `event`: The [`OrderEvent`] of the change, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct SetDeliveryToOrder {
     pub order: OrderDTO,
     pub delivery_id: String,
+    #[serde(default)]
+    pub event: Option<OrderEvent>,
 }
 
 /// Message to get all deliveries from storage.
@@ -406,6 +642,22 @@ pub struct SetDeliveryToOrder {
 #[rtype(result = "Vec<DeliveryDTO>")]
 pub struct GetDeliveries;
 
+/// Message to get the deliveries near a position from storage.
+///
+/// ## Purpose
+/// Used to retrieve the delivery agents close to a restaurant, looking only at the part of
+/// the spatial index around it.
+///
+/// ## Contents
+/// - `position`: The position to search around.
+/// - `radius`: The maximum distance to `position`.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<DeliveryDTO>")]
+pub struct GetNearbyDeliveries {
+    pub position: (f32, f32),
+    pub radius: f32,
+}
+
 /// Message to get all available deliveries from storage.
 ///
 /// ## Purpose
@@ -422,11 +674,14 @@ pub struct GetAllAvailableDeliveries;
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order.
 /// - `order_status`: The [`OrderStatus`] to set.
+/// - `event`: The [`OrderEvent`] of the change, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct SetOrderStatus {
     pub order: OrderDTO,
     pub order_status: OrderStatus,
+    #[serde(default)]
+    pub event: Option<OrderEvent>,
 }
 
 /// Message struct used to set the expected time for an order.
@@ -437,13 +692,357 @@ pub struct SetOrderStatus {
 /// # Fields
 /// - `order_id`: The unique identifier of the order.
 /// - `expected_time`: Expected time to deliver the order.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct SetOrderExpectedTime {
     pub order_id: u64,
     pub expected_time: u64,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message struct used to set the preparation time estimated for an order.
+///
+/// ## Purpose
+/// Used to update the time the restaurant expects to have the order ready.
+///
+/// # Fields
+/// - `order_id`: The unique identifier of the order.
+/// - `preparation_time`: Expected time to prepare the order, in milliseconds.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct SetOrderPreparationTime {
+    pub order_id: u64,
+    pub preparation_time: u64,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to record what a delivery agent earned for an order it delivered.
+///
+/// ## Purpose
+/// Used when an order is finalized, so the delivery agent can be paid out and can ask for
+/// its earnings later, even from another leader.
+///
+/// ## Contents
+/// - `delivery_id`: The ID of the delivery agent.
+/// - `order`: The [`OrderDTO`] delivered.
+/// - `earning`: The [`EarningDTO`], computed from the distance between the restaurant and
+///   the client by the storage that applies it first.
+///
+/// ## Returns
+/// - `Option<EarningDTO>`: The earning, or `None` if it was already recorded.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Option<EarningDTO>")]
+pub struct AddDeliveryEarning {
+    pub delivery_id: String,
+    pub order: OrderDTO,
+    #[serde(default)]
+    pub earning: Option<EarningDTO>,
+}
+
+/// Message to mark the earning of a delivery agent for an order as paid out.
+///
+/// ## Purpose
+/// Used when the payment gateway confirms the payout of an earning.
+///
+/// ## Contents
+/// - `delivery_id`: The ID of the delivery agent.
+/// - `order_id`: The ID of the order.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct SetEarningPaidOut {
+    pub delivery_id: String,
+    pub order_id: u64,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to get the earnings of a delivery agent from storage.
+///
+/// ## Purpose
+/// Used to answer a delivery agent that asks for its earnings.
+///
+/// ## Contents
+/// - `delivery_id`: The ID of the delivery agent.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "EarningsReport")]
+pub struct GetDeliveryEarnings {
+    pub delivery_id: String,
+}
+
+/// Message to credit a restaurant with the fee kept from an order cancelled by its client.
+///
+/// ## Purpose
+/// Used when a client cancels an order the restaurant already started, so the restaurant is
+/// compensated for it.
+///
+/// ## Contents
+/// - `restaurant_id`: The ID of the restaurant.
+/// - `fee`: The [`CancellationFee`] of the order. The fee of an order is only credited once.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct AddCancellationFee {
+    pub restaurant_id: String,
+    pub fee: CancellationFee,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to record whether a restaurant is within its opening hours.
+///
+/// ## Purpose
+/// Used when a restaurant opens or closes, so its clients stop seeing it and its orders are
+/// rejected while it is closed.
+///
+/// ## Contents
+/// - `restaurant_id`: The ID of the restaurant.
+/// - `open`: Whether the restaurant is open.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct SetRestaurantOpen {
+    pub restaurant_id: String,
+    pub open: bool,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to record the cuisines or categories of a restaurant.
+///
+/// ## Purpose
+/// Used when a restaurant registers again with other tags than the stored ones, so the
+/// clients find it by the tags it started with.
+///
+/// ## Contents
+/// - `restaurant_id`: The ID of the restaurant.
+/// - `tags`: The new tags of the restaurant.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct SetRestaurantTags {
+    pub restaurant_id: String,
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to record that a user left the system on a clean shutdown.
+///
+/// ## Purpose
+/// Used when a user deregisters, so a delivery gets no more offers and a restaurant is not
+/// shown to the clients until they register again.
+///
+/// ## Contents
+/// - `user_id`: The ID of the user.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct SetUserOffline {
+    pub user_id: String,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to claim a user ID for the holder of a secret.
+///
+/// ## Purpose
+/// Used when a user registers, so that once an ID was registered with a secret only that
+/// secret can be used with it, even through another leader.
+///
+/// User IDs are global to the cluster, not to each tenant: an ID held by a user of another
+/// tenant is refused, even if no user of this tenant has it.
+///
+/// ## Contents
+/// - `user_id`: The ID of the user.
+/// - `secret`: The secret sent by the user, if any. It is never logged.
+/// - `tenant_id`: The marketplace the user registers in.
+/// - `secret_hash`: The salted hash of the secret, set by the storage when it logs the claim.
+///
+/// ## Returns
+/// - `bool`: `false` if the ID was claimed before with another secret, or belongs to a user of
+///   another tenant.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "bool")]
+pub struct ClaimUserId {
+    pub user_id: String,
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub tenant_id: TenantId,
+    #[serde(default)]
+    pub secret_hash: Option<SecretHash>,
+}
+
+/// Message to record the ID given to an order placed by a client.
+///
+/// ## Purpose
+/// Used when an order is placed, so that a client resending the same request, even through
+/// another leader, gets the order already created instead of a new one.
+///
+/// ## Contents
+/// - `client_id`: The ID of the client that placed the order.
+/// - `request`: The [`OrderRequest`], with the `request_id` chosen by the client and the ID
+///   given to the order.
+///
+/// ## Returns
+/// - `u64`: The ID of the order of the request, which is an earlier one if the client already
+///   sent it.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "u64")]
+pub struct ClaimOrderRequest {
+    pub client_id: String,
+    pub request: OrderRequest,
+}
+
+/// Message to record that a client used a promo code.
+///
+/// ## Purpose
+/// Used when an order with a promo code is authorized, so the client cannot use the code
+/// again, even through another leader.
+///
+/// ## Contents
+/// - `redemption`: The [`PromoRedemption`]. Its timestamp is set by the storage that
+///   applies it first.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+///
+/// ## Returns
+/// - `bool`: `false` if the client had already used the code.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "bool")]
+pub struct AddPromoRedemption {
+    pub redemption: PromoRedemption,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
 }
 
+/// Message to get the uses of a promo code from storage.
+///
+/// ## Purpose
+/// Used to check whether a client already used a promo code before applying it.
+///
+/// ## Contents
+/// - `code`: The promo code, in uppercase.
+///
+/// ## Returns
+/// - `Vec<PromoRedemption>`: The uses of the code, oldest first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<PromoRedemption>")]
+pub struct GetPromoRedemptions {
+    pub code: String,
+}
+
+/// Message to keep the bill of a finalized order in the payment outbox.
+///
+/// ## Purpose
+/// Used before billing an order, so the bill is sent again if the payment gateway or the
+/// leader is lost before the gateway confirms it.
+///
+/// ## Contents
+/// - `order`: The finalized [`OrderDTO`]. An order already in the outbox is not added again.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct AddOutboxPayment {
+    pub order: OrderDTO,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to mark the bill of an order in the payment outbox as completed.
+///
+/// ## Purpose
+/// Used when the payment gateway confirms the payment of an order. The gateway confirms a
+/// repeated bill again, so the payment is only finalized the first time.
+///
+/// ## Contents
+/// - `order`: The billed [`OrderDTO`]. It is kept as completed even if it was not in the
+///   outbox, so a later confirmation is recognized as repeated.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+///
+/// ## Returns
+/// - `bool`: `false` if the payment of the order had already been completed.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "bool")]
+pub struct CompleteOutboxPayment {
+    pub order: OrderDTO,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to get the bills of the payment outbox the gateway did not confirm yet.
+///
+/// ## Purpose
+/// Used to send the bills again after reconnecting to the payment gateway or becoming the
+/// leader.
+///
+/// ## Returns
+/// - `Vec<OrderDTO>`: The orders to bill, oldest bill first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<OrderDTO>")]
+pub struct GetPendingPayments;
+
+/// Message to record that some users are still connected.
+///
+/// ## Purpose
+/// Sent periodically by the leader with the users connected to the cluster, so that only
+/// the clients and deliveries that stopped coming are collected.
+///
+/// ## Contents
+/// - `user_ids`: The IDs of the connected users. Unknown IDs are ignored.
+/// - `time_stamp`: When the change was applied, set by the storage that applies it first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct TouchUsers {
+    pub user_ids: Vec<String>,
+    #[serde(default)]
+    pub time_stamp: Option<HlcTimestamp>,
+}
+
+/// Message to get the clients and deliveries not seen for a while.
+///
+/// ## Purpose
+/// Used by the leader to evict the users that left without deregistering.
+///
+/// ## Contents
+/// - `seen_before`: Users last seen before this time are stale.
+///
+/// ## Returns
+/// - `Vec<String>`: The IDs of the stale users without active orders, sorted.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<String>")]
+pub struct GetStaleUsers {
+    pub seen_before: HlcTimestamp,
+}
+
+/// Message to record a leader change in the election history.
+///
+/// ## Purpose
+/// Used by a new leader, so the history is replicated and survives restarts.
+///
+/// ## Contents
+/// - `record`: The [`ElectionRecord`] of the change. A record of an epoch already stored
+///   is ignored.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct RecordElection {
+    pub record: ElectionRecord,
+}
+
+/// Message to get the election history from storage.
+///
+/// ## Purpose
+/// Used to answer an administrator that asks for the recent leader changes.
+///
+/// ## Returns
+/// - `Vec<ElectionRecord>`: The stored leader changes, oldest first.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<ElectionRecord>")]
+pub struct GetElectionHistory;
+
 /////////////////////////////////////////////////////////////////////
 // Mensajes de servicios internos
 /////////////////////////////////////////////////////////////////////
@@ -456,11 +1055,15 @@ pub struct SetOrderExpectedTime {
 /// ## Contents
 /// - `order_id`: The ID of the order.
 /// - `delivery_id`: The ID of the delivery agent.
+/// - `epoch`: The leader epoch in which the acceptance was decided. An acceptance never
+///   replaces another one of a newer epoch for the same order.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct InsertAcceptedDelivery {
     pub order_id: u64,
     pub delivery_id: String,
+    #[serde(default)]
+    pub epoch: u64,
 }
 
 /// Message to remove accepted deliveries for an order.
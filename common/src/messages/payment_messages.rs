@@ -1,4 +1,6 @@
+use crate::types::decline_reason::DeclineReason;
 use crate::types::dtos::OrderDTO;
+use crate::types::ledger::{LedgerEntry, LedgerSummary};
 use actix::Message;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
@@ -25,10 +27,13 @@ pub struct RequestAuthorization {
 ///
 /// # Contents
 /// - `result`: The [`OrderDTO`] with updated authorization status.
+/// - `decline`: Why the authorization was declined, if it was and the gateway knows why.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct AuthorizationResult {
     pub result: OrderDTO,
+    #[serde(default)]
+    pub decline: Option<DeclineReason>,
 }
 
 /// Message sent to notify that payment has been completed for an order.
@@ -38,10 +43,13 @@ pub struct AuthorizationResult {
 ///
 /// # Contents
 /// - `order`: The [`OrderDTO`] representing the completed payment.
+/// - `amount`: The amount billed, in cents. Express orders cost more.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct PaymentCompleted {
     pub order: OrderDTO,
+    #[serde(default)]
+    pub amount: u64,
 }
 
 /// Message sent to request billing for a payment.
@@ -58,3 +66,142 @@ pub struct BillPayment {
     pub origin_address: SocketAddr,
     pub order: OrderDTO,
 }
+
+/// Message sent to refund the payment of a cancelled order.
+///
+/// # Purpose
+/// Used by a coordinator to ask the payment gateway to release the authorization of an order
+/// cancelled by the client before it was billed, keeping the cancellation fee if any.
+///
+/// # Contents
+/// - `origin_address`: The address of the requester.
+/// - `order`: The [`OrderDTO`] to be refunded.
+/// - `fee`: The cents of the authorization charged anyway as a cancellation fee, instead of
+///   being given back to the client.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct RefundPayment {
+    pub origin_address: SocketAddr,
+    pub order: OrderDTO,
+    #[serde(default)]
+    pub fee: u64,
+}
+
+/// Message sent to notify that the payment of an order has been refunded.
+///
+/// # Purpose
+/// Used by the payment gateway to confirm a [`RefundPayment`] to the requester.
+///
+/// # Contents
+/// - `order`: The [`OrderDTO`] representing the refunded order.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct PaymentRefunded {
+    pub order: OrderDTO,
+}
+
+/// Message sent to query the ledger of a payment gateway.
+///
+/// # Purpose
+/// Used by the admin CLI to reconcile the charges of the gateway with the orders of the
+/// cluster.
+///
+/// # Contents
+/// - `origin_address`: The address of the requester.
+/// - `order_id`: Only the entries of this order, if set.
+/// - `client_id`: Only the entries of the orders of this client, if set.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct RequestLedger {
+    pub origin_address: SocketAddr,
+    pub order_id: Option<u64>,
+    pub client_id: Option<String>,
+}
+
+/// Message sent with the entries of a ledger matching a [`RequestLedger`].
+///
+/// # Purpose
+/// Used by the payment gateway to answer a [`RequestLedger`].
+///
+/// # Contents
+/// - `entries`: The matching entries, oldest first.
+/// - `summary`: The totals of the matching entries.
+/// - `balance`: The current balance of the client, in cents, if the query was for a client.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct LedgerReport {
+    pub entries: Vec<LedgerEntry>,
+    pub summary: LedgerSummary,
+    #[serde(default)]
+    pub balance: Option<u64>,
+}
+
+/// Message sent to add funds to the simulated balance of a client.
+///
+/// # Purpose
+/// Used by the admin CLI so that a client whose orders are being rejected for insufficient
+/// funds can order again.
+///
+/// # Contents
+/// - `origin_address`: The address of the requester.
+/// - `client_id`: The client whose balance is topped up.
+/// - `amount`: The cents to add.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct TopUpBalance {
+    pub origin_address: SocketAddr,
+    pub client_id: String,
+    pub amount: u64,
+}
+
+/// Message sent with the balance of a client after a [`TopUpBalance`].
+///
+/// # Purpose
+/// Used by the payment gateway to confirm a [`TopUpBalance`] to the requester.
+///
+/// # Contents
+/// - `client_id`: The client whose balance was topped up.
+/// - `balance`: The new balance of the client, in cents.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct ClientBalance {
+    pub client_id: String,
+    pub balance: u64,
+}
+
+/// Message sent to pay a delivery agent what it earned for an order.
+///
+/// # Purpose
+/// Used by a coordinator once an order is finalized. The payment gateway pays every order
+/// out only once.
+///
+/// # Contents
+/// - `origin_address`: The address of the requester.
+/// - `delivery_id`: The delivery agent paid.
+/// - `order_id`: The order delivered.
+/// - `amount`: The cents earned.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct PayOutDelivery {
+    pub origin_address: SocketAddr,
+    pub delivery_id: String,
+    pub order_id: u64,
+    pub amount: u64,
+}
+
+/// Message sent to notify that a delivery agent was paid for an order.
+///
+/// # Purpose
+/// Used by the payment gateway to confirm a [`PayOutDelivery`] to the requester.
+///
+/// # Contents
+/// - `delivery_id`: The delivery agent paid.
+/// - `order_id`: The order delivered.
+/// - `amount`: The cents paid out.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct DeliveryPaidOut {
+    pub delivery_id: String,
+    pub order_id: u64,
+    pub amount: u64,
+}
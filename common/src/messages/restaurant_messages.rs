@@ -1,5 +1,7 @@
 use crate::types::{
     dtos::{DeliveryDTO, OrderDTO},
+    kitchen_load::KitchenLoad,
+    rejection_reason::RejectionReason,
     restaurant_info::RestaurantInfo,
 };
 use actix::Message;
@@ -12,10 +14,16 @@ use serde::{Deserialize, Serialize};
 ///
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order whose status has changed.
+/// - `reason`: Why the restaurant rejected the order, if it did.
+/// - `kitchen_load`: How busy the kitchen of the restaurant is, if the kitchen sent it.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct UpdateOrderStatus {
     pub order: OrderDTO,
+    #[serde(default)]
+    pub reason: Option<RejectionReason>,
+    #[serde(default)]
+    pub kitchen_load: Option<KitchenLoad>,
 }
 
 /// Message sent to cancel an order at a restaurant.
@@ -25,10 +33,13 @@ pub struct UpdateOrderStatus {
 ///
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order to be cancelled.
+/// - `reason`: Why the restaurant rejected the order, if it was the restaurant who cancelled it.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct CancelOrder {
     pub order: OrderDTO,
+    #[serde(default)]
+    pub reason: Option<RejectionReason>,
 }
 
 /// Message sent by a restaurant to request a list of nearby delivery agents.
@@ -39,11 +50,15 @@ pub struct CancelOrder {
 /// ## Contents
 /// - `order`: The [`OrderDTO`] for which delivery is needed.
 /// - `restaurant_info`: The [`RestaurantInfo`] with details about the restaurant.
+/// - `radius`: The maximum distance to the restaurant, in blocks. If `None`, the default
+///   radius is used. The server widens it each time it offers the order again.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct RequestNearbyDelivery {
     pub order: OrderDTO,
     pub restaurant_info: RestaurantInfo,
+    #[serde(default)]
+    pub radius: Option<f32>,
 }
 
 /// Message sent to notify that a delivery agent has accepted a delivery for an order.
@@ -60,3 +75,19 @@ pub struct DeliveryAccepted {
     pub order: OrderDTO,
     pub delivery: DeliveryDTO,
 }
+
+/// Message sent by a restaurant when it opens or closes.
+///
+/// ## Purpose
+/// Used by a restaurant to tell the server whether it is within its opening hours, when they
+/// start or end and each time it registers.
+///
+/// ## Contents
+/// - `restaurant_id`: The ID of the restaurant.
+/// - `open`: Whether the restaurant is open.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct RestaurantAvailability {
+    pub restaurant_id: String,
+    pub open: bool,
+}
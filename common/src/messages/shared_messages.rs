@@ -4,10 +4,16 @@ use crate::messages::coordinatormanager_messages::*;
 use crate::messages::delivery_messages::*;
 use crate::messages::payment_messages::*;
 use crate::messages::restaurant_messages::*;
-use crate::types::dtos::UserDTO;
+use crate::network::faults::FaultConfig;
+use crate::types::dtos::{ElectionRecord, UserDTO};
+use crate::types::storage_violation::StorageViolation;
+use crate::types::tenant::TenantId;
+use crate::types::vehicle::VehicleType;
 use actix::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
+use std::time::Duration;
+use strum::IntoStaticStr;
 
 /// Enum representing all possible network messages exchanged between system components.
 ///
@@ -18,7 +24,7 @@ use std::net::SocketAddr;
 /// ## Contents
 /// Each variant wraps a specific message struct, grouping messages by their origin or target.
 /// See the documentation for each variant's struct for details.
-#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[derive(Serialize, Deserialize, Debug, Message, Clone, IntoStaticStr)]
 #[serde(tag = "type")]
 #[rtype(result = "()")]
 pub enum NetworkMessage {
@@ -31,6 +37,10 @@ pub enum NetworkMessage {
     LeaderIdIs(LeaderIdIs),
     /// Register a new user in the system.
     RegisterUser(RegisterUser),
+    /// A user leaving the system on a clean shutdown.
+    DeregisterUser(DeregisterUser),
+    /// Rejects the registration of a user with an ID that belongs to someone else.
+    IdentityRejected(IdentityRejected),
     /// Response with recovered user information.
     RecoveredInfo(UserDTO),
     /// Indicates no recovered information is available.
@@ -41,10 +51,20 @@ pub enum NetworkMessage {
     RequestThisOrder(RequestThisOrder),
     /// Client requests a list of nearby restaurants.
     RequestNearbyRestaurants(RequestNearbyRestaurants),
+    /// Client requests the current state of an order.
+    RequestOrderStatus(RequestOrderStatus),
+    /// Client cancels one of its orders.
+    CancelMyOrder(CancelMyOrder),
+    /// Client acknowledges the order updates it received.
+    AckOrderUpdate(AckOrderUpdate),
     /// Notifies the client that their order has been finalized.
     OrderFinalized(OrderFinalized),
     /// Informs the client of the expected delivery time.
     DeliveryExpectedTime(DeliveryExpectedTime),
+    /// Tells the client that its order is being offered to the deliveries again.
+    StillLookingForDelivery(StillLookingForDelivery),
+    /// Tells the client how much of its cancelled order is refunded.
+    CancellationCharged(CancellationCharged),
 
     // Delivery messages
     /// Delivery agent announces availability.
@@ -57,6 +77,8 @@ pub enum NetworkMessage {
     DeliverThisOrder(DeliverThisOrder),
     /// Delivery agent updates about ongoing delivery.
     IAmDelivering(IAmDelivering),
+    /// Delivery agent asks for what it earned.
+    GetMyEarnings(GetMyEarnings),
 
     // Payment messages
     /// Requests payment authorization for an order.
@@ -67,6 +89,22 @@ pub enum NetworkMessage {
     PaymentCompleted(PaymentCompleted),
     /// Requests billing for a payment.
     BillPayment(BillPayment),
+    /// Requests the refund of a cancelled order.
+    RefundPayment(RefundPayment),
+    /// Notifies that the payment of an order has been refunded.
+    PaymentRefunded(PaymentRefunded),
+    /// Queries the ledger of a payment gateway.
+    RequestLedger(RequestLedger),
+    /// Entries of a payment gateway ledger.
+    LedgerReport(LedgerReport),
+    /// Adds funds to the balance of a client at a payment gateway.
+    TopUpBalance(TopUpBalance),
+    /// Balance of a client after a top-up.
+    ClientBalance(ClientBalance),
+    /// Pays a delivery agent what it earned for an order.
+    PayOutDelivery(PayOutDelivery),
+    /// Notifies that a delivery agent was paid for an order.
+    DeliveryPaidOut(DeliveryPaidOut),
 
     // Restaurant messages
     /// Notifies a restaurant of a new order.
@@ -83,16 +121,24 @@ pub enum NetworkMessage {
     NearbyDeliveries(NearbyDeliveries),
     /// Notifies a restaurant that a delivery agent is available.
     DeliveryAvailable(DeliveryAvailable),
+    /// Restaurant announces that it opened or closed.
+    RestaurantAvailability(RestaurantAvailability),
 
     // Coordinator messages
     /// Provides a client with a list of nearby restaurants.
     NearbyRestaurants(NearbyRestaurants),
     /// Notifies a peer that an order has been updated.
     NotifyOrderUpdated(NotifyOrderUpdated),
+    /// Tells a client that the servers know no order of its with the ID it asked for.
+    OrderNotFound(OrderNotFound),
+    /// Tells a restaurant or delivery that the status it sent for an order was rejected.
+    StatusRejected(StatusRejected),
     /// Offers a delivery agent a new order to deliver.
     NewOfferToDeliver(NewOfferToDeliver),
     /// Notifies a delivery agent that their services are not needed.
     DeliveryNoNeeded(DeliveryNoNeeded),
+    /// Tells a delivery agent what it earned.
+    MyEarnings(MyEarnings),
 
     // CoordinatorManager messages
     /// Requests new storage updates from the coordinator manager.
@@ -101,12 +147,31 @@ pub enum NetworkMessage {
     StorageUpdates(StorageUpdates),
     /// Requests all storage data.
     RequestAllStorage(RequestAllStorage),
+    RequestStorageSubset(RequestStorageSubset),
+    AntiEntropyDigest(AntiEntropyDigest),
+    AntiEntropyRepair(AntiEntropyRepair),
     /// Provides a snapshot of storage.
     StorageSnapshot(StorageSnapshot),
+    /// Provides a piece of a storage snapshot.
+    SnapshotChunk(SnapshotChunk),
+    /// Replicates a storage write that waits for a quorum.
+    ReplicateWrite(ReplicateWrite),
+    /// Confirms a replicated storage write.
+    WriteAck(WriteAck),
+    /// Asks the coordinator the leadership is handed off to to pull the log from the leader.
+    PrepareLeadership(PrepareLeadership),
+    /// Forwards a user message received by a follower to the leader.
+    ForwardToLeader(ForwardToLeader),
+    /// Forwards a message from the leader to a user connected to a follower.
+    ForwardToUser(ForwardToUser),
+    /// Tells the leader that a user is connected to a follower, or left it.
+    ProxyUser(ProxyUser),
     /// Requests recovery of storage operations.
     RecoverStorageOperations(RecoverStorageOperations),
     /// Initiates or participates in a leader election.
     LeaderElection(LeaderElection),
+    /// Participates in a bully leader election.
+    BullyElection(BullyElection),
     /// Ping message for liveness checks.
     Ping(Ping),
     /// Pong response for liveness checks.
@@ -117,6 +182,90 @@ pub enum NetworkMessage {
 
     /// Notifies that a TCP connection has been closed.
     ConnectionClosed(ConnectionClosed),
+
+    // Connection handshake
+    /// Announces the protocol version and capabilities of a peer when a connection is opened.
+    Handshake(Handshake),
+    /// Notifies that the peer's protocol version is not supported and the connection is rejected.
+    IncompatibleVersion(IncompatibleVersion),
+    /// Tells the peer of a user connection that this end is still alive.
+    KeepAlive(KeepAlive),
+    /// Wraps a message that the peer must acknowledge.
+    Reliable(ReliableMessage),
+    /// Acknowledges a [`ReliableMessage`].
+    Ack(MessageAck),
+
+    // Chaos testing
+    /// Changes the network faults a node injects on its connections.
+    InjectFaults(InjectFaults),
+
+    // Diagnostics
+    /// Asks a coordinator for the recent leader changes.
+    RequestElectionHistory(RequestElectionHistory),
+    /// Recent leader changes known by a coordinator.
+    ElectionHistory(ElectionHistory),
+    /// Asks a coordinator to check the references between the entries of its storage.
+    VerifyStorage(VerifyStorage),
+    /// References that do not hold in the storage of a coordinator.
+    StorageReport(StorageReport),
+
+    // Maintenance
+    /// Asks a coordinator to hand its users and leadership off and shut down.
+    DrainCoordinator(DrainCoordinator),
+    /// Tells the admin that a coordinator was drained, or could not be.
+    CoordinatorDrained(CoordinatorDrained),
+    /// Asks the leader to hand the leadership off to a given coordinator.
+    TransferLeadership(TransferLeadership),
+    /// Tells the admin whether the leadership was transferred.
+    LeadershipTransferred(LeadershipTransferred),
+}
+
+impl NetworkMessage {
+    /// Returns the name of the message type, as in its `type` tag.
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
+
+    /// Returns the ID of the order the message is about, if any. Forwarded and reliable
+    /// messages return the one of the message they carry.
+    pub fn order_id(&self) -> Option<u64> {
+        match self {
+            NetworkMessage::RequestThisOrder(msg) => Some(msg.order.order_id),
+            NetworkMessage::OrderFinalized(msg) => Some(msg.order.order_id),
+            NetworkMessage::DeliveryExpectedTime(msg) => Some(msg.order.order_id),
+            NetworkMessage::StillLookingForDelivery(msg) => Some(msg.order.order_id),
+            NetworkMessage::AcceptedOrder(msg) => Some(msg.order.order_id),
+            NetworkMessage::OrderDelivered(msg) => Some(msg.order.order_id),
+            NetworkMessage::DeliverThisOrder(msg) => Some(msg.order.order_id),
+            NetworkMessage::IAmDelivering(msg) => Some(msg.order.order_id),
+            NetworkMessage::RequestAuthorization(msg) => Some(msg.order.order_id),
+            NetworkMessage::AuthorizationResult(msg) => Some(msg.result.order_id),
+            NetworkMessage::PaymentCompleted(msg) => Some(msg.order.order_id),
+            NetworkMessage::BillPayment(msg) => Some(msg.order.order_id),
+            NetworkMessage::RefundPayment(msg) => Some(msg.order.order_id),
+            NetworkMessage::PaymentRefunded(msg) => Some(msg.order.order_id),
+            NetworkMessage::NewOrder(msg) => Some(msg.order.order_id),
+            NetworkMessage::UpdateOrderStatus(msg) => Some(msg.order.order_id),
+            NetworkMessage::CancelOrder(msg) => Some(msg.order.order_id),
+            NetworkMessage::RequestNearbyDelivery(msg) => Some(msg.order.order_id),
+            NetworkMessage::DeliveryAccepted(msg) => Some(msg.order.order_id),
+            NetworkMessage::NearbyDeliveries(msg) => Some(msg.order.order_id),
+            NetworkMessage::DeliveryAvailable(msg) => Some(msg.order.order_id),
+            NetworkMessage::NotifyOrderUpdated(msg) => Some(msg.order.order_id),
+            NetworkMessage::StatusRejected(msg) => Some(msg.order.order_id),
+            NetworkMessage::NewOfferToDeliver(msg) => Some(msg.order.order_id),
+            NetworkMessage::DeliveryNoNeeded(msg) => Some(msg.order.order_id),
+            NetworkMessage::RequestOrderStatus(msg) => Some(msg.order_id),
+            NetworkMessage::OrderNotFound(msg) => Some(msg.order_id),
+            NetworkMessage::CancelMyOrder(msg) => Some(msg.order_id),
+            NetworkMessage::PayOutDelivery(msg) => Some(msg.order_id),
+            NetworkMessage::DeliveryPaidOut(msg) => Some(msg.order_id),
+            NetworkMessage::ForwardToLeader(msg) => msg.message.order_id(),
+            NetworkMessage::ForwardToUser(msg) => msg.message.order_id(),
+            NetworkMessage::Reliable(msg) => msg.message.order_id(),
+            _ => None,
+        }
+    }
 }
 
 /// Message sent to query for the current leader in the system.
@@ -154,10 +303,14 @@ pub struct LeaderIs {
 ///
 /// ## Contents
 /// - `leader_id`: The user ID of the current leader.
+/// - `epoch`: The epoch in which the leader was elected. Announcements of an older epoch
+///   than the one already known are ignored.
 #[derive(Serialize, Deserialize, Debug, Message, Clone)]
 #[rtype(result = "()")]
 pub struct LeaderIdIs {
     pub leader_id: String,
+    #[serde(default)]
+    pub epoch: u64,
 }
 
 /// Message sent to start the running of the system.
@@ -177,14 +330,67 @@ pub struct StartRunning;
 /// - `origin_addr`: The address of the registering node.
 /// - `user_id`: The ID of the user.
 /// - `position`: The (x, y) position of the user.
+/// - `secret`: The secret of the [`Identity`](crate::identity::Identity) of the user, if it
+///   keeps one. Once an ID was registered with a secret, it cannot be used without it.
+/// - `tags`: The cuisines or categories of a restaurant, which replace the stored ones. Empty
+///   for the other users.
+/// - `vehicle`: The vehicle of a delivery, which replaces the stored one. `None` for the
+///   other users.
+/// - `tenant_id`: The marketplace the user belongs to. An ID registered in one tenant cannot
+///   be used in another.
 #[derive(Serialize, Deserialize, Debug, Message, Clone)]
 #[rtype(result = "()")]
 pub struct RegisterUser {
     pub origin_addr: SocketAddr,
     pub user_id: String,
     pub position: (f32, f32),
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub vehicle: Option<VehicleType>,
+    #[serde(default)]
+    pub tenant_id: TenantId,
+}
+
+/// Message sent to a user whose registration was rejected.
+///
+/// ## Purpose
+/// Used by the leader when a user registers with an ID that belongs to someone else: the ID
+/// was registered before with a secret, and the user did not send the same one.
+///
+/// ## Contents
+/// - `user_id`: The ID the user tried to register with.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct IdentityRejected {
+    pub user_id: String,
+}
+
+/// Message sent by a user when it shuts down cleanly.
+///
+/// ## Purpose
+/// Lets the leader act on the user leaving right away instead of waiting for the reaper:
+/// a delivery stops getting offers and its orders are reassigned, a restaurant is closed,
+/// and the orders of a client the restaurant did not start yet are cancelled.
+///
+/// ## Contents
+/// - `user_id`: The ID of the user.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct DeregisterUser {
+    pub user_id: String,
 }
 
+/// Message sent to a user actor to make it leave the system before shutting down.
+///
+/// ## Purpose
+/// Sent on a clean shutdown, so that the actor sends a [`DeregisterUser`] to the leader.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct Deregister;
+
 /// Message sent to recover user information.
 ///
 /// ## Purpose
@@ -205,10 +411,21 @@ pub struct RecoverProcedure {
 ///
 /// ## Contents
 /// - `origin_addr`: The address of the node that should retry.
+/// - `retry_after_millis`: How long the sender suggests to wait before retrying, in
+///   milliseconds (0 if it does not know).
 #[derive(Serialize, Deserialize, Debug, Message, Clone)]
 #[rtype(result = "()")]
 pub struct RetryLater {
     pub origin_addr: SocketAddr,
+    #[serde(default)]
+    pub retry_after_millis: u64,
+}
+
+impl RetryLater {
+    /// Returns how long the sender suggests to wait before retrying.
+    pub fn retry_after(&self) -> Duration {
+        Duration::from_millis(self.retry_after_millis)
+    }
 }
 
 /// Message sent to notify that a TCP connection has been closed.
@@ -224,9 +441,272 @@ pub struct ConnectionClosed {
     pub remote_addr: SocketAddr,
 }
 
+/// Message sent periodically over an idle user connection.
+///
+/// ## Purpose
+/// Lets each end of a connection with a client, restaurant or delivery notice that the other
+/// one stopped responding, without waiting for TCP to fail. It is consumed by the
+/// [`TCPReceiver`](crate::network::tcp_receiver::TCPReceiver) and never forwarded.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct KeepAlive {}
+
+/// Envelope of a message sent with acknowledged delivery.
+///
+/// ## Purpose
+/// Lets a connection resend the critical messages (e.g. `DeliverThisOrder`) that the peer
+/// does not acknowledge in time. The [`TCPReceiver`](crate::network::tcp_receiver::TCPReceiver)
+/// of the peer answers with a [`MessageAck`], drops the copies it already received and
+/// forwards only the wrapped message.
+///
+/// ## Contents
+/// - `id`: The ID of the message in the connection.
+/// - `message`: The wrapped [`NetworkMessage`].
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct ReliableMessage {
+    pub id: u64,
+    pub message: Box<NetworkMessage>,
+}
+
+/// Message sent to acknowledge a [`ReliableMessage`].
+///
+/// ## Purpose
+/// Stops the sender from resending the message. It is consumed by the
+/// [`TCPReceiver`](crate::network::tcp_receiver::TCPReceiver) and never forwarded.
+///
+/// ## Contents
+/// - `id`: The ID of the acknowledged message.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct MessageAck {
+    pub id: u64,
+}
+
+/// Message exchanged by both ends of a connection before any other message.
+///
+/// ## Purpose
+/// Lets peers running different binaries agree on a protocol version, or reject each other
+/// instead of failing to deserialize later messages.
+///
+/// ## Contents
+/// - `protocol_version`: The newest protocol version the peer speaks.
+/// - `min_protocol_version`: The oldest protocol version the peer still accepts.
+/// - `capabilities`: Optional features supported by the peer.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct Handshake {
+    pub protocol_version: u32,
+    pub min_protocol_version: u32,
+    pub capabilities: Vec<String>,
+}
+
+/// Message sent when a connection is rejected because of the peer's protocol version.
+///
+/// ## Purpose
+/// Informs both the remote peer and the local actor that the connection cannot be used.
+///
+/// ## Contents
+/// - `remote_addr`: The address of the peer whose version was rejected.
+/// - `supported`: The (oldest, newest) protocol versions accepted by the rejecting node.
+/// - `received`: The protocol version announced by the rejected peer.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct IncompatibleVersion {
+    pub remote_addr: SocketAddr,
+    pub supported: (u32, u32),
+    pub received: u32,
+}
+
+/// Admin message sent to change the network faults a node injects.
+///
+/// ## Purpose
+/// Reproduces failure scenarios (lost, delayed, duplicated or reordered messages) on a running
+/// cluster. A configuration that injects no faults turns them off.
+///
+/// ## Contents
+/// - `config`: The [`FaultConfig`] applied to the outgoing messages of every connection.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct InjectFaults {
+    pub config: FaultConfig,
+}
+
+/// Admin message sent to ask a coordinator for the recent leader changes.
+///
+/// ## Purpose
+/// Helps to diagnose a leadership that keeps flapping. Any coordinator answers from its
+/// replica of the storage with an [`ElectionHistory`].
+///
+/// ## Contents
+/// - `origin_addr`: The address of the requester.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct RequestElectionHistory {
+    pub origin_addr: SocketAddr,
+}
+
+/// Message sent with the leader changes known by a coordinator.
+///
+/// ## Purpose
+/// Used by a coordinator to answer a [`RequestElectionHistory`].
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the coordinator that answers.
+/// - `epoch`: The epoch (term) of the leader it follows.
+/// - `elections`: The stored leader changes, oldest first.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct ElectionHistory {
+    pub coordinator_id: String,
+    pub epoch: u64,
+    pub elections: Vec<ElectionRecord>,
+}
+
+/// Admin message sent to ask a coordinator to verify the integrity of its storage.
+///
+/// ## Purpose
+/// Finds references that do not hold: orders of clients, restaurants or deliveries that are
+/// not stored, deliveries that accepted an order assigned to another one, and orders both
+/// pending and authorized at their restaurant. Any coordinator checks its own replica and
+/// answers with a [`StorageReport`].
+///
+/// ## Contents
+/// - `origin_addr`: The address of the requester.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct VerifyStorage {
+    pub origin_addr: SocketAddr,
+}
+
+/// Message sent with the result of verifying the storage of a coordinator.
+///
+/// ## Purpose
+/// Used by a coordinator to answer a [`VerifyStorage`].
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the coordinator that answers.
+/// - `next_log_id`: The index of the next log entry its storage applies.
+/// - `violations`: The references that do not hold, empty if the storage is consistent.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct StorageReport {
+    pub coordinator_id: String,
+    pub next_log_id: u64,
+    pub violations: Vec<StorageViolation>,
+}
+
+/// Admin message sent to drain a coordinator before a planned restart.
+///
+/// ## Purpose
+/// The coordinator sends the users that connect to it to another coordinator and, if it is
+/// the leader, hands the leadership off to the most up-to-date follower. Then it tells its
+/// users where the leader is and shuts down, so the ring can be restarted one node at a
+/// time without an election.
+///
+/// ## Contents
+/// - `origin_addr`: The address of the requester.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct DrainCoordinator {
+    pub origin_addr: SocketAddr,
+}
+
+/// Message sent to answer a [`DrainCoordinator`].
+///
+/// ## Contents
+/// - `coordinator_id`: The ID of the drained coordinator.
+/// - `leader`: The address of the leader its users were sent to, or `None` if no other
+///   coordinator could take over, in which case it keeps running.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct CoordinatorDrained {
+    pub coordinator_id: String,
+    pub leader: Option<SocketAddr>,
+}
+
+/// Admin message sent to make a given coordinator the leader, e.g. before restarting the
+/// current one.
+///
+/// ## Purpose
+/// The leader waits for `target` to apply its whole log and announces it as the leader of a
+/// new epoch, without waiting for the heartbeats to time out. A follower forwards the message
+/// to the leader.
+///
+/// ## Contents
+/// - `origin_addr`: The address of the requester.
+/// - `target`: The ID of the coordinator that takes over.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct TransferLeadership {
+    pub origin_addr: SocketAddr,
+    pub target: String,
+}
+
+/// Message sent to answer a [`TransferLeadership`].
+///
+/// ## Contents
+/// - `target`: The ID of the coordinator asked to take over.
+/// - `leader`: The address of `target` if it became the leader, or `None` if it could not
+///   catch up with the log in time or is not connected.
+#[derive(Serialize, Deserialize, Debug, Message, Clone)]
+#[rtype(result = "()")]
+pub struct LeadershipTransferred {
+    pub target: String,
+    pub leader: Option<SocketAddr>,
+}
+
 /////////////////////////////////////////////////////////////////////
 ///// Mensajes del communicator
 /// /////////////////////////////////////////////////////////////////////
 #[derive(Message)]
 #[rtype(result = "()")]
 pub struct Shutdown;
+
+/// Message received over a connection, tagged with the address of the connection.
+///
+/// ## Purpose
+/// Lets the destination actor of a connection created with
+/// [`Communicator::new_tagged`](crate::network::communicator::Communicator::new_tagged) know
+/// who sent each message, whatever the message claims.
+///
+/// ## Contents
+/// - `remote_addr`: The address of the remote peer of the connection.
+/// - `message`: The message received.
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct ReceivedFrom {
+    pub remote_addr: SocketAddr,
+    pub message: NetworkMessage,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_the_message_and_its_order() {
+        let status = NetworkMessage::RequestOrderStatus(RequestOrderStatus {
+            client_id: "client_1".to_string(),
+            order_id: 7,
+        });
+        assert_eq!(status.kind(), "RequestOrderStatus");
+        assert_eq!(status.order_id(), Some(7));
+
+        let reliable = NetworkMessage::Reliable(ReliableMessage {
+            id: 1,
+            message: Box::new(status),
+        });
+        assert_eq!(reliable.kind(), "Reliable");
+        assert_eq!(reliable.order_id(), Some(7));
+
+        let who = NetworkMessage::WhoIsLeader(WhoIsLeader {
+            origin_addr: "127.0.0.1:9000".parse().unwrap(),
+            user_id: "client_1".to_string(),
+        });
+        assert_eq!(who.order_id(), None);
+        // El nombre es el mismo que el tag con el que se serializa
+        let json = serde_json::to_value(&who).unwrap();
+        assert_eq!(json["type"], who.kind());
+    }
+}
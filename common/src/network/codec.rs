@@ -0,0 +1,678 @@
+use crate::messages::shared_messages::NetworkMessage;
+#[cfg(feature = "protobuf")]
+use crate::network::protobuf;
+use flate2::Compression as GzipLevel;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+#[cfg(feature = "protobuf")]
+use prost::Message as _;
+use std::io::{Read, Write};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt};
+
+/// First byte of a binary frame. It can never start a JSON line, which always begins with `{`.
+pub const FRAME_MAGIC: u8 = 0xB1;
+/// Version of the binary frame header written when compression was negotiated.
+///
+/// - v1: `[FRAME_MAGIC, 1, format, len (u32 BE), payload]`
+/// - v2: `[FRAME_MAGIC, 2, format, flags, len (u32 BE), payload]`
+pub const FRAME_VERSION: u8 = 2;
+/// Version of the binary frame header written when compression was not negotiated, which
+/// binaries that only know v1 frames can read.
+pub const PLAIN_FRAME_VERSION: u8 = 1;
+/// Maximum size of a message, after reassembling its chunks and decompressing it.
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+/// Maximum payload of a single frame; larger messages are split into several chunks.
+pub const MAX_CHUNK_LEN: usize = 64 * 1024;
+/// Payloads smaller than this are sent uncompressed even if compression was negotiated.
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// Flags of a v2 frame header.
+const FLAG_GZIP: u8 = 0b001;
+const FLAG_LZ4: u8 = 0b010;
+const COMPRESSION_MASK: u8 = 0b011;
+/// More chunks of the same message follow this frame.
+const FLAG_MORE_CHUNKS: u8 = 0b100;
+
+/// Wire format used to serialize [`NetworkMessage`]s on a connection.
+///
+/// Every frame identifies its own format, so a receiver decodes legacy JSON lines and
+/// binary frames on the same connection. This lets peers switch formats after the
+/// handshake without any coordination, and keeps older binaries readable during a
+/// rolling upgrade.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WireFormat {
+    /// One JSON object per line, the original format.
+    Json,
+    /// Length-prefixed protobuf envelopes. Requires the `protobuf` feature.
+    Protobuf,
+}
+
+impl WireFormat {
+    /// Name announced in the handshake capabilities.
+    pub fn capability(&self) -> &'static str {
+        match self {
+            WireFormat::Json => "json",
+            WireFormat::Protobuf => "protobuf",
+        }
+    }
+
+    /// Identifier written in the binary frame header.
+    fn id(&self) -> u8 {
+        match self {
+            WireFormat::Json => 0,
+            WireFormat::Protobuf => 1,
+        }
+    }
+
+    fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(WireFormat::Json),
+            1 => Some(WireFormat::Protobuf),
+            _ => None,
+        }
+    }
+
+    /// Formats this binary can decode, from most to least preferred.
+    pub fn supported() -> Vec<WireFormat> {
+        let mut formats = Vec::new();
+        if cfg!(feature = "protobuf") {
+            formats.push(WireFormat::Protobuf);
+        }
+        formats.push(WireFormat::Json);
+        formats
+    }
+
+    /// Picks the preferred format that the peer also announced, falling back to JSON.
+    pub fn negotiate(remote_capabilities: &[String]) -> WireFormat {
+        WireFormat::supported()
+            .into_iter()
+            .find(|format| remote_capabilities.iter().any(|c| c == format.capability()))
+            .unwrap_or(WireFormat::Json)
+    }
+}
+
+/// Compression applied to the payload of binary frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    /// Payloads are sent as they are.
+    None,
+    /// gzip, slower but with a better ratio.
+    Gzip,
+    /// LZ4 block compression, fast enough for every large frame.
+    Lz4,
+}
+
+impl Compression {
+    /// Name announced in the handshake capabilities.
+    pub fn capability(&self) -> &'static str {
+        match self {
+            Compression::None => "uncompressed",
+            Compression::Gzip => "gzip",
+            Compression::Lz4 => "lz4",
+        }
+    }
+
+    /// Compression algorithms this binary can decode, from most to least preferred.
+    pub fn supported() -> Vec<Compression> {
+        vec![Compression::Lz4, Compression::Gzip]
+    }
+
+    /// Picks the preferred compression that the peer also announced, if any.
+    pub fn negotiate(remote_capabilities: &[String]) -> Compression {
+        Compression::supported()
+            .into_iter()
+            .find(|c| remote_capabilities.iter().any(|r| r == c.capability()))
+            .unwrap_or(Compression::None)
+    }
+
+    fn flag(&self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Gzip => FLAG_GZIP,
+            Compression::Lz4 => FLAG_LZ4,
+        }
+    }
+
+    fn from_flags(flags: u8) -> Option<Self> {
+        match flags & COMPRESSION_MASK {
+            0 => Some(Compression::None),
+            FLAG_GZIP => Some(Compression::Gzip),
+            FLAG_LZ4 => Some(Compression::Lz4),
+            _ => None,
+        }
+    }
+
+    fn compress(&self, payload: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Compression::None => Ok(payload.to_vec()),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), GzipLevel::fast());
+                encoder
+                    .write_all(payload)
+                    .and_then(|_| encoder.finish())
+                    .map_err(|e| format!("Error compressing with gzip: {}", e))
+            }
+            Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(payload)),
+        }
+    }
+
+    fn decompress(&self, payload: &[u8]) -> Result<Vec<u8>, String> {
+        match self {
+            Compression::None => Ok(payload.to_vec()),
+            Compression::Gzip => {
+                let mut decompressed = Vec::new();
+                GzDecoder::new(payload)
+                    .take(MAX_FRAME_LEN as u64 + 1)
+                    .read_to_end(&mut decompressed)
+                    .map_err(|e| format!("Error decompressing gzip: {}", e))?;
+                if decompressed.len() > MAX_FRAME_LEN {
+                    return Err("Decompressed message exceeds the limit".to_string());
+                }
+                Ok(decompressed)
+            }
+            Compression::Lz4 => {
+                // El tamaño original va al principio; se valida antes de reservar memoria
+                let size = payload
+                    .get(..4)
+                    .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as usize)
+                    .ok_or("Truncated lz4 payload")?;
+                if size > MAX_FRAME_LEN {
+                    return Err("Decompressed message exceeds the limit".to_string());
+                }
+                lz4_flex::decompress_size_prepended(payload)
+                    .map_err(|e| format!("Error decompressing lz4: {}", e))
+            }
+        }
+    }
+}
+
+/// Encoding of a connection, agreed by both peers in the handshake.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WireSettings {
+    /// Serialization format of the messages.
+    pub format: WireFormat,
+    /// Compression applied to large payloads.
+    pub compression: Compression,
+}
+
+impl Default for WireSettings {
+    /// Plain JSON lines, understood by every binary.
+    fn default() -> Self {
+        Self {
+            format: WireFormat::Json,
+            compression: Compression::None,
+        }
+    }
+}
+
+impl WireSettings {
+    /// Capabilities announced in the handshake: every format and compression this binary decodes.
+    pub fn capabilities() -> Vec<String> {
+        WireFormat::supported()
+            .iter()
+            .map(|format| format.capability())
+            .chain(Compression::supported().iter().map(|c| c.capability()))
+            .map(|c| c.to_string())
+            .collect()
+    }
+
+    /// Picks the best settings supported by both peers.
+    pub fn negotiate(remote_capabilities: &[String]) -> Self {
+        Self {
+            format: WireFormat::negotiate(remote_capabilities),
+            compression: Compression::negotiate(remote_capabilities),
+        }
+    }
+}
+
+/// Serializes [`NetworkMessage`]s to and from bytes in a given [`WireFormat`].
+pub trait MessageCodec {
+    /// Serializes a message.
+    fn encode(&self, message: &NetworkMessage) -> Result<Vec<u8>, String>;
+
+    /// Deserializes a message.
+    fn decode(&self, bytes: &[u8]) -> Result<NetworkMessage, String>;
+}
+
+/// [`MessageCodec`] for JSON, matching the serde representation of [`NetworkMessage`].
+pub struct JsonCodec;
+
+impl MessageCodec for JsonCodec {
+    fn encode(&self, message: &NetworkMessage) -> Result<Vec<u8>, String> {
+        serde_json::to_vec(message).map_err(|e| format!("Error serializing message: {}", e))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<NetworkMessage, String> {
+        serde_json::from_slice(bytes).map_err(|e| format!("Error deserializing message: {}", e))
+    }
+}
+
+/// [`MessageCodec`] for the protobuf envelope defined in `proto/coordinator.proto`.
+#[cfg(feature = "protobuf")]
+pub struct ProtobufCodec;
+
+#[cfg(feature = "protobuf")]
+impl MessageCodec for ProtobufCodec {
+    fn encode(&self, message: &NetworkMessage) -> Result<Vec<u8>, String> {
+        Ok(protobuf::encode(message)?.encode_to_vec())
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<NetworkMessage, String> {
+        let envelope = protobuf::proto::Envelope::decode(bytes)
+            .map_err(|e| format!("Error decoding protobuf envelope: {}", e))?;
+        protobuf::decode(envelope)
+    }
+}
+
+/// Returns the [`MessageCodec`] for a [`WireFormat`].
+pub fn codec_for(format: WireFormat) -> Result<Box<dyn MessageCodec>, String> {
+    match format {
+        WireFormat::Json => Ok(Box::new(JsonCodec)),
+        #[cfg(feature = "protobuf")]
+        WireFormat::Protobuf => Ok(Box::new(ProtobufCodec)),
+        #[cfg(not(feature = "protobuf"))]
+        WireFormat::Protobuf => Err("Protobuf format requires the `protobuf` feature".to_string()),
+    }
+}
+
+/// Serializes a message into the frames to be written to the socket, in order.
+///
+/// Uncompressed JSON is written as a single plain line so that binaries without frame support
+/// can still read it, and other uncompressed formats as a single v1 frame, since a peer that did
+/// not announce any compression may not know v2 frames. Otherwise the payload is compressed if
+/// it is large enough and split into v2 frames of at most [`MAX_CHUNK_LEN`] bytes.
+pub fn encode_frames(
+    settings: WireSettings,
+    message: &NetworkMessage,
+) -> Result<Vec<Vec<u8>>, String> {
+    let mut payload = codec_for(settings.format)?.encode(message)?;
+    if settings == WireSettings::default() {
+        payload.push(b'\n');
+        return Ok(vec![payload]);
+    }
+    if settings.compression == Compression::None {
+        if payload.len() > MAX_FRAME_LEN {
+            return Err(format!(
+                "Message of {} bytes exceeds the limit",
+                payload.len()
+            ));
+        }
+        let mut frame = Vec::with_capacity(payload.len() + 7);
+        frame.push(FRAME_MAGIC);
+        frame.push(PLAIN_FRAME_VERSION);
+        frame.push(settings.format.id());
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&payload);
+        return Ok(vec![frame]);
+    }
+
+    let compression = if payload.len() >= COMPRESSION_THRESHOLD {
+        settings.compression
+    } else {
+        Compression::None
+    };
+    let payload = compression.compress(&payload)?;
+    if payload.len() > MAX_FRAME_LEN {
+        return Err(format!(
+            "Message of {} bytes exceeds the limit",
+            payload.len()
+        ));
+    }
+
+    let mut chunks: Vec<&[u8]> = payload.chunks(MAX_CHUNK_LEN).collect();
+    if chunks.is_empty() {
+        chunks.push(&[]);
+    }
+    let last = chunks.len() - 1;
+    let frames = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut flags = compression.flag();
+            if i < last {
+                flags |= FLAG_MORE_CHUNKS;
+            }
+            let mut frame = Vec::with_capacity(chunk.len() + 8);
+            frame.push(FRAME_MAGIC);
+            frame.push(FRAME_VERSION);
+            frame.push(settings.format.id());
+            frame.push(flags);
+            frame.extend_from_slice(&(chunk.len() as u32).to_be_bytes());
+            frame.extend_from_slice(chunk);
+            frame
+        })
+        .collect();
+    Ok(frames)
+}
+
+/// Result of reading a frame from the socket.
+#[derive(Debug)]
+pub enum Frame {
+    /// A message was decoded.
+    Message(Box<NetworkMessage>),
+    /// A frame was read but its contents could not be decoded; the stream is still usable.
+    Undecodable(String),
+}
+
+/// Reads the next message from the socket, reassembling its chunks and decompressing it.
+///
+/// Returns `Ok(None)` when the connection is closed, and an error if the stream is no longer
+/// readable (I/O error or an unknown frame header).
+pub async fn read_frame<R>(reader: &mut R) -> Result<Option<Frame>, String>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let first = match reader.fill_buf().await {
+        Ok([]) => return Ok(None),
+        Ok(buf) => buf[0],
+        Err(e) => return Err(format!("Error reading from socket: {}", e)),
+    };
+
+    if first != FRAME_MAGIC {
+        let mut line = String::new();
+        return match reader.read_line(&mut line).await {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(decode_payload(
+                WireFormat::Json,
+                line.trim_end().as_bytes(),
+            ))),
+            Err(e) => Err(format!("Error reading from socket: {}", e)),
+        };
+    }
+
+    // Los chunks de un mismo mensaje se escriben siempre seguidos
+    let mut payload = Vec::new();
+    let (format_id, flags) = loop {
+        let (format_id, flags, len) = read_frame_header(reader).await?;
+        if payload.len() + len > MAX_FRAME_LEN {
+            return Err(format!(
+                "Message of more than {} bytes exceeds the limit",
+                payload.len() + len
+            ));
+        }
+        let start = payload.len();
+        payload.resize(start + len, 0);
+        reader
+            .read_exact(&mut payload[start..])
+            .await
+            .map_err(|e| format!("Error reading frame payload: {}", e))?;
+        if flags & FLAG_MORE_CHUNKS == 0 {
+            break (format_id, flags);
+        }
+    };
+
+    // El largo es conocido, así que un frame desconocido se puede saltear
+    let Some(format) = WireFormat::from_id(format_id) else {
+        return Ok(Some(Frame::Undecodable(format!(
+            "Unknown wire format {}",
+            format_id
+        ))));
+    };
+    let Some(compression) = Compression::from_flags(flags) else {
+        return Ok(Some(Frame::Undecodable(format!(
+            "Unknown compression flags {:#05b}",
+            flags
+        ))));
+    };
+    match compression.decompress(&payload) {
+        Ok(payload) => Ok(Some(decode_payload(format, &payload))),
+        Err(e) => Ok(Some(Frame::Undecodable(e))),
+    }
+}
+
+/// Reads a v1 or v2 frame header, returning the format, flags and payload length.
+async fn read_frame_header<R>(reader: &mut R) -> Result<(u8, u8, usize), String>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut prefix = [0u8; 3];
+    reader
+        .read_exact(&mut prefix)
+        .await
+        .map_err(|e| format!("Error reading frame header: {}", e))?;
+    if prefix[0] != FRAME_MAGIC {
+        return Err(format!("Invalid frame magic {:#04x}", prefix[0]));
+    }
+    let flags = match prefix[1] {
+        1 => 0,
+        2 => reader
+            .read_u8()
+            .await
+            .map_err(|e| format!("Error reading frame header: {}", e))?,
+        version => return Err(format!("Unsupported frame version {}", version)),
+    };
+    let len = reader
+        .read_u32()
+        .await
+        .map_err(|e| format!("Error reading frame header: {}", e))? as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(format!("Frame of {} bytes exceeds the limit", len));
+    }
+    Ok((prefix[2], flags, len))
+}
+
+fn decode_payload(format: WireFormat, payload: &[u8]) -> Frame {
+    match codec_for(format).and_then(|codec| codec.decode(payload)) {
+        Ok(message) => Frame::Message(Box::new(message)),
+        Err(e) => Frame::Undecodable(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::shared_messages::LeaderIdIs;
+    use tokio::io::BufReader;
+
+    async fn read_all(bytes: &[u8]) -> Vec<NetworkMessage> {
+        let mut reader = BufReader::new(bytes);
+        let mut messages = Vec::new();
+        while let Some(frame) = read_frame(&mut reader).await.expect("readable stream") {
+            match frame {
+                Frame::Message(message) => messages.push(*message),
+                Frame::Undecodable(e) => panic!("undecodable frame: {}", e),
+            }
+        }
+        messages
+    }
+
+    fn leader_id_is(leader_id: &str) -> NetworkMessage {
+        NetworkMessage::LeaderIdIs(LeaderIdIs {
+            leader_id: leader_id.to_string(),
+            epoch: 0,
+        })
+    }
+
+    fn encode(settings: WireSettings, message: &NetworkMessage) -> Vec<u8> {
+        encode_frames(settings, message).unwrap().concat()
+    }
+
+    #[tokio::test]
+    async fn legacy_json_lines_still_decode() {
+        let legacy = b"{\"type\":\"LeaderIdIs\",\"leader_id\":\"server_1\"}\n\
+            {\"type\":\"WhoIsLeader\",\"origin_addr\":\"127.0.0.1:8080\",\"user_id\":\"server_0\"}\n";
+        let messages = read_all(legacy).await;
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(&messages[0], NetworkMessage::LeaderIdIs(m) if m.leader_id == "server_1"));
+        assert!(matches!(&messages[1], NetworkMessage::WhoIsLeader(m) if m.user_id == "server_0"));
+    }
+
+    #[tokio::test]
+    async fn uncompressed_json_is_a_plain_line() {
+        let frames = encode_frames(WireSettings::default(), &leader_id_is("server_2")).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].first(), Some(&b'{'));
+        assert_eq!(frames[0].last(), Some(&b'\n'));
+        assert!(
+            matches!(&read_all(&frames[0]).await[0], NetworkMessage::LeaderIdIs(m) if m.leader_id == "server_2")
+        );
+    }
+
+    #[tokio::test]
+    async fn v1_frames_still_decode() {
+        let payload = serde_json::to_vec(&leader_id_is("server_3")).unwrap();
+        let mut bytes = vec![FRAME_MAGIC, PLAIN_FRAME_VERSION, WireFormat::Json.id()];
+        bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&payload);
+        assert!(
+            matches!(&read_all(&bytes).await[0], NetworkMessage::LeaderIdIs(m) if m.leader_id == "server_3")
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_binary_format_is_skipped() {
+        let mut bytes = vec![FRAME_MAGIC, FRAME_VERSION, 42, 0, 0, 0, 0, 3, 1, 2, 3];
+        bytes.extend_from_slice(b"{\"type\":\"NoRecoveredInfo\"}\n");
+        let mut reader = BufReader::new(bytes.as_slice());
+        assert!(matches!(
+            read_frame(&mut reader).await,
+            Ok(Some(Frame::Undecodable(_)))
+        ));
+        assert!(matches!(
+            read_frame(&mut reader).await,
+            Ok(Some(Frame::Message(message))) if matches!(*message, NetworkMessage::NoRecoveredInfo)
+        ));
+    }
+
+    #[tokio::test]
+    async fn large_messages_are_compressed() {
+        let leader_id = "server_".repeat(50_000);
+        let message = leader_id_is(&leader_id);
+        let plain_len = serde_json::to_vec(&message).unwrap().len();
+
+        for compression in [Compression::Gzip, Compression::Lz4] {
+            let settings = WireSettings {
+                format: WireFormat::Json,
+                compression,
+            };
+            let frames = encode_frames(settings, &message).unwrap();
+            assert!(frames.concat().len() < plain_len / 10);
+
+            let mut bytes = frames.concat();
+            bytes.extend(encode(settings, &leader_id_is("server_0")));
+            let messages = read_all(&bytes).await;
+            assert_eq!(messages.len(), 2);
+            assert!(
+                matches!(&messages[0], NetworkMessage::LeaderIdIs(m) if m.leader_id == leader_id)
+            );
+            assert!(
+                matches!(&messages[1], NetworkMessage::LeaderIdIs(m) if m.leader_id == "server_0")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn large_payloads_are_split_in_chunks() {
+        // Datos poco comprimibles, para que el payload comprimido siga siendo grande
+        let mut seed: u64 = 42;
+        let leader_id: String = (0..400_000)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                char::from(b'a' + (seed >> 59) as u8)
+            })
+            .collect();
+        let settings = WireSettings {
+            format: WireFormat::Json,
+            compression: Compression::Lz4,
+        };
+        let frames = encode_frames(settings, &leader_id_is(&leader_id)).unwrap();
+        assert!(frames.len() > 1);
+        assert!(frames.iter().all(|f| f.len() <= MAX_CHUNK_LEN + 8));
+        assert!(
+            matches!(&read_all(&frames.concat()).await[0], NetworkMessage::LeaderIdIs(m) if m.leader_id == leader_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn v1_and_v2_frames_mix_on_one_stream() {
+        let payload = serde_json::to_vec(&leader_id_is("server_1")).unwrap();
+        let mut bytes = vec![FRAME_MAGIC, PLAIN_FRAME_VERSION, WireFormat::Json.id()];
+        bytes.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        bytes.extend_from_slice(&payload);
+        let compressed = WireSettings {
+            format: WireFormat::Json,
+            compression: Compression::Gzip,
+        };
+        let leader_id = "server_".repeat(1_000);
+        let v2 = encode(compressed, &leader_id_is(&leader_id));
+        assert_eq!(v2[1], FRAME_VERSION);
+        bytes.extend(v2);
+        bytes.extend(encode(WireSettings::default(), &leader_id_is("server_2")));
+
+        let messages = read_all(&bytes).await;
+        assert_eq!(messages.len(), 3);
+        assert!(matches!(&messages[0], NetworkMessage::LeaderIdIs(m) if m.leader_id == "server_1"));
+        assert!(matches!(&messages[1], NetworkMessage::LeaderIdIs(m) if m.leader_id == leader_id));
+        assert!(matches!(&messages[2], NetworkMessage::LeaderIdIs(m) if m.leader_id == "server_2"));
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[tokio::test]
+    async fn uncompressed_binary_frames_use_the_v1_header() {
+        let plain = WireSettings {
+            format: WireFormat::Protobuf,
+            compression: Compression::None,
+        };
+        // Sin compresión no hay chunks: todo el mensaje va en un único frame v1
+        let leader_id = "server_".repeat(20_000);
+        let frames = encode_frames(plain, &leader_id_is(&leader_id)).unwrap();
+        assert_eq!(frames.len(), 1);
+        let frame = &frames[0];
+        assert_eq!(
+            frame[..3],
+            [FRAME_MAGIC, PLAIN_FRAME_VERSION, WireFormat::Protobuf.id()]
+        );
+        let len = u32::from_be_bytes([frame[3], frame[4], frame[5], frame[6]]) as usize;
+        assert_eq!(len, frame.len() - 7);
+        assert!(
+            matches!(&read_all(frame).await[0], NetworkMessage::LeaderIdIs(m) if m.leader_id == leader_id)
+        );
+
+        let compressed = WireSettings {
+            format: WireFormat::Protobuf,
+            compression: Compression::Lz4,
+        };
+        assert_eq!(
+            encode(compressed, &leader_id_is(&leader_id))[1],
+            FRAME_VERSION
+        );
+    }
+
+    #[cfg(feature = "protobuf")]
+    #[tokio::test]
+    async fn json_and_protobuf_frames_mix_on_one_stream() {
+        use crate::messages::shared_messages::WhoIsLeader;
+
+        let who = NetworkMessage::WhoIsLeader(WhoIsLeader {
+            origin_addr: "127.0.0.1:8081".parse().unwrap(),
+            user_id: "server_1".to_string(),
+        });
+        let protobuf = WireSettings {
+            format: WireFormat::Protobuf,
+            compression: Compression::Lz4,
+        };
+        let mut bytes = encode(WireSettings::default(), &who);
+        bytes.extend(encode(protobuf, &who));
+        bytes.extend(encode(protobuf, &leader_id_is("server_3")));
+
+        let messages = read_all(&bytes).await;
+        assert_eq!(messages.len(), 3);
+        for message in &messages[..2] {
+            assert!(matches!(message, NetworkMessage::WhoIsLeader(m) if m.user_id == "server_1"));
+        }
+        assert!(matches!(&messages[2], NetworkMessage::LeaderIdIs(m) if m.leader_id == "server_3"));
+    }
+
+    #[test]
+    fn negotiation_falls_back_to_plain_json() {
+        assert_eq!(
+            WireSettings::negotiate(&["json".to_string()]),
+            WireSettings::default()
+        );
+        assert_eq!(WireSettings::negotiate(&[]), WireSettings::default());
+        assert_eq!(
+            WireSettings::negotiate(&["json".to_string(), "gzip".to_string()]).compression,
+            Compression::Gzip
+        );
+    }
+}
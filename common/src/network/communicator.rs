@@ -1,18 +1,40 @@
 use crate::messages::shared_messages::NetworkMessage;
-use crate::messages::shared_messages::Shutdown;
+use crate::messages::shared_messages::{ReceivedFrom, Shutdown};
+use crate::network::faults::FaultConfig;
+use crate::network::handshake::local_handshake;
+use crate::network::outbound::{OutboundConfig, OutboundMetrics, OutboundStats};
 use crate::network::peer_types::PeerType;
+use crate::network::reliable::{ReliableConfig, UnacknowledgedMessages};
+use crate::network::stream::PeerStream;
 use crate::network::tcp_receiver::TCPReceiver;
-use crate::network::tcp_sender::TCPSender;
+use crate::network::tcp_sender::{SetFaults, TCPSender};
 use actix::prelude::*;
 
 use std::net::SocketAddr;
 use std::sync::Arc;
 use tokio::io::split;
-use tokio::net::TcpStream;
 
 /// The `Communicator` struct manages a TCP connection between two peers,
 /// handling both sending and receiving of [`NetworkMessage`]s using Actix actors.
 ///
+/// A [`Handshake`](crate::messages::shared_messages::Handshake) with the local protocol version
+/// is sent as soon as the connection is created.
+///
+/// Faults set in [`FAULTS_ENV_VAR`](crate::network::faults::FAULTS_ENV_VAR) are injected on the
+/// outgoing messages of every connection.
+///
+/// Connections with clients, restaurants and deliveries exchange a
+/// [`KeepAlive`](crate::messages::shared_messages::KeepAlive) while idle, and the destination
+/// actor receives a `ConnectionClosed` if the peer goes silent.
+///
+/// Outgoing messages wait in a bounded queue configured by
+/// [`OUTBOUND_QUEUE_ENV_VAR`](crate::network::outbound::OUTBOUND_QUEUE_ENV_VAR). If the queue
+/// overflows under the `disconnect` policy, the destination actor receives a `ConnectionClosed`.
+///
+/// The messages set in [`RELIABLE_DELIVERY_ENV_VAR`](crate::network::reliable::RELIABLE_DELIVERY_ENV_VAR)
+/// are resent until the peer acknowledges them. Those still unacknowledged when the connection
+/// closes can be taken with [`Communicator::take_unacknowledged`] to send them again.
+///
 /// ## Type Parameters
 /// - `A`: The actor type that will receive incoming [`NetworkMessage`]s.
 #[derive(Debug)]
@@ -30,32 +52,100 @@ where
     pub receiver: Option<Arc<Addr<TCPReceiver<A>>>>,
     /// The type of the remote peer.
     pub peer_type: PeerType, // Enum: Client, Restaurant, Delivery, Coordinator, Gateway
+    /// The counters of the outbound queue.
+    pub outbound: OutboundMetrics,
+    /// The messages sent with acknowledged delivery and not acknowledged yet.
+    pub unacknowledged: UnacknowledgedMessages,
 }
 
 impl<A> Communicator<A>
 where
     A: Actor<Context = Context<A>> + Handler<NetworkMessage>,
 {
-    /// Creates a new `Communicator` for a TCP connection.
+    /// Creates a new `Communicator` for a connection.
+    ///
+    /// ## Arguments
+    /// * `tcp_stream` - The established connection, over TCP or in memory.
+    /// * `destination_address` - The Actix address of the actor that will receive messages.
+    /// * `peer_type` - The type of the remote peer.
+    pub fn new(
+        tcp_stream: impl Into<PeerStream>,
+        destination_address: Addr<A>,
+        peer_type: PeerType,
+    ) -> Self {
+        Self::open(tcp_stream.into(), destination_address, peer_type, None)
+    }
+
+    /// Creates a new `Communicator` for a connection whose messages the destination actor
+    /// receives as [`ReceivedFrom`], tagged with the address of the connection.
     ///
     /// ## Arguments
-    /// * `tcp_stream` - The established TCP stream.
+    /// * `tcp_stream` - The established connection, over TCP or in memory.
     /// * `destination_address` - The Actix address of the actor that will receive messages.
     /// * `peer_type` - The type of the remote peer.
-    pub fn new(tcp_stream: TcpStream, destination_address: Addr<A>, peer_type: PeerType) -> Self {
+    pub fn new_tagged(
+        tcp_stream: impl Into<PeerStream>,
+        destination_address: Addr<A>,
+        peer_type: PeerType,
+    ) -> Self
+    where
+        A: Handler<ReceivedFrom>,
+    {
+        let tagged = destination_address.clone().recipient();
+        Self::open(
+            tcp_stream.into(),
+            destination_address,
+            peer_type,
+            Some(tagged),
+        )
+    }
+
+    fn open(
+        tcp_stream: PeerStream,
+        destination_address: Addr<A>,
+        peer_type: PeerType,
+        tagged: Option<Recipient<ReceivedFrom>>,
+    ) -> Self {
         let local_address = tcp_stream
             .local_addr()
             .expect("Failed to get local address");
         let peer_address = tcp_stream.peer_addr().expect("Failed to get peer address");
         let (read_half, write_half) = split(tcp_stream);
+        let unacknowledged = UnacknowledgedMessages::default();
+        let sender = TCPSender::new(
+            write_half,
+            OutboundConfig::from_env(),
+            peer_address,
+            destination_address.clone().recipient(),
+            ReliableConfig::from_env(),
+            unacknowledged.clone(),
+        );
+        let outbound = sender.metrics();
+        let sender = sender.start();
+        // El handshake es siempre el primer mensaje de la conexión
+        sender.do_send(NetworkMessage::Handshake(local_handshake()));
+        if let Some(faults) = FaultConfig::from_env() {
+            sender.do_send(SetFaults(faults));
+        }
+        let mut receiver = TCPReceiver::new(
+            read_half,
+            peer_address,
+            destination_address,
+            sender.clone(),
+            peer_type.is_user(),
+        );
+        if let Some(tagged) = tagged {
+            receiver = receiver.tagged(tagged);
+        }
+        let receiver = receiver.start();
         Self {
             local_address,
             peer_address,
-            sender: Some(Arc::new(TCPSender::new(write_half).start())),
-            receiver: Some(Arc::new(
-                TCPReceiver::new(read_half, peer_address, destination_address).start(),
-            )),
+            sender: Some(Arc::new(sender)),
+            receiver: Some(Arc::new(receiver)),
             peer_type,
+            outbound,
+            unacknowledged,
         }
     }
 }
@@ -64,6 +154,24 @@ impl<A> Communicator<A>
 where
     A: Actor<Context = Context<A>> + Handler<NetworkMessage>,
 {
+    /// Changes the faults injected on the outgoing messages of this connection.
+    pub fn set_faults(&self, config: FaultConfig) {
+        if let Some(sender) = &self.sender {
+            sender.do_send(SetFaults(config));
+        }
+    }
+
+    /// Returns the current depth and drop counters of the outbound queue.
+    pub fn outbound_stats(&self) -> OutboundStats {
+        self.outbound.snapshot()
+    }
+
+    /// Takes the messages sent with acknowledged delivery that the peer did not acknowledge,
+    /// in the order they were sent, so they can be sent again over another connection.
+    pub fn take_unacknowledged(&self) -> Vec<NetworkMessage> {
+        self.unacknowledged.take()
+    }
+
     /// Shuts down the sender and receiver actors, closing the connection.
     pub fn shutdown(&mut self) {
         if let Some(sender) = self.sender.take() {
@@ -1,26 +1,74 @@
-use crate::network::peer_types::PeerType;
+use crate::constants::{BASE_PORT, NUM_COORDINATORS, SERVER_IP_ADDRESS};
+use crate::network::peer_types::PeerIdentity;
+use crate::network::stream::PeerStream;
 use std::collections::HashMap;
-use std::net::SocketAddr;
-use tokio::io::AsyncWriteExt;
-use tokio::net::TcpStream;
+use std::net::{SocketAddr, ToSocketAddrs};
 use tokio::time::{Duration, timeout};
 
+/// Environment variable with the port of the first coordinator, to run a cluster on other
+/// ports than the default ones. The coordinators listen on [`NUM_COORDINATORS`] consecutive
+/// ports starting there.
+pub const BASE_PORT_ENV_VAR: &str = "BASE_PORT";
+
+/// Reads the port of the first coordinator from [`BASE_PORT_ENV_VAR`], or [`BASE_PORT`] if it
+/// is not set or is invalid.
+pub fn base_port_from_env() -> u16 {
+    match std::env::var(BASE_PORT_ENV_VAR) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .ok()
+            .filter(|port| *port > 0)
+            .unwrap_or_else(|| {
+                eprintln!("Ignoring invalid {}: '{}'", BASE_PORT_ENV_VAR, value);
+                BASE_PORT
+            }),
+        Err(_) => BASE_PORT,
+    }
+}
+
+/// Parses an address written as `host:port`, resolving `host` if it is a name instead of an
+/// IP, e.g. the name of a container.
+pub fn parse_addr(value: &str) -> Result<SocketAddr, String> {
+    let value = value.trim();
+    if let Ok(addr) = value.parse() {
+        return Ok(addr);
+    }
+    value
+        .to_socket_addrs()
+        .map_err(|e| format!("Invalid address {}: {}", value, e))?
+        .next()
+        .ok_or_else(|| format!("{} does not resolve to any address", value))
+}
+
+/// Returns the addresses of the coordinators, by index, on the ports set in
+/// [`BASE_PORT_ENV_VAR`].
+pub fn coordinator_addresses() -> Vec<SocketAddr> {
+    let base_port = base_port_from_env();
+    (0..NUM_COORDINATORS)
+        .map(|i| {
+            format!("{}:{}", SERVER_IP_ADDRESS, base_port + i)
+                .parse()
+                .expect("Invalid IP address")
+        })
+        .collect()
+}
+
 pub async fn connect_to_all(
     servers: Vec<SocketAddr>,
-    peer_type: PeerType,
-) -> HashMap<SocketAddr, TcpStream> {
+    identity: &PeerIdentity,
+) -> HashMap<SocketAddr, PeerStream> {
     let mut connections = HashMap::new();
 
     for addr in servers {
-        match TcpStream::connect(addr).await {
+        match PeerStream::connect(addr, identity).await {
             Ok(mut stream) => {
-                // Enviar solo el byte del tipo de peer
-                let type_byte = [peer_type.to_u8()];
-                if let Err(e) = stream.write_all(&type_byte).await {
-                    eprintln!("Failed to send peer type to {}: {}", addr, e);
+                // Enviar el tipo de peer y el ID del usuario
+                if let Err(e) = identity.write_to(&mut stream).await {
+                    eprintln!("Failed to send identity to {}: {}", addr, e);
                     continue;
                 }
-                println!("Connected to {} as {:?}", addr, peer_type);
+                println!("Connected to {} as {:?}", addr, identity.peer_type);
                 connections.insert(addr, stream);
             }
             Err(e) => {
@@ -32,17 +80,16 @@ pub async fn connect_to_all(
     connections
 }
 
-pub async fn connect_some(servers: Vec<SocketAddr>, peer_type: PeerType) -> Option<TcpStream> {
+pub async fn connect_some(servers: Vec<SocketAddr>, identity: &PeerIdentity) -> Option<PeerStream> {
     for addr in servers {
-        match try_to_connect(addr).await {
+        match try_to_connect(addr, identity).await {
             Some(mut stream) => {
-                // Enviar solo el byte del tipo de peer
-                let type_byte = [peer_type.to_u8()];
-                if let Err(e) = stream.write_all(&type_byte).await {
-                    eprintln!("Failed to send peer type to {}: {}", addr, e);
+                // Enviar el tipo de peer y el ID del usuario
+                if let Err(e) = identity.write_to(&mut stream).await {
+                    eprintln!("Failed to send identity to {}: {}", addr, e);
                     continue;
                 }
-                println!("Connected to {} as {:?}", addr, peer_type);
+                println!("Connected to {} as {:?}", addr, identity.peer_type);
                 return Some(stream);
             }
             None => {
@@ -54,15 +101,14 @@ pub async fn connect_some(servers: Vec<SocketAddr>, peer_type: PeerType) -> Opti
     None
 }
 
-pub async fn connect_one(addr: SocketAddr, peer_type: PeerType) -> Option<TcpStream> {
-    if let Some(mut stream) = try_to_connect(addr).await {
-        // Enviar solo el byte del tipo de peer
-        let type_byte = [peer_type.to_u8()];
-        if let Err(e) = stream.write_all(&type_byte).await {
-            eprintln!("Failed to send peer type to {}: {}", addr, e);
+pub async fn connect_one(addr: SocketAddr, identity: &PeerIdentity) -> Option<PeerStream> {
+    if let Some(mut stream) = try_to_connect(addr, identity).await {
+        // Enviar el tipo de peer y el ID del usuario
+        if let Err(e) = identity.write_to(&mut stream).await {
+            eprintln!("Failed to send identity to {}: {}", addr, e);
             return None;
         }
-        println!("Connected to {} as {:?}", addr, peer_type);
+        println!("Connected to {} as {:?}", addr, identity.peer_type);
         Some(stream)
     } else {
         eprintln!("Failed to connect to {}", addr);
@@ -70,19 +116,18 @@ pub async fn connect_one(addr: SocketAddr, peer_type: PeerType) -> Option<TcpStr
     }
 }
 
-async fn try_to_connect(server_addr: SocketAddr) -> Option<TcpStream> {
-    TcpStream::connect(server_addr).await.ok()
+async fn try_to_connect(server_addr: SocketAddr, identity: &PeerIdentity) -> Option<PeerStream> {
+    PeerStream::connect(server_addr, identity).await.ok()
 }
 
-pub async fn reconnect(servers: Vec<SocketAddr>, peer_type: PeerType) -> Option<TcpStream> {
+pub async fn reconnect(servers: Vec<SocketAddr>, identity: &PeerIdentity) -> Option<PeerStream> {
     for addr in servers {
         println!("Trying to connect to {}", addr);
-        match timeout(Duration::from_secs(2), TcpStream::connect(addr)).await {
+        match timeout(Duration::from_secs(2), PeerStream::connect(addr, identity)).await {
             Ok(Ok(mut stream)) => {
-                // Enviar solo el byte del tipo de peer
-                let type_byte = [peer_type.to_u8()];
-                if let Err(e) = stream.write_all(&type_byte).await {
-                    println!("Failed to send peer type to {}: {}", addr, e);
+                // Enviar el tipo de peer y el ID del usuario
+                if let Err(e) = identity.write_to(&mut stream).await {
+                    println!("Failed to send identity to {}: {}", addr, e);
                     continue;
                 }
                 println!("Successfully connected to {}", addr);
@@ -0,0 +1,254 @@
+use crate::messages::shared_messages::NetworkMessage;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Environment variable used to inject network faults in every connection of a process.
+///
+/// Its value is a comma-separated list of `key=value` pairs, e.g.
+/// `drop=0.1,delay=200,duplicate=0.05,reorder=0.1,seed=42,only=AcceptedOrder|LeaderIdIs`.
+pub const FAULTS_ENV_VAR: &str = "NETWORK_FAULTS";
+
+/// Faults injected on the outgoing messages of a connection.
+///
+/// Handshakes are never affected, so the connection can always be established.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct FaultConfig {
+    /// Probability of dropping a message.
+    pub drop_rate: f64,
+    /// Delay added to every affected message, in milliseconds.
+    pub delay_ms: u64,
+    /// Probability of sending a message twice.
+    pub duplicate_rate: f64,
+    /// Probability of holding a message back and sending it after the next one.
+    pub reorder_rate: f64,
+    /// Seed for the random decisions. Runs with the same seed and the same traffic inject
+    /// the same faults.
+    pub seed: Option<u64>,
+    /// Types of the affected messages (e.g. `AcceptedOrder`). Every message if empty.
+    pub only: Vec<String>,
+}
+
+impl FaultConfig {
+    /// Reads the faults from [`FAULTS_ENV_VAR`].
+    ///
+    /// Returns `None` if the variable is not set, is invalid, or injects no faults.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(FAULTS_ENV_VAR).ok()?;
+        match Self::parse(&value) {
+            Ok(config) if config.is_active() => Some(config),
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("[Faults] Ignoring {}: {}", FAULTS_ENV_VAR, e);
+                None
+            }
+        }
+    }
+
+    /// Parses a list of `key=value` pairs as described in [`FAULTS_ENV_VAR`].
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, got '{}'", pair))?;
+            let invalid = || format!("Invalid value for {}: '{}'", key, value);
+            match key.trim() {
+                "drop" => config.drop_rate = parse_rate(value).ok_or_else(invalid)?,
+                "delay" => config.delay_ms = value.trim().parse().map_err(|_| invalid())?,
+                "duplicate" => config.duplicate_rate = parse_rate(value).ok_or_else(invalid)?,
+                "reorder" => config.reorder_rate = parse_rate(value).ok_or_else(invalid)?,
+                "seed" => config.seed = Some(value.trim().parse().map_err(|_| invalid())?),
+                "only" => {
+                    config.only = value
+                        .split('|')
+                        .map(|kind| kind.trim().to_string())
+                        .filter(|kind| !kind.is_empty())
+                        .collect()
+                }
+                other => return Err(format!("Unknown fault '{}'", other)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Returns whether this configuration injects any fault.
+    pub fn is_active(&self) -> bool {
+        self.drop_rate > 0.0
+            || self.delay_ms > 0
+            || self.duplicate_rate > 0.0
+            || self.reorder_rate > 0.0
+    }
+
+    /// Returns whether `message` is affected by the faults.
+    fn applies_to(&self, message: &NetworkMessage) -> bool {
+        if matches!(message, NetworkMessage::Handshake(_)) {
+            return false;
+        }
+        if self.only.is_empty() {
+            return true;
+        }
+        let kind = message_kind(message);
+        self.only.contains(&kind)
+    }
+}
+
+/// Parses a probability between `0.0` and `1.0`.
+fn parse_rate(value: &str) -> Option<f64> {
+    value
+        .trim()
+        .parse::<f64>()
+        .ok()
+        .filter(|rate| (0.0..=1.0).contains(rate))
+}
+
+/// Returns the name of the [`NetworkMessage`] variant, e.g. `AcceptedOrder`.
+///
+/// Messages sent with acknowledged delivery are named after the message they wrap.
+pub fn message_kind(message: &NetworkMessage) -> String {
+    if let NetworkMessage::Reliable(reliable) = message {
+        return message_kind(&reliable.message);
+    }
+    let debug = format!("{:?}", message);
+    debug
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// What to do with an outgoing message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FaultDecision {
+    /// The message is not sent.
+    pub drop: bool,
+    /// The message is sent twice.
+    pub duplicate: bool,
+    /// The message is sent after the next one.
+    pub reorder: bool,
+    /// Time to wait before sending the message.
+    pub delay: Duration,
+}
+
+impl FaultDecision {
+    /// The decision for messages that are not affected by any fault.
+    pub const DELIVER: FaultDecision = FaultDecision {
+        drop: false,
+        duplicate: false,
+        reorder: false,
+        delay: Duration::ZERO,
+    };
+}
+
+/// Decides which faults to inject on each outgoing message, following a [`FaultConfig`].
+#[derive(Debug)]
+pub struct FaultInjector {
+    /// The faults to inject.
+    pub config: FaultConfig,
+    /// Source of the random decisions.
+    rng: StdRng,
+}
+
+impl FaultInjector {
+    /// Creates a new `FaultInjector`, seeded with the configured seed if there is one.
+    pub fn new(config: FaultConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self { config, rng }
+    }
+
+    /// Decides which faults to inject on `message`.
+    pub fn decide(&mut self, message: &NetworkMessage) -> FaultDecision {
+        if !self.config.applies_to(message) {
+            return FaultDecision::DELIVER;
+        }
+        // Se sortean siempre las tres, para que la secuencia no dependa del resultado
+        let drop = self.rng.r#gen::<f64>() < self.config.drop_rate;
+        let duplicate = self.rng.r#gen::<f64>() < self.config.duplicate_rate;
+        let reorder = self.rng.r#gen::<f64>() < self.config.reorder_rate;
+        FaultDecision {
+            drop,
+            duplicate: !drop && duplicate,
+            reorder: !drop && reorder,
+            delay: Duration::from_millis(self.config.delay_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::coordinatormanager_messages::Ping;
+    use crate::messages::shared_messages::RetryLater;
+    use crate::network::handshake::local_handshake;
+    use std::net::SocketAddr;
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:8080".parse().unwrap()
+    }
+
+    fn ping() -> NetworkMessage {
+        NetworkMessage::Ping(Ping { from: addr() })
+    }
+
+    #[test]
+    fn parses_every_fault() {
+        let config = FaultConfig::parse(
+            "drop=0.1, delay=200,duplicate=0.05,reorder=0.2,seed=42,only=Ping|AcceptedOrder",
+        )
+        .unwrap();
+        assert_eq!(
+            config,
+            FaultConfig {
+                drop_rate: 0.1,
+                delay_ms: 200,
+                duplicate_rate: 0.05,
+                reorder_rate: 0.2,
+                seed: Some(42),
+                only: vec!["Ping".to_string(), "AcceptedOrder".to_string()],
+            }
+        );
+        assert!(config.is_active());
+    }
+
+    #[test]
+    fn rejects_invalid_faults() {
+        assert!(FaultConfig::parse("drop=1.5").is_err());
+        assert!(FaultConfig::parse("lose=0.1").is_err());
+        assert!(FaultConfig::parse("delay").is_err());
+        assert!(!FaultConfig::parse("seed=1").unwrap().is_active());
+    }
+
+    #[test]
+    fn same_seed_injects_same_faults() {
+        let config = FaultConfig::parse("drop=0.3,duplicate=0.3,reorder=0.3,seed=7").unwrap();
+        let mut first = FaultInjector::new(config.clone());
+        let mut second = FaultInjector::new(config);
+        for _ in 0..100 {
+            assert_eq!(first.decide(&ping()), second.decide(&ping()));
+        }
+    }
+
+    #[test]
+    fn only_affects_listed_messages() {
+        let config = FaultConfig::parse("drop=1,only=Ping").unwrap();
+        let mut injector = FaultInjector::new(config);
+        assert!(injector.decide(&ping()).drop);
+        let retry = NetworkMessage::RetryLater(RetryLater {
+            origin_addr: addr(),
+            retry_after_millis: 0,
+        });
+        assert_eq!(injector.decide(&retry), FaultDecision::DELIVER);
+    }
+
+    #[test]
+    fn never_affects_handshakes() {
+        let config = FaultConfig::parse("drop=1").unwrap();
+        let mut injector = FaultInjector::new(config);
+        let handshake = NetworkMessage::Handshake(local_handshake());
+        assert_eq!(injector.decide(&handshake), FaultDecision::DELIVER);
+    }
+}
@@ -0,0 +1,157 @@
+use crate::constants::GRPC_PORT_OFFSET;
+use crate::logger::Logger;
+use crate::messages::shared_messages::NetworkMessage;
+use crate::network::protobuf::{decode, encode, proto};
+use crate::network::transport::PeerTransport;
+use actix::prelude::*;
+use colored::Color;
+use proto::coordinator_transport_client::CoordinatorTransportClient;
+use proto::coordinator_transport_server::{CoordinatorTransport, CoordinatorTransportServer};
+use std::net::SocketAddr;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tonic::transport::{Channel, Server};
+use tonic::{Request, Response, Status};
+
+/// Returns the address where the gRPC endpoint of the coordinator listening on `addr` is served.
+pub fn grpc_address(addr: SocketAddr) -> SocketAddr {
+    let mut grpc_addr = addr;
+    grpc_addr.set_port(addr.port() + GRPC_PORT_OFFSET);
+    grpc_addr
+}
+
+/// The `GrpcTransport` struct is a [`PeerTransport`] that delivers [`NetworkMessage`]s to
+/// another coordinator through its gRPC endpoint.
+///
+/// ## Responsibilities
+/// - Queues outgoing messages and delivers them in order from a background task.
+/// - Connects lazily to the peer and reconnects on the next message after a failure.
+#[derive(Debug)]
+pub struct GrpcTransport {
+    /// The ring address of this coordinator.
+    pub local_address: SocketAddr,
+    /// The ring address of the remote coordinator.
+    pub peer_address: SocketAddr,
+    /// Queue of messages pending delivery.
+    sender: Option<UnboundedSender<NetworkMessage>>,
+}
+
+impl GrpcTransport {
+    /// Creates a new `GrpcTransport` and spawns its delivery task.
+    ///
+    /// ## Arguments
+    /// * `local_address` - The ring address of this coordinator.
+    /// * `peer_address` - The ring address of the remote coordinator.
+    pub fn new(local_address: SocketAddr, peer_address: SocketAddr) -> Self {
+        let (sender, mut receiver) = unbounded_channel::<NetworkMessage>();
+        let logger = Logger::new("GRPC TRANSPORT", Color::BrightBlue);
+        let endpoint = format!("http://{}", grpc_address(peer_address));
+
+        tokio::spawn(async move {
+            let mut client: Option<CoordinatorTransportClient<Channel>> = None;
+            while let Some(message) = receiver.recv().await {
+                let envelope = match encode(&message) {
+                    Ok(envelope) => proto::Envelope {
+                        from: local_address.to_string(),
+                        ..envelope
+                    },
+                    Err(e) => {
+                        logger.error(format!("Error encoding message: {}", e));
+                        continue;
+                    }
+                };
+                if client.is_none() {
+                    match CoordinatorTransportClient::connect(endpoint.clone()).await {
+                        Ok(connected) => client = Some(connected),
+                        Err(e) => {
+                            logger.warn(format!("Could not connect to {}: {}", endpoint, e));
+                            continue;
+                        }
+                    }
+                }
+                if let Some(connected) = client.as_mut()
+                    && let Err(e) = connected.deliver(envelope).await
+                {
+                    logger.warn(format!("Error delivering to {}: {}", endpoint, e));
+                    // Se vuelve a conectar con el próximo mensaje
+                    client = None;
+                }
+            }
+        });
+
+        Self {
+            local_address,
+            peer_address,
+            sender: Some(sender),
+        }
+    }
+}
+
+impl PeerTransport for GrpcTransport {
+    fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    fn peer_address(&self) -> SocketAddr {
+        self.peer_address
+    }
+
+    fn send(&self, message: NetworkMessage) -> Result<(), String> {
+        match &self.sender {
+            Some(sender) => sender
+                .send(message)
+                .map_err(|e| format!("Failed to send to {}: {}", self.peer_address, e)),
+            None => Err(format!("Transport to {} already closed", self.peer_address)),
+        }
+    }
+
+    fn shutdown(&mut self) {
+        // Al soltar el sender termina la tarea de envío
+        self.sender.take();
+    }
+}
+
+/// gRPC service that forwards every received envelope to a local actor.
+struct CoordinatorTransportService {
+    destination: Recipient<NetworkMessage>,
+    logger: Logger,
+}
+
+#[tonic::async_trait]
+impl CoordinatorTransport for CoordinatorTransportService {
+    async fn deliver(
+        &self,
+        request: Request<proto::Envelope>,
+    ) -> Result<Response<proto::Ack>, Status> {
+        let envelope = request.into_inner();
+        let from = envelope.from.clone();
+        match decode(envelope) {
+            Ok(message) => {
+                self.destination.do_send(message);
+                Ok(Response::new(proto::Ack {}))
+            }
+            Err(e) => {
+                self.logger
+                    .warn(format!("Invalid envelope from {}: {}", from, e));
+                Err(Status::invalid_argument(e))
+            }
+        }
+    }
+}
+
+/// Serves the gRPC endpoint of the coordinator listening on `addr`, forwarding every
+/// received message to `destination`.
+///
+/// ## Arguments
+/// * `addr` - The ring address of this coordinator; the endpoint binds to [`grpc_address`].
+/// * `destination` - The actor that handles incoming [`NetworkMessage`]s.
+pub async fn serve(addr: SocketAddr, destination: Recipient<NetworkMessage>) -> Result<(), String> {
+    let service = CoordinatorTransportService {
+        destination,
+        logger: Logger::new("GRPC TRANSPORT", Color::BrightBlue),
+    };
+    Server::builder()
+        .add_service(CoordinatorTransportServer::new(service))
+        .serve(grpc_address(addr))
+        .await
+        .map_err(|e| e.to_string())
+}
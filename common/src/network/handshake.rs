@@ -0,0 +1,144 @@
+use crate::constants::{MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use crate::messages::shared_messages::{Handshake, IncompatibleVersion};
+use crate::network::codec::WireSettings;
+use std::net::SocketAddr;
+
+/// Capability announced by binaries that send a
+/// [`KeepAlive`](crate::messages::shared_messages::KeepAlive) over idle user connections.
+pub const KEEPALIVE_CAPABILITY: &str = "keepalive";
+
+/// Capability announced by binaries that answer a
+/// [`ReliableMessage`](crate::messages::shared_messages::ReliableMessage) with a
+/// [`MessageAck`](crate::messages::shared_messages::MessageAck).
+pub const ACK_CAPABILITY: &str = "ack";
+
+/// Builds the [`Handshake`] this node sends as the first message of every connection.
+///
+/// The capabilities list the wire formats and compressions this binary can decode, and
+/// whether it sends keepalives and acknowledges reliable messages.
+pub fn local_handshake() -> Handshake {
+    let mut capabilities = WireSettings::capabilities();
+    capabilities.push(KEEPALIVE_CAPABILITY.to_string());
+    capabilities.push(ACK_CAPABILITY.to_string());
+    Handshake {
+        protocol_version: PROTOCOL_VERSION,
+        min_protocol_version: MIN_PROTOCOL_VERSION,
+        capabilities,
+    }
+}
+
+/// Returns whether the peer that sent `handshake` sends keepalives.
+///
+/// Peers running older binaries do not, so their silence must not close the connection.
+pub fn supports_keepalive(handshake: &Handshake) -> bool {
+    handshake
+        .capabilities
+        .iter()
+        .any(|capability| capability == KEEPALIVE_CAPABILITY)
+}
+
+/// Returns whether the peer that sent `handshake` acknowledges reliable messages.
+///
+/// Peers running older binaries do not understand the envelope, so messages are sent to
+/// them as they are.
+pub fn supports_ack(handshake: &Handshake) -> bool {
+    handshake
+        .capabilities
+        .iter()
+        .any(|capability| capability == ACK_CAPABILITY)
+}
+
+/// Negotiates the protocol version to use with a peer.
+///
+/// Both peers downgrade to the oldest of their newest versions, as long as each one still
+/// accepts it. Otherwise the connection is rejected with an [`IncompatibleVersion`].
+///
+/// ## Arguments
+/// * `remote_addr` - The address of the peer.
+/// * `remote` - The handshake received from the peer.
+pub fn negotiate_version(
+    remote_addr: SocketAddr,
+    remote: &Handshake,
+) -> Result<u32, IncompatibleVersion> {
+    negotiate_between(
+        remote_addr,
+        (MIN_PROTOCOL_VERSION, PROTOCOL_VERSION),
+        remote,
+    )
+}
+
+/// Negotiates the protocol version as [`negotiate_version`], for a node that supports the
+/// versions in `supported`, as `(oldest, newest)`.
+fn negotiate_between(
+    remote_addr: SocketAddr,
+    supported: (u32, u32),
+    remote: &Handshake,
+) -> Result<u32, IncompatibleVersion> {
+    let (min_version, max_version) = supported;
+    let version = max_version.min(remote.protocol_version);
+    if version < min_version || version < remote.min_protocol_version {
+        return Err(IncompatibleVersion {
+            remote_addr,
+            supported,
+            received: remote.protocol_version,
+        });
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remote(min_protocol_version: u32, protocol_version: u32) -> Handshake {
+        Handshake {
+            protocol_version,
+            min_protocol_version,
+            capabilities: Vec::new(),
+        }
+    }
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:8080".parse().unwrap()
+    }
+
+    #[test]
+    fn the_highest_common_version_is_chosen() {
+        // (versiones locales, versiones remotas, versión acordada)
+        let cases = [
+            ((1, 3), (1, 3), 3),
+            ((1, 3), (1, 2), 2),
+            ((1, 2), (1, 5), 2),
+            ((2, 4), (1, 3), 3),
+            ((1, 3), (3, 7), 3),
+        ];
+        for (local, (min, max), expected) in cases {
+            assert_eq!(
+                negotiate_between(addr(), local, &remote(min, max)).ok(),
+                Some(expected),
+                "local {:?}, remote ({}, {})",
+                local,
+                min,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn disjoint_version_ranges_are_rejected() {
+        for (local, (min, max)) in [((3, 4), (1, 2)), ((1, 2), (3, 4))] {
+            let rejection = negotiate_between(addr(), local, &remote(min, max)).unwrap_err();
+            assert_eq!(rejection.supported, local);
+            assert_eq!(rejection.received, max);
+            assert_eq!(rejection.remote_addr, addr());
+        }
+    }
+
+    #[test]
+    fn this_binary_agrees_with_itself() {
+        assert_eq!(
+            negotiate_version(addr(), &local_handshake()).ok(),
+            Some(PROTOCOL_VERSION)
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf, copy_bidirectional, duplex};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::sync::oneshot;
+
+/// Size of the in-memory buffer of each direction of a connection.
+const MEMORY_BUFFER_SIZE: usize = 64 * 1024;
+/// First port assigned to the connecting side of in-memory connections.
+const FIRST_EPHEMERAL_PORT: u16 = 50000;
+
+thread_local! {
+    /// The in-memory network of the current thread, if enabled.
+    static NETWORK: RefCell<Option<MemoryNetwork>> = const { RefCell::new(None) };
+}
+
+/// An in-process network that replaces TCP, used to simulate a whole cluster in a single
+/// thread.
+///
+/// Every connection is relayed by a task that can be cut, so nodes can be isolated as if
+/// they had crashed.
+#[derive(Default)]
+struct MemoryNetwork {
+    /// Listeners bound to each address.
+    listeners: HashMap<SocketAddr, UnboundedSender<MemoryStream>>,
+    /// Open connections.
+    connections: Vec<MemoryConnection>,
+    /// Addresses that no longer accept connections.
+    isolated_addrs: HashSet<SocketAddr>,
+    /// Users that can no longer open connections.
+    isolated_users: HashSet<String>,
+    /// Next port assigned to the connecting side of a connection.
+    next_port: u16,
+}
+
+/// An open in-memory connection.
+struct MemoryConnection {
+    /// The user that opened the connection.
+    user_id: String,
+    /// The address the connection was opened to.
+    listener_addr: SocketAddr,
+    /// Cuts the connection when sent or dropped.
+    cut: oneshot::Sender<()>,
+}
+
+/// Enables the in-memory network on the current thread. Connections and listeners created
+/// afterwards on this thread do not use TCP.
+pub fn enable() {
+    NETWORK.with(|network| {
+        *network.borrow_mut() = Some(MemoryNetwork {
+            next_port: FIRST_EPHEMERAL_PORT,
+            ..MemoryNetwork::default()
+        })
+    });
+}
+
+/// Disables the in-memory network on the current thread, cutting every open connection.
+pub fn disable() {
+    NETWORK.with(|network| network.borrow_mut().take());
+}
+
+/// Returns whether the in-memory network is enabled on the current thread.
+pub fn is_enabled() -> bool {
+    NETWORK.with(|network| network.borrow().is_some())
+}
+
+/// Cuts every connection to `addr` or opened by `user_id`, and refuses new ones, as if the
+/// node had crashed.
+pub fn isolate(addr: SocketAddr, user_id: &str) {
+    NETWORK.with(|network| {
+        if let Some(network) = network.borrow_mut().as_mut() {
+            network.listeners.remove(&addr);
+            network.isolated_addrs.insert(addr);
+            network.isolated_users.insert(user_id.to_string());
+            // Al soltar el sender se corta la conexión
+            network
+                .connections
+                .retain(|c| c.listener_addr != addr && c.user_id != user_id);
+        }
+    });
+}
+
+/// Binds a listener to `addr` on the in-memory network.
+pub fn bind(addr: SocketAddr) -> io::Result<MemoryListener> {
+    NETWORK.with(|network| {
+        let mut network = network.borrow_mut();
+        let network = network.as_mut().ok_or_else(not_enabled)?;
+        if network.listeners.contains_key(&addr) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("{} already bound", addr),
+            ));
+        }
+        let (sender, incoming) = unbounded_channel();
+        network.listeners.insert(addr, sender);
+        Ok(MemoryListener { incoming })
+    })
+}
+
+/// Opens a connection from `user_id` to the listener bound to `addr`.
+pub fn connect(addr: SocketAddr, user_id: &str) -> io::Result<MemoryStream> {
+    NETWORK.with(|network| {
+        let mut network = network.borrow_mut();
+        let network = network.as_mut().ok_or_else(not_enabled)?;
+        let refused = || io::Error::new(io::ErrorKind::ConnectionRefused, addr.to_string());
+        if network.isolated_addrs.contains(&addr) || network.isolated_users.contains(user_id) {
+            return Err(refused());
+        }
+        let listener = network.listeners.get(&addr).ok_or_else(refused)?;
+        // Se olvidan las conexiones que ya se cerraron
+        network.connections.retain(|c| !c.cut.is_closed());
+
+        let local_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), network.next_port);
+        network.next_port = network.next_port.wrapping_add(1).max(FIRST_EPHEMERAL_PORT);
+
+        let (local, mut relay_local) = duplex(MEMORY_BUFFER_SIZE);
+        let (mut relay_remote, remote) = duplex(MEMORY_BUFFER_SIZE);
+        listener
+            .send(MemoryStream {
+                inner: remote,
+                local_addr: addr,
+                peer_addr: local_addr,
+            })
+            .map_err(|_| refused())?;
+
+        let (cut, cut_signal) = oneshot::channel();
+        tokio::spawn(async move {
+            tokio::select! {
+                _ = copy_bidirectional(&mut relay_local, &mut relay_remote) => {}
+                _ = cut_signal => {}
+            }
+        });
+        network.connections.push(MemoryConnection {
+            user_id: user_id.to_string(),
+            listener_addr: addr,
+            cut,
+        });
+
+        Ok(MemoryStream {
+            inner: local,
+            local_addr,
+            peer_addr: addr,
+        })
+    })
+}
+
+fn not_enabled() -> io::Error {
+    io::Error::other("in-memory network not enabled")
+}
+
+/// A listener bound on the in-memory network.
+pub struct MemoryListener {
+    /// Connections waiting to be accepted.
+    incoming: UnboundedReceiver<MemoryStream>,
+}
+
+impl MemoryListener {
+    /// Waits for the next connection. Never returns once the listener has been isolated.
+    pub async fn accept(&mut self) -> MemoryStream {
+        match self.incoming.recv().await {
+            Some(stream) => stream,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+/// One end of an in-memory connection.
+#[derive(Debug)]
+pub struct MemoryStream {
+    /// The buffer shared with the relay task.
+    inner: DuplexStream,
+    /// The address of this end.
+    pub local_addr: SocketAddr,
+    /// The address of the other end.
+    pub peer_addr: SocketAddr,
+}
+
+impl AsyncRead for MemoryStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for MemoryStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
@@ -1,5 +1,18 @@
+pub mod codec;
 pub mod communicator;
 pub mod connections;
+pub mod faults;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod handshake;
+pub mod memory;
+pub mod outbound;
 pub mod peer_types;
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+pub mod reconnection;
+pub mod reliable;
+pub mod stream;
 pub mod tcp_receiver;
 pub mod tcp_sender;
+pub mod transport;
@@ -0,0 +1,356 @@
+use crate::constants::{OUTBOUND_BLOCK_TIMEOUT, OUTBOUND_QUEUE_CAPACITY};
+use crate::messages::shared_messages::NetworkMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Environment variable used to configure the outbound queue of every connection of a process.
+///
+/// Its value is a comma-separated list of `key=value` pairs, e.g.
+/// `capacity=256,overflow=block,timeout=500`. The overflow policy is one of `drop-oldest`,
+/// `disconnect` or `block`, and the timeout is in milliseconds.
+pub const OUTBOUND_QUEUE_ENV_VAR: &str = "OUTBOUND_QUEUE";
+
+/// What a connection does with an outgoing message when its queue is full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The oldest queued message is dropped to make room.
+    DropOldest,
+    /// The connection is closed, as the peer cannot keep up.
+    Disconnect,
+    /// The message waits for room up to the given time, and is dropped if there is none.
+    Block(Duration),
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::Block(OUTBOUND_BLOCK_TIMEOUT)
+    }
+}
+
+/// Size of the outbound queue of a connection and what to do when it is full.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutboundConfig {
+    /// Maximum number of messages waiting to be written.
+    pub capacity: usize,
+    /// What to do with a message that does not fit.
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for OutboundConfig {
+    fn default() -> Self {
+        Self {
+            capacity: OUTBOUND_QUEUE_CAPACITY,
+            overflow: OverflowPolicy::default(),
+        }
+    }
+}
+
+impl OutboundConfig {
+    /// Reads the configuration from [`OUTBOUND_QUEUE_ENV_VAR`].
+    ///
+    /// Returns the default configuration if the variable is not set or is invalid.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(OUTBOUND_QUEUE_ENV_VAR) else {
+            return Self::default();
+        };
+        Self::parse(&value).unwrap_or_else(|e| {
+            eprintln!("[Outbound] Ignoring {}: {}", OUTBOUND_QUEUE_ENV_VAR, e);
+            Self::default()
+        })
+    }
+
+    /// Parses a list of `key=value` pairs as described in [`OUTBOUND_QUEUE_ENV_VAR`].
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut capacity = OUTBOUND_QUEUE_CAPACITY;
+        let mut policy = "block".to_string();
+        let mut timeout = OUTBOUND_BLOCK_TIMEOUT;
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, got '{}'", pair))?;
+            let invalid = || format!("Invalid value for {}: '{}'", key, value);
+            match key.trim() {
+                "capacity" => {
+                    capacity = value
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|capacity| *capacity > 0)
+                        .ok_or_else(invalid)?
+                }
+                "overflow" => policy = value.trim().to_string(),
+                "timeout" => {
+                    timeout = Duration::from_millis(value.trim().parse().map_err(|_| invalid())?)
+                }
+                other => return Err(format!("Unknown setting '{}'", other)),
+            }
+        }
+        let overflow = match policy.as_str() {
+            "drop-oldest" => OverflowPolicy::DropOldest,
+            "disconnect" => OverflowPolicy::Disconnect,
+            "block" => OverflowPolicy::Block(timeout),
+            other => return Err(format!("Unknown overflow policy '{}'", other)),
+        };
+        Ok(Self { capacity, overflow })
+    }
+}
+
+/// Counters of the outbound queue of a connection, shared with its
+/// [`Communicator`](crate::network::communicator::Communicator).
+#[derive(Debug, Clone, Default)]
+pub struct OutboundMetrics {
+    /// Messages waiting to be written.
+    depth: Arc<AtomicUsize>,
+    /// Messages waiting for room under [`OverflowPolicy::Block`].
+    blocked: Arc<AtomicUsize>,
+    /// Highest depth reached.
+    peak: Arc<AtomicUsize>,
+    /// Messages dropped because the queue was full.
+    dropped: Arc<AtomicU64>,
+}
+
+impl OutboundMetrics {
+    /// Returns the current values of the counters.
+    pub fn snapshot(&self) -> OutboundStats {
+        OutboundStats {
+            depth: self.depth.load(Ordering::Relaxed),
+            blocked: self.blocked.load(Ordering::Relaxed),
+            peak: self.peak.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The counters of an outbound queue at some point in time.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OutboundStats {
+    /// Messages waiting to be written.
+    pub depth: usize,
+    /// Messages waiting for room in the queue.
+    pub blocked: usize,
+    /// Highest depth reached.
+    pub peak: usize,
+    /// Messages dropped because the queue was full.
+    pub dropped: u64,
+}
+
+/// Result of adding a message to an [`OutboundQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    /// The message was queued.
+    Queued,
+    /// The message was queued after dropping the oldest one.
+    DroppedOldest,
+    /// The message waits for room until the returned deadline.
+    Blocked(Instant),
+    /// The message was not queued and the connection must be closed.
+    Disconnect,
+}
+
+/// A bounded queue of outgoing messages, following an [`OutboundConfig`].
+#[derive(Debug)]
+pub struct OutboundQueue {
+    /// The size of the queue and its overflow policy.
+    config: OutboundConfig,
+    /// Messages waiting to be written, in order.
+    queued: VecDeque<NetworkMessage>,
+    /// Messages waiting for room, with the time they are dropped at.
+    blocked: VecDeque<(NetworkMessage, Instant)>,
+    /// Counters shared with the owner of the connection.
+    metrics: OutboundMetrics,
+}
+
+impl OutboundQueue {
+    /// Creates an empty queue.
+    pub fn new(config: OutboundConfig) -> Self {
+        Self {
+            config,
+            queued: VecDeque::new(),
+            blocked: VecDeque::new(),
+            metrics: OutboundMetrics::default(),
+        }
+    }
+
+    /// Returns the counters of this queue.
+    pub fn metrics(&self) -> OutboundMetrics {
+        self.metrics.clone()
+    }
+
+    /// Returns the number of messages waiting to be written.
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+
+    /// Returns whether there are no messages waiting to be written.
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+
+    /// Adds a message at the end of the queue, applying the overflow policy if it is full.
+    pub fn push(&mut self, msg: NetworkMessage, now: Instant) -> Admission {
+        // Los bloqueados tienen prioridad, para no alterar el orden de los mensajes
+        if self.queued.len() < self.config.capacity && self.blocked.is_empty() {
+            self.queued.push_back(msg);
+            self.update_depth();
+            return Admission::Queued;
+        }
+        let admission = match self.config.overflow {
+            OverflowPolicy::DropOldest => {
+                self.queued.pop_front();
+                self.queued.push_back(msg);
+                self.metrics.dropped.fetch_add(1, Ordering::Relaxed);
+                Admission::DroppedOldest
+            }
+            OverflowPolicy::Disconnect => Admission::Disconnect,
+            OverflowPolicy::Block(timeout) => {
+                let deadline = now + timeout;
+                self.blocked.push_back((msg, deadline));
+                Admission::Blocked(deadline)
+            }
+        };
+        self.update_depth();
+        admission
+    }
+
+    /// Takes the next message to write, making room for a blocked one.
+    pub fn pop_front(&mut self, now: Instant) -> Option<NetworkMessage> {
+        let msg = self.queued.pop_front();
+        self.expire(now);
+        while self.queued.len() < self.config.capacity {
+            let Some((blocked, _)) = self.blocked.pop_front() else {
+                break;
+            };
+            self.queued.push_back(blocked);
+        }
+        self.update_depth();
+        msg
+    }
+
+    /// Drops the blocked messages whose deadline passed.
+    ///
+    /// ## Returns
+    /// The number of messages dropped.
+    pub fn expire(&mut self, now: Instant) -> usize {
+        let before = self.blocked.len();
+        self.blocked.retain(|(_, deadline)| *deadline > now);
+        let expired = before - self.blocked.len();
+        self.metrics
+            .dropped
+            .fetch_add(expired as u64, Ordering::Relaxed);
+        self.update_depth();
+        expired
+    }
+
+    /// Removes every queued and blocked message.
+    pub fn clear(&mut self) {
+        self.queued.clear();
+        self.blocked.clear();
+        self.update_depth();
+    }
+
+    /// Publishes the current depth to the metrics.
+    fn update_depth(&self) {
+        let depth = self.queued.len();
+        self.metrics.depth.store(depth, Ordering::Relaxed);
+        self.metrics
+            .blocked
+            .store(self.blocked.len(), Ordering::Relaxed);
+        self.metrics.peak.fetch_max(depth, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::coordinatormanager_messages::Ping;
+    use std::net::SocketAddr;
+
+    fn ping(port: u16) -> NetworkMessage {
+        let from: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        NetworkMessage::Ping(Ping { from })
+    }
+
+    fn port(msg: Option<NetworkMessage>) -> Option<u16> {
+        match msg {
+            Some(NetworkMessage::Ping(ping)) => Some(ping.from.port()),
+            _ => None,
+        }
+    }
+
+    fn config(overflow: OverflowPolicy) -> OutboundConfig {
+        OutboundConfig {
+            capacity: 2,
+            overflow,
+        }
+    }
+
+    #[test]
+    fn parses_every_setting() {
+        assert_eq!(
+            OutboundConfig::parse("capacity=8, overflow=block,timeout=250").unwrap(),
+            OutboundConfig {
+                capacity: 8,
+                overflow: OverflowPolicy::Block(Duration::from_millis(250)),
+            }
+        );
+        assert_eq!(
+            OutboundConfig::parse("overflow=drop-oldest").unwrap(),
+            OutboundConfig {
+                capacity: OUTBOUND_QUEUE_CAPACITY,
+                overflow: OverflowPolicy::DropOldest,
+            }
+        );
+        assert!(OutboundConfig::parse("capacity=0").is_err());
+        assert!(OutboundConfig::parse("overflow=ignore").is_err());
+    }
+
+    #[test]
+    fn applies_the_overflow_policy_when_full() {
+        let now = Instant::now();
+        let mut queue = OutboundQueue::new(config(OverflowPolicy::DropOldest));
+        for port in 1..=3 {
+            queue.push(ping(port), now);
+        }
+        assert_eq!(port(queue.pop_front(now)), Some(2));
+        assert_eq!(queue.metrics().snapshot().dropped, 1);
+
+        let mut queue = OutboundQueue::new(config(OverflowPolicy::Disconnect));
+        queue.push(ping(1), now);
+        queue.push(ping(2), now);
+        assert_eq!(queue.push(ping(3), now), Admission::Disconnect);
+        assert_eq!(queue.len(), 2);
+    }
+
+    #[test]
+    fn blocked_messages_wait_for_room_until_their_deadline() {
+        let timeout = Duration::from_secs(1);
+        let now = Instant::now();
+        let mut queue = OutboundQueue::new(config(OverflowPolicy::Block(timeout)));
+        queue.push(ping(1), now);
+        queue.push(ping(2), now);
+        assert_eq!(queue.push(ping(3), now), Admission::Blocked(now + timeout));
+        assert_eq!(queue.push(ping(4), now), Admission::Blocked(now + timeout));
+
+        // Al liberarse lugar entra el primer bloqueado, en orden
+        assert_eq!(port(queue.pop_front(now)), Some(1));
+        assert_eq!(queue.metrics().snapshot().blocked, 1);
+
+        let later = now + timeout * 2;
+        assert_eq!(port(queue.pop_front(later)), Some(2));
+        assert_eq!(port(queue.pop_front(later)), Some(3));
+        assert!(queue.is_empty());
+        assert_eq!(
+            queue.metrics().snapshot(),
+            OutboundStats {
+                depth: 0,
+                blocked: 0,
+                peak: 2,
+                dropped: 1,
+            }
+        );
+    }
+}
@@ -1,5 +1,9 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
 /// Enum representing the type of a peer in the distributed system.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PeerType {
     /// A client peer.
     ClientType,
@@ -32,6 +36,17 @@ impl PeerType {
         }
     }
 
+    /// Returns whether this is a client, restaurant or delivery.
+    ///
+    /// Both ends of a user connection use the user's type, so the connection is checked for
+    /// liveness with keepalives.
+    pub fn is_user(&self) -> bool {
+        matches!(
+            self,
+            PeerType::ClientType | PeerType::RestaurantType | PeerType::DeliveryType
+        )
+    }
+
     /// Converts a `PeerType` to its corresponding `u8` value.
     pub fn to_u8(&self) -> u8 {
         match self {
@@ -43,3 +58,117 @@ impl PeerType {
         }
     }
 }
+
+/// Identity a peer announces as soon as it connects, before any [`NetworkMessage`] is sent.
+///
+/// On the wire it is the peer type byte, followed by the length of the user ID (`u16`,
+/// big-endian) and the user ID in UTF-8.
+///
+/// [`NetworkMessage`]: crate::messages::shared_messages::NetworkMessage
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerIdentity {
+    /// The type of the connecting peer.
+    pub peer_type: PeerType,
+    /// The ID of the connecting user (or coordinator).
+    pub user_id: String,
+}
+
+impl PeerIdentity {
+    /// Creates a new `PeerIdentity`.
+    ///
+    /// # Arguments
+    /// - `peer_type`: The type of the connecting peer.
+    /// - `user_id`: The ID of the connecting user.
+    pub fn new(peer_type: PeerType, user_id: impl Into<String>) -> Self {
+        Self {
+            peer_type,
+            user_id: user_id.into(),
+        }
+    }
+
+    /// Writes the identity to a freshly opened connection.
+    pub async fn write_to<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> io::Result<()> {
+        let user_id = self.user_id.as_bytes();
+        let len = u16::try_from(user_id.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "user ID too long"))?;
+        let mut preamble = Vec::with_capacity(3 + user_id.len());
+        preamble.push(self.peer_type.to_u8());
+        preamble.extend_from_slice(&len.to_be_bytes());
+        preamble.extend_from_slice(user_id);
+        writer.write_all(&preamble).await
+    }
+
+    /// Reads the identity sent by a peer that just connected.
+    ///
+    /// # Returns
+    /// - An `InvalidData` error if the peer type is unknown or the user ID is not UTF-8.
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Self> {
+        let peer_type = PeerType::from_u8(reader.read_u8().await?).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "unsupported peer type byte")
+        })?;
+        let len = reader.read_u16().await?;
+        let mut user_id = vec![0u8; len as usize];
+        reader.read_exact(&mut user_id).await?;
+        let user_id = String::from_utf8(user_id)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "user ID is not UTF-8"))?;
+        Ok(Self { peer_type, user_id })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn an_identity_survives_the_round_trip() {
+        for identity in [
+            PeerIdentity::new(PeerType::ClientType, "client_1"),
+            PeerIdentity::new(PeerType::CoordinatorType, "server_0"),
+            PeerIdentity::new(PeerType::GatewayType, ""),
+            PeerIdentity::new(PeerType::RestaurantType, "parrilla_ñandú"),
+        ] {
+            let mut wire = Vec::new();
+            identity.write_to(&mut wire).await.unwrap();
+            assert_eq!(wire.len(), 3 + identity.user_id.len());
+
+            let read = PeerIdentity::read_from(&mut wire.as_slice()).await.unwrap();
+            assert_eq!(read, identity);
+        }
+    }
+
+    #[tokio::test]
+    async fn a_truncated_identity_is_rejected() {
+        let mut wire = Vec::new();
+        PeerIdentity::new(PeerType::DeliveryType, "delivery_1")
+            .write_to(&mut wire)
+            .await
+            .unwrap();
+
+        // Se corta en cada punto posible: tipo, largo y cuerpo del ID
+        for cut in 0..wire.len() {
+            let error = PeerIdentity::read_from(&mut &wire[..cut])
+                .await
+                .unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof, "cut at {}", cut);
+        }
+    }
+
+    #[tokio::test]
+    async fn an_invalid_identity_is_rejected() {
+        let unknown_type = [9u8, 0, 1, b'x'];
+        let error = PeerIdentity::read_from(&mut &unknown_type[..])
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let not_utf8 = [0u8, 0, 2, 0xC3, 0x28];
+        let error = PeerIdentity::read_from(&mut &not_utf8[..])
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let too_long = PeerIdentity::new(PeerType::ClientType, "a".repeat(u16::MAX as usize + 1));
+        let error = too_long.write_to(&mut Vec::new()).await.unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,1644 @@
+use crate::bimap::BiMap;
+use crate::messages::coordinator_messages::NotifyOrderUpdated;
+use crate::messages::coordinatormanager_messages::{
+    LeaderElection, StorageSnapshot, StorageUpdates,
+};
+use crate::messages::internal_messages::*;
+use crate::messages::shared_messages::{NetworkMessage, WhoIsLeader};
+use crate::types::decline_reason::DeclineReason;
+use crate::types::delivery_status::DeliveryStatus;
+use crate::types::dtos::{
+    CancellationFee, ClientDTO, DeliveryDTO, EarningDTO, ElectionRecord, OrderDTO, OrderItem,
+    OrderRequest, OutboxPayment, PromoRedemption, RestaurantDTO, Snapshot, Tombstones,
+};
+use crate::types::hlc::HlcTimestamp;
+use crate::types::order_event::{OrderEvent, OrderEventKind};
+use crate::types::order_priority::OrderPriority;
+use crate::types::order_status::OrderStatus;
+use crate::types::rejection_reason::RejectionReason;
+use crate::types::secret_hash::SecretHash;
+use crate::types::tenant::TenantId;
+use crate::types::vehicle::VehicleType;
+use proto::envelope::Payload;
+use proto::storage_log_message::Operation;
+use std::collections::HashMap;
+
+/// Code generated from `proto/coordinator.proto`.
+#[allow(clippy::large_enum_variant)]
+pub mod proto {
+    include!(concat!(env!("OUT_DIR"), "/coordinator.rs"));
+}
+
+/// Wraps a [`NetworkMessage`] into a protobuf [`proto::Envelope`].
+///
+/// `WhoIsLeader`, `StorageUpdates`, `StorageSnapshot` and `LeaderElection` have their own
+/// protobuf messages, down to every operation of the log and every entity of the snapshot;
+/// any other variant travels as JSON. The `from` field is left empty.
+pub fn encode(message: &NetworkMessage) -> Result<proto::Envelope, String> {
+    let payload = match message {
+        NetworkMessage::WhoIsLeader(msg) => Payload::WhoIsLeader(proto::WhoIsLeader {
+            origin_addr: msg.origin_addr.to_string(),
+            user_id: msg.user_id.clone(),
+        }),
+        NetworkMessage::StorageUpdates(msg) => Payload::StorageUpdates(proto::StorageUpdates {
+            updates: msg
+                .updates
+                .iter()
+                .map(|(index, update)| (*index, update.into()))
+                .collect(),
+            coordinator_id: msg.coordinator_id.clone(),
+            epoch: msg.epoch,
+        }),
+        NetworkMessage::StorageSnapshot(msg) => Payload::StorageSnapshot(proto::StorageSnapshot {
+            snapshot: Some(msg.snapshot.as_ref().into()),
+            coordinator_id: msg.coordinator_id.clone(),
+            epoch: msg.epoch,
+        }),
+        NetworkMessage::LeaderElection(msg) => Payload::LeaderElection(proto::LeaderElection {
+            initiator: msg.initiator.clone(),
+            candidates: msg.candidates.clone(),
+            epoch: msg.epoch,
+        }),
+        other => Payload::Json(serde_json::to_string(other).map_err(|e| e.to_string())?),
+    };
+    Ok(proto::Envelope {
+        from: String::new(),
+        payload: Some(payload),
+    })
+}
+
+/// Unwraps a protobuf [`proto::Envelope`] back into a [`NetworkMessage`].
+pub fn decode(envelope: proto::Envelope) -> Result<NetworkMessage, String> {
+    match envelope.payload {
+        Some(Payload::WhoIsLeader(msg)) => Ok(NetworkMessage::WhoIsLeader(WhoIsLeader {
+            origin_addr: msg
+                .origin_addr
+                .parse()
+                .map_err(|e| format!("Invalid origin address: {}", e))?,
+            user_id: msg.user_id,
+        })),
+        Some(Payload::StorageUpdates(msg)) => {
+            let mut updates = HashMap::new();
+            for (index, update) in msg.updates {
+                updates.insert(index, update.try_into()?);
+            }
+            Ok(NetworkMessage::StorageUpdates(StorageUpdates {
+                updates,
+                coordinator_id: msg.coordinator_id,
+                epoch: msg.epoch,
+            }))
+        }
+        Some(Payload::StorageSnapshot(msg)) => {
+            Ok(NetworkMessage::StorageSnapshot(StorageSnapshot {
+                snapshot: Box::new(required(msg.snapshot, "snapshot")?.try_into()?),
+                coordinator_id: msg.coordinator_id,
+                epoch: msg.epoch,
+            }))
+        }
+        Some(Payload::LeaderElection(msg)) => Ok(NetworkMessage::LeaderElection(LeaderElection {
+            initiator: msg.initiator,
+            candidates: msg.candidates,
+            epoch: msg.epoch,
+        })),
+        Some(Payload::Json(json)) => serde_json::from_str(&json).map_err(|e| e.to_string()),
+        None => Err("Empty envelope".to_string()),
+    }
+}
+
+/// Returns the nested message `field`, which must be present.
+fn required<T>(value: Option<T>, field: &str) -> Result<T, String> {
+    value.ok_or_else(|| format!("Missing field {}", field))
+}
+
+/// Returns the timestamp `field`, which must be present.
+fn required_stamp(value: Option<proto::HlcTimestamp>, field: &str) -> Result<HlcTimestamp, String> {
+    required(value, field).map(Into::into)
+}
+
+/// Converts an enum received as an `i32` into the protobuf enum `E`.
+fn known_enum<E: TryFrom<i32>>(value: i32, name: &str) -> Result<E, String> {
+    E::try_from(value).map_err(|_| format!("Unknown {} {}", name, value))
+}
+
+/////////////////////////////////////////////////////////////////////
+// Tipos del storage
+/////////////////////////////////////////////////////////////////////
+
+fn position(position: (f32, f32)) -> Option<proto::Position> {
+    Some(proto::Position {
+        x: position.0,
+        y: position.1,
+    })
+}
+
+fn from_position(position: Option<proto::Position>, field: &str) -> Result<(f32, f32), String> {
+    required(position, field).map(|p| (p.x, p.y))
+}
+
+impl From<HlcTimestamp> for proto::HlcTimestamp {
+    fn from(stamp: HlcTimestamp) -> Self {
+        Self {
+            physical_millis: stamp.physical_millis,
+            logical: stamp.logical,
+        }
+    }
+}
+
+impl From<proto::HlcTimestamp> for HlcTimestamp {
+    fn from(stamp: proto::HlcTimestamp) -> Self {
+        Self {
+            physical_millis: stamp.physical_millis,
+            logical: stamp.logical,
+        }
+    }
+}
+
+impl From<&OrderStatus> for proto::OrderStatus {
+    fn from(status: &OrderStatus) -> Self {
+        match status {
+            OrderStatus::Requested => Self::Requested,
+            OrderStatus::Authorized => Self::Authorized,
+            OrderStatus::Unauthorized => Self::Unauthorized,
+            OrderStatus::Pending => Self::Pending,
+            OrderStatus::Preparing => Self::Preparing,
+            OrderStatus::ReadyForDelivery => Self::ReadyForDelivery,
+            OrderStatus::Delivering => Self::Delivering,
+            OrderStatus::Delivered => Self::Delivered,
+            OrderStatus::Cancelled => Self::Cancelled,
+            OrderStatus::OutOfStock => Self::OutOfStock,
+            OrderStatus::OutOfZone => Self::OutOfZone,
+            OrderStatus::InvalidPromoCode => Self::InvalidPromoCode,
+            OrderStatus::RestaurantClosed => Self::RestaurantClosed,
+            OrderStatus::OtherTenant => Self::OtherTenant,
+        }
+    }
+}
+
+fn order_status(value: i32) -> Result<OrderStatus, String> {
+    Ok(match known_enum(value, "order status")? {
+        proto::OrderStatus::Requested => OrderStatus::Requested,
+        proto::OrderStatus::Authorized => OrderStatus::Authorized,
+        proto::OrderStatus::Unauthorized => OrderStatus::Unauthorized,
+        proto::OrderStatus::Pending => OrderStatus::Pending,
+        proto::OrderStatus::Preparing => OrderStatus::Preparing,
+        proto::OrderStatus::ReadyForDelivery => OrderStatus::ReadyForDelivery,
+        proto::OrderStatus::Delivering => OrderStatus::Delivering,
+        proto::OrderStatus::Delivered => OrderStatus::Delivered,
+        proto::OrderStatus::Cancelled => OrderStatus::Cancelled,
+        proto::OrderStatus::OutOfStock => OrderStatus::OutOfStock,
+        proto::OrderStatus::OutOfZone => OrderStatus::OutOfZone,
+        proto::OrderStatus::InvalidPromoCode => OrderStatus::InvalidPromoCode,
+        proto::OrderStatus::RestaurantClosed => OrderStatus::RestaurantClosed,
+        proto::OrderStatus::OtherTenant => OrderStatus::OtherTenant,
+    })
+}
+
+impl From<OrderPriority> for proto::OrderPriority {
+    fn from(priority: OrderPriority) -> Self {
+        match priority {
+            OrderPriority::Standard => Self::Standard,
+            OrderPriority::Express => Self::Express,
+        }
+    }
+}
+
+fn order_priority(value: i32) -> Result<OrderPriority, String> {
+    Ok(match known_enum(value, "order priority")? {
+        proto::OrderPriority::Standard => OrderPriority::Standard,
+        proto::OrderPriority::Express => OrderPriority::Express,
+    })
+}
+
+impl From<DeliveryStatus> for proto::DeliveryStatus {
+    fn from(status: DeliveryStatus) -> Self {
+        match status {
+            DeliveryStatus::Reconnecting => Self::Reconnecting,
+            DeliveryStatus::Recovering => Self::Recovering,
+            DeliveryStatus::Available => Self::Available,
+            DeliveryStatus::WaitingConfirmation => Self::WaitingConfirmation,
+            DeliveryStatus::Delivering => Self::Delivering,
+            DeliveryStatus::Offline => Self::Offline,
+        }
+    }
+}
+
+fn delivery_status(value: i32) -> Result<DeliveryStatus, String> {
+    Ok(match known_enum(value, "delivery status")? {
+        proto::DeliveryStatus::Reconnecting => DeliveryStatus::Reconnecting,
+        proto::DeliveryStatus::Recovering => DeliveryStatus::Recovering,
+        proto::DeliveryStatus::Available => DeliveryStatus::Available,
+        proto::DeliveryStatus::WaitingConfirmation => DeliveryStatus::WaitingConfirmation,
+        proto::DeliveryStatus::Delivering => DeliveryStatus::Delivering,
+        proto::DeliveryStatus::Offline => DeliveryStatus::Offline,
+    })
+}
+
+impl From<VehicleType> for proto::VehicleType {
+    fn from(vehicle: VehicleType) -> Self {
+        match vehicle {
+            VehicleType::Bike => Self::Bike,
+            VehicleType::Moto => Self::Moto,
+            VehicleType::Car => Self::Car,
+        }
+    }
+}
+
+fn vehicle_type(value: i32) -> Result<VehicleType, String> {
+    Ok(match known_enum(value, "vehicle type")? {
+        proto::VehicleType::Bike => VehicleType::Bike,
+        proto::VehicleType::Moto => VehicleType::Moto,
+        proto::VehicleType::Car => VehicleType::Car,
+    })
+}
+
+impl From<OrderEventKind> for proto::OrderEventKind {
+    fn from(kind: OrderEventKind) -> Self {
+        match kind {
+            OrderEventKind::Created => Self::Created,
+            OrderEventKind::StatusChanged => Self::StatusChanged,
+            OrderEventKind::DeliveryAssigned => Self::DeliveryAssigned,
+            OrderEventKind::Removed => Self::Removed,
+        }
+    }
+}
+
+fn order_event_kind(value: i32) -> Result<OrderEventKind, String> {
+    Ok(match known_enum(value, "order event kind")? {
+        proto::OrderEventKind::Created => OrderEventKind::Created,
+        proto::OrderEventKind::StatusChanged => OrderEventKind::StatusChanged,
+        proto::OrderEventKind::DeliveryAssigned => OrderEventKind::DeliveryAssigned,
+        proto::OrderEventKind::Removed => OrderEventKind::Removed,
+    })
+}
+
+impl From<RejectionReason> for proto::RejectionReason {
+    fn from(reason: RejectionReason) -> Self {
+        match reason {
+            RejectionReason::Closed => Self::Closed,
+            RejectionReason::OutOfStock => Self::OutOfStock,
+            RejectionReason::TooBusy => Self::TooBusy,
+        }
+    }
+}
+
+fn rejection_reason(value: i32) -> Result<RejectionReason, String> {
+    Ok(match known_enum(value, "rejection reason")? {
+        proto::RejectionReason::Closed => RejectionReason::Closed,
+        proto::RejectionReason::OutOfStock => RejectionReason::OutOfStock,
+        proto::RejectionReason::TooBusy => RejectionReason::TooBusy,
+    })
+}
+
+impl From<DeclineReason> for proto::DeclineReason {
+    fn from(reason: DeclineReason) -> Self {
+        match reason {
+            DeclineReason::InsufficientFunds => Self::InsufficientFunds,
+            DeclineReason::FraudCheck => Self::FraudCheck,
+            DeclineReason::IssuerUnavailable => Self::IssuerUnavailable,
+            DeclineReason::GatewayMaintenance => Self::GatewayMaintenance,
+            DeclineReason::GatewayTimeout => Self::GatewayTimeout,
+        }
+    }
+}
+
+fn decline_reason(value: i32) -> Result<DeclineReason, String> {
+    Ok(match known_enum(value, "decline reason")? {
+        proto::DeclineReason::InsufficientFunds => DeclineReason::InsufficientFunds,
+        proto::DeclineReason::FraudCheck => DeclineReason::FraudCheck,
+        proto::DeclineReason::IssuerUnavailable => DeclineReason::IssuerUnavailable,
+        proto::DeclineReason::GatewayMaintenance => DeclineReason::GatewayMaintenance,
+        proto::DeclineReason::GatewayTimeout => DeclineReason::GatewayTimeout,
+    })
+}
+
+impl From<&OrderItem> for proto::OrderItem {
+    fn from(item: &OrderItem) -> Self {
+        Self {
+            dish: item.dish.clone(),
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+        }
+    }
+}
+
+impl From<proto::OrderItem> for OrderItem {
+    fn from(item: proto::OrderItem) -> Self {
+        Self {
+            dish: item.dish,
+            quantity: item.quantity,
+            unit_price: item.unit_price,
+        }
+    }
+}
+
+impl From<&OrderDTO> for proto::Order {
+    fn from(order: &OrderDTO) -> Self {
+        Self {
+            order_id: order.order_id,
+            items: order.items.iter().map(Into::into).collect(),
+            client_id: order.client_id.clone(),
+            restaurant_id: order.restaurant_id.clone(),
+            delivery_id: order.delivery_id.clone(),
+            status: proto::OrderStatus::from(&order.status).into(),
+            client_position: position(order.client_position),
+            expected_delivery_time: order.expected_delivery_time,
+            expected_preparation_time: order.expected_preparation_time,
+            priority: proto::OrderPriority::from(order.priority).into(),
+            discount: order.discount,
+            tenant_id: order.tenant_id.to_string(),
+            time_stamp: Some(order.time_stamp.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::Order> for OrderDTO {
+    type Error = String;
+
+    fn try_from(order: proto::Order) -> Result<Self, String> {
+        Ok(Self {
+            order_id: order.order_id,
+            items: order.items.into_iter().map(Into::into).collect(),
+            client_id: order.client_id,
+            restaurant_id: order.restaurant_id,
+            delivery_id: order.delivery_id,
+            status: order_status(order.status)?,
+            client_position: from_position(order.client_position, "client_position")?,
+            expected_delivery_time: order.expected_delivery_time,
+            expected_preparation_time: order.expected_preparation_time,
+            priority: order_priority(order.priority)?,
+            discount: order.discount,
+            tenant_id: TenantId::new(&order.tenant_id),
+            time_stamp: required_stamp(order.time_stamp, "time_stamp")?,
+        })
+    }
+}
+
+/// Converts a required order.
+fn from_order(order: Option<proto::Order>) -> Result<OrderDTO, String> {
+    required(order, "order")?.try_into()
+}
+
+impl From<&ClientDTO> for proto::Client {
+    fn from(client: &ClientDTO) -> Self {
+        Self {
+            client_position: position(client.client_position),
+            client_id: client.client_id.clone(),
+            client_order: client.client_order.as_ref().map(Into::into),
+            tenant_id: client.tenant_id.to_string(),
+            time_stamp: Some(client.time_stamp.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::Client> for ClientDTO {
+    type Error = String;
+
+    fn try_from(client: proto::Client) -> Result<Self, String> {
+        Ok(Self {
+            client_position: from_position(client.client_position, "client_position")?,
+            client_id: client.client_id,
+            client_order: client.client_order.map(TryInto::try_into).transpose()?,
+            tenant_id: TenantId::new(&client.tenant_id),
+            time_stamp: required_stamp(client.time_stamp, "time_stamp")?,
+        })
+    }
+}
+
+impl From<&CancellationFee> for proto::CancellationFee {
+    fn from(fee: &CancellationFee) -> Self {
+        Self {
+            order_id: fee.order_id,
+            stage: proto::OrderStatus::from(&fee.stage).into(),
+            price: fee.price,
+            fee: fee.fee,
+        }
+    }
+}
+
+impl TryFrom<proto::CancellationFee> for CancellationFee {
+    type Error = String;
+
+    fn try_from(fee: proto::CancellationFee) -> Result<Self, String> {
+        Ok(Self {
+            order_id: fee.order_id,
+            stage: order_status(fee.stage)?,
+            price: fee.price,
+            fee: fee.fee,
+        })
+    }
+}
+
+impl From<&RestaurantDTO> for proto::Restaurant {
+    fn from(restaurant: &RestaurantDTO) -> Self {
+        Self {
+            restaurant_position: position(restaurant.restaurant_position),
+            restaurant_id: restaurant.restaurant_id.clone(),
+            authorized_orders: restaurant
+                .authorized_orders
+                .iter()
+                .map(Into::into)
+                .collect(),
+            pending_orders: restaurant.pending_orders.iter().map(Into::into).collect(),
+            cancellation_fees: restaurant
+                .cancellation_fees
+                .iter()
+                .map(Into::into)
+                .collect(),
+            closed: restaurant.closed,
+            tags: restaurant.tags.clone(),
+            tenant_id: restaurant.tenant_id.to_string(),
+            time_stamp: Some(restaurant.time_stamp.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::Restaurant> for RestaurantDTO {
+    type Error = String;
+
+    fn try_from(restaurant: proto::Restaurant) -> Result<Self, String> {
+        Ok(Self {
+            restaurant_position: from_position(
+                restaurant.restaurant_position,
+                "restaurant_position",
+            )?,
+            restaurant_id: restaurant.restaurant_id,
+            authorized_orders: collect(restaurant.authorized_orders)?,
+            pending_orders: collect(restaurant.pending_orders)?,
+            cancellation_fees: collect(restaurant.cancellation_fees)?,
+            closed: restaurant.closed,
+            tags: restaurant.tags,
+            tenant_id: TenantId::new(&restaurant.tenant_id),
+            time_stamp: required_stamp(restaurant.time_stamp, "time_stamp")?,
+        })
+    }
+}
+
+impl From<&DeliveryDTO> for proto::Delivery {
+    fn from(delivery: &DeliveryDTO) -> Self {
+        Self {
+            delivery_position: position(delivery.delivery_position),
+            delivery_id: delivery.delivery_id.clone(),
+            current_client_id: delivery.current_client_id.clone(),
+            current_order: delivery.current_order.as_ref().map(Into::into),
+            status: proto::DeliveryStatus::from(delivery.status).into(),
+            vehicle: proto::VehicleType::from(delivery.vehicle).into(),
+            tenant_id: delivery.tenant_id.to_string(),
+            time_stamp: Some(delivery.time_stamp.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::Delivery> for DeliveryDTO {
+    type Error = String;
+
+    fn try_from(delivery: proto::Delivery) -> Result<Self, String> {
+        Ok(Self {
+            delivery_position: from_position(delivery.delivery_position, "delivery_position")?,
+            delivery_id: delivery.delivery_id,
+            current_client_id: delivery.current_client_id,
+            current_order: delivery.current_order.map(TryInto::try_into).transpose()?,
+            status: delivery_status(delivery.status)?,
+            vehicle: vehicle_type(delivery.vehicle)?,
+            tenant_id: TenantId::new(&delivery.tenant_id),
+            time_stamp: required_stamp(delivery.time_stamp, "time_stamp")?,
+        })
+    }
+}
+
+impl From<&OrderEvent> for proto::OrderEvent {
+    fn from(event: &OrderEvent) -> Self {
+        Self {
+            kind: proto::OrderEventKind::from(event.kind).into(),
+            status: proto::OrderStatus::from(&event.status).into(),
+            delivery_id: event.delivery_id.clone(),
+            actor: event.actor.clone(),
+            node: event.node.clone(),
+            timestamp: Some(event.timestamp.into()),
+            reason: event
+                .reason
+                .map(|reason| proto::RejectionReason::from(reason).into()),
+        }
+    }
+}
+
+impl TryFrom<proto::OrderEvent> for OrderEvent {
+    type Error = String;
+
+    fn try_from(event: proto::OrderEvent) -> Result<Self, String> {
+        Ok(Self {
+            kind: order_event_kind(event.kind)?,
+            status: order_status(event.status)?,
+            delivery_id: event.delivery_id,
+            actor: event.actor,
+            node: event.node,
+            timestamp: required_stamp(event.timestamp, "timestamp")?,
+            reason: event.reason.map(rejection_reason).transpose()?,
+        })
+    }
+}
+
+/// Converts an optional order event.
+fn from_event(event: Option<proto::OrderEvent>) -> Result<Option<OrderEvent>, String> {
+    event.map(TryInto::try_into).transpose()
+}
+
+impl From<&EarningDTO> for proto::Earning {
+    fn from(earning: &EarningDTO) -> Self {
+        Self {
+            order_id: earning.order_id,
+            base_fee: earning.base_fee,
+            distance_fee: earning.distance_fee,
+            paid_out: earning.paid_out,
+            time_stamp: Some(earning.time_stamp.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::Earning> for EarningDTO {
+    type Error = String;
+
+    fn try_from(earning: proto::Earning) -> Result<Self, String> {
+        Ok(Self {
+            order_id: earning.order_id,
+            base_fee: earning.base_fee,
+            distance_fee: earning.distance_fee,
+            paid_out: earning.paid_out,
+            time_stamp: required_stamp(earning.time_stamp, "time_stamp")?,
+        })
+    }
+}
+
+impl From<&PromoRedemption> for proto::PromoRedemption {
+    fn from(redemption: &PromoRedemption) -> Self {
+        Self {
+            code: redemption.code.clone(),
+            client_id: redemption.client_id.clone(),
+            order_id: redemption.order_id,
+            discount: redemption.discount,
+            time_stamp: Some(redemption.time_stamp.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::PromoRedemption> for PromoRedemption {
+    type Error = String;
+
+    fn try_from(redemption: proto::PromoRedemption) -> Result<Self, String> {
+        Ok(Self {
+            code: redemption.code,
+            client_id: redemption.client_id,
+            order_id: redemption.order_id,
+            discount: redemption.discount,
+            time_stamp: required_stamp(redemption.time_stamp, "time_stamp")?,
+        })
+    }
+}
+
+impl From<&OrderRequest> for proto::OrderRequest {
+    fn from(request: &OrderRequest) -> Self {
+        Self {
+            request_id: request.request_id,
+            order_id: request.order_id,
+        }
+    }
+}
+
+impl From<proto::OrderRequest> for OrderRequest {
+    fn from(request: proto::OrderRequest) -> Self {
+        Self {
+            request_id: request.request_id,
+            order_id: request.order_id,
+        }
+    }
+}
+
+impl From<&OutboxPayment> for proto::OutboxPayment {
+    fn from(payment: &OutboxPayment) -> Self {
+        Self {
+            order: Some((&payment.order).into()),
+            completed: payment.completed,
+            time_stamp: Some(payment.time_stamp.into()),
+        }
+    }
+}
+
+impl TryFrom<proto::OutboxPayment> for OutboxPayment {
+    type Error = String;
+
+    fn try_from(payment: proto::OutboxPayment) -> Result<Self, String> {
+        Ok(Self {
+            order: from_order(payment.order)?,
+            completed: payment.completed,
+            time_stamp: required_stamp(payment.time_stamp, "time_stamp")?,
+        })
+    }
+}
+
+impl From<&ElectionRecord> for proto::ElectionRecord {
+    fn from(record: &ElectionRecord) -> Self {
+        Self {
+            leader_id: record.leader_id.clone(),
+            epoch: record.epoch,
+            at_millis: record.at_millis,
+        }
+    }
+}
+
+impl From<proto::ElectionRecord> for ElectionRecord {
+    fn from(record: proto::ElectionRecord) -> Self {
+        Self {
+            leader_id: record.leader_id,
+            epoch: record.epoch,
+            at_millis: record.at_millis,
+        }
+    }
+}
+
+impl From<&NotifyOrderUpdated> for proto::OrderUpdate {
+    fn from(update: &NotifyOrderUpdated) -> Self {
+        Self {
+            peer_id: update.peer_id.clone(),
+            order: Some((&update.order).into()),
+            update_id: update.update_id,
+            request_id: update.request_id,
+            decline: update
+                .decline
+                .map(|reason| proto::DeclineReason::from(reason).into()),
+        }
+    }
+}
+
+impl TryFrom<proto::OrderUpdate> for NotifyOrderUpdated {
+    type Error = String;
+
+    fn try_from(update: proto::OrderUpdate) -> Result<Self, String> {
+        Ok(Self {
+            peer_id: update.peer_id,
+            order: from_order(update.order)?,
+            update_id: update.update_id,
+            request_id: update.request_id,
+            decline: update.decline.map(decline_reason).transpose()?,
+        })
+    }
+}
+
+/// Converts every message of `values`, failing on the first one that cannot be converted.
+fn collect<P, T, C>(values: Vec<P>) -> Result<C, String>
+where
+    T: TryFrom<P, Error = String>,
+    C: FromIterator<T>,
+{
+    values.into_iter().map(T::try_from).collect()
+}
+
+/// Converts the values of a map of messages, keeping its keys.
+fn collect_map<K, P, T>(values: HashMap<K, P>) -> Result<HashMap<K, T>, String>
+where
+    K: std::hash::Hash + Eq,
+    T: TryFrom<P, Error = String>,
+{
+    values
+        .into_iter()
+        .map(|(key, value)| Ok((key, T::try_from(value)?)))
+        .collect()
+}
+
+impl From<&Snapshot> for proto::Snapshot {
+    fn from(snapshot: &Snapshot) -> Self {
+        Self {
+            clients: map_values(&snapshot.clients, |value| value.into()),
+            restaurants: map_values(&snapshot.restaurants, |value| value.into()),
+            deliveries: map_values(&snapshot.deliverys, |value| value.into()),
+            orders: map_values(&snapshot.orders, |value| value.into()),
+            accepted_deliveries: snapshot
+                .accepted_deliveries
+                .keys()
+                .zip(snapshot.accepted_deliveries.values())
+                .map(|(order_id, delivery_id)| (*order_id, delivery_id.clone()))
+                .collect(),
+            accepted_epochs: snapshot.accepted_epochs.clone(),
+            order_events: map_values(&snapshot.order_events, |events| proto::OrderEvents {
+                events: events.iter().map(Into::into).collect(),
+            }),
+            removed_orders: map_values(&snapshot.removed_orders, |value| value.into()),
+            tombstones: Some((&snapshot.tombstones).into()),
+            order_updates: map_values(&snapshot.order_updates, |updates| proto::OrderUpdates {
+                updates: updates.iter().map(Into::into).collect(),
+            }),
+            next_order_update_id: snapshot.next_order_update_id,
+            delivery_earnings: map_values(&snapshot.delivery_earnings, |earnings| {
+                proto::Earnings {
+                    earnings: earnings.iter().map(Into::into).collect(),
+                }
+            }),
+            elections: snapshot.elections.iter().map(Into::into).collect(),
+            promo_redemptions: map_values(&snapshot.promo_redemptions, |redemptions| {
+                proto::PromoRedemptions {
+                    redemptions: redemptions.iter().map(Into::into).collect(),
+                }
+            }),
+            user_secrets: HashMap::new(),
+            user_secret_hashes: map_values(&snapshot.user_secrets, |hash| hash.into()),
+            payment_outbox: map_values(&snapshot.payment_outbox, |value| value.into()),
+            order_requests: map_values(&snapshot.order_requests, |requests| proto::OrderRequests {
+                requests: requests.iter().map(Into::into).collect(),
+            }),
+            last_seen: map_values(&snapshot.last_seen, |stamp| (*stamp).into()),
+            next_log_id: snapshot.next_log_id,
+            min_persistent_log_index: snapshot.min_persistent_log_index,
+        }
+    }
+}
+
+impl From<&Tombstones> for proto::Tombstones {
+    fn from(tombstones: &Tombstones) -> Self {
+        let stamps = |stamp: &HlcTimestamp| (*stamp).into();
+        Self {
+            clients: map_values(&tombstones.clients, stamps),
+            restaurants: map_values(&tombstones.restaurants, stamps),
+            deliveries: map_values(&tombstones.deliverys, stamps),
+            orders: map_values(&tombstones.orders, stamps),
+        }
+    }
+}
+
+impl From<proto::Tombstones> for Tombstones {
+    fn from(tombstones: proto::Tombstones) -> Self {
+        fn stamps<K: std::hash::Hash + Eq>(
+            values: HashMap<K, proto::HlcTimestamp>,
+        ) -> HashMap<K, HlcTimestamp> {
+            values
+                .into_iter()
+                .map(|(key, stamp)| (key, stamp.into()))
+                .collect()
+        }
+        Self {
+            clients: stamps(tombstones.clients),
+            restaurants: stamps(tombstones.restaurants),
+            deliverys: stamps(tombstones.deliveries),
+            orders: stamps(tombstones.orders),
+        }
+    }
+}
+
+impl From<&SecretHash> for proto::SecretHash {
+    fn from(hash: &SecretHash) -> Self {
+        Self {
+            salt: hash.salt.clone(),
+            hash: hash.hash.clone(),
+        }
+    }
+}
+
+impl From<proto::SecretHash> for SecretHash {
+    fn from(hash: proto::SecretHash) -> Self {
+        Self {
+            salt: hash.salt,
+            hash: hash.hash,
+        }
+    }
+}
+
+/// Converts the values of a map into protobuf messages, keeping its keys.
+fn map_values<K, V, P>(values: &HashMap<K, V>, convert: impl Fn(&V) -> P) -> HashMap<K, P>
+where
+    K: std::hash::Hash + Eq + Clone,
+{
+    values
+        .iter()
+        .map(|(key, value)| (key.clone(), convert(value)))
+        .collect()
+}
+
+impl TryFrom<proto::Snapshot> for Snapshot {
+    type Error = String;
+
+    fn try_from(snapshot: proto::Snapshot) -> Result<Self, String> {
+        let mut accepted_deliveries = BiMap::new();
+        for (order_id, delivery_id) in snapshot.accepted_deliveries {
+            accepted_deliveries.insert(order_id, delivery_id);
+        }
+        Ok(Self {
+            clients: collect_map(snapshot.clients)?,
+            restaurants: collect_map(snapshot.restaurants)?,
+            deliverys: collect_map(snapshot.deliveries)?,
+            orders: collect_map(snapshot.orders)?,
+            accepted_deliveries,
+            accepted_epochs: snapshot.accepted_epochs,
+            order_events: snapshot
+                .order_events
+                .into_iter()
+                .map(|(order_id, events)| Ok((order_id, collect(events.events)?)))
+                .collect::<Result<_, String>>()?,
+            removed_orders: collect_map(snapshot.removed_orders)?,
+            tombstones: snapshot.tombstones.map(Into::into).unwrap_or_default(),
+            order_updates: snapshot
+                .order_updates
+                .into_iter()
+                .map(|(user_id, updates)| Ok((user_id, collect(updates.updates)?)))
+                .collect::<Result<_, String>>()?,
+            next_order_update_id: snapshot.next_order_update_id,
+            delivery_earnings: snapshot
+                .delivery_earnings
+                .into_iter()
+                .map(|(delivery_id, earnings)| Ok((delivery_id, collect(earnings.earnings)?)))
+                .collect::<Result<_, String>>()?,
+            elections: snapshot.elections.into_iter().map(Into::into).collect(),
+            promo_redemptions: snapshot
+                .promo_redemptions
+                .into_iter()
+                .map(|(code, redemptions)| Ok((code, collect(redemptions.redemptions)?)))
+                .collect::<Result<_, String>>()?,
+            user_secrets: snapshot
+                .user_secrets
+                .into_iter()
+                .map(|(user_id, secret)| (user_id, SecretHash::new(&secret)))
+                .chain(
+                    snapshot
+                        .user_secret_hashes
+                        .into_iter()
+                        .map(|(user_id, hash)| (user_id, hash.into())),
+                )
+                .collect(),
+            payment_outbox: collect_map(snapshot.payment_outbox)?,
+            order_requests: snapshot
+                .order_requests
+                .into_iter()
+                .map(|(client_id, requests)| {
+                    (
+                        client_id,
+                        requests.requests.into_iter().map(Into::into).collect(),
+                    )
+                })
+                .collect(),
+            last_seen: snapshot
+                .last_seen
+                .into_iter()
+                .map(|(user_id, stamp)| (user_id, stamp.into()))
+                .collect(),
+            next_log_id: snapshot.next_log_id,
+            min_persistent_log_index: snapshot.min_persistent_log_index,
+        })
+    }
+}
+
+/////////////////////////////////////////////////////////////////////
+// Operaciones del log de storage
+/////////////////////////////////////////////////////////////////////
+
+fn stamp(time_stamp: &Option<HlcTimestamp>) -> Option<proto::HlcTimestamp> {
+    time_stamp.map(Into::into)
+}
+
+fn from_stamp(time_stamp: Option<proto::HlcTimestamp>) -> Option<HlcTimestamp> {
+    time_stamp.map(Into::into)
+}
+
+fn order_change(order: &OrderDTO, event: &Option<OrderEvent>) -> proto::OrderChange {
+    proto::OrderChange {
+        order: Some(order.into()),
+        event: event.as_ref().map(Into::into),
+    }
+}
+
+fn restaurant_order_change(
+    restaurant_id: &str,
+    order: &OrderDTO,
+    time_stamp: &Option<HlcTimestamp>,
+) -> proto::RestaurantOrderChange {
+    proto::RestaurantOrderChange {
+        restaurant_id: restaurant_id.to_string(),
+        order: Some(order.into()),
+        time_stamp: stamp(time_stamp),
+    }
+}
+
+fn outbox_change(order: &OrderDTO, time_stamp: &Option<HlcTimestamp>) -> proto::OutboxChange {
+    proto::OutboxChange {
+        order: Some(order.into()),
+        time_stamp: stamp(time_stamp),
+    }
+}
+
+impl From<&StorageLogMessage> for proto::StorageLogMessage {
+    fn from(message: &StorageLogMessage) -> Self {
+        let operation = match message {
+            StorageLogMessage::AddClient(msg) => Operation::AddClient(proto::AddClient {
+                client: Some((&msg.client).into()),
+            }),
+            StorageLogMessage::AddRestaurant(msg) => {
+                Operation::AddRestaurant(proto::AddRestaurant {
+                    restaurant: Some((&msg.restaurant).into()),
+                })
+            }
+            StorageLogMessage::AddDelivery(msg) => Operation::AddDelivery(proto::AddDelivery {
+                delivery: Some((&msg.delivery).into()),
+            }),
+            StorageLogMessage::RemoveClient(msg) => Operation::RemoveClient(proto::RemoveClient {
+                client_id: msg.client_id.clone(),
+                time_stamp: stamp(&msg.time_stamp),
+            }),
+            StorageLogMessage::RemoveRestaurant(msg) => {
+                Operation::RemoveRestaurant(proto::RemoveRestaurant {
+                    restaurant_id: msg.restaurant_id.clone(),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::RemoveDelivery(msg) => {
+                Operation::RemoveDelivery(proto::RemoveDelivery {
+                    delivery_id: msg.delivery_id.clone(),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::SetDeliveryPosition(msg) => {
+                Operation::SetDeliveryPosition(proto::SetDeliveryPosition {
+                    delivery_id: msg.delivery_id.clone(),
+                    position: position(msg.position),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::SetCurrentClientToDelivery(msg) => {
+                Operation::SetCurrentClientToDelivery(proto::SetCurrentClientToDelivery {
+                    delivery_id: msg.delivery_id.clone(),
+                    client_id: msg.client_id.clone(),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::SetDeliveryStatus(msg) => {
+                Operation::SetDeliveryStatus(proto::SetDeliveryStatus {
+                    delivery_id: msg.delivery_id.clone(),
+                    delivery_status: proto::DeliveryStatus::from(msg.delivery_status).into(),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::AddOrder(msg) => {
+                Operation::AddOrder(order_change(&msg.order, &msg.event))
+            }
+            StorageLogMessage::RemoveOrder(msg) => {
+                Operation::RemoveOrder(order_change(&msg.order, &msg.event))
+            }
+            StorageLogMessage::AddAuthorizedOrderToRestaurant(msg) => {
+                Operation::AddAuthorizedOrderToRestaurant(restaurant_order_change(
+                    &msg.restaurant_id,
+                    &msg.order,
+                    &msg.time_stamp,
+                ))
+            }
+            StorageLogMessage::AddPendingOrderToRestaurant(msg) => {
+                Operation::AddPendingOrderToRestaurant(restaurant_order_change(
+                    &msg.restaurant_id,
+                    &msg.order,
+                    &msg.time_stamp,
+                ))
+            }
+            StorageLogMessage::RemoveAuthorizedOrderToRestaurant(msg) => {
+                Operation::RemoveAuthorizedOrderToRestaurant(restaurant_order_change(
+                    &msg.restaurant_id,
+                    &msg.order,
+                    &msg.time_stamp,
+                ))
+            }
+            StorageLogMessage::RemovePendingOrderToRestaurant(msg) => {
+                Operation::RemovePendingOrderToRestaurant(restaurant_order_change(
+                    &msg.restaurant_id,
+                    &msg.order,
+                    &msg.time_stamp,
+                ))
+            }
+            StorageLogMessage::SetCurrentOrderToDelivery(msg) => {
+                Operation::SetCurrentOrderToDelivery(proto::SetCurrentOrderToDelivery {
+                    delivery_id: msg.delivery_id.clone(),
+                    order: Some((&msg.order).into()),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::SetDeliveryToOrder(msg) => {
+                Operation::SetDeliveryToOrder(proto::SetDeliveryToOrder {
+                    order: Some((&msg.order).into()),
+                    delivery_id: msg.delivery_id.clone(),
+                    event: msg.event.as_ref().map(Into::into),
+                })
+            }
+            StorageLogMessage::SetOrderStatus(msg) => {
+                Operation::SetOrderStatus(proto::SetOrderStatus {
+                    order: Some((&msg.order).into()),
+                    order_status: proto::OrderStatus::from(&msg.order_status).into(),
+                    event: msg.event.as_ref().map(Into::into),
+                })
+            }
+            StorageLogMessage::SetOrderExpectedTime(msg) => {
+                Operation::SetOrderExpectedTime(proto::SetOrderTime {
+                    order_id: msg.order_id,
+                    time: msg.expected_time,
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::SetOrderPreparationTime(msg) => {
+                Operation::SetOrderPreparationTime(proto::SetOrderTime {
+                    order_id: msg.order_id,
+                    time: msg.preparation_time,
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::InsertAcceptedDelivery(msg) => {
+                Operation::InsertAcceptedDelivery(proto::InsertAcceptedDelivery {
+                    order_id: msg.order_id,
+                    delivery_id: msg.delivery_id.clone(),
+                    epoch: msg.epoch,
+                })
+            }
+            StorageLogMessage::RemoveAcceptedDeliveries(msg) => {
+                Operation::RemoveAcceptedDeliveries(proto::RemoveAcceptedDeliveries {
+                    order_id: msg.order_id,
+                })
+            }
+            StorageLogMessage::AddDeliveryEarning(msg) => {
+                Operation::AddDeliveryEarning(proto::AddDeliveryEarning {
+                    delivery_id: msg.delivery_id.clone(),
+                    order: Some((&msg.order).into()),
+                    earning: msg.earning.as_ref().map(Into::into),
+                })
+            }
+            StorageLogMessage::SetEarningPaidOut(msg) => {
+                Operation::SetEarningPaidOut(proto::SetEarningPaidOut {
+                    delivery_id: msg.delivery_id.clone(),
+                    order_id: msg.order_id,
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::AddCancellationFee(msg) => {
+                Operation::AddCancellationFee(proto::AddCancellationFee {
+                    restaurant_id: msg.restaurant_id.clone(),
+                    fee: Some((&msg.fee).into()),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::AddPromoRedemption(msg) => {
+                Operation::AddPromoRedemption(proto::AddPromoRedemption {
+                    redemption: Some((&msg.redemption).into()),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::AddOutboxPayment(msg) => {
+                Operation::AddOutboxPayment(outbox_change(&msg.order, &msg.time_stamp))
+            }
+            StorageLogMessage::CompleteOutboxPayment(msg) => {
+                Operation::CompleteOutboxPayment(outbox_change(&msg.order, &msg.time_stamp))
+            }
+            StorageLogMessage::SetRestaurantOpen(msg) => {
+                Operation::SetRestaurantOpen(proto::SetRestaurantOpen {
+                    restaurant_id: msg.restaurant_id.clone(),
+                    open: msg.open,
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::SetRestaurantTags(msg) => {
+                Operation::SetRestaurantTags(proto::SetRestaurantTags {
+                    restaurant_id: msg.restaurant_id.clone(),
+                    tags: msg.tags.clone(),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::SetUserOffline(msg) => {
+                Operation::SetUserOffline(proto::SetUserOffline {
+                    user_id: msg.user_id.clone(),
+                    time_stamp: stamp(&msg.time_stamp),
+                })
+            }
+            StorageLogMessage::TouchUsers(msg) => Operation::TouchUsers(proto::TouchUsers {
+                user_ids: msg.user_ids.clone(),
+                time_stamp: stamp(&msg.time_stamp),
+            }),
+            StorageLogMessage::ClaimUserId(msg) => Operation::ClaimUserId(proto::ClaimUserId {
+                user_id: msg.user_id.clone(),
+                secret: msg.secret.clone(),
+                tenant_id: msg.tenant_id.to_string(),
+                secret_hash: msg.secret_hash.as_ref().map(Into::into),
+            }),
+            StorageLogMessage::ClaimOrderRequest(msg) => {
+                Operation::ClaimOrderRequest(proto::ClaimOrderRequest {
+                    client_id: msg.client_id.clone(),
+                    request: Some((&msg.request).into()),
+                })
+            }
+            StorageLogMessage::RecordElection(msg) => {
+                Operation::RecordElection(proto::RecordElection {
+                    record: Some((&msg.record).into()),
+                })
+            }
+            StorageLogMessage::QueueOrderUpdate(msg) => {
+                Operation::QueueOrderUpdate(proto::QueueOrderUpdate {
+                    update: Some((&msg.update).into()),
+                })
+            }
+            StorageLogMessage::AckOrderUpdates(msg) => {
+                Operation::AckOrderUpdates(proto::AckOrderUpdates {
+                    user_id: msg.user_id.clone(),
+                    update_id: msg.update_id,
+                })
+            }
+        };
+        Self {
+            operation: Some(operation),
+        }
+    }
+}
+
+impl TryFrom<proto::StorageLogMessage> for StorageLogMessage {
+    type Error = String;
+
+    fn try_from(message: proto::StorageLogMessage) -> Result<Self, String> {
+        Ok(match required(message.operation, "operation")? {
+            Operation::AddClient(msg) => StorageLogMessage::AddClient(AddClient {
+                client: required(msg.client, "client")?.try_into()?,
+            }),
+            Operation::AddRestaurant(msg) => StorageLogMessage::AddRestaurant(AddRestaurant {
+                restaurant: required(msg.restaurant, "restaurant")?.try_into()?,
+            }),
+            Operation::AddDelivery(msg) => StorageLogMessage::AddDelivery(AddDelivery {
+                delivery: required(msg.delivery, "delivery")?.try_into()?,
+            }),
+            Operation::RemoveClient(msg) => StorageLogMessage::RemoveClient(RemoveClient {
+                client_id: msg.client_id,
+                time_stamp: from_stamp(msg.time_stamp),
+            }),
+            Operation::RemoveRestaurant(msg) => {
+                StorageLogMessage::RemoveRestaurant(RemoveRestaurant {
+                    restaurant_id: msg.restaurant_id,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::RemoveDelivery(msg) => StorageLogMessage::RemoveDelivery(RemoveDelivery {
+                delivery_id: msg.delivery_id,
+                time_stamp: from_stamp(msg.time_stamp),
+            }),
+            Operation::SetDeliveryPosition(msg) => {
+                StorageLogMessage::SetDeliveryPosition(SetDeliveryPosition {
+                    delivery_id: msg.delivery_id,
+                    position: from_position(msg.position, "position")?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::SetCurrentClientToDelivery(msg) => {
+                StorageLogMessage::SetCurrentClientToDelivery(SetCurrentClientToDelivery {
+                    delivery_id: msg.delivery_id,
+                    client_id: msg.client_id,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::SetDeliveryStatus(msg) => {
+                StorageLogMessage::SetDeliveryStatus(SetDeliveryStatus {
+                    delivery_id: msg.delivery_id,
+                    delivery_status: delivery_status(msg.delivery_status)?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::AddOrder(msg) => StorageLogMessage::AddOrder(AddOrder {
+                order: from_order(msg.order)?,
+                event: from_event(msg.event)?,
+            }),
+            Operation::RemoveOrder(msg) => StorageLogMessage::RemoveOrder(RemoveOrder {
+                order: from_order(msg.order)?,
+                event: from_event(msg.event)?,
+            }),
+            Operation::AddAuthorizedOrderToRestaurant(msg) => {
+                StorageLogMessage::AddAuthorizedOrderToRestaurant(AddAuthorizedOrderToRestaurant {
+                    restaurant_id: msg.restaurant_id,
+                    order: from_order(msg.order)?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::AddPendingOrderToRestaurant(msg) => {
+                StorageLogMessage::AddPendingOrderToRestaurant(AddPendingOrderToRestaurant {
+                    restaurant_id: msg.restaurant_id,
+                    order: from_order(msg.order)?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::RemoveAuthorizedOrderToRestaurant(msg) => {
+                StorageLogMessage::RemoveAuthorizedOrderToRestaurant(
+                    RemoveAuthorizedOrderToRestaurant {
+                        restaurant_id: msg.restaurant_id,
+                        order: from_order(msg.order)?,
+                        time_stamp: from_stamp(msg.time_stamp),
+                    },
+                )
+            }
+            Operation::RemovePendingOrderToRestaurant(msg) => {
+                StorageLogMessage::RemovePendingOrderToRestaurant(RemovePendingOrderToRestaurant {
+                    restaurant_id: msg.restaurant_id,
+                    order: from_order(msg.order)?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::SetCurrentOrderToDelivery(msg) => {
+                StorageLogMessage::SetCurrentOrderToDelivery(SetCurrentOrderToDelivery {
+                    delivery_id: msg.delivery_id,
+                    order: from_order(msg.order)?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::SetDeliveryToOrder(msg) => {
+                StorageLogMessage::SetDeliveryToOrder(SetDeliveryToOrder {
+                    order: from_order(msg.order)?,
+                    delivery_id: msg.delivery_id,
+                    event: from_event(msg.event)?,
+                })
+            }
+            Operation::SetOrderStatus(msg) => StorageLogMessage::SetOrderStatus(SetOrderStatus {
+                order: from_order(msg.order)?,
+                order_status: order_status(msg.order_status)?,
+                event: from_event(msg.event)?,
+            }),
+            Operation::SetOrderExpectedTime(msg) => {
+                StorageLogMessage::SetOrderExpectedTime(SetOrderExpectedTime {
+                    order_id: msg.order_id,
+                    expected_time: msg.time,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::SetOrderPreparationTime(msg) => {
+                StorageLogMessage::SetOrderPreparationTime(SetOrderPreparationTime {
+                    order_id: msg.order_id,
+                    preparation_time: msg.time,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::InsertAcceptedDelivery(msg) => {
+                StorageLogMessage::InsertAcceptedDelivery(InsertAcceptedDelivery {
+                    order_id: msg.order_id,
+                    delivery_id: msg.delivery_id,
+                    epoch: msg.epoch,
+                })
+            }
+            Operation::RemoveAcceptedDeliveries(msg) => {
+                StorageLogMessage::RemoveAcceptedDeliveries(RemoveAcceptedDeliveries {
+                    order_id: msg.order_id,
+                })
+            }
+            Operation::AddDeliveryEarning(msg) => {
+                StorageLogMessage::AddDeliveryEarning(AddDeliveryEarning {
+                    delivery_id: msg.delivery_id,
+                    order: from_order(msg.order)?,
+                    earning: msg.earning.map(TryInto::try_into).transpose()?,
+                })
+            }
+            Operation::SetEarningPaidOut(msg) => {
+                StorageLogMessage::SetEarningPaidOut(SetEarningPaidOut {
+                    delivery_id: msg.delivery_id,
+                    order_id: msg.order_id,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::AddCancellationFee(msg) => {
+                StorageLogMessage::AddCancellationFee(AddCancellationFee {
+                    restaurant_id: msg.restaurant_id,
+                    fee: required(msg.fee, "fee")?.try_into()?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::AddPromoRedemption(msg) => {
+                StorageLogMessage::AddPromoRedemption(AddPromoRedemption {
+                    redemption: required(msg.redemption, "redemption")?.try_into()?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::AddOutboxPayment(msg) => {
+                StorageLogMessage::AddOutboxPayment(AddOutboxPayment {
+                    order: from_order(msg.order)?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::CompleteOutboxPayment(msg) => {
+                StorageLogMessage::CompleteOutboxPayment(CompleteOutboxPayment {
+                    order: from_order(msg.order)?,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::SetRestaurantOpen(msg) => {
+                StorageLogMessage::SetRestaurantOpen(SetRestaurantOpen {
+                    restaurant_id: msg.restaurant_id,
+                    open: msg.open,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::SetRestaurantTags(msg) => {
+                StorageLogMessage::SetRestaurantTags(SetRestaurantTags {
+                    restaurant_id: msg.restaurant_id,
+                    tags: msg.tags,
+                    time_stamp: from_stamp(msg.time_stamp),
+                })
+            }
+            Operation::SetUserOffline(msg) => StorageLogMessage::SetUserOffline(SetUserOffline {
+                user_id: msg.user_id,
+                time_stamp: from_stamp(msg.time_stamp),
+            }),
+            Operation::TouchUsers(msg) => StorageLogMessage::TouchUsers(TouchUsers {
+                user_ids: msg.user_ids,
+                time_stamp: from_stamp(msg.time_stamp),
+            }),
+            Operation::ClaimUserId(msg) => StorageLogMessage::ClaimUserId(ClaimUserId {
+                user_id: msg.user_id,
+                secret: msg.secret,
+                tenant_id: TenantId::new(&msg.tenant_id),
+                secret_hash: msg.secret_hash.map(Into::into),
+            }),
+            Operation::ClaimOrderRequest(msg) => {
+                StorageLogMessage::ClaimOrderRequest(ClaimOrderRequest {
+                    client_id: msg.client_id,
+                    request: required(msg.request, "request")?.into(),
+                })
+            }
+            Operation::RecordElection(msg) => StorageLogMessage::RecordElection(RecordElection {
+                record: required(msg.record, "record")?.into(),
+            }),
+            Operation::QueueOrderUpdate(msg) => {
+                StorageLogMessage::QueueOrderUpdate(QueueOrderUpdate {
+                    update: required(msg.update, "update")?.try_into()?,
+                })
+            }
+            Operation::AckOrderUpdates(msg) => {
+                StorageLogMessage::AckOrderUpdates(AckOrderUpdates {
+                    user_id: msg.user_id,
+                    update_id: msg.update_id,
+                })
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+    use std::collections::HashSet;
+
+    fn stamp_at(physical_millis: u64) -> HlcTimestamp {
+        HlcTimestamp {
+            physical_millis,
+            logical: 2,
+        }
+    }
+
+    fn order(order_id: u64) -> OrderDTO {
+        OrderDTO {
+            order_id,
+            items: vec![OrderItem::new("Pizza", 2), OrderItem::new("Empanada", 6)],
+            client_id: "client_1".to_string(),
+            restaurant_id: "resto_1".to_string(),
+            delivery_id: Some("delivery_1".to_string()),
+            status: OrderStatus::Delivering,
+            client_position: (1.5, 7.25),
+            expected_delivery_time: 30,
+            expected_preparation_time: 1_200,
+            priority: OrderPriority::Express,
+            discount: 150,
+            tenant_id: TenantId::new("staging"),
+            time_stamp: stamp_at(10),
+        }
+    }
+
+    fn event(order: &OrderDTO) -> OrderEvent {
+        let mut event = OrderEvent::new(OrderEventKind::StatusChanged, order, "server_1");
+        event.reason = Some(RejectionReason::TooBusy);
+        event
+    }
+
+    /// Sends `message` through a protobuf frame and back.
+    fn round_trip(message: &NetworkMessage) -> NetworkMessage {
+        let bytes = encode(message).unwrap().encode_to_vec();
+        decode(proto::Envelope::decode(bytes.as_slice()).unwrap()).unwrap()
+    }
+
+    fn as_json<T: serde::Serialize>(value: &T) -> serde_json::Value {
+        serde_json::to_value(value).unwrap()
+    }
+
+    #[test]
+    fn storage_updates_travel_as_protobuf_messages() {
+        let order = order(7);
+        let operations = vec![
+            StorageLogMessage::AddOrder(AddOrder {
+                order: order.clone(),
+                event: Some(event(&order)),
+            }),
+            StorageLogMessage::SetDeliveryStatus(SetDeliveryStatus {
+                delivery_id: "delivery_1".to_string(),
+                delivery_status: DeliveryStatus::WaitingConfirmation,
+                time_stamp: Some(stamp_at(11)),
+            }),
+            StorageLogMessage::AddPendingOrderToRestaurant(AddPendingOrderToRestaurant {
+                restaurant_id: "resto_1".to_string(),
+                order: order.clone(),
+                time_stamp: None,
+            }),
+            StorageLogMessage::SetOrderPreparationTime(SetOrderPreparationTime {
+                order_id: 7,
+                preparation_time: 900,
+                time_stamp: Some(stamp_at(12)),
+            }),
+            StorageLogMessage::AddDeliveryEarning(AddDeliveryEarning {
+                delivery_id: "delivery_1".to_string(),
+                order: order.clone(),
+                earning: Some(EarningDTO::for_distance(7, 4.0, stamp_at(13))),
+            }),
+            StorageLogMessage::ClaimUserId(ClaimUserId {
+                user_id: "client_1".to_string(),
+                secret: Some("s3cret".to_string()),
+                tenant_id: TenantId::new("staging"),
+                secret_hash: Some(SecretHash {
+                    salt: "5a17".to_string(),
+                    hash: "ab12".to_string(),
+                }),
+            }),
+            StorageLogMessage::ClaimOrderRequest(ClaimOrderRequest {
+                client_id: "client_1".to_string(),
+                request: OrderRequest {
+                    request_id: 99,
+                    order_id: 7,
+                },
+            }),
+            StorageLogMessage::QueueOrderUpdate(QueueOrderUpdate {
+                update: NotifyOrderUpdated {
+                    peer_id: "client_1".to_string(),
+                    order: order.clone(),
+                    update_id: 3,
+                    request_id: 99,
+                    decline: Some(DeclineReason::FraudCheck),
+                },
+            }),
+            StorageLogMessage::CompleteOutboxPayment(CompleteOutboxPayment {
+                order,
+                time_stamp: Some(stamp_at(14)),
+            }),
+        ];
+        let message = NetworkMessage::StorageUpdates(StorageUpdates {
+            updates: operations
+                .into_iter()
+                .enumerate()
+                .map(|(i, op)| (i as u64, op))
+                .collect(),
+            coordinator_id: "server_0".to_string(),
+            epoch: 4,
+        });
+
+        let Payload::StorageUpdates(encoded) = encode(&message).unwrap().payload.unwrap() else {
+            panic!("StorageUpdates should have its own protobuf message");
+        };
+        assert!(
+            encoded
+                .updates
+                .values()
+                .all(|update| update.operation.is_some())
+        );
+        assert_eq!(as_json(&round_trip(&message)), as_json(&message));
+    }
+
+    #[test]
+    fn snapshots_travel_as_protobuf_messages() {
+        let removed = order(5);
+        let order = order(7);
+        let mut accepted_deliveries = BiMap::new();
+        accepted_deliveries.insert(7, "delivery_1".to_string());
+        let snapshot = Snapshot {
+            clients: HashMap::from([(
+                "client_1".to_string(),
+                ClientDTO {
+                    client_position: (1.5, 7.25),
+                    client_id: "client_1".to_string(),
+                    client_order: Some(order.clone()),
+                    tenant_id: TenantId::new("staging"),
+                    time_stamp: stamp_at(1),
+                },
+            )]),
+            restaurants: HashMap::from([(
+                "resto_1".to_string(),
+                RestaurantDTO {
+                    restaurant_position: (3.0, 4.0),
+                    restaurant_id: "resto_1".to_string(),
+                    authorized_orders: HashSet::from([order.clone()]),
+                    pending_orders: HashSet::new(),
+                    cancellation_fees: vec![CancellationFee {
+                        order_id: 5,
+                        stage: OrderStatus::Preparing,
+                        price: 1_000,
+                        fee: 200,
+                    }],
+                    closed: true,
+                    tags: vec!["pizza".to_string()],
+                    tenant_id: TenantId::new("staging"),
+                    time_stamp: stamp_at(2),
+                },
+            )]),
+            deliverys: HashMap::from([(
+                "delivery_1".to_string(),
+                DeliveryDTO {
+                    delivery_position: (5.0, 6.0),
+                    delivery_id: "delivery_1".to_string(),
+                    current_client_id: Some("client_1".to_string()),
+                    current_order: Some(order.clone()),
+                    status: DeliveryStatus::Delivering,
+                    vehicle: VehicleType::Car,
+                    tenant_id: TenantId::new("staging"),
+                    time_stamp: stamp_at(3),
+                },
+            )]),
+            orders: HashMap::from([(7, order.clone())]),
+            accepted_deliveries,
+            accepted_epochs: HashMap::from([(7, 4)]),
+            order_events: HashMap::from([(7, vec![event(&order)]), (5, vec![event(&removed)])]),
+            removed_orders: HashMap::from([(5, removed)]),
+            tombstones: Tombstones {
+                clients: HashMap::from([("client_2".to_string(), stamp_at(8))]),
+                orders: HashMap::from([(6, stamp_at(9))]),
+                ..Tombstones::default()
+            },
+            order_updates: HashMap::from([(
+                "client_1".to_string(),
+                vec![NotifyOrderUpdated {
+                    peer_id: "client_1".to_string(),
+                    order: order.clone(),
+                    update_id: 3,
+                    request_id: 0,
+                    decline: None,
+                }],
+            )]),
+            next_order_update_id: 4,
+            delivery_earnings: HashMap::from([(
+                "delivery_1".to_string(),
+                vec![EarningDTO::for_distance(7, 4.0, stamp_at(4))],
+            )]),
+            elections: vec![ElectionRecord {
+                leader_id: "server_1".to_string(),
+                epoch: 4,
+                at_millis: 1_000,
+            }],
+            promo_redemptions: HashMap::from([(
+                "WELCOME".to_string(),
+                vec![PromoRedemption {
+                    code: "WELCOME".to_string(),
+                    client_id: "client_1".to_string(),
+                    order_id: 7,
+                    discount: 150,
+                    time_stamp: stamp_at(5),
+                }],
+            )]),
+            user_secrets: HashMap::from([(
+                "client_1".to_string(),
+                SecretHash {
+                    salt: "5a17".to_string(),
+                    hash: "ab12".to_string(),
+                },
+            )]),
+            payment_outbox: HashMap::from([(
+                7,
+                OutboxPayment {
+                    order,
+                    completed: false,
+                    time_stamp: stamp_at(6),
+                },
+            )]),
+            order_requests: HashMap::from([(
+                "client_1".to_string(),
+                vec![OrderRequest {
+                    request_id: 99,
+                    order_id: 7,
+                }],
+            )]),
+            last_seen: HashMap::from([("client_1".to_string(), stamp_at(7))]),
+            next_log_id: 42,
+            min_persistent_log_index: 30,
+        };
+        let message = NetworkMessage::StorageSnapshot(StorageSnapshot {
+            snapshot: Box::new(snapshot),
+            coordinator_id: "server_0".to_string(),
+            epoch: 4,
+        });
+
+        assert_eq!(as_json(&round_trip(&message)), as_json(&message));
+    }
+
+    #[test]
+    fn plain_secrets_of_older_coordinators_are_hashed() {
+        let encoded = proto::Snapshot {
+            user_secrets: HashMap::from([("client_1".to_string(), "s3cret".to_string())]),
+            ..Default::default()
+        };
+        let snapshot = Snapshot::try_from(encoded).unwrap();
+
+        assert!(snapshot.user_secrets["client_1"].matches("s3cret"));
+        assert!(proto::Snapshot::from(&snapshot).user_secrets.is_empty());
+    }
+
+    #[test]
+    fn unknown_enum_values_are_rejected() {
+        let mut encoded = proto::Order::from(&order(7));
+        encoded.status = 99;
+        assert!(OrderDTO::try_from(encoded).is_err());
+
+        let missing = proto::StorageLogMessage {
+            operation: Some(Operation::AddClient(proto::AddClient { client: None })),
+        };
+        assert!(StorageLogMessage::try_from(missing).is_err());
+    }
+}
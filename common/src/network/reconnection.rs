@@ -0,0 +1,290 @@
+use crate::constants::{
+    DELAY_SECONDS_TO_START_RECONNECT, RECONNECT_JITTER, RECONNECT_MAX_ATTEMPTS, RECONNECT_MAX_DELAY,
+};
+use crate::network::connections::reconnect;
+use crate::network::peer_types::PeerIdentity;
+use crate::network::stream::PeerStream;
+use actix::fut::wrap_future;
+use actix::prelude::*;
+use rand::Rng;
+use std::fmt;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// State of the connection of a peer to the servers, as seen by a [`ReconnectingConnection`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReconnectionState {
+    /// The peer is connected, or has not lost its connection yet.
+    Connected,
+    /// The connection was lost and the attempt `attempt` is due after `delay`.
+    Waiting { attempt: u32, delay: Duration },
+    /// The attempt `attempt` to reach one of the servers is in progress.
+    Reconnecting { attempt: u32 },
+    /// Every attempt failed: the peer gives up.
+    GaveUp,
+}
+
+impl fmt::Display for ReconnectionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReconnectionState::Connected => write!(f, "connected"),
+            ReconnectionState::Waiting { attempt, delay } => {
+                write!(f, "waiting {:?} before attempt {}", delay, attempt)
+            }
+            ReconnectionState::Reconnecting { attempt } => {
+                write!(f, "reconnecting (attempt {})", attempt)
+            }
+            ReconnectionState::GaveUp => write!(f, "gave up"),
+        }
+    }
+}
+
+/// Reconnection of a peer to the servers after its connection is lost.
+///
+/// Each attempt tries every server once. The first attempt is made
+/// [`DELAY_SECONDS_TO_START_RECONNECT`] after the connection is lost, and each failed attempt
+/// doubles the wait before the next one up to [`RECONNECT_MAX_DELAY`], moved a random
+/// fraction of up to [`RECONNECT_JITTER`] so that the peers disconnected by the same failure
+/// do not all come back at once. After [`RECONNECT_MAX_ATTEMPTS`] failed attempts the peer
+/// gives up.
+#[derive(Debug)]
+pub struct ReconnectingConnection {
+    /// The servers to reconnect to, in order of preference.
+    servers: Vec<SocketAddr>,
+    /// The identity announced to the servers when connecting.
+    identity: PeerIdentity,
+    /// Current state.
+    state: ReconnectionState,
+    /// Failed attempts since the connection was lost.
+    attempts: u32,
+    /// Delay before the first attempt.
+    base: Duration,
+    /// Maximum delay between attempts.
+    max: Duration,
+    /// Attempts before giving up.
+    max_attempts: u32,
+    /// Largest fraction of the delay added or removed at random.
+    jitter: f64,
+    /// Timer of the next attempt, while waiting.
+    timer: Option<SpawnHandle>,
+}
+
+impl ReconnectingConnection {
+    /// Creates a connected `ReconnectingConnection` with the default backoff.
+    ///
+    /// ## Arguments
+    /// * `servers` - The servers to reconnect to.
+    /// * `identity` - The identity announced to the servers when connecting.
+    pub fn new(servers: Vec<SocketAddr>, identity: PeerIdentity) -> Self {
+        Self {
+            servers,
+            identity,
+            state: ReconnectionState::Connected,
+            attempts: 0,
+            base: DELAY_SECONDS_TO_START_RECONNECT,
+            max: RECONNECT_MAX_DELAY,
+            max_attempts: RECONNECT_MAX_ATTEMPTS,
+            jitter: RECONNECT_JITTER,
+            timer: None,
+        }
+    }
+
+    /// Replaces the default backoff.
+    ///
+    /// ## Arguments
+    /// * `base` - Delay before the first attempt.
+    /// * `max` - Maximum delay between attempts.
+    /// * `max_attempts` - Attempts before giving up.
+    /// * `jitter` - Largest fraction of the delay added or removed at random.
+    pub fn with_backoff(
+        mut self,
+        base: Duration,
+        max: Duration,
+        max_attempts: u32,
+        jitter: f64,
+    ) -> Self {
+        self.base = base;
+        self.max = max;
+        self.max_attempts = max_attempts;
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> ReconnectionState {
+        self.state
+    }
+
+    /// Returns whether an attempt is due or in progress.
+    pub fn is_reconnecting(&self) -> bool {
+        matches!(
+            self.state,
+            ReconnectionState::Waiting { .. } | ReconnectionState::Reconnecting { .. }
+        )
+    }
+
+    /// Records that the peer is connected again, resetting the backoff.
+    fn record_success(&mut self) {
+        self.state = ReconnectionState::Connected;
+        self.attempts = 0;
+        self.timer = None;
+    }
+
+    /// Records a failed attempt, or the lost connection before the first one.
+    ///
+    /// ## Arguments
+    /// * `sample` - A number in `[0, 1)` choosing the jitter of the delay.
+    ///
+    /// ## Returns
+    /// How long to wait before the next attempt, or `None` if the peer gives up.
+    fn record_failure(&mut self, sample: f64) -> Option<Duration> {
+        if self.attempts >= self.max_attempts {
+            self.state = ReconnectionState::GaveUp;
+            return None;
+        }
+        self.attempts += 1;
+        // 2^(intentos - 1) veces la espera base, sin pasarse del máximo
+        let exponent = (self.attempts - 1).min(31);
+        let delay = self
+            .base
+            .checked_mul(1 << exponent)
+            .map_or(self.max, |delay| delay.min(self.max));
+        let delay = delay.mul_f64(1.0 + self.jitter * (2.0 * sample - 1.0));
+        self.state = ReconnectionState::Waiting {
+            attempt: self.attempts,
+            delay,
+        };
+        Some(delay)
+    }
+}
+
+/// An actor that reconnects to the servers with a [`ReconnectingConnection`] when its
+/// connection is lost.
+///
+/// The actor reports the lost connection with [`Reconnecting::connection_lost`], and the
+/// reconnection calls it back with the new stream, and on every change of state.
+pub trait Reconnecting: Actor<Context = Context<Self>> {
+    /// Returns the reconnection of the actor.
+    fn reconnection(&mut self) -> &mut ReconnectingConnection;
+
+    /// Returns whether the actor got a connection by other means, e.g. to a new leader, so
+    /// that a pending attempt is not needed anymore.
+    fn is_connected(&self) -> bool;
+
+    /// Called with the stream to the server reached after the connection was lost.
+    fn on_reconnected(&mut self, stream: PeerStream, ctx: &mut Context<Self>);
+
+    /// Called when the state of the reconnection changes.
+    fn on_reconnection_state(&mut self, _state: ReconnectionState, _ctx: &mut Context<Self>) {}
+
+    /// Called when the actor gives up reconnecting. Stops the actor by default.
+    fn on_reconnection_failed(&mut self, ctx: &mut Context<Self>) {
+        ctx.stop();
+    }
+
+    /// Starts reconnecting after the connection was lost, unless it is already reconnecting.
+    fn connection_lost(&mut self, ctx: &mut Context<Self>) {
+        if self.reconnection().is_reconnecting() {
+            return;
+        }
+        self.schedule_reconnection(ctx);
+    }
+
+    /// Schedules the next attempt after the backoff, or gives up.
+    fn schedule_reconnection(&mut self, ctx: &mut Context<Self>) {
+        let sample = rand::thread_rng().gen_range(0.0..1.0);
+        let Some(delay) = self.reconnection().record_failure(sample) else {
+            self.on_reconnection_state(ReconnectionState::GaveUp, ctx);
+            self.on_reconnection_failed(ctx);
+            return;
+        };
+        let state = self.reconnection().state();
+        self.on_reconnection_state(state, ctx);
+        let timer = ctx.run_later(delay, |actor, ctx| actor.attempt_reconnection(ctx));
+        self.reconnection().timer = Some(timer);
+    }
+
+    /// Tries every server once, and schedules the next attempt if none answers.
+    fn attempt_reconnection(&mut self, ctx: &mut Context<Self>) {
+        let reconnection = self.reconnection();
+        reconnection.timer = None;
+        if self.is_connected() {
+            self.reconnection().record_success();
+            self.on_reconnection_state(ReconnectionState::Connected, ctx);
+            return;
+        }
+
+        let reconnection = self.reconnection();
+        let state = ReconnectionState::Reconnecting {
+            attempt: reconnection.attempts,
+        };
+        reconnection.state = state;
+        let servers = reconnection.servers.clone();
+        let identity = reconnection.identity.clone();
+        self.on_reconnection_state(state, ctx);
+
+        ctx.spawn(
+            wrap_future::<_, Self>(async move { reconnect(servers, &identity).await }).map(
+                |result, actor, ctx| match result {
+                    Some(stream) => {
+                        actor.reconnection().record_success();
+                        actor.on_reconnection_state(ReconnectionState::Connected, ctx);
+                        actor.on_reconnected(stream, ctx);
+                    }
+                    None => actor.schedule_reconnection(ctx),
+                },
+            ),
+        );
+    }
+
+    /// Cancels the pending attempt, if any, once the actor got a connection by other means.
+    fn cancel_reconnection(&mut self, ctx: &mut Context<Self>) {
+        if let Some(timer) = self.reconnection().timer.take() {
+            ctx.cancel_future(timer);
+        }
+        self.reconnection().record_success();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::peer_types::PeerType;
+
+    fn connection() -> ReconnectingConnection {
+        ReconnectingConnection::new(
+            vec!["127.0.0.1:8080".parse().unwrap()],
+            PeerIdentity::new(PeerType::ClientType, "client_1".to_string()),
+        )
+        .with_backoff(Duration::from_secs(1), Duration::from_secs(5), 4, 0.5)
+    }
+
+    #[test]
+    fn backs_off_exponentially_until_giving_up() {
+        let mut connection = connection();
+        // Con la muestra del medio no se mueve la espera
+        let delays: Vec<Option<u64>> = (0..5)
+            .map(|_| connection.record_failure(0.5).map(|d| d.as_secs()))
+            .collect();
+        assert_eq!(delays, vec![Some(1), Some(2), Some(4), Some(5), None]);
+        assert_eq!(connection.state(), ReconnectionState::GaveUp);
+        assert!(!connection.is_reconnecting());
+
+        connection.record_success();
+        assert_eq!(connection.state(), ReconnectionState::Connected);
+        assert_eq!(connection.record_failure(0.5), Some(Duration::from_secs(1)));
+        assert!(connection.is_reconnecting());
+    }
+
+    #[test]
+    fn moves_the_delay_within_the_jitter() {
+        let mut connection = connection();
+        assert_eq!(
+            connection.record_failure(0.0),
+            Some(Duration::from_millis(500))
+        );
+        connection.record_success();
+        let delay = connection.record_failure(0.99).unwrap();
+        assert!(delay > Duration::from_millis(1400) && delay < Duration::from_millis(1500));
+    }
+}
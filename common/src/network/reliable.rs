@@ -0,0 +1,320 @@
+use crate::constants::{RELIABLE_MAX_ATTEMPTS, RELIABLE_RESEND_INTERVAL, RELIABLE_SEEN_IDS};
+use crate::messages::shared_messages::NetworkMessage;
+use crate::network::faults::message_kind;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Environment variable used to configure which messages every connection of a process
+/// sends with acknowledged delivery.
+///
+/// Its value is `off` or a comma-separated list of `key=value` pairs, e.g.
+/// `only=DeliverThisOrder|CancelOrder,resend=1000,attempts=3`. The resend interval is in
+/// milliseconds.
+pub const RELIABLE_DELIVERY_ENV_VAR: &str = "RELIABLE_DELIVERY";
+
+/// Messages sent with acknowledged delivery unless [`RELIABLE_DELIVERY_ENV_VAR`] says otherwise.
+pub const DEFAULT_RELIABLE_MESSAGES: [&str; 3] =
+    ["DeliverThisOrder", "CancelOrder", "AcceptedOrder"];
+
+/// Which messages a connection sends with acknowledged delivery, and how it resends them.
+///
+/// Each of these messages is wrapped in a
+/// [`ReliableMessage`](crate::messages::shared_messages::ReliableMessage) and resent every
+/// `resend_after` until the peer acknowledges it, up to `max_attempts` times.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReliableConfig {
+    /// Types of the messages sent with acknowledged delivery (e.g. `AcceptedOrder`).
+    pub only: Vec<String>,
+    /// Time to wait for an acknowledgement before resending a message.
+    pub resend_after: Duration,
+    /// Times a message is sent before giving up on it.
+    pub max_attempts: u32,
+}
+
+impl Default for ReliableConfig {
+    fn default() -> Self {
+        Self {
+            only: DEFAULT_RELIABLE_MESSAGES
+                .iter()
+                .map(|kind| kind.to_string())
+                .collect(),
+            resend_after: RELIABLE_RESEND_INTERVAL,
+            max_attempts: RELIABLE_MAX_ATTEMPTS,
+        }
+    }
+}
+
+impl ReliableConfig {
+    /// Reads the configuration from [`RELIABLE_DELIVERY_ENV_VAR`].
+    ///
+    /// Returns the default configuration if the variable is not set or is invalid.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(RELIABLE_DELIVERY_ENV_VAR) else {
+            return Self::default();
+        };
+        Self::parse(&value).unwrap_or_else(|e| {
+            eprintln!("[Reliable] Ignoring {}: {}", RELIABLE_DELIVERY_ENV_VAR, e);
+            Self::default()
+        })
+    }
+
+    /// Parses a configuration as described in [`RELIABLE_DELIVERY_ENV_VAR`].
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut config = Self::default();
+        if value.trim() == "off" {
+            config.only.clear();
+            return Ok(config);
+        }
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, got '{}'", pair))?;
+            let invalid = || format!("Invalid value for {}: '{}'", key, value);
+            match key.trim() {
+                "only" => {
+                    config.only = value
+                        .split('|')
+                        .map(|kind| kind.trim().to_string())
+                        .filter(|kind| !kind.is_empty())
+                        .collect()
+                }
+                "resend" => {
+                    config.resend_after = value
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|millis| *millis > 0)
+                        .map(Duration::from_millis)
+                        .ok_or_else(invalid)?
+                }
+                "attempts" => {
+                    config.max_attempts = value
+                        .trim()
+                        .parse()
+                        .ok()
+                        .filter(|attempts| *attempts > 0)
+                        .ok_or_else(invalid)?
+                }
+                other => return Err(format!("Unknown setting '{}'", other)),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Returns whether any message is sent with acknowledged delivery.
+    pub fn is_active(&self) -> bool {
+        !self.only.is_empty()
+    }
+
+    /// Returns whether `message` is sent with acknowledged delivery.
+    pub fn covers(&self, message: &NetworkMessage) -> bool {
+        self.is_active() && self.only.contains(&message_kind(message))
+    }
+}
+
+/// A message sent with acknowledged delivery and not acknowledged yet.
+#[derive(Debug, Clone)]
+struct Unacknowledged {
+    /// The message, without its envelope.
+    message: NetworkMessage,
+    /// When the message was last sent.
+    sent_at: Instant,
+    /// Times the message was sent.
+    attempts: u32,
+}
+
+/// The messages of a connection waiting for an acknowledgement, shared by its
+/// [`TCPSender`](crate::network::tcp_sender::TCPSender) and its
+/// [`Communicator`](crate::network::communicator::Communicator).
+///
+/// When the connection closes, its owner takes the messages left to send them again over
+/// the next connection.
+#[derive(Debug, Clone, Default)]
+pub struct UnacknowledgedMessages {
+    /// The ID of the last message tracked, and the messages by ID.
+    inner: Arc<Mutex<(u64, BTreeMap<u64, Unacknowledged>)>>,
+}
+
+impl UnacknowledgedMessages {
+    /// Tracks a message just sent for the first time.
+    ///
+    /// ## Returns
+    /// The ID of the message.
+    pub fn track(&self, message: NetworkMessage, now: Instant) -> u64 {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.0 += 1;
+        let id = inner.0;
+        inner.1.insert(
+            id,
+            Unacknowledged {
+                message,
+                sent_at: now,
+                attempts: 1,
+            },
+        );
+        id
+    }
+
+    /// Stops tracking the message `id`, once acknowledged.
+    pub fn acknowledge(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.1.remove(&id);
+    }
+
+    /// Returns the messages to resend: the ones not acknowledged within `resend_after`.
+    ///
+    /// The messages already sent `max_attempts` times are given up on instead.
+    ///
+    /// ## Returns
+    /// The ID and message of each message to resend, and the messages given up on.
+    pub fn due(
+        &self,
+        now: Instant,
+        resend_after: Duration,
+        max_attempts: u32,
+    ) -> (Vec<(u64, NetworkMessage)>, Vec<NetworkMessage>) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut resend = Vec::new();
+        let mut given_up = Vec::new();
+        inner.1.retain(|id, pending| {
+            if now.saturating_duration_since(pending.sent_at) < resend_after {
+                return true;
+            }
+            if pending.attempts >= max_attempts {
+                given_up.push(pending.message.clone());
+                return false;
+            }
+            pending.attempts += 1;
+            pending.sent_at = now;
+            resend.push((*id, pending.message.clone()));
+            true
+        });
+        (resend, given_up)
+    }
+
+    /// Stops tracking every message.
+    ///
+    /// ## Returns
+    /// The messages not acknowledged yet, in the order they were sent.
+    pub fn take(&self) -> Vec<NetworkMessage> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        std::mem::take(&mut inner.1)
+            .into_values()
+            .map(|pending| pending.message)
+            .collect()
+    }
+}
+
+/// The IDs of the last [`RELIABLE_SEEN_IDS`] reliable messages received on a connection, used
+/// to drop the copies of a message resent because its acknowledgement was lost.
+#[derive(Debug, Default)]
+pub struct SeenMessages {
+    /// The IDs received.
+    ids: HashSet<u64>,
+    /// The IDs received, oldest first.
+    order: VecDeque<u64>,
+}
+
+impl SeenMessages {
+    /// Records the message `id`.
+    ///
+    /// ## Returns
+    /// Whether it is the first time the message is received.
+    pub fn insert(&mut self, id: u64) -> bool {
+        if !self.ids.insert(id) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > RELIABLE_SEEN_IDS
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.ids.remove(&oldest);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::shared_messages::{KeepAlive, ReliableMessage, RetryLater};
+
+    fn retry_later() -> NetworkMessage {
+        NetworkMessage::RetryLater(RetryLater {
+            origin_addr: "127.0.0.1:8080".parse().unwrap(),
+            retry_after_millis: 0,
+        })
+    }
+
+    #[test]
+    fn parses_the_configuration() {
+        let config = ReliableConfig::parse("only=RetryLater|Ping, resend=500,attempts=3").unwrap();
+        assert_eq!(
+            config,
+            ReliableConfig {
+                only: vec!["RetryLater".to_string(), "Ping".to_string()],
+                resend_after: Duration::from_millis(500),
+                max_attempts: 3,
+            }
+        );
+        assert!(config.covers(&retry_later()));
+        assert!(!config.covers(&NetworkMessage::KeepAlive(KeepAlive {})));
+        assert!(!ReliableConfig::parse("off").unwrap().covers(&retry_later()));
+        assert!(ReliableConfig::parse("resend=0").is_err());
+        assert!(ReliableConfig::parse("retries=3").is_err());
+    }
+
+    #[test]
+    fn resends_until_acknowledged_or_given_up() {
+        let now = Instant::now();
+        let resend_after = Duration::from_secs(2);
+        let pending = UnacknowledgedMessages::default();
+        let first = pending.track(retry_later(), now);
+        let second = pending.track(retry_later(), now);
+        assert!(pending.due(now, resend_after, 2).0.is_empty());
+
+        pending.acknowledge(first);
+        let (resend, given_up) = pending.due(now + resend_after, resend_after, 2);
+        assert_eq!(
+            resend.iter().map(|(id, _)| *id).collect::<Vec<_>>(),
+            [second]
+        );
+        assert!(given_up.is_empty());
+
+        let (resend, given_up) = pending.due(now + resend_after * 2, resend_after, 2);
+        assert!(resend.is_empty());
+        assert_eq!(given_up.len(), 1);
+        assert!(pending.take().is_empty());
+    }
+
+    #[test]
+    fn hands_over_the_messages_left_in_order() {
+        let pending = UnacknowledgedMessages::default();
+        let shared = pending.clone();
+        pending.track(retry_later(), Instant::now());
+        pending.track(NetworkMessage::KeepAlive(KeepAlive {}), Instant::now());
+        let left = shared.take();
+        assert!(matches!(left[0], NetworkMessage::RetryLater(_)));
+        assert!(matches!(left[1], NetworkMessage::KeepAlive(_)));
+    }
+
+    #[test]
+    fn drops_the_copies_of_a_message() {
+        let mut seen = SeenMessages::default();
+        assert!(seen.insert(1));
+        assert!(!seen.insert(1));
+        for id in 2..=(RELIABLE_SEEN_IDS as u64 + 1) {
+            assert!(seen.insert(id));
+        }
+        // El ID más viejo se olvida para acotar la memoria
+        assert!(seen.insert(1));
+        let wrapped = NetworkMessage::Reliable(ReliableMessage {
+            id: 1,
+            message: Box::new(retry_later()),
+        });
+        assert_eq!(message_kind(&wrapped), "RetryLater");
+    }
+}
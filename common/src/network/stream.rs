@@ -0,0 +1,170 @@
+use crate::network::memory::{self, MemoryListener, MemoryStream};
+use crate::network::peer_types::PeerIdentity;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::OnceLock;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpSocket, TcpStream};
+
+/// Local address the outgoing TCP connections of this process are opened from, if set.
+static LOCAL_ADDRESS: OnceLock<SocketAddr> = OnceLock::new();
+
+/// Opens every outgoing TCP connection of this process from `addr`, to choose the interface
+/// (and, if the port is not 0, the port) used to reach the servers.
+///
+/// Only the first call has effect.
+pub fn bind_outgoing(addr: SocketAddr) {
+    if LOCAL_ADDRESS.set(addr).is_err() {
+        eprintln!("Outgoing connections are already bound, ignoring {}", addr);
+    }
+}
+
+/// A connection with a peer, over TCP or over the in-memory network used by simulations.
+#[derive(Debug)]
+pub enum PeerStream {
+    /// A TCP connection.
+    Tcp(TcpStream),
+    /// A connection on the [`memory`] network.
+    Memory(MemoryStream),
+}
+
+impl PeerStream {
+    /// Opens a connection to `addr` on behalf of `identity`.
+    ///
+    /// Uses the in-memory network if it is enabled on the current thread, TCP otherwise.
+    pub async fn connect(addr: SocketAddr, identity: &PeerIdentity) -> io::Result<Self> {
+        if memory::is_enabled() {
+            memory::connect(addr, &identity.user_id).map(PeerStream::Memory)
+        } else if let Some(local_addr) = LOCAL_ADDRESS.get() {
+            let socket = if local_addr.is_ipv4() {
+                TcpSocket::new_v4()?
+            } else {
+                TcpSocket::new_v6()?
+            };
+            // Permite reconectarse desde el mismo puerto sin esperar a que se libere
+            socket.set_reuseaddr(true)?;
+            socket.bind(*local_addr)?;
+            socket.connect(addr).await.map(PeerStream::Tcp)
+        } else {
+            TcpStream::connect(addr).await.map(PeerStream::Tcp)
+        }
+    }
+
+    /// Returns the address of this end of the connection.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            PeerStream::Tcp(stream) => stream.local_addr(),
+            PeerStream::Memory(stream) => Ok(stream.local_addr),
+        }
+    }
+
+    /// Returns the address of the other end of the connection.
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self {
+            PeerStream::Tcp(stream) => stream.peer_addr(),
+            PeerStream::Memory(stream) => Ok(stream.peer_addr),
+        }
+    }
+}
+
+impl From<TcpStream> for PeerStream {
+    fn from(stream: TcpStream) -> Self {
+        PeerStream::Tcp(stream)
+    }
+}
+
+impl AsyncRead for PeerStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            PeerStream::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            PeerStream::Memory(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for PeerStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            PeerStream::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            PeerStream::Memory(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            PeerStream::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            PeerStream::Memory(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            PeerStream::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            PeerStream::Memory(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// A listener for incoming [`PeerStream`]s, over TCP or over the in-memory network.
+pub enum Listener {
+    /// A TCP listener.
+    Tcp(TcpListener),
+    /// A listener on the [`memory`] network.
+    Memory(MemoryListener),
+}
+
+impl Listener {
+    /// Binds a listener to `addr`.
+    ///
+    /// Uses the in-memory network if it is enabled on the current thread, TCP otherwise.
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        if memory::is_enabled() {
+            memory::bind(addr).map(Listener::Memory)
+        } else {
+            TcpListener::bind(addr).await.map(Listener::Tcp)
+        }
+    }
+
+    /// Binds a listener to `addr`, as [`Listener::bind`], keeping up to `backlog` TCP
+    /// connections waiting to be accepted.
+    pub async fn bind_with_backlog(addr: SocketAddr, backlog: u32) -> io::Result<Self> {
+        if memory::is_enabled() {
+            return memory::bind(addr).map(Listener::Memory);
+        }
+        let socket = if addr.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        // Como TcpListener::bind, permite reiniciar el servidor sin esperar a que se libere
+        #[cfg(unix)]
+        socket.set_reuseaddr(true)?;
+        socket.bind(addr)?;
+        socket.listen(backlog).map(Listener::Tcp)
+    }
+
+    /// Waits for the next connection, returning it along with the address of the peer.
+    pub async fn accept(&mut self) -> io::Result<(PeerStream, SocketAddr)> {
+        match self {
+            Listener::Tcp(listener) => {
+                let (stream, remote_addr) = listener.accept().await?;
+                Ok((PeerStream::Tcp(stream), remote_addr))
+            }
+            Listener::Memory(listener) => {
+                let stream = listener.accept().await;
+                let remote_addr = stream.peer_addr;
+                Ok((PeerStream::Memory(stream), remote_addr))
+            }
+        }
+    }
+}
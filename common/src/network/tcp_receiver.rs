@@ -1,24 +1,54 @@
 use actix::dev::ToEnvelope;
 use actix::prelude::*;
 // Update the path below if 'common' is a sibling module or crate; adjust as needed:
+use crate::constants::KEEPALIVE_TIMEOUT;
 use crate::messages::shared_messages::Shutdown;
-use crate::messages::shared_messages::{ConnectionClosed, NetworkMessage};
+use crate::messages::shared_messages::{
+    ConnectionClosed, MessageAck, NetworkMessage, ReceivedFrom,
+};
+use crate::network::codec::{Frame, WireSettings, read_frame};
+use crate::network::handshake::{negotiate_version, supports_ack, supports_keepalive};
+use crate::network::reliable::SeenMessages;
+use crate::network::stream::PeerStream;
+use crate::network::tcp_sender::{
+    AckReceived, SetReliableDelivery, SetWireSettings, StartKeepAlive, TCPSender,
+};
 use std::net::SocketAddr;
-use tokio::io::{AsyncBufReadExt, BufReader, ReadHalf};
-use tokio::net::TcpStream;
+use tokio::io::{BufReader, ReadHalf};
+use tracing::{Instrument, info_span};
 
-/// The `TCPReceiver` actor reads incoming lines from a TCP stream,
+/// The `TCPReceiver` actor reads incoming frames from a TCP stream,
 /// deserializes them as [`NetworkMessage`]s, and forwards them to the destination actor.
 ///
+/// The peer's [`Handshake`](crate::messages::shared_messages::Handshake) is consumed here:
+/// the sender of the connection switches to the best wire format and compression both peers support or, if the
+/// protocol version is not supported, an `IncompatibleVersion` is sent back to the peer and to
+/// the destination actor, and the connection stops being read.
+///
+/// On user connections, if the peer announces that it sends keepalives, both ends start
+/// sending them and the connection is closed when nothing arrives within [`KEEPALIVE_TIMEOUT`].
+///
+/// Every [`ReliableMessage`](crate::messages::shared_messages::ReliableMessage) is acknowledged
+/// and forwarded unwrapped, once: copies resent because an ack was lost are dropped.
+///
+/// If the receiver is [tagged](TCPReceiver::tagged), the messages of the peer are forwarded as
+/// [`ReceivedFrom`], with the address of the connection.
+///
 /// ## Type Parameters
 /// - `A`: The actor type that will receive the deserialized messages.
 pub struct TCPReceiver<A: Actor + Handler<NetworkMessage>> {
     /// The remote peer's socket address.
     remote_addr: SocketAddr,
     /// The buffered reader for the TCP stream.
-    reader: Option<BufReader<ReadHalf<TcpStream>>>,
+    reader: Option<BufReader<ReadHalf<PeerStream>>>,
     /// The Actix address of the destination actor.
     destination: Addr<A>,
+    /// The sender of the same connection, used to answer the handshake.
+    reply_to: Addr<TCPSender>,
+    /// Whether the connection is checked for liveness with keepalives.
+    keepalive: bool,
+    /// Where the messages of the peer go tagged with the address of the connection, if any.
+    tagged: Option<Recipient<ReceivedFrom>>,
 }
 
 impl<A> TCPReceiver<A>
@@ -31,12 +61,55 @@ where
     /// * `reader` - The read half of the TCP stream.
     /// * `remote_addr` - The address of the remote peer.
     /// * `destination` - The Actix address of the actor to forward messages to.
-    pub fn new(reader: ReadHalf<TcpStream>, remote_addr: SocketAddr, destination: Addr<A>) -> Self {
+    /// * `reply_to` - The sender of the same connection.
+    /// * `keepalive` - Whether to check the connection for liveness with keepalives.
+    pub fn new(
+        reader: ReadHalf<PeerStream>,
+        remote_addr: SocketAddr,
+        destination: Addr<A>,
+        reply_to: Addr<TCPSender>,
+        keepalive: bool,
+    ) -> Self {
         Self {
             remote_addr,
             reader: Some(BufReader::new(reader)),
             destination,
+            reply_to,
+            keepalive,
+            tagged: None,
+        }
+    }
+
+    /// Forwards the messages of the peer to `recipient` as [`ReceivedFrom`] instead of
+    /// forwarding them to the destination actor as they are.
+    pub fn tagged(mut self, recipient: Recipient<ReceivedFrom>) -> Self {
+        self.tagged = Some(recipient);
+        self
+    }
+}
+
+/// Forwards `message`, received from `remote_addr`, to `tagged` if it is set, or else to
+/// `destination`, waiting until it is handled.
+async fn deliver<A>(
+    destination: &Addr<A>,
+    tagged: &Option<Recipient<ReceivedFrom>>,
+    remote_addr: SocketAddr,
+    message: NetworkMessage,
+) -> Result<(), MailboxError>
+where
+    A: Actor + Handler<NetworkMessage>,
+    A::Context: ToEnvelope<A, NetworkMessage>,
+{
+    match tagged {
+        Some(tagged) => {
+            tagged
+                .send(ReceivedFrom {
+                    remote_addr,
+                    message,
+                })
+                .await
         }
+        None => destination.send(message).await,
     }
 }
 
@@ -51,21 +124,105 @@ where
         let addr = self.destination.clone();
         let reader = self.reader.take().unwrap();
         let remote_addr = self.remote_addr;
+        let reply_to = self.reply_to.clone();
+        let keepalive = self.keepalive;
+        let tagged = self.tagged.clone();
 
         ctx.spawn(
             async move {
-                let mut lines = reader.lines();
-                while let Ok(Some(line)) = lines.next_line().await {
-                    match serde_json::from_str::<NetworkMessage>(&line) {
-                        Ok(msg) => {
-                            if let Err(e) = addr.send(msg).await {
-                                eprintln!("Failed to send NetworkMessage: {}", e);
+                let mut reader = reader;
+                // Sólo se espera un tiempo acotado una vez que ambos extremos envían keepalives
+                let mut read_timeout = None;
+                let mut seen = SeenMessages::default();
+                loop {
+                    let read = match read_timeout {
+                        Some(timeout) => {
+                            match tokio::time::timeout(timeout, read_frame(&mut reader)).await {
+                                Ok(read) => read,
+                                Err(_) => {
+                                    eprintln!(
+                                        "[TCPReceiver] Nothing received from {} in {:?}, closing connection",
+                                        remote_addr, timeout
+                                    );
+                                    break;
+                                }
                             }
                         }
+                        None => read_frame(&mut reader).await,
+                    };
+                    let frame = match read {
+                        Ok(Some(frame)) => frame,
+                        Ok(None) => break,
                         Err(e) => {
-                            panic!(
-                                "This message cannot be deserialized: {}. Error: {}",
-                                line, e
+                            eprintln!("[TCPReceiver] Closing connection with {}: {}", remote_addr, e);
+                            break;
+                        }
+                    };
+                    match frame {
+                        Frame::Message(msg) => match *msg {
+                        NetworkMessage::Handshake(handshake) => {
+                            match negotiate_version(remote_addr, &handshake) {
+                                Ok(version) => {
+                                    let settings = WireSettings::negotiate(&handshake.capabilities);
+                                    println!(
+                                        "[TCPReceiver] Using protocol v{} with {}: {:?}",
+                                        version, remote_addr, settings
+                                    );
+                                    reply_to.do_send(SetWireSettings(settings));
+                                    reply_to.do_send(SetReliableDelivery(supports_ack(&handshake)));
+                                    if keepalive && supports_keepalive(&handshake) {
+                                        reply_to.do_send(StartKeepAlive);
+                                        read_timeout = Some(KEEPALIVE_TIMEOUT);
+                                    }
+                                }
+                                Err(incompatible) => {
+                                    eprintln!(
+                                        "[TCPReceiver] Rejecting {}: protocol v{} not in supported range {:?}",
+                                        remote_addr, incompatible.received, incompatible.supported
+                                    );
+                                    reply_to.do_send(NetworkMessage::IncompatibleVersion(
+                                        incompatible.clone(),
+                                    ));
+                                    if let Err(e) = addr
+                                        .send(NetworkMessage::IncompatibleVersion(incompatible))
+                                        .await
+                                    {
+                                        eprintln!("Failed to send IncompatibleVersion: {}", e);
+                                    }
+                                    break;
+                                }
+                            }
+                        }
+                        NetworkMessage::KeepAlive(_) => {}
+                        NetworkMessage::Reliable(reliable) => {
+                            reply_to.do_send(NetworkMessage::Ack(MessageAck { id: reliable.id }));
+                            if !seen.insert(reliable.id) {
+                                continue;
+                            }
+                            let span = receive_span(&reliable.message, remote_addr);
+                            if let Err(e) = deliver(&addr, &tagged, remote_addr, *reliable.message)
+                                .instrument(span)
+                                .await
+                            {
+                                eprintln!("Failed to send NetworkMessage: {}", e);
+                            }
+                        }
+                        NetworkMessage::Ack(ack) => reply_to.do_send(AckReceived(ack.id)),
+                        msg => {
+                            let span = receive_span(&msg, remote_addr);
+                            if let Err(e) = deliver(&addr, &tagged, remote_addr, msg)
+                                .instrument(span)
+                                .await
+                            {
+                                eprintln!("Failed to send NetworkMessage: {}", e);
+                            }
+                        }
+                        },
+                        Frame::Undecodable(e) => {
+                            // Puede ser un mensaje de una versión más nueva del protocolo
+                            eprintln!(
+                                "[TCPReceiver] Discarding message from {} that cannot be decoded: {}",
+                                remote_addr, e
                             );
                         }
                     }
@@ -84,6 +241,12 @@ where
     }
 }
 
+/// Returns the span of a message received from `peer`, from the moment it is read until the
+/// destination actor finishes handling it.
+fn receive_span(msg: &NetworkMessage, peer: SocketAddr) -> tracing::Span {
+    info_span!("receive", msg = msg.kind(), order_id = msg.order_id(), peer = %peer)
+}
+
 impl<A> Handler<Shutdown> for TCPReceiver<A>
 where
     A: Actor + Handler<NetworkMessage> + 'static,
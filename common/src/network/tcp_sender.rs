@@ -1,27 +1,233 @@
-use crate::messages::shared_messages::NetworkMessage;
+use crate::constants::{FAULT_REORDER_WINDOW, KEEPALIVE_INTERVAL};
 use crate::messages::shared_messages::Shutdown;
+use crate::messages::shared_messages::{
+    ConnectionClosed, KeepAlive, NetworkMessage, ReliableMessage,
+};
+use crate::network::codec::{WireSettings, encode_frames};
+use crate::network::faults::{FaultConfig, FaultInjector, message_kind};
+use crate::network::outbound::{Admission, OutboundConfig, OutboundMetrics, OutboundQueue};
+use crate::network::reliable::{ReliableConfig, UnacknowledgedMessages};
+use crate::network::stream::PeerStream;
 use actix::prelude::*;
-use std::collections::VecDeque;
+use std::net::SocketAddr;
 use tokio::io::{AsyncWriteExt, BufWriter, WriteHalf};
-use tokio::net::TcpStream;
+use tokio::time::Instant;
 
 /// The `TCPSender` actor is responsible for serializing and sending [`NetworkMessage`]s
 /// over a TCP stream to a remote peer. It maintains a queue to ensure messages are sent in order.
+///
+/// The queue is bounded by an [`OutboundConfig`]: when a slow peer lets it fill up, the
+/// oldest message is dropped, the connection is closed or the message waits for room,
+/// depending on the [`OverflowPolicy`](crate::network::outbound::OverflowPolicy).
+///
+/// Messages are written as JSON lines until the handshake selects other [`WireSettings`].
+/// Large messages are written as a sequence of chunks, flushing after each one.
+///
+/// If a [`FaultConfig`] is set, outgoing messages may be dropped, delayed, duplicated or
+/// reordered before being queued.
+///
+/// The messages covered by the [`ReliableConfig`] are wrapped in a [`ReliableMessage`] and
+/// resent until the peer acknowledges them, if the peer's handshake shows it can. Until the
+/// handshake arrives, those messages and the ones sent after them are held back.
 pub struct TCPSender {
     /// The buffered writer for the TCP stream.
-    pub writer: Option<BufWriter<WriteHalf<TcpStream>>>,
+    pub writer: Option<BufWriter<WriteHalf<PeerStream>>>,
     /// The queue of messages to be sent.
-    pub queue: VecDeque<NetworkMessage>,
+    pub queue: OutboundQueue,
+    /// Where to report the connection as closed if the queue overflows.
+    pub on_disconnect: Recipient<NetworkMessage>,
+    /// The remote peer's socket address.
+    pub peer_address: SocketAddr,
+    /// The format and compression used to serialize outgoing messages.
+    pub settings: WireSettings,
+    /// The faults injected on outgoing messages, if any.
+    pub faults: Option<FaultInjector>,
+    /// A message held back to be sent after the next one.
+    pub held: Option<NetworkMessage>,
+    /// The messages sent with acknowledged delivery.
+    pub reliable: ReliableConfig,
+    /// The messages sent with acknowledged delivery and not acknowledged yet.
+    pub unacknowledged: UnacknowledgedMessages,
+    /// Whether the peer acknowledges reliable messages, or `None` until its handshake arrives.
+    pub peer_acks: Option<bool>,
+    /// The messages held back until the peer's handshake arrives, with the ID they are
+    /// tracked with if covered.
+    pub awaiting_handshake: Vec<(Option<u64>, NetworkMessage)>,
 }
 
 impl TCPSender {
     /// Creates a new `TCPSender` with the given write half of a TCP stream.
-    pub fn new(write_half: WriteHalf<TcpStream>) -> Self {
+    ///
+    /// ## Arguments
+    /// * `write_half` - The write half of the TCP stream.
+    /// * `config` - The size of the outbound queue and its overflow policy.
+    /// * `peer_address` - The address of the remote peer.
+    /// * `on_disconnect` - The actor told about the connection closing if the queue overflows.
+    /// * `reliable` - The messages sent with acknowledged delivery.
+    /// * `unacknowledged` - Where to track the messages not acknowledged yet.
+    pub fn new(
+        write_half: WriteHalf<PeerStream>,
+        config: OutboundConfig,
+        peer_address: SocketAddr,
+        on_disconnect: Recipient<NetworkMessage>,
+        reliable: ReliableConfig,
+        unacknowledged: UnacknowledgedMessages,
+    ) -> Self {
+        // Sin mensajes confiables no hace falta esperar el handshake del peer
+        let peer_acks = (!reliable.is_active()).then_some(false);
         Self {
             writer: Some(BufWriter::new(write_half)),
-            queue: VecDeque::new(),
+            queue: OutboundQueue::new(config),
+            on_disconnect,
+            peer_address,
+            settings: WireSettings::default(),
+            faults: None,
+            held: None,
+            reliable,
+            unacknowledged,
+            peer_acks,
+            awaiting_handshake: Vec::new(),
         }
     }
+
+    /// Returns the counters of the outbound queue.
+    pub fn metrics(&self) -> OutboundMetrics {
+        self.queue.metrics()
+    }
+
+    /// Adds a message to the queue, starting to process it if nothing is being written.
+    fn enqueue(&mut self, msg: NetworkMessage, ctx: &mut Context<Self>) {
+        match self.queue.push(msg, Instant::now()) {
+            Admission::Queued => {}
+            Admission::DroppedOldest => {
+                eprintln!(
+                    "[TCPSender] Outbound queue to {} is full, dropped the oldest message",
+                    self.peer_address
+                );
+            }
+            Admission::Blocked(deadline) => {
+                let wait = deadline.saturating_duration_since(Instant::now());
+                ctx.run_later(wait, |act, _ctx| {
+                    let expired = act.queue.expire(Instant::now());
+                    if expired > 0 {
+                        eprintln!(
+                            "[TCPSender] Dropped {} messages to {} that waited too long for room",
+                            expired, act.peer_address
+                        );
+                    }
+                });
+            }
+            Admission::Disconnect => {
+                eprintln!(
+                    "[TCPSender] Outbound queue to {} is full, closing the connection",
+                    self.peer_address
+                );
+                self.on_disconnect
+                    .do_send(NetworkMessage::ConnectionClosed(ConnectionClosed {
+                        remote_addr: self.peer_address,
+                    }));
+                self.writer = None;
+                self.queue.clear();
+                ctx.stop();
+                return;
+            }
+        }
+        if self.writer.is_some() {
+            ctx.notify(ProcessQueue);
+        }
+    }
+
+    /// Sends a message, wrapping it for acknowledged delivery if it is covered.
+    fn deliver(&mut self, msg: NetworkMessage, ctx: &mut Context<Self>) {
+        match self.peer_acks {
+            // Se retiene también lo posterior para no alterar el orden de los mensajes
+            None if !self.awaiting_handshake.is_empty() || self.reliable.covers(&msg) => {
+                // Se registra ya, así no se pierde si la conexión cae antes del handshake
+                let id = self
+                    .reliable
+                    .covers(&msg)
+                    .then(|| self.unacknowledged.track(msg.clone(), Instant::now()));
+                self.awaiting_handshake.push((id, msg));
+            }
+            Some(true) if self.reliable.covers(&msg) => {
+                let id = self.unacknowledged.track(msg.clone(), Instant::now());
+                self.inject_faults(reliable_envelope(id, msg), ctx);
+            }
+            _ => self.inject_faults(msg, ctx),
+        }
+    }
+
+    /// Injects the configured faults on a message, then queues it.
+    fn inject_faults(&mut self, msg: NetworkMessage, ctx: &mut Context<Self>) {
+        let Some(faults) = self.faults.as_mut() else {
+            self.enqueue(msg, ctx);
+            return;
+        };
+
+        let decision = faults.decide(&msg);
+        if decision.drop {
+            eprintln!("[TCPSender] Fault injected: dropped {}", message_kind(&msg));
+            return;
+        }
+        let copies = if decision.duplicate { 2 } else { 1 };
+
+        if decision.reorder && self.held.is_none() {
+            // Se envía después del próximo mensaje, o al cerrarse la ventana
+            self.held = Some(msg);
+            ctx.run_later(FAULT_REORDER_WINDOW, |act, ctx| act.release_held(ctx));
+            return;
+        }
+
+        if decision.delay.is_zero() {
+            for _ in 0..copies {
+                self.enqueue(msg.clone(), ctx);
+            }
+            self.release_held(ctx);
+        } else {
+            ctx.run_later(decision.delay, move |act, ctx| {
+                for _ in 0..copies {
+                    act.enqueue(msg.clone(), ctx);
+                }
+                act.release_held(ctx);
+            });
+        }
+    }
+
+    /// Resends the reliable messages not acknowledged in time, giving up on the ones sent
+    /// too many times.
+    fn resend_unacknowledged(&mut self, ctx: &mut Context<Self>) {
+        let (resend, given_up) = self.unacknowledged.due(
+            Instant::now(),
+            self.reliable.resend_after,
+            self.reliable.max_attempts,
+        );
+        for msg in given_up {
+            eprintln!(
+                "[TCPSender] {} was not acknowledged by {} after {} attempts, giving up",
+                message_kind(&msg),
+                self.peer_address,
+                self.reliable.max_attempts
+            );
+        }
+        for (id, msg) in resend {
+            self.inject_faults(reliable_envelope(id, msg), ctx);
+        }
+    }
+
+    /// Queues the message held back for reordering, if any.
+    fn release_held(&mut self, ctx: &mut Context<Self>) {
+        if let Some(held) = self.held.take() {
+            self.enqueue(held, ctx);
+        }
+    }
+}
+
+/// Wraps a message for acknowledged delivery.
+fn reliable_envelope(id: u64, msg: NetworkMessage) -> NetworkMessage {
+    NetworkMessage::Reliable(ReliableMessage {
+        id,
+        message: Box::new(msg),
+    })
 }
 
 /// Message sent to indicate an error occurred while sending data over the socket.
@@ -33,6 +239,38 @@ impl Actor for TCPSender {
     type Context = Context<Self>;
 }
 
+/// Message sent once the handshake picks the wire settings for outgoing messages.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetWireSettings(pub WireSettings);
+
+/// Message sent to change the faults injected on outgoing messages.
+///
+/// Faults are disabled if the configuration does not inject any.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetFaults(pub FaultConfig);
+
+/// Message sent once the handshake shows that both ends of a user connection send keepalives.
+///
+/// From then on a [`KeepAlive`] is sent every [`KEEPALIVE_INTERVAL`] while nothing else is queued.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct StartKeepAlive;
+
+/// Message sent once the peer's handshake shows whether it acknowledges reliable messages.
+///
+/// The messages held back until then are sent, and if the peer acknowledges them, the ones
+/// not acknowledged are resent every [`ReliableConfig::resend_after`].
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct SetReliableDelivery(pub bool);
+
+/// Message sent when the peer acknowledges the reliable message with this ID.
+#[derive(Message)]
+#[rtype(result = "()")]
+pub struct AckReceived(pub u64);
+
 struct ProcessQueue;
 
 impl Message for ProcessQueue {
@@ -43,10 +281,12 @@ impl Handler<NetworkMessage> for TCPSender {
     type Result = ();
 
     fn handle(&mut self, msg: NetworkMessage, ctx: &mut Self::Context) {
-        self.queue.push_back(msg);
-        if self.queue.len() == 1 {
-            ctx.notify(ProcessQueue);
+        // El handshake y los acks nunca esperan al handshake del peer
+        if matches!(msg, NetworkMessage::Handshake(_) | NetworkMessage::Ack(_)) {
+            self.inject_faults(msg, ctx);
+            return;
         }
+        self.deliver(msg, ctx);
     }
 }
 
@@ -54,25 +294,32 @@ impl Handler<ProcessQueue> for TCPSender {
     type Result = ResponseActFuture<Self, ()>;
 
     fn handle(&mut self, _msg: ProcessQueue, _ctx: &mut Self::Context) -> Self::Result {
-        if let (Some(mut writer), Some(msg)) = (self.writer.take(), self.queue.front().cloned()) {
+        if self.writer.is_none() || self.queue.is_empty() {
+            return Box::pin(async {}.into_actor(self));
+        }
+        // El mensaje en escritura sale de la cola, así nunca se descarta a medio enviar
+        if let (Some(mut writer), Some(msg)) =
+            (self.writer.take(), self.queue.pop_front(Instant::now()))
+        {
+            let settings = self.settings;
             let fut = async move {
-                let serialized = match serde_json::to_string(&msg) {
-                    Ok(s) => s,
+                let frames = match encode_frames(settings, &msg) {
+                    Ok(frames) => frames,
                     Err(e) => {
                         // No panic, se puede loguear o manejar el error.
-                        let err = format!("Error serializing message: {:?}", e);
-                        return Err(err);
+                        return Err(e);
                     }
                 };
-                let to_send = format!("{}\n", serialized);
 
-                if let Err(e) = writer.write_all(to_send.as_bytes()).await {
-                    let err = format!("Error writing to socket: {:?}", e);
-                    return Err(err);
-                }
-                if let Err(e) = writer.flush().await {
-                    let err = format!("Error flushing socket: {:?}", e);
-                    return Err(err);
+                for frame in frames {
+                    if let Err(e) = writer.write_all(&frame).await {
+                        let err = format!("Error writing to socket: {:?}", e);
+                        return Err(err);
+                    }
+                    if let Err(e) = writer.flush().await {
+                        let err = format!("Error flushing socket: {:?}", e);
+                        return Err(err);
+                    }
                 }
 
                 Ok(writer)
@@ -82,7 +329,6 @@ impl Handler<ProcessQueue> for TCPSender {
                 match res {
                     Ok(writer) => {
                         act.writer = Some(writer);
-                        act.queue.pop_front();
                         if !act.queue.is_empty() {
                             ctx.notify(ProcessQueue);
                         }
@@ -102,12 +348,81 @@ impl Handler<ProcessQueue> for TCPSender {
     }
 }
 
+impl Handler<SetWireSettings> for TCPSender {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetWireSettings, _ctx: &mut Self::Context) {
+        self.settings = msg.0;
+    }
+}
+
+impl Handler<SetFaults> for TCPSender {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetFaults, ctx: &mut Self::Context) {
+        self.release_held(ctx);
+        self.faults = msg.0.is_active().then(|| FaultInjector::new(msg.0));
+    }
+}
+
+impl Handler<StartKeepAlive> for TCPSender {
+    type Result = ();
+
+    fn handle(&mut self, _msg: StartKeepAlive, ctx: &mut Self::Context) {
+        ctx.run_interval(KEEPALIVE_INTERVAL, |act, ctx| {
+            // Si hay mensajes en cola, esos ya le muestran al peer que seguimos vivos
+            if act.queue.is_empty() && act.writer.is_some() {
+                act.enqueue(NetworkMessage::KeepAlive(KeepAlive {}), ctx);
+            }
+        });
+    }
+}
+
+impl Handler<SetReliableDelivery> for TCPSender {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetReliableDelivery, ctx: &mut Self::Context) {
+        if self.peer_acks.is_some() {
+            return;
+        }
+        let peer_acks = msg.0 && self.reliable.is_active();
+        self.peer_acks = Some(peer_acks);
+        if peer_acks {
+            ctx.run_interval(self.reliable.resend_after, |act, ctx| {
+                if act.writer.is_some() {
+                    act.resend_unacknowledged(ctx);
+                }
+            });
+        }
+        for (id, msg) in std::mem::take(&mut self.awaiting_handshake) {
+            match id {
+                Some(id) if peer_acks => self.inject_faults(reliable_envelope(id, msg), ctx),
+                Some(id) => {
+                    self.unacknowledged.acknowledge(id);
+                    self.inject_faults(msg, ctx);
+                }
+                None => self.inject_faults(msg, ctx),
+            }
+        }
+    }
+}
+
+impl Handler<AckReceived> for TCPSender {
+    type Result = ();
+
+    fn handle(&mut self, msg: AckReceived, _ctx: &mut Self::Context) {
+        self.unacknowledged.acknowledge(msg.0);
+    }
+}
+
 impl Handler<Shutdown> for TCPSender {
     type Result = ();
 
     fn handle(&mut self, _msg: Shutdown, ctx: &mut Self::Context) {
         self.writer = None;
         self.queue.clear();
+        self.held = None;
+        self.awaiting_handshake.clear();
         ctx.stop();
     }
 }
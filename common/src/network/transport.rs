@@ -0,0 +1,143 @@
+use crate::messages::shared_messages::NetworkMessage;
+use crate::network::communicator::Communicator;
+use crate::network::faults::FaultConfig;
+#[cfg(feature = "grpc")]
+use crate::network::grpc::{self, GrpcTransport};
+use actix::prelude::*;
+use std::fmt::Debug;
+use std::net::SocketAddr;
+
+/// Environment variable used to select the transport between coordinators.
+pub const TRANSPORT_ENV_VAR: &str = "COORDINATOR_TRANSPORT";
+
+/// Transport used to exchange [`NetworkMessage`]s between coordinators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TransportKind {
+    /// Newline-delimited JSON over a raw TCP connection (the default).
+    #[default]
+    Tcp,
+    /// gRPC over HTTP/2. Requires the `grpc` feature.
+    Grpc,
+}
+
+impl TransportKind {
+    /// Reads the transport from [`TRANSPORT_ENV_VAR`] (`tcp` or `grpc`), defaulting to TCP.
+    ///
+    /// Falls back to TCP if gRPC is requested but the `grpc` feature is disabled.
+    pub fn from_env() -> Self {
+        match std::env::var(TRANSPORT_ENV_VAR) {
+            Ok(value) if value.eq_ignore_ascii_case("grpc") => {
+                if cfg!(feature = "grpc") {
+                    TransportKind::Grpc
+                } else {
+                    println!(
+                        "[Transport] gRPC transport requested but the `grpc` feature is disabled, using TCP"
+                    );
+                    TransportKind::Tcp
+                }
+            }
+            _ => TransportKind::Tcp,
+        }
+    }
+}
+
+/// A connection to another coordinator, independent of the underlying wire protocol.
+///
+/// ## Purpose
+/// Lets the `CoordinatorManager` talk to its peers without knowing whether the messages
+/// travel over the framed TCP protocol or over gRPC.
+pub trait PeerTransport: Debug + Send {
+    /// The address this node is known by on this connection.
+    fn local_address(&self) -> SocketAddr;
+
+    /// The address of the remote coordinator.
+    fn peer_address(&self) -> SocketAddr;
+
+    /// Queues a message for the remote coordinator.
+    ///
+    /// Returns an error if the connection can no longer be used.
+    fn send(&self, message: NetworkMessage) -> Result<(), String>;
+
+    /// Closes the connection.
+    fn shutdown(&mut self);
+
+    /// Changes the faults injected on the outgoing messages. Ignored by transports that do
+    /// not support fault injection.
+    fn set_faults(&self, _config: FaultConfig) {}
+}
+
+impl<A> PeerTransport for Communicator<A>
+where
+    A: Actor<Context = Context<A>> + Handler<NetworkMessage> + Debug,
+{
+    fn local_address(&self) -> SocketAddr {
+        self.local_address
+    }
+
+    fn peer_address(&self) -> SocketAddr {
+        self.peer_address
+    }
+
+    fn send(&self, message: NetworkMessage) -> Result<(), String> {
+        match &self.sender {
+            Some(sender) => sender
+                .try_send(message)
+                .map_err(|e| format!("Failed to send to {}: {:?}", self.peer_address, e)),
+            None => Err(format!(
+                "Sender not initialized in communicator for {}",
+                self.peer_address
+            )),
+        }
+    }
+
+    fn shutdown(&mut self) {
+        Communicator::shutdown(self);
+    }
+
+    fn set_faults(&self, config: FaultConfig) {
+        Communicator::set_faults(self, config);
+    }
+}
+
+/// Opens a transport to `peer_address` that does not need an established TCP stream.
+///
+/// Returns `None` for [`TransportKind::Tcp`]: those connections are opened with
+/// `connect_to_all` and received by the acceptor.
+pub fn open_transport(
+    kind: TransportKind,
+    local_address: SocketAddr,
+    peer_address: SocketAddr,
+) -> Option<Box<dyn PeerTransport>> {
+    match kind {
+        TransportKind::Tcp => None,
+        #[cfg(feature = "grpc")]
+        TransportKind::Grpc => Some(Box::new(GrpcTransport::new(local_address, peer_address))),
+        #[cfg(not(feature = "grpc"))]
+        TransportKind::Grpc => {
+            let _ = (local_address, peer_address);
+            None
+        }
+    }
+}
+
+/// Serves the endpoint that receives messages from other coordinators, forwarding them
+/// to `destination`.
+///
+/// For [`TransportKind::Tcp`] this returns immediately, since the acceptor already listens
+/// on `addr`.
+pub async fn serve_transport(
+    kind: TransportKind,
+    addr: SocketAddr,
+    destination: Recipient<NetworkMessage>,
+) -> Result<(), String> {
+    match kind {
+        TransportKind::Tcp => Ok(()),
+        #[cfg(feature = "grpc")]
+        TransportKind::Grpc => grpc::serve(addr, destination).await,
+        #[cfg(not(feature = "grpc"))]
+        TransportKind::Grpc => {
+            let _ = (addr, destination);
+            Err("gRPC transport requires the `grpc` feature".to_string())
+        }
+    }
+}
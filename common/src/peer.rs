@@ -0,0 +1,735 @@
+use crate::constants::{RETRY_LATER_BASE_DELAY, RETRY_LATER_MAX_ATTEMPTS, RETRY_LATER_MAX_DELAY};
+use crate::logger::Logger;
+use crate::messages::shared_messages::{DeregisterUser, NetworkMessage, RegisterUser, WhoIsLeader};
+use crate::network::communicator::Communicator;
+use crate::network::connections::{connect_one, connect_some};
+use crate::network::peer_types::PeerIdentity;
+use crate::network::reconnection::{Reconnecting, ReconnectingConnection, ReconnectionState};
+use crate::network::stream::PeerStream;
+use crate::types::dtos::UserDTO;
+use crate::types::tenant::TenantId;
+use crate::types::vehicle::VehicleType;
+use actix::fut::wrap_future;
+use actix::prelude::*;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Time to wait after connecting to a server before asking it for the leader.
+const WAIT_BEFORE_ASKING_FOR_LEADER: Duration = Duration::from_millis(100);
+
+/// Backoff of the questions for the leader while the server answers
+/// [`RetryLater`](crate::messages::shared_messages::RetryLater).
+///
+/// Each retry waits twice as long as the previous one, from [`RETRY_LATER_BASE_DELAY`] up to
+/// [`RETRY_LATER_MAX_DELAY`], or longer if the server asks for it. After
+/// [`RETRY_LATER_MAX_ATTEMPTS`] retries the user gives up on that server.
+#[derive(Debug, Default)]
+struct LeaderRetry {
+    /// Retries since the server last answered something else.
+    attempts: u32,
+}
+
+impl LeaderRetry {
+    /// Records a `RetryLater` from the server.
+    ///
+    /// ## Arguments
+    /// * `suggested` - The wait the server asked for.
+    ///
+    /// ## Returns
+    /// How long to wait before asking again, or `None` if the user gives up.
+    fn next_delay(&mut self, suggested: Duration) -> Option<Duration> {
+        if self.attempts >= RETRY_LATER_MAX_ATTEMPTS {
+            return None;
+        }
+        self.attempts += 1;
+        let exponent = (self.attempts - 1).min(31);
+        let backoff = RETRY_LATER_BASE_DELAY
+            .checked_mul(1 << exponent)
+            .map_or(RETRY_LATER_MAX_DELAY, |delay| {
+                delay.min(RETRY_LATER_MAX_DELAY)
+            });
+        Some(backoff.max(suggested))
+    }
+
+    /// Forgets the retries, once the server answers.
+    fn reset(&mut self) {
+        self.attempts = 0;
+    }
+}
+
+/// The connection of a user (a client, a restaurant or a delivery) to the leader of the
+/// servers, shared by the actors of the three kinds of users.
+///
+/// It keeps the connection to the server asked for the leader, and then to the leader, the
+/// reconnection to the servers when it is lost, and the messages the server did not
+/// acknowledge over a previous connection. The actor drives it through the [`Peer`] trait.
+#[derive(Debug)]
+pub struct PeerCore<A>
+where
+    A: Actor<Context = Context<A>> + Handler<NetworkMessage>,
+{
+    /// The identity announced to the servers when connecting.
+    identity: PeerIdentity,
+    /// Secret of the identity of the user, sent when registering.
+    secret: Option<String>,
+    /// Marketplace the user registers in, read from
+    /// [`TENANT_ENV_VAR`](crate::types::tenant::TENANT_ENV_VAR).
+    tenant_id: TenantId,
+    /// List of server socket addresses.
+    pub servers: Vec<SocketAddr>,
+    /// Communicator with the leader, or with the server asked for it.
+    pub communicator: Option<Communicator<A>>,
+    /// Connection made before the actor starts.
+    pending_stream: Option<PeerStream>,
+    /// Timer for waiting before asking for the leader after connecting.
+    waiting_timer: Option<SpawnHandle>,
+    /// Reconnection to the servers after the connection to the leader is lost.
+    reconnection: ReconnectingConnection,
+    /// Whether the user already registered and recovered its state.
+    pub already_connected: bool,
+    /// Whether the user registered over the current connection.
+    registered: bool,
+    /// Backoff of the questions for the leader answered with a `RetryLater`.
+    leader_retry: LeaderRetry,
+    /// Messages the server did not acknowledge before the connection closed, sent again
+    /// once registered over the next one.
+    unacknowledged: Vec<NetworkMessage>,
+    /// Logger of the actor.
+    logger: Logger,
+}
+
+impl<A> PeerCore<A>
+where
+    A: Actor<Context = Context<A>> + Handler<NetworkMessage>,
+{
+    /// Connects to the first server that answers.
+    ///
+    /// ## Arguments
+    /// * `servers` - List of server socket addresses, in order of preference.
+    /// * `identity` - The identity announced to the servers when connecting.
+    /// * `logger` - Logger of the actor.
+    ///
+    /// ## Returns
+    /// `None` if no server answered.
+    pub async fn connect(
+        servers: Vec<SocketAddr>,
+        identity: PeerIdentity,
+        logger: Logger,
+    ) -> Option<Self> {
+        let pending_stream = connect_some(servers.clone(), &identity).await;
+        if pending_stream.is_none() {
+            logger.error(format!(
+                "Failed to connect to any server from the list: {:?}",
+                servers
+            ));
+            return None;
+        }
+        let reconnection = ReconnectingConnection::new(servers.clone(), identity.clone());
+        Some(Self {
+            identity,
+            secret: None,
+            tenant_id: TenantId::from_env(),
+            servers,
+            communicator: None,
+            pending_stream,
+            waiting_timer: None,
+            reconnection,
+            already_connected: false,
+            registered: false,
+            leader_retry: LeaderRetry::default(),
+            unacknowledged: Vec::new(),
+            logger,
+        })
+    }
+
+    /// Returns the identity announced to the servers when connecting.
+    pub fn identity(&self) -> &PeerIdentity {
+        &self.identity
+    }
+
+    /// Returns the marketplace the user registers in.
+    pub fn tenant_id(&self) -> &TenantId {
+        &self.tenant_id
+    }
+
+    /// Registers with the secret of the [`Identity`](crate::identity::Identity) of the user
+    /// from now on.
+    pub fn set_secret(&mut self, secret: Option<String>) {
+        self.secret = secret;
+    }
+
+    /// Returns the local address of the connection, if connected.
+    pub fn local_address(&self) -> Option<SocketAddr> {
+        self.communicator.as_ref().map(|c| c.local_address)
+    }
+
+    /// Sends a network message through the current connection.
+    pub fn send(&self, message: NetworkMessage) {
+        if let Some(communicator) = &self.communicator {
+            if let Some(sender) = &communicator.sender {
+                sender.do_send(message);
+            } else {
+                self.logger.error("Sender not initialized in communicator");
+            }
+        } else {
+            self.logger.error("Communicator not found!");
+        }
+    }
+
+    /// Uses `stream` as the current connection.
+    fn attach(&mut self, stream: PeerStream, address: Addr<A>) {
+        self.communicator = Some(Communicator::new(stream, address, self.identity.peer_type));
+        self.registered = false;
+        self.leader_retry.reset();
+    }
+
+    /// Closes the current connection, keeping the messages the server did not acknowledge
+    /// over it.
+    pub fn disconnect(&mut self) {
+        if let Some(mut communicator) = self.communicator.take() {
+            self.unacknowledged
+                .extend(communicator.take_unacknowledged());
+            communicator.shutdown();
+        }
+    }
+
+    /// Sends again the messages the server did not acknowledge over a previous connection.
+    fn resend_unacknowledged(&mut self) {
+        if self.unacknowledged.is_empty() {
+            return;
+        }
+        self.logger.info(format!(
+            "Resending {} unacknowledged messages",
+            self.unacknowledged.len()
+        ));
+        for message in std::mem::take(&mut self.unacknowledged) {
+            self.send(message);
+        }
+    }
+}
+
+/// An actor of a user that registers with the leader of the servers through a
+/// [`PeerCore`].
+///
+/// The actor starts the connection with [`Peer::start_peer`] and passes every
+/// [`NetworkMessage`] through [`Peer::handle_peer_message`], which takes care of finding the
+/// leader, registering and recovering the state of the user. The actor is called back to
+/// recover its state and once registered.
+pub trait Peer: Actor<Context = Context<Self>> + Handler<NetworkMessage> {
+    /// Returns the connection of the actor.
+    fn peer(&self) -> &PeerCore<Self>;
+
+    /// Returns the connection of the actor, to change it.
+    fn peer_mut(&mut self) -> &mut PeerCore<Self>;
+
+    /// Returns the position announced when registering.
+    fn position(&self) -> (f32, f32);
+
+    /// Returns the tags announced when registering, only given by the restaurants.
+    fn tags(&self) -> Vec<String> {
+        Vec::new()
+    }
+
+    /// Returns the vehicle announced when registering, only given by the deliveries.
+    fn vehicle(&self) -> Option<VehicleType> {
+        None
+    }
+
+    /// Called with the state the servers kept of the user, once checked it is its own.
+    fn on_recovered(&mut self, user: UserDTO, ctx: &mut Context<Self>);
+
+    /// Called once registered, after sending again the unacknowledged messages.
+    ///
+    /// ## Arguments
+    /// * `recovered` - Whether the servers had a state of the user.
+    fn on_registered(&mut self, _recovered: bool, _ctx: &mut Context<Self>) {}
+
+    /// Called when registering with `leader_addr`, the leader the actor is connected to.
+    fn on_leader_found(&mut self, _leader_addr: SocketAddr, _ctx: &mut Context<Self>) {}
+
+    /// Starts using the connection made before the actor started, and asks for the leader.
+    fn start_peer(&mut self, ctx: &mut Context<Self>) {
+        let stream = self
+            .peer_mut()
+            .pending_stream
+            .take()
+            .expect("Pending stream should be set");
+        self.peer_mut().attach(stream, ctx.address());
+        self.ask_for_leader_later(ctx);
+    }
+
+    /// Asks the server the actor is connected to for the leader.
+    fn ask_for_leader(&mut self) {
+        let peer = self.peer();
+        let Some(origin_addr) = peer.local_address() else {
+            peer.logger
+                .error("Not connected, cannot ask for the leader");
+            return;
+        };
+        peer.logger.info(format!(
+            "Starting {} at position: {:?}",
+            peer.identity.user_id,
+            self.position()
+        ));
+        peer.send(NetworkMessage::WhoIsLeader(WhoIsLeader {
+            origin_addr,
+            user_id: peer.identity.user_id.clone(),
+        }));
+    }
+
+    /// Asks for the leader after giving the new connection some time to settle.
+    fn ask_for_leader_later(&mut self, ctx: &mut Context<Self>) {
+        if let Some(timer) = self.peer_mut().waiting_timer.take() {
+            ctx.cancel_future(timer);
+        }
+        let timer = ctx.run_later(WAIT_BEFORE_ASKING_FOR_LEADER, |actor, _ctx| {
+            actor.peer_mut().waiting_timer = None;
+            actor.ask_for_leader();
+        });
+        self.peer_mut().waiting_timer = Some(timer);
+    }
+
+    /// Registers with the leader if already connected to it, or connects to it and asks
+    /// again.
+    fn leader_is(&mut self, leader_addr: SocketAddr, ctx: &mut Context<Self>) {
+        self.peer()
+            .logger
+            .info(format!("Received new leader address: {}", leader_addr));
+        if let Some(timer) = self.peer_mut().waiting_timer.take() {
+            ctx.cancel_future(timer);
+        }
+        self.peer_mut().leader_retry.reset();
+
+        let peer = self.peer();
+        if let Some(communicator) = &peer.communicator
+            && communicator.peer_address == leader_addr
+        {
+            peer.logger.info(format!(
+                "Already connected to the leader at address: {}",
+                leader_addr
+            ));
+            peer.send(NetworkMessage::RegisterUser(RegisterUser {
+                origin_addr: communicator.local_address,
+                user_id: peer.identity.user_id.clone(),
+                position: self.position(),
+                secret: peer.secret.clone(),
+                tags: self.tags(),
+                vehicle: self.vehicle(),
+                tenant_id: peer.tenant_id.clone(),
+            }));
+            self.on_leader_found(leader_addr, ctx);
+            return;
+        }
+
+        // Si no estoy conectado al líder, cierro la conexión anterior y conecto al nuevo líder
+        self.peer_mut().disconnect();
+        let identity = self.peer().identity.clone();
+        let logger = self.peer().logger.clone();
+        ctx.spawn(
+            wrap_future::<_, Self>(async move {
+                logger.info(format!(
+                    "Attempting to connect to the new leader at address: {}",
+                    leader_addr
+                ));
+                connect_one(leader_addr, &identity).await
+            })
+            .map(move |maybe_stream, actor, ctx| match maybe_stream {
+                Some(stream) => {
+                    actor.peer_mut().attach(stream, ctx.address());
+                    actor.peer().logger.info(format!(
+                        "Communicator updated with new peer address: {}",
+                        leader_addr
+                    ));
+                    actor.ask_for_leader_later(ctx);
+                }
+                None => {
+                    actor.peer().logger.error(format!(
+                        "Failed to connect to the new leader at {}",
+                        leader_addr
+                    ));
+                    // Si el líder no responde, se vuelve a buscar entre todos los servidores
+                    actor.connection_lost(ctx);
+                }
+            }),
+        );
+    }
+
+    /// Asks for the leader again after the server answered [`RetryLater`], waiting at least
+    /// `suggested` and backing off while the server keeps answering the same. If it never
+    /// stops, the user reconnects, to another server if the first one does not answer.
+    ///
+    /// Once registered, the `RetryLater` throttles a request of the user, which is not sent
+    /// again.
+    ///
+    /// [`RetryLater`]: crate::messages::shared_messages::RetryLater
+    fn retry_later(&mut self, suggested: Duration, ctx: &mut Context<Self>) {
+        if self.peer().registered {
+            self.peer().logger.warn(format!(
+                "The server is throttling the requests, it asked to wait {:?}",
+                suggested
+            ));
+            return;
+        }
+        if let Some(timer) = self.peer_mut().waiting_timer.take() {
+            ctx.cancel_future(timer);
+        }
+        let Some(delay) = self.peer_mut().leader_retry.next_delay(suggested) else {
+            self.peer().logger.error(format!(
+                "The server kept asking to retry later after {} attempts, reconnecting",
+                RETRY_LATER_MAX_ATTEMPTS
+            ));
+            self.peer_connection_closed(ctx);
+            return;
+        };
+        self.peer().logger.info(format!(
+            "The server asked to retry later, asking again for the leader in {:?} (attempt {}/{})",
+            delay,
+            self.peer().leader_retry.attempts,
+            RETRY_LATER_MAX_ATTEMPTS
+        ));
+        let timer = ctx.run_later(delay, |actor, _ctx| {
+            actor.peer_mut().waiting_timer = None;
+            actor.ask_for_leader();
+        });
+        self.peer_mut().waiting_timer = Some(timer);
+    }
+
+    /// Forgets the connection that was closed and starts reconnecting to the servers.
+    fn peer_connection_closed(&mut self, ctx: &mut Context<Self>) {
+        self.peer_mut().disconnect();
+        self.peer()
+            .logger
+            .warn("Retrying to reconnect to the server ...");
+        self.connection_lost(ctx);
+    }
+
+    /// Tells the leader the user leaves the system, before shutting down.
+    fn deregister(&mut self) {
+        let peer = self.peer();
+        if peer.communicator.is_none() {
+            peer.logger
+                .warn("Not connected, leaving without deregistering");
+            return;
+        }
+        peer.logger.info("Deregistering from the servers");
+        peer.send(NetworkMessage::DeregisterUser(DeregisterUser {
+            user_id: peer.identity.user_id.clone(),
+        }));
+    }
+
+    /// Handles the messages every user gets while finding the leader and registering.
+    ///
+    /// ## Returns
+    /// The message back if it is not one of them.
+    fn handle_peer_message(
+        &mut self,
+        msg: NetworkMessage,
+        ctx: &mut Context<Self>,
+    ) -> Option<NetworkMessage> {
+        match msg {
+            NetworkMessage::RetryLater(msg_data) => self.retry_later(msg_data.retry_after(), ctx),
+            NetworkMessage::LeaderIs(msg_data) => self.leader_is(msg_data.coord_addr, ctx),
+            NetworkMessage::RecoveredInfo(user) => {
+                // Con el estado de otro usuario no quedamos registrados: no se reenvía nada
+                let identity = self.peer().identity();
+                if user.peer_type() != identity.peer_type || user.user_id() != identity.user_id {
+                    self.peer().logger.warn(format!(
+                        "Received recovered info of another user ({:?} {}), ignoring",
+                        user.peer_type(),
+                        user.user_id()
+                    ));
+                    return None;
+                }
+                self.peer_mut().registered = true;
+                self.peer_mut().resend_unacknowledged();
+                self.on_registered(true, ctx);
+                self.on_recovered(user, ctx);
+            }
+            NetworkMessage::NoRecoveredInfo => {
+                self.peer()
+                    .logger
+                    .info("No recovered info received, proceeding with normal flow");
+                self.peer_mut().already_connected = true;
+                self.peer_mut().registered = true;
+                self.peer_mut().resend_unacknowledged();
+                self.on_registered(false, ctx);
+            }
+            NetworkMessage::IncompatibleVersion(msg_data) => {
+                self.peer().logger.error(format!(
+                    "Incompatible protocol version with the server (supported: {:?}, received: v{}). Exiting.",
+                    msg_data.supported, msg_data.received
+                ));
+                ctx.stop();
+            }
+            NetworkMessage::IdentityRejected(msg_data) => {
+                self.peer().logger.error(format!(
+                    "The ID {} belongs to another user, whose identity file is not this one. Exiting.",
+                    msg_data.user_id
+                ));
+                ctx.stop();
+            }
+            other => return Some(other),
+        }
+        None
+    }
+}
+
+/// Reconnects every [`Peer`] to the servers when the connection to the leader is lost, and
+/// asks the server reached for the leader again.
+impl<T: Peer> Reconnecting for T {
+    fn reconnection(&mut self) -> &mut ReconnectingConnection {
+        &mut self.peer_mut().reconnection
+    }
+
+    fn is_connected(&self) -> bool {
+        self.peer().communicator.is_some()
+    }
+
+    fn on_reconnected(&mut self, stream: PeerStream, ctx: &mut Context<Self>) {
+        self.peer_mut().attach(stream, ctx.address());
+        self.peer()
+            .logger
+            .info("Reconnected successfully. Restarting actor...");
+        self.ask_for_leader_later(ctx);
+    }
+
+    fn on_reconnection_state(&mut self, state: ReconnectionState, _ctx: &mut Context<Self>) {
+        match state {
+            ReconnectionState::GaveUp => self
+                .peer()
+                .logger
+                .error("Failed to reconnect to any server after closed connection"),
+            state => self
+                .peer()
+                .logger
+                .info(format!("Connection to the server: {}", state)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::memory;
+    use crate::network::peer_types::PeerType;
+    use crate::types::dtos::ClientDTO;
+    use crate::types::hlc::HlcTimestamp;
+    use colored::Color;
+    use std::sync::{Arc, Mutex};
+
+    /// A user that remembers what its [`Peer`] callbacks were called with.
+    struct TestUser {
+        core: PeerCore<TestUser>,
+        registered: Vec<bool>,
+        recovered: Vec<String>,
+    }
+
+    impl Actor for TestUser {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<NetworkMessage> for TestUser {
+        type Result = ();
+
+        fn handle(&mut self, _msg: NetworkMessage, _ctx: &mut Context<Self>) {}
+    }
+
+    impl Peer for TestUser {
+        fn peer(&self) -> &PeerCore<Self> {
+            &self.core
+        }
+
+        fn peer_mut(&mut self) -> &mut PeerCore<Self> {
+            &mut self.core
+        }
+
+        fn position(&self) -> (f32, f32) {
+            (0.0, 0.0)
+        }
+
+        fn on_recovered(&mut self, user: UserDTO, _ctx: &mut Context<Self>) {
+            self.recovered.push(user.user_id().to_string());
+        }
+
+        fn on_registered(&mut self, recovered: bool, _ctx: &mut Context<Self>) {
+            self.registered.push(recovered);
+        }
+    }
+
+    /// A server end that keeps every message it receives.
+    struct Recorder {
+        received: Arc<Mutex<Vec<NetworkMessage>>>,
+    }
+
+    impl Actor for Recorder {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<NetworkMessage> for Recorder {
+        type Result = ();
+
+        fn handle(&mut self, msg: NetworkMessage, _ctx: &mut Context<Self>) {
+            self.received.lock().unwrap().push(msg);
+        }
+    }
+
+    /// Returns `client_1`, not connected to any server.
+    fn test_user() -> TestUser {
+        let identity = PeerIdentity::new(PeerType::ClientType, "client_1");
+        let servers = vec![server_addr()];
+        TestUser {
+            core: PeerCore {
+                reconnection: ReconnectingConnection::new(servers.clone(), identity.clone()),
+                identity,
+                secret: None,
+                tenant_id: TenantId::default(),
+                servers,
+                communicator: None,
+                pending_stream: None,
+                waiting_timer: None,
+                already_connected: false,
+                registered: false,
+                leader_retry: LeaderRetry::default(),
+                unacknowledged: Vec::new(),
+                logger: Logger::new("Test", Color::White),
+            },
+            registered: Vec::new(),
+            recovered: Vec::new(),
+        }
+    }
+
+    fn server_addr() -> SocketAddr {
+        "127.0.0.1:8080".parse().unwrap()
+    }
+
+    fn recovered_info(client_id: &str) -> NetworkMessage {
+        NetworkMessage::RecoveredInfo(UserDTO::Client(ClientDTO {
+            client_position: (0.0, 0.0),
+            client_id: client_id.to_string(),
+            client_order: None,
+            tenant_id: TenantId::default(),
+            time_stamp: HlcTimestamp::default(),
+        }))
+    }
+
+    fn deregister(user_id: &str) -> NetworkMessage {
+        NetworkMessage::DeregisterUser(DeregisterUser {
+            user_id: user_id.to_string(),
+        })
+    }
+
+    /// Opens a connection to the in-memory server and returns both ends, the server one
+    /// recording what it receives.
+    async fn connect(
+        listener: &mut memory::MemoryListener,
+    ) -> (
+        PeerStream,
+        Communicator<Recorder>,
+        Arc<Mutex<Vec<NetworkMessage>>>,
+    ) {
+        let client = memory::connect(server_addr(), "client_1").unwrap();
+        let server = listener.accept().await;
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let recorder = Recorder {
+            received: received.clone(),
+        }
+        .start();
+        let server = Communicator::new(PeerStream::Memory(server), recorder, PeerType::ClientType);
+        (PeerStream::Memory(client), server, received)
+    }
+
+    /// Waits until the server receives a `DeregisterUser` and returns the IDs of them all.
+    async fn wait_for_deregisters(received: &Arc<Mutex<Vec<NetworkMessage>>>) -> Vec<String> {
+        for _ in 0..100 {
+            let ids: Vec<String> = received
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|message| match message {
+                    NetworkMessage::DeregisterUser(msg) => Some(msg.user_id.clone()),
+                    _ => None,
+                })
+                .collect();
+            if !ids.is_empty() {
+                return ids;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Vec::new()
+    }
+
+    #[actix_rt::test]
+    async fn recovered_info_of_another_user_does_not_register() {
+        let mut user = test_user();
+        let mut ctx = Context::new();
+        user.core.unacknowledged.push(deregister("client_1"));
+
+        assert!(
+            user.handle_peer_message(recovered_info("client_2"), &mut ctx)
+                .is_none()
+        );
+        assert!(!user.core.registered);
+        assert_eq!(user.core.unacknowledged.len(), 1);
+        assert!(user.registered.is_empty());
+        assert!(user.recovered.is_empty());
+
+        user.handle_peer_message(recovered_info("client_1"), &mut ctx);
+        assert!(user.core.registered);
+        assert!(user.core.unacknowledged.is_empty());
+        assert_eq!(user.registered, [true]);
+        assert_eq!(user.recovered, ["client_1"]);
+    }
+
+    #[actix_rt::test]
+    async fn unacknowledged_messages_are_resent_once_registered_again() {
+        memory::enable();
+        let mut listener = memory::bind(server_addr()).unwrap();
+        let mut user = test_user();
+        let mut ctx = Context::<TestUser>::new();
+
+        let (stream, _first_server, _) = connect(&mut listener).await;
+        user.core.attach(stream, ctx.address());
+        user.handle_peer_message(NetworkMessage::NoRecoveredInfo, &mut ctx);
+        assert!(user.core.registered);
+        assert!(user.core.already_connected);
+
+        // El servidor nunca confirmó el mensaje antes de que se cortara la conexión
+        let communicator = user.core.communicator.as_ref().unwrap();
+        communicator
+            .unacknowledged
+            .track(deregister("client_1"), tokio::time::Instant::now());
+        user.core.disconnect();
+        assert!(user.core.communicator.is_none());
+        assert_eq!(user.core.unacknowledged.len(), 1);
+
+        // Se reenvía recién cuando se registra sobre la nueva conexión
+        let (stream, _second_server, received) = connect(&mut listener).await;
+        user.core.attach(stream, ctx.address());
+        assert!(!user.core.registered);
+        assert_eq!(user.core.unacknowledged.len(), 1);
+
+        user.handle_peer_message(NetworkMessage::NoRecoveredInfo, &mut ctx);
+        assert!(user.core.unacknowledged.is_empty());
+        assert_eq!(user.registered, [false, false]);
+        assert_eq!(wait_for_deregisters(&received).await, ["client_1"]);
+        memory::disable();
+    }
+
+    #[test]
+    fn backs_off_until_giving_up() {
+        let mut retry = LeaderRetry::default();
+        let delays: Vec<_> = (0..RETRY_LATER_MAX_ATTEMPTS)
+            .map(|_| retry.next_delay(Duration::ZERO).unwrap())
+            .collect();
+        assert_eq!(delays[0], RETRY_LATER_BASE_DELAY);
+        assert_eq!(delays[1], RETRY_LATER_BASE_DELAY * 2);
+        assert!(delays.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(delays.iter().all(|delay| *delay <= RETRY_LATER_MAX_DELAY));
+        assert_eq!(retry.next_delay(Duration::ZERO), None);
+
+        // La espera pedida por el servidor se respeta aunque supere el backoff
+        retry.reset();
+        let suggested = RETRY_LATER_MAX_DELAY * 2;
+        assert_eq!(retry.next_delay(suggested), Some(suggested));
+    }
+}
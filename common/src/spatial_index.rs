@@ -0,0 +1,135 @@
+use crate::utils::calculate_distance;
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// A grid of square cells that indexes entities by their position, so that the entities
+/// near a point are found by looking only at the cells around it instead of at every entity.
+#[derive(Debug, Clone)]
+pub struct SpatialIndex<K> {
+    /// Length of the side of each cell.
+    cell_size: f32,
+    /// Entities inside each cell, by the coordinates of the cell.
+    cells: HashMap<(i32, i32), HashSet<K>>,
+    /// Position of each entity.
+    positions: HashMap<K, (f32, f32)>,
+}
+
+impl<K> SpatialIndex<K>
+where
+    K: Hash + Eq + Clone,
+{
+    /// Creates an empty index.
+    ///
+    /// ## Arguments
+    /// * `cell_size` - Length of the side of each cell. Queries are cheapest when it is close
+    ///   to the radius usually searched.
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Returns the cell that contains `position`.
+    fn cell_of(&self, position: (f32, f32)) -> (i32, i32) {
+        (
+            (position.0 / self.cell_size).floor() as i32,
+            (position.1 / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Indexes `key` at `position`, moving it if it was already indexed.
+    pub fn insert(&mut self, key: K, position: (f32, f32)) {
+        self.remove(&key);
+        let cell = self.cell_of(position);
+        self.cells.entry(cell).or_default().insert(key.clone());
+        self.positions.insert(key, position);
+    }
+
+    /// Removes `key` from the index, if present.
+    pub fn remove(&mut self, key: &K) {
+        let Some(position) = self.positions.remove(key) else {
+            return;
+        };
+        let cell = self.cell_of(position);
+        if let Some(keys) = self.cells.get_mut(&cell) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.cells.remove(&cell);
+            }
+        }
+    }
+
+    /// Returns the number of entities indexed.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// Returns whether the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// Returns the entities at most `radius` away from `center`, measured with
+    /// [`calculate_distance`].
+    pub fn within(&self, center: (f32, f32), radius: f32) -> Vec<&K> {
+        let (min_x, min_y) = self.cell_of((center.0 - radius, center.1 - radius));
+        let (max_x, max_y) = self.cell_of((center.0 + radius, center.1 + radius));
+        let mut found = Vec::new();
+        for x in min_x..=max_x {
+            for y in min_y..=max_y {
+                let Some(keys) = self.cells.get(&(x, y)) else {
+                    continue;
+                };
+                found.extend(keys.iter().filter(|key| {
+                    self.positions
+                        .get(*key)
+                        .is_some_and(|position| calculate_distance(*position, center) <= radius)
+                }));
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(keys: Vec<&&'static str>) -> Vec<&'static str> {
+        let mut keys: Vec<&'static str> = keys.into_iter().copied().collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn finds_only_the_entities_within_the_radius() {
+        let mut index = SpatialIndex::new(8.0);
+        index.insert("near", (2.0, 3.0));
+        index.insert("other_cell", (9.0, 1.0));
+        index.insert("far", (20.0, 20.0));
+        // Está en la misma celda, pero a distancia 11
+        index.insert("corner", (6.0, 7.0));
+
+        assert_eq!(
+            sorted(index.within((1.0, 1.0), 8.0)),
+            vec!["near", "other_cell"]
+        );
+        assert_eq!(sorted(index.within((-5.0, -5.0), 2.0)), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn moves_and_removes_entities() {
+        let mut index = SpatialIndex::new(8.0);
+        index.insert("delivery", (0.0, 0.0));
+        index.insert("delivery", (30.0, 30.0));
+        assert_eq!(index.len(), 1);
+        assert!(index.within((0.0, 0.0), 5.0).is_empty());
+        assert_eq!(sorted(index.within((29.0, 29.0), 5.0)), vec!["delivery"]);
+
+        index.remove(&"delivery");
+        assert!(index.is_empty());
+        assert!(index.within((29.0, 29.0), 5.0).is_empty());
+    }
+}
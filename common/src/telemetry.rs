@@ -0,0 +1,141 @@
+use crate::logger::Logger;
+use tracing_subscriber::EnvFilter;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+/// Environment variable selecting where the traces of a process go:
+/// - `off` (default): no traces, the loggers print as always.
+/// - `fmt`: the logs and the spans of each handled message are printed, with the time each
+///   span took when it closes.
+/// - `otlp`: as `fmt`, and the spans are also exported with OTLP to
+///   `OTEL_EXPORTER_OTLP_ENDPOINT` (only if built with the `otlp` feature of `common`).
+///
+/// Which spans and logs are kept is chosen with `RUST_LOG`, `info` by default.
+pub const TRACING_ENV_VAR: &str = "TRACING";
+
+/// Where the traces of a process go, see [`TRACING_ENV_VAR`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TracingMode {
+    Off,
+    Fmt,
+    Otlp,
+}
+
+/// Returns the mode selected by [`TRACING_ENV_VAR`].
+pub fn mode_from_env() -> TracingMode {
+    match std::env::var(TRACING_ENV_VAR) {
+        Ok(value) => match value.trim().to_lowercase().as_str() {
+            "" | "off" => TracingMode::Off,
+            "fmt" => TracingMode::Fmt,
+            "otlp" => TracingMode::Otlp,
+            _ => {
+                eprintln!(
+                    "[Telemetry] Ignoring invalid {}: '{}'",
+                    TRACING_ENV_VAR, value
+                );
+                TracingMode::Off
+            }
+        },
+        Err(_) => TracingMode::Off,
+    }
+}
+
+/// Keeps the traces of the process flowing. The spans not exported yet are flushed when it is
+/// dropped, so it must live until the process ends.
+#[must_use]
+pub struct Telemetry {
+    #[cfg(feature = "otlp")]
+    provider: Option<opentelemetry_sdk::trace::TracerProvider>,
+}
+
+impl Telemetry {
+    /// Returns a telemetry without spans to flush.
+    fn local() -> Self {
+        Self {
+            #[cfg(feature = "otlp")]
+            provider: None,
+        }
+    }
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otlp")]
+        if let Some(provider) = self.provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("[Telemetry] Error flushing the spans: {}", e);
+        }
+    }
+}
+
+/// Starts collecting the traces of the process as [`TRACING_ENV_VAR`] selects, and makes
+/// every [`Logger`] log through them.
+///
+/// ## Arguments
+/// * `service` - Name of the process in the exported spans, e.g. `server_8081`.
+pub fn init(service: &str) -> Telemetry {
+    let mode = mode_from_env();
+    if mode == TracingMode::Off {
+        return Telemetry::local();
+    }
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let fmt = tracing_subscriber::fmt::layer()
+        .with_target(false)
+        .with_span_events(FmtSpan::CLOSE);
+    let registry = tracing_subscriber::registry().with(filter).with(fmt);
+
+    #[cfg(feature = "otlp")]
+    if mode == TracingMode::Otlp {
+        match otlp_provider(service) {
+            Ok(provider) => {
+                use opentelemetry::trace::TracerProvider as _;
+                let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("pedidos"));
+                if registry.with(layer).try_init().is_ok() {
+                    Logger::set_tracing(true);
+                }
+                return Telemetry {
+                    provider: Some(provider),
+                };
+            }
+            Err(e) => eprintln!("[Telemetry] Cannot export spans with OTLP: {}", e),
+        }
+    }
+    #[cfg(not(feature = "otlp"))]
+    if mode == TracingMode::Otlp {
+        eprintln!(
+            "[Telemetry] {} of {} needs the otlp feature, printing them instead",
+            TRACING_ENV_VAR, service
+        );
+    }
+
+    // Puede fallar si otro subscriber ya estaba instalado, p. ej. en los tests
+    if registry.try_init().is_ok() {
+        Logger::set_tracing(true);
+    }
+    Telemetry::local()
+}
+
+/// Creates the provider exporting the spans of `service` with OTLP over gRPC, to the endpoint
+/// in `OTEL_EXPORTER_OTLP_ENDPOINT` (`http://localhost:4317` by default).
+#[cfg(feature = "otlp")]
+fn otlp_provider(
+    service: &str,
+) -> Result<opentelemetry_sdk::trace::TracerProvider, opentelemetry::trace::TraceError> {
+    use opentelemetry::KeyValue;
+    use opentelemetry_otlp::WithExportConfig as _;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_timeout(std::time::Duration::from_secs(3))
+        .build()?;
+    // El runtime de actix es de un solo hilo: el exportador corre en uno propio
+    Ok(opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::TokioCurrentThread)
+        .with_resource(opentelemetry_sdk::Resource::new(vec![KeyValue::new(
+            "service.name",
+            service.to_string(),
+        )]))
+        .build())
+}
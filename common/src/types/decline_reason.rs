@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Enum representing why the authorization of an order was declined
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeclineReason {
+    /// The client cannot afford the order
+    InsufficientFunds,
+    /// The payment was flagged by the fraud check of the issuer
+    FraudCheck,
+    /// The issuer did not answer the gateway in time
+    IssuerUnavailable,
+    /// The gateway is in a maintenance window
+    GatewayMaintenance,
+    /// The server could not reach the gateway in time
+    GatewayTimeout,
+}
+
+impl DeclineReason {
+    /// Returns whether the same order may be authorized if it is requested again later.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            DeclineReason::IssuerUnavailable
+                | DeclineReason::GatewayMaintenance
+                | DeclineReason::GatewayTimeout
+        )
+    }
+}
+
+impl fmt::Display for DeclineReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeclineReason::InsufficientFunds => write!(f, "insufficient funds"),
+            DeclineReason::FraudCheck => write!(f, "flagged by the fraud check"),
+            DeclineReason::IssuerUnavailable => write!(f, "issuer unavailable"),
+            DeclineReason::GatewayMaintenance => write!(f, "gateway under maintenance"),
+            DeclineReason::GatewayTimeout => write!(f, "payment gateway timed out"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_unavailable_issuer_or_gateway_is_worth_retrying() {
+        assert!(DeclineReason::GatewayTimeout.is_transient());
+        assert!(DeclineReason::IssuerUnavailable.is_transient());
+        assert!(!DeclineReason::InsufficientFunds.is_transient());
+        assert!(!DeclineReason::FraudCheck.is_transient());
+        assert_eq!(
+            serde_json::to_string(&DeclineReason::FraudCheck).unwrap(),
+            "\"fraud_check\""
+        );
+    }
+}
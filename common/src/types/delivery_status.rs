@@ -13,4 +13,6 @@ pub enum DeliveryStatus {
     WaitingConfirmation,
     /// Delivering an order
     Delivering,
+    /// Signed off on shutdown, not taking orders until it registers again
+    Offline,
 }
@@ -1,11 +1,24 @@
 use std::collections::HashSet;
 
+use crate::constants::{DELIVERY_BASE_FEE_CENTS, DELIVERY_FEE_PER_BLOCK_CENTS, ORDER_PRICE_CENTS};
+use crate::messages::coordinator_messages::NotifyOrderUpdated;
+use crate::network::peer_types::PeerType;
+use crate::types::hlc::HlcTimestamp;
+use crate::types::order_event::OrderEvent;
+use crate::types::order_priority::OrderPriority;
 use crate::types::order_status::OrderStatus;
+use crate::types::restaurant_info::RestaurantInfo;
+use crate::types::secret_hash::SecretHash;
+use crate::types::storage_entity::StorageEntity;
+use crate::types::tenant::TenantId;
+use crate::types::vehicle::VehicleType;
+use crate::utils::calculate_distance;
 use crate::{bimap::BiMap, types::delivery_status::DeliveryStatus};
 use actix::prelude::*;
 use serde::{Deserialize, Serialize};
 use serde_with::{DisplayFromStr, serde_as};
 use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Custom serialization for BiMap<u64, String>
 mod bimap_u64_string_serde {
@@ -62,6 +75,26 @@ pub enum UserDTO {
     Delivery(DeliveryDTO),
 }
 
+impl UserDTO {
+    /// Returns the ID of the user.
+    pub fn user_id(&self) -> &str {
+        match self {
+            UserDTO::Client(client) => &client.client_id,
+            UserDTO::Restaurant(restaurant) => &restaurant.restaurant_id,
+            UserDTO::Delivery(delivery) => &delivery.delivery_id,
+        }
+    }
+
+    /// Returns the type of peer the user connects as.
+    pub fn peer_type(&self) -> PeerType {
+        match self {
+            UserDTO::Client(_) => PeerType::ClientType,
+            UserDTO::Restaurant(_) => PeerType::RestaurantType,
+            UserDTO::Delivery(_) => PeerType::DeliveryType,
+        }
+    }
+}
+
 /// Data Transfer Object to represent a client in the system.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ClientDTO {
@@ -71,8 +104,11 @@ pub struct ClientDTO {
     pub client_id: String,
     /// User Order associated with the client (if any).
     pub client_order: Option<OrderDTO>,
+    /// Marketplace the client belongs to.
+    #[serde(default)]
+    pub tenant_id: TenantId,
     /// Timestamp that records the last update of the client.
-    pub time_stamp: std::time::SystemTime,
+    pub time_stamp: HlcTimestamp,
 }
 
 /// Data Transfer Object to represent a restaurant in the system.
@@ -86,8 +122,32 @@ pub struct RestaurantDTO {
     pub authorized_orders: HashSet<OrderDTO>,
     /// Pending orders that the restaurant has not yet prepared.
     pub pending_orders: HashSet<OrderDTO>,
+    /// Fees kept from the orders cancelled by their clients, credited to the restaurant.
+    #[serde(default)]
+    pub cancellation_fees: Vec<CancellationFee>,
+    /// Whether the restaurant is outside its opening hours, as it last announced.
+    #[serde(default)]
+    pub closed: bool,
+    /// The cuisines or categories of the restaurant, as given when it last started.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Marketplace the restaurant belongs to.
+    #[serde(default)]
+    pub tenant_id: TenantId,
     /// Timestamp that records the last update of the restaurant.
-    pub time_stamp: std::time::SystemTime,
+    pub time_stamp: HlcTimestamp,
+}
+
+impl RestaurantDTO {
+    /// Returns what the clients and the nearby services know about the restaurant.
+    pub fn info(&self) -> RestaurantInfo {
+        RestaurantInfo {
+            id: self.restaurant_id.clone(),
+            position: self.restaurant_position,
+            tags: self.tags.clone(),
+            tenant_id: self.tenant_id.clone(),
+        }
+    }
 }
 
 /// Data Transfer Object to represent a delivery user in the system.
@@ -103,8 +163,48 @@ pub struct DeliveryDTO {
     pub current_order: Option<OrderDTO>,
     /// State of delivery user
     pub status: DeliveryStatus,
+    /// Vehicle the delivery user travels with, as given when it last started.
+    #[serde(default)]
+    pub vehicle: VehicleType,
+    /// Marketplace the delivery user belongs to.
+    #[serde(default)]
+    pub tenant_id: TenantId,
     /// Timestamp that records the last update of the delivery user.
-    pub time_stamp: std::time::SystemTime,
+    pub time_stamp: HlcTimestamp,
+}
+
+/// A line of the cart of an order: some units of a dish.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderItem {
+    /// Name of the dish.
+    pub dish: String,
+    /// Units of the dish ordered.
+    pub quantity: u32,
+    /// Price of each unit, in cents, set by the server.
+    #[serde(default)]
+    pub unit_price: u64,
+}
+
+impl OrderItem {
+    /// Creates a line of `quantity` units of `dish` at the standard price.
+    pub fn new(dish: &str, quantity: u32) -> Self {
+        Self {
+            dish: dish.to_string(),
+            quantity,
+            unit_price: ORDER_PRICE_CENTS,
+        }
+    }
+
+    /// Returns the price of every unit of the line, in cents.
+    pub fn total(&self) -> u64 {
+        self.unit_price * self.quantity as u64
+    }
+}
+
+impl std::fmt::Display for OrderItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x {}", self.quantity, self.dish)
+    }
 }
 
 /// Data Transfer Object to represent an order in the system.
@@ -112,8 +212,8 @@ pub struct DeliveryDTO {
 pub struct OrderDTO {
     /// Unique ID of the order.
     pub order_id: u64,
-    /// Name of the dish associated with the order.
-    pub dish_name: String,
+    /// Dishes in the cart of the order.
+    pub items: Vec<OrderItem>,
     /// Unique ID of the client who placed the order.
     pub client_id: String,
     /// Unique ID of the restaurant that will prepare the order.
@@ -126,8 +226,73 @@ pub struct OrderDTO {
     pub client_position: (f32, f32),
     /// Estimated time for the order to be delivered.
     pub expected_delivery_time: u64,
+    /// Estimated time, in milliseconds, for the restaurant to have the order ready.
+    #[serde(default)]
+    pub expected_preparation_time: u64,
+    /// Priority chosen by the client.
+    #[serde(default)]
+    pub priority: OrderPriority,
+    /// Cents taken off the price by the promo code of the order, set by the server.
+    #[serde(default)]
+    pub discount: u64,
+    /// Marketplace of the client who placed the order, set by the server.
+    #[serde(default)]
+    pub tenant_id: TenantId,
     /// Timestamp that records the last update of the order.
-    pub time_stamp: std::time::SystemTime,
+    pub time_stamp: HlcTimestamp,
+}
+
+impl OrderDTO {
+    /// Creates a just requested order of `client_id` at `restaurant_id`, with the default
+    /// priority, tenant and time stamp and no delivery, discount or estimated times yet.
+    ///
+    /// ## Arguments
+    /// * `order_id` - The ID of the order.
+    /// * `client_id` - The ID of the client placing it.
+    /// * `restaurant_id` - The ID of the restaurant that prepares it.
+    /// * `items` - The dishes of the order.
+    pub fn new(order_id: u64, client_id: &str, restaurant_id: &str, items: Vec<OrderItem>) -> Self {
+        Self {
+            order_id,
+            items,
+            client_id: client_id.to_string(),
+            restaurant_id: restaurant_id.to_string(),
+            delivery_id: None,
+            status: OrderStatus::Requested,
+            client_position: (0.0, 0.0),
+            expected_delivery_time: 0,
+            expected_preparation_time: 0,
+            priority: OrderPriority::default(),
+            discount: 0,
+            tenant_id: TenantId::default(),
+            time_stamp: HlcTimestamp::default(),
+        }
+    }
+
+    /// Returns the price of the order before its discount, in cents: the sum of its items
+    /// plus the surcharge of its priority.
+    pub fn list_price(&self) -> u64 {
+        self.items.iter().map(OrderItem::total).sum::<u64>() + self.priority.surcharge()
+    }
+
+    /// Returns the amount billed for the order, in cents, after its discount.
+    pub fn price(&self) -> u64 {
+        self.list_price().saturating_sub(self.discount)
+    }
+
+    /// Returns the units of dishes of the order, which a courier has to carry.
+    pub fn units(&self) -> u32 {
+        self.items.iter().map(|item| item.quantity).sum()
+    }
+
+    /// Describes the items of the order, e.g. `2x Pizza, 1x Empanadas`.
+    pub fn summary(&self) -> String {
+        self.items
+            .iter()
+            .map(OrderItem::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
 }
 
 impl Eq for OrderDTO {}
@@ -144,6 +309,232 @@ impl std::hash::Hash for OrderDTO {
     }
 }
 
+/// Data Transfer Object to represent what a delivery user earned for an order.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EarningDTO {
+    /// Unique ID of the delivered order.
+    pub order_id: u64,
+    /// Fixed fee of every delivery, in cents.
+    pub base_fee: u64,
+    /// Fee for the distance from the restaurant to the client, in cents.
+    pub distance_fee: u64,
+    /// Whether the payment gateway already paid the earning out.
+    pub paid_out: bool,
+    /// Timestamp that records the last update of the earning.
+    pub time_stamp: HlcTimestamp,
+}
+
+impl EarningDTO {
+    /// Creates the earning of delivering the order `order_id` over `distance` blocks.
+    pub fn for_distance(order_id: u64, distance: f32, time_stamp: HlcTimestamp) -> Self {
+        Self {
+            order_id,
+            base_fee: DELIVERY_BASE_FEE_CENTS,
+            distance_fee: (distance.max(0.0).round() as u64) * DELIVERY_FEE_PER_BLOCK_CENTS,
+            paid_out: false,
+            time_stamp,
+        }
+    }
+
+    /// Returns the cents earned.
+    pub fn total(&self) -> u64 {
+        self.base_fee + self.distance_fee
+    }
+}
+
+/// What a delivery user is told about an order offered to it, so it can decide whether to
+/// take it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct OfferDetails {
+    /// Position of the restaurant the order is picked up at.
+    pub restaurant_position: (f32, f32),
+    /// Blocks from the delivery user to the restaurant.
+    pub pickup_distance: f32,
+    /// Blocks from the restaurant to the client.
+    pub dropoff_distance: f32,
+    /// What the delivery pays, in cents, as [`EarningDTO::for_distance`] computes it.
+    pub payout: u64,
+}
+
+impl OfferDetails {
+    /// Computes the details of offering `order`, picked up at `restaurant_position`, to a
+    /// delivery user at `delivery_position`.
+    pub fn new(
+        order: &OrderDTO,
+        delivery_position: (f32, f32),
+        restaurant_position: (f32, f32),
+    ) -> Self {
+        let dropoff_distance = calculate_distance(restaurant_position, order.client_position);
+        Self {
+            restaurant_position,
+            pickup_distance: calculate_distance(delivery_position, restaurant_position),
+            dropoff_distance,
+            payout: EarningDTO::for_distance(
+                order.order_id,
+                dropoff_distance,
+                HlcTimestamp::default(),
+            )
+            .total(),
+        }
+    }
+
+    /// Returns the blocks of the whole trip: to the restaurant and then to the client.
+    pub fn trip_distance(&self) -> f32 {
+        self.pickup_distance + self.dropoff_distance
+    }
+}
+
+/// Data Transfer Object to represent the earnings of a delivery user.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct EarningsReport {
+    /// Unique ID of the delivery user.
+    pub delivery_id: String,
+    /// Earning of each order delivered, oldest first.
+    pub earnings: Vec<EarningDTO>,
+}
+
+impl EarningsReport {
+    /// Returns the cents earned for every order.
+    pub fn total(&self) -> u64 {
+        self.earnings.iter().map(EarningDTO::total).sum()
+    }
+
+    /// Returns the cents already paid out by the payment gateway.
+    pub fn paid_out(&self) -> u64 {
+        self.earnings
+            .iter()
+            .filter(|earning| earning.paid_out)
+            .map(EarningDTO::total)
+            .sum()
+    }
+}
+
+/// What a client pays for cancelling an order, which depends on how far the restaurant got
+/// with it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CancellationFee {
+    /// Unique ID of the cancelled order.
+    pub order_id: u64,
+    /// Status of the order when it was cancelled.
+    pub stage: OrderStatus,
+    /// Amount the client paid for the order, in cents.
+    pub price: u64,
+    /// Amount kept and credited to the restaurant, in cents.
+    pub fee: u64,
+}
+
+impl CancellationFee {
+    /// Computes the fee of cancelling `order` in its current status.
+    ///
+    /// ## Arguments
+    /// * `order` - The order, as stored before the cancellation.
+    /// * `waived` - Whether the client gets the whole price back anyway, e.g. when the
+    ///   cluster cancels an order it got stuck with.
+    pub fn for_order(order: &OrderDTO, waived: bool) -> Self {
+        let price = order.price();
+        let percent = if waived {
+            0
+        } else {
+            order.status.cancellation_fee_percent()
+        };
+        Self {
+            order_id: order.order_id,
+            stage: order.status.clone(),
+            price,
+            fee: price * percent / 100,
+        }
+    }
+
+    /// Returns the cents given back to the client.
+    pub fn refunded(&self) -> u64 {
+        self.price - self.fee
+    }
+}
+
+/// A promo code used by a client, kept so that each client uses a code only once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PromoRedemption {
+    /// The promo code, in uppercase.
+    pub code: String,
+    /// ID of the client that used it.
+    pub client_id: String,
+    /// ID of the order it was used for.
+    pub order_id: u64,
+    /// Cents taken off the price of the order.
+    pub discount: u64,
+    /// Timestamp of when it was used.
+    pub time_stamp: HlcTimestamp,
+}
+
+/// The bill of a finalized order sent to the payment gateway, kept until the gateway
+/// confirms the payment so it can be sent again after losing the gateway or the leader.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxPayment {
+    /// The finalized order to bill.
+    pub order: OrderDTO,
+    /// Whether the payment gateway confirmed the payment.
+    pub completed: bool,
+    /// Timestamp that records the last update of the payment.
+    pub time_stamp: HlcTimestamp,
+}
+
+/// An order placed by a client with a `request_id` of its choice, kept so that a resent
+/// request gets the order already created instead of a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderRequest {
+    /// Number chosen by the client for the request.
+    pub request_id: u64,
+    /// ID the leader gave to the order.
+    pub order_id: u64,
+}
+
+/// A leader change, kept to diagnose a leadership that keeps flapping.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElectionRecord {
+    /// ID of the new leader.
+    pub leader_id: String,
+    /// Epoch (term) the new leader leads.
+    #[serde(default)]
+    pub epoch: u64,
+    /// When the change was seen, in milliseconds since the Unix epoch.
+    pub at_millis: u64,
+}
+
+impl ElectionRecord {
+    /// Creates a record of `leader_id` becoming the leader of `epoch` now.
+    pub fn now(leader_id: &str, epoch: u64) -> Self {
+        let at_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        Self {
+            leader_id: leader_id.to_string(),
+            epoch,
+            at_millis,
+        }
+    }
+}
+
+/// When each recently removed entity was removed, so that an older copy of it received in
+/// a snapshot does not bring it back.
+#[serde_as]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstones {
+    /// When each client was removed, by client ID.
+    #[serde(default)]
+    pub clients: HashMap<String, HlcTimestamp>,
+    /// When each restaurant was removed, by restaurant ID.
+    #[serde(default)]
+    pub restaurants: HashMap<String, HlcTimestamp>,
+    /// When each delivery was removed, by delivery ID.
+    #[serde(default)]
+    pub deliverys: HashMap<String, HlcTimestamp>,
+    /// When each order was removed, by order ID.
+    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    pub orders: HashMap<u64, HlcTimestamp>,
+}
+
 /// Data Transfer Object to represent a snapshot of the system state.
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize, Message, Clone)]
@@ -161,8 +552,203 @@ pub struct Snapshot {
     /// BiMap of accepted deliveries
     #[serde(with = "bimap_u64_string_serde")]
     pub accepted_deliveries: BiMap<u64, String>,
+    /// Leader epoch in which each accepted delivery was decided
+    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    pub accepted_epochs: HashMap<u64, u64>,
+    /// Timeline of each order, including the recently removed ones
+    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    pub order_events: HashMap<u64, Vec<OrderEvent>>,
+    /// Last state of the recently removed orders, stamped when they were removed
+    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    pub removed_orders: HashMap<u64, OrderDTO>,
+    /// When the recently removed clients, restaurants, deliveries and orders were removed
+    #[serde(default)]
+    pub tombstones: Tombstones,
+    /// Order updates not acknowledged yet by each user, oldest first
+    #[serde(default)]
+    pub order_updates: HashMap<String, Vec<NotifyOrderUpdated>>,
+    /// ID of the next order update
+    #[serde(default)]
+    pub next_order_update_id: u64,
+    /// Earnings of each delivery user, oldest first
+    #[serde(default)]
+    pub delivery_earnings: HashMap<String, Vec<EarningDTO>>,
+    /// Most recent leader changes, oldest first
+    #[serde(default)]
+    pub elections: Vec<ElectionRecord>,
+    /// Promo codes used by the clients, by code
+    #[serde(default)]
+    pub promo_redemptions: HashMap<String, Vec<PromoRedemption>>,
+    /// Salted hash of the secret each user ID was claimed with
+    #[serde(default)]
+    pub user_secrets: HashMap<String, SecretHash>,
+    /// Bills sent to the payment gateway, by order ID
+    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
+    #[serde(default)]
+    pub payment_outbox: HashMap<u64, OutboxPayment>,
+    /// Orders placed by each client, by client ID
+    #[serde(default)]
+    pub order_requests: HashMap<String, Vec<OrderRequest>>,
+    /// When each client and delivery was last seen
+    #[serde(default)]
+    pub last_seen: HashMap<String, HlcTimestamp>,
     /// Index of the next log
     pub next_log_id: u64,
     /// Index of the minimum persistent log
     pub min_persistent_log_index: u64,
 }
+
+impl Snapshot {
+    /// Leaves in the snapshot only the `entities` asked for. The leader changes, the secrets
+    /// of the users, when they were last seen and the indices of the log are always kept.
+    pub fn subset(mut self, entities: &[StorageEntity]) -> Self {
+        if !entities.contains(&StorageEntity::Clients) {
+            self.clients.clear();
+            self.tombstones.clients.clear();
+        }
+        if !entities.contains(&StorageEntity::Restaurants) {
+            self.restaurants.clear();
+            self.tombstones.restaurants.clear();
+        }
+        if !entities.contains(&StorageEntity::Deliveries) {
+            self.deliverys.clear();
+            self.delivery_earnings.clear();
+            self.tombstones.deliverys.clear();
+        }
+        if !entities.contains(&StorageEntity::Orders) {
+            self.orders.clear();
+            self.removed_orders.clear();
+            self.tombstones.orders.clear();
+            self.accepted_deliveries = BiMap::new();
+            self.accepted_epochs.clear();
+            self.order_events.clear();
+            self.order_updates.clear();
+            self.next_order_update_id = 0;
+            self.promo_redemptions.clear();
+            self.payment_outbox.clear();
+            self.order_requests.clear();
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{CANCELLATION_FEE_PREPARING_PERCENT, CANCELLATION_FEE_READY_PERCENT};
+    use crate::types::tenant::TenantId;
+
+    #[test]
+    fn earnings_grow_with_the_distance() {
+        let short = EarningDTO::for_distance(1, 2.0, HlcTimestamp::default());
+        let long = EarningDTO::for_distance(2, 12.4, HlcTimestamp::default());
+        assert_eq!(
+            short.total(),
+            DELIVERY_BASE_FEE_CENTS + 2 * DELIVERY_FEE_PER_BLOCK_CENTS
+        );
+        assert_eq!(long.distance_fee, 12 * DELIVERY_FEE_PER_BLOCK_CENTS);
+
+        let report = EarningsReport {
+            delivery_id: "delivery_1".to_string(),
+            earnings: vec![
+                EarningDTO {
+                    paid_out: true,
+                    ..short.clone()
+                },
+                long.clone(),
+            ],
+        };
+        assert_eq!(report.total(), short.total() + long.total());
+        assert_eq!(report.paid_out(), short.total());
+    }
+
+    #[test]
+    fn offers_pay_for_the_distance_from_the_restaurant() {
+        let order = OrderDTO {
+            order_id: 7,
+            client_id: "client_1".to_string(),
+            restaurant_id: "restaurant_1".to_string(),
+            items: vec![OrderItem::new("Pizza", 1)],
+            status: OrderStatus::ReadyForDelivery,
+            delivery_id: None,
+            time_stamp: HlcTimestamp::default(),
+            client_position: (3.0, 2.0),
+            expected_delivery_time: 0,
+            expected_preparation_time: 0,
+            priority: OrderPriority::Standard,
+            discount: 0,
+            tenant_id: TenantId::default(),
+        };
+        let details = OfferDetails::new(&order, (0.0, 2.0), (0.0, 0.0));
+        assert_eq!(details.restaurant_position, (0.0, 0.0));
+        assert_eq!(details.pickup_distance, 2.0);
+        assert_eq!(details.dropoff_distance, 5.0);
+        assert_eq!(details.trip_distance(), 7.0);
+        assert_eq!(
+            details.payout,
+            DELIVERY_BASE_FEE_CENTS + 5 * DELIVERY_FEE_PER_BLOCK_CENTS
+        );
+    }
+
+    #[test]
+    fn cancellation_fees_grow_with_the_stage() {
+        let order = |status: OrderStatus| OrderDTO {
+            order_id: 1,
+            client_id: "client_1".to_string(),
+            restaurant_id: "restaurant_1".to_string(),
+            items: vec![OrderItem::new("Pizza", 1)],
+            status,
+            delivery_id: None,
+            time_stamp: HlcTimestamp::default(),
+            client_position: (0.0, 0.0),
+            expected_delivery_time: 0,
+            expected_preparation_time: 0,
+            priority: OrderPriority::Standard,
+            discount: 0,
+            tenant_id: TenantId::default(),
+        };
+        let price = ORDER_PRICE_CENTS;
+
+        let pending = CancellationFee::for_order(&order(OrderStatus::Pending), false);
+        assert_eq!((pending.fee, pending.refunded()), (0, price));
+        let preparing = CancellationFee::for_order(&order(OrderStatus::Preparing), false);
+        assert_eq!(
+            preparing.fee,
+            price * CANCELLATION_FEE_PREPARING_PERCENT / 100
+        );
+        let ready = CancellationFee::for_order(&order(OrderStatus::ReadyForDelivery), false);
+        assert_eq!(ready.fee, price * CANCELLATION_FEE_READY_PERCENT / 100);
+        assert_eq!(ready.fee + ready.refunded(), price);
+        let waived = CancellationFee::for_order(&order(OrderStatus::ReadyForDelivery), true);
+        assert_eq!(waived.fee, 0);
+    }
+
+    #[test]
+    fn bills_the_sum_of_the_items() {
+        let mut order = OrderDTO {
+            order_id: 1,
+            client_id: "client_1".to_string(),
+            restaurant_id: "restaurant_1".to_string(),
+            items: vec![OrderItem::new("Pizza", 2), OrderItem::new("Empanadas", 1)],
+            status: OrderStatus::Requested,
+            delivery_id: None,
+            time_stamp: HlcTimestamp::default(),
+            client_position: (0.0, 0.0),
+            expected_delivery_time: 0,
+            expected_preparation_time: 0,
+            priority: OrderPriority::Express,
+            discount: 0,
+            tenant_id: TenantId::default(),
+        };
+        assert_eq!(order.summary(), "2x Pizza, 1x Empanadas");
+        assert_eq!(
+            order.price(),
+            3 * ORDER_PRICE_CENTS + OrderPriority::Express.surcharge()
+        );
+        order.discount = order.list_price() + 1;
+        assert_eq!(order.price(), 0);
+    }
+}
@@ -0,0 +1,176 @@
+use crate::constants::HLC_ID_LOGICAL_BITS;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A timestamp of a [`HybridClock`].
+///
+/// Timestamps are ordered by their physical time first and by their logical counter
+/// second, so two changes stamped within the same millisecond, or by a process whose clock
+/// is behind, are still ordered after every change they could have seen.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct HlcTimestamp {
+    /// The highest physical time seen, in milliseconds since the Unix epoch.
+    pub physical_millis: u64,
+    /// Counter of the changes stamped with the same physical time.
+    pub logical: u32,
+}
+
+impl HlcTimestamp {
+    /// Returns a new timestamp of the clock of this process.
+    pub fn now() -> Self {
+        CLOCK.now()
+    }
+
+    /// Makes the clock of this process catch up with `remote`, a timestamp received from
+    /// another process, so that the next timestamps are ordered after it.
+    pub fn observe(remote: HlcTimestamp) {
+        CLOCK.observe(remote);
+    }
+
+    /// Packs the timestamp in a single number ordered like the timestamps, as the time prefix
+    /// of a UUIDv7: the milliseconds take the high bits and the logical counter the last
+    /// [`HLC_ID_LOGICAL_BITS`] (44 bits of milliseconds last until the year 2527).
+    pub fn as_u64(&self) -> u64 {
+        let mask = (1 << HLC_ID_LOGICAL_BITS) - 1;
+        (self.physical_millis << HLC_ID_LOGICAL_BITS) | (u64::from(self.logical) & mask)
+    }
+}
+
+impl fmt::Display for HlcTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.physical_millis, self.logical)
+    }
+}
+
+/// The clock shared by every actor of the process.
+static CLOCK: HybridClock = HybridClock::new();
+
+/// A hybrid logical clock: it follows the physical clock but never goes backwards, and it
+/// moves past every timestamp received from other processes, so the clock skew between
+/// coordinators cannot reorder causally related changes.
+#[derive(Debug, Default)]
+pub struct HybridClock {
+    /// The last timestamp given or observed.
+    last: Mutex<HlcTimestamp>,
+}
+
+impl HybridClock {
+    /// Creates a clock that has not given any timestamp yet.
+    pub const fn new() -> Self {
+        Self {
+            last: Mutex::new(HlcTimestamp {
+                physical_millis: 0,
+                logical: 0,
+            }),
+        }
+    }
+
+    /// Returns a new timestamp, later than every timestamp given or observed before.
+    pub fn now(&self) -> HlcTimestamp {
+        self.tick(physical_millis())
+    }
+
+    /// Moves the clock past `remote`.
+    ///
+    /// ## Returns
+    /// The new timestamp of the clock.
+    pub fn observe(&self, remote: HlcTimestamp) -> HlcTimestamp {
+        self.merge(remote, physical_millis())
+    }
+
+    /// Returns a new timestamp given the physical time `physical`.
+    fn tick(&self, physical: u64) -> HlcTimestamp {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        *last = if physical > last.physical_millis {
+            HlcTimestamp {
+                physical_millis: physical,
+                logical: 0,
+            }
+        } else {
+            HlcTimestamp {
+                physical_millis: last.physical_millis,
+                logical: last.logical + 1,
+            }
+        };
+        *last
+    }
+
+    /// Moves the clock past `remote` given the physical time `physical`.
+    fn merge(&self, remote: HlcTimestamp, physical: u64) -> HlcTimestamp {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        let physical_millis = physical
+            .max(last.physical_millis)
+            .max(remote.physical_millis);
+        let same_as_last = physical_millis == last.physical_millis;
+        let same_as_remote = physical_millis == remote.physical_millis;
+        let logical = match (same_as_last, same_as_remote) {
+            (true, true) => last.logical.max(remote.logical) + 1,
+            (true, false) => last.logical + 1,
+            (false, true) => remote.logical + 1,
+            (false, false) => 0,
+        };
+        *last = HlcTimestamp {
+            physical_millis,
+            logical,
+        };
+        *last
+    }
+}
+
+/// Returns the physical time, in milliseconds since the Unix epoch.
+fn physical_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(physical_millis: u64, logical: u32) -> HlcTimestamp {
+        HlcTimestamp {
+            physical_millis,
+            logical,
+        }
+    }
+
+    #[test]
+    fn never_goes_backwards() {
+        let clock = HybridClock::new();
+        assert_eq!(clock.tick(100), stamp(100, 0));
+        assert_eq!(clock.tick(100), stamp(100, 1));
+        // El reloj físico retrocede
+        assert_eq!(clock.tick(90), stamp(100, 2));
+        assert_eq!(clock.tick(120), stamp(120, 0));
+    }
+
+    #[test]
+    fn orders_local_changes_after_the_remote_ones_seen() {
+        let clock = HybridClock::new();
+        clock.tick(100);
+        // Otro coordinador tiene el reloj adelantado
+        let remote = stamp(500, 3);
+        assert_eq!(clock.merge(remote, 110), stamp(500, 4));
+        let local = clock.tick(120);
+        assert!(local > remote);
+        assert_eq!(local, stamp(500, 5));
+
+        assert_eq!(clock.merge(stamp(200, 9), 130), stamp(500, 6));
+        assert_eq!(clock.merge(stamp(50, 0), 900), stamp(900, 0));
+    }
+
+    #[test]
+    fn packed_timestamps_keep_their_order() {
+        let stamps = [stamp(100, 0), stamp(100, 1), stamp(100, 7), stamp(101, 0)];
+        for pair in stamps.windows(2) {
+            assert!(pair[0].as_u64() < pair[1].as_u64());
+        }
+        assert_eq!(stamp(0, 5).as_u64(), 5);
+        assert!(HlcTimestamp::now().as_u64() > stamp(100, 0).as_u64());
+    }
+}
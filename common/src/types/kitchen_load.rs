@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// How busy the kitchen of a restaurant is, reported with the status of its orders.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct KitchenLoad {
+    /// Number of chefs of the kitchen.
+    pub chefs: usize,
+    /// Number of chefs cooking an item.
+    pub busy_chefs: usize,
+    /// Number of items waiting for a chef.
+    pub queued_items: usize,
+}
+
+impl KitchenLoad {
+    /// Returns the percentage of the chefs that are cooking.
+    pub fn utilization_percent(&self) -> u64 {
+        if self.chefs == 0 {
+            return 0;
+        }
+        (self.busy_chefs * 100 / self.chefs) as u64
+    }
+}
+
+impl fmt::Display for KitchenLoad {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} chefs busy ({}%), {} items queued",
+            self.busy_chefs,
+            self.chefs,
+            self.utilization_percent(),
+            self.queued_items
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_the_share_of_busy_chefs() {
+        let load = KitchenLoad {
+            chefs: 4,
+            busy_chefs: 3,
+            queued_items: 2,
+        };
+        assert_eq!(load.utilization_percent(), 75);
+        assert_eq!(load.to_string(), "3/4 chefs busy (75%), 2 items queued");
+        assert_eq!(KitchenLoad::default().utilization_percent(), 0);
+    }
+}
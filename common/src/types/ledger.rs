@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// An operation recorded in the ledger of a payment gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LedgerOperation {
+    /// The payment of an order was authorized, holding its amount.
+    Authorization,
+    /// The authorization of an order was rejected.
+    Rejection,
+    /// An authorized order was billed.
+    Capture,
+    /// The authorization of a cancelled order was released.
+    Refund,
+    /// The balance of a client was topped up. It applies to no order.
+    TopUp,
+    /// A delivery was paid what it earned for an order.
+    Payout,
+}
+
+impl fmt::Display for LedgerOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operation = match self {
+            LedgerOperation::Authorization => "AUTHORIZATION",
+            LedgerOperation::Rejection => "REJECTION",
+            LedgerOperation::Capture => "CAPTURE",
+            LedgerOperation::Refund => "REFUND",
+            LedgerOperation::TopUp => "TOP UP",
+            LedgerOperation::Payout => "PAYOUT",
+        };
+        write!(f, "{}", operation)
+    }
+}
+
+/// A line of the ledger of a payment gateway.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LedgerEntry {
+    /// Position of the entry in the ledger, starting at 1.
+    pub seq: u64,
+    /// When the operation happened, in milliseconds since the Unix epoch.
+    pub timestamp_millis: u64,
+    /// The operation.
+    pub operation: LedgerOperation,
+    /// The order the operation applies to, or 0 for top-ups.
+    pub order_id: u64,
+    /// The client that placed the order, or whose balance was topped up, or the delivery
+    /// paid out.
+    pub client_id: String,
+    /// Amount of the operation, in cents. Rejections have no amount.
+    pub amount: u64,
+}
+
+/// Totals of a set of ledger entries, to reconcile them with the orders of the cluster.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct LedgerSummary {
+    /// Authorizations recorded.
+    pub authorizations: u64,
+    /// Rejections recorded.
+    pub rejections: u64,
+    /// Captures recorded.
+    pub captures: u64,
+    /// Refunds recorded.
+    pub refunds: u64,
+    /// Cents authorized.
+    pub authorized_amount: u64,
+    /// Cents billed.
+    pub captured_amount: u64,
+    /// Cents refunded.
+    pub refunded_amount: u64,
+    /// Top-ups recorded.
+    #[serde(default)]
+    pub top_ups: u64,
+    /// Cents topped up.
+    #[serde(default)]
+    pub topped_up_amount: u64,
+    /// Payouts to deliveries recorded.
+    #[serde(default)]
+    pub payouts: u64,
+    /// Cents paid out to deliveries.
+    #[serde(default)]
+    pub paid_out_amount: u64,
+}
+
+impl LedgerSummary {
+    /// Adds the totals of `entries`.
+    pub fn from_entries<'a>(entries: impl IntoIterator<Item = &'a LedgerEntry>) -> Self {
+        let mut summary = Self::default();
+        for entry in entries {
+            match entry.operation {
+                LedgerOperation::Authorization => {
+                    summary.authorizations += 1;
+                    summary.authorized_amount += entry.amount;
+                }
+                LedgerOperation::Rejection => summary.rejections += 1,
+                LedgerOperation::Capture => {
+                    summary.captures += 1;
+                    summary.captured_amount += entry.amount;
+                }
+                LedgerOperation::Refund => {
+                    summary.refunds += 1;
+                    summary.refunded_amount += entry.amount;
+                }
+                LedgerOperation::TopUp => {
+                    summary.top_ups += 1;
+                    summary.topped_up_amount += entry.amount;
+                }
+                LedgerOperation::Payout => {
+                    summary.payouts += 1;
+                    summary.paid_out_amount += entry.amount;
+                }
+            }
+        }
+        summary
+    }
+
+    /// Cents authorized that were neither billed nor refunded yet.
+    ///
+    /// Orders authorized by another gateway before a failover can be billed here, so the
+    /// result never goes below zero.
+    pub fn outstanding_amount(&self) -> u64 {
+        self.authorized_amount
+            .saturating_sub(self.captured_amount + self.refunded_amount)
+    }
+}
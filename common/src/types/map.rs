@@ -0,0 +1,178 @@
+use crate::constants::COORDINATE_SCALE;
+use rand::Rng;
+use std::fmt;
+
+/// A named rectangular district of the [`Map`], borders included.
+#[derive(Debug, Clone, PartialEq)]
+pub struct District {
+    /// Name used to pick the district, e.g. with `--district`.
+    pub name: &'static str,
+    /// Corner with the lowest coordinates.
+    pub min: (f32, f32),
+    /// Corner with the highest coordinates.
+    pub max: (f32, f32),
+}
+
+impl District {
+    /// Returns whether `point` is inside the district.
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        let (x, y) = point;
+        self.min.0 <= x && x <= self.max.0 && self.min.1 <= y && y <= self.max.1
+    }
+
+    /// Returns a random block (integer coordinates) of the district.
+    pub fn random_position(&self) -> (f32, f32) {
+        let mut rng = rand::thread_rng();
+        (
+            rng.gen_range(self.min.0..=self.max.0).round(),
+            rng.gen_range(self.min.1..=self.max.1).round(),
+        )
+    }
+}
+
+/// The city every user lives in: a grid of blocks from `(0, 0)` to `(size, size)`, split in
+/// named districts.
+///
+/// Distances between users are only meaningful if they are all inside the same map, so every
+/// position given by hand is checked against it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Map {
+    /// Number of blocks of each side of the city.
+    pub size: f32,
+    /// Districts of the city, which together cover the whole grid.
+    pub districts: Vec<District>,
+}
+
+/// Error returned when a position or a district is not in the [`Map`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MapError {
+    /// The position is outside the grid.
+    OutOfBounds { position: (f32, f32), size: f32 },
+    /// There is no district with that name.
+    UnknownDistrict(String),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapError::OutOfBounds { position, size } => write!(
+                f,
+                "position ({}, {}) is outside the map, from (0, 0) to ({}, {})",
+                position.0, position.1, size, size
+            ),
+            MapError::UnknownDistrict(name) => write!(f, "unknown district '{}'", name),
+        }
+    }
+}
+
+impl Default for Map {
+    /// The city of [`COORDINATE_SCALE`] blocks per side, with a `centro` surrounded by
+    /// `norte`, `sur`, `este` and `oeste`.
+    fn default() -> Self {
+        let size = COORDINATE_SCALE;
+        let third = (size * 0.3).round();
+        let far = size - third;
+        let district = |name, min, max| District { name, min, max };
+        Self {
+            size,
+            districts: vec![
+                district("centro", (third, third), (far, far)),
+                district("norte", (0.0, far), (size, size)),
+                district("sur", (0.0, 0.0), (size, third)),
+                district("este", (far, third), (size, far)),
+                district("oeste", (0.0, third), (third, far)),
+            ],
+        }
+    }
+}
+
+impl Map {
+    /// Returns whether `position` is inside the grid.
+    pub fn contains(&self, position: (f32, f32)) -> bool {
+        let (x, y) = position;
+        (0.0..=self.size).contains(&x) && (0.0..=self.size).contains(&y)
+    }
+
+    /// Returns `position` if it is inside the grid.
+    pub fn check(&self, position: (f32, f32)) -> Result<(f32, f32), MapError> {
+        if self.contains(position) {
+            Ok(position)
+        } else {
+            Err(MapError::OutOfBounds {
+                position,
+                size: self.size,
+            })
+        }
+    }
+
+    /// Returns the district called `name`, ignoring case.
+    pub fn district(&self, name: &str) -> Result<&District, MapError> {
+        self.districts
+            .iter()
+            .find(|district| district.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| MapError::UnknownDistrict(name.to_string()))
+    }
+
+    /// Returns the district `position` is in, if it is inside the grid.
+    pub fn district_of(&self, position: (f32, f32)) -> Option<&District> {
+        self.districts
+            .iter()
+            .find(|district| district.contains(position))
+    }
+
+    /// Returns a random block (integer coordinates) of the city.
+    pub fn random_position(&self) -> (f32, f32) {
+        let mut rng = rand::thread_rng();
+        (
+            rng.gen_range(0.0..=self.size).round(),
+            rng.gen_range(0.0..=self.size).round(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_block_of_the_city_is_in_a_district() {
+        let map = Map::default();
+        let size = map.size as u32;
+        for x in 0..=size {
+            for y in 0..=size {
+                let position = (x as f32, y as f32);
+                assert!(map.contains(position));
+                assert!(map.district_of(position).is_some(), "{:?}", position);
+            }
+        }
+    }
+
+    #[test]
+    fn random_positions_fall_inside_their_district() {
+        let map = Map::default();
+        for district in &map.districts {
+            for _ in 0..50 {
+                assert!(district.contains(district.random_position()));
+            }
+        }
+        for _ in 0..50 {
+            assert!(map.contains(map.random_position()));
+        }
+    }
+
+    #[test]
+    fn rejects_positions_and_districts_outside_the_map() {
+        let map = Map::default();
+        assert!(map.check((map.size, 0.0)).is_ok());
+        assert!(matches!(
+            map.check((map.size + 1.0, 0.0)),
+            Err(MapError::OutOfBounds { .. })
+        ));
+        assert!(map.check((-1.0, 3.0)).is_err());
+        assert_eq!(map.district("Centro").unwrap().name, "centro");
+        assert_eq!(
+            map.district("afueras"),
+            Err(MapError::UnknownDistrict("afueras".to_string()))
+        );
+    }
+}
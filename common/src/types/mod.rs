@@ -1,5 +1,21 @@
+pub mod decline_reason;
 pub mod delivery_status;
 pub mod dtos;
+pub mod hlc;
+pub mod kitchen_load;
+pub mod ledger;
+pub mod map;
+pub mod order_event;
+pub mod order_priority;
 pub mod order_status;
 pub mod payment_status;
+pub mod promotion;
+pub mod rejection_reason;
+pub mod restaurant_filter;
 pub mod restaurant_info;
+pub mod secret_hash;
+pub mod storage_entity;
+pub mod storage_violation;
+pub mod tenant;
+pub mod vehicle;
+pub mod zone;
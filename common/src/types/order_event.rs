@@ -0,0 +1,106 @@
+use crate::types::dtos::OrderDTO;
+use crate::types::hlc::HlcTimestamp;
+use crate::types::order_status::OrderStatus;
+use crate::types::rejection_reason::RejectionReason;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What happened to an order in an [`OrderEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OrderEventKind {
+    /// The authorized order was stored.
+    Created,
+    /// The status of the order changed.
+    StatusChanged,
+    /// A delivery was assigned to the order.
+    DeliveryAssigned,
+    /// The order finished and was removed from the storage.
+    Removed,
+}
+
+impl fmt::Display for OrderEventKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            OrderEventKind::Created => "created",
+            OrderEventKind::StatusChanged => "status changed",
+            OrderEventKind::DeliveryAssigned => "delivery assigned",
+            OrderEventKind::Removed => "removed",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/// An entry of the timeline of an order, recorded by the storage on every change.
+///
+/// The event is created by the coordinator that applies the change first and replicated
+/// with it, so every coordinator shows the same timeline.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderEvent {
+    /// What happened.
+    pub kind: OrderEventKind,
+    /// The status of the order after the change.
+    pub status: OrderStatus,
+    /// The delivery of the order after the change, if any.
+    pub delivery_id: Option<String>,
+    /// Who caused the change (e.g. `restaurant resto_1`).
+    pub actor: String,
+    /// ID of the coordinator that applied the change.
+    pub node: String,
+    /// When the change was applied, by the clock of the coordinator that applied it.
+    ///
+    /// Timestamps of a [`HybridClock`](crate::types::hlc::HybridClock) are used, so a
+    /// coordinator with its clock behind never stamps a change before the ones it replicated.
+    pub timestamp: HlcTimestamp,
+    /// Why the restaurant rejected the order, if the change is its rejection.
+    #[serde(default)]
+    pub reason: Option<RejectionReason>,
+}
+
+impl OrderEvent {
+    /// Creates the event of a change to `order`, applied now by the coordinator `node`.
+    ///
+    /// The status and delivery of the event are those of `order` after the change, and the
+    /// actor is inferred from them: use [`OrderEvent::with_actor`] when someone else caused it.
+    pub fn new(kind: OrderEventKind, order: &OrderDTO, node: &str) -> Self {
+        let client = format!("client {}", order.client_id);
+        let restaurant = format!("restaurant {}", order.restaurant_id);
+        let delivery = match &order.delivery_id {
+            Some(delivery_id) => format!("delivery {}", delivery_id),
+            None => "delivery".to_string(),
+        };
+        let actor = match (kind, &order.status) {
+            (OrderEventKind::Created, _) => client,
+            (OrderEventKind::DeliveryAssigned, _) => delivery,
+            (_, OrderStatus::Requested | OrderStatus::Cancelled) => client,
+            (_, OrderStatus::Authorized | OrderStatus::Unauthorized) => {
+                "payment gateway".to_string()
+            }
+            (_, OrderStatus::Delivering | OrderStatus::Delivered) => delivery,
+            (OrderEventKind::Removed, OrderStatus::OutOfStock) => restaurant,
+            // El servidor descarta pedidos que nadie puede entregar
+            (OrderEventKind::Removed, _) => "coordinator".to_string(),
+            (_, _) => restaurant,
+        };
+        Self {
+            kind,
+            status: order.status.clone(),
+            delivery_id: order.delivery_id.clone(),
+            actor,
+            node: node.to_string(),
+            timestamp: HlcTimestamp::now(),
+            reason: None,
+        }
+    }
+
+    /// Sets who caused the change.
+    pub fn with_actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = actor.into();
+        self
+    }
+
+    /// Records why the restaurant rejected the order.
+    pub fn with_reason(mut self, reason: RejectionReason) -> Self {
+        self.reason = Some(reason);
+        self
+    }
+}
@@ -0,0 +1,48 @@
+use crate::constants::{
+    DELIVERY_OFFER_TIMEOUT, EXPRESS_DELIVERY_OFFER_TIMEOUT, EXPRESS_SURCHARGE_CENTS,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Enum representing how urgent an order is, chosen by the client when placing it
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum OrderPriority {
+    /// The order is prepared and delivered in arrival order
+    #[default]
+    Standard,
+    /// The order goes ahead of the standard ones, for an extra charge
+    Express,
+}
+
+impl OrderPriority {
+    /// Returns `true` for express orders.
+    pub fn is_express(&self) -> bool {
+        *self == OrderPriority::Express
+    }
+
+    /// Returns how long the deliveries have to accept an order before it is cancelled.
+    pub fn delivery_offer_timeout(&self) -> Duration {
+        match self {
+            OrderPriority::Standard => DELIVERY_OFFER_TIMEOUT,
+            OrderPriority::Express => EXPRESS_DELIVERY_OFFER_TIMEOUT,
+        }
+    }
+
+    /// Returns the amount billed on top of the items of an order, in cents.
+    pub fn surcharge(&self) -> u64 {
+        match self {
+            OrderPriority::Standard => 0,
+            OrderPriority::Express => EXPRESS_SURCHARGE_CENTS,
+        }
+    }
+}
+
+impl fmt::Display for OrderPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OrderPriority::Standard => write!(f, "Standard"),
+            OrderPriority::Express => write!(f, "Express"),
+        }
+    }
+}
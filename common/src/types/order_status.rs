@@ -1,3 +1,4 @@
+use crate::constants::{CANCELLATION_FEE_PREPARING_PERCENT, CANCELLATION_FEE_READY_PERCENT};
 use serde::{Deserialize, Serialize};
 use std::fmt;
 
@@ -22,6 +23,80 @@ pub enum OrderStatus {
     Delivered,
     /// The order has been cancelled
     Cancelled,
+    /// The restaurant ran out of the dish and rejected the order
+    OutOfStock,
+    /// The client is outside the zones served by the restaurant
+    OutOfZone,
+    /// The promo code of the order cannot be used
+    InvalidPromoCode,
+    /// The restaurant is outside its opening hours
+    RestaurantClosed,
+    /// The restaurant belongs to another marketplace than the client
+    OtherTenant,
+}
+
+impl OrderStatus {
+    /// Returns `true` if the client can still cancel an order in this status: once it is
+    /// on its way (or finished) it can no longer be cancelled.
+    pub fn is_cancellable(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Requested
+                | OrderStatus::Authorized
+                | OrderStatus::Pending
+                | OrderStatus::Preparing
+                | OrderStatus::ReadyForDelivery
+        )
+    }
+
+    /// Returns `true` if the order finished in this status, delivered or not, and will not
+    /// change anymore.
+    pub fn is_final(&self) -> bool {
+        !self.is_cancellable() && *self != OrderStatus::Delivering
+    }
+
+    /// Returns `true` if an order in this status can move to `next`, following the life of an
+    /// order: authorized, accepted by the restaurant, prepared, picked up and delivered, and
+    /// cancelled or rejected at any point before it is on its way. Staying in the same status
+    /// is allowed, since peers send their last status again when they reconnect. A delivery
+    /// that leaves an order on its way gives it back to `ReadyForDelivery`.
+    pub fn can_become(&self, next: &OrderStatus) -> bool {
+        use OrderStatus::*;
+        if self == next {
+            return true;
+        }
+        match self {
+            Requested => matches!(
+                next,
+                Authorized
+                    | Unauthorized
+                    | Cancelled
+                    | OutOfStock
+                    | OutOfZone
+                    | InvalidPromoCode
+                    | RestaurantClosed
+                    | OtherTenant
+            ),
+            Authorized => matches!(next, Pending | Cancelled | OutOfStock | RestaurantClosed),
+            Pending => matches!(next, Preparing | Cancelled),
+            Preparing => matches!(next, ReadyForDelivery | Cancelled),
+            ReadyForDelivery => matches!(next, Delivering | Cancelled),
+            Delivering => matches!(next, Delivered | ReadyForDelivery),
+            // El resto son estados finales
+            _ => false,
+        }
+    }
+
+    /// Returns the percentage of the price kept when the client cancels an order in this
+    /// status: nothing until the kitchen starts it, and most of it once it is ready, since
+    /// the restaurant already spent the ingredients and the work.
+    pub fn cancellation_fee_percent(&self) -> u64 {
+        match self {
+            OrderStatus::Preparing => CANCELLATION_FEE_PREPARING_PERCENT,
+            OrderStatus::ReadyForDelivery => CANCELLATION_FEE_READY_PERCENT,
+            _ => 0,
+        }
+    }
 }
 
 impl fmt::Display for OrderStatus {
@@ -36,6 +111,50 @@ impl fmt::Display for OrderStatus {
             OrderStatus::Delivering => write!(f, "Delivering"),
             OrderStatus::Delivered => write!(f, "Delivered"),
             OrderStatus::Cancelled => write!(f, "Cancelled. Try again later."),
+            OrderStatus::OutOfStock => write!(f, "Out of stock"),
+            OrderStatus::OutOfZone => write!(f, "Out of the restaurant's delivery zone"),
+            OrderStatus::InvalidPromoCode => write!(f, "Invalid promo code"),
+            OrderStatus::RestaurantClosed => write!(f, "The restaurant is closed"),
+            OrderStatus::OtherTenant => {
+                write!(f, "The restaurant belongs to another marketplace")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn follows_the_life_of_an_order() {
+        let life = [
+            OrderStatus::Requested,
+            OrderStatus::Authorized,
+            OrderStatus::Pending,
+            OrderStatus::Preparing,
+            OrderStatus::ReadyForDelivery,
+            OrderStatus::Delivering,
+            OrderStatus::Delivered,
+        ];
+        for step in life.windows(2) {
+            assert!(step[0].can_become(&step[1]), "{} -> {}", step[0], step[1]);
+            assert!(!step[1].can_become(&step[0]) || step[1] == OrderStatus::Delivering);
         }
+        assert!(OrderStatus::Delivering.can_become(&OrderStatus::ReadyForDelivery));
+        assert!(OrderStatus::Preparing.can_become(&OrderStatus::Preparing));
+        assert!(
+            life.iter()
+                .all(|status| status.is_final() == (*status == OrderStatus::Delivered))
+        );
+    }
+
+    #[test]
+    fn rejects_illegal_jumps() {
+        assert!(!OrderStatus::Pending.can_become(&OrderStatus::Delivered));
+        assert!(!OrderStatus::Authorized.can_become(&OrderStatus::Delivering));
+        assert!(!OrderStatus::Delivering.can_become(&OrderStatus::Cancelled));
+        assert!(!OrderStatus::Delivered.can_become(&OrderStatus::Preparing));
+        assert!(!OrderStatus::Cancelled.can_become(&OrderStatus::Pending));
     }
 }
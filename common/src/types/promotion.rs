@@ -0,0 +1,255 @@
+use crate::types::dtos::OrderDTO;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Environment variable used to configure the promo codes of a server.
+///
+/// Its value is a `;`-separated list of `CODE=discount` entries. The discount is a
+/// percentage of the price (`10%`) or a fixed amount in cents (`500`), optionally followed by
+/// `,restaurant=<id>` to only apply it to the orders of a restaurant and by `,expires=<secs>`
+/// with the Unix time at which it stops being valid, e.g.
+/// `BIENVENIDA=10%;PIZZA5=500,restaurant=restaurant_1,expires=1893456000`.
+pub const PROMO_CODES_ENV_VAR: &str = "PROMO_CODES";
+
+/// How much a promo code takes off the price of an order.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Discount {
+    /// A percentage of the price.
+    Percent(u64),
+    /// A fixed amount, in cents.
+    Fixed(u64),
+}
+
+impl Discount {
+    /// Returns the cents taken off `price`, never more than the price itself.
+    pub fn amount(&self, price: u64) -> u64 {
+        match self {
+            Discount::Percent(percent) => price * (*percent).min(100) / 100,
+            Discount::Fixed(cents) => (*cents).min(price),
+        }
+    }
+
+    /// Parses a discount: `<percent>%` or `<cents>`.
+    fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid discount '{}'", value);
+        match value.strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<u64>() {
+                Ok(percent) if percent <= 100 => Ok(Discount::Percent(percent)),
+                _ => Err(invalid()),
+            },
+            None => value.parse().map(Discount::Fixed).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// Why a promo code was not applied to an order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum PromoRejection {
+    /// No promo code is configured with that name.
+    Unknown,
+    /// The promo code is no longer valid.
+    Expired,
+    /// The promo code only applies to the orders of another restaurant.
+    OtherRestaurant,
+    /// The client already used the promo code for another order.
+    AlreadyRedeemed,
+}
+
+impl fmt::Display for PromoRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PromoRejection::Unknown => write!(f, "unknown promo code"),
+            PromoRejection::Expired => write!(f, "expired promo code"),
+            PromoRejection::OtherRestaurant => write!(f, "promo code of another restaurant"),
+            PromoRejection::AlreadyRedeemed => write!(f, "promo code already used"),
+        }
+    }
+}
+
+/// A promo code the clients can use once to pay less for an order.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PromoCode {
+    /// The code typed by the client, in uppercase.
+    pub code: String,
+    /// How much it takes off the price.
+    pub discount: Discount,
+    /// The only restaurant whose orders it applies to, if any.
+    pub restaurant_id: Option<String>,
+    /// When it stops being valid, in seconds since the Unix epoch, if ever.
+    pub expires_at: Option<u64>,
+}
+
+impl PromoCode {
+    /// Returns the cents the code takes off `order` at `now`, in seconds since the Unix
+    /// epoch, without checking whether the client already used it.
+    pub fn discount_for(&self, order: &OrderDTO, now: u64) -> Result<u64, PromoRejection> {
+        if self.expires_at.is_some_and(|expires_at| now >= expires_at) {
+            return Err(PromoRejection::Expired);
+        }
+        if self
+            .restaurant_id
+            .as_ref()
+            .is_some_and(|restaurant_id| *restaurant_id != order.restaurant_id)
+        {
+            return Err(PromoRejection::OtherRestaurant);
+        }
+        Ok(self.discount.amount(order.list_price()))
+    }
+
+    /// Parses an entry as described in [`PROMO_CODES_ENV_VAR`].
+    fn parse(entry: &str) -> Result<Self, String> {
+        let (code, value) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("Expected CODE=discount, got '{}'", entry))?;
+        let code = code.trim().to_uppercase();
+        if code.is_empty() {
+            return Err(format!("Missing code in '{}'", entry));
+        }
+        let mut options = value.split(',').map(str::trim);
+        let discount = Discount::parse(options.next().unwrap_or_default())?;
+        let mut promo = PromoCode {
+            code,
+            discount,
+            restaurant_id: None,
+            expires_at: None,
+        };
+        for option in options {
+            match option.split_once('=') {
+                Some(("restaurant", restaurant_id)) => {
+                    promo.restaurant_id = Some(restaurant_id.trim().to_string())
+                }
+                Some(("expires", expires_at)) => {
+                    promo.expires_at = Some(
+                        expires_at
+                            .trim()
+                            .parse()
+                            .map_err(|_| format!("Invalid expiry '{}'", expires_at))?,
+                    )
+                }
+                _ => return Err(format!("Unknown option '{}' of {}", option, promo.code)),
+            }
+        }
+        Ok(promo)
+    }
+}
+
+/// The promo codes configured in a server, by code.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Promotions {
+    /// The promo codes, by code in uppercase.
+    codes: HashMap<String, PromoCode>,
+}
+
+impl Promotions {
+    /// Reads the promo codes from [`PROMO_CODES_ENV_VAR`].
+    ///
+    /// Returns no promo codes if the variable is not set or is invalid.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(PROMO_CODES_ENV_VAR) else {
+            return Self::default();
+        };
+        Self::parse(&value).unwrap_or_else(|e| {
+            eprintln!("[Promotions] Ignoring {}: {}", PROMO_CODES_ENV_VAR, e);
+            Self::default()
+        })
+    }
+
+    /// Parses the promo codes as described in [`PROMO_CODES_ENV_VAR`].
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut promotions = Self::default();
+        for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let promo = PromoCode::parse(entry)?;
+            promotions.codes.insert(promo.code.clone(), promo);
+        }
+        Ok(promotions)
+    }
+
+    /// Returns the number of promo codes.
+    pub fn len(&self) -> usize {
+        self.codes.len()
+    }
+
+    /// Returns whether no promo code is configured.
+    pub fn is_empty(&self) -> bool {
+        self.codes.is_empty()
+    }
+
+    /// Returns the promo code `code`, which is case-insensitive.
+    pub fn get(&self, code: &str) -> Option<&PromoCode> {
+        self.codes.get(&code.trim().to_uppercase())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::ORDER_PRICE_CENTS;
+    use crate::types::dtos::OrderItem;
+
+    fn order(restaurant_id: &str) -> OrderDTO {
+        OrderDTO::new(
+            1,
+            "client_1",
+            restaurant_id,
+            vec![OrderItem::new("Pizza", 2)],
+        )
+    }
+
+    #[test]
+    fn parses_the_promo_codes() {
+        let promotions = Promotions::parse(
+            "bienvenida=10%; PIZZA5=500,restaurant=restaurant_1,expires=1893456000",
+        )
+        .unwrap();
+        assert_eq!(promotions.len(), 2);
+        assert_eq!(
+            promotions.get(" Bienvenida").map(|promo| promo.discount),
+            Some(Discount::Percent(10))
+        );
+        assert_eq!(
+            promotions.get("pizza5"),
+            Some(&PromoCode {
+                code: "PIZZA5".to_string(),
+                discount: Discount::Fixed(500),
+                restaurant_id: Some("restaurant_1".to_string()),
+                expires_at: Some(1893456000),
+            })
+        );
+
+        assert!(Promotions::parse("MITAD=150%").is_err());
+        assert!(Promotions::parse("GRATIS=free").is_err());
+        assert!(Promotions::parse("PIZZA5=500,until=10").is_err());
+        assert!(Promotions::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn applies_a_promo_code_to_an_order() {
+        let promotions = Promotions::parse(
+            "MITAD=50%;TODO=100000;PIZZA5=500,restaurant=restaurant_1,expires=100",
+        )
+        .unwrap();
+        let mitad = promotions.get("MITAD").unwrap();
+        assert_eq!(
+            mitad.discount_for(&order("restaurant_2"), 1000),
+            Ok(ORDER_PRICE_CENTS)
+        );
+        // Nunca se descuenta más que el precio
+        let todo = promotions.get("TODO").unwrap();
+        assert_eq!(
+            todo.discount_for(&order("restaurant_2"), 1000),
+            Ok(2 * ORDER_PRICE_CENTS)
+        );
+
+        let pizza = promotions.get("PIZZA5").unwrap();
+        assert_eq!(pizza.discount_for(&order("restaurant_1"), 99), Ok(500));
+        assert_eq!(
+            pizza.discount_for(&order("restaurant_2"), 99),
+            Err(PromoRejection::OtherRestaurant)
+        );
+        assert_eq!(
+            pizza.discount_for(&order("restaurant_1"), 100),
+            Err(PromoRejection::Expired)
+        );
+    }
+}
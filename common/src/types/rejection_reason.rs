@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Enum representing why a restaurant rejected an authorized order
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RejectionReason {
+    /// The order arrived outside business hours
+    Closed,
+    /// The restaurant does not have the dish
+    OutOfStock,
+    /// The restaurant cannot take more orders right now
+    TooBusy,
+}
+
+impl fmt::Display for RejectionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectionReason::Closed => write!(f, "closed"),
+            RejectionReason::OutOfStock => write!(f, "out of stock"),
+            RejectionReason::TooBusy => write!(f, "too busy"),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use crate::types::restaurant_info::RestaurantInfo;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Order in which the restaurants are listed to a client.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RestaurantSort {
+    /// Closest first.
+    #[default]
+    Distance,
+    /// Alphabetically, by restaurant ID.
+    Name,
+}
+
+impl fmt::Display for RestaurantSort {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RestaurantSort::Distance => write!(f, "distance"),
+            RestaurantSort::Name => write!(f, "name"),
+        }
+    }
+}
+
+impl FromStr for RestaurantSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "distance" => Ok(RestaurantSort::Distance),
+            "name" => Ok(RestaurantSort::Name),
+            other => Err(format!(
+                "Invalid sort '{}', expected distance or name",
+                other
+            )),
+        }
+    }
+}
+
+/// Which restaurants a client wants to see, and in which order, applied by the server
+/// before answering a [`RequestNearbyRestaurants`](crate::messages::RequestNearbyRestaurants).
+///
+/// The default one lists the open restaurants, closest first.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct RestaurantFilter {
+    /// Text that the ID of the restaurants must contain, ignoring case.
+    #[serde(default)]
+    pub query: Option<String>,
+    /// Tag that the restaurants must have, ignoring case, e.g. `pizza`.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Whether the restaurants outside their opening hours are also listed.
+    #[serde(default)]
+    pub include_closed: bool,
+    /// Order of the list.
+    #[serde(default)]
+    pub sort: RestaurantSort,
+}
+
+impl RestaurantFilter {
+    /// Returns whether `restaurant` matches the query and the tag of the filter.
+    pub fn matches(&self, restaurant: &RestaurantInfo) -> bool {
+        let query = match &self.query {
+            Some(query) => restaurant
+                .id
+                .to_lowercase()
+                .contains(&query.trim().to_lowercase()),
+            None => true,
+        };
+        query && self.tag.as_ref().is_none_or(|tag| restaurant.has_tag(tag))
+    }
+
+    /// Leaves out the `restaurants` that do not match the query or the tag, and sorts the rest. Those
+    /// without a known distance go last when sorting by distance.
+    ///
+    /// ## Arguments
+    /// * `restaurants` - The restaurants to filter.
+    /// * `distances` - The distance to each restaurant, by restaurant ID.
+    pub fn apply(
+        &self,
+        mut restaurants: Vec<RestaurantInfo>,
+        distances: &HashMap<String, f32>,
+    ) -> Vec<RestaurantInfo> {
+        restaurants.retain(|restaurant| self.matches(restaurant));
+        match self.sort {
+            RestaurantSort::Distance => restaurants.sort_by(|a, b| {
+                let distance =
+                    |r: &RestaurantInfo| distances.get(&r.id).copied().unwrap_or(f32::MAX);
+                distance(a)
+                    .partial_cmp(&distance(b))
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| a.id.cmp(&b.id))
+            }),
+            RestaurantSort::Name => restaurants.sort_by(|a, b| a.id.cmp(&b.id)),
+        }
+        restaurants
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::restaurant_info::normalize_tags;
+    use crate::types::tenant::TenantId;
+
+    fn restaurant(id: &str, tags: &[&str]) -> RestaurantInfo {
+        RestaurantInfo {
+            id: id.to_string(),
+            position: (0.0, 0.0),
+            tags: normalize_tags(tags.iter().map(|tag| tag.to_string())),
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    #[test]
+    fn filters_by_name_and_tag_and_sorts_the_restaurants() {
+        let restaurants = vec![
+            restaurant("pizzeria_sur", &["Pizza"]),
+            restaurant("Pizzeria_Centro", &["pizza", "italiana", " PIZZA "]),
+            restaurant("parrilla", &["carnes"]),
+        ];
+        assert_eq!(restaurants[1].tags, ["pizza", "italiana"]);
+        let distances = HashMap::from([
+            ("pizzeria_sur".to_string(), 3.0),
+            ("Pizzeria_Centro".to_string(), 7.0),
+            ("parrilla".to_string(), 1.0),
+        ]);
+        let ids = |restaurants: Vec<RestaurantInfo>| {
+            restaurants.into_iter().map(|r| r.id).collect::<Vec<_>>()
+        };
+
+        let closest = RestaurantFilter::default();
+        assert_eq!(
+            ids(closest.apply(restaurants.clone(), &distances)),
+            ["parrilla", "pizzeria_sur", "Pizzeria_Centro"]
+        );
+
+        let pizza = RestaurantFilter {
+            query: Some("PIZZ".to_string()),
+            sort: RestaurantSort::Name,
+            ..RestaurantFilter::default()
+        };
+        assert_eq!(
+            ids(pizza.apply(restaurants.clone(), &distances)),
+            ["Pizzeria_Centro", "pizzeria_sur"]
+        );
+
+        let italian = RestaurantFilter {
+            tag: Some("Italiana".to_string()),
+            ..RestaurantFilter::default()
+        };
+        assert_eq!(
+            ids(italian.apply(restaurants, &distances)),
+            ["Pizzeria_Centro"]
+        );
+
+        assert_eq!("Name".parse(), Ok(RestaurantSort::Name));
+        assert!("rating".parse::<RestaurantSort>().is_err());
+    }
+}
@@ -1,3 +1,4 @@
+use crate::types::tenant::TenantId;
 use serde::{Deserialize, Serialize};
 
 /// Represents information about a restaurant
@@ -5,8 +6,35 @@ use serde::{Deserialize, Serialize};
 /// Components:
 /// - `id`: A unique identifier for the restaurant.
 /// - `position`: A tuple representing the restaurant's position in a 2D space
+/// - `tags`: The cuisines or categories of the restaurant (e.g. `pizza`), as given when it
+///   started, in lowercase.
+/// - `tenant_id`: The marketplace the restaurant belongs to.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RestaurantInfo {
     pub id: String,
     pub position: (f32, f32),
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub tenant_id: TenantId,
+}
+
+impl RestaurantInfo {
+    /// Returns whether the restaurant has `tag`, ignoring case.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim();
+        self.tags.iter().any(|t| t.eq_ignore_ascii_case(tag))
+    }
+}
+
+/// Returns the `tags` of a restaurant trimmed and in lowercase, without empty or repeated ones.
+pub fn normalize_tags(tags: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags {
+        let tag = tag.trim().to_lowercase();
+        if !tag.is_empty() && !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+    normalized
 }
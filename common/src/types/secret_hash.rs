@@ -0,0 +1,94 @@
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Length in bytes of the salt of each secret.
+const SALT_LEN: usize = 16;
+
+/// The secret a user ID was claimed with, kept as a salted SHA-256 hash.
+///
+/// Each user gets its own random salt, so neither the snapshots, the storage log nor its files
+/// hold the secret itself, and two users with the same secret get different hashes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "StoredSecret")]
+pub struct SecretHash {
+    /// Random salt, in hexadecimal.
+    pub salt: String,
+    /// SHA-256 of the salt followed by the secret, in hexadecimal.
+    pub hash: String,
+}
+
+impl SecretHash {
+    /// Hashes `secret` with a new random salt.
+    pub fn new(secret: &str) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        rand::thread_rng().fill_bytes(&mut salt);
+        let salt = to_hex(&salt);
+        let hash = digest(&salt, secret);
+        Self { salt, hash }
+    }
+
+    /// Returns whether `secret` is the secret that was hashed.
+    pub fn matches(&self, secret: &str) -> bool {
+        let hash = digest(&self.salt, secret);
+        // Se compara sin cortar en el primer byte distinto, para no filtrar el hash por tiempos.
+        hash.len() == self.hash.len()
+            && hash
+                .bytes()
+                .zip(self.hash.bytes())
+                .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// A secret as it may be found in a snapshot or a log: hashed, or in plain text if it was
+/// written before secrets were hashed.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredSecret {
+    Hashed { salt: String, hash: String },
+    Plain(String),
+}
+
+impl From<StoredSecret> for SecretHash {
+    fn from(stored: StoredSecret) -> Self {
+        match stored {
+            StoredSecret::Hashed { salt, hash } => Self { salt, hash },
+            StoredSecret::Plain(secret) => Self::new(&secret),
+        }
+    }
+}
+
+fn digest(salt: &str, secret: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(secret.as_bytes());
+    to_hex(&hasher.finalize())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_hashed_secret_matches() {
+        let hashed = SecretHash::new("s3cret");
+        assert!(hashed.matches("s3cret"));
+        assert!(!hashed.matches("other"));
+        assert!(!hashed.hash.contains("s3cret"));
+        assert_ne!(hashed.hash, SecretHash::new("s3cret").hash);
+    }
+
+    #[test]
+    fn plain_secrets_from_old_snapshots_are_hashed() {
+        let hashed: SecretHash = serde_json::from_str("\"s3cret\"").unwrap();
+        assert!(hashed.matches("s3cret"));
+        let json = serde_json::to_string(&hashed).unwrap();
+        assert!(!json.contains("s3cret"));
+        assert_eq!(serde_json::from_str::<SecretHash>(&json).unwrap(), hashed);
+    }
+}
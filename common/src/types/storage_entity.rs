@@ -0,0 +1,103 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// A kind of entity kept in the storage, to transfer only some of them in a snapshot.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub enum StorageEntity {
+    /// The clients.
+    Clients,
+    /// The restaurants.
+    Restaurants,
+    /// The deliveries and their earnings.
+    Deliveries,
+    /// The orders, who delivers them, their timelines, the updates not acknowledged yet by
+    /// the users and the promo codes used in them.
+    Orders,
+}
+
+impl StorageEntity {
+    /// Every kind of entity, which makes up a full snapshot.
+    pub const ALL: [StorageEntity; 4] = [
+        StorageEntity::Clients,
+        StorageEntity::Restaurants,
+        StorageEntity::Deliveries,
+        StorageEntity::Orders,
+    ];
+}
+
+/// Revision of the entries of one kind of entity in a storage: how many there are and a hash
+/// of their IDs and revisions. Two storages with the same entries have the same digest, no
+/// matter the order in which they were added, as long as both processes run the same build.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct EntityDigest {
+    /// Number of entries.
+    pub count: u64,
+    /// XOR of the hashes of the entries.
+    pub hash: u64,
+}
+
+impl EntityDigest {
+    /// Adds an entry, given by its ID and its revision (e.g. its `time_stamp`).
+    pub fn add(&mut self, entry: impl Hash) {
+        let mut hasher = DefaultHasher::new();
+        entry.hash(&mut hasher);
+        self.count += 1;
+        self.hash ^= hasher.finish();
+    }
+}
+
+/// Digest of a storage, compared between followers to find the kinds of entities in which
+/// they diverged.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub struct StorageDigest {
+    /// Index of the next log entry the storage applies. Only digests taken at the same index
+    /// can be compared.
+    pub next_log_id: u64,
+    /// Digest of each kind of entity.
+    pub entities: HashMap<StorageEntity, EntityDigest>,
+}
+
+impl StorageDigest {
+    /// Returns the kinds of entities whose digest differs from the one in `other`.
+    pub fn differing(&self, other: &StorageDigest) -> Vec<StorageEntity> {
+        StorageEntity::ALL
+            .into_iter()
+            .filter(|entity| self.entities.get(entity) != other.entities.get(entity))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_the_entities_whose_entries_differ() {
+        let digest = |orders: &[(u64, u64)]| {
+            let mut digest = StorageDigest::default();
+            for entity in StorageEntity::ALL {
+                digest.entities.insert(entity, EntityDigest::default());
+            }
+            let entry = digest.entities.entry(StorageEntity::Orders).or_default();
+            for order in orders {
+                entry.add(order);
+            }
+            digest
+        };
+        // El orden en que se agregan no cambia el digest
+        let first = digest(&[(1, 10), (2, 20)]);
+        assert_eq!(first, digest(&[(2, 20), (1, 10)]));
+        assert!(first.differing(&digest(&[(2, 20), (1, 10)])).is_empty());
+
+        // Ni una revisión distinta ni una entrada de más pasan inadvertidas
+        assert_eq!(
+            first.differing(&digest(&[(1, 10), (2, 21)])),
+            [StorageEntity::Orders]
+        );
+        assert_eq!(
+            first.differing(&digest(&[(1, 10), (2, 20), (3, 30)])),
+            [StorageEntity::Orders]
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A reference between the entries of a storage that does not hold, found when verifying
+/// its integrity.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum StorageViolation {
+    /// An order was placed by a client that is not stored.
+    MissingClient { order_id: u64, client_id: String },
+    /// An order was placed at a restaurant that is not stored.
+    MissingRestaurant {
+        order_id: u64,
+        restaurant_id: String,
+    },
+    /// An order is assigned to a delivery that is not stored.
+    MissingDelivery { order_id: u64, delivery_id: String },
+    /// A delivery accepted an order that is not stored.
+    AcceptedMissingOrder { order_id: u64, delivery_id: String },
+    /// The delivery that accepted an order is not the one assigned to it.
+    AcceptedByAnotherDelivery {
+        order_id: u64,
+        accepted_by: String,
+        assigned_to: Option<String>,
+    },
+    /// An order is both pending and authorized at its restaurant.
+    PendingAndAuthorized {
+        order_id: u64,
+        restaurant_id: String,
+    },
+}
+
+impl fmt::Display for StorageViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageViolation::MissingClient {
+                order_id,
+                client_id,
+            } => write!(
+                f,
+                "Order {} was placed by missing client {}",
+                order_id, client_id
+            ),
+            StorageViolation::MissingRestaurant {
+                order_id,
+                restaurant_id,
+            } => write!(
+                f,
+                "Order {} was placed at missing restaurant {}",
+                order_id, restaurant_id
+            ),
+            StorageViolation::MissingDelivery {
+                order_id,
+                delivery_id,
+            } => write!(
+                f,
+                "Order {} is assigned to missing delivery {}",
+                order_id, delivery_id
+            ),
+            StorageViolation::AcceptedMissingOrder {
+                order_id,
+                delivery_id,
+            } => write!(
+                f,
+                "Delivery {} accepted missing order {}",
+                delivery_id, order_id
+            ),
+            StorageViolation::AcceptedByAnotherDelivery {
+                order_id,
+                accepted_by,
+                assigned_to,
+            } => write!(
+                f,
+                "Order {} was accepted by {} but is assigned to {}",
+                order_id,
+                accepted_by,
+                assigned_to.as_deref().unwrap_or("nobody")
+            ),
+            StorageViolation::PendingAndAuthorized {
+                order_id,
+                restaurant_id,
+            } => write!(
+                f,
+                "Order {} is both pending and authorized at {}",
+                order_id, restaurant_id
+            ),
+        }
+    }
+}
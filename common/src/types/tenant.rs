@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Environment variable with the tenant a client, restaurant or delivery belongs to.
+pub const TENANT_ENV_VAR: &str = "TENANT_ID";
+
+/// Tenant of the users that do not say which one they belong to.
+pub const DEFAULT_TENANT: &str = "default";
+
+/// The marketplace a user or an order belongs to.
+///
+/// A cluster of coordinators can host several independent marketplaces: the clients only see
+/// the restaurants of their tenant, and the orders are only offered to the deliveries of the
+/// tenant of the order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TenantId(String);
+
+impl TenantId {
+    /// Creates a tenant, the default one if `id` is blank.
+    pub fn new(id: &str) -> Self {
+        let id = id.trim();
+        if id.is_empty() {
+            Self::default()
+        } else {
+            Self(id.to_string())
+        }
+    }
+
+    /// Reads the tenant from [`TENANT_ENV_VAR`], or the default one if it is not set.
+    pub fn from_env() -> Self {
+        std::env::var(TENANT_ENV_VAR)
+            .map(|id| Self::new(&id))
+            .unwrap_or_default()
+    }
+
+    /// Returns the ID of the tenant.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Default for TenantId {
+    fn default() -> Self {
+        Self(DEFAULT_TENANT.to_string())
+    }
+}
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blank_tenants_are_the_default_one() {
+        assert_eq!(TenantId::new("  "), TenantId::default());
+        assert_eq!(TenantId::new(" staging ").as_str(), "staging");
+        assert_eq!(
+            serde_json::from_str::<TenantId>("\"staging\"").unwrap(),
+            TenantId::new("staging")
+        );
+    }
+}
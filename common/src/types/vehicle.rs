@@ -0,0 +1,107 @@
+use crate::constants::{
+    BIKE_MAX_TRIP_BLOCKS, BIKE_MAX_UNITS, BIKE_SPEED_FACTOR, CAR_MAX_UNITS, CAR_SPEED_FACTOR,
+    MOTO_MAX_TRIP_BLOCKS, MOTO_MAX_UNITS,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// Vehicle a courier delivers the orders with, which sets how fast it travels, how much it
+/// carries and which trips it is best suited for.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum VehicleType {
+    /// Slow, carries little: for short trips.
+    Bike,
+    /// The couriers that do not say otherwise.
+    #[default]
+    Moto,
+    /// Fast on long trips and carries large orders.
+    Car,
+}
+
+impl VehicleType {
+    /// Returns the speed of the vehicle relative to a moto.
+    pub fn speed_factor(&self) -> f32 {
+        match self {
+            VehicleType::Bike => BIKE_SPEED_FACTOR,
+            VehicleType::Moto => 1.0,
+            VehicleType::Car => CAR_SPEED_FACTOR,
+        }
+    }
+
+    /// Returns the units of dishes the vehicle carries in each trip.
+    pub fn max_units(&self) -> u32 {
+        match self {
+            VehicleType::Bike => BIKE_MAX_UNITS,
+            VehicleType::Moto => MOTO_MAX_UNITS,
+            VehicleType::Car => CAR_MAX_UNITS,
+        }
+    }
+
+    /// Returns whether the vehicle carries an order of `units` units of dishes.
+    pub fn carries(&self, units: u32) -> bool {
+        units <= self.max_units()
+    }
+
+    /// Returns the vehicle best suited for a trip of `distance` blocks: the smallest one
+    /// that is not too slow for it.
+    pub fn for_trip(distance: f32) -> Self {
+        if distance <= BIKE_MAX_TRIP_BLOCKS {
+            VehicleType::Bike
+        } else if distance <= MOTO_MAX_TRIP_BLOCKS {
+            VehicleType::Moto
+        } else {
+            VehicleType::Car
+        }
+    }
+}
+
+impl fmt::Display for VehicleType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VehicleType::Bike => write!(f, "bike"),
+            VehicleType::Moto => write!(f, "moto"),
+            VehicleType::Car => write!(f, "car"),
+        }
+    }
+}
+
+impl FromStr for VehicleType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bike" => Ok(VehicleType::Bike),
+            "moto" => Ok(VehicleType::Moto),
+            "car" => Ok(VehicleType::Car),
+            other => Err(format!(
+                "Invalid vehicle '{}', expected bike, moto or car",
+                other
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_trip_suits_the_smallest_vehicle_fast_enough() {
+        assert_eq!(VehicleType::for_trip(3.0), VehicleType::Bike);
+        assert_eq!(
+            VehicleType::for_trip(BIKE_MAX_TRIP_BLOCKS),
+            VehicleType::Bike
+        );
+        assert_eq!(VehicleType::for_trip(10.0), VehicleType::Moto);
+        assert_eq!(VehicleType::for_trip(25.0), VehicleType::Car);
+
+        assert!(VehicleType::Bike.carries(BIKE_MAX_UNITS));
+        assert!(!VehicleType::Bike.carries(BIKE_MAX_UNITS + 1));
+        assert!(VehicleType::Car.carries(BIKE_MAX_UNITS + 1));
+
+        assert_eq!("Car".parse(), Ok(VehicleType::Car));
+        assert!("truck".parse::<VehicleType>().is_err());
+    }
+}
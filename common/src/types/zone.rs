@@ -0,0 +1,226 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Environment variable used to configure the delivery zones of a server.
+///
+/// Its value is a `;`-separated list of `key=value` entries. An entry whose value is a shape
+/// defines a zone, and any other entry assigns a restaurant or delivery ID to a `|`-separated
+/// list of zones, e.g.
+/// `centro=rect:0,0,50,50;norte=polygon:0,50 50,50 25,100;restaurant_1=centro;delivery_1=centro|norte`.
+pub const DELIVERY_ZONES_ENV_VAR: &str = "DELIVERY_ZONES";
+
+/// An area of the city served by some restaurants and deliveries.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum Zone {
+    /// A rectangle, borders included.
+    Rectangle {
+        /// Corner with the lowest coordinates.
+        min: (f32, f32),
+        /// Corner with the highest coordinates.
+        max: (f32, f32),
+    },
+    /// A simple polygon given by its vertices, in order.
+    Polygon(Vec<(f32, f32)>),
+}
+
+impl Zone {
+    /// Returns whether `point` is inside the zone.
+    pub fn contains(&self, point: (f32, f32)) -> bool {
+        let (x, y) = point;
+        match self {
+            Zone::Rectangle { min, max } => min.0 <= x && x <= max.0 && min.1 <= y && y <= max.1,
+            Zone::Polygon(vertices) => {
+                // Se cuenta cuántos lados cruza una semirrecta horizontal desde el punto
+                let mut inside = false;
+                let mut previous = vertices.len() - 1;
+                for (current, &(xi, yi)) in vertices.iter().enumerate() {
+                    let (xj, yj) = vertices[previous];
+                    if (yi > y) != (yj > y) && x < (xj - xi) * (y - yi) / (yj - yi) + xi {
+                        inside = !inside;
+                    }
+                    previous = current;
+                }
+                inside
+            }
+        }
+    }
+
+    /// Parses a shape: `rect:x1,y1,x2,y2` or `polygon:x1,y1 x2,y2 x3,y3 ...`.
+    fn parse(value: &str) -> Result<Self, String> {
+        let invalid = || format!("Invalid zone '{}'", value);
+        let parse_point = |point: &str| -> Result<(f32, f32), String> {
+            let (x, y) = point.split_once(',').ok_or_else(invalid)?;
+            Ok((
+                x.trim().parse().map_err(|_| invalid())?,
+                y.trim().parse().map_err(|_| invalid())?,
+            ))
+        };
+        if let Some(corners) = value.strip_prefix("rect:") {
+            let coordinates = corners
+                .split(',')
+                .map(|c| c.trim().parse::<f32>().map_err(|_| invalid()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let [x1, y1, x2, y2] = coordinates[..] else {
+                return Err(invalid());
+            };
+            return Ok(Zone::Rectangle {
+                min: (x1.min(x2), y1.min(y2)),
+                max: (x1.max(x2), y1.max(y2)),
+            });
+        }
+        if let Some(vertices) = value.strip_prefix("polygon:") {
+            let vertices = vertices
+                .split_whitespace()
+                .map(parse_point)
+                .collect::<Result<Vec<_>, _>>()?;
+            if vertices.len() < 3 {
+                return Err(invalid());
+            }
+            return Ok(Zone::Polygon(vertices));
+        }
+        Err(invalid())
+    }
+
+    /// Returns whether `value` describes a shape rather than a list of zones.
+    fn is_shape(value: &str) -> bool {
+        value.starts_with("rect:") || value.starts_with("polygon:")
+    }
+}
+
+/// The delivery zones of the city and the zones each restaurant and delivery serves.
+///
+/// A restaurant only takes orders from clients inside its zones, and a delivery is only
+/// offered orders whose restaurant and client are both inside one of its zones. Restaurants
+/// and deliveries without zones serve the whole city.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeliveryZones {
+    /// The zones, by name.
+    zones: HashMap<String, Zone>,
+    /// The names of the zones served by each restaurant or delivery, by its ID.
+    assignments: HashMap<String, Vec<String>>,
+}
+
+impl DeliveryZones {
+    /// Reads the zones from [`DELIVERY_ZONES_ENV_VAR`].
+    ///
+    /// Returns no zones, so that everyone serves the whole city, if the variable is not set
+    /// or is invalid.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(DELIVERY_ZONES_ENV_VAR) else {
+            return Self::default();
+        };
+        Self::parse(&value).unwrap_or_else(|e| {
+            eprintln!("[Zones] Ignoring {}: {}", DELIVERY_ZONES_ENV_VAR, e);
+            Self::default()
+        })
+    }
+
+    /// Parses the zones as described in [`DELIVERY_ZONES_ENV_VAR`].
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut zones = Self::default();
+        for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, got '{}'", entry))?;
+            let (key, value) = (key.trim().to_string(), value.trim());
+            if Zone::is_shape(value) {
+                zones.zones.insert(key, Zone::parse(value)?);
+            } else {
+                let names = value
+                    .split('|')
+                    .map(|name| name.trim().to_string())
+                    .filter(|name| !name.is_empty())
+                    .collect();
+                zones.assignments.insert(key, names);
+            }
+        }
+        // Las zonas se pueden definir después de asignarlas
+        for (id, names) in &zones.assignments {
+            if let Some(unknown) = names.iter().find(|name| !zones.zones.contains_key(*name)) {
+                return Err(format!("Unknown zone '{}' assigned to {}", unknown, id));
+            }
+        }
+        Ok(zones)
+    }
+
+    /// Returns whether any restaurant or delivery is restricted to some zones.
+    pub fn is_active(&self) -> bool {
+        !self.assignments.is_empty()
+    }
+
+    /// Returns whether the restaurant or delivery `id` serves `position`.
+    pub fn covers(&self, id: &str, position: (f32, f32)) -> bool {
+        self.assignments.get(id).is_none_or(|names| {
+            names
+                .iter()
+                .filter_map(|name| self.zones.get(name))
+                .any(|zone| zone.contains(position))
+        })
+    }
+
+    /// Returns whether the delivery `delivery_id` can take an order from `restaurant` to
+    /// `client`: one of its zones has to cover both.
+    pub fn covers_route(
+        &self,
+        delivery_id: &str,
+        restaurant: (f32, f32),
+        client: (f32, f32),
+    ) -> bool {
+        self.assignments.get(delivery_id).is_none_or(|names| {
+            names
+                .iter()
+                .filter_map(|name| self.zones.get(name))
+                .any(|zone| zone.contains(restaurant) && zone.contains(client))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tells_whether_a_point_is_inside_a_zone() {
+        let square = Zone::parse("rect:10,10,0,0").unwrap();
+        assert!(square.contains((0.0, 10.0)));
+        assert!(!square.contains((10.5, 5.0)));
+
+        // Una "L": el hueco de la esquina superior derecha queda afuera
+        let l_shape = Zone::parse("polygon:0,0 10,0 10,5 5,5 5,10 0,10").unwrap();
+        assert!(l_shape.contains((2.0, 8.0)));
+        assert!(l_shape.contains((8.0, 2.0)));
+        assert!(!l_shape.contains((8.0, 8.0)));
+        assert!(!l_shape.contains((-1.0, 2.0)));
+    }
+
+    #[test]
+    fn parses_the_zones_and_their_assignments() {
+        let zones = DeliveryZones::parse(
+            "restaurant_1=centro; centro=rect:0,0,50,50; norte=polygon:0,50 50,50 25,100; delivery_1=centro|norte",
+        )
+        .unwrap();
+        assert!(zones.is_active());
+        assert!(zones.covers("restaurant_1", (25.0, 25.0)));
+        assert!(!zones.covers("restaurant_1", (25.0, 75.0)));
+        assert!(zones.covers("delivery_1", (25.0, 75.0)));
+        // Sin zonas asignadas se atiende toda la ciudad
+        assert!(zones.covers("restaurant_2", (500.0, 500.0)));
+
+        assert!(DeliveryZones::parse("centro=rect:0,0,50").is_err());
+        assert!(DeliveryZones::parse("centro=polygon:0,0 1,1").is_err());
+        assert!(DeliveryZones::parse("restaurant_1=sur").is_err());
+        assert!(!DeliveryZones::parse("").unwrap().is_active());
+    }
+
+    #[test]
+    fn a_delivery_needs_a_zone_covering_the_whole_route() {
+        let zones = DeliveryZones::parse(
+            "oeste=rect:0,0,50,100;este=rect:50,0,100,100;delivery_1=oeste|este",
+        )
+        .unwrap();
+        assert!(zones.covers_route("delivery_1", (10.0, 10.0), (40.0, 90.0)));
+        // Cada punta en una zona distinta
+        assert!(!zones.covers_route("delivery_1", (10.0, 10.0), (90.0, 90.0)));
+        assert!(zones.covers_route("delivery_2", (10.0, 10.0), (90.0, 90.0)));
+    }
+}
@@ -1,13 +1,13 @@
-use crate::constants::COORDINATE_SCALE;
 //use crate::constants::SUCCESS_PROBABILITY;
+use crate::constants::{BASE_DELAY_MILLIS, COURIER_BLOCKS_PER_SECOND, TRAFFIC_MULTIPLIER};
+use crate::types::map::Map;
+use crate::types::vehicle::VehicleType;
 use rand::random;
 use std::io::{self, Write};
 
+/// Returns a random block of the city [`Map`].
 pub fn get_rand_f32_tuple() -> (f32, f32) {
-    (
-        (rand::random::<f32>() * COORDINATE_SCALE).round(),
-        (rand::random::<f32>() * COORDINATE_SCALE).round(),
-    )
+    Map::default().random_position()
 }
 
 pub fn random_bool_by_given_probability(probability: f32) -> bool {
@@ -21,6 +21,106 @@ pub fn calculate_distance(point1: (f32, f32), point2: (f32, f32)) -> f32 {
     dx + dy
 }
 
+/// Environment variable used to tune how fast couriers travel.
+///
+/// Its value is a `,`-separated list of `key=value` pairs, all optional:
+/// - `speed`: Blocks a courier on a moto travels per second; bikes are slower and cars faster.
+/// - `traffic`: Factor applied to the time on the streets, e.g. `1.5` for heavy traffic.
+/// - `base`: Fixed milliseconds of every trip, to pick up and hand over the order.
+///
+/// e.g. `speed=2,traffic=1.5,base=500`.
+pub const TRAVEL_MODEL_ENV_VAR: &str = "TRAVEL_MODEL";
+
+/// How long a courier takes to travel between points of the city.
+///
+/// Used both by the delivery to simulate its trips and by the client to estimate when its
+/// order arrives, so both agree.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TravelModel {
+    /// Blocks a courier travels per second.
+    pub blocks_per_second: f32,
+    /// Factor applied to the time on the streets: `1.0` is no traffic, `2.0` doubles it.
+    pub traffic: f32,
+    /// Fixed time of every trip, in milliseconds.
+    pub base_delay_millis: u64,
+}
+
+impl Default for TravelModel {
+    fn default() -> Self {
+        Self {
+            blocks_per_second: COURIER_BLOCKS_PER_SECOND,
+            traffic: TRAFFIC_MULTIPLIER,
+            base_delay_millis: BASE_DELAY_MILLIS,
+        }
+    }
+}
+
+impl TravelModel {
+    /// Reads the model from [`TRAVEL_MODEL_ENV_VAR`].
+    ///
+    /// Returns the default model if the variable is not set or is invalid.
+    pub fn from_env() -> Self {
+        let Ok(value) = std::env::var(TRAVEL_MODEL_ENV_VAR) else {
+            return Self::default();
+        };
+        Self::parse(&value).unwrap_or_else(|e| {
+            eprintln!("Ignoring {}: {}", TRAVEL_MODEL_ENV_VAR, e);
+            Self::default()
+        })
+    }
+
+    /// Parses a list of `key=value` pairs as described in [`TRAVEL_MODEL_ENV_VAR`].
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let mut model = Self::default();
+        for pair in value.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("Expected key=value, got '{}'", pair))?;
+            let invalid = || format!("Invalid value for {}: '{}'", key, value);
+            let positive = || {
+                value
+                    .trim()
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|v| v.is_finite() && *v > 0.0)
+                    .ok_or_else(invalid)
+            };
+            match key.trim() {
+                "speed" => model.blocks_per_second = positive()?,
+                "traffic" => model.traffic = positive()?,
+                "base" => model.base_delay_millis = value.trim().parse().map_err(|_| invalid())?,
+                other => return Err(format!("Unknown setting '{}'", other)),
+            }
+        }
+        Ok(model)
+    }
+
+    /// Returns the model of a courier traveling by `vehicle`: `speed` is the one of a moto,
+    /// and the other vehicles are faster or slower.
+    pub fn for_vehicle(&self, vehicle: VehicleType) -> Self {
+        Self {
+            blocks_per_second: self.blocks_per_second * vehicle.speed_factor(),
+            ..*self
+        }
+    }
+
+    /// Returns the milliseconds it takes to travel from `from` to `to`.
+    pub fn segment_millis(&self, from: (f32, f32), to: (f32, f32)) -> u64 {
+        let seconds = calculate_distance(from, to) / self.blocks_per_second * self.traffic;
+        (seconds * 1000.0).round() as u64
+    }
+
+    /// Returns the milliseconds of a trip through `stops`, in order: the base delay plus the
+    /// time of each segment.
+    pub fn trip_millis(&self, stops: &[(f32, f32)]) -> u64 {
+        self.base_delay_millis
+            + stops
+                .windows(2)
+                .map(|segment| self.segment_millis(segment[0], segment[1]))
+                .sum::<u64>()
+    }
+}
+
 pub fn print_welcome_message() {
     print!("\x1B[2J\x1B[1;1H");
     io::stdout().flush().unwrap();
@@ -31,3 +131,56 @@ pub fn print_welcome_message() {
     println!("Your fast, secure, and efficient ordering system.");
     println!("===========================================\n");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adds_the_time_of_every_segment_to_the_base_delay() {
+        let model = TravelModel {
+            blocks_per_second: 2.0,
+            traffic: 1.5,
+            base_delay_millis: 500,
+        };
+        // 4 cuadras a 2 cuadras por segundo, con tráfico: 3 segundos
+        assert_eq!(model.segment_millis((0.0, 0.0), (1.0, 3.0)), 3000);
+        assert_eq!(
+            model.trip_millis(&[(0.0, 0.0), (1.0, 3.0), (1.0, 5.0)]),
+            500 + 3000 + 1500
+        );
+        assert_eq!(model.trip_millis(&[(2.0, 2.0)]), 500);
+    }
+
+    #[test]
+    fn travels_at_the_speed_of_the_vehicle() {
+        let model = TravelModel {
+            blocks_per_second: 2.0,
+            traffic: 1.0,
+            base_delay_millis: 0,
+        };
+        let trip = |vehicle| {
+            model
+                .for_vehicle(vehicle)
+                .segment_millis((0.0, 0.0), (0.0, 6.0))
+        };
+        assert_eq!(trip(VehicleType::Moto), 3000);
+        assert!(trip(VehicleType::Bike) > trip(VehicleType::Moto));
+        assert!(trip(VehicleType::Car) < trip(VehicleType::Moto));
+    }
+
+    #[test]
+    fn parses_the_travel_model() {
+        assert_eq!(
+            TravelModel::parse("speed=2, traffic=1.5,base=500").unwrap(),
+            TravelModel {
+                blocks_per_second: 2.0,
+                traffic: 1.5,
+                base_delay_millis: 500,
+            }
+        );
+        assert_eq!(TravelModel::parse("").unwrap(), TravelModel::default());
+        assert!(TravelModel::parse("speed=0").is_err());
+        assert!(TravelModel::parse("wind=3").is_err());
+    }
+}
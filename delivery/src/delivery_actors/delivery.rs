@@ -1,38 +1,37 @@
-use actix::fut::wrap_future;
+use crate::offer::{acceptance_probability, describe_offer};
 use actix::prelude::*;
 use colored::Color;
-use common::constants::{BASE_DELAY_MILLIS, DELAY_SECONDS_TO_START_RECONNECT};
+use common::constants::EARNINGS_REPORT_TIMEOUT;
 use common::logger::Logger;
 use common::messages::delivery_messages::*;
-use common::messages::shared_messages::*;
 use common::messages::{
-    AcceptedOrder, DeliverThisOrder, DeliveryNoNeeded, LeaderIs, NetworkMessage, NewOfferToDeliver,
-    RecoverProcedure, UpdateOrderStatus, WhoIsLeader,
+    AcceptedOrder, DeliverThisOrder, DeliveryNoNeeded, Deregister, GetMyEarnings, NetworkMessage,
+    NewOfferToDeliver, RecoverProcedure, UpdateOrderStatus,
 };
 
-use common::network::communicator::Communicator;
-use common::network::connections::{connect_one, connect_some, reconnect};
-use common::network::peer_types::PeerType;
+use common::network::peer_types::{PeerIdentity, PeerType};
+use common::peer::{Peer, PeerCore};
 use common::types::delivery_status::DeliveryStatus;
-use common::types::dtos::{DeliveryDTO, OrderDTO, UserDTO};
+use common::types::dtos::{DeliveryDTO, EarningsReport, OrderDTO, UserDTO};
+use common::types::hlc::HlcTimestamp;
 use common::types::order_status::OrderStatus;
-use common::utils::calculate_distance;
+use common::types::vehicle::VehicleType;
+use common::utils::TravelModel;
 use std::net::SocketAddr;
 use std::process;
 use std::time::Duration;
-use tokio::net::TcpStream;
+use tokio::sync::oneshot;
 
 /// The `Delivery` actor represents a delivery person in the distributed restaurant ordering system.
 ///
 /// This actor is responsible for:
 /// - Registering itself with the server cluster.
-/// - Receiving and accepting delivery offers.
+/// - Receiving and accepting delivery offers, at random or asking the user.
 /// - Simulating the delivery process (including travel and delivery time).
 /// - Updating its status and reporting order delivery.
 /// - Handling recovery and reconnection scenarios.
+/// - Asking the server for what it earned, to print it at shutdown.
 pub struct Delivery {
-    /// List of server socket addresses to connect to.
-    pub servers: Vec<SocketAddr>,
     /// Unique identifier for the delivery actor.
     pub delivery_id: String,
     /// Current position of the delivery actor.
@@ -41,20 +40,22 @@ pub struct Delivery {
     pub status: DeliveryStatus,
     /// Probability of rejecting an available order.
     pub probability: f32,
+    /// Time the user has to answer each offer, or `None` to decide by `probability`.
+    pub manual_offers: Option<Duration>,
+    /// Offer waiting for the user to answer, and the timer that declines it.
+    pending_offer: Option<(OrderDTO, SpawnHandle)>,
     /// Current order being delivered, if any.
     pub current_order: Option<OrderDTO>,
-    /// Communicator for network interactions with the server.
-    pub communicator: Option<Communicator<Delivery>>,
-    /// Pending TCP stream before the actor starts.
-    pub pending_stream: Option<TcpStream>,
+    /// How long the trips of the delivery take on a moto.
+    pub travel: TravelModel,
+    /// Vehicle the delivery travels with, which makes its trips faster or slower.
+    pub vehicle: VehicleType,
+    /// Connection to the leader of the servers.
+    pub peer: PeerCore<Delivery>,
     /// Logger for delivery events.
     pub logger: Logger,
-    /// Timer handle for waiting reconnection attempts.
-    waiting_reconnection_timer: Option<actix::SpawnHandle>,
-    /// Timer handle for keeping the actor alive during reconnection attempts.
-    keep_alive_timer: Option<actix::SpawnHandle>,
-    /// Flag to indicate if the delivery is already connected and waiting for reconnection.
-    already_connected: bool,
+    /// Requests waiting for the earnings report of the server.
+    earnings_waiters: Vec<oneshot::Sender<EarningsReport>>,
 }
 
 impl Delivery {
@@ -79,79 +80,124 @@ impl Delivery {
         let logger = Logger::new(format!("Delivery {}", &delivery_id), Color::BrightGreen);
         logger.info(format!("Hello: {}!", delivery_id));
         // Intentamos conectarnos a los servidores
-        let pending_stream = connect_some(servers.clone(), PeerType::DeliveryType).await;
-
-        if pending_stream.is_none() {
-            logger.error(format!(
-                "Failed to connect to any server from the list: {:?}",
-                servers
-            ));
+        let identity = PeerIdentity::new(PeerType::DeliveryType, delivery_id.clone());
+        let Some(peer) = PeerCore::connect(servers, identity, logger.clone()).await else {
             std::process::exit(1);
-        }
+        };
 
         Self {
-            servers,
             delivery_id,
             position,
             status: DeliveryStatus::Available,
             probability,
+            manual_offers: None,
+            pending_offer: None,
             current_order: None,
-            communicator: None,
-            pending_stream,
+            travel: TravelModel::from_env(),
+            vehicle: VehicleType::default(),
+            peer,
             logger,
-            waiting_reconnection_timer: None,
-            keep_alive_timer: None,
-            already_connected: false,
+            earnings_waiters: Vec::new(),
         }
     }
 
-    /// Sends a network message to the connected server via the communicator.
-    ///
-    /// # Arguments
-    ///
-    /// * `message` - The network message to send.
-    pub fn send_network_message(&self, message: NetworkMessage) {
-        if let Some(communicator) = &self.communicator {
-            if let Some(sender) = &communicator.sender {
-                sender.do_send(message);
-            } else {
-                self.logger.error("Sender not initialized in communicator");
-            }
-        } else {
-            self.logger
-                .error("Communicator not initialized, cannot send network message");
+    /// Registers with `secret`, the secret of the identity of the delivery.
+    pub fn with_secret(mut self, secret: Option<String>) -> Self {
+        self.peer.set_secret(secret);
+        self
+    }
+
+    /// Makes the user accept or decline each offer from the console instead of deciding at
+    /// random. Offers not answered within `timeout` are declined.
+    pub fn with_manual_offers(mut self, timeout: Duration) -> Self {
+        self.manual_offers = Some(timeout);
+        self
+    }
+
+    /// Makes the trips of the delivery take the time given by `travel`.
+    pub fn with_travel_model(mut self, travel: TravelModel) -> Self {
+        self.travel = travel;
+        self
+    }
+
+    /// Makes the delivery travel by `vehicle`.
+    pub fn with_vehicle(mut self, vehicle: VehicleType) -> Self {
+        self.vehicle = vehicle;
+        self
+    }
+
+    /// Returns how long the trips of the delivery take with its vehicle.
+    fn trip_model(&self) -> TravelModel {
+        self.travel.for_vehicle(self.vehicle)
+    }
+
+    /// Tells the server the delivery takes `order`, and waits for its confirmation.
+    fn accept_offer(&mut self, order: OrderDTO) {
+        self.status = DeliveryStatus::WaitingConfirmation;
+        let my_info = DeliveryDTO {
+            delivery_id: self.delivery_id.clone(),
+            delivery_position: self.position,
+            status: self.status,
+            current_order: Some(order.clone()),
+            current_client_id: Some(order.client_id.clone()),
+            vehicle: self.vehicle,
+            tenant_id: self.peer.tenant_id().clone(),
+            time_stamp: HlcTimestamp::now(),
+        };
+        self.send_network_message(NetworkMessage::AcceptedOrder(AcceptedOrder {
+            order,
+            delivery_info: my_info,
+        }));
+    }
+
+    /// Shows the offer to the user and declines it if not answered within `timeout`.
+    fn ask_for_offer(
+        &mut self,
+        msg: NewOfferToDeliver,
+        timeout: Duration,
+        ctx: &mut Context<Self>,
+    ) {
+        if let Some((pending, _)) = &self.pending_offer {
+            self.logger.info(format!(
+                "Still waiting for an answer to order {}, declining order {}",
+                pending.order_id, msg.order.order_id
+            ));
+            return;
         }
+        println!(
+            "New offer: {}",
+            describe_offer(&msg.order, msg.details.as_ref())
+        );
+        println!("Accept? [y/n] ({}s to answer)", timeout.as_secs());
+        let order_id = msg.order.order_id;
+        let timer = ctx.run_later(timeout, move |act, _ctx| {
+            if act
+                .pending_offer
+                .take_if(|(order, _)| order.order_id == order_id)
+                .is_some()
+            {
+                println!("No answer, order {} declined", order_id);
+            }
+        });
+        self.pending_offer = Some((msg.order, timer));
     }
 
-    /// Starts the delivery logic by requesting the current leader from the server.
+    /// Sends a network message to the connected server via the communicator.
     ///
     /// # Arguments
     ///
-    /// * `_ctx` - The Actix actor context.
-    pub fn start_running(&self, _ctx: &mut Context<Self>) {
-        let actual_socket_addr = self
-            .communicator
-            .as_ref()
-            .map(|c| c.local_address)
-            .expect("Socket address not initialized");
-        self.logger.info(format!(
-            "Starting Delivery actor with ID: {} at position: {:?}",
-            self.delivery_id, self.position
-        ));
-        self.send_network_message(NetworkMessage::WhoIsLeader(WhoIsLeader {
-            origin_addr: actual_socket_addr,
-            user_id: self.delivery_id.clone(),
-        }));
+    /// * `message` - The network message to send.
+    pub fn send_network_message(&self, message: NetworkMessage) {
+        self.peer.send(message);
     }
 
-    /// Calculates the delivery delay in milliseconds based on the distance from the delivery's
-    /// current position to the restaurant and from the restaurant to the client.
+    /// Calculates the delivery delay in milliseconds with the [`TravelModel`] of the delivery
+    /// and its vehicle, from its current position to the restaurant and from the restaurant to the client.
     ///
     /// # Arguments
     ///
     /// * `restaurant_position` - The position of the restaurant.
     /// * `client_position` - The position of the client.
-    /// * `base_delay_millis` - The base delay in milliseconds.
     ///
     /// # Returns
     ///
@@ -160,80 +206,32 @@ impl Delivery {
         &self,
         restaurant_position: (f32, f32),
         client_position: (f32, f32),
-        base_delay_millis: u64,
     ) -> u64 {
-        let distance_from_restaurant = calculate_distance(self.position, restaurant_position);
-        let distance_restaurant_from_client =
-            calculate_distance(restaurant_position, client_position);
-        let total_distance = distance_from_restaurant + distance_restaurant_from_client;
-        base_delay_millis + (total_distance as u64 * 1000)
+        self.trip_model()
+            .trip_millis(&[self.position, restaurant_position, client_position])
     }
 }
 
-/// Handles [`ConnectionClosed`] messages.
-///
-/// This handler is triggered when the connection to the server is lost.
-/// It attempts to reconnect to one of the known servers. If reconnection is successful,
-/// it reinitializes the communicator and restarts the actor. If not, the actor is stopped.
-impl Handler<ConnectionClosed> for Delivery {
-    type Result = ();
+/// Registers the delivery with the leader of the servers and recovers its state.
+impl Peer for Delivery {
+    fn peer(&self) -> &PeerCore<Self> {
+        &self.peer
+    }
 
-    fn handle(&mut self, _msg: ConnectionClosed, ctx: &mut Self::Context) -> Self::Result {
-        println!("[Delivery][ConnectionClosed] Handler called");
-        println!(
-            "[Delivery][ConnectionClosed] Communicator state: {:?}",
-            self.communicator.is_some()
-        );
-        let servers = self.servers.clone();
-        println!(
-            "[Delivery][ConnectionClosed] Calling reconnect with: {:?}",
-            servers
-        );
-        let fut = async move { reconnect(servers, PeerType::DeliveryType).await };
-
-        let fut = wrap_future::<_, Self>(fut).map(|result, actor: &mut Self, ctx| {
-            println!("[Delivery][ConnectionClosed] Reconnection future finished");
-            match result {
-                Some(stream) => {
-                    println!(
-                        "[Delivery][ConnectionClosed] Reconnection successful, creating communicator"
-                    );
-                    let communicator =
-                        Communicator::new(stream, ctx.address(), PeerType::DeliveryType);
-                    actor.communicator = Some(communicator);
-
-                    actor
-                        .logger
-                        .info("Reconnected successfully. Restarting actor...");
-
-                    if let Some(handler) = actor.waiting_reconnection_timer.take() {
-                        ctx.cancel_future(handler);
-                        actor.waiting_reconnection_timer = None;
-                        println!("[Delivery][ConnectionClosed] Cancelling reconnection timer");
-                    }
-
-                    let addr = ctx.address();
-                    let handler =
-                        ctx.run_later(std::time::Duration::from_millis(100), move |_, _| {
-                            println!(
-                                "[Delivery][ConnectionClosed] Sending StartRunning after reconnection"
-                            );
-                            addr.do_send(StartRunning);
-                        });
-                    actor.waiting_reconnection_timer = Some(handler);
-                }
-                None => {
-                    println!(
-                        "[Delivery][ConnectionClosed] Failed to reconnect, stopping actor"
-                    );
-                    actor
-                        .logger
-                        .error("Failed to reconnect to any server after closed connection");
-                    ctx.stop();
-                }
-            }
-        });
-        ctx.spawn(fut);
+    fn peer_mut(&mut self) -> &mut PeerCore<Self> {
+        &mut self.peer
+    }
+
+    fn position(&self) -> (f32, f32) {
+        self.position
+    }
+
+    fn vehicle(&self) -> Option<VehicleType> {
+        Some(self.vehicle)
+    }
+
+    fn on_recovered(&mut self, user: UserDTO, ctx: &mut Context<Self>) {
+        ctx.address().do_send(RecoverProcedure { user_info: user });
     }
 }
 
@@ -248,108 +246,10 @@ impl Actor for Delivery {
     ///
     /// * `ctx` - The Actix actor context.
     fn started(&mut self, ctx: &mut Self::Context) {
-        let communicator = Communicator::new(
-            self.pending_stream
-                .take()
-                .expect("Pending stream should be set"),
-            ctx.address(),
-            PeerType::DeliveryType,
-        );
-        self.communicator = Some(communicator);
-        // Esperar 100ms antes de enviar WhoIsLeader
-        let addr = ctx.address();
-        let handler = ctx.run_later(std::time::Duration::from_millis(100), move |_, _| {
-            addr.do_send(StartRunning);
-        });
-        self.waiting_reconnection_timer = Some(handler);
+        self.start_peer(ctx);
     }
 }
 
-impl Handler<LeaderIs> for Delivery {
-    type Result = ();
-
-    fn handle(&mut self, msg: LeaderIs, ctx: &mut Self::Context) -> Self::Result {
-        // cambia esto por un logger info en ingles
-        self.logger
-            .info(format!(" Received LeaderIs message: {:?}", msg.coord_addr));
-
-        let leader_addr = msg.coord_addr;
-
-        let communicator_opt = self.communicator.as_ref().map(|c| c.peer_address);
-
-        if Some(leader_addr) == communicator_opt {
-            self.logger.info("Already connected to leader");
-            // CANCELA EL KEEP-ALIVE SI EXISTE
-            if let Some(handler) = self.keep_alive_timer.take() {
-                ctx.cancel_future(handler);
-                self.logger
-                    .info("KEEP-ALIVE CANCELLED (already connected to leader)");
-            }
-
-            let local_address = self
-                .communicator
-                .as_ref()
-                .map(|c| c.local_address)
-                .expect("Socket address not set");
-            self.send_network_message(NetworkMessage::RegisterUser(RegisterUser {
-                origin_addr: local_address,
-                user_id: self.delivery_id.clone(),
-                position: self.position,
-            }));
-            return;
-        }
-
-        if let Some(comm) = self.communicator.as_mut() {
-            comm.shutdown();
-        }
-        self.communicator = None;
-
-        let fut_connect = async move { connect_one(leader_addr, PeerType::DeliveryType).await };
-
-        let fut = wrap_future::<_, Self>(fut_connect).map(|result, actor: &mut Self, ctx| {
-            actor.logger.info("Reconnection finished");
-            match result {
-                Some(stream) => {
-                    actor
-                        .logger
-                        .info("Reconnection successful, creating communicator");
-                    let communicator =
-                        Communicator::new(stream, ctx.address(), PeerType::DeliveryType);
-                    actor.communicator = Some(communicator);
-
-                    actor
-                        .logger
-                        .info("Reconnected successfully. Restarting actor...");
-
-                    // CANCELA EL KEEP-ALIVE SI EXISTE
-                    if let Some(handler) = actor.keep_alive_timer.take() {
-                        ctx.cancel_future(handler);
-                    }
-
-                    if let Some(handler) = actor.waiting_reconnection_timer.take() {
-                        ctx.cancel_future(handler);
-                        actor.waiting_reconnection_timer = None;
-                    }
-
-                    let addr = ctx.address();
-                    let handler =
-                        ctx.run_later(std::time::Duration::from_millis(100), move |_, _| {
-                            addr.do_send(StartRunning);
-                        });
-                    actor.waiting_reconnection_timer = Some(handler);
-                }
-                None => {
-                    actor
-                        .logger
-                        .error("Failed to reconnect to leader, stopping actor");
-                    ctx.stop();
-                }
-            }
-        });
-
-        ctx.spawn(fut);
-    }
-}
 // Mensaje para pedir la dirección local
 pub struct GetLocalAddress;
 
@@ -362,16 +262,50 @@ impl Handler<GetLocalAddress> for Delivery {
     type Result = MessageResult<GetLocalAddress>;
 
     fn handle(&mut self, _msg: GetLocalAddress, _ctx: &mut Self::Context) -> Self::Result {
-        let addr = self.communicator.as_ref().map(|c| c.local_address);
+        let addr = self.peer.local_address();
         MessageResult(addr)
     }
 }
 
-impl Handler<StartRunning> for Delivery {
+/// Message to ask the server what the delivery earned.
+pub struct FetchEarnings;
+
+impl Message for FetchEarnings {
+    type Result = Result<EarningsReport, String>;
+}
+
+/// Handler for the `FetchEarnings` message.
+///
+/// Asks the server for the earnings of the delivery and waits for the answer, failing after
+/// [`EARNINGS_REPORT_TIMEOUT`].
+impl Handler<FetchEarnings> for Delivery {
+    type Result = ResponseFuture<Result<EarningsReport, String>>;
+
+    fn handle(&mut self, _msg: FetchEarnings, _ctx: &mut Self::Context) -> Self::Result {
+        let (tx, rx) = oneshot::channel();
+        self.earnings_waiters.push(tx);
+        self.send_network_message(NetworkMessage::GetMyEarnings(GetMyEarnings {
+            delivery_id: self.delivery_id.clone(),
+        }));
+        Box::pin(async move {
+            match tokio::time::timeout(EARNINGS_REPORT_TIMEOUT, rx).await {
+                Ok(Ok(report)) => Ok(report),
+                Ok(Err(_)) => Err("Delivery stopped before the answer arrived".to_string()),
+                Err(_) => Err("Timed out waiting for the earnings".to_string()),
+            }
+        })
+    }
+}
+
+/// Handler for the `Deregister` message.
+///
+/// Tells the leader the delivery leaves before shutting down, so that it gets no more offers
+/// and its orders are reassigned.
+impl Handler<Deregister> for Delivery {
     type Result = ();
 
-    fn handle(&mut self, _msg: StartRunning, ctx: &mut Self::Context) -> Self::Result {
-        self.start_running(ctx);
+    fn handle(&mut self, _msg: Deregister, _ctx: &mut Self::Context) -> Self::Result {
+        self.deregister();
     }
 }
 
@@ -383,7 +317,7 @@ impl Handler<RecoverProcedure> for Delivery {
     type Result = ();
 
     fn handle(&mut self, msg: RecoverProcedure, ctx: &mut Self::Context) -> Self::Result {
-        if self.already_connected {
+        if self.peer.already_connected {
             self.logger
                 .info("Already connected, skipping recovery procedure.");
             return;
@@ -400,7 +334,7 @@ impl Handler<RecoverProcedure> for Delivery {
                 return;
             }
         };
-        self.already_connected = true;
+        self.peer.already_connected = true;
         let order_dto = delivery_dto.current_order.clone();
 
         // Actualizar el estado del delivery con la información recuperada
@@ -443,9 +377,9 @@ impl Handler<RecoverProcedure> for Delivery {
                     self.logger
                         .info("Resuming delivery process after reconnection");
                     self.status = DeliveryStatus::Delivering;
-                    let distance_from_client =
-                        calculate_distance(self.position, order.client_position);
-                    let delay_ms = BASE_DELAY_MILLIS + (distance_from_client as u64 * 1000);
+                    let delay_ms = self
+                        .trip_model()
+                        .trip_millis(&[self.position, order.client_position]);
                     let mut order = order.clone();
                     order.status = OrderStatus::Delivering;
                     order.expected_delivery_time = delay_ms;
@@ -460,6 +394,8 @@ impl Handler<RecoverProcedure> for Delivery {
                     self.send_network_message(NetworkMessage::UpdateOrderStatus(
                         UpdateOrderStatus {
                             order: order.clone(),
+                            reason: None,
+                            kitchen_load: None,
                         },
                     ));
 
@@ -480,7 +416,8 @@ impl Handler<RecoverProcedure> for Delivery {
                     }));
                 }
             }
-            DeliveryStatus::Available => {
+            // Un delivery que se dio de baja vuelve a estar disponible al registrarse
+            DeliveryStatus::Available | DeliveryStatus::Offline => {
                 // Check if there's a current order despite being "Available"
                 // This can happen if the delivery was disconnected while delivering
                 if let Some(order) = &self.current_order {
@@ -491,9 +428,9 @@ impl Handler<RecoverProcedure> for Delivery {
 
                     // Change status to Delivering and resume the process
                     self.status = DeliveryStatus::Delivering;
-                    let distance_from_client =
-                        calculate_distance(self.position, order.client_position);
-                    let delay_ms = BASE_DELAY_MILLIS + (distance_from_client as u64 * 1000);
+                    let delay_ms = self
+                        .trip_model()
+                        .trip_millis(&[self.position, order.client_position]);
                     let mut order = order.clone();
                     order.status = OrderStatus::Delivering;
                     order.expected_delivery_time = delay_ms;
@@ -508,6 +445,8 @@ impl Handler<RecoverProcedure> for Delivery {
                     self.send_network_message(NetworkMessage::UpdateOrderStatus(
                         UpdateOrderStatus {
                             order: order.clone(),
+                            reason: None,
+                            kitchen_load: None,
                         },
                     ));
 
@@ -522,6 +461,7 @@ impl Handler<RecoverProcedure> for Delivery {
                 } else {
                     self.logger
                         .info("Delivery is available and has no current order, ready to accept new orders.");
+                    self.status = DeliveryStatus::Available;
                     self.send_network_message(NetworkMessage::IAmAvailable(IAmAvailable {
                         delivery_info: delivery_dto.clone(),
                     }));
@@ -539,11 +479,13 @@ impl Handler<RecoverProcedure> for Delivery {
 
 /// Handler for the `NewOfferToDeliver` message.
 ///
-/// Handles a new delivery offer. If available, may accept the order based on probability.
+/// Handles a new delivery offer. If available, may accept the order based on probability,
+/// lowered for offers that pay little for the whole trip, or asks the user when offers are
+/// answered manually.
 impl Handler<NewOfferToDeliver> for Delivery {
     type Result = ();
 
-    fn handle(&mut self, msg: NewOfferToDeliver, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: NewOfferToDeliver, ctx: &mut Self::Context) -> Self::Result {
         self.logger.info(format!(
             "Received NewOfferToDeliver for order ID: {}",
             msg.order.order_id
@@ -551,29 +493,21 @@ impl Handler<NewOfferToDeliver> for Delivery {
         match self.status {
             // Si estoy disponible o esperando confirmación, acepto el pedido
             DeliveryStatus::Available | DeliveryStatus::WaitingConfirmation => {
-                // Probabilidad de aceptar el pedido
-                let accept_order = rand::random::<f32>() < self.probability;
+                if let Some(timeout) = self.manual_offers {
+                    self.ask_for_offer(msg, timeout, ctx);
+                    return;
+                }
+                // Probabilidad de aceptar el pedido, según lo que paga por cuadra recorrida
+                let probability = acceptance_probability(self.probability, msg.details.as_ref());
+                let accept_order = rand::random::<f32>() < probability;
                 if !accept_order {
                     self.logger.warn(format!(
-                        "Order ID: {} rejected by delivery with probability {}",
-                        msg.order.order_id, self.probability
+                        "Order ID: {} rejected by delivery with probability {:.2}",
+                        msg.order.order_id, probability
                     ));
                     return;
                 }
-                // self.current_order = Some(msg.order.clone());
-                self.status = DeliveryStatus::WaitingConfirmation;
-                let my_info = DeliveryDTO {
-                    delivery_id: self.delivery_id.clone(),
-                    delivery_position: self.position,
-                    status: self.status,
-                    current_order: Some(msg.order.clone()),
-                    current_client_id: Some(msg.order.client_id.clone()),
-                    time_stamp: std::time::SystemTime::now(),
-                };
-                self.send_network_message(NetworkMessage::AcceptedOrder(AcceptedOrder {
-                    order: msg.order,
-                    delivery_info: my_info.clone(),
-                }));
+                self.accept_offer(msg.order);
             }
             // Si estoy en otro estado, ignoro el pedido
             _ => {
@@ -586,13 +520,64 @@ impl Handler<NewOfferToDeliver> for Delivery {
     }
 }
 
+/// Message with the answer of the user to the offer waiting for one.
+pub struct OfferDecision {
+    /// Whether the user takes the order.
+    pub accept: bool,
+}
+
+impl Message for OfferDecision {
+    type Result = ();
+}
+
+/// Handler for the `OfferDecision` message.
+///
+/// Accepts or declines the offer waiting for an answer, if the delivery can still take it.
+impl Handler<OfferDecision> for Delivery {
+    type Result = ();
+
+    fn handle(&mut self, msg: OfferDecision, ctx: &mut Self::Context) -> Self::Result {
+        let Some((order, timer)) = self.pending_offer.take() else {
+            println!("There is no offer waiting for an answer");
+            return;
+        };
+        ctx.cancel_future(timer);
+        let available = matches!(
+            self.status,
+            DeliveryStatus::Available | DeliveryStatus::WaitingConfirmation
+        );
+        if !msg.accept {
+            println!("Order {} declined", order.order_id);
+        } else if !available {
+            println!(
+                "Order {} can no longer be taken, current status: {:?}",
+                order.order_id, self.status
+            );
+        } else {
+            println!(
+                "Order {} accepted, waiting for the restaurant",
+                order.order_id
+            );
+            self.accept_offer(order);
+        }
+    }
+}
+
 /// Handler for the `DeliveryNoNeeded` message.
 ///
 /// Handles notification that a delivery is no longer needed, resetting the current order and status.
+/// If the user was still deciding on that order, the question is withdrawn.
 impl Handler<DeliveryNoNeeded> for Delivery {
     type Result = ();
 
-    fn handle(&mut self, msg: DeliveryNoNeeded, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: DeliveryNoNeeded, ctx: &mut Self::Context) -> Self::Result {
+        if let Some((_, timer)) = self
+            .pending_offer
+            .take_if(|(order, _)| order.order_id == msg.order.order_id)
+        {
+            ctx.cancel_future(timer);
+            println!("Order {} was taken by another delivery", msg.order.order_id);
+        }
         if let Some(_current_order) = &self.current_order {
             self.logger.info(format!(
                 "Received DeliveryNoNeeded for a different order ({}), ignoring",
@@ -631,11 +616,8 @@ impl Handler<DeliverThisOrder> for Delivery {
             ));
 
             // Simular el tiempo de llegada al restaurante y al cliente
-            let delay_ms = self.calcular_delay_ms(
-                msg.restaurant_info.position,
-                msg.order.client_position,
-                BASE_DELAY_MILLIS,
-            );
+            let delay_ms =
+                self.calcular_delay_ms(msg.restaurant_info.position, msg.order.client_position);
 
             self.logger.info(format!(
                 "Estimated delivery time: {:.2} seconds",
@@ -648,6 +630,8 @@ impl Handler<DeliverThisOrder> for Delivery {
 
             self.send_network_message(NetworkMessage::UpdateOrderStatus(UpdateOrderStatus {
                 order: new_order.clone(),
+                reason: None,
+                kitchen_load: None,
             }));
 
             let order = msg.order.clone();
@@ -690,7 +674,9 @@ impl Handler<OrderDelivered> for Delivery {
                     status: self.status,
                     current_order: None, // No current order after delivery
                     current_client_id: None,
-                    time_stamp: std::time::SystemTime::now(),
+                    vehicle: self.vehicle,
+                    tenant_id: self.peer.tenant_id().clone(),
+                    time_stamp: HlcTimestamp::now(),
                 };
 
                 self.send_network_message(NetworkMessage::IAmAvailable(IAmAvailable {
@@ -715,49 +701,23 @@ impl Handler<OrderDelivered> for Delivery {
 impl Handler<NetworkMessage> for Delivery {
     type Result = ();
     fn handle(&mut self, msg: NetworkMessage, ctx: &mut Self::Context) -> Self::Result {
+        let Some(msg) = self.handle_peer_message(msg, ctx) else {
+            return;
+        };
         match msg {
-            // Users messages
-            NetworkMessage::RetryLater(_msg_data) => {
-                self.logger.info("Retrying to connect in some seconds");
-            }
-            NetworkMessage::LeaderIs(msg_data) => ctx.address().do_send(msg_data),
-            NetworkMessage::RecoveredInfo(user_dto_opt) => {
-                let user_dto = user_dto_opt;
-                match user_dto {
-                    UserDTO::Delivery(delivery_dto) => {
-                        if delivery_dto.delivery_id == self.delivery_id {
-                            self.logger.info(format!(
-                                "Recovered info for Delivery ID={}, updating local state...",
-                                delivery_dto.delivery_id
-                            ));
-                            ctx.address().do_send(RecoverProcedure {
-                                user_info: UserDTO::Delivery(delivery_dto.clone()),
-                            });
-                        } else {
-                            self.logger.warn(format!(
-                                "Received recovered info for a different delivery ({}), ignoring",
-                                delivery_dto.delivery_id
-                            ));
-                        }
-                    }
-                    other => {
-                        self.logger.warn(format!(
-                            "Received recovered info of type {:?}, but I'm Delivery. Ignoring.",
-                            other
-                        ));
-                    }
-                }
-            }
-
-            NetworkMessage::NoRecoveredInfo => {
-                self.logger
-                    .warn("No recovered info available, proceeding with normal operation.");
-                self.already_connected = true;
-            }
-
             NetworkMessage::NewOfferToDeliver(msg_data) => {
                 ctx.address().do_send(msg_data);
             }
+            NetworkMessage::MyEarnings(msg_data) => {
+                self.logger.info(format!(
+                    "Earned {} cents for {} orders",
+                    msg_data.report.total(),
+                    msg_data.report.earnings.len()
+                ));
+                for waiter in self.earnings_waiters.drain(..) {
+                    let _ = waiter.send(msg_data.report.clone());
+                }
+            }
             NetworkMessage::DeliveryNoNeeded(msg_data) => {
                 self.logger.info(format!(
                     "Received DeliveryNoNeeded for order ID: {}",
@@ -772,51 +732,45 @@ impl Handler<NetworkMessage> for Delivery {
                 ));
                 ctx.address().do_send(msg_data);
             }
+            NetworkMessage::StatusRejected(msg_data) => {
+                let order = msg_data.order;
+                self.logger.warn(format!(
+                    "Server rejected status {:?} for order {}, which is {:?}",
+                    msg_data.rejected_status, order.order_id, order.status
+                ));
+                // Si el pedido ya no lo lleva este delivery, se deja y se vuelve a estar disponible
+                let still_mine = order.status == OrderStatus::Delivering
+                    && order.delivery_id.as_ref() == Some(&self.delivery_id);
+                if !still_mine
+                    && self
+                        .current_order
+                        .as_ref()
+                        .is_some_and(|current| current.order_id == order.order_id)
+                {
+                    self.current_order = None;
+                    self.status = DeliveryStatus::Available;
+                    self.send_network_message(NetworkMessage::IAmAvailable(IAmAvailable {
+                        delivery_info: DeliveryDTO {
+                            delivery_id: self.delivery_id.clone(),
+                            delivery_position: self.position,
+                            status: self.status,
+                            current_order: None,
+                            current_client_id: None,
+                            vehicle: self.vehicle,
+                            tenant_id: self.peer.tenant_id().clone(),
+                            time_stamp: HlcTimestamp::now(),
+                        },
+                    }));
+                }
+            }
             NetworkMessage::ConnectionClosed(msg_data) => {
-                println!(
-                    "[Delivery][NetworkMessage] ConnectionClosed received: {:?}",
-                    msg_data.remote_addr
-                );
-                println!("[DEBUG] Trying to reconnect to servers: {:?}", self.servers);
                 self.logger.info(format!(
                     "Connection closed with address: {}",
                     msg_data.remote_addr
                 ));
-
-                self.logger.info(format!(
-                    "Removing communicator for address: {}",
-                    msg_data.remote_addr
-                ));
-                if let Some(comm) = self.communicator.as_mut() {
-                    comm.shutdown();
-                }
-                self.communicator = None;
-                self.logger.warn("Retrying to reconnect to the server ...");
-
-                let msg_data_cloned = msg_data.clone();
-
-                // --- KEEP ALIVE TIMER ---
-                // Cancela uno anterior si existe
-                if let Some(handle) = self.keep_alive_timer.take() {
-                    ctx.cancel_future(handle);
-                }
-                // Programa un timer dummy para mantener vivo el actor
-                let keep_alive_handle = ctx.run_interval(Duration::from_secs(1), |_, _| {
-                    // No hace nada, solo mantiene vivo el actor
-                    println!("[Delivery][KeepAlive] Keeping actor alive during reconnection");
-                });
-                self.keep_alive_timer = Some(keep_alive_handle);
-
-                // --- TIMER DE RECONEXIÓN ---
-                let handle = ctx.run_later(DELAY_SECONDS_TO_START_RECONNECT, move |_, ctx| {
-                    println!("Attempting to reconnect after delay...");
-                    ctx.address().do_send(ConnectionClosed {
-                        remote_addr: msg_data_cloned.remote_addr,
-                    });
-                    println!("Reconnection attempt sent.");
-                });
-                self.waiting_reconnection_timer = Some(handle);
+                self.peer_connection_closed(ctx);
             }
+
             _ => {
                 self.logger
                     .info(format!("NetworkMessage ignored: {:?}", msg));
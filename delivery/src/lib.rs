@@ -1 +1,2 @@
 pub mod delivery_actors;
+pub mod offer;
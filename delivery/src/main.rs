@@ -1,43 +1,137 @@
 use actix::prelude::*;
-use common::constants::{
-    BASE_PORT, DELIVERY_SUCCESS_PROBABILITY, NUM_COORDINATORS, SERVER_IP_ADDRESS,
-};
-use common::utils::{get_rand_f32_tuple, print_welcome_message};
-use delivery::delivery_actors::delivery::Delivery;
-use std::env;
-use std::net::SocketAddr;
-
+use clap::Parser;
+use common::cli::UserArgs;
+use common::constants::{DELIVERY_SUCCESS_PROBABILITY, DEREGISTER_WAIT, MANUAL_OFFER_TIMEOUT};
+use common::messages::Deregister;
+use common::types::dtos::EarningsReport;
+use common::types::vehicle::VehicleType;
+use common::utils::print_welcome_message;
+use delivery::delivery_actors::delivery::{Delivery, FetchEarnings, OfferDecision};
+use delivery::offer::{dollars, parse_decision};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::signal::ctrl_c;
 
+/// Delivery of PedidosRust: takes the orders from the restaurants to the clients.
+#[derive(Parser)]
+struct Args {
+    /// ID of the delivery (same as --id)
+    #[arg(value_name = "DELIVERY_ID", conflicts_with = "id")]
+    delivery_id: Option<String>,
+    #[command(flatten)]
+    user: UserArgs,
+    /// Answer the offers from the console instead of at random
+    #[arg(long)]
+    manual: bool,
+    /// Seconds to answer each offer from the console (implies --manual)
+    #[arg(long, value_name = "SECONDS", value_parser = parse_answer_time)]
+    answer_within: Option<u64>,
+    /// Vehicle of the delivery: bike, moto or car
+    #[arg(long, value_name = "VEHICLE", default_value_t = VehicleType::default())]
+    vehicle: VehicleType,
+}
+
+impl Args {
+    /// Returns the time to answer each offer if they are answered from the console, or
+    /// `None` if they are decided at random.
+    fn manual_offers(&self) -> Option<Duration> {
+        match self.answer_within {
+            Some(seconds) => Some(Duration::from_secs(seconds)),
+            None => self.manual.then_some(MANUAL_OFFER_TIMEOUT),
+        }
+    }
+}
+
 #[actix::main]
 async fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Usage: {} <delivery_id>", args[0]);
-        std::process::exit(1);
-    }
+    let args = Args::parse();
 
-    let id = args[1].clone();
-    let servers: Vec<SocketAddr> = (0..NUM_COORDINATORS)
-        .map(|i| {
-            format!("{}:{}", SERVER_IP_ADDRESS, BASE_PORT + i)
-                .parse()
-                .expect("Invalid IP address")
-        })
-        .collect();
+    let manual_offers = args.manual_offers();
+    let identity = args.user.identity(args.delivery_id, "delivery")?;
+    let servers = args.user.servers();
+    args.user.apply_bind();
 
-    let position = get_rand_f32_tuple();
+    let position = args.user.position();
 
     print_welcome_message();
 
-    let delivery = Delivery::new(servers.clone(), id, position, DELIVERY_SUCCESS_PROBABILITY).await;
-    delivery.start();
+    let mut delivery = Delivery::new(
+        servers,
+        identity.user_id,
+        position,
+        DELIVERY_SUCCESS_PROBABILITY,
+    )
+    .await
+    .with_secret(identity.secret)
+    .with_vehicle(args.vehicle);
+    if let Some(timeout) = manual_offers {
+        delivery = delivery.with_manual_offers(timeout);
+    }
+    let delivery = delivery.start();
+    if manual_offers.is_some() {
+        actix::spawn(read_decisions(delivery.clone()));
+    }
 
     tokio::select! {
         _ = ctrl_c() => {
+            match delivery.send(FetchEarnings).await {
+                Ok(Ok(report)) => print_earnings(&report),
+                Ok(Err(e)) => eprintln!("Could not get the earnings: {}", e),
+                Err(e) => eprintln!("Could not get the earnings: {}", e),
+            }
+            // Se avisa a los servidores antes de cerrar la conexión
+            if delivery.send(Deregister).await.is_ok() {
+                tokio::time::sleep(DEREGISTER_WAIT).await;
+            }
             actix::System::current().stop();
         }
     }
 
     Ok(())
 }
+
+/// Parses the time to answer each offer, which must be positive.
+fn parse_answer_time(value: &str) -> Result<u64, String> {
+    match value.parse() {
+        Ok(0) => Err(format!("Answer time must be positive: {}", value)),
+        Ok(seconds) => Ok(seconds),
+        Err(_) => Err(format!("Invalid answer time: {}", value)),
+    }
+}
+
+/// Lee de la consola las respuestas a las ofertas y se las envía al delivery.
+async fn read_decisions(delivery: Addr<Delivery>) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_decision(&line) {
+            Some(accept) => delivery.do_send(OfferDecision { accept }),
+            None => eprintln!("Answer y to accept the offer or n to decline it"),
+        }
+    }
+}
+
+/// Prints what the delivery earned for each order, and how much of it was paid out.
+fn print_earnings(report: &EarningsReport) {
+    println!("===========================================");
+    println!("Earnings of {}", report.delivery_id);
+    for earning in &report.earnings {
+        println!(
+            "  Order {:>6}: {} (base {} + distance {}){}",
+            earning.order_id,
+            dollars(earning.total()),
+            dollars(earning.base_fee),
+            dollars(earning.distance_fee),
+            if earning.paid_out { "" } else { " - pending" }
+        );
+    }
+    println!(
+        "Total: {} for {} orders, {} paid out",
+        dollars(report.total()),
+        report.earnings.len(),
+        dollars(report.paid_out())
+    );
+    println!("===========================================");
+}
@@ -0,0 +1,129 @@
+use common::constants::DELIVERY_FEE_PER_BLOCK_CENTS;
+use common::types::dtos::{OfferDetails, OrderDTO};
+
+/// Formats an amount of cents as dollars.
+pub fn dollars(cents: u64) -> String {
+    format!("${}.{:02}", cents / 100, cents % 100)
+}
+
+/// Describes an offer to deliver `order` so the user can decide whether to take it: the
+/// restaurant, how far it is and how far the client is from it, and what the delivery pays.
+///
+/// ## Arguments
+/// * `order` - The order offered.
+/// * `details` - The distances and the payout computed by the server, if known.
+pub fn describe_offer(order: &OrderDTO, details: Option<&OfferDetails>) -> String {
+    let Some(details) = details else {
+        return format!(
+            "Order {} from {} ({}), distance unknown",
+            order.order_id,
+            order.restaurant_id,
+            order.summary()
+        );
+    };
+    format!(
+        "Order {} from {} ({}): {:.1} blocks to the restaurant, {:.1} to the client, pays {}",
+        order.order_id,
+        order.restaurant_id,
+        order.summary(),
+        details.pickup_distance,
+        details.dropoff_distance,
+        dollars(details.payout)
+    )
+}
+
+/// Returns the probability of taking an offer for a delivery that takes offers with
+/// `probability`.
+///
+/// Offers paying less than [`DELIVERY_FEE_PER_BLOCK_CENTS`] for each block of the whole trip,
+/// i.e. whose restaurant is far from the delivery, are taken less, in proportion.
+pub fn acceptance_probability(probability: f32, details: Option<&OfferDetails>) -> f32 {
+    let Some(details) = details else {
+        return probability;
+    };
+    let trip = details.trip_distance();
+    if trip <= 0.0 {
+        return probability;
+    }
+    let per_block = details.payout as f32 / trip;
+    probability * (per_block / DELIVERY_FEE_PER_BLOCK_CENTS as f32).min(1.0)
+}
+
+/// Parses the answer typed to an offer.
+///
+/// ## Returns
+/// `Some(true)` to accept, `Some(false)` to decline, or `None` if it is not an answer.
+pub fn parse_decision(line: &str) -> Option<bool> {
+    match line.trim().to_ascii_lowercase().as_str() {
+        "y" | "yes" | "accept" => Some(true),
+        "n" | "no" | "decline" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::constants::DELIVERY_BASE_FEE_CENTS;
+    use common::types::dtos::OrderItem;
+    use common::types::hlc::HlcTimestamp;
+    use common::types::order_priority::OrderPriority;
+    use common::types::order_status::OrderStatus;
+    use common::types::tenant::TenantId;
+
+    #[test]
+    fn describes_the_distances_and_the_payout() {
+        let order = OrderDTO {
+            order_id: 5,
+            client_id: "client_1".to_string(),
+            restaurant_id: "resto_1".to_string(),
+            items: vec![OrderItem::new("Pizza", 1)],
+            status: OrderStatus::ReadyForDelivery,
+            delivery_id: None,
+            time_stamp: HlcTimestamp::default(),
+            client_position: (3.0, 2.0),
+            expected_delivery_time: 0,
+            expected_preparation_time: 0,
+            priority: OrderPriority::Standard,
+            discount: 0,
+            tenant_id: TenantId::default(),
+        };
+        let details = OfferDetails::new(&order, (0.0, 2.0), (0.0, 0.0));
+        let payout = dollars(DELIVERY_BASE_FEE_CENTS + 5 * DELIVERY_FEE_PER_BLOCK_CENTS);
+        assert_eq!(
+            describe_offer(&order, Some(&details)),
+            format!(
+                "Order 5 from resto_1 (1x Pizza): 2.0 blocks to the restaurant, 5.0 to the client, pays {}",
+                payout
+            )
+        );
+        assert_eq!(
+            describe_offer(&order, None),
+            "Order 5 from resto_1 (1x Pizza), distance unknown"
+        );
+    }
+
+    #[test]
+    fn far_restaurants_are_taken_less() {
+        let details = |pickup_distance: f32, dropoff_distance: f32| OfferDetails {
+            restaurant_position: (0.0, 0.0),
+            pickup_distance,
+            dropoff_distance,
+            payout: DELIVERY_BASE_FEE_CENTS
+                + dropoff_distance as u64 * DELIVERY_FEE_PER_BLOCK_CENTS,
+        };
+        assert_eq!(acceptance_probability(0.8, None), 0.8);
+        assert_eq!(acceptance_probability(0.8, Some(&details(1.0, 4.0))), 0.8);
+        let far = acceptance_probability(0.8, Some(&details(16.0, 2.0)));
+        assert!(far < 0.8 && far > 0.0, "{}", far);
+        let farther = acceptance_probability(0.8, Some(&details(20.0, 2.0)));
+        assert!(farther < far);
+    }
+
+    #[test]
+    fn parses_the_answers() {
+        assert_eq!(parse_decision(" Y "), Some(true));
+        assert_eq!(parse_decision("decline"), Some(false));
+        assert_eq!(parse_decision("maybe"), None);
+    }
+}
@@ -0,0 +1,404 @@
+use crate::http;
+use common::constants::{
+    DASHBOARD_PORT_OFFSET, NUM_COORDINATORS, SERVER_IP_ADDRESS, WS_NOTIFICATIONS_PORT_OFFSET,
+};
+use common::identity::IDENTITY_DIR_ENV_VAR;
+use common::network::connections::BASE_PORT_ENV_VAR;
+use common::types::dtos::OrderDTO;
+use common::types::order_event::OrderEvent;
+use common::types::order_status::OrderStatus;
+use serde_json::json;
+use server::dashboard::DASHBOARD_ENV_VAR;
+use server::server_actors::coordinator_manager::WRITE_ACK_ENV_VAR;
+use server::server_actors::services::orders_services::PAYMENT_GATEWAYS_ENV_VAR;
+use std::fs::{self, File};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Binaries launched by a deployment.
+const BINARIES: [&str; 5] = ["server", "payment", "restaurant", "delivery", "api"];
+/// Time given to each coordinator to start before the next one.
+const STARTUP_STEP: Duration = Duration::from_secs(1);
+/// Time between two checks while waiting for a condition.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// Lowest port a deployment may start on.
+const MIN_BASE_PORT: u16 = 20000;
+/// How many ports past [`MIN_BASE_PORT`] a deployment may start on.
+const BASE_PORT_RANGE: u16 = 20000;
+/// Attempts to find a free range of ports before giving up.
+const PORT_ATTEMPTS: u32 = 50;
+/// Position of the clients placing orders, in the middle of the city.
+const CLIENT_POSITION: (f32, f32) = (5.0, 5.0);
+
+/// Size of a deployment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentConfig {
+    /// Number of coordinators, at most [`NUM_COORDINATORS`].
+    pub coordinators: u16,
+    /// Number of restaurants. They accept every order.
+    pub restaurants: usize,
+    /// Number of deliveries.
+    pub deliveries: usize,
+}
+
+impl Default for DeploymentConfig {
+    fn default() -> Self {
+        Self {
+            coordinators: NUM_COORDINATORS,
+            restaurants: 1,
+            deliveries: 2,
+        }
+    }
+}
+
+/// A child process of a deployment, killed when dropped.
+pub struct Process {
+    /// Name of the process, also used for its log file.
+    name: String,
+    /// The running binary.
+    child: Child,
+}
+
+impl Process {
+    /// Kills the process, as if it had crashed.
+    pub fn kill(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+    /// Returns whether the process is still running.
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        if self.is_running() {
+            self.kill();
+        }
+    }
+}
+
+/// Builds the binaries of the workspace once per test run, with the profile of the tests.
+fn build_binaries() {
+    static BUILT: OnceLock<()> = OnceLock::new();
+    BUILT.get_or_init(|| {
+        let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+        let mut command = Command::new(cargo);
+        command.arg("build").arg("--bins");
+        for binary in BINARIES {
+            command.arg("--package").arg(binary);
+        }
+        if !cfg!(debug_assertions) {
+            command.arg("--release");
+        }
+        let status = command.status().expect("Failed to run cargo build");
+        assert!(status.success(), "Failed to build the binaries");
+    });
+}
+
+/// Returns the path of a binary of the workspace, next to the directory of the test binary.
+fn binary_path(name: &str) -> PathBuf {
+    let test_binary = std::env::current_exe().expect("Failed to get the test binary");
+    // target/<profile>/deps/<test> -> target/<profile>/<name>
+    let profile_dir = test_binary
+        .parent()
+        .and_then(Path::parent)
+        .expect("Unexpected location of the test binary");
+    profile_dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX))
+}
+
+/// Returns whether a local port can be listened on.
+fn is_free(port: u16) -> bool {
+    TcpListener::bind((SERVER_IP_ADDRESS, port)).is_ok()
+}
+
+/// Picks a base port such that every port used by the deployment is free.
+///
+/// Ports are tried from a different place on each run, so that deployments of tests running
+/// at the same time do not collide.
+fn pick_base_port(coordinators: u16) -> u16 {
+    let seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos())
+        .wrapping_add(std::process::id().wrapping_mul(7919));
+    for attempt in 0..PORT_ATTEMPTS {
+        let offset = seed.wrapping_add(attempt.wrapping_mul(104729)) % u32::from(BASE_PORT_RANGE);
+        let base_port = MIN_BASE_PORT + offset as u16;
+        let mut ports = (0..coordinators).flat_map(|i| {
+            let port = base_port + i;
+            [
+                port,
+                port + WS_NOTIFICATIONS_PORT_OFFSET,
+                port + DASHBOARD_PORT_OFFSET,
+            ]
+        });
+        // El gateway de pagos y la API usan los dos puertos siguientes al último coordinador
+        let mut extra = [
+            base_port + NUM_COORDINATORS + 1,
+            base_port + NUM_COORDINATORS + 2,
+        ]
+        .into_iter();
+        if ports.all(is_free) && extra.all(is_free) {
+            return base_port;
+        }
+    }
+    panic!("No free range of ports found for the deployment");
+}
+
+/// A cluster running as separate processes of the real binaries, over real TCP connections.
+///
+/// The coordinators listen on ports picked at random, passed to every process through
+/// [`BASE_PORT_ENV_VAR`]. The output of each process is written to a file in
+/// [`Deployment::log_dir`], to inspect a failed scenario. Every process is killed when the
+/// deployment is dropped.
+pub struct Deployment {
+    /// Port of the first coordinator.
+    base_port: u16,
+    /// The coordinator processes, by index, or `None` once killed.
+    servers: Vec<Option<Process>>,
+    /// The payment gateway, restaurants, deliveries and API processes.
+    peers: Vec<Process>,
+    /// IDs of the restaurants, by index.
+    restaurant_ids: Vec<String>,
+    /// Directory with the output of every process.
+    log_dir: PathBuf,
+}
+
+impl Deployment {
+    /// Builds the binaries and starts the payment gateway and the coordinators, waits for a
+    /// leader, and then starts the restaurants, deliveries and the REST API.
+    ///
+    /// ## Panics
+    /// If the binaries cannot be built or started, or no leader is elected.
+    pub fn start(config: DeploymentConfig) -> Self {
+        assert!(
+            (1..=NUM_COORDINATORS).contains(&config.coordinators),
+            "A deployment needs between 1 and {} coordinators",
+            NUM_COORDINATORS
+        );
+        build_binaries();
+        let base_port = pick_base_port(config.coordinators);
+        let log_dir = std::env::temp_dir().join(format!("pedidos_rust_{}", base_port));
+        fs::create_dir_all(&log_dir).expect("Failed to create the log directory");
+        println!("Deployment on port {}, logs in {:?}", base_port, log_dir);
+
+        let mut deployment = Self {
+            base_port,
+            servers: Vec::new(),
+            peers: Vec::new(),
+            restaurant_ids: Vec::new(),
+            log_dir,
+        };
+
+        let payment_port = deployment.payment_port().to_string();
+        let payment = deployment.spawn("payment", "payment", &[&payment_port]);
+        deployment.peers.push(payment);
+        thread::sleep(STARTUP_STEP);
+
+        for i in 0..config.coordinators {
+            let port = (base_port + i).to_string();
+            let server = deployment.spawn(&format!("server_{}", i), "server", &[&port]);
+            deployment.servers.push(Some(server));
+            thread::sleep(STARTUP_STEP);
+        }
+        deployment
+            .wait_for_leader(Duration::from_secs(60))
+            .expect("No leader was elected");
+
+        for i in 0..config.restaurants {
+            let id = format!("it_restaurant_{}", i);
+            let restaurant = deployment.spawn(
+                &id,
+                "restaurant",
+                &[&id, "--probability", "1.0", "--cook-time", "Pizza=1"],
+            );
+            deployment.peers.push(restaurant);
+            deployment.restaurant_ids.push(id);
+        }
+        for i in 0..config.deliveries {
+            let id = format!("it_delivery_{}", i);
+            let delivery = deployment.spawn(&id, "delivery", &[&id]);
+            deployment.peers.push(delivery);
+        }
+        let api_port = deployment.api_port().to_string();
+        let api = deployment.spawn("api", "api", &[&api_port]);
+        deployment.peers.push(api);
+        deployment.wait_for_api(Duration::from_secs(30));
+        deployment
+    }
+
+    /// Starts a binary with the ports of this deployment, writing its output to its log file.
+    fn spawn(&self, name: &str, binary: &str, args: &[&str]) -> Process {
+        let log = File::create(self.log_dir.join(format!("{}.log", name)))
+            .expect("Failed to create the log file");
+        let child = Command::new(binary_path(binary))
+            .args(args)
+            .env(BASE_PORT_ENV_VAR, self.base_port.to_string())
+            .env(
+                PAYMENT_GATEWAYS_ENV_VAR,
+                format!("{}:{}", SERVER_IP_ADDRESS, self.payment_port()),
+            )
+            .env(DASHBOARD_ENV_VAR, "1")
+            // Cada cambio confirmado de un pedido ya está en la mayoría de los coordinadores,
+            // así sobrevive a que maten al líder
+            .env(WRITE_ACK_ENV_VAR, "quorum")
+            .env(IDENTITY_DIR_ENV_VAR, self.log_dir.join("identities"))
+            // La entrada queda abierta: los restaurantes leen comandos de ella
+            .stdin(Stdio::piped())
+            .stdout(log.try_clone().expect("Failed to open the log file"))
+            .stderr(log)
+            .spawn()
+            .unwrap_or_else(|e| panic!("Failed to start {}: {}", binary, e));
+        Process {
+            name: name.to_string(),
+            child,
+        }
+    }
+
+    /// Returns the directory with the output of every process.
+    pub fn log_dir(&self) -> &Path {
+        &self.log_dir
+    }
+
+    /// Returns the port of the payment gateway.
+    pub fn payment_port(&self) -> u16 {
+        self.base_port + NUM_COORDINATORS + 1
+    }
+
+    /// Returns the port of the REST API.
+    pub fn api_port(&self) -> u16 {
+        self.payment_port() + 1
+    }
+
+    /// Returns the ID of a restaurant, by index.
+    pub fn restaurant_id(&self, restaurant: usize) -> &str {
+        &self.restaurant_ids[restaurant]
+    }
+
+    /// Returns the index of the coordinator serving the dashboard, which only the leader does.
+    pub fn leader(&mut self) -> Option<usize> {
+        let base_port = self.base_port;
+        for (i, server) in self.servers.iter_mut().enumerate() {
+            if !server.as_mut().is_some_and(Process::is_running) {
+                continue;
+            }
+            let port = base_port + i as u16 + DASHBOARD_PORT_OFFSET;
+            if http::get(port, "/api/snapshot").is_some_and(|r| r.is_success()) {
+                return Some(i);
+            }
+        }
+        None
+    }
+
+    /// Waits until a coordinator still running serves the dashboard as leader.
+    ///
+    /// ## Returns
+    /// The index of the leader, or `None` if there was none within `timeout`.
+    pub fn wait_for_leader(&mut self, timeout: Duration) -> Option<usize> {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if let Some(leader) = self.leader() {
+                return Some(leader);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        None
+    }
+
+    /// Waits until the REST API answers.
+    fn wait_for_api(&self, timeout: Duration) {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if http::get(self.api_port(), "/orders/0").is_some() {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        panic!(
+            "The REST API did not start, see the logs in {:?}",
+            self.log_dir
+        );
+    }
+
+    /// Kills the coordinator at index `server`, as if its process had crashed.
+    pub fn kill_server(&mut self, server: usize) {
+        if let Some(mut process) = self.servers[server].take() {
+            println!("Killing {}", process.name);
+            process.kill();
+        }
+    }
+
+    /// Places an order through the REST API.
+    ///
+    /// ## Returns
+    /// The order, or why it was not accepted.
+    pub fn place_order(
+        &self,
+        client_id: &str,
+        restaurant: usize,
+        dish_name: &str,
+    ) -> Result<OrderDTO, String> {
+        let body = json!({
+            "client_id": client_id,
+            "position": CLIENT_POSITION,
+            "restaurant_id": self.restaurant_id(restaurant),
+            "items": [{ "dish": dish_name, "quantity": 1 }],
+        });
+        let response = http::post_json(self.api_port(), "/orders", &body)
+            .ok_or("The REST API did not answer")?;
+        if !response.is_success() {
+            return Err(format!("{}: {}", response.status, response.body));
+        }
+        serde_json::from_str(&response.body).map_err(|e| e.to_string())
+    }
+
+    /// Returns the last status of an order as the REST API reports it to its client.
+    pub fn order_status(&self, order: &OrderDTO) -> Option<OrderStatus> {
+        let path = format!("/orders/{}?client_id={}", order.order_id, order.client_id);
+        let response = http::get(self.api_port(), &path)?;
+        if !response.is_success() {
+            return None;
+        }
+        serde_json::from_str::<OrderDTO>(&response.body)
+            .ok()
+            .map(|order| order.status)
+    }
+
+    /// Waits until the REST API sees the order in `status`.
+    ///
+    /// ## Returns
+    /// `false` if the order did not reach `status` within `timeout`.
+    pub fn wait_for_status(
+        &self,
+        order: &OrderDTO,
+        status: OrderStatus,
+        timeout: Duration,
+    ) -> bool {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            if self.order_status(order).as_ref() == Some(&status) {
+                return true;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+        false
+    }
+
+    /// Returns the timeline of an order, as served by the dashboard of the leader.
+    pub fn order_timeline(&mut self, order_id: u64) -> Vec<OrderEvent> {
+        let Some(leader) = self.leader() else {
+            return Vec::new();
+        };
+        let port = self.base_port + leader as u16 + DASHBOARD_PORT_OFFSET;
+        http::get(port, &format!("/api/orders/{}/timeline", order_id))
+            .filter(|response| response.is_success())
+            .and_then(|response| serde_json::from_str(&response.body).ok())
+            .unwrap_or_default()
+    }
+}
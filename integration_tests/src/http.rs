@@ -0,0 +1,73 @@
+use common::constants::SERVER_IP_ADDRESS;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+/// Time allowed to connect, send a request and read its response.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The status code and body of an HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    /// The status code (e.g. `200`).
+    pub status: u16,
+    /// The body, as text.
+    pub body: String,
+}
+
+impl Response {
+    /// Returns whether the status code is a `2xx`.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Sends a `GET` request to a local HTTP server.
+///
+/// ## Returns
+/// The response, or `None` if nothing is listening on `port` or the response is invalid.
+pub fn get(port: u16, path: &str) -> Option<Response> {
+    request(port, "GET", path, None)
+}
+
+/// Sends a `POST` request with a JSON body to a local HTTP server.
+///
+/// ## Returns
+/// The response, or `None` if nothing is listening on `port` or the response is invalid.
+pub fn post_json(port: u16, path: &str, body: &serde_json::Value) -> Option<Response> {
+    request(port, "POST", path, Some(body.to_string()))
+}
+
+/// Sends a request over a new connection, closed by the server once it responds.
+fn request(port: u16, method: &str, path: &str, body: Option<String>) -> Option<Response> {
+    let addr = format!("{}:{}", SERVER_IP_ADDRESS, port).parse().ok()?;
+    let mut stream = TcpStream::connect_timeout(&addr, HTTP_TIMEOUT).ok()?;
+    stream.set_read_timeout(Some(HTTP_TIMEOUT)).ok()?;
+    stream.set_write_timeout(Some(HTTP_TIMEOUT)).ok()?;
+
+    let mut request = format!(
+        "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n",
+        method, path, addr
+    );
+    if let Some(body) = &body {
+        request.push_str("Content-Type: application/json\r\n");
+        request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    request.push_str("\r\n");
+    request.push_str(body.as_deref().unwrap_or_default());
+    stream.write_all(request.as_bytes()).ok()?;
+
+    let mut raw = Vec::new();
+    stream.read_to_end(&mut raw).ok()?;
+    parse_response(&String::from_utf8_lossy(&raw))
+}
+
+/// Parses the status line and body of a response with a `Content-Length`.
+fn parse_response(raw: &str) -> Option<Response> {
+    let (head, body) = raw.split_once("\r\n\r\n")?;
+    let status = head.split_whitespace().nth(1)?.parse().ok()?;
+    Some(Response {
+        status,
+        body: body.to_string(),
+    })
+}
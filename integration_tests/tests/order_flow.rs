@@ -0,0 +1,42 @@
+use common::types::order_status::OrderStatus;
+use integration_tests::deployment::{Deployment, DeploymentConfig};
+use std::time::Duration;
+
+#[test]
+#[ignore = "spawns the real binaries, run with `cargo test -- --ignored`"]
+fn order_is_delivered_after_the_leader_process_is_killed() {
+    let mut deployment = Deployment::start(DeploymentConfig::default());
+    let leader = deployment
+        .wait_for_leader(Duration::from_secs(30))
+        .expect("No leader was elected");
+
+    let order = deployment
+        .place_order("it_client_0", 0, "Pizza")
+        .expect("The order was not placed");
+    // Los seguidores replican el log cada pocos segundos: mientras el pedido viaja, el que
+    // quede como líder ya lo conoce
+    assert!(
+        deployment.wait_for_status(&order, OrderStatus::Delivering, Duration::from_secs(60)),
+        "The order did not leave the restaurant, see the logs in {:?}",
+        deployment.log_dir()
+    );
+    deployment.kill_server(leader);
+    let new_leader = deployment
+        .wait_for_leader(Duration::from_secs(60))
+        .expect("No leader was elected after the failure");
+    assert_ne!(new_leader, leader);
+
+    assert!(
+        deployment.wait_for_status(&order, OrderStatus::Delivered, Duration::from_secs(180)),
+        "The order was not delivered, see the logs in {:?}",
+        deployment.log_dir()
+    );
+    let timeline = deployment.order_timeline(order.order_id);
+    assert!(
+        timeline
+            .iter()
+            .any(|event| event.status == OrderStatus::Delivered),
+        "The leader did not record the delivery: {:?}",
+        timeline
+    );
+}
@@ -0,0 +1,179 @@
+use common::constants::HEALTH_WAIT_TIMEOUT;
+use rand::Rng;
+use std::time::Duration;
+
+/// How the time between two consecutive orders is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Arrivals {
+    /// Orders are placed at a fixed interval.
+    #[default]
+    Constant,
+    /// Orders follow a Poisson process: exponentially distributed intervals.
+    Poisson,
+}
+
+impl Arrivals {
+    /// Returns the time to wait before placing the next order.
+    ///
+    /// ## Arguments
+    /// * `rate` - The average number of orders per second.
+    pub fn next_delay(&self, rate: f64) -> Duration {
+        match self {
+            Arrivals::Constant => Duration::from_secs_f64(1.0 / rate),
+            Arrivals::Poisson => {
+                // 1 - U está en (0, 1], así que el logaritmo es finito
+                let u: f64 = 1.0 - rand::thread_rng().r#gen::<f64>();
+                Duration::from_secs_f64(-u.ln() / rate)
+            }
+        }
+    }
+}
+
+/// How clients and restaurants are chosen for each order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Popularity {
+    /// Every client orders, and every restaurant is chosen, with the same probability.
+    #[default]
+    Uniform,
+    /// A few clients and restaurants get most of the orders (Zipf distribution with `s = 1`).
+    Skewed,
+}
+
+impl Popularity {
+    /// Picks an index in `0..len`. `len` must be greater than zero.
+    pub fn pick(&self, len: usize) -> usize {
+        let mut rng = rand::thread_rng();
+        match self {
+            Popularity::Uniform => rng.gen_range(0..len),
+            Popularity::Skewed => {
+                // Peso 1 / (i + 1) para el i-ésimo elemento
+                let total: f64 = (1..=len).map(|i| 1.0 / i as f64).sum();
+                let mut target = rng.gen_range(0.0..total);
+                for i in 0..len {
+                    target -= 1.0 / (i + 1) as f64;
+                    if target < 0.0 {
+                        return i;
+                    }
+                }
+                len - 1
+            }
+        }
+    }
+}
+
+/// Settings of a load generation run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadConfig {
+    /// Number of simulated clients.
+    pub clients: usize,
+    /// Number of simulated restaurants.
+    pub restaurants: usize,
+    /// Number of simulated deliveries.
+    pub deliveries: usize,
+    /// Average number of orders placed per second, across all clients.
+    pub rate: f64,
+    /// Distribution of the time between orders.
+    pub arrivals: Arrivals,
+    /// Distribution of the clients and restaurants chosen for each order.
+    pub popularity: Popularity,
+    /// How long to place orders for. Runs until interrupted if `None`.
+    pub duration: Option<Duration>,
+    /// Interval between two progress reports.
+    pub report_interval: Duration,
+    /// How long to wait for the health endpoint of a ready leader before starting. Zero to
+    /// start right away.
+    pub wait_ready: Duration,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            clients: 100,
+            restaurants: 10,
+            deliveries: 20,
+            rate: 5.0,
+            arrivals: Arrivals::default(),
+            popularity: Popularity::default(),
+            duration: None,
+            report_interval: Duration::from_secs(5),
+            wait_ready: HEALTH_WAIT_TIMEOUT,
+        }
+    }
+}
+
+impl LoadConfig {
+    /// Builds a configuration from command line flags, starting from the default one.
+    ///
+    /// ## Flags
+    /// - `--clients <n>`, `--restaurants <n>`, `--deliveries <n>`: Number of simulated peers.
+    /// - `--rate <orders/s>`: Average number of orders per second.
+    /// - `--arrivals <constant|poisson>`: Distribution of the time between orders.
+    /// - `--popularity <uniform|skewed>`: Distribution of clients and restaurants.
+    /// - `--duration <s>`: Seconds to place orders for.
+    /// - `--report <s>`: Seconds between progress reports.
+    /// - `--wait-ready <s>`: Seconds to wait for a ready leader, or `0` not to wait.
+    ///
+    /// ## Returns
+    /// An error describing the first invalid flag.
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args.iter();
+
+        while let Some(flag) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("Missing value for {}", flag))?;
+            match flag.as_str() {
+                "--clients" => config.clients = parse_count(flag, value)?,
+                "--restaurants" => config.restaurants = parse_count(flag, value)?,
+                "--deliveries" => config.deliveries = parse_count(flag, value)?,
+                "--rate" => config.rate = parse_positive(flag, value)?,
+                "--arrivals" => {
+                    config.arrivals = match value.as_str() {
+                        "constant" => Arrivals::Constant,
+                        "poisson" => Arrivals::Poisson,
+                        _ => return Err(format!("Invalid arrivals: {}", value)),
+                    }
+                }
+                "--popularity" => {
+                    config.popularity = match value.as_str() {
+                        "uniform" => Popularity::Uniform,
+                        "skewed" => Popularity::Skewed,
+                        _ => return Err(format!("Invalid popularity: {}", value)),
+                    }
+                }
+                "--duration" => {
+                    config.duration = Some(Duration::from_secs_f64(parse_positive(flag, value)?))
+                }
+                "--report" => {
+                    config.report_interval = Duration::from_secs_f64(parse_positive(flag, value)?)
+                }
+                "--wait-ready" => match value.parse::<f64>() {
+                    Ok(seconds) if seconds >= 0.0 && seconds.is_finite() => {
+                        config.wait_ready = Duration::from_secs_f64(seconds)
+                    }
+                    _ => return Err(format!("Invalid value for {}: {}", flag, value)),
+                },
+                other => return Err(format!("Unknown flag: {}", other)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+/// Parses a number of peers, which must be at least one.
+fn parse_count(flag: &str, value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("Invalid value for {}: {}", flag, value)),
+    }
+}
+
+/// Parses a strictly positive number.
+fn parse_positive(flag: &str, value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number > 0.0 && number.is_finite() => Ok(number),
+        _ => Err(format!("Invalid value for {}: {}", flag, value)),
+    }
+}
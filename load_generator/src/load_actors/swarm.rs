@@ -0,0 +1,237 @@
+use crate::config::LoadConfig;
+use actix::prelude::*;
+use api::api_actors::session::ClientSession;
+use api::messages::internal_messages::{FetchOrderStatus, SubmitOrder};
+use colored::Color;
+use common::logger::Logger;
+use common::types::dtos::OrderItem;
+use common::types::order_priority::OrderPriority;
+use common::types::order_status::OrderStatus;
+use rand::seq::SliceRandom;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Dishes the simulated clients order.
+const DISHES: [&str; 6] = [
+    "Pizza",
+    "Empanadas",
+    "Milanesa",
+    "Sushi",
+    "Hamburguesa",
+    "Ravioles",
+];
+
+/// An order placed by the swarm that has not reached a final state yet.
+struct InFlightOrder {
+    /// The session that placed the order.
+    session: Addr<ClientSession>,
+    /// When the order was placed.
+    placed_at: Instant,
+}
+
+/// Counters reported by the [`Swarm`].
+#[derive(Debug, Default)]
+struct SwarmStats {
+    placed: u64,
+    failed: u64,
+    delivered: u64,
+    cancelled: u64,
+    unauthorized: u64,
+    total_delivery_time: Duration,
+}
+
+/// The `Swarm` actor places orders on behalf of many simulated clients.
+///
+/// ## Responsibilities
+/// - Places orders at the configured rate, choosing clients and restaurants with the
+///   configured distribution.
+/// - Polls the sessions to follow every order until it is delivered or cancelled.
+/// - Periodically reports throughput and delivery times.
+pub struct Swarm {
+    /// Settings of the run.
+    config: LoadConfig,
+    /// Sessions of the simulated clients.
+    sessions: Vec<Addr<ClientSession>>,
+    /// IDs of the simulated restaurants.
+    restaurant_ids: Vec<String>,
+    /// Orders waiting for a final state, keyed by order ID.
+    in_flight: HashMap<u64, InFlightOrder>,
+    /// Counters for the reports.
+    stats: SwarmStats,
+    /// When the swarm started placing orders.
+    started_at: Instant,
+    /// Logger for swarm events.
+    logger: Logger,
+}
+
+impl Swarm {
+    /// Creates a new `Swarm`.
+    ///
+    /// ## Arguments
+    /// * `config` - Settings of the run.
+    /// * `sessions` - Sessions of the simulated clients. Must not be empty.
+    /// * `restaurant_ids` - IDs of the simulated restaurants. Must not be empty.
+    pub fn new(
+        config: LoadConfig,
+        sessions: Vec<Addr<ClientSession>>,
+        restaurant_ids: Vec<String>,
+    ) -> Self {
+        Self {
+            config,
+            sessions,
+            restaurant_ids,
+            in_flight: HashMap::new(),
+            stats: SwarmStats::default(),
+            started_at: Instant::now(),
+            logger: Logger::new("SWARM", Color::BrightYellow),
+        }
+    }
+
+    /// Schedules the next order according to the arrival distribution.
+    fn schedule_next_order(&self, ctx: &mut Context<Self>) {
+        let delay = self.config.arrivals.next_delay(self.config.rate);
+        ctx.run_later(delay, |act, ctx| {
+            act.place_order(ctx);
+            act.schedule_next_order(ctx);
+        });
+    }
+
+    /// Places one order from a random client at a random restaurant.
+    fn place_order(&mut self, ctx: &mut Context<Self>) {
+        let popularity = self.config.popularity;
+        let session = self.sessions[popularity.pick(self.sessions.len())].clone();
+        let restaurant_id = self.restaurant_ids[popularity.pick(self.restaurant_ids.len())].clone();
+        let dish = DISHES.choose(&mut rand::thread_rng()).unwrap_or(&DISHES[0]);
+
+        session
+            .send(SubmitOrder {
+                restaurant_id,
+                items: vec![OrderItem::new(dish, 1)],
+                priority: OrderPriority::Standard,
+                promo_code: None,
+            })
+            .into_actor(self)
+            .map(
+                move |result, act, _ctx| match result.map_err(|e| e.to_string()).flatten() {
+                    Ok(order) => {
+                        act.stats.placed += 1;
+                        act.in_flight.insert(
+                            order.order_id,
+                            InFlightOrder {
+                                session,
+                                placed_at: Instant::now(),
+                            },
+                        );
+                    }
+                    Err(e) => {
+                        act.stats.failed += 1;
+                        act.logger.warn(format!("Could not place order: {}", e));
+                    }
+                },
+            )
+            .spawn(ctx);
+    }
+
+    /// Asks the cluster, through the sessions, for the state of every order in flight.
+    fn poll_orders(&self, ctx: &mut Context<Self>) {
+        for (order_id, order) in &self.in_flight {
+            let order_id = *order_id;
+            order
+                .session
+                .send(FetchOrderStatus { order_id })
+                .into_actor(self)
+                .map(move |result, act, _ctx| {
+                    if let Ok(Ok(Some(order))) = result {
+                        act.record_status(order_id, &order.status);
+                    }
+                })
+                .spawn(ctx);
+        }
+    }
+
+    /// Updates the counters if `status` is final for the order.
+    fn record_status(&mut self, order_id: u64, status: &OrderStatus) {
+        if !matches!(
+            status,
+            OrderStatus::Delivered
+                | OrderStatus::Cancelled
+                | OrderStatus::OutOfStock
+                | OrderStatus::OutOfZone
+                | OrderStatus::InvalidPromoCode
+                | OrderStatus::RestaurantClosed
+                | OrderStatus::OtherTenant
+                | OrderStatus::Unauthorized
+        ) {
+            return;
+        }
+        // Puede llegar más de una respuesta para el mismo pedido
+        let Some(order) = self.in_flight.remove(&order_id) else {
+            return;
+        };
+        match status {
+            OrderStatus::Delivered => {
+                self.stats.delivered += 1;
+                self.stats.total_delivery_time += order.placed_at.elapsed();
+            }
+            OrderStatus::Cancelled
+            | OrderStatus::OutOfStock
+            | OrderStatus::OutOfZone
+            | OrderStatus::InvalidPromoCode
+            | OrderStatus::RestaurantClosed
+            | OrderStatus::OtherTenant => self.stats.cancelled += 1,
+            _ => self.stats.unauthorized += 1,
+        }
+    }
+
+    /// Logs the counters collected so far.
+    fn report(&self) {
+        let elapsed = self.started_at.elapsed().as_secs_f64();
+        let average_delivery = if self.stats.delivered > 0 {
+            self.stats.total_delivery_time.as_secs_f64() / self.stats.delivered as f64
+        } else {
+            0.0
+        };
+        self.logger.info(format!(
+            "[{:.0}s] placed={} ({:.2}/s) in_flight={} delivered={} cancelled={} unauthorized={} failed={} avg_delivery={:.1}s",
+            elapsed,
+            self.stats.placed,
+            self.stats.placed as f64 / elapsed.max(1.0),
+            self.in_flight.len(),
+            self.stats.delivered,
+            self.stats.cancelled,
+            self.stats.unauthorized,
+            self.stats.failed,
+            average_delivery
+        ));
+    }
+}
+
+impl Actor for Swarm {
+    type Context = Context<Self>;
+
+    /// Starts placing orders and reporting, and stops the system once the run is over.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.started_at = Instant::now();
+        self.logger.info(format!(
+            "Placing {} orders/s ({:?}) from {} clients at {} restaurants",
+            self.config.rate,
+            self.config.arrivals,
+            self.sessions.len(),
+            self.restaurant_ids.len()
+        ));
+        self.schedule_next_order(ctx);
+
+        ctx.run_interval(self.config.report_interval, |act, ctx| {
+            act.poll_orders(ctx);
+            act.report();
+        });
+
+        if let Some(duration) = self.config.duration {
+            ctx.run_later(duration, |act, _ctx| {
+                act.logger.info("Load generation finished.");
+                act.report();
+                System::current().stop();
+            });
+        }
+    }
+}
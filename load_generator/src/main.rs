@@ -0,0 +1,99 @@
+use actix::prelude::*;
+use api::api_actors::session::ClientSession;
+use common::constants::DELIVERY_SUCCESS_PROBABILITY;
+use common::health;
+use common::network::connections::coordinator_addresses;
+use common::types::restaurant_info::RestaurantInfo;
+use common::types::tenant::TenantId;
+use common::utils::{get_rand_f32_tuple, print_welcome_message};
+use delivery::delivery_actors::delivery::Delivery;
+use load_generator::config::LoadConfig;
+use load_generator::load_actors::swarm::Swarm;
+use restaurant::policy::AcceptancePolicy;
+use restaurant::restaurant_actors::restaurant::Restaurant;
+use std::env;
+use tokio::signal::ctrl_c;
+
+#[actix::main]
+async fn main() -> std::io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+    let config = match LoadConfig::from_args(&args[1..]) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Usage: {} [--clients <n>] [--restaurants <n>] [--deliveries <n>] [--rate <orders/s>] [--arrivals <constant|poisson>] [--popularity <uniform|skewed>] [--duration <s>] [--report <s>] [--wait-ready <s>]",
+                args[0]
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let servers = coordinator_addresses();
+
+    print_welcome_message();
+
+    // Esperar a que algún servidor sea un líder listo, según su endpoint de salud
+    if !config.wait_ready.is_zero() {
+        let health_addrs: Vec<_> = servers.iter().copied().map(health::default_addr).collect();
+        match health::wait_until(&health_addrs, config.wait_ready, |report| {
+            report.role == "leader"
+        })
+        .await
+        {
+            Ok(report) => println!("Leader {} is ready", report.node_id),
+            Err(e) => eprintln!("{}, starting anyway", e),
+        }
+    }
+
+    // Primero los restaurantes y repartidores, para que los pedidos encuentren quién los atienda
+    let mut restaurant_ids = Vec::with_capacity(config.restaurants);
+    for i in 0..config.restaurants {
+        let id = format!("load_restaurant_{}", i);
+        let info = RestaurantInfo {
+            id: id.clone(),
+            position: get_rand_f32_tuple(),
+            tags: Vec::new(),
+            tenant_id: TenantId::from_env(),
+        };
+        Restaurant::new(info, AcceptancePolicy::default(), servers.clone())
+            .await
+            .start();
+        restaurant_ids.push(id);
+    }
+
+    for i in 0..config.deliveries {
+        Delivery::new(
+            servers.clone(),
+            format!("load_delivery_{}", i),
+            get_rand_f32_tuple(),
+            DELIVERY_SUCCESS_PROBABILITY,
+        )
+        .await
+        .start();
+    }
+
+    let mut sessions = Vec::with_capacity(config.clients);
+    for i in 0..config.clients {
+        let client_id = format!("load_client_{}", i);
+        match ClientSession::new(servers.clone(), client_id.clone(), get_rand_f32_tuple()).await {
+            Some(session) => sessions.push(session.start()),
+            None => eprintln!("Could not connect {}", client_id),
+        }
+    }
+
+    if sessions.is_empty() {
+        eprintln!("No client could connect to the cluster.");
+        std::process::exit(1);
+    }
+
+    Swarm::new(config, sessions, restaurant_ids).start();
+
+    tokio::select! {
+        _ = ctrl_c() => {
+            actix::System::current().stop();
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,237 @@
+use actix::prelude::*;
+use common::constants::{API_REQUEST_TIMEOUT, PAYMENT_GATEWAY_PORT, SERVER_IP_ADDRESS};
+use common::messages::shared_messages::NetworkMessage;
+use common::messages::{RequestLedger, TopUpBalance};
+use common::network::communicator::Communicator;
+use common::network::connections::connect_one;
+use common::network::peer_types::{PeerIdentity, PeerType};
+use common::types::ledger::{LedgerEntry, LedgerSummary};
+use std::env;
+use std::net::SocketAddr;
+use tokio::sync::oneshot;
+
+/// Formats an amount in cents as dollars.
+fn dollars(cents: u64) -> String {
+    format!("${}.{:02}", cents / 100, cents % 100)
+}
+
+/// Prints the entries of a ledger and their totals.
+fn print_report(entries: &[LedgerEntry], summary: &LedgerSummary, balance: Option<u64>) {
+    println!(
+        "{:>6}  {:<13}  {:>20}  {:<20}  {:>10}",
+        "SEQ", "OPERATION", "ORDER", "CLIENT", "AMOUNT"
+    );
+    for entry in entries {
+        println!(
+            "{:>6}  {:<13}  {:>20}  {:<20}  {:>10}",
+            entry.seq,
+            entry.operation.to_string(),
+            entry.order_id,
+            entry.client_id,
+            dollars(entry.amount)
+        );
+    }
+    println!();
+    println!(
+        "Authorized: {} ({})",
+        summary.authorizations,
+        dollars(summary.authorized_amount)
+    );
+    println!("Rejected:   {}", summary.rejections);
+    println!(
+        "Captured:   {} ({})",
+        summary.captures,
+        dollars(summary.captured_amount)
+    );
+    println!(
+        "Refunded:   {} ({})",
+        summary.refunds,
+        dollars(summary.refunded_amount)
+    );
+    println!(
+        "Topped up:  {} ({})",
+        summary.top_ups,
+        dollars(summary.topped_up_amount)
+    );
+    println!(
+        "Paid out:   {} ({})",
+        summary.payouts,
+        dollars(summary.paid_out_amount)
+    );
+    println!(
+        "Outstanding authorizations: {}",
+        dollars(summary.outstanding_amount())
+    );
+    if let Some(balance) = balance {
+        println!("Balance: {}", dollars(balance));
+    }
+}
+
+/// Asks a payment gateway for its ledger, or tops up the balance of a client, and prints the
+/// answer.
+struct LedgerQuery {
+    /// Connection with the gateway.
+    communicator: Option<Communicator<LedgerQuery>>,
+    /// The request sent once connected.
+    request: NetworkMessage,
+    /// Where to send the exit code of the process once done.
+    done: Option<oneshot::Sender<i32>>,
+}
+
+impl LedgerQuery {
+    /// Finishes the query with `code` as exit code.
+    fn finish(&mut self, code: i32) {
+        if let Some(done) = self.done.take() {
+            let _ = done.send(code);
+        }
+    }
+}
+
+impl Actor for LedgerQuery {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(sender) = self.communicator.as_ref().and_then(|c| c.sender.as_ref()) {
+            sender.do_send(self.request.clone());
+        }
+        ctx.run_later(API_REQUEST_TIMEOUT, |act, _| {
+            eprintln!("The payment gateway did not answer in time");
+            act.finish(1);
+        });
+    }
+}
+
+impl Handler<NetworkMessage> for LedgerQuery {
+    type Result = ();
+
+    fn handle(&mut self, msg: NetworkMessage, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            NetworkMessage::LedgerReport(report) => {
+                print_report(&report.entries, &report.summary, report.balance);
+                self.finish(0);
+            }
+            NetworkMessage::ClientBalance(balance) => {
+                println!(
+                    "Balance of {}: {}",
+                    balance.client_id,
+                    dollars(balance.balance)
+                );
+                self.finish(0);
+            }
+            NetworkMessage::ConnectionClosed(_) => {
+                eprintln!("Connection with the payment gateway closed");
+                self.finish(1);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The options of the command line.
+struct Options {
+    /// Address of the payment gateway.
+    gateway: SocketAddr,
+    /// Only the entries of this order, if set.
+    order_id: Option<u64>,
+    /// Only the entries of this client, or the client to top up.
+    client_id: Option<String>,
+    /// Cents to add to the balance of the client, instead of querying the ledger.
+    top_up: Option<u64>,
+}
+
+/// Parses `[--gateway <addr>] [--order <id>] [--client <id>] [--top-up <cents>]`.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut gateway = format!("{}:{}", SERVER_IP_ADDRESS, PAYMENT_GATEWAY_PORT)
+        .parse::<SocketAddr>()
+        .map_err(|e| e.to_string())?;
+    let mut order_id = None;
+    let mut client_id = None;
+    let mut top_up = None;
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--gateway" => {
+                gateway = value
+                    .parse()
+                    .map_err(|_| format!("Invalid gateway address: {}", value))?
+            }
+            "--order" => {
+                order_id = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid order ID: {}", value))?,
+                )
+            }
+            "--client" => client_id = Some(value.clone()),
+            "--top-up" => {
+                top_up = Some(
+                    value
+                        .parse()
+                        .map_err(|_| format!("Invalid amount of cents: {}", value))?,
+                )
+            }
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+    if top_up.is_some() && client_id.is_none() {
+        return Err("--top-up needs the --client to top up".to_string());
+    }
+    Ok(Options {
+        gateway,
+        order_id,
+        client_id,
+        top_up,
+    })
+}
+
+#[actix::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    let options = match parse_args(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Usage: {} [--gateway <addr>] [--order <id>] [--client <id>] [--top-up <cents>]",
+                args[0]
+            );
+            std::process::exit(1);
+        }
+    };
+
+    let identity = PeerIdentity::new(PeerType::CoordinatorType, "payment_admin");
+    let Some(stream) = connect_one(options.gateway, &identity).await else {
+        eprintln!(
+            "Failed to connect to the payment gateway at {}",
+            options.gateway
+        );
+        std::process::exit(1);
+    };
+
+    let (done, finished) = oneshot::channel();
+    LedgerQuery::create(|ctx| {
+        let communicator = Communicator::new(stream, ctx.address(), PeerType::GatewayType);
+        let origin_address = communicator.local_address;
+        let request = match (options.top_up, options.client_id) {
+            (Some(amount), Some(client_id)) => NetworkMessage::TopUpBalance(TopUpBalance {
+                origin_address,
+                client_id,
+                amount,
+            }),
+            (_, client_id) => NetworkMessage::RequestLedger(RequestLedger {
+                origin_address,
+                order_id: options.order_id,
+                client_id,
+            }),
+        };
+        LedgerQuery {
+            communicator: Some(communicator),
+            request,
+            done: Some(done),
+        }
+    });
+    std::process::exit(finished.await.unwrap_or(1));
+}
@@ -0,0 +1,208 @@
+use common::constants::DEFAULT_PAYMENT_LEDGER_DIR;
+use common::types::dtos::OrderDTO;
+use common::types::ledger::{LedgerEntry, LedgerOperation, LedgerSummary};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable with the directory where each payment gateway keeps its ledger.
+/// [`DEFAULT_PAYMENT_LEDGER_DIR`] is used if it is not set, and `off` keeps the ledger only
+/// in memory.
+pub const PAYMENT_LEDGER_DIR_ENV_VAR: &str = "PAYMENT_LEDGER_DIR";
+
+/// Returns the directory selected by [`PAYMENT_LEDGER_DIR_ENV_VAR`], or `None` if the ledger
+/// is not persisted.
+pub fn dir_from_env() -> Option<PathBuf> {
+    match std::env::var(PAYMENT_LEDGER_DIR_ENV_VAR) {
+        Ok(value) if value.trim().eq_ignore_ascii_case("off") => None,
+        Ok(value) if !value.trim().is_empty() => Some(PathBuf::from(value.trim())),
+        _ => Some(PathBuf::from(DEFAULT_PAYMENT_LEDGER_DIR)),
+    }
+}
+
+/// The record of every authorization, rejection, capture, refund, top-up and payout of a
+/// payment gateway.
+///
+/// Entries are kept in memory and, if the ledger is persisted, appended to a file with one
+/// JSON [`LedgerEntry`] per line, so that a restarted gateway remembers which orders it
+/// already authorized and billed.
+#[derive(Debug, Default)]
+pub struct Ledger {
+    /// The file the entries are appended to, if persisted.
+    file: Option<File>,
+    /// Every entry, oldest first.
+    entries: Vec<LedgerEntry>,
+}
+
+impl Ledger {
+    /// Creates a ledger kept only in memory.
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Opens (or creates) the ledger of `gateway_id` inside `dir`, reading its entries.
+    ///
+    /// Reading stops at the first line that cannot be parsed: a crash in the middle of an
+    /// append leaves a truncated last line.
+    pub fn open(dir: &Path, gateway_id: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}.ledger", gateway_id));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut entries = Vec::new();
+        for line in BufReader::new(File::open(&path)?).lines() {
+            match serde_json::from_str(&line?) {
+                Ok(entry) => entries.push(entry),
+                Err(_) => break,
+            }
+        }
+        Ok(Self {
+            file: Some(file),
+            entries,
+        })
+    }
+
+    /// Returns every entry, oldest first.
+    pub fn entries(&self) -> &[LedgerEntry] {
+        &self.entries
+    }
+
+    /// Records an operation on `order`.
+    ///
+    /// The entry is kept in memory even if it cannot be written to the file.
+    pub fn record(
+        &mut self,
+        operation: LedgerOperation,
+        order: &OrderDTO,
+        amount: u64,
+    ) -> io::Result<()> {
+        self.append(operation, order.order_id, &order.client_id, amount)
+    }
+
+    /// Records a top-up of `amount` cents to the balance of `client_id`.
+    pub fn record_top_up(&mut self, client_id: &str, amount: u64) -> io::Result<()> {
+        self.append(LedgerOperation::TopUp, 0, client_id, amount)
+    }
+
+    /// Records a payout of `amount` cents to `delivery_id` for delivering `order_id`.
+    pub fn record_payout(
+        &mut self,
+        order_id: u64,
+        delivery_id: &str,
+        amount: u64,
+    ) -> io::Result<()> {
+        self.append(LedgerOperation::Payout, order_id, delivery_id, amount)
+    }
+
+    /// Appends an entry to the ledger, keeping it in memory even if it cannot be written.
+    fn append(
+        &mut self,
+        operation: LedgerOperation,
+        order_id: u64,
+        client_id: &str,
+        amount: u64,
+    ) -> io::Result<()> {
+        let entry = LedgerEntry {
+            seq: self.entries.len() as u64 + 1,
+            timestamp_millis: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64),
+            operation,
+            order_id,
+            client_id: client_id.to_string(),
+            amount,
+        };
+        let written = match self.file.as_mut() {
+            Some(file) => serde_json::to_string(&entry)
+                .map_err(io::Error::from)
+                .and_then(|line| writeln!(file, "{}", line)),
+            None => Ok(()),
+        };
+        self.entries.push(entry);
+        written
+    }
+
+    /// Returns the entries of `order_id` and of `client_id`, when set, with their totals.
+    pub fn query(
+        &self,
+        order_id: Option<u64>,
+        client_id: Option<&str>,
+    ) -> (Vec<LedgerEntry>, LedgerSummary) {
+        let entries: Vec<LedgerEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| order_id.is_none_or(|id| entry.order_id == id))
+            .filter(|entry| client_id.is_none_or(|id| entry.client_id == id))
+            .cloned()
+            .collect();
+        let summary = LedgerSummary::from_entries(&entries);
+        (entries, summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::dtos::OrderItem;
+
+    fn order(order_id: u64, client_id: &str) -> OrderDTO {
+        OrderDTO::new(
+            order_id,
+            client_id,
+            "restaurant",
+            vec![OrderItem::new("Pizza", 1)],
+        )
+    }
+
+    #[test]
+    fn reads_the_entries_back_after_a_restart() {
+        let dir = std::env::temp_dir().join(format!("ledger_{}", rand::random::<u64>()));
+        let mut ledger = Ledger::open(&dir, "gateway").unwrap();
+        ledger
+            .record(LedgerOperation::Authorization, &order(1, "ana"), 1000)
+            .unwrap();
+        ledger
+            .record(LedgerOperation::Capture, &order(1, "ana"), 1000)
+            .unwrap();
+        drop(ledger);
+
+        let ledger = Ledger::open(&dir, "gateway").unwrap();
+        assert_eq!(ledger.entries().len(), 2);
+        assert_eq!(ledger.entries()[1].seq, 2);
+        assert_eq!(ledger.entries()[1].operation, LedgerOperation::Capture);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reconciles_the_entries_of_a_client() {
+        let mut ledger = Ledger::in_memory();
+        ledger
+            .record(LedgerOperation::Authorization, &order(1, "ana"), 1000)
+            .unwrap();
+        ledger
+            .record(LedgerOperation::Authorization, &order(2, "ana"), 1500)
+            .unwrap();
+        ledger
+            .record(LedgerOperation::Authorization, &order(3, "bob"), 1000)
+            .unwrap();
+        ledger
+            .record(LedgerOperation::Capture, &order(1, "ana"), 1000)
+            .unwrap();
+
+        let (entries, summary) = ledger.query(None, Some("ana"));
+        assert_eq!(entries.len(), 3);
+        assert_eq!(summary.authorized_amount, 2500);
+        assert_eq!(summary.captured_amount, 1000);
+        assert_eq!(summary.outstanding_amount(), 1500);
+
+        let (entries, _) = ledger.query(Some(3), None);
+        assert_eq!(entries.len(), 1);
+
+        ledger.record_payout(1, "carla", 300).unwrap();
+        let (entries, summary) = ledger.query(Some(1), None);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(summary.paid_out_amount, 300);
+        // El pago al delivery no cuenta como cobro al cliente
+        assert_eq!(ledger.query(None, Some("ana")).1.paid_out_amount, 0);
+    }
+}
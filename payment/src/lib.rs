@@ -0,0 +1,5 @@
+pub mod ledger;
+pub mod payment;
+pub mod payment_acceptor;
+pub mod simulation;
+pub mod wallet;
@@ -1,29 +1,72 @@
 use actix::prelude::*;
-use common::constants::{PAYMENT_GATEWAY_PORT, PAYMENT_SUCCESS_PROBABILITY, SERVER_IP_ADDRESS};
+use clap::Parser;
+use common::constants::{PAYMENT_GATEWAY_PORT, SERVER_IP_ADDRESS};
+use common::health::{self, GetHealth};
+use payment::ledger::{self, Ledger};
+use payment::payment::PaymentGateway;
+use payment::payment_acceptor::PaymentAcceptor;
+use payment::simulation;
+use payment::wallet;
 use std::io::{self, Write};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use tokio::signal::ctrl_c;
-mod payment;
-mod payment_acceptor;
-use payment::PaymentGateway;
-use payment_acceptor::PaymentAcceptor;
+
+/// Payment gateway of PedidosRust: authorizes and charges the orders of the clients.
+#[derive(Parser)]
+struct Args {
+    /// Port to listen on, to run more than one gateway
+    #[arg(default_value_t = PAYMENT_GATEWAY_PORT, conflicts_with = "bind")]
+    port: u16,
+    /// Address to listen on [default: the local address, on PORT]
+    #[arg(long, value_name = "ADDR")]
+    bind: Option<SocketAddr>,
+}
 
 #[actix::main]
 async fn main() {
-    let my_addr = format!("{}:{}", SERVER_IP_ADDRESS, PAYMENT_GATEWAY_PORT)
-        .parse::<SocketAddr>()
-        .expect("Failed to parse server address");
+    // Permitir pasar el puerto como argumento, para levantar más de un gateway: cargo run --bin payment -- 8090
+    let args = Args::parse();
+    let my_addr = args.bind.unwrap_or_else(|| {
+        let ip: IpAddr = SERVER_IP_ADDRESS.parse().expect("Invalid IP address");
+        SocketAddr::new(ip, args.port)
+    });
+    let port = my_addr.port();
 
     print!("\x1B[2J\x1B[1;1H");
     io::stdout().flush().unwrap();
 
-    // Iniciar el PaymentGateway
-    let payment_gateway = PaymentGateway::new(PAYMENT_SUCCESS_PROBABILITY);
+    // Cada gateway guarda su propio registro de operaciones
+    let ledger = match ledger::dir_from_env() {
+        Some(dir) => Ledger::open(&dir, &format!("gateway_{}", port)).unwrap_or_else(|e| {
+            eprintln!("Error opening the payment ledger in {:?}: {}", dir, e);
+            Ledger::in_memory()
+        }),
+        None => Ledger::in_memory(),
+    };
+
+    // Iniciar el PaymentGateway, con el saldo inicial de los clientes y las fallas a simular
+    let payment_gateway = PaymentGateway::with_ledger(wallet::wallets_from_env(), ledger)
+        .with_simulation(simulation::simulation_from_env());
     let payment_gateway_addr = payment_gateway.start();
 
     // Iniciar el Acceptor
     PaymentAcceptor::new(my_addr, payment_gateway_addr.clone()).start();
 
+    // Iniciar el endpoint de salud
+    if let Some(health_addr) = health::addr_from_env(my_addr) {
+        let report = move || {
+            let gateway = payment_gateway_addr.clone();
+            async move {
+                let mut report = gateway.send(GetHealth).await.unwrap_or_default();
+                report.node_id = format!("gateway_{}", port);
+                report
+            }
+        };
+        if let Err(e) = health::serve(health_addr, report).await {
+            eprintln!("Cannot serve the health endpoint at {}: {}", health_addr, e);
+        }
+    }
+
     // Esperar señal de apagado
     tokio::select! {
         _ = ctrl_c() => {
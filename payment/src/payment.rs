@@ -1,48 +1,154 @@
 use actix::prelude::*;
+use common::health::{GetHealth, HealthReport};
 use common::messages::AuthorizationResult;
+use common::messages::ClientBalance;
+use common::messages::DeliveryPaidOut;
+use common::messages::LedgerReport;
 use common::messages::PaymentCompleted;
+use common::messages::PaymentRefunded;
+use common::messages::coordinatormanager_messages::Pong;
+use common::types::decline_reason::DeclineReason;
+use common::types::dtos::OrderDTO;
+use common::types::ledger::LedgerOperation;
 use common::types::order_status::OrderStatus;
 
+use crate::ledger::Ledger;
 use crate::payment_acceptor::RegisterConnection;
+use crate::simulation::PaymentSimulation;
+use crate::wallet::Wallets;
 use colored::Color;
 use common::logger::Logger;
 use common::messages::shared_messages::NetworkMessage;
 use common::network::communicator::Communicator;
-use common::utils::random_bool_by_given_probability;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::net::SocketAddr;
+use std::time::Instant;
 
 /// The `PaymentGateway` actor simulates a payment gateway that authorizes and charges orders.
 ///
 /// # Responsibilities
 /// - Receives authorization and payment requests from coordinators.
-/// - Decides whether to authorize an order based on a probability.
+/// - Authorizes an order only if the client can afford it, holding its amount from the
+///   simulated balance of the client in [`Wallets`], and tells why it declined the others
+///   with a [`DeclineReason`].
+/// - Simulates the latency, lost answers, declines and maintenance windows configured in its
+///   [`PaymentSimulation`].
 /// - Tracks authorized orders and processes payment completion.
+/// - Refunds the authorized orders that are cancelled before being billed, giving the amount
+///   back to the client except for the cancellation fee, which is captured.
+/// - Tops up the balance of the clients at the request of the admin CLI.
+/// - Pays the deliveries what they earned for each order, only once per order.
+/// - Answers repeated authorization and billing requests with the first result, so that a
+///   request sent again after a failover is never charged twice.
+/// - Answers the health checks of the coordinators, and reports its own health to its health
+///   endpoint.
+/// - Records every authorization, rejection, capture, refund, top-up and payout in a [`Ledger`], and answers
+///   the queries of the admin CLI about it.
 /// - Communicates results back to the requesting coordinator via a [`Communicator`].
 #[derive(Debug)]
 pub struct PaymentGateway {
     /// Set of order IDs that have been authorized for payment.
     pub authorized_orders: HashSet<u64>,
+    /// Set of order IDs whose authorization was rejected.
+    pub rejected_orders: HashSet<u64>,
+    /// Why the authorization of each order rejected since the gateway started was declined.
+    decline_reasons: HashMap<u64, DeclineReason>,
+    /// Amount billed for each order already billed, by order ID.
+    pub billed_orders: HashMap<u64, u64>,
+    /// Amount paid out to the delivery of each order already paid out, by order ID.
+    pub paid_out_orders: HashMap<u64, u64>,
     /// Active communicators mapped by remote address.
     pub communicators: HashMap<SocketAddr, Communicator<PaymentGateway>>,
-    /// Probability that an order will be authorized (between 0.0 and 1.0).
-    pub probability_of_success: f32,
+    /// Simulated balance of each client.
+    pub wallets: Wallets,
     /// Logger for payment gateway events.
     pub logger: Logger,
+    /// Record of every operation of the gateway.
+    pub ledger: Ledger,
+    /// The failures the gateway simulates.
+    simulation: PaymentSimulation,
+    /// When the gateway started, to know when its maintenance window is.
+    started_at: Instant,
 }
 
 impl PaymentGateway {
-    /// Creates a new `PaymentGateway` instance.
+    /// Creates a new `PaymentGateway` instance, with a ledger kept only in memory.
     ///
     /// # Arguments
-    /// * `probability_of_success` - Probability that an order will be authorized.
-    pub fn new(probability_of_success: f32) -> Self {
-        Self {
+    /// * `wallets` - The initial balance of the clients.
+    pub fn new(wallets: Wallets) -> Self {
+        Self::with_ledger(wallets, Ledger::in_memory())
+    }
+
+    /// Creates a new `PaymentGateway` instance that remembers the operations in `ledger`.
+    ///
+    /// # Arguments
+    /// * `wallets` - The initial balance of the clients.
+    /// * `ledger` - The ledger of the gateway, possibly with the entries of a previous run.
+    pub fn with_ledger(wallets: Wallets, ledger: Ledger) -> Self {
+        let mut gateway = Self {
             authorized_orders: HashSet::new(),
+            rejected_orders: HashSet::new(),
+            decline_reasons: HashMap::new(),
+            billed_orders: HashMap::new(),
+            paid_out_orders: HashMap::new(),
             communicators: HashMap::new(),
-            probability_of_success,
+            wallets,
             logger: Logger::new("Payment gateway", Color::BrightWhite),
+            ledger,
+            simulation: PaymentSimulation::default(),
+            started_at: Instant::now(),
+        };
+        // Se reconstruye qué pedidos ya se autorizaron, rechazaron, cobraron y pagaron a su
+        // delivery, y el saldo de cada cliente
+        for entry in gateway.ledger.entries() {
+            match entry.operation {
+                LedgerOperation::Authorization => {
+                    gateway.authorized_orders.insert(entry.order_id);
+                    gateway.wallets.hold(&entry.client_id, entry.amount);
+                }
+                LedgerOperation::Rejection => {
+                    gateway.rejected_orders.insert(entry.order_id);
+                }
+                LedgerOperation::Capture => {
+                    gateway.billed_orders.insert(entry.order_id, entry.amount);
+                }
+                LedgerOperation::Refund => {
+                    gateway.authorized_orders.remove(&entry.order_id);
+                    gateway.wallets.credit(&entry.client_id, entry.amount);
+                }
+                LedgerOperation::TopUp => {
+                    gateway.wallets.credit(&entry.client_id, entry.amount);
+                }
+                LedgerOperation::Payout => {
+                    gateway.paid_out_orders.insert(entry.order_id, entry.amount);
+                }
+            }
+        }
+        if !gateway.ledger.entries().is_empty() {
+            gateway.logger.info(format!(
+                "Recovered {} ledger entries",
+                gateway.ledger.entries().len()
+            ));
+        }
+        gateway
+    }
+
+    /// Sets the failures the gateway simulates.
+    pub fn with_simulation(mut self, simulation: PaymentSimulation) -> Self {
+        self.simulation = simulation;
+        self
+    }
+
+    /// Records an operation on `order` in the ledger.
+    fn record(&mut self, operation: LedgerOperation, order: &OrderDTO, amount: u64) {
+        if let Err(e) = self.ledger.record(operation, order, amount) {
+            self.logger.error(format!(
+                "Failed to write {} of order {} to the ledger: {}",
+                operation, order.order_id, e
+            ));
         }
     }
 
@@ -64,6 +170,74 @@ impl PaymentGateway {
     }
 }
 
+impl PaymentGateway {
+    /// Answers a payment request of `destination` after the simulated latency, unless the
+    /// simulation loses the answer.
+    fn answer(&self, destination: SocketAddr, message: NetworkMessage, ctx: &mut Context<Self>) {
+        let mut rng = rand::thread_rng();
+        if self.simulation.times_out(&mut rng) {
+            self.logger.warn(format!(
+                "Simulating a timeout, {} of order {:?} not sent",
+                message.kind(),
+                message.order_id()
+            ));
+            return;
+        }
+        let latency = self.simulation.latency.sample(&mut rng);
+        if latency.is_zero() {
+            self.send_network_message(destination, message);
+            return;
+        }
+        ctx.run_later(latency, move |act, _ctx| {
+            act.send_network_message(destination, message);
+        });
+    }
+
+    /// Turns down the requests that arrive during the maintenance window: the
+    /// authorizations are declined with [`DeclineReason::GatewayMaintenance`], and the other
+    /// payment requests and the health checks are not answered, so the coordinators fail
+    /// over and send again the bills they did not get confirmed.
+    ///
+    /// ## Returns
+    /// Whether the request was turned down.
+    fn turned_down_for_maintenance(&self, msg: &NetworkMessage, ctx: &mut Context<Self>) -> bool {
+        if !self.simulation.in_maintenance(self.started_at.elapsed()) {
+            return false;
+        }
+        match msg {
+            NetworkMessage::RequestAuthorization(request) => {
+                self.logger.warn(format!(
+                    "Under maintenance, declining order {}",
+                    request.order.order_id
+                ));
+                let mut order = request.order.clone();
+                order.status = OrderStatus::Unauthorized;
+                self.answer(
+                    request.origin_address,
+                    NetworkMessage::AuthorizationResult(AuthorizationResult {
+                        result: order,
+                        decline: Some(DeclineReason::GatewayMaintenance),
+                    }),
+                    ctx,
+                );
+                true
+            }
+            NetworkMessage::BillPayment(_)
+            | NetworkMessage::RefundPayment(_)
+            | NetworkMessage::PayOutDelivery(_) => {
+                self.logger.warn(format!(
+                    "Under maintenance, ignoring {} of order {:?}",
+                    msg.kind(),
+                    msg.order_id()
+                ));
+                true
+            }
+            NetworkMessage::Ping(_) => true,
+            _ => false,
+        }
+    }
+}
+
 impl Actor for PaymentGateway {
     type Context = Context<Self>;
 }
@@ -76,66 +250,280 @@ impl Handler<RegisterConnection> for PaymentGateway {
     }
 }
 
+/// Handles requests for the health of the gateway, answered by its health endpoint. The
+/// gateway is ready as soon as it runs.
+impl Handler<GetHealth> for PaymentGateway {
+    type Result = MessageResult<GetHealth>;
+
+    fn handle(&mut self, _msg: GetHealth, _ctx: &mut Self::Context) -> Self::Result {
+        let mut connections = BTreeMap::new();
+        for communicator in self.communicators.values() {
+            *connections
+                .entry(format!("{:?}", communicator.peer_type))
+                .or_insert(0) += 1;
+        }
+        MessageResult(HealthReport {
+            service: "payment".to_string(),
+            ready: true,
+            role: "gateway".to_string(),
+            connections,
+            ..HealthReport::default()
+        })
+    }
+}
+
 /// Handles [`NetworkMessage`] messages for payment authorization and payment completion.
 ///
-/// - On [`NetworkMessage::RequestAuthorization`], decides to authorize or reject the order.
-/// - On [`NetworkMessage::BillPayment`], completes the payment if the order was previously authorized.
+/// - On [`NetworkMessage::RequestAuthorization`], authorizes the order if the client can
+///   afford it, or repeats the decision already made for it.
+/// - On [`NetworkMessage::BillPayment`], completes the payment unless the order was rejected,
+///   billing more for express orders. An order already billed is not charged again.
+/// - On [`NetworkMessage::RefundPayment`], releases the authorization of a cancelled order,
+///   capturing its cancellation fee.
+/// - On [`NetworkMessage::TopUpBalance`], adds funds to the balance of a client.
+/// - On [`NetworkMessage::PayOutDelivery`], pays a delivery what it earned for an order. An
+///   order already paid out is not paid again.
+/// - On [`NetworkMessage::Ping`], answers with a [`NetworkMessage::Pong`].
+/// - On [`NetworkMessage::RequestLedger`], answers with the matching ledger entries.
 impl Handler<NetworkMessage> for PaymentGateway {
     type Result = ();
 
-    fn handle(&mut self, msg: NetworkMessage, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: NetworkMessage, ctx: &mut Self::Context) -> Self::Result {
+        if self.turned_down_for_maintenance(&msg, ctx) {
+            return;
+        }
         match msg {
             NetworkMessage::RequestAuthorization(msg) => {
                 let mut new_order_dto = msg.order.clone();
                 let order_id = new_order_dto.order_id;
                 self.logger.info(format!(
-                    "New order received: Dish='{}', Client={}, Restaurant={}",
-                    new_order_dto.dish_name, new_order_dto.client_id, new_order_dto.restaurant_id
+                    "New order received: Items='{}', Client={}, Restaurant={}",
+                    new_order_dto.summary(),
+                    new_order_dto.client_id,
+                    new_order_dto.restaurant_id
                 ));
-                let should_authorize =
-                    random_bool_by_given_probability(self.probability_of_success);
+                // Un pedido repetido recibe la misma respuesta que la primera vez
+                let (should_authorize, decline) = if self.authorized_orders.contains(&order_id) {
+                    self.logger
+                        .info(format!("Order {} was already authorized", order_id));
+                    (true, None)
+                } else if self.rejected_orders.contains(&order_id) {
+                    self.logger
+                        .info(format!("Order {} was already rejected", order_id));
+                    (false, self.decline_reasons.get(&order_id).copied())
+                } else {
+                    let client_id = &msg.order.client_id;
+                    let amount = msg.order.price();
+                    let decline = match self.simulation.decline(&mut rand::thread_rng()) {
+                        Some(code) => Some(code),
+                        None if self.wallets.hold(client_id, amount) => None,
+                        None => {
+                            let balance = self.wallets.balance(client_id);
+                            self.logger.warn(format!(
+                                "Insufficient funds for client {}: balance ${}.{:02}, order ${}.{:02}",
+                                client_id,
+                                balance / 100,
+                                balance % 100,
+                                amount / 100,
+                                amount % 100
+                            ));
+                            Some(DeclineReason::InsufficientFunds)
+                        }
+                    };
+                    match decline {
+                        None => self.record(LedgerOperation::Authorization, &msg.order, amount),
+                        // Un rechazo transitorio no se recuerda: el pedido se puede reintentar
+                        Some(code) if code.is_transient() => {}
+                        Some(code) => {
+                            self.record(LedgerOperation::Rejection, &msg.order, 0);
+                            self.rejected_orders.insert(order_id);
+                            self.decline_reasons.insert(order_id, code);
+                        }
+                    }
+                    (decline.is_none(), decline)
+                };
                 if should_authorize {
                     self.logger.info("✅ Order authorized");
                     self.authorized_orders.insert(order_id);
                     new_order_dto.status = OrderStatus::Authorized;
                 } else {
-                    self.logger.warn("❌ Order rejected");
+                    match decline {
+                        Some(code) => self.logger.warn(format!("❌ Order rejected: {}", code)),
+                        None => self.logger.warn("❌ Order rejected"),
+                    }
                     new_order_dto.status = OrderStatus::Unauthorized;
                 }
-                self.send_network_message(
+                self.answer(
                     msg.origin_address,
                     NetworkMessage::AuthorizationResult(AuthorizationResult {
                         result: new_order_dto.clone(),
+                        decline,
                     }),
+                    ctx,
                 );
             }
             NetworkMessage::BillPayment(msg) => {
                 let order_id = msg.order.order_id;
-
-                self.logger
-                    .info(format!("💸 Payment successful for order {}", order_id));
-
-                if self.authorized_orders.contains(&order_id) {
+                let amount = if let Some(amount) = self.billed_orders.get(&order_id) {
                     self.logger.info(format!(
-                        "Order {} is authorized, proceeding with payment.",
+                        "Order {} was already billed, not charging it again.",
                         order_id
                     ));
+                    *amount
                 } else {
+                    if self.rejected_orders.contains(&order_id) {
+                        self.logger.warn(format!(
+                            "Order {} is not authorized, cannot proceed with payment.",
+                            order_id
+                        ));
+                        return;
+                    }
+                    if self.authorized_orders.contains(&order_id) {
+                        self.logger.info(format!(
+                            "Order {} is authorized, proceeding with payment.",
+                            order_id
+                        ));
+                    } else {
+                        // Lo autorizó otra instancia del gateway antes de un failover
+                        self.logger.info(format!(
+                            "Order {} was authorized by another gateway, proceeding with payment.",
+                            order_id
+                        ));
+                    }
+                    let amount = msg.order.price();
+                    self.logger.info(format!(
+                        "💸 Payment of ${}.{:02} successful for order {} ({})",
+                        amount / 100,
+                        amount % 100,
+                        order_id,
+                        msg.order.priority
+                    ));
+                    self.billed_orders.insert(order_id, amount);
+                    self.record(LedgerOperation::Capture, &msg.order, amount);
+                    amount
+                };
+                self.answer(
+                    msg.origin_address,
+                    NetworkMessage::PaymentCompleted(PaymentCompleted {
+                        order: msg.order.clone(),
+                        amount,
+                    }),
+                    ctx,
+                );
+            }
+            NetworkMessage::RefundPayment(msg) => {
+                let order_id = msg.order.order_id;
+                if !self.authorized_orders.remove(&order_id) {
                     self.logger.warn(format!(
-                        "Order {} is not authorized, cannot proceed with payment.",
+                        "Order {} is not authorized, there is nothing to refund.",
                         order_id
                     ));
                     return;
                 }
-                if let Some(communicator) = self.communicators.get(&msg.origin_address) {
-                    if let Some(sender) = &communicator.sender {
-                        sender.do_send(NetworkMessage::PaymentCompleted(PaymentCompleted {
-                            order: msg.order.clone(),
-                        }));
-                    } else {
-                        self.logger.error("Sender not initialized in communicator");
-                    }
+                let price = msg.order.price();
+                let fee = msg.fee.min(price);
+                let amount = price - fee;
+                self.logger.info(format!(
+                    "↩️ Payment refunded for order {}, ${}.{:02} kept as cancellation fee",
+                    order_id,
+                    fee / 100,
+                    fee % 100
+                ));
+                self.wallets.credit(&msg.order.client_id, amount);
+                self.record(LedgerOperation::Refund, &msg.order, amount);
+                // La comisión se cobra como cualquier otro pago
+                if fee > 0 {
+                    self.billed_orders.insert(order_id, fee);
+                    self.record(LedgerOperation::Capture, &msg.order, fee);
+                }
+                self.answer(
+                    msg.origin_address,
+                    NetworkMessage::PaymentRefunded(PaymentRefunded {
+                        order: msg.order.clone(),
+                    }),
+                    ctx,
+                );
+            }
+            NetworkMessage::Ping(ping) => {
+                if let Some(communicator) = self.communicators.get(&ping.from) {
+                    let from = communicator.local_address;
+                    self.send_network_message(ping.from, NetworkMessage::Pong(Pong { from }));
+                }
+            }
+            NetworkMessage::RequestLedger(msg) => {
+                let (entries, summary) = self.ledger.query(msg.order_id, msg.client_id.as_deref());
+                self.logger.info(format!(
+                    "Sending {} ledger entries to {}",
+                    entries.len(),
+                    msg.origin_address
+                ));
+                self.send_network_message(
+                    msg.origin_address,
+                    NetworkMessage::LedgerReport(LedgerReport {
+                        entries,
+                        summary,
+                        balance: msg.client_id.map(|id| self.wallets.balance(&id)),
+                    }),
+                );
+            }
+            NetworkMessage::TopUpBalance(msg) => {
+                let balance = self.wallets.credit(&msg.client_id, msg.amount);
+                if let Err(e) = self.ledger.record_top_up(&msg.client_id, msg.amount) {
+                    self.logger.error(format!(
+                        "Failed to write top-up of client {} to the ledger: {}",
+                        msg.client_id, e
+                    ));
                 }
+                self.logger.info(format!(
+                    "💰 Balance of client {} topped up to ${}.{:02}",
+                    msg.client_id,
+                    balance / 100,
+                    balance % 100
+                ));
+                self.send_network_message(
+                    msg.origin_address,
+                    NetworkMessage::ClientBalance(ClientBalance {
+                        client_id: msg.client_id,
+                        balance,
+                    }),
+                );
+            }
+            NetworkMessage::PayOutDelivery(msg) => {
+                let amount = if let Some(amount) = self.paid_out_orders.get(&msg.order_id) {
+                    self.logger.info(format!(
+                        "Delivery of order {} was already paid out, not paying it again.",
+                        msg.order_id
+                    ));
+                    *amount
+                } else {
+                    self.logger.info(format!(
+                        "🛵 Paid ${}.{:02} to delivery {} for order {}",
+                        msg.amount / 100,
+                        msg.amount % 100,
+                        msg.delivery_id,
+                        msg.order_id
+                    ));
+                    self.paid_out_orders.insert(msg.order_id, msg.amount);
+                    if let Err(e) =
+                        self.ledger
+                            .record_payout(msg.order_id, &msg.delivery_id, msg.amount)
+                    {
+                        self.logger.error(format!(
+                            "Failed to write payout of order {} to the ledger: {}",
+                            msg.order_id, e
+                        ));
+                    }
+                    msg.amount
+                };
+                self.answer(
+                    msg.origin_address,
+                    NetworkMessage::DeliveryPaidOut(DeliveryPaidOut {
+                        delivery_id: msg.delivery_id,
+                        order_id: msg.order_id,
+                        amount,
+                    }),
+                    ctx,
+                );
             }
             NetworkMessage::ConnectionClosed(msg) => {
                 self.logger
@@ -3,11 +3,9 @@ use actix::prelude::*;
 use colored::Color;
 use common::logger::Logger;
 use common::network::communicator::Communicator;
-use common::network::peer_types::PeerType;
+use common::network::peer_types::{PeerIdentity, PeerType};
+use common::network::stream::{Listener, PeerStream};
 use std::net::SocketAddr;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpListener;
-use tokio::net::TcpStream;
 
 /// The `PaymentAcceptor` actor listens for incoming TCP connections and registers them
 /// with the [`PaymentGateway`] actor.
@@ -56,35 +54,28 @@ impl Actor for PaymentAcceptor {
 
         ctx.spawn(
             async move {
-                match TcpListener::bind(addr).await {
-                    Ok(listener) => {
+                match Listener::bind(addr).await {
+                    Ok(mut listener) => {
                         logger.info(format!("Payment acceptor started on {}", addr));
 
                         loop {
                             match listener.accept().await {
                                 Ok((mut stream, remote_addr)) => {
-                                    let mut peer_type_byte = [0u8; 1];
-
-                                    match stream.read_exact(&mut peer_type_byte).await {
-                                        Ok(_) => {
-                                            if let Some(peer_type) =
-                                                PeerType::from_u8(peer_type_byte[0])
-                                            {
-                                                payment_acceptor_addr.do_send(HandleConnection {
-                                                    stream,
-                                                    remote_addr,
-                                                    peer_type,
-                                                });
-                                            } else {
-                                                logger.info(format!(
-                                                    "Unknown peer type byte from {}",
-                                                    remote_addr
-                                                ));
-                                            }
+                                    match PeerIdentity::read_from(&mut stream).await {
+                                        Ok(identity) => {
+                                            logger.info(format!(
+                                                "{} identified as {:?}",
+                                                identity.user_id, identity.peer_type
+                                            ));
+                                            payment_acceptor_addr.do_send(HandleConnection {
+                                                stream,
+                                                remote_addr,
+                                                peer_type: identity.peer_type,
+                                            });
                                         }
                                         Err(e) => {
                                             logger.info(format!(
-                                                "Error reading peer type from {}: {}",
+                                                "Error reading identity from {}: {}",
                                                 remote_addr, e
                                             ));
                                         }
@@ -125,7 +116,7 @@ pub struct RegisterConnection {
 #[rtype(result = "()")]
 struct HandleConnection {
     /// The TCP stream for the connection.
-    stream: TcpStream,
+    stream: PeerStream,
     /// The remote address of the peer.
     remote_addr: SocketAddr,
     /// The type of peer that connected.
@@ -0,0 +1,234 @@
+use common::types::decline_reason::DeclineReason;
+use rand::Rng;
+use std::ops::Range;
+use std::time::Duration;
+
+/// Environment variable with the latency of the answers of the gateway, in milliseconds: a
+/// fixed value (`50`), a uniform range (`20-200`) or an exponential distribution with the
+/// given mean (`exp:100`). The gateway answers right away if it is not set.
+pub const PAYMENT_LATENCY_ENV_VAR: &str = "PAYMENT_LATENCY_MS";
+
+/// Environment variable with the probability, from 0 to 1, that the answer to a payment
+/// request is lost, as if it timed out. The request is still processed.
+pub const PAYMENT_TIMEOUT_RATE_ENV_VAR: &str = "PAYMENT_TIMEOUT_RATE";
+
+/// Environment variable with the probability, from 0 to 1, that a new authorization is
+/// declined with [`DeclineReason::IssuerUnavailable`]. The order may be authorized if it is
+/// requested again.
+pub const PAYMENT_TRANSIENT_DECLINE_RATE_ENV_VAR: &str = "PAYMENT_TRANSIENT_DECLINE_RATE";
+
+/// Environment variable with the probability, from 0 to 1, that a new authorization is
+/// declined with [`DeclineReason::FraudCheck`]. The order is never authorized.
+pub const PAYMENT_PERMANENT_DECLINE_RATE_ENV_VAR: &str = "PAYMENT_PERMANENT_DECLINE_RATE";
+
+/// Environment variable with the maintenance window of the gateway, in seconds since it
+/// started, as `start-end` (e.g. `30-60`).
+pub const PAYMENT_MAINTENANCE_ENV_VAR: &str = "PAYMENT_MAINTENANCE";
+
+/// Longest latency sampled from an exponential distribution, in means.
+const MAX_EXPONENTIAL_MEANS: f64 = 10.0;
+
+/// How long the gateway takes to answer a request.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum LatencyDistribution {
+    /// Answers right away.
+    #[default]
+    None,
+    /// Always takes the same time.
+    Fixed(Duration),
+    /// Takes any time in the range, all equally likely.
+    Uniform(Range<Duration>),
+    /// Takes an exponentially distributed time with the given mean, so most answers are
+    /// quick and a few are very slow.
+    Exponential(Duration),
+}
+
+impl LatencyDistribution {
+    /// Parses a distribution as written in [`PAYMENT_LATENCY_ENV_VAR`].
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let millis = |value: &str| value.trim().parse().ok().map(Duration::from_millis);
+        if let Some(mean) = value.strip_prefix("exp:") {
+            return Some(LatencyDistribution::Exponential(millis(mean)?));
+        }
+        match value.split_once('-') {
+            Some((min, max)) => {
+                let (min, max) = (millis(min)?, millis(max)?);
+                (min <= max).then_some(LatencyDistribution::Uniform(min..max))
+            }
+            None => Some(LatencyDistribution::Fixed(millis(value)?)),
+        }
+    }
+
+    /// Returns how long to wait before answering.
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        match self {
+            LatencyDistribution::None => Duration::ZERO,
+            LatencyDistribution::Fixed(latency) => *latency,
+            LatencyDistribution::Uniform(range) if range.is_empty() => range.start,
+            LatencyDistribution::Uniform(range) => rng.gen_range(range.clone()),
+            LatencyDistribution::Exponential(mean) => {
+                let means = -(1.0 - rng.r#gen::<f64>()).ln();
+                mean.mul_f64(means.min(MAX_EXPONENTIAL_MEANS))
+            }
+        }
+    }
+}
+
+/// The failures the gateway simulates, so that the retries of the clients and the
+/// coordinators can be exercised. By default it simulates none.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PaymentSimulation {
+    /// How long the gateway takes to answer.
+    pub latency: LatencyDistribution,
+    /// Probability that an answer is lost.
+    pub timeout_rate: f64,
+    /// Probability that a new authorization is declined for a while.
+    pub transient_decline_rate: f64,
+    /// Probability that a new authorization is declined for good.
+    pub permanent_decline_rate: f64,
+    /// Seconds since the gateway started during which it is under maintenance.
+    pub maintenance: Option<Range<u64>>,
+}
+
+/// Returns the simulation configured by the `PAYMENT_*` environment variables, ignoring
+/// the invalid ones.
+pub fn simulation_from_env() -> PaymentSimulation {
+    let var = |name: &str| {
+        std::env::var(name)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+    };
+    let invalid = |name: &str, value: &str| {
+        eprintln!("[Payment] Ignoring invalid {}: '{}'", name, value);
+    };
+    let rate = |name: &str| {
+        let value = var(name)?;
+        match value.trim().parse::<f64>() {
+            Ok(rate) if (0.0..=1.0).contains(&rate) => Some(rate),
+            _ => {
+                invalid(name, &value);
+                None
+            }
+        }
+    };
+    let mut simulation = PaymentSimulation::default();
+    if let Some(value) = var(PAYMENT_LATENCY_ENV_VAR) {
+        match LatencyDistribution::parse(&value) {
+            Some(latency) => simulation.latency = latency,
+            None => invalid(PAYMENT_LATENCY_ENV_VAR, &value),
+        }
+    }
+    simulation.timeout_rate = rate(PAYMENT_TIMEOUT_RATE_ENV_VAR).unwrap_or_default();
+    simulation.transient_decline_rate =
+        rate(PAYMENT_TRANSIENT_DECLINE_RATE_ENV_VAR).unwrap_or_default();
+    simulation.permanent_decline_rate =
+        rate(PAYMENT_PERMANENT_DECLINE_RATE_ENV_VAR).unwrap_or_default();
+    if let Some(value) = var(PAYMENT_MAINTENANCE_ENV_VAR) {
+        match parse_window(&value) {
+            Some(window) => simulation.maintenance = Some(window),
+            None => invalid(PAYMENT_MAINTENANCE_ENV_VAR, &value),
+        }
+    }
+    simulation
+}
+
+/// Parses a maintenance window as written in [`PAYMENT_MAINTENANCE_ENV_VAR`].
+fn parse_window(value: &str) -> Option<Range<u64>> {
+    let (start, end) = value.split_once('-')?;
+    let (start, end) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+    (start < end).then_some(start..end)
+}
+
+impl PaymentSimulation {
+    /// Returns whether the gateway is under maintenance `elapsed` after it started.
+    pub fn in_maintenance(&self, elapsed: Duration) -> bool {
+        self.maintenance
+            .as_ref()
+            .is_some_and(|window| window.contains(&elapsed.as_secs()))
+    }
+
+    /// Returns whether to lose the answer to a request.
+    pub fn times_out(&self, rng: &mut impl Rng) -> bool {
+        rng.gen_bool(self.timeout_rate)
+    }
+
+    /// Returns why to decline a new authorization the client can afford, if it is declined.
+    pub fn decline(&self, rng: &mut impl Rng) -> Option<DeclineReason> {
+        if rng.gen_bool(self.permanent_decline_rate) {
+            Some(DeclineReason::FraudCheck)
+        } else if rng.gen_bool(self.transient_decline_rate) {
+            Some(DeclineReason::IssuerUnavailable)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn parses_the_latency_distributions() {
+        let millis = Duration::from_millis;
+        assert_eq!(
+            LatencyDistribution::parse("50"),
+            Some(LatencyDistribution::Fixed(millis(50)))
+        );
+        assert_eq!(
+            LatencyDistribution::parse(" 20-200 "),
+            Some(LatencyDistribution::Uniform(millis(20)..millis(200)))
+        );
+        assert_eq!(
+            LatencyDistribution::parse("exp:100"),
+            Some(LatencyDistribution::Exponential(millis(100)))
+        );
+        assert_eq!(LatencyDistribution::parse("200-20"), None);
+        assert_eq!(LatencyDistribution::parse("slow"), None);
+    }
+
+    #[test]
+    fn samples_latencies_within_the_distribution() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let millis = Duration::from_millis;
+        let uniform = LatencyDistribution::Uniform(millis(20)..millis(200));
+        let exponential = LatencyDistribution::Exponential(millis(100));
+        for _ in 0..100 {
+            let latency = uniform.sample(&mut rng);
+            assert!(latency >= millis(20) && latency < millis(200));
+            assert!(exponential.sample(&mut rng) <= millis(1000));
+        }
+        assert_eq!(LatencyDistribution::None.sample(&mut rng), Duration::ZERO);
+    }
+
+    #[test]
+    fn is_under_maintenance_only_within_the_window() {
+        let simulation = PaymentSimulation {
+            maintenance: parse_window("30-60"),
+            ..PaymentSimulation::default()
+        };
+        assert!(!simulation.in_maintenance(Duration::from_secs(29)));
+        assert!(simulation.in_maintenance(Duration::from_secs(30)));
+        assert!(!simulation.in_maintenance(Duration::from_secs(60)));
+        assert_eq!(parse_window("60-30"), None);
+    }
+
+    #[test]
+    fn declines_nothing_by_default() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let simulation = PaymentSimulation::default();
+        assert!(!simulation.times_out(&mut rng));
+        assert_eq!(simulation.decline(&mut rng), None);
+
+        let refusing = PaymentSimulation {
+            permanent_decline_rate: 1.0,
+            transient_decline_rate: 1.0,
+            ..PaymentSimulation::default()
+        };
+        assert_eq!(refusing.decline(&mut rng), Some(DeclineReason::FraudCheck));
+        assert!(!DeclineReason::FraudCheck.is_transient());
+    }
+}
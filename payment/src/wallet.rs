@@ -0,0 +1,128 @@
+use common::constants::DEFAULT_CLIENT_BALANCE;
+use std::collections::HashMap;
+
+/// Environment variable with the initial balance of the clients, in cents, as a
+/// comma-separated list of `client=cents` (e.g. `cliente_1=500,cliente_2=0`). The key `*`
+/// sets the balance of the clients not listed, which is [`DEFAULT_CLIENT_BALANCE`] otherwise.
+pub const CLIENT_BALANCES_ENV_VAR: &str = "CLIENT_BALANCES";
+
+/// Returns the wallets seeded from [`CLIENT_BALANCES_ENV_VAR`].
+///
+/// Entries that cannot be parsed are ignored.
+pub fn wallets_from_env() -> Wallets {
+    let mut wallets = Wallets::new(DEFAULT_CLIENT_BALANCE);
+    let Ok(value) = std::env::var(CLIENT_BALANCES_ENV_VAR) else {
+        return wallets;
+    };
+    for entry in value.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let parsed = entry
+            .split_once('=')
+            .and_then(|(client, cents)| Some((client.trim(), cents.trim().parse().ok()?)));
+        match parsed {
+            Some(("*", balance)) => wallets.default_balance = balance,
+            Some((client, balance)) => wallets = wallets.with_balance(client, balance),
+            None => eprintln!(
+                "[Payment] Ignoring invalid {} entry: '{}'",
+                CLIENT_BALANCES_ENV_VAR, entry
+            ),
+        }
+    }
+    wallets
+}
+
+/// The simulated balance of each client, in cents.
+///
+/// Authorizing an order holds its amount, so an order is rejected when the client cannot
+/// afford it, and refunding it gives the amount back. Billing an authorized order does not
+/// change the balance: the amount was already held.
+#[derive(Debug, Clone)]
+pub struct Wallets {
+    /// Balance of the clients seen for the first time.
+    default_balance: u64,
+    /// Balance of each client, by client ID.
+    balances: HashMap<String, u64>,
+}
+
+impl Default for Wallets {
+    fn default() -> Self {
+        Self::new(DEFAULT_CLIENT_BALANCE)
+    }
+}
+
+impl Wallets {
+    /// Creates the wallets, with `default_balance` cents for every client.
+    pub fn new(default_balance: u64) -> Self {
+        Self {
+            default_balance,
+            balances: HashMap::new(),
+        }
+    }
+
+    /// Creates wallets that can afford any order.
+    pub fn unlimited() -> Self {
+        Self::new(u64::MAX)
+    }
+
+    /// Sets the initial balance of `client_id`.
+    pub fn with_balance(mut self, client_id: &str, balance: u64) -> Self {
+        self.balances.insert(client_id.to_string(), balance);
+        self
+    }
+
+    /// Returns the balance of `client_id`.
+    pub fn balance(&self, client_id: &str) -> u64 {
+        self.balances
+            .get(client_id)
+            .copied()
+            .unwrap_or(self.default_balance)
+    }
+
+    /// Holds `amount` from the balance of `client_id`.
+    ///
+    /// # Returns
+    /// Whether the client could afford it. The balance is left untouched otherwise.
+    pub fn hold(&mut self, client_id: &str, amount: u64) -> bool {
+        match self.balance(client_id).checked_sub(amount) {
+            Some(balance) => {
+                self.balances.insert(client_id.to_string(), balance);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Gives `amount` back to `client_id`, after a refund or a top-up.
+    ///
+    /// # Returns
+    /// The new balance of the client.
+    pub fn credit(&mut self, client_id: &str, amount: u64) -> u64 {
+        let balance = self.balance(client_id).saturating_add(amount);
+        self.balances.insert(client_id.to_string(), balance);
+        balance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_what_the_client_cannot_afford() {
+        let mut wallets = Wallets::new(1000).with_balance("bob", 300);
+        assert!(wallets.hold("ana", 600));
+        assert!(!wallets.hold("ana", 600));
+        assert_eq!(wallets.balance("ana"), 400);
+        assert!(!wallets.hold("bob", 600));
+        assert_eq!(wallets.balance("bob"), 300);
+    }
+
+    #[test]
+    fn refunds_and_top_ups_restore_the_balance() {
+        let mut wallets = Wallets::new(0);
+        assert!(!wallets.hold("ana", 500));
+        assert_eq!(wallets.credit("ana", 500), 500);
+        assert!(wallets.hold("ana", 500));
+        assert_eq!(wallets.credit("ana", 200), 200);
+        assert!(Wallets::unlimited().hold("ana", 1_000_000));
+    }
+}
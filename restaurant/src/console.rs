@@ -0,0 +1,90 @@
+use crate::internal_messages::messages::Restock;
+use crate::stock::parse_restock;
+use std::time::Duration;
+
+/// Commands accepted by the restaurant console.
+pub const USAGE: &str = "Commands: pause | resume | list orders | set prep-time <dish> <seconds> | restock [<dish> <units>]";
+
+/// A command typed in the restaurant console.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleCommand {
+    /// Stop accepting new orders.
+    Pause,
+    /// Accept new orders again.
+    Resume,
+    /// Print the orders accepted and not yet handed to a delivery.
+    ListOrders,
+    /// Change the time to cook a dish.
+    SetPrepTime {
+        dish: String,
+        time_to_cook: Duration,
+    },
+    /// Replenish the stock, see [`parse_restock`].
+    Restock(Restock),
+}
+
+/// Parses a command typed in the restaurant console.
+///
+/// ## Commands
+/// - `pause` / `resume`: Stops or starts accepting new orders.
+/// - `list orders`: Prints the open orders.
+/// - `set prep-time <dish> <seconds>`: Changes the time to cook a dish. The dish name can
+///   contain spaces.
+/// - `restock [<dish> <units>]`: Replenishes the stock.
+///
+/// ## Returns
+/// The usage of the command, or of every command if it is unknown.
+pub fn parse_command(line: &str) -> Result<ConsoleCommand, String> {
+    let line = line.trim();
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        ["pause"] => Ok(ConsoleCommand::Pause),
+        ["resume"] => Ok(ConsoleCommand::Resume),
+        ["list", "orders"] => Ok(ConsoleCommand::ListOrders),
+        ["set", "prep-time", ..] => {
+            let usage = || "Usage: set prep-time <dish> <seconds>".to_string();
+            let rest = line
+                .strip_prefix("set")
+                .map(str::trim_start)
+                .and_then(|rest| rest.strip_prefix("prep-time"))
+                .ok_or_else(usage)?;
+            let (dish, seconds) = rest.trim().rsplit_once(' ').ok_or_else(usage)?;
+            let seconds: u64 = seconds.parse().map_err(|_| usage())?;
+            Ok(ConsoleCommand::SetPrepTime {
+                dish: dish.trim().to_string(),
+                time_to_cook: Duration::from_secs(seconds),
+            })
+        }
+        ["restock", ..] => parse_restock(line).map(ConsoleCommand::Restock),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_console_commands() {
+        assert_eq!(parse_command(" pause "), Ok(ConsoleCommand::Pause));
+        assert_eq!(parse_command("resume"), Ok(ConsoleCommand::Resume));
+        assert_eq!(
+            parse_command("list  orders"),
+            Ok(ConsoleCommand::ListOrders)
+        );
+        assert_eq!(
+            parse_command("set prep-time Papas fritas 6"),
+            Ok(ConsoleCommand::SetPrepTime {
+                dish: "Papas fritas".to_string(),
+                time_to_cook: Duration::from_secs(6),
+            })
+        );
+        assert_eq!(
+            parse_command("restock"),
+            Ok(ConsoleCommand::Restock(Restock { dish: None }))
+        );
+        assert!(parse_command("set prep-time Pizza").is_err());
+        assert!(parse_command("set prep-time Pizza fast").is_err());
+        assert_eq!(parse_command("cook Pizza"), Err(USAGE.to_string()));
+    }
+}
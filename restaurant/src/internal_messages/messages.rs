@@ -1,7 +1,8 @@
 use crate::restaurant_actors::chef::Chef;
 use actix::{Addr, Message};
-use common::types::dtos::OrderDTO;
+use common::types::dtos::{OrderDTO, OrderItem};
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 /// Message sent from the restaurant to the kitchen to enqueue a new order for preparation.
 ///
@@ -12,16 +13,18 @@ pub struct SendToKitchen {
     pub order: OrderDTO,
 }
 
-/// Message sent from the kitchen to a chef to assign an order for preparation.
+/// Message sent from the kitchen to a chef to assign an item of an order for preparation.
 ///
-/// Contains the [`OrderDTO`](../../common/types/dtos/struct.OrderDTO.html) to be cooked.
+/// Contains the ID of the order, the item to be cooked and the time it takes to cook it.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
 #[rtype(result = "()")]
 pub struct AssignToChef {
-    pub order: OrderDTO,
+    pub order_id: u64,
+    pub item: OrderItem,
+    pub time_to_cook: Duration,
 }
 
-/// Message sent from a chef to the delivery assigner when an order is ready for delivery.
+/// Message sent from the kitchen to the delivery assigner when an order is ready for delivery.
 ///
 /// Contains the [`OrderDTO`](../../common/types/dtos/struct.OrderDTO.html) that is ready.
 #[derive(Message, Debug, Clone, Serialize, Deserialize)]
@@ -30,12 +33,49 @@ pub struct SendThisOrder {
     pub order: OrderDTO,
 }
 
-/// Message sent from a chef to the kitchen indicating the chef is available for a new order.
+/// Message sent from a chef to the kitchen indicating the chef finished an item and is
+/// available for a new one.
 ///
-/// Contains the address of the chef and the last order handled.
+/// Contains the address of the chef and the ID of the order of the item cooked.
 #[derive(Message, Debug, Clone)]
 #[rtype(result = "()")]
 pub struct IAmAvailable {
     pub chef_addr: Addr<Chef>,
-    pub order: OrderDTO,
+    pub order_id: u64,
+}
+
+/// Message sent to the restaurant to replenish its stock.
+///
+/// Contains the dish to replenish and the units to add, or `None` to refill every dish to
+/// its full stock.
+#[derive(Message, Debug, Clone, PartialEq)]
+#[rtype(result = "()")]
+pub struct Restock {
+    pub dish: Option<(String, u32)>,
+}
+
+/// Message sent to the restaurant to stop or start accepting new orders.
+///
+/// While paused, the authorized orders are rejected as if the restaurant were closed. The
+/// orders already accepted are still prepared.
+#[derive(Message, Debug, Clone, PartialEq)]
+#[rtype(result = "()")]
+pub struct SetAcceptingOrders {
+    pub accepting: bool,
+}
+
+/// Message sent to the restaurant to print the orders accepted and not yet handed to a
+/// delivery.
+#[derive(Message, Debug, Clone, PartialEq)]
+#[rtype(result = "()")]
+pub struct ListOrders;
+
+/// Message sent to the kitchen, through the restaurant, to change the time to cook a dish.
+///
+/// Only the orders assigned to a chef from then on take the new time.
+#[derive(Message, Debug, Clone, PartialEq)]
+#[rtype(result = "()")]
+pub struct SetPreparationTime {
+    pub dish: String,
+    pub time_to_cook: Duration,
 }
@@ -1,2 +1,6 @@
+pub mod console;
 pub mod internal_messages;
+pub mod policy;
+pub mod preparation;
 pub mod restaurant_actors;
+pub mod stock;
@@ -1,50 +1,94 @@
 use actix::prelude::*;
-use common::constants::{
-    BASE_PORT, NUM_COORDINATORS, RESTAURANT_SUCCESS_PROBABILITY, SERVER_IP_ADDRESS,
-};
-use common::types::restaurant_info::RestaurantInfo;
-use common::utils::{get_rand_f32_tuple, print_welcome_message};
+use clap::Parser;
+use common::cli::UserArgs;
+use common::constants::DEREGISTER_WAIT;
+use common::messages::Deregister;
+use common::types::restaurant_info::{RestaurantInfo, normalize_tags};
+use common::types::tenant::TenantId;
+use common::utils::print_welcome_message;
+use restaurant::console::{ConsoleCommand, parse_command};
+use restaurant::internal_messages::messages::{ListOrders, SetAcceptingOrders, SetPreparationTime};
+use restaurant::policy::{AcceptancePolicy, PolicyArgs};
 use restaurant::restaurant_actors::restaurant::Restaurant;
-use std::env;
-use std::net::SocketAddr;
+use tokio::io::{AsyncBufReadExt, BufReader};
 use tokio::signal::ctrl_c;
 
+/// Restaurant of PedidosRust: accepts and cooks the orders of the clients.
+#[derive(Parser)]
+struct Args {
+    /// ID of the restaurant (same as --id)
+    #[arg(value_name = "RESTAURANT_ID", conflicts_with = "id")]
+    restaurant_id: Option<String>,
+    #[command(flatten)]
+    user: UserArgs,
+    #[command(flatten)]
+    policy: PolicyArgs,
+    /// Comma-separated cuisines or categories of the restaurant, e.g. pizza,italiana, which
+    /// the clients can search for
+    #[arg(long, value_delimiter = ',', value_name = "TAG,...")]
+    tags: Vec<String>,
+}
+
 #[actix::main]
 async fn main() -> std::io::Result<()> {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        eprintln!("Uso: {} <restaurant_id>", args[0]);
-        std::process::exit(1);
-    }
+    let args = Args::parse();
 
-    let id = args[1].clone();
+    let identity = args.user.identity(args.restaurant_id, "restaurant")?;
+    let policy = AcceptancePolicy::from(args.policy);
 
-    let servers: Vec<SocketAddr> = (0..NUM_COORDINATORS)
-        .map(|i| {
-            format!("{}:{}", SERVER_IP_ADDRESS, BASE_PORT + i)
-                .parse()
-                .expect("Dirección IP inválida")
-        })
-        .collect();
+    let servers = args.user.servers();
+    args.user.apply_bind();
 
-    let position = get_rand_f32_tuple();
+    let position = args.user.position();
 
     print_welcome_message();
 
-    let restaurant = Restaurant::new(
-        RestaurantInfo { id, position },
-        RESTAURANT_SUCCESS_PROBABILITY,
-        servers,
-    )
-    .await;
+    let info = RestaurantInfo {
+        id: identity.user_id,
+        position,
+        tags: normalize_tags(args.tags),
+        tenant_id: TenantId::from_env(),
+    };
+    let restaurant = Restaurant::new(info, policy, servers)
+        .await
+        .with_secret(identity.secret);
 
-    restaurant.start();
+    let restaurant = restaurant.start();
+    actix::spawn(read_commands(restaurant.clone()));
 
     tokio::select! {
         _ = ctrl_c() => {
+            // Se avisa a los servidores antes de cerrar la conexión
+            if restaurant.send(Deregister).await.is_ok() {
+                tokio::time::sleep(DEREGISTER_WAIT).await;
+            }
             actix::System::current().stop();
         }
     }
 
     Ok(())
 }
+
+/// Lee comandos de la consola y se los envía al restaurante.
+async fn read_commands(restaurant: Addr<Restaurant>) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_command(&line) {
+            Ok(ConsoleCommand::Pause) => {
+                restaurant.do_send(SetAcceptingOrders { accepting: false })
+            }
+            Ok(ConsoleCommand::Resume) => {
+                restaurant.do_send(SetAcceptingOrders { accepting: true })
+            }
+            Ok(ConsoleCommand::ListOrders) => restaurant.do_send(ListOrders),
+            Ok(ConsoleCommand::SetPrepTime { dish, time_to_cook }) => {
+                restaurant.do_send(SetPreparationTime { dish, time_to_cook })
+            }
+            Ok(ConsoleCommand::Restock(restock)) => restaurant.do_send(restock),
+            Err(usage) => eprintln!("{}", usage),
+        }
+    }
+}
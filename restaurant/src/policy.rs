@@ -0,0 +1,493 @@
+use chrono::{Local, Timelike};
+use clap::Args;
+use common::constants::{NUMBER_OF_CHEFS, RESTAURANT_SUCCESS_PROBABILITY};
+use common::types::dtos::OrderDTO;
+use common::types::rejection_reason::RejectionReason;
+use common::utils::random_bool_by_given_probability;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+/// Rules a restaurant applies to decide whether to accept an authorized order.
+///
+/// ## Rules
+/// Evaluated in order, the first one that fails rejects the order:
+/// - `rejected_dishes`: Dishes the restaurant never prepares.
+/// - `business_hours`: Hours (local time) in which the restaurant takes orders.
+/// - `max_open_orders`: Maximum number of orders accepted but not yet handed to a delivery.
+/// - `probability`: Probability of accepting an order that passed every other rule.
+///
+/// The restaurant also rejects the dishes it ran out of, see [`Stock`](crate::stock::Stock).
+/// The time to cook each dish and the number of chefs are kept here too, since they are
+/// configured with the same flags.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AcceptancePolicy {
+    /// Probability of accepting an order.
+    pub probability: f32,
+    /// Maximum number of open orders, if limited.
+    pub max_open_orders: Option<usize>,
+    /// Opening and closing hour (`0..24`). Closing before opening means the restaurant
+    /// closes after midnight, and `0-24` that it never closes.
+    pub business_hours: Option<(u32, u32)>,
+    /// Names of the dishes that are always rejected.
+    pub rejected_dishes: HashSet<String>,
+    /// Units of each dish the restaurant starts with and is restocked to. Dishes not listed
+    /// are unlimited.
+    pub stock: HashMap<String, u32>,
+    /// Time between two automatic restocks, if any.
+    pub restock_interval: Option<Duration>,
+    /// Time to cook each dish that does not take the default time.
+    pub cook_times: HashMap<String, Duration>,
+    /// Number of chefs of the kitchen, which is the number of items cooked at once.
+    pub chefs: usize,
+}
+
+/// Reason why an [`AcceptancePolicy`] rejected an order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RejectReason {
+    /// The dish is in the restaurant's blacklist.
+    DishNotServed(String),
+    /// The order arrived outside business hours.
+    Closed { hour: u32 },
+    /// The restaurant already has too many open orders.
+    TooManyOpenOrders(usize),
+    /// The order was rejected at random.
+    Unlucky,
+    /// The restaurant ran out of the dish.
+    OutOfStock(String),
+    /// The restaurant was paused from its console.
+    Paused,
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RejectReason::DishNotServed(dish) => write!(f, "dish '{}' is not served", dish),
+            RejectReason::Closed { hour } => write!(f, "closed at {}hs", hour),
+            RejectReason::TooManyOpenOrders(open) => {
+                write!(f, "already has {} open orders", open)
+            }
+            RejectReason::Unlucky => write!(f, "rejected by probability"),
+            RejectReason::OutOfStock(dish) => write!(f, "out of '{}'", dish),
+            RejectReason::Paused => write!(f, "not accepting orders"),
+        }
+    }
+}
+
+impl RejectReason {
+    /// Returns the reason told to the server, and through it to the client.
+    pub fn code(&self) -> RejectionReason {
+        match self {
+            RejectReason::Closed { .. } | RejectReason::Paused => RejectionReason::Closed,
+            // Para el cliente, un plato que no se prepara es un plato que no hay
+            RejectReason::DishNotServed(_) | RejectReason::OutOfStock(_) => {
+                RejectionReason::OutOfStock
+            }
+            RejectReason::TooManyOpenOrders(_) | RejectReason::Unlucky => RejectionReason::TooBusy,
+        }
+    }
+}
+
+impl Default for AcceptancePolicy {
+    fn default() -> Self {
+        Self {
+            probability: RESTAURANT_SUCCESS_PROBABILITY,
+            max_open_orders: None,
+            business_hours: None,
+            rejected_dishes: HashSet::new(),
+            stock: HashMap::new(),
+            restock_interval: None,
+            cook_times: HashMap::new(),
+            chefs: NUMBER_OF_CHEFS,
+        }
+    }
+}
+
+/// Command line flags of the [`AcceptancePolicy`] of a restaurant.
+#[derive(Debug, Clone, Default, Args)]
+pub struct PolicyArgs {
+    /// Probability of accepting an order (0.0..=1.0)
+    #[arg(long, value_parser = parse_probability)]
+    pub probability: Option<f32>,
+    /// Maximum number of open orders
+    #[arg(long, value_name = "N")]
+    pub max_open_orders: Option<usize>,
+    /// Business hours, e.g. 9-23, 20-2 or 0-24 to never close
+    #[arg(long, value_name = "OPEN-CLOSE", value_parser = parse_hours)]
+    pub hours: Option<(u32, u32)>,
+    /// Dish that is never accepted. Can be repeated
+    #[arg(long, value_name = "DISH")]
+    pub reject_dish: Vec<String>,
+    /// Units of a dish in stock. Can be repeated
+    #[arg(long, value_name = "DISH=UNITS", value_parser = parse_stock)]
+    pub stock: Vec<(String, u32)>,
+    /// Seconds between two automatic restocks
+    #[arg(long, value_name = "SECONDS", value_parser = parse_restock_interval)]
+    pub restock_every: Option<u64>,
+    /// Seconds to cook a dish. Can be repeated
+    #[arg(long, value_name = "DISH=SECONDS", value_parser = parse_cook_time)]
+    pub cook_time: Vec<(String, u64)>,
+    /// Number of chefs of the kitchen
+    #[arg(long, value_name = "N", value_parser = parse_chefs)]
+    pub chefs: Option<usize>,
+}
+
+impl From<PolicyArgs> for AcceptancePolicy {
+    /// Builds a policy from its command line flags, starting from the default one.
+    fn from(args: PolicyArgs) -> Self {
+        let default = Self::default();
+        Self {
+            probability: args.probability.unwrap_or(default.probability),
+            max_open_orders: args.max_open_orders,
+            business_hours: args.hours,
+            rejected_dishes: args.reject_dish.into_iter().collect(),
+            stock: args.stock.into_iter().collect(),
+            restock_interval: args.restock_every.map(Duration::from_secs),
+            cook_times: args
+                .cook_time
+                .into_iter()
+                .map(|(dish, seconds)| (dish, Duration::from_secs(seconds)))
+                .collect(),
+            chefs: args.chefs.unwrap_or(default.chefs),
+        }
+    }
+}
+
+impl AcceptancePolicy {
+    /// Decides whether to accept `order`, given the number of orders the restaurant
+    /// currently has open.
+    pub fn evaluate(&self, order: &OrderDTO, open_orders: usize) -> Result<(), RejectReason> {
+        self.evaluate_at(order, open_orders, Local::now().hour())
+    }
+
+    /// Decides whether to accept `order` at `hour` (local time).
+    fn evaluate_at(
+        &self,
+        order: &OrderDTO,
+        open_orders: usize,
+        hour: u32,
+    ) -> Result<(), RejectReason> {
+        if let Some(item) = order
+            .items
+            .iter()
+            .find(|item| self.rejected_dishes.contains(&item.dish))
+        {
+            return Err(RejectReason::DishNotServed(item.dish.clone()));
+        }
+        if let Some((open, close)) = self.business_hours
+            && !is_open(open, close, hour)
+        {
+            return Err(RejectReason::Closed { hour });
+        }
+        if let Some(max) = self.max_open_orders
+            && open_orders >= max
+        {
+            return Err(RejectReason::TooManyOpenOrders(open_orders));
+        }
+        if !random_bool_by_given_probability(self.probability) {
+            return Err(RejectReason::Unlucky);
+        }
+        Ok(())
+    }
+
+    /// Returns whether the restaurant is within its business hours now, and how long until
+    /// it opens or closes.
+    pub fn opening_now(&self) -> (bool, Option<Duration>) {
+        self.opening_at(Local::now().num_seconds_from_midnight())
+    }
+
+    /// Returns whether the restaurant is within its business hours `seconds` after midnight
+    /// (local time), and how long until it opens or closes. Without business hours the
+    /// restaurant is always open, and with the same opening and closing hour always closed.
+    fn opening_at(&self, seconds: u32) -> (bool, Option<Duration>) {
+        let Some((open, close)) = self.business_hours else {
+            return (true, None);
+        };
+        let now_open = is_open(open, close, seconds / 3600);
+        if open == close {
+            return (now_open, None);
+        }
+        let change = if now_open { close } else { open };
+        let until =
+            match (change * 3600 + SECONDS_PER_DAY - seconds % SECONDS_PER_DAY) % SECONDS_PER_DAY {
+                // Abierto todo el día: se vuelve a mirar mañana
+                0 => SECONDS_PER_DAY,
+                until => until,
+            };
+        (now_open, Some(Duration::from_secs(u64::from(until))))
+    }
+}
+
+/// Seconds in a day, to wrap the business hours around midnight.
+const SECONDS_PER_DAY: u32 = 24 * 3600;
+
+/// Parses a probability, which must be within `0.0..=1.0`.
+fn parse_probability(value: &str) -> Result<f32, String> {
+    let probability: f32 = value
+        .parse()
+        .map_err(|_| format!("Invalid probability: {}", value))?;
+    if !(0.0..=1.0).contains(&probability) {
+        return Err(format!("Probability out of range: {}", value));
+    }
+    Ok(probability)
+}
+
+/// Parses the seconds between two restocks, which must be positive.
+fn parse_restock_interval(value: &str) -> Result<u64, String> {
+    match value.parse() {
+        Ok(0) => Err(format!("Restock interval must be positive: {}", value)),
+        Ok(seconds) => Ok(seconds),
+        Err(_) => Err(format!("Invalid restock interval: {}", value)),
+    }
+}
+
+/// Parses the number of chefs, which must be at least one.
+fn parse_chefs(value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(0) => Err(format!("A kitchen needs at least one chef: {}", value)),
+        Ok(chefs) => Ok(chefs),
+        Err(_) => Err(format!("Invalid number of chefs: {}", value)),
+    }
+}
+
+/// Parses business hours written as `<open>-<close>`. The same opening and closing hour is
+/// rejected, since it would leave the restaurant always closed.
+fn parse_hours(value: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("Invalid hours (expected <open>-<close>): {}", value);
+    let (open, close) = value.split_once('-').ok_or_else(invalid)?;
+    let open: u32 = open.trim().parse().map_err(|_| invalid())?;
+    let close: u32 = close.trim().parse().map_err(|_| invalid())?;
+    if open > 23 || close > 24 {
+        return Err(invalid());
+    }
+    if open == close {
+        return Err(format!(
+            "Opening and closing hour are the same (use 0-24 to never close): {}",
+            value
+        ));
+    }
+    Ok((open, close))
+}
+
+/// Parses the stock of a dish written as `<name>=<units>`.
+fn parse_stock(value: &str) -> Result<(String, u32), String> {
+    let invalid = || format!("Invalid stock (expected <dish>=<units>): {}", value);
+    let (dish, units) = value.rsplit_once('=').ok_or_else(invalid)?;
+    let units: u32 = units.trim().parse().map_err(|_| invalid())?;
+    if dish.trim().is_empty() {
+        return Err(invalid());
+    }
+    Ok((dish.trim().to_string(), units))
+}
+
+/// Parses the time to cook a dish written as `<name>=<seconds>`.
+fn parse_cook_time(value: &str) -> Result<(String, u64), String> {
+    let invalid = || format!("Invalid cook time (expected <dish>=<seconds>): {}", value);
+    let (dish, seconds) = value.rsplit_once('=').ok_or_else(invalid)?;
+    let seconds: u64 = seconds.trim().parse().map_err(|_| invalid())?;
+    if dish.trim().is_empty() {
+        return Err(invalid());
+    }
+    Ok((dish.trim().to_string(), seconds))
+}
+
+/// Returns whether `hour` falls within business hours, which may span midnight.
+fn is_open(open: u32, close: u32, hour: u32) -> bool {
+    if open <= close {
+        (open..close).contains(&hour)
+    } else {
+        hour >= open || hour < close
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::dtos::OrderItem;
+    use common::types::order_status::OrderStatus;
+
+    fn order(dishes: &[&str]) -> OrderDTO {
+        OrderDTO {
+            status: OrderStatus::Authorized,
+            ..OrderDTO::new(
+                1,
+                "client_1",
+                "restaurant_1",
+                dishes.iter().map(|dish| OrderItem::new(dish, 1)).collect(),
+            )
+        }
+    }
+
+    fn with_hours(open: u32, close: u32) -> AcceptancePolicy {
+        AcceptancePolicy {
+            business_hours: Some((open, close)),
+            ..AcceptancePolicy::default()
+        }
+    }
+
+    #[test]
+    fn tells_how_long_until_the_restaurant_opens_or_closes() {
+        let hour = |h: u32| h * 3600;
+        assert_eq!(
+            AcceptancePolicy::default().opening_at(hour(3)),
+            (true, None)
+        );
+
+        let day = with_hours(9, 23);
+        assert_eq!(
+            day.opening_at(hour(8) + 1800),
+            (false, Some(Duration::from_secs(1800)))
+        );
+        assert_eq!(
+            day.opening_at(hour(9)),
+            (true, Some(Duration::from_secs(14 * 3600)))
+        );
+        assert_eq!(
+            day.opening_at(hour(23)),
+            (false, Some(Duration::from_secs(10 * 3600)))
+        );
+
+        // Abre a la noche y cierra pasada la medianoche
+        let night = with_hours(20, 2);
+        assert_eq!(
+            night.opening_at(hour(22)),
+            (true, Some(Duration::from_secs(4 * 3600)))
+        );
+        assert_eq!(
+            night.opening_at(hour(2)),
+            (false, Some(Duration::from_secs(18 * 3600)))
+        );
+        assert_eq!(
+            with_hours(0, 24).opening_at(0),
+            (true, Some(Duration::from_secs(24 * 3600)))
+        );
+        assert_eq!(with_hours(5, 5).opening_at(hour(5)), (false, None));
+    }
+
+    #[test]
+    fn parses_the_business_hours() {
+        let cases = [
+            ("9-23", Ok((9, 23))),
+            ("20-2", Ok((20, 2))),
+            (" 0 - 24 ", Ok((0, 24))),
+            ("9-9", Err(())),
+            ("24-2", Err(())),
+            ("9-25", Err(())),
+            ("9", Err(())),
+            ("nueve-23", Err(())),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(parse_hours(value).map_err(|_| ()), expected, "{}", value);
+        }
+    }
+
+    #[test]
+    fn tells_whether_an_hour_is_within_business_hours() {
+        let cases = [
+            ((9, 23), 8, false),
+            ((9, 23), 9, true),
+            ((9, 23), 22, true),
+            ((9, 23), 23, false),
+            // Cierra pasada la medianoche
+            ((20, 2), 19, false),
+            ((20, 2), 20, true),
+            ((20, 2), 0, true),
+            ((20, 2), 1, true),
+            ((20, 2), 2, false),
+            ((0, 24), 0, true),
+            ((0, 24), 23, true),
+        ];
+        for ((open, close), hour, expected) in cases {
+            assert_eq!(
+                is_open(open, close, hour),
+                expected,
+                "{}-{} at {}",
+                open,
+                close,
+                hour
+            );
+        }
+    }
+
+    #[test]
+    fn builds_the_policy_from_its_flags() {
+        assert_eq!(
+            AcceptancePolicy::from(PolicyArgs::default()),
+            AcceptancePolicy::default()
+        );
+
+        let policy = AcceptancePolicy::from(PolicyArgs {
+            probability: Some(0.5),
+            max_open_orders: Some(3),
+            hours: Some((20, 2)),
+            reject_dish: vec!["Sushi".to_string()],
+            stock: vec![("Pizza".to_string(), 4)],
+            restock_every: Some(60),
+            cook_time: vec![("Pizza".to_string(), 10)],
+            chefs: Some(2),
+        });
+        assert_eq!(policy.probability, 0.5);
+        assert_eq!(policy.max_open_orders, Some(3));
+        assert_eq!(policy.business_hours, Some((20, 2)));
+        assert!(policy.rejected_dishes.contains("Sushi"));
+        assert_eq!(policy.stock.get("Pizza"), Some(&4));
+        assert_eq!(policy.restock_interval, Some(Duration::from_secs(60)));
+        assert_eq!(
+            policy.cook_times.get("Pizza"),
+            Some(&Duration::from_secs(10))
+        );
+        assert_eq!(policy.chefs, 2);
+    }
+
+    #[test]
+    fn applies_the_rules_in_order() {
+        let policy = AcceptancePolicy {
+            probability: 1.0,
+            max_open_orders: Some(2),
+            business_hours: Some((20, 2)),
+            rejected_dishes: HashSet::from(["Sushi".to_string()]),
+            ..AcceptancePolicy::default()
+        };
+        let cases = [
+            (&["Pizza"][..], 0, 21, Ok(())),
+            (&["Pizza"][..], 1, 1, Ok(())),
+            (
+                &["Pizza", "Sushi"][..],
+                5,
+                12,
+                Err(RejectReason::DishNotServed("Sushi".to_string())),
+            ),
+            (
+                &["Pizza"][..],
+                5,
+                12,
+                Err(RejectReason::Closed { hour: 12 }),
+            ),
+            (
+                &["Pizza"][..],
+                2,
+                23,
+                Err(RejectReason::TooManyOpenOrders(2)),
+            ),
+        ];
+        for (dishes, open_orders, hour, expected) in cases {
+            assert_eq!(
+                policy.evaluate_at(&order(dishes), open_orders, hour),
+                expected,
+                "{:?} with {} open orders at {}",
+                dishes,
+                open_orders,
+                hour
+            );
+        }
+
+        let unlucky = AcceptancePolicy {
+            probability: 0.0,
+            ..AcceptancePolicy::default()
+        };
+        assert_eq!(
+            unlucky.evaluate(&order(&["Pizza"]), 0),
+            Err(RejectReason::Unlucky)
+        );
+    }
+}
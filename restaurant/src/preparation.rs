@@ -0,0 +1,152 @@
+use common::constants::DEFAULT_TIME_TO_COOK;
+use common::types::dtos::OrderItem;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::Duration;
+
+/// Time a chef takes to cook each dish.
+///
+/// Dishes without a specific time take [`DEFAULT_TIME_TO_COOK`] seconds.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PreparationTimes {
+    /// Time to cook each dish with a specific time.
+    per_dish: HashMap<String, Duration>,
+}
+
+impl PreparationTimes {
+    /// Creates the preparation times of a kitchen.
+    ///
+    /// ## Arguments
+    /// * `per_dish` - Time to cook each dish that does not take the default time.
+    pub fn new(per_dish: HashMap<String, Duration>) -> Self {
+        Self { per_dish }
+    }
+
+    /// Sets the time a chef takes to cook `dish`.
+    pub fn set(&mut self, dish: &str, time_to_cook: Duration) {
+        self.per_dish.insert(dish.to_string(), time_to_cook);
+    }
+
+    /// Returns the time a chef takes to cook `dish`.
+    pub fn time_to_cook(&self, dish: &str) -> Duration {
+        self.per_dish
+            .get(dish)
+            .copied()
+            .unwrap_or(Duration::from_secs(DEFAULT_TIME_TO_COOK))
+    }
+
+    /// Returns the time a chef takes to cook every unit of `item`, one after the other.
+    pub fn time_to_cook_item(&self, item: &OrderItem) -> Duration {
+        self.time_to_cook(&item.dish) * item.quantity
+    }
+}
+
+/// Estimates how long an order takes to be ready, counting the items queued before it.
+///
+/// Each item goes to the first chef to become free, as the kitchen does, so the items of
+/// an order are cooked at the same time when there are enough chefs. The order is ready
+/// when its slowest item is.
+///
+/// ## Arguments
+/// * `chefs_free_in` - Time left for each chef to become free, zero if already available.
+/// * `queued` - Time to cook each item waiting ahead, in queue order.
+/// * `items` - Time to cook each item of the order being estimated, in queue order.
+pub fn estimate_ready_in(
+    chefs_free_in: impl IntoIterator<Item = Duration>,
+    queued: impl IntoIterator<Item = Duration>,
+    items: impl IntoIterator<Item = Duration>,
+) -> Duration {
+    let mut chefs: BinaryHeap<Reverse<Duration>> = chefs_free_in.into_iter().map(Reverse).collect();
+    let mut cook = |time_to_cook: Duration| {
+        let Reverse(free_in) = chefs.pop()?;
+        chefs.push(Reverse(free_in + time_to_cook));
+        Some(free_in + time_to_cook)
+    };
+    for time_to_cook in queued {
+        cook(time_to_cook);
+    }
+    items
+        .into_iter()
+        .map(|time_to_cook| cook(time_to_cook).unwrap_or(time_to_cook))
+        .max()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_the_default_time_for_dishes_without_a_specific_one() {
+        let times = PreparationTimes::new(HashMap::from([(
+            "Pizza".to_string(),
+            Duration::from_secs(3),
+        )]));
+        assert_eq!(times.time_to_cook("Pizza"), Duration::from_secs(3));
+        assert_eq!(
+            times.time_to_cook("Empanadas"),
+            Duration::from_secs(DEFAULT_TIME_TO_COOK)
+        );
+    }
+
+    #[test]
+    fn adds_up_the_time_of_every_unit_of_an_item() {
+        let times = PreparationTimes::new(HashMap::from([(
+            "Pizza".to_string(),
+            Duration::from_secs(3),
+        )]));
+        assert_eq!(
+            times.time_to_cook_item(&OrderItem::new("Pizza", 2)),
+            Duration::from_secs(6)
+        );
+        assert_eq!(
+            times.time_to_cook_item(&OrderItem::new("Empanadas", 1)),
+            Duration::from_secs(DEFAULT_TIME_TO_COOK)
+        );
+    }
+
+    #[test]
+    fn changes_the_time_of_a_dish() {
+        let mut times = PreparationTimes::new(HashMap::new());
+        times.set("Pizza", Duration::from_secs(2));
+        assert_eq!(times.time_to_cook("Pizza"), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn waits_for_the_first_chef_to_become_free() {
+        let secs = Duration::from_secs;
+        assert_eq!(
+            estimate_ready_in([secs(0), secs(4)], [], [secs(5)]),
+            secs(5)
+        );
+        // El primer plato en cola ocupa al chef libre, este espera al que termina en 4s
+        assert_eq!(
+            estimate_ready_in([secs(0), secs(4)], [secs(6)], [secs(5)]),
+            secs(9)
+        );
+        assert_eq!(
+            estimate_ready_in([secs(2), secs(4)], [secs(6), secs(1)], [secs(5)]),
+            secs(10)
+        );
+    }
+
+    #[test]
+    fn cooks_the_items_of_an_order_at_the_same_time() {
+        let secs = Duration::from_secs;
+        // Con tres chefs libres, el pedido está listo cuando termina el plato más lento
+        assert_eq!(
+            estimate_ready_in([secs(0); 3], [], [secs(6), secs(2), secs(4)]),
+            secs(6)
+        );
+        // Con dos, el tercer plato espera al chef que termina primero
+        assert_eq!(
+            estimate_ready_in([secs(0); 2], [], [secs(6), secs(2), secs(4)]),
+            secs(6)
+        );
+        assert_eq!(
+            estimate_ready_in([secs(0); 2], [], [secs(6), secs(2), secs(5)]),
+            secs(7)
+        );
+        assert_eq!(estimate_ready_in([secs(0); 2], [], []), secs(0));
+    }
+}
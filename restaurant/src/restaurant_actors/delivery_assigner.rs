@@ -10,7 +10,7 @@ use common::{
     },
     types::{dtos::OrderDTO, order_status::OrderStatus, restaurant_info::RestaurantInfo},
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 /// The `DeliveryAssigner` actor is responsible for tracking orders that are ready for delivery,
 /// assigning them to available delivery personnel, and notifying the restaurant and delivery actors.
@@ -25,6 +25,8 @@ pub struct DeliveryAssigner {
     pub ready_orders: HashMap<u64, OrderDTO>,
     /// Mapping of orders to assigned delivery IDs.
     pub orders_delivery: HashMap<u64, String>,
+    /// Orders cancelled while they were still in the kitchen.
+    pub cancelled_orders: HashSet<u64>,
     /// Address of the parent restaurant actor.
     pub my_restaurant: Addr<Restaurant>,
     /// Logger for delivery assigner events.
@@ -47,6 +49,7 @@ impl DeliveryAssigner {
             restaurant_info,
             ready_orders: HashMap::new(),
             orders_delivery: HashMap::new(),
+            cancelled_orders: HashSet::new(),
             my_restaurant: restaurant_addr,
             logger,
         }
@@ -69,17 +72,29 @@ impl Handler<SendThisOrder> for DeliveryAssigner {
     fn handle(&mut self, msg: SendThisOrder, _ctx: &mut Self::Context) -> Self::Result {
         self.logger.info(format!(
             "Received order to send: {:?} for: {:?}",
-            msg.order.dish_name, msg.order.client_id
+            msg.order.summary(),
+            msg.order.client_id
         ));
+        // Si el pedido se canceló mientras se cocinaba, no se busca delivery
+        if self.cancelled_orders.remove(&msg.order.order_id) {
+            self.logger.info(format!(
+                "Order {} was cancelled, discarding it",
+                msg.order.order_id
+            ));
+            return;
+        }
         let mut order = msg.order.clone();
         order.status = OrderStatus::ReadyForDelivery;
         self.ready_orders.insert(order.order_id, order.clone());
         self.my_restaurant.do_send(UpdateOrderStatus {
             order: order.clone(),
+            reason: None,
+            kitchen_load: None,
         });
         self.my_restaurant.do_send(RequestNearbyDelivery {
             order: order.clone(),
             restaurant_info: self.restaurant_info.clone(),
+            radius: None,
         });
     }
 }
@@ -125,9 +140,30 @@ impl Handler<DeliveryAvailable> for DeliveryAssigner {
     }
 }
 
+/// Handles [`UpdateOrderStatus`] messages.
+///
+/// Receives notification from the server that the delivery assigned to an order disconnected
+/// and the order is ready for delivery again. The order waits for the next available delivery,
+/// even if it was dispatched before this restaurant reconnected.
+impl Handler<UpdateOrderStatus> for DeliveryAssigner {
+    type Result = ();
+
+    fn handle(&mut self, msg: UpdateOrderStatus, _ctx: &mut Self::Context) -> Self::Result {
+        let order_id = msg.order.order_id;
+        if let Some(delivery_id) = self.orders_delivery.remove(&order_id) {
+            self.logger.warn(format!(
+                "Delivery '{}' was lost, waiting for another one for order {}",
+                delivery_id, order_id
+            ));
+        }
+        self.ready_orders.insert(order_id, msg.order);
+    }
+}
+
 /// Handles [`CancelOrder`] messages.
 ///
-/// Receives a cancellation request for an order and removes it from the ready queue.
+/// Receives a cancellation request for an order and removes it from the ready queue. If the
+/// order is not ready yet, it is discarded when it leaves the kitchen.
 impl Handler<CancelOrder> for DeliveryAssigner {
     type Result = ();
 
@@ -135,7 +171,9 @@ impl Handler<CancelOrder> for DeliveryAssigner {
         self.logger
             .warn(format!("Cancelling order: {}", msg.order.order_id));
         // Remove the order from ready orders if it exists
-        self.ready_orders.remove(&msg.order.order_id);
+        if self.ready_orders.remove(&msg.order.order_id).is_none() {
+            self.cancelled_orders.insert(msg.order.order_id);
+        }
     }
 }
 
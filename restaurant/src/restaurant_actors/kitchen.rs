@@ -1,29 +1,59 @@
-use crate::internal_messages::messages::{AssignToChef, IAmAvailable, SendToKitchen};
+use crate::internal_messages::messages::{
+    AssignToChef, IAmAvailable, SendThisOrder, SendToKitchen, SetPreparationTime,
+};
+use crate::preparation::{PreparationTimes, estimate_ready_in};
 use crate::restaurant_actors::chef::Chef;
 use crate::restaurant_actors::delivery_assigner::DeliveryAssigner;
 use crate::restaurant_actors::restaurant::Restaurant;
 use actix::prelude::*;
 use colored::Color;
-use common::constants::NUMBER_OF_CHEFS;
 use common::logger::Logger;
 use common::messages::UpdateOrderStatus;
-use common::types::dtos::OrderDTO;
+use common::types::dtos::{OrderDTO, OrderItem};
+use common::types::kitchen_load::KitchenLoad;
 use common::types::order_status::OrderStatus;
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::time::Instant;
 
-/// The `Kitchen` actor is responsible for managing the queue of orders to be prepared,
+/// An item of an order waiting for a chef.
+#[derive(Debug, Clone)]
+pub struct KitchenTask {
+    /// ID of the order of the item.
+    pub order_id: u64,
+    /// Whether the order is express.
+    pub express: bool,
+    /// The item to cook.
+    pub item: OrderItem,
+    /// Time to cook the item.
+    pub time_to_cook: Duration,
+}
+
+/// The `Kitchen` actor is responsible for managing the queue of items to be prepared,
 /// assigning them to available chefs, and coordinating with the restaurant and delivery assigner.
 ///
 /// ## Responsibilities:
-/// - Maintains a queue of pending orders.
-/// - Manages available chefs for order preparation.
-/// - Assigns orders to chefs as they become available.
-/// - Notifies the restaurant and delivery assigner when orders are ready.
+/// - Splits each order into its items, which different chefs cook at the same time.
+/// - Maintains a queue of items waiting for a chef, with the ones of express orders ahead of
+///   the standard ones.
+/// - Assigns items to chefs as they become available, up to the number of chefs of the
+///   restaurant.
+/// - Estimates how long each order takes to be ready, counting the items ahead of it.
+/// - Notifies the delivery assigner when the last item of an order is cooked, and the
+///   restaurant when an order starts being prepared, with the load of the kitchen.
 pub struct Kitchen {
-    /// Queue of orders waiting to be prepared.
-    pub pending_orders: VecDeque<OrderDTO>,
+    /// Queue of items waiting to be cooked, the ones of express orders first.
+    pub pending_items: VecDeque<KitchenTask>,
+    /// Orders in the kitchen, with the number of their items not cooked yet.
+    pub orders: HashMap<u64, (OrderDTO, usize)>,
+    /// Number of chefs of the kitchen.
+    pub chefs: usize,
     /// Queue of available chefs.
     pub chefs_available: VecDeque<Addr<Chef>>,
+    /// Instant in which each busy chef finishes the item being cooked.
+    pub busy_chefs: HashMap<Addr<Chef>, Instant>,
+    /// Time to cook each dish.
+    pub preparation_times: PreparationTimes,
     /// Address of the parent restaurant actor.
     pub my_restaurant: Addr<Restaurant>,
     /// Address of the delivery assigner actor.
@@ -35,14 +65,18 @@ pub struct Kitchen {
 impl Kitchen {
     /// Creates a new `Kitchen` actor with the specified restaurant and delivery assigner addresses.
     ///
-    /// Initializes the kitchen with empty queues for pending orders and available chefs.
+    /// Initializes the kitchen with empty queues for pending items and available chefs.
     ///
     /// ## Arguments
     /// * `my_restaurant` - The address of the restaurant actor.
     /// * `my_delivery_assigner` - The address of the delivery assigner actor.
+    /// * `preparation_times` - Time to cook each dish.
+    /// * `chefs` - Number of chefs, which is the number of items cooked at once.
     pub fn new(
         my_restaurant: Addr<Restaurant>,
         my_delivery_assigner: Addr<DeliveryAssigner>,
+        preparation_times: PreparationTimes,
+        chefs: usize,
     ) -> Self {
         let logger = Logger::new(
             "Kitchen",
@@ -53,81 +87,215 @@ impl Kitchen {
             },
         );
         Kitchen {
-            pending_orders: VecDeque::new(),
+            pending_items: VecDeque::new(),
+            orders: HashMap::new(),
+            chefs,
             chefs_available: VecDeque::new(),
+            busy_chefs: HashMap::new(),
+            preparation_times,
             my_restaurant,
             my_delivery_assigner,
             logger,
         }
     }
 
-    /// Assigns pending orders to available chefs.
+    /// Returns how busy the kitchen is.
+    pub fn load(&self) -> KitchenLoad {
+        KitchenLoad {
+            chefs: self.chefs,
+            busy_chefs: self.busy_chefs.len(),
+            queued_items: self.pending_items.len(),
+        }
+    }
+
+    /// Informs the restaurant of the status of `order`, with the load of the kitchen.
+    fn update_order_status(&self, order: &OrderDTO) {
+        self.my_restaurant.do_send(UpdateOrderStatus {
+            order: order.clone(),
+            reason: None,
+            kitchen_load: Some(self.load()),
+        });
+    }
+
+    /// Assigns pending items to available chefs.
     ///
-    /// Iterates through the available chefs and pending orders, assigning each order to a chef.
-    /// Only updates order status to "Preparing" if the order is currently "Pending".
-    /// Orders already in "Preparing" or "ReadyForDelivery" maintain their status.
-    pub fn assign_orders_to_chefs(&mut self, _ctx: &mut Context<Kitchen>) {
-        while let Some(chef) = self.chefs_available.pop_front() {
-            if let Some(mut order) = self.pending_orders.pop_front() {
-                // Only change status to Preparing if it's currently Pending
-                if order.status == OrderStatus::Pending {
-                    order.status = OrderStatus::Preparing;
-                    // Notify the restaurant that the order is being prepared
-                    self.my_restaurant.do_send(UpdateOrderStatus {
-                        order: order.clone(),
-                    });
-                }
-                // Assign the order to the chef regardless of status
-                chef.do_send(AssignToChef {
-                    order: order.clone(),
-                });
-            } else {
-                // If no pending orders, push the chef back to the available queue
-                self.chefs_available.push_back(chef);
-                break; // Exit the loop if no more orders are pending
+    /// The first item assigned of a "Pending" order changes its status to "Preparing".
+    pub fn assign_items_to_chefs(&mut self, _ctx: &mut Context<Kitchen>) {
+        while !self.pending_items.is_empty() {
+            let Some(chef) = self.chefs_available.pop_front() else {
+                break;
+            };
+            let Some(task) = self.pending_items.pop_front() else {
+                break;
+            };
+            self.busy_chefs
+                .insert(chef.clone(), Instant::now() + task.time_to_cook);
+            if let Some((order, _)) = self.orders.get_mut(&task.order_id)
+                && order.status == OrderStatus::Pending
+            {
+                order.status = OrderStatus::Preparing;
+                let order = order.clone();
+                // Notify the restaurant that the order is being prepared
+                self.update_order_status(&order);
             }
+            chef.do_send(AssignToChef {
+                order_id: task.order_id,
+                item: task.item,
+                time_to_cook: task.time_to_cook,
+            });
         }
     }
+
+    /// Returns the items of `order` to cook, the slowest first so that the order is ready
+    /// as soon as possible. An order already ready has nothing to cook.
+    fn tasks_of(&self, order: &OrderDTO) -> Vec<KitchenTask> {
+        if order.status == OrderStatus::ReadyForDelivery {
+            return Vec::new();
+        }
+        let mut tasks: Vec<KitchenTask> = order
+            .items
+            .iter()
+            .map(|item| KitchenTask {
+                order_id: order.order_id,
+                express: order.priority.is_express(),
+                item: item.clone(),
+                time_to_cook: self.preparation_times.time_to_cook_item(item),
+            })
+            .collect();
+        tasks.sort_by_key(|task| std::cmp::Reverse(task.time_to_cook));
+        tasks
+    }
+
+    /// Returns where the items of `order` go in the queue: the ones of express orders go
+    /// behind the other express ones but ahead of every standard item, which go last.
+    fn queue_position(&self, order: &OrderDTO) -> usize {
+        if order.priority.is_express() {
+            self.pending_items
+                .iter()
+                .position(|queued| !queued.express)
+                .unwrap_or(self.pending_items.len())
+        } else {
+            self.pending_items.len()
+        }
+    }
+
+    /// Estimates how long the `tasks` of an order take to be ready if they are queued now at
+    /// `position`, behind the items waiting ahead of them and the ones being cooked.
+    pub fn estimate_preparation_time(&self, tasks: &[KitchenTask], position: usize) -> Duration {
+        let now = Instant::now();
+        let chefs_free_in = self.chefs_available.iter().map(|_| Duration::ZERO).chain(
+            self.busy_chefs
+                .values()
+                .map(|finish| finish.saturating_duration_since(now)),
+        );
+        let queued = self
+            .pending_items
+            .iter()
+            .take(position)
+            .map(|queued| queued.time_to_cook);
+        estimate_ready_in(
+            chefs_free_in,
+            queued,
+            tasks.iter().map(|task| task.time_to_cook),
+        )
+    }
+
+    /// Hands a cooked order to the delivery assigner.
+    fn finish_order(&mut self, order: OrderDTO) {
+        self.logger
+            .info(format!("Order {} is ready for delivery.", order.order_id));
+        self.my_delivery_assigner.do_send(SendThisOrder { order });
+    }
 }
 
 impl Actor for Kitchen {
     type Context = Context<Self>;
 
-    /// Initializes the kitchen by spawning chefs and assigning any pending orders.
+    /// Initializes the kitchen by spawning chefs and assigning any pending items.
     fn started(&mut self, ctx: &mut Self::Context) {
         // Initialize chefs
-        for _ in 0..NUMBER_OF_CHEFS {
-            let chef = Chef::new(self.my_delivery_assigner.clone(), ctx.address());
+        for _ in 0..self.chefs {
+            let chef = Chef::new(ctx.address());
             let chef_addr = chef.start();
             self.chefs_available.push_back(chef_addr);
         }
-        // Assign orders to chefs if any are available
-        self.assign_orders_to_chefs(ctx);
+        // Assign items to chefs if any are available
+        self.assign_items_to_chefs(ctx);
     }
 }
 
 /// Handles [`SendToKitchen`] messages.
 ///
-/// Receives a new order from the restaurant and enqueues it for preparation.
-/// Triggers assignment of orders to available chefs.
+/// Receives a new order from the restaurant and enqueues its items for preparation, ahead
+/// of the standard ones if it is express. Orders already ready go straight to the delivery
+/// assigner.
+/// "Pending" orders are reported back to the restaurant with their estimated
+/// preparation time before being assigned, so the update reaches the server first.
+/// Triggers assignment of items to available chefs.
 impl Handler<SendToKitchen> for Kitchen {
     type Result = ();
 
     fn handle(&mut self, msg: SendToKitchen, ctx: &mut Self::Context) -> Self::Result {
-        self.pending_orders.push_back(msg.order);
-        self.assign_orders_to_chefs(ctx);
+        let mut order = msg.order;
+        let tasks = self.tasks_of(&order);
+        if tasks.is_empty() {
+            self.finish_order(order);
+            return;
+        }
+        let position = self.queue_position(&order);
+        let estimate = self.estimate_preparation_time(&tasks, position);
+        order.expected_preparation_time = estimate.as_millis() as u64;
+        if order.status == OrderStatus::Pending {
+            self.logger.info(format!(
+                "Order {} will be ready in about {:.2} seconds",
+                order.order_id,
+                estimate.as_secs_f64()
+            ));
+            self.update_order_status(&order);
+        }
+        self.orders.insert(order.order_id, (order, tasks.len()));
+        for (offset, task) in tasks.into_iter().enumerate() {
+            self.pending_items.insert(position + offset, task);
+        }
+        self.assign_items_to_chefs(ctx);
     }
 }
 
 /// Handles [`IAmAvailable`] messages.
 ///
-/// Receives notification from a chef that they are available for a new order.
-/// Adds the chef to the available queue and attempts to assign pending orders.
+/// Receives notification from a chef that they cooked an item and are available for a new
+/// one. Once the slowest item of an order is cooked, the order is ready for delivery.
+/// Adds the chef to the available queue and attempts to assign pending items.
 impl Handler<IAmAvailable> for Kitchen {
     type Result = ();
 
     fn handle(&mut self, msg: IAmAvailable, ctx: &mut Self::Context) -> Self::Result {
+        self.busy_chefs.remove(&msg.chef_addr);
         self.chefs_available.push_back(msg.chef_addr);
-        self.assign_orders_to_chefs(ctx);
+        if let Some((_, items_left)) = self.orders.get_mut(&msg.order_id) {
+            *items_left -= 1;
+            if *items_left == 0
+                && let Some((order, _)) = self.orders.remove(&msg.order_id)
+            {
+                self.finish_order(order);
+            }
+        }
+        self.assign_items_to_chefs(ctx);
+    }
+}
+
+/// Handles [`SetPreparationTime`] messages.
+///
+/// Changes the time to cook a dish for the orders sent to the kitchen from now on.
+impl Handler<SetPreparationTime> for Kitchen {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetPreparationTime, _ctx: &mut Self::Context) -> Self::Result {
+        self.preparation_times.set(&msg.dish, msg.time_to_cook);
+        self.logger.info(format!(
+            "'{}' now takes {} seconds to cook",
+            msg.dish,
+            msg.time_to_cook.as_secs()
+        ));
     }
 }
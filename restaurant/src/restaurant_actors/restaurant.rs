@@ -1,57 +1,57 @@
-use crate::internal_messages::messages::SendToKitchen;
+use crate::internal_messages::messages::{
+    ListOrders, Restock, SendToKitchen, SetAcceptingOrders, SetPreparationTime,
+};
+use crate::policy::{AcceptancePolicy, RejectReason};
+use crate::preparation::PreparationTimes;
 use crate::restaurant_actors::delivery_assigner::DeliveryAssigner;
 use crate::restaurant_actors::kitchen::Kitchen;
-use actix::fut::wrap_future;
+use crate::stock::Stock;
 use actix::prelude::*;
 use colored::Color;
-use common::constants::DELAY_SECONDS_TO_START_RECONNECT;
 use common::logger::Logger;
 use common::messages::{
-    CancelOrder, ConnectionClosed, DeliverThisOrder, DeliveryAccepted, LeaderIs, NetworkMessage,
-    NewOrder, RecoverProcedure, RegisterUser, RequestNearbyDelivery, StartRunning,
-    UpdateOrderStatus, WhoIsLeader,
+    CancelOrder, DeliverThisOrder, DeliveryAccepted, Deregister, NetworkMessage, NewOrder,
+    RecoverProcedure, RequestNearbyDelivery, RestaurantAvailability, UpdateOrderStatus,
 };
-use common::network::communicator::Communicator;
-use common::network::connections::{connect_one, connect_some, reconnect};
-use common::network::peer_types::PeerType;
+use common::network::peer_types::{PeerIdentity, PeerType};
+use common::peer::{Peer, PeerCore};
 use common::types::dtos::{OrderDTO, UserDTO};
 use common::types::order_status::OrderStatus;
 use common::types::restaurant_info::RestaurantInfo;
-use common::utils::random_bool_by_given_probability;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::process;
-use tokio::net::TcpStream;
 
 /// The `Restaurant` actor represents a restaurant in the distributed food ordering system.
 ///
 /// ## Responsibilities
 /// - Registers itself with the server cluster.
 /// - Receives and processes new orders from clients.
-/// - Forwards orders to the kitchen for preparation.
+/// - Forwards orders to the kitchen for preparation, keeping track of the stock of each dish.
 /// - Handles delivery assignment and order status updates.
+/// - Opens and closes at its business hours, telling the server.
 /// - Manages reconnection and recovery scenarios.
 pub struct Restaurant {
     /// Basic information about the restaurant.
     pub info: RestaurantInfo,
-    /// Probability for accepting or rejecting an order.
-    pub probability: f32,
+    /// Rules for accepting or rejecting an order.
+    pub policy: AcceptancePolicy,
+    /// Orders accepted and not yet handed to a delivery, by ID.
+    pub open_orders: HashMap<u64, OrderDTO>,
+    /// Whether new orders are accepted, `false` while paused from the console.
+    pub accepting_orders: bool,
+    /// Whether the restaurant is within its business hours.
+    pub open: bool,
+    /// Units left of each dish.
+    pub stock: Stock,
     /// Address of the kitchen actor.
     pub kitchen_address: Option<Addr<Kitchen>>,
     /// Address of the delivery assigner actor.
     pub delivery_assigner_address: Option<Addr<DeliveryAssigner>>,
-    /// Network communicator for server interaction.
-    pub communicator: Option<Communicator<Restaurant>>,
-    /// Pending TCP stream before the actor starts.
-    pub pending_stream: Option<TcpStream>,
+    /// Connection to the leader of the servers.
+    pub peer: PeerCore<Restaurant>,
     /// Logger for restaurant events.
     pub logger: Logger,
-    /// List of server socket addresses.
-    pub servers: Vec<SocketAddr>,
-    /// Timer handle for waiting reconnection attempts.
-    waiting_reconnection_timer: Option<actix::SpawnHandle>,
-    /// Flag to indicate if the restaurant is already connected to a server.
-    pub already_connected: bool,
 }
 
 impl Restaurant {
@@ -59,124 +59,108 @@ impl Restaurant {
     ///
     /// # Arguments
     /// * `info` - Basic information about the restaurant.
-    /// * `probability` - Probability fo accepting or rejecting an order.
+    /// * `policy` - Rules for accepting or rejecting an order.
     /// * `servers` - List of server socket addresses.
     /// * `logger` - Logger for restaurant events.
-    pub async fn new(info: RestaurantInfo, probability: f32, servers: Vec<SocketAddr>) -> Self {
+    pub async fn new(
+        info: RestaurantInfo,
+        policy: AcceptancePolicy,
+        servers: Vec<SocketAddr>,
+    ) -> Self {
         let logger = Logger::new("Restaurant", Color::BrightGreen);
         logger.info(format!("Hello: {}'s restaurant!", info.id));
         // Intentamos conectarnos a los servidores
-        let pending_stream = connect_some(servers.clone(), PeerType::RestaurantType).await;
-
-        if pending_stream.is_none() {
-            logger.error(format!(
-                "Failed to connect to any server from the list: {:?}",
-                servers
-            ));
+        let identity = PeerIdentity::new(PeerType::RestaurantType, info.id.clone());
+        let Some(peer) = PeerCore::connect(servers, identity, logger.clone()).await else {
             std::process::exit(1);
-        }
+        };
 
+        let stock = Stock::new(policy.stock.clone());
+        let (open, _) = policy.opening_now();
         Self {
             info,
-            probability,
+            policy,
+            open_orders: HashMap::new(),
+            accepting_orders: true,
+            open,
+            stock,
             kitchen_address: None,
             delivery_assigner_address: None,
-            communicator: None,
-            pending_stream,
+            peer,
             logger,
-            servers,
-            waiting_reconnection_timer: None,
-            already_connected: false,
         }
     }
 
-    pub fn send_network_message(&self, message: NetworkMessage) {
-        if let Some(communicator) = &self.communicator {
-            if let Some(sender) = &communicator.sender {
-                sender.do_send(message);
-            } else {
-                self.logger.error("Sender not initialized in communicator");
-            }
-        } else {
-            self.logger.error("Communicator not found!");
-        }
+    /// Registers with `secret`, the secret of the identity of the restaurant.
+    pub fn with_secret(mut self, secret: Option<String>) -> Self {
+        self.peer.set_secret(secret);
+        self
     }
 
-    pub fn start_running(&self, _ctx: &mut Context<Self>) {
-        let actual_socket_addr = self
-            .communicator
-            .as_ref()
-            .map(|c| c.local_address)
-            .expect("Socket address not initialized");
+    pub fn send_network_message(&self, message: NetworkMessage) {
+        self.peer.send(message);
+    }
 
-        self.logger.info(format!(
-            "Starting Restaurant actor with ID: {} at position: {:?}",
-            self.info.id, self.info.position
+    /// Tells the server whether the restaurant is open, so it is only offered to clients
+    /// while it is.
+    fn announce_opening(&self) {
+        if self.peer.communicator.is_none() {
+            return;
+        }
+        self.send_network_message(NetworkMessage::RestaurantAvailability(
+            RestaurantAvailability {
+                restaurant_id: self.info.id.clone(),
+                open: self.open,
+            },
         ));
-        self.send_network_message(NetworkMessage::WhoIsLeader(WhoIsLeader {
-            origin_addr: actual_socket_addr,
-            user_id: self.info.id.clone(),
-        }));
     }
-}
 
-/// Handles [`ConnectionClosed`] messages.
-///
-/// This handler is triggered when the connection to the server is lost.
-/// It attempts to reconnect to one of the known servers. If reconnection is successful,
-/// it reinitializes the communicator and restarts the actor. If not, the actor is stopped.
-impl Handler<ConnectionClosed> for Restaurant {
-    type Result = ();
-
-    fn handle(&mut self, _msg: ConnectionClosed, ctx: &mut Self::Context) -> Self::Result {
-        // Llama a la función async y usa wrap_future para obtener el resultado
-        // Antes de reconectar o crear communicator:
-        if self.communicator.is_some() {
-            self.logger
-                .info("Already connected, skipping reconnection.");
-            return;
+    /// Opens or closes the restaurant according to its business hours, and schedules the
+    /// next time it opens or closes.
+    fn schedule_opening(&mut self, ctx: &mut Context<Self>) {
+        let (open, next_change) = self.policy.opening_now();
+        if open != self.open {
+            self.open = open;
+            if open {
+                self.logger.info("The restaurant opened");
+            } else {
+                self.logger.info(format!(
+                    "The restaurant closed, {} open orders are still prepared",
+                    self.open_orders.len()
+                ));
+            }
+            self.announce_opening();
         }
+        if let Some(wait) = next_change {
+            ctx.run_later(wait, |act, ctx| act.schedule_opening(ctx));
+        }
+    }
+}
 
-        let servers = self.servers.clone();
-        let fut = async move { reconnect(servers, PeerType::RestaurantType).await };
+/// Registers the restaurant with the leader of the servers and recovers its orders.
+impl Peer for Restaurant {
+    fn peer(&self) -> &PeerCore<Self> {
+        &self.peer
+    }
 
-        let fut = wrap_future::<_, Self>(fut).map(|result, actor: &mut Self, ctx| match result {
-            Some(stream) => {
-                let communicator =
-                    Communicator::new(stream, ctx.address(), PeerType::RestaurantType);
-                actor.communicator = Some(communicator);
+    fn peer_mut(&mut self) -> &mut PeerCore<Self> {
+        &mut self.peer
+    }
 
-                actor
-                    .logger
-                    .info("Reconnected successfully. Restarting actor...");
+    fn position(&self) -> (f32, f32) {
+        self.info.position
+    }
 
-                if let Some(handler) = actor.waiting_reconnection_timer.take() {
-                    ctx.cancel_future(handler);
-                    actor.waiting_reconnection_timer = None;
-                }
+    fn tags(&self) -> Vec<String> {
+        self.info.tags.clone()
+    }
 
-                // Esperar 100ms antes de enviar WhoIsLeader tras reconexión
-                let addr = ctx.address();
-                let handler = ctx.run_later(std::time::Duration::from_millis(100), move |_, _| {
-                    addr.do_send(StartRunning);
-                });
-                actor.waiting_reconnection_timer = Some(handler);
-            }
-            None => {
-                actor
-                    .logger
-                    .error("Failed to reconnect to any server after closed connection");
-                ctx.stop();
-            }
-        });
-        ctx.spawn(fut);
+    fn on_recovered(&mut self, user: UserDTO, ctx: &mut Context<Self>) {
+        ctx.address().do_send(RecoverProcedure { user_info: user });
     }
-}
 
-impl Handler<StartRunning> for Restaurant {
-    type Result = ();
-    fn handle(&mut self, _msg: StartRunning, ctx: &mut Self::Context) -> Self::Result {
-        self.start_running(ctx);
+    fn on_registered(&mut self, _recovered: bool, _ctx: &mut Context<Self>) {
+        self.announce_opening();
     }
 }
 
@@ -184,15 +168,6 @@ impl Actor for Restaurant {
     type Context = Context<Self>;
 
     fn started(&mut self, ctx: &mut Self::Context) {
-        let communicator = Communicator::new(
-            self.pending_stream
-                .take()
-                .expect("Pending stream should be set"),
-            ctx.address(),
-            PeerType::RestaurantType,
-        );
-        self.communicator = Some(communicator);
-
         self.delivery_assigner_address =
             Some(DeliveryAssigner::new(self.info.clone(), ctx.address()).start());
 
@@ -200,96 +175,30 @@ impl Actor for Restaurant {
             Kitchen::new(
                 ctx.address(),
                 self.delivery_assigner_address.clone().unwrap(),
+                PreparationTimes::new(self.policy.cook_times.clone()),
+                self.policy.chefs,
             )
             .start(),
         );
-        self.start_running(ctx);
+
+        if let Some(interval) = self.policy.restock_interval {
+            ctx.run_interval(interval, |act, ctx| {
+                act.handle(Restock { dish: None }, ctx);
+            });
+        }
+        self.schedule_opening(ctx);
+        self.start_peer(ctx);
     }
 }
 
-/// Handles [`LeaderIs`] messages.
+/// Handles [`Deregister`] messages.
 ///
-/// This handler is called when the server notifies the restaurant of the current leader's address.
-/// If already connected to the leader, it registers itself. Otherwise, it attempts to connect to the new leader
-/// and updates its communicator accordingly.
-impl Handler<LeaderIs> for Restaurant {
+/// Tells the leader the restaurant leaves before shutting down, so that it is closed.
+impl Handler<Deregister> for Restaurant {
     type Result = ();
 
-    fn handle(&mut self, msg: LeaderIs, ctx: &mut Self::Context) -> Self::Result {
-        self.logger
-            .info(format!("Received new leader address: {}", msg.coord_addr));
-        let leader_addr = msg.coord_addr;
-        let self_addr = ctx.address();
-        let logger = self.logger.clone();
-
-        if let Some(handler) = self.waiting_reconnection_timer.take() {
-            ctx.cancel_future(handler);
-            self.waiting_reconnection_timer = None;
-        }
-
-        let communicator_opt = self.communicator.as_ref().map(|c| c.peer_address);
-
-        // Si ya estamos conectados al líder, no hacemos nada
-        if Some(leader_addr) == communicator_opt {
-            self.logger.info(format!(
-                "Already connected to the leader at address: {}",
-                leader_addr.clone()
-            ));
-
-            let local_address = self
-                .communicator
-                .as_ref()
-                .map(|c| c.local_address)
-                .expect("Socket address not set");
-
-            self.send_network_message(NetworkMessage::RegisterUser(RegisterUser {
-                origin_addr: local_address,
-                user_id: self.info.id.clone(),
-                position: self.info.position,
-            }));
-            return;
-        }
-
-        // Si no estoy conectado al líder, cierro el communicator anterior y conecto al nuevo líder
-        if let Some(comm) = self.communicator.as_mut() {
-            comm.shutdown();
-        }
-        self.communicator = None;
-
-        ctx.spawn(
-            wrap_future(async move {
-                logger.info(format!(
-                    "Attempting to connect to the new leader at address: {}",
-                    leader_addr
-                ));
-                if let Some(new_stream) = connect_one(leader_addr, PeerType::RestaurantType).await {
-                    let new_communicator =
-                        Communicator::new(new_stream, self_addr.clone(), PeerType::RestaurantType);
-                    Some(new_communicator)
-                } else {
-                    logger.error(format!(
-                        "Failed to connect to the new leader at {}",
-                        leader_addr
-                    ));
-                    None
-                }
-            })
-            .map(move |maybe_communicator, actor: &mut Self, ctx| {
-                if let Some(new_communicator) = maybe_communicator {
-                    actor.logger.info(format!(
-                        "Communicator updated with new peer address: {}",
-                        new_communicator.peer_address
-                    ));
-                    actor.communicator = Some(new_communicator);
-
-                    let handler =
-                        ctx.run_later(std::time::Duration::from_millis(100), move |_, ctx| {
-                            ctx.address().do_send(StartRunning);
-                        });
-                    actor.waiting_reconnection_timer = Some(handler);
-                }
-            }),
-        );
+    fn handle(&mut self, _msg: Deregister, _ctx: &mut Self::Context) -> Self::Result {
+        self.deregister();
     }
 }
 
@@ -301,7 +210,7 @@ impl Handler<RecoverProcedure> for Restaurant {
     type Result = ();
 
     fn handle(&mut self, msg: RecoverProcedure, ctx: &mut Self::Context) -> Self::Result {
-        if self.already_connected {
+        if self.peer.already_connected {
             self.logger
                 .info("Already connected and recovered, ignoring RecoverProcedure message.");
             return;
@@ -331,7 +240,7 @@ impl Handler<RecoverProcedure> for Restaurant {
                             order: order.clone(),
                         });
                     }
-                    self.already_connected = true;
+                    self.peer.already_connected = true;
                 } else {
                     self.logger.warn(format!(
                         "Received recovered info for a different restaurant ({}), ignoring",
@@ -352,8 +261,8 @@ impl Handler<RecoverProcedure> for Restaurant {
 /// Handles [`NewOrder`] messages.
 ///
 /// Processes a new order received from the server. If the order is pending, it forwards it to the kitchen.
-/// If the order is authorized, it randomly accepts or rejects it based on the restaurant's probability,
-/// then updates the order status accordingly.
+/// If the order is authorized, it accepts or rejects it based on the restaurant's [`AcceptancePolicy`]
+/// and its [`Stock`], then updates the order status accordingly.
 impl Handler<NewOrder> for Restaurant {
     type Result = ();
 
@@ -362,12 +271,15 @@ impl Handler<NewOrder> for Restaurant {
         match new_order.status {
             OrderStatus::Pending => {
                 self.logger.info(format!(
-                    "Pending order detected: Client '{}' has an order for the dish '{}'.",
-                    new_order.client_id, new_order.dish_name
+                    "Pending order detected: Client '{}' has an order for '{}'.",
+                    new_order.client_id,
+                    new_order.summary()
                 ));
+                self.open_orders
+                    .insert(new_order.order_id, new_order.clone());
                 if let Some(kitchen_addr) = self.kitchen_address.clone() {
                     self.logger
-                        .info(format!("Sending order {} to kitchen", new_order.dish_name));
+                        .info(format!("Sending order {} to kitchen", new_order.order_id));
                     // Enviamos el pedido a la cocina
                     kitchen_addr.do_send(SendToKitchen {
                         order: new_order.clone(),
@@ -378,17 +290,44 @@ impl Handler<NewOrder> for Restaurant {
                 }
             }
             OrderStatus::Authorized => {
-                if random_bool_by_given_probability(self.probability) {
-                    // Simulamos que el restaurante acepta el pedido
+                let verdict = if self.accepting_orders {
+                    self.policy
+                        .evaluate(&new_order, self.open_orders.len())
+                        .and_then(|_| self.stock.take(&new_order.items))
+                } else {
+                    Err(RejectReason::Paused)
+                };
+                if let Err(reason) = verdict {
+                    self.logger.info(format!(
+                        "❌ Restaurant {} rejected the order for client {} ({}): {}",
+                        self.info.id,
+                        new_order.client_id,
+                        new_order.summary(),
+                        reason
+                    ));
+                    new_order.status = match reason {
+                        RejectReason::OutOfStock(_) => OrderStatus::OutOfStock,
+                        _ => OrderStatus::Cancelled,
+                    };
+                    // El servidor le explica al cliente por qué se rechazó el pedido
+                    ctx.address().do_send(CancelOrder {
+                        order: new_order.clone(),
+                        reason: Some(reason.code()),
+                    });
+                } else {
                     self.logger.info(format!(
-                        "✅ Restaurant '{}' accepted order for client {} (dish: '{}')",
-                        self.info.id, new_order.client_id, new_order.dish_name
+                        "✅ Restaurant '{}' accepted order for client {} ('{}')",
+                        self.info.id,
+                        new_order.client_id,
+                        new_order.summary()
                     ));
                     new_order.status = OrderStatus::Pending;
+                    self.open_orders
+                        .insert(new_order.order_id, new_order.clone());
                     if let Some(kitchen_addr) = self.kitchen_address.clone() {
                         self.logger
                             .info(format!("Sending order {} to kitchen", new_order.order_id));
-                        // Enviamos el pedido a la cocina
+                        // Enviamos el pedido a la cocina, que informa el estado con el tiempo estimado
                         kitchen_addr.do_send(SendToKitchen {
                             order: new_order.clone(),
                         });
@@ -396,21 +335,6 @@ impl Handler<NewOrder> for Restaurant {
                         self.logger
                             .error("Kitchen sender is not set, cannot send order to kitchen");
                     }
-
-                    ctx.address().do_send(UpdateOrderStatus {
-                        order: new_order.clone(),
-                    });
-                } else {
-                    // Simulamos que el restaurante rechaza el pedido
-                    self.logger.info(format!(
-                        "❌ Restaurant {} rejected the order for client {} (dish: {})",
-                        self.info.id, new_order.client_id, new_order.dish_name
-                    ));
-                    new_order.status = OrderStatus::Cancelled;
-                    // Aquí podrías enviar un mensaje de rechazo al coordinador o al cliente
-                    ctx.address().do_send(CancelOrder {
-                        order: new_order.clone(),
-                    });
                 }
             }
             OrderStatus::Preparing | OrderStatus::ReadyForDelivery => {
@@ -420,6 +344,8 @@ impl Handler<NewOrder> for Restaurant {
                     "Recovering order {} with status {:?}, sending to kitchen to continue workflow",
                     new_order.order_id, new_order.status
                 ));
+                self.open_orders
+                    .insert(new_order.order_id, new_order.clone());
                 if let Some(kitchen_addr) = self.kitchen_address.clone() {
                     kitchen_addr.do_send(SendToKitchen {
                         order: new_order.clone(),
@@ -439,13 +365,113 @@ impl Handler<NewOrder> for Restaurant {
     }
 }
 
+/// Handles [`Restock`] messages.
+///
+/// Replenishes the stock of a dish, or of every dish.
+impl Handler<Restock> for Restaurant {
+    type Result = ();
+
+    fn handle(&mut self, msg: Restock, _ctx: &mut Self::Context) -> Self::Result {
+        match msg.dish {
+            None => {
+                self.stock.refill();
+                self.logger.info("Every dish was restocked");
+            }
+            Some((dish, units)) => {
+                if self.stock.add(&dish, units) {
+                    self.logger.info(format!(
+                        "Restocked {} units of '{}', {} left",
+                        units,
+                        dish,
+                        self.stock.units(&dish).unwrap_or_default()
+                    ));
+                } else {
+                    self.logger
+                        .warn(format!("'{}' has no stock limit, nothing to restock", dish));
+                }
+            }
+        }
+    }
+}
+
+/// Handles [`SetAcceptingOrders`] messages.
+///
+/// Pauses or resumes the acceptance of new orders.
+impl Handler<SetAcceptingOrders> for Restaurant {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetAcceptingOrders, _ctx: &mut Self::Context) -> Self::Result {
+        if self.accepting_orders == msg.accepting {
+            return;
+        }
+        self.accepting_orders = msg.accepting;
+        if msg.accepting {
+            self.logger.info("Accepting new orders again");
+        } else {
+            self.logger.info(format!(
+                "Paused: new orders are rejected, {} open orders are still prepared",
+                self.open_orders.len()
+            ));
+        }
+    }
+}
+
+/// Handles [`ListOrders`] messages.
+///
+/// Prints the open orders, oldest first, and whether new orders are accepted.
+impl Handler<ListOrders> for Restaurant {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ListOrders, _ctx: &mut Self::Context) -> Self::Result {
+        let mut orders: Vec<&OrderDTO> = self.open_orders.values().collect();
+        orders.sort_by_key(|order| order.time_stamp);
+        let state = if !self.open {
+            "closed"
+        } else if self.accepting_orders {
+            "accepting orders"
+        } else {
+            "paused"
+        };
+        println!("{} open orders ({}):", orders.len(), state);
+        for order in orders {
+            println!(
+                "  #{} {:<20} client: {:<12} status: {}",
+                order.order_id,
+                order.summary(),
+                order.client_id,
+                order.status
+            );
+        }
+    }
+}
+
+/// Handles [`SetPreparationTime`] messages.
+///
+/// Forwards the new time to cook a dish to the kitchen.
+impl Handler<SetPreparationTime> for Restaurant {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetPreparationTime, _ctx: &mut Self::Context) -> Self::Result {
+        match &self.kitchen_address {
+            Some(kitchen_addr) => kitchen_addr.do_send(msg),
+            None => self
+                .logger
+                .error("Kitchen sender is not set, cannot change the preparation time"),
+        }
+    }
+}
+
 /// Handles [`UpdateOrderStatus`] messages.
 ///
-/// Forwards an order status update to the server cluster via the network communicator.
+/// Keeps the status of the open order and forwards the update to the server cluster via the
+/// network communicator.
 impl Handler<UpdateOrderStatus> for Restaurant {
     type Result = ();
 
     fn handle(&mut self, msg: UpdateOrderStatus, _ctx: &mut Self::Context) -> Self::Result {
+        if let Some(order) = self.open_orders.get_mut(&msg.order.order_id) {
+            order.status = msg.order.status.clone();
+        }
         self.send_network_message(NetworkMessage::UpdateOrderStatus(msg));
     }
 }
@@ -495,6 +521,8 @@ impl Handler<DeliverThisOrder> for Restaurant {
     type Result = ();
 
     fn handle(&mut self, msg: DeliverThisOrder, _ctx: &mut Self::Context) -> Self::Result {
+        // El pedido ya sale del restaurante
+        self.open_orders.remove(&msg.order.order_id);
         self.send_network_message(NetworkMessage::DeliverThisOrder(msg));
     }
 }
@@ -507,46 +535,24 @@ impl Handler<DeliverThisOrder> for Restaurant {
 impl Handler<NetworkMessage> for Restaurant {
     type Result = ();
     fn handle(&mut self, msg: NetworkMessage, ctx: &mut Self::Context) -> Self::Result {
+        let Some(msg) = self.handle_peer_message(msg, ctx) else {
+            return;
+        };
         match msg {
-            // All Users messages
-            NetworkMessage::RetryLater(_msg_data) => {
-                self.logger.info("Retrying to connect in some seconds");
-            }
-            NetworkMessage::LeaderIs(msg_data) => ctx.address().do_send(msg_data),
-            NetworkMessage::RecoveredInfo(user_dto_opt) => {
-                println!("Received RecoveredInfo in Restaurant");
-                let user_dto = user_dto_opt;
-                match user_dto {
-                    UserDTO::Restaurant(restaurant_dto) => {
-                        if restaurant_dto.restaurant_id == self.info.id {
-                            ctx.address().do_send(RecoverProcedure {
-                                user_info: UserDTO::Restaurant(restaurant_dto),
-                            });
-                        } else {
-                            self.logger.warn(format!(
-                                "Received recovered info for a different delivery ({}), ignoring",
-                                restaurant_dto.restaurant_id
-                            ));
-                        }
-                    }
-                    other => {
-                        self.logger.warn(format!(
-                            "Received recovered info of type {:?}, but I'm Delivery. Ignoring.",
-                            other
-                        ));
-                    }
-                }
-            }
-            NetworkMessage::NoRecoveredInfo => {
-                self.logger
-                    .info("No recovered info received, waiting for new orders.");
-                self.already_connected = true;
-            }
             // Restaurant messages
             NetworkMessage::NewOrder(msg_data) => ctx.address().do_send(msg_data),
-            NetworkMessage::UpdateOrderStatus(_msg_data) => {
-                self.logger
-                    .info("Received UpdateOrderStatus message, not implemented yet");
+            NetworkMessage::UpdateOrderStatus(msg_data) => {
+                // El servidor devuelve a la espera los pedidos cuyo delivery se desconectó
+                if msg_data.order.status == OrderStatus::ReadyForDelivery
+                    && let Some(addr) = self.delivery_assigner_address.as_ref()
+                {
+                    addr.do_send(msg_data);
+                } else {
+                    self.logger.info(format!(
+                        "Received status {:?} for order {}",
+                        msg_data.order.status, msg_data.order.order_id
+                    ));
+                }
             }
             NetworkMessage::OrderFinalized(msg_data) => {
                 self.logger.info(format!(
@@ -564,33 +570,26 @@ impl Handler<NetworkMessage> for Restaurant {
                     "Order with ID: {} has been cancelled.",
                     msg_data.order.order_id
                 ));
-                if let Some(addr) = self.delivery_assigner_address.as_ref() {
+                // Sólo los pedidos aceptados pueden estar en la cocina o esperando delivery
+                if self.open_orders.remove(&msg_data.order.order_id).is_some()
+                    && let Some(addr) = self.delivery_assigner_address.as_ref()
+                {
                     addr.do_send(msg_data);
                 }
             }
+            NetworkMessage::StatusRejected(msg_data) => {
+                self.logger.warn(format!(
+                    "Server rejected status {:?} for order {}, which is {:?}",
+                    msg_data.rejected_status, msg_data.order.order_id, msg_data.order.status
+                ));
+            }
 
             NetworkMessage::ConnectionClosed(msg_data) => {
                 self.logger.info(format!(
                     "Connection closed with address: {}",
                     msg_data.remote_addr
                 ));
-                // Si el comunicador actual posee una peer_address que coincide con la dirección cerrada,
-                // se elimina el comunicador actual. Si no, se ignora.
-                self.logger.info(format!(
-                    "Removing communicator for address: {}",
-                    msg_data.remote_addr
-                ));
-                self.communicator = None;
-                self.logger.warn("Retrying to reconnect to the server ...");
-
-                let msg_data_cloned = msg_data.clone();
-
-                // Inicia un temporizador para reconectar después de un tiempo
-                let handle = ctx.run_later(DELAY_SECONDS_TO_START_RECONNECT, move |_, ctx| {
-                    ctx.address().do_send(msg_data_cloned.clone());
-                });
-
-                self.waiting_reconnection_timer = Some(handle);
+                self.peer_connection_closed(ctx);
             }
 
             _ => {
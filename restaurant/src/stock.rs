@@ -0,0 +1,156 @@
+use crate::internal_messages::messages::Restock;
+use crate::policy::RejectReason;
+use common::types::dtos::OrderItem;
+use std::collections::HashMap;
+
+/// Units left of each dish a restaurant prepares.
+///
+/// Only the dishes with a limited stock are tracked: any other dish is always available.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Stock {
+    /// Units left of each dish.
+    available: HashMap<String, u32>,
+    /// Units each dish is restocked to.
+    full: HashMap<String, u32>,
+}
+
+impl Stock {
+    /// Creates a full stock.
+    ///
+    /// ## Arguments
+    /// * `full` - Units of each limited dish.
+    pub fn new(full: HashMap<String, u32>) -> Self {
+        Self {
+            available: full.clone(),
+            full,
+        }
+    }
+
+    /// Returns the units left of `dish`, or `None` if it is unlimited.
+    pub fn units(&self, dish: &str) -> Option<u32> {
+        self.available.get(dish).copied()
+    }
+
+    /// Takes the units of every item of an order entering the kitchen.
+    ///
+    /// Nothing is taken unless there are enough units of every dish.
+    ///
+    /// ## Returns
+    /// [`RejectReason::OutOfStock`] with the first dish without enough units left.
+    pub fn take(&mut self, items: &[OrderItem]) -> Result<(), RejectReason> {
+        let mut needed: HashMap<&str, u32> = HashMap::new();
+        for item in items {
+            *needed.entry(item.dish.as_str()).or_default() += item.quantity;
+        }
+        for item in items {
+            if let Some(units) = self.units(&item.dish)
+                && units < needed[item.dish.as_str()]
+            {
+                return Err(RejectReason::OutOfStock(item.dish.clone()));
+            }
+        }
+        for (dish, units) in needed {
+            if let Some(available) = self.available.get_mut(dish) {
+                *available -= units;
+            }
+        }
+        Ok(())
+    }
+
+    /// Refills every limited dish to its full stock.
+    pub fn refill(&mut self) {
+        self.available = self.full.clone();
+    }
+
+    /// Adds `units` of a limited dish.
+    ///
+    /// ## Returns
+    /// `false` if the dish is unlimited, in which case nothing changes.
+    pub fn add(&mut self, dish: &str, units: u32) -> bool {
+        match self.available.get_mut(dish) {
+            Some(available) => {
+                *available = available.saturating_add(units);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Parses a restock command typed in the restaurant console.
+///
+/// ## Commands
+/// - `restock`: Refills every dish to its full stock.
+/// - `restock <dish> <units>`: Adds units of a dish. The dish name can contain spaces.
+pub fn parse_restock(line: &str) -> Result<Restock, String> {
+    let usage = || "Usage: restock [<dish> <units>]".to_string();
+    let rest = line
+        .trim()
+        .strip_prefix("restock")
+        .ok_or_else(usage)?
+        .trim();
+    if rest.is_empty() {
+        return Ok(Restock { dish: None });
+    }
+    let (dish, units) = rest.rsplit_once(' ').ok_or_else(usage)?;
+    let units: u32 = units.parse().map_err(|_| usage())?;
+    Ok(Restock {
+        dish: Some((dish.trim().to_string(), units)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_a_dish_once_it_runs_out_until_restocked() {
+        let mut stock = Stock::new(HashMap::from([("Pizza".to_string(), 1)]));
+        assert!(stock.take(&[OrderItem::new("Pizza", 1)]).is_ok());
+        assert_eq!(
+            stock.take(&[OrderItem::new("Pizza", 1)]),
+            Err(RejectReason::OutOfStock("Pizza".to_string()))
+        );
+        assert!(stock.take(&[OrderItem::new("Empanadas", 1)]).is_ok());
+
+        stock.refill();
+        assert_eq!(stock.units("Pizza"), Some(1));
+        assert!(stock.add("Pizza", 2));
+        assert!(!stock.add("Empanadas", 2));
+        assert_eq!(stock.units("Pizza"), Some(3));
+    }
+
+    #[test]
+    fn takes_every_item_of_an_order_or_none() {
+        let mut stock = Stock::new(HashMap::from([
+            ("Pizza".to_string(), 3),
+            ("Flan".to_string(), 1),
+        ]));
+        // Falta un flan: no se toma ninguna pizza
+        let order = [
+            OrderItem::new("Pizza", 2),
+            OrderItem::new("Flan", 1),
+            OrderItem::new("Flan", 1),
+        ];
+        assert_eq!(
+            stock.take(&order),
+            Err(RejectReason::OutOfStock("Flan".to_string()))
+        );
+        assert_eq!(stock.units("Pizza"), Some(3));
+
+        assert!(stock.take(&order[..2]).is_ok());
+        assert_eq!(stock.units("Pizza"), Some(1));
+        assert_eq!(stock.units("Flan"), Some(0));
+    }
+
+    #[test]
+    fn parses_restock_commands() {
+        assert_eq!(parse_restock("restock").unwrap().dish, None);
+        assert_eq!(
+            parse_restock("restock Papas fritas 4").unwrap().dish,
+            Some(("Papas fritas".to_string(), 4))
+        );
+        assert!(parse_restock("restock Pizza").is_err());
+        assert!(parse_restock("cook Pizza 1").is_err());
+    }
+}
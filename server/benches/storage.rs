@@ -0,0 +1,143 @@
+//! Benchmarks of how fast a follower's [`Storage`] applies the log replicated by the leader,
+//! kept in memory or also persisted to its log file.
+//!
+//! Run them with `cargo bench -p server`.
+
+use actix::prelude::*;
+use common::logger::Logger;
+use common::messages::internal_messages::{
+    AddClient, AddOrder, ApplyStorageUpdates, SetDeliveryPosition, SetOrderStatus,
+    StorageLogMessage,
+};
+use common::types::dtos::{ClientDTO, OrderDTO, OrderItem};
+use common::types::hlc::HlcTimestamp;
+use common::types::order_status::OrderStatus;
+use common::types::tenant::TenantId;
+use criterion::{BatchSize, Criterion, Throughput, criterion_group, criterion_main};
+use server::server_actors::coordinator::Coordinator;
+use server::server_actors::storage::Storage;
+use server::server_actors::storage_log::StorageLogFile;
+use server::server_actors::storage_view::StorageReadView;
+use std::path::PathBuf;
+
+/// Entries of the replicated log applied in each iteration.
+const ENTRIES: u64 = 100_000;
+
+fn order(order_id: u64) -> OrderDTO {
+    OrderDTO {
+        client_position: (3.0, 7.0),
+        ..OrderDTO::new(
+            order_id,
+            &format!("client_{}", order_id % 1000),
+            &format!("restaurant_{}", order_id % 50),
+            vec![OrderItem::new("Pizza", 1)],
+        )
+    }
+}
+
+/// Returns the log to apply: clients placing orders that are then prepared, while the
+/// deliveries move around.
+fn replicated_log() -> Vec<(u64, StorageLogMessage)> {
+    (1..=ENTRIES)
+        .map(|index| {
+            let update = match index % 4 {
+                0 => StorageLogMessage::AddClient(AddClient {
+                    client: ClientDTO {
+                        client_position: (3.0, 7.0),
+                        client_id: format!("client_{}", index % 1000),
+                        client_order: None,
+                        time_stamp: HlcTimestamp::default(),
+                        tenant_id: TenantId::default(),
+                    },
+                }),
+                1 => StorageLogMessage::AddOrder(AddOrder {
+                    order: order(index),
+                    event: None,
+                }),
+                2 => StorageLogMessage::SetOrderStatus(SetOrderStatus {
+                    order: order(index - 1),
+                    order_status: OrderStatus::Preparing,
+                    event: None,
+                }),
+                _ => StorageLogMessage::SetDeliveryPosition(SetDeliveryPosition {
+                    delivery_id: format!("delivery_{}", index % 200),
+                    position: ((index % 20) as f32, (index % 30) as f32),
+                    time_stamp: None,
+                }),
+            };
+            (index, update)
+        })
+        .collect()
+}
+
+/// Returns an empty storage whose coordinator is never started, and its context.
+fn new_storage(log_file: Option<StorageLogFile>) -> (Storage, Context<Storage>) {
+    let coordinator = Context::<Coordinator>::new().address();
+    (
+        Storage::new(
+            coordinator,
+            "server_1".to_string(),
+            log_file,
+            StorageReadView::default(),
+        ),
+        Context::new(),
+    )
+}
+
+fn apply(c: &mut Criterion) {
+    // Cada operación aplicada se loguea: se silencia para medir sólo el storage
+    Logger::set_muted(true);
+    let log = replicated_log();
+    let dir = std::env::temp_dir().join(format!("storage_bench_{}", std::process::id()));
+
+    let mut group = c.benchmark_group("storage_apply");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(ENTRIES));
+    group.bench_function("memory", |b| {
+        b.iter_batched(
+            || (new_storage(None), log.clone()),
+            |((mut storage, mut ctx), updates)| {
+                storage.handle(
+                    ApplyStorageUpdates {
+                        is_leader: false,
+                        updates,
+                    },
+                    &mut ctx,
+                );
+                storage
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    let mut run = 0;
+    group.bench_function("log_file", |b| {
+        b.iter_batched(
+            || {
+                run += 1;
+                let log_file = StorageLogFile::open(&run_dir(&dir, run), "server_1").unwrap();
+                (new_storage(Some(log_file)), log.clone())
+            },
+            |((mut storage, mut ctx), updates)| {
+                storage.handle(
+                    ApplyStorageUpdates {
+                        is_leader: false,
+                        updates,
+                    },
+                    &mut ctx,
+                );
+                storage
+            },
+            BatchSize::PerIteration,
+        )
+    });
+    group.finish();
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+/// Returns a new directory for the log file of each run, so that none replays the last one.
+fn run_dir(dir: &std::path::Path, run: u64) -> PathBuf {
+    dir.join(run.to_string())
+}
+
+criterion_group!(benches, apply);
+criterion_main!(benches);
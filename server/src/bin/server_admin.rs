@@ -0,0 +1,271 @@
+use actix::prelude::*;
+use chrono::DateTime;
+use common::constants::{API_REQUEST_TIMEOUT, LEADERSHIP_HANDOFF_TIMEOUT, SERVER_IP_ADDRESS};
+use common::messages::shared_messages::{
+    DrainCoordinator, NetworkMessage, RequestElectionHistory, TransferLeadership, VerifyStorage,
+};
+use common::network::communicator::Communicator;
+use common::network::connections::{base_port_from_env, connect_one};
+use common::network::peer_types::{PeerIdentity, PeerType};
+use common::types::dtos::ElectionRecord;
+use std::env;
+use std::net::SocketAddr;
+use tokio::sync::oneshot;
+
+/// Formats milliseconds since the Unix epoch as a UTC date.
+fn date(millis: u64) -> String {
+    DateTime::from_timestamp_millis(millis as i64)
+        .map(|date| date.format("%Y-%m-%d %H:%M:%S%.3f").to_string())
+        .unwrap_or_else(|| millis.to_string())
+}
+
+/// Formats a duration in milliseconds as seconds.
+fn seconds(millis: u64) -> String {
+    format!("{}.{:03}s", millis / 1000, millis % 1000)
+}
+
+/// Prints the leader changes known by a coordinator and how long each leader lasted.
+fn print_history(coordinator_id: &str, epoch: u64, elections: &[ElectionRecord]) {
+    println!("Coordinator {} follows epoch {}", coordinator_id, epoch);
+    println!();
+    println!(
+        "{:>6}  {:<12}  {:<23}  {:>12}",
+        "EPOCH", "LEADER", "ELECTED AT (UTC)", "LED FOR"
+    );
+    for (i, election) in elections.iter().enumerate() {
+        let led_for = elections
+            .get(i + 1)
+            .map(|next| seconds(next.at_millis.saturating_sub(election.at_millis)))
+            .unwrap_or_else(|| "-".to_string());
+        println!(
+            "{:>6}  {:<12}  {:<23}  {:>12}",
+            election.epoch,
+            election.leader_id,
+            date(election.at_millis),
+            led_for
+        );
+    }
+    println!();
+    println!("Leader changes: {}", elections.len().saturating_sub(1));
+    let shortest = elections
+        .windows(2)
+        .map(|pair| pair[1].at_millis.saturating_sub(pair[0].at_millis))
+        .min();
+    if let Some(shortest) = shortest {
+        println!("Shortest leadership: {}", seconds(shortest));
+    }
+}
+
+/// What to ask the coordinator for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Command {
+    /// Print its election history.
+    Elections,
+    /// Drain it before a restart: hand its users and leadership off and shut it down.
+    Drain,
+    /// Make the coordinator with the given ID the leader.
+    Transfer(String),
+    /// Check the references between the entries of its storage.
+    Verify,
+}
+
+impl Command {
+    /// Returns how long to wait for the answer of the coordinator.
+    fn timeout(&self) -> std::time::Duration {
+        match self {
+            Command::Elections | Command::Verify => API_REQUEST_TIMEOUT,
+            // El líder espera a que su sucesor aplique todo el log
+            Command::Drain | Command::Transfer(_) => {
+                API_REQUEST_TIMEOUT + LEADERSHIP_HANDOFF_TIMEOUT
+            }
+        }
+    }
+}
+
+/// Sends a command to a coordinator and prints the answer.
+struct AdminRequest {
+    /// What to ask for.
+    command: Command,
+    /// Connection with the coordinator.
+    communicator: Option<Communicator<AdminRequest>>,
+    /// Where to send the exit code of the process once done.
+    done: Option<oneshot::Sender<i32>>,
+}
+
+impl AdminRequest {
+    /// Finishes the request with `code` as exit code.
+    fn finish(&mut self, code: i32) {
+        if let Some(done) = self.done.take() {
+            let _ = done.send(code);
+        }
+    }
+}
+
+impl Actor for AdminRequest {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(communicator) = &self.communicator
+            && let Some(sender) = communicator.sender.as_ref()
+        {
+            let origin_addr = communicator.local_address;
+            sender.do_send(match &self.command {
+                Command::Elections => {
+                    NetworkMessage::RequestElectionHistory(RequestElectionHistory { origin_addr })
+                }
+                Command::Drain => {
+                    NetworkMessage::DrainCoordinator(DrainCoordinator { origin_addr })
+                }
+                Command::Transfer(target) => {
+                    NetworkMessage::TransferLeadership(TransferLeadership {
+                        origin_addr,
+                        target: target.clone(),
+                    })
+                }
+                Command::Verify => NetworkMessage::VerifyStorage(VerifyStorage { origin_addr }),
+            });
+        }
+        ctx.run_later(self.command.timeout(), |act, _| {
+            eprintln!("The coordinator did not answer in time");
+            act.finish(1);
+        });
+    }
+}
+
+impl Handler<NetworkMessage> for AdminRequest {
+    type Result = ();
+
+    fn handle(&mut self, msg: NetworkMessage, _ctx: &mut Self::Context) -> Self::Result {
+        match msg {
+            NetworkMessage::ElectionHistory(history) => {
+                print_history(&history.coordinator_id, history.epoch, &history.elections);
+                self.finish(0);
+            }
+            NetworkMessage::StorageReport(report) => {
+                println!(
+                    "Storage of {} up to log entry {}: {} violations",
+                    report.coordinator_id,
+                    report.next_log_id,
+                    report.violations.len()
+                );
+                for violation in &report.violations {
+                    println!("  {}", violation);
+                }
+                self.finish(if report.violations.is_empty() { 0 } else { 1 });
+            }
+            NetworkMessage::CoordinatorDrained(drained) => match drained.leader {
+                Some(leader) => {
+                    println!(
+                        "Coordinator {} drained: its users were sent to the leader at {}",
+                        drained.coordinator_id, leader
+                    );
+                    self.finish(0);
+                }
+                None => {
+                    eprintln!(
+                        "Coordinator {} cannot be drained: no other coordinator can take over",
+                        drained.coordinator_id
+                    );
+                    self.finish(1);
+                }
+            },
+            NetworkMessage::LeadershipTransferred(transferred) => match transferred.leader {
+                Some(leader) => {
+                    println!("{} at {} is the leader now", transferred.target, leader);
+                    self.finish(0);
+                }
+                None => {
+                    eprintln!(
+                        "The leadership could not be transferred to {}",
+                        transferred.target
+                    );
+                    self.finish(1);
+                }
+            },
+            NetworkMessage::ConnectionClosed(_) => {
+                eprintln!("Connection with the coordinator closed");
+                self.finish(1);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parses `[elections|drain|transfer <id>|verify] [--server <addr>]`.
+fn parse_args(args: &[String]) -> Result<(Command, SocketAddr), String> {
+    let mut server = format!("{}:{}", SERVER_IP_ADDRESS, base_port_from_env())
+        .parse::<SocketAddr>()
+        .map_err(|e| e.to_string())?;
+    let mut command = Command::Elections;
+    let mut args = args.iter().peekable();
+    match args.peek().map(|arg| arg.as_str()) {
+        Some("elections") => {
+            args.next();
+        }
+        Some("drain") => {
+            command = Command::Drain;
+            args.next();
+        }
+        Some("verify") => {
+            command = Command::Verify;
+            args.next();
+        }
+        Some("transfer") => {
+            args.next();
+            let target = args
+                .next()
+                .ok_or("Missing the ID of the coordinator to transfer the leadership to")?;
+            command = Command::Transfer(target.clone());
+        }
+        _ => {}
+    }
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("Missing value for {}", flag))?;
+        match flag.as_str() {
+            "--server" => {
+                server = value
+                    .parse()
+                    .map_err(|_| format!("Invalid server address: {}", value))?
+            }
+            _ => return Err(format!("Unknown option: {}", flag)),
+        }
+    }
+    Ok((command, server))
+}
+
+#[actix::main]
+async fn main() {
+    let args: Vec<String> = env::args().collect();
+    let (command, server) = match parse_args(&args[1..]) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!(
+                "Usage: {} [elections|drain|transfer <id>|verify] [--server <addr>]",
+                args[0]
+            );
+            std::process::exit(1);
+        }
+    };
+
+    // El coordinador registra las conexiones de usuarios, no las de otros tipos
+    let identity = PeerIdentity::new(PeerType::ClientType, "server_admin");
+    let Some(stream) = connect_one(server, &identity).await else {
+        eprintln!("Failed to connect to the coordinator at {}", server);
+        std::process::exit(1);
+    };
+
+    let (done, finished) = oneshot::channel();
+    AdminRequest::create(|ctx| AdminRequest {
+        command,
+        communicator: Some(Communicator::new(
+            stream,
+            ctx.address(),
+            PeerType::CoordinatorType,
+        )),
+        done: Some(done),
+    });
+    std::process::exit(finished.await.unwrap_or(1));
+}
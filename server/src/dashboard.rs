@@ -0,0 +1,77 @@
+use crate::messages::internal_messages::GetClusterSnapshot;
+use crate::server_actors::services::cluster_snapshot::ClusterSnapshotService;
+use actix::Addr;
+use actix_web::dev::Server;
+use actix_web::{App, HttpResponse, HttpServer, Responder, get, web};
+use common::messages::internal_messages::GetOrderTimeline;
+use std::io;
+use std::net::SocketAddr;
+
+/// Environment variable that enables the web dashboard on the leader (`1`, `true` or `on`).
+pub const DASHBOARD_ENV_VAR: &str = "CLUSTER_DASHBOARD";
+
+/// The dashboard page. It polls `GET /api/snapshot` and renders the result.
+const DASHBOARD_PAGE: &str = include_str!("dashboard.html");
+
+/// Returns whether [`DASHBOARD_ENV_VAR`] enables the dashboard.
+pub fn is_enabled() -> bool {
+    std::env::var(DASHBOARD_ENV_VAR).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "on"
+        )
+    })
+}
+
+/// `GET /`: the dashboard page.
+#[get("/")]
+pub async fn index() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(DASHBOARD_PAGE)
+}
+
+/// `GET /api/snapshot`: the current state of the cluster, as JSON.
+#[get("/api/snapshot")]
+pub async fn snapshot(service: web::Data<Addr<ClusterSnapshotService>>) -> impl Responder {
+    match service.send(GetClusterSnapshot).await {
+        Ok(snapshot) => HttpResponse::Ok().json(snapshot),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// `GET /api/orders/{order_id}/timeline`: every change of an order, oldest first, as JSON.
+#[get("/api/orders/{order_id}/timeline")]
+pub async fn order_timeline(
+    service: web::Data<Addr<ClusterSnapshotService>>,
+    order_id: web::Path<u64>,
+) -> impl Responder {
+    let order_id = order_id.into_inner();
+    match service.send(GetOrderTimeline { order_id }).await {
+        Ok(timeline) if timeline.is_empty() => {
+            HttpResponse::NotFound().body(format!("No timeline for order {}", order_id))
+        }
+        Ok(timeline) => HttpResponse::Ok().json(timeline),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
+
+/// Binds the dashboard to `addr`. The returned server must be awaited (or spawned) to run.
+///
+/// ## Arguments
+/// * `addr` - The address to listen on.
+/// * `service` - The service that builds the snapshots.
+pub fn serve(addr: SocketAddr, service: Addr<ClusterSnapshotService>) -> io::Result<Server> {
+    let service = web::Data::new(service);
+    Ok(HttpServer::new(move || {
+        App::new()
+            .app_data(service.clone())
+            .service(index)
+            .service(snapshot)
+            .service(order_timeline)
+    })
+    .workers(1)
+    .disable_signals()
+    .bind(addr)?
+    .run())
+}
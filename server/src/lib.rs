@@ -1,3 +1,6 @@
+pub mod dashboard;
 pub mod messages;
 pub mod server_acceptor;
 pub mod server_actors;
+#[cfg(feature = "tui")]
+pub mod tui;
@@ -1,47 +1,92 @@
 use actix::prelude::*;
+use clap::Parser;
 use common::constants::SERVER_IP_ADDRESS;
-use common::constants::{BASE_PORT, NUM_COORDINATORS};
+use common::network::connections::{base_port_from_env, coordinator_addresses, parse_addr};
+use common::network::transport::TransportKind;
+use common::telemetry;
 use server::server_acceptor::acceptor::Acceptor;
+use server::server_acceptor::metrics::AcceptorMetrics;
 use server::server_actors::coordinator::Coordinator;
+use server::server_actors::coordinator_manager::WriteAckMode;
+use server::server_actors::election::ElectionKind;
+use server::server_actors::storage_log;
 use std::collections::HashMap;
-use std::env;
 use std::io::{self, Write};
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use tokio::signal::ctrl_c;
 
+/// Coordinator of PedidosRust: one of the servers of the ring.
+#[derive(Parser)]
+struct Args {
+    /// Port to listen on [default: BASE_PORT]
+    #[arg(conflicts_with = "bind")]
+    port: Option<u16>,
+    /// Address to listen on [default: the local address, on PORT]
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+    bind: Option<SocketAddr>,
+    /// Address the other servers and the users reach this one at, as listed in --servers,
+    /// e.g. when listening on 0.0.0.0 inside a container [default: the address to listen on]
+    #[arg(long, value_name = "ADDR", value_parser = parse_addr)]
+    advertise: Option<SocketAddr>,
+    /// Comma-separated addresses of every server of the ring, as host:port [default: the
+    /// local cluster, starting at BASE_PORT]
+    #[arg(long, value_delimiter = ',', value_name = "ADDR,...", value_parser = parse_addr)]
+    servers: Vec<SocketAddr>,
+}
+
 #[actix::main]
 async fn main() {
     // Permitir pasar el puerto como argumento: ejemplo => cargo run -- 8081
-    let args: Vec<String> = env::args().collect();
-    let port = if args.len() > 1 {
-        args[1].parse::<u16>().expect("Invalid port number")
+    let args = Args::parse();
+    let bind_addr = args.bind.unwrap_or_else(|| {
+        let ip: IpAddr = SERVER_IP_ADDRESS.parse().expect("Invalid IP address");
+        SocketAddr::new(ip, args.port.unwrap_or_else(base_port_from_env))
+    });
+    // Los demás servidores y los usuarios nos conocen por la dirección anunciada
+    let my_addr = args.advertise.unwrap_or(bind_addr);
+    if my_addr.ip().is_unspecified() {
+        eprintln!(
+            "Cannot advertise {}: use --advertise with an address the others can reach",
+            my_addr
+        );
+        std::process::exit(1);
+    }
+    let _telemetry = telemetry::init(&format!("server_{}", my_addr.port()));
+    let servers = if args.servers.is_empty() {
+        coordinator_addresses()
     } else {
-        BASE_PORT
+        args.servers
     };
 
-    let my_addr = format!("{}:{}", SERVER_IP_ADDRESS, port)
-        .parse::<SocketAddr>()
-        .expect("Failed to parse server address");
     // Construir la lista completa de ring_nodes
-    let ip: std::net::IpAddr = SERVER_IP_ADDRESS.parse().unwrap();
-
-    let ring_nodes: HashMap<String, SocketAddr> = (0..NUM_COORDINATORS)
-        .map(|i| {
-            let name = format!("server_{}", i);
-            let addr = SocketAddr::new(ip, BASE_PORT + i);
-            (name, addr)
-        })
+    let ring_nodes: HashMap<String, SocketAddr> = servers
+        .into_iter()
+        .enumerate()
+        .map(|(i, addr)| (format!("server_{}", i), addr))
         .collect();
 
     print!("\x1B[2J\x1B[1;1H");
     io::stdout().flush().unwrap();
 
-    // Iniciar el Coordinator
-    let coordinator = Coordinator::new(my_addr, ring_nodes).await;
+    // Iniciar el Coordinator, que reporta las conexiones que cuenta el Acceptor
+    let acceptor_metrics = AcceptorMetrics::default();
+    let coordinator = Coordinator::new(
+        my_addr,
+        ring_nodes,
+        TransportKind::from_env(),
+        WriteAckMode::from_env(),
+        ElectionKind::from_env(),
+        storage_log::dir_from_env(),
+    )
+    .await
+    .with_bind_addr(bind_addr)
+    .with_acceptor_metrics(acceptor_metrics.clone());
     let coordinator_addr = coordinator.start();
 
     // Iniciar el Acceptor (le pasamos ambos)
-    Acceptor::new(my_addr, coordinator_addr.clone()).start();
+    Acceptor::new(bind_addr, coordinator_addr.clone())
+        .with_metrics(acceptor_metrics)
+        .start();
 
     // Esperar señal de apagado
     tokio::select! {
@@ -1,14 +1,29 @@
 use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::coordinator_error::CoordinatorError;
 use crate::server_actors::coordinator_manager::CoordinatorManager;
+use crate::server_actors::dead_letters::DeadLetterStats;
+use crate::server_actors::gossip::{GossipPacket, MemberState};
+use crate::server_actors::order_reaper::OrderReaperStats;
+use crate::server_actors::services::cluster_snapshot::{
+    ClusterSnapshot, ConnectedPeer, FollowerProgress, RingState,
+};
 use crate::server_actors::services::orders_services::OrderService;
-use crate::server_actors::storage::Storage;
+use crate::server_actors::services::promotion_service::PromotionService;
+use crate::server_actors::storage::{CompactionStatus, Storage};
 use actix::prelude::*;
 use common::messages::internal_messages::StorageLogMessage;
+use common::messages::shared_messages::StorageReport;
 use common::network::communicator::Communicator;
-use common::types::dtos::{DeliveryDTO, OrderDTO, Snapshot};
+use common::network::transport::PeerTransport;
+use common::types::dtos::{DeliveryDTO, OrderDTO, PromoRedemption, Snapshot};
+use common::types::kitchen_load::KitchenLoad;
+use common::types::order_event::OrderEvent;
+use common::types::promotion::PromoRejection;
+use common::types::storage_entity::{StorageDigest, StorageEntity};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::net::SocketAddr;
+use tokio::sync::mpsc::UnboundedSender;
 
 /////////////////////////////////////////////////////////////////////
 // Mensajes del Aceptador al Coordinator
@@ -21,11 +36,13 @@ use std::net::SocketAddr;
 ///
 /// ## Contents
 /// - `client_addr`: The socket address of the client.
+/// - `user_id`: The user ID announced by the client when connecting.
 /// - `communicator`: The [`Communicator`] for the client connection.
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
 pub struct RegisterConnection {
     pub client_addr: SocketAddr,
+    pub user_id: String,
     pub communicator: Communicator<Coordinator>,
 }
 
@@ -53,29 +70,36 @@ pub struct SetCoordinatorManager {
 ///
 /// ## Contents
 /// - `remote_addr`: The socket address of the remote peer.
-/// - `communicator`: The [`Communicator`] for the peer connection.
+/// - `communicator`: The [`PeerTransport`] for the peer connection.
 #[derive(Message, Debug)]
 #[rtype(result = "()")]
 pub struct RegisterConnectionWithCoordinator {
     pub remote_addr: SocketAddr,
-    pub communicator: Communicator<Coordinator>,
+    pub communicator: Box<dyn PeerTransport>,
 }
 
 /// Message sent to add an accepted order and its delivery assignment.
 ///
 /// ## Purpose
-/// Used to record that an order has been accepted by a delivery agent and to notify the order service.
+/// Used to record that an order has been accepted by a delivery agent. If the acceptance
+/// is refused, the order service is sent a `DeliveryNoNeeded` instead.
 ///
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order.
 /// - `delivery`: The [`DeliveryDTO`] representing the delivery agent.
 /// - `addr`: The [`Addr<OrderService>`] for the order service.
+/// - `epoch`: The leader epoch in which the acceptance is decided.
+///
+/// ## Returns
+/// - `Option<LoggedWrite>`: The acceptance as it was logged, to be replicated, or `None` if
+///   it was refused.
 #[derive(Message, Debug, Clone)]
-#[rtype(result = "()")]
+#[rtype(result = "Option<LoggedWrite>")]
 pub struct AddOrderAccepted {
     pub order: OrderDTO,
     pub delivery: DeliveryDTO,
     pub addr: Addr<OrderService>,
+    pub epoch: u64,
 }
 
 /// Message sent to finish a delivery assignment for an order.
@@ -86,30 +110,127 @@ pub struct AddOrderAccepted {
 /// ## Contents
 /// - `order`: The [`OrderDTO`] representing the order.
 /// - `addr`: The [`Addr<OrderService>`] for the order service.
+///
+/// ## Returns
+/// - `bool`: Whether the delivery of the order is the one that accepted it. A repeated or
+///   conflicting assignment is not applied.
 #[derive(Message, Debug, Clone)]
-#[rtype(result = "()")]
+#[rtype(result = "bool")]
 pub struct FinishDeliveryAssignment {
     pub order: OrderDTO,
     pub addr: Addr<OrderService>,
 }
 
+/////////////////////////////////////////////////////////////////////
+// Mensajes del Coordinator Manager al Coordinator
+/////////////////////////////////////////////////////////////////////
+
+/// Message sent by the coordinator manager each time the local storage catches up with
+/// the ring.
+///
+/// ## Purpose
+/// Lets a follower coordinator know how fresh its replica is before answering read-only
+/// queries from it.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct ReplicaSynced;
+
 /////////////////////////////////////////////////////////////////////
 // Mensajes del Order Service
 /////////////////////////////////////////////////////////////////////
 
-/// Message to set the addresses of the coordinator and storage actors in the order service.
+/// Message to set the addresses of the coordinator, storage and promotion actors in the
+/// order service.
 ///
 /// ## Purpose
-/// Allows the order service to communicate with the coordinator and storage actors.
+/// Allows the order service to communicate with the coordinator, storage and promotion
+/// actors.
 ///
 /// ## Contents
 /// - `coordinator_addr`: The [`Addr<Coordinator>`] for the coordinator.
 /// - `storage_addr`: The [`Addr<Storage>`] for the storage actor.
+/// - `promotion_addr`: The [`Addr<PromotionService>`] that validates the promo codes.
 #[derive(Message, Debug, Clone)]
 #[rtype(result = "()")]
 pub struct SetActorsAddresses {
     pub coordinator_addr: Addr<Coordinator>,
     pub storage_addr: Addr<Storage>,
+    pub promotion_addr: Addr<PromotionService>,
+}
+
+/// Message sent by the coordinator each time a new leader epoch starts.
+///
+/// ## Purpose
+/// Lets the order service record in which epoch each delivery acceptance is decided, and
+/// bill again the orders of the payment outbox once its coordinator becomes the leader.
+///
+/// ## Contents
+/// - `epoch`: The epoch of the current leader.
+/// - `leader`: Whether the coordinator is the current leader.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct SetLeaderEpoch {
+    pub epoch: u64,
+    pub leader: bool,
+}
+
+/// A write applied to the storage of the leader, as it was logged there.
+///
+/// ## Contents
+/// - `index`: The index of the write in the log of the leader.
+/// - `update`: The [`StorageLogMessage`] logged, with the stamps of the leader.
+#[derive(Debug, Clone)]
+pub struct LoggedWrite {
+    pub index: u64,
+    pub update: StorageLogMessage,
+}
+
+/// Message sent by the order service to apply a write to the local storage before
+/// replicating it.
+///
+/// ## Contents
+/// - `update`: The [`StorageLogMessage`] to apply.
+///
+/// ## Returns
+/// - `Option<LoggedWrite>`: The write as it was logged, or `None` if the storage ignored it.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Option<LoggedWrite>")]
+pub struct AppendWrite {
+    pub update: StorageLogMessage,
+}
+
+/// Message sent by the order service to replicate a write it already applied locally.
+///
+/// ## Purpose
+/// In quorum write mode, lets the order service wait until a majority of the coordinators
+/// hold the write before notifying the client.
+///
+/// ## Contents
+/// - `write`: The [`LoggedWrite`] to replicate.
+///
+/// ## Returns
+/// - `Result<(), CoordinatorError>`: `Ok` if the write reached the quorum in time, or why it
+///   did not.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<(), CoordinatorError>")]
+pub struct QuorumWrite {
+    pub write: LoggedWrite,
+}
+
+/// Message sent to apply a write replicated by the leader.
+///
+/// ## Purpose
+/// The write is logged at the same index it has in the log of the leader, so that the
+/// next coordinators of the ring receive it with that index.
+///
+/// ## Contents
+/// - `index`: The index of the write in the log of the leader.
+/// - `update`: The [`StorageLogMessage`] to apply.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct ApplyReplicatedWrite {
+    pub index: u64,
+    pub update: StorageLogMessage,
 }
 
 /// Message to get the minimum log index from storage.
@@ -123,6 +244,17 @@ pub struct SetActorsAddresses {
 #[rtype(result = "u64")]
 pub struct GetMinLogIndex;
 
+/// Message to ask whether the storage was rebuilt from its persisted log at boot.
+///
+/// ## Purpose
+/// Lets a restarted coordinator catch up with storage updates instead of a full snapshot.
+///
+/// ## Returns
+/// - `bool`: Whether any record was replayed from the log.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "bool")]
+pub struct IsRestoredFromLog;
+
 /// Message to get all storage log messages from a given index.
 ///
 /// ## Purpose
@@ -139,6 +271,64 @@ pub struct GetLogsFromIndex {
     pub index: u64,
 }
 
+/// Message to verify the references between the entries of the storage.
+///
+/// ## Purpose
+/// Used to answer an administrator that asks for a [`VerifyStorage`](common::messages::VerifyStorage).
+///
+/// ## Returns
+/// - [`StorageReport`]: The references that do not hold, sorted by order.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "StorageReport")]
+pub struct CheckIntegrity;
+
+/// Message to get the digest of the storage.
+///
+/// ## Purpose
+/// Lets a follower compare its storage with the one of another follower.
+///
+/// ## Returns
+/// - [`StorageDigest`]: The revision of each kind of entity and the next index of the log.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "StorageDigest")]
+pub struct GetStorageDigest;
+
+/// Message to merge the entries of another follower into the storage.
+///
+/// ## Purpose
+/// Repairs the entries in which two followers diverged. Unlike a snapshot from the leader,
+/// the indices of the log are kept.
+///
+/// ## Contents
+/// - `snapshot`: The [`Snapshot`] with the entries to merge.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct MergeSnapshot {
+    pub snapshot: Box<Snapshot>,
+}
+
+/// Message to compact the storage log.
+///
+/// ## Purpose
+/// Drops the log entries every follower already applied and, if enough operations were
+/// persisted since the last compaction, replaces the persisted log with a snapshot.
+///
+/// ## Contents
+/// - `up_to`: The first log index still needed by some follower.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct CompactLog {
+    pub up_to: u64,
+}
+
+/// Message to get the state of the storage log and its compactions.
+///
+/// ## Returns
+/// - [`CompactionStatus`]: The indexes, size and compactions of the log.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "CompactionStatus")]
+pub struct GetCompactionStatus;
+
 /// Message to get a full snapshot of all storage data.
 ///
 /// ## Purpose
@@ -150,6 +340,29 @@ pub struct GetLogsFromIndex {
 #[rtype(result = "Snapshot")]
 pub struct GetAllStorage;
 
+/// Message to get the address of the current leader.
+///
+/// ## Purpose
+/// Lets tools and tests outside the ring find out which coordinator is the leader.
+///
+/// ## Returns
+/// - `Option<SocketAddr>`: The leader's address, or `None` while there is no leader.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Option<SocketAddr>")]
+pub struct GetLeader;
+
+/// Message to get the users connected to the cluster through this coordinator.
+///
+/// ## Purpose
+/// Lets the leader record that its users are still there before collecting the stale ones.
+///
+/// ## Returns
+/// - `Vec<String>`: The IDs of the users connected to this coordinator, and on the leader
+///   also the ones reached through a follower.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Vec<String>")]
+pub struct GetConnectedUsers;
+
 /// Represents a message to trigger a timer for the removal or cleanup of
 /// a user by its unique identifier.
 ///
@@ -161,6 +374,61 @@ pub struct ReapUser {
     pub user_id: String,
 }
 
+/// Message sent by the [`Reaper`](crate::server_actors::reaper::Reaper) when a delivery that
+/// was carrying an order, or had accepted one, does not reconnect within the grace period.
+///
+/// # Fields
+/// * `order_id` - The order to reassign.
+/// * `delivery_id` - The delivery that stopped responding.
+///
+/// # Returns
+/// `Ok` once the order was reassigned or if it no longer needs to be, or why it could not
+/// be, e.g. [`CoordinatorError::OrderNotFound`] if the order is gone.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Result<(), CoordinatorError>")]
+pub struct ReassignOrder {
+    pub order_id: u64,
+    pub delivery_id: String,
+}
+
+/// Message sent by the [`OrderReaper`](crate::server_actors::order_reaper::OrderReaper) when an
+/// order ready for delivery was not taken by any delivery for too long.
+///
+/// # Fields
+/// * `order_id` - The order to search a delivery for.
+/// * `radius` - How far from the restaurant to search, or `None` for the default radius.
+///
+/// # Returns
+/// `Ok` once the search started or if the order no longer needs one, or why it could not
+/// start, e.g. [`CoordinatorError::OrderNotFound`] if the order is gone.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "Result<(), CoordinatorError>")]
+pub struct RetryDeliverySearch {
+    pub order_id: u64,
+    pub radius: Option<f32>,
+}
+
+/// Message sent by the [`Coordinator`](crate::server_actors::coordinator::Coordinator) to the
+/// [`OrderService`](crate::server_actors::services::orders_services::OrderService) when no
+/// delivery accepted the offers of an order in time.
+///
+/// # Fields
+/// * `order` - The order nobody accepted.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "()")]
+pub struct DeliveryOffersExpired {
+    pub order: OrderDTO,
+}
+
+/// Message to get the counters of the stuck orders found by the
+/// [`OrderReaper`](crate::server_actors::order_reaper::OrderReaper).
+///
+/// ## Returns
+/// - [`OrderReaperStats`]: The stuck orders found, cancelled and retried.
+#[derive(Message, Debug, Clone, Serialize, Deserialize)]
+#[rtype(result = "OrderReaperStats")]
+pub struct GetOrderReaperStats;
+
 /// Message used to cancel reaping process for a specific user.
 ///
 /// # Fields
@@ -170,3 +438,214 @@ pub struct ReapUser {
 pub struct ReconnectUser {
     pub user_id: String,
 }
+
+/////////////////////////////////////////////////////////////////////
+// Mensajes del Promotion Service
+/////////////////////////////////////////////////////////////////////
+
+/// Message sent by the order service to apply the promo code chosen by the client to a new
+/// order.
+///
+/// ## Purpose
+/// Checks that the code exists, is still valid, applies to the restaurant of the order and
+/// was not used by the client before, and computes the discount.
+///
+/// ## Contents
+/// - `code`: The promo code typed by the client.
+/// - `order`: The new [`OrderDTO`].
+///
+/// ## Returns
+/// - `Result<PromoRedemption, PromoRejection>`: The use of the code to record once the
+///   order is authorized, or why the code cannot be used.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Result<PromoRedemption, PromoRejection>")]
+pub struct ApplyPromoCode {
+    pub code: String,
+    pub order: OrderDTO,
+}
+
+/////////////////////////////////////////////////////////////////////
+// Mensajes del Notification Service
+/////////////////////////////////////////////////////////////////////
+
+/// Message sent when an external consumer subscribes to the order updates of a client.
+///
+/// ## Purpose
+/// Registers a WebSocket subscriber with the notification service.
+///
+/// ## Contents
+/// - `client_id`: The client whose orders the subscriber wants to follow.
+/// - `subscriber_id`: Unique identifier of the subscription.
+/// - `sender`: Channel used to push the serialized updates to the WebSocket.
+#[derive(Message, Debug)]
+#[rtype(result = "()")]
+pub struct SubscribeNotifications {
+    pub client_id: String,
+    pub subscriber_id: u64,
+    pub sender: UnboundedSender<String>,
+}
+
+/// Message sent when a WebSocket subscriber disconnects.
+///
+/// ## Contents
+/// - `client_id`: The client the subscriber was following.
+/// - `subscriber_id`: Unique identifier of the subscription.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct UnsubscribeNotifications {
+    pub client_id: String,
+    pub subscriber_id: u64,
+}
+
+/////////////////////////////////////////////////////////////////////
+// Mensajes del Event Publisher
+/////////////////////////////////////////////////////////////////////
+
+/// Message sent by the storage when it records an event of an order that this coordinator
+/// applied first.
+///
+/// ## Purpose
+/// Hands the event to the event publisher, which sends it to the external consumers if it
+/// is a step of the lifecycle of the order.
+///
+/// ## Contents
+/// - `order`: The order after the change.
+/// - `event`: The event recorded in the timeline of the order.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct PublishOrderEvent {
+    pub order: OrderDTO,
+    pub event: OrderEvent,
+}
+
+/////////////////////////////////////////////////////////////////////
+// Mensajes del Cluster Snapshot Service
+/////////////////////////////////////////////////////////////////////
+
+/// Message to get the state of the ring from the coordinator manager.
+///
+/// ## Returns
+/// - [`RingState`]: The leader, the reachable coordinators and the recent elections.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "RingState")]
+pub struct GetRingState;
+
+/// Message to get the users connected to a coordinator.
+///
+/// ## Returns
+/// - `Vec<ConnectedPeer>`: One entry per open connection with a user.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Vec<ConnectedPeer>")]
+pub struct GetConnectedPeers;
+
+/// Message to get the counters of the messages a coordinator could not deliver to its users.
+///
+/// ## Returns
+/// - [`DeadLetterStats`]: The messages waiting, redelivered and discarded.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "DeadLetterStats")]
+pub struct GetDeadLetterStats;
+
+/// Message to get the last load reported by the kitchen of each restaurant.
+///
+/// ## Returns
+/// - `HashMap<String, KitchenLoad>`: The load of each kitchen, by restaurant ID.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "HashMap<String, KitchenLoad>")]
+pub struct GetKitchenLoads;
+
+/// Message to get the state of the whole cluster, as shown by the dashboard.
+///
+/// ## Returns
+/// - [`ClusterSnapshot`]: The aggregated state of the ring, the users and the storage.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "ClusterSnapshot")]
+pub struct GetClusterSnapshot;
+
+/////////////////////////////////////////////////////////////////////
+// Mensajes del TUI
+/////////////////////////////////////////////////////////////////////
+
+/// Message to count the users connected to a coordinator by type.
+///
+/// ## Returns
+/// - `BTreeMap<String, usize>`: The number of connections of each peer type.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "BTreeMap<String, usize>")]
+pub struct GetPeerCounts;
+
+/// Message to get the orders that are not finished yet from storage.
+///
+/// ## Returns
+/// - `Vec<OrderDTO>`: The orders neither delivered nor cancelled, oldest first.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Vec<OrderDTO>")]
+pub struct GetInFlightOrders;
+
+/// Message to get how far the coordinators that pull the storage log from this one have
+/// applied it.
+///
+/// ## Returns
+/// - `Vec<FollowerProgress>`: One entry per coordinator that reported its progress recently,
+///   sorted by ID.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Vec<FollowerProgress>")]
+pub struct GetFollowerProgress;
+
+/// Message asking the coordinator manager of the leader to hand the leadership off, before
+/// the coordinator is drained or to transfer it to a given coordinator.
+///
+/// The successor pulls the log from the leader and, once it applied the whole log, is
+/// announced as the leader of a new epoch.
+///
+/// ## Contents
+/// - `successor`: The ID of the coordinator that takes over, or `None` for the follower that
+///   applied most of the log.
+///
+/// ## Returns
+/// - `Option<SocketAddr>`: The address of the new leader, or `None` if the successor did not
+///   catch up in time. Without a successor, a coordinator that is not the leader returns the
+///   current one; with one, it forwards the request to the leader and waits for the
+///   announcement.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "Option<SocketAddr>")]
+pub struct HandOffLeadership {
+    pub successor: Option<String>,
+}
+
+/// Message asking the coordinator manager of a follower to recover some kinds of entities
+/// from the leader, without transferring the whole storage.
+///
+/// The entities received are merged into the local storage like a full snapshot: each entry
+/// only replaces the local one if it changed later.
+///
+/// ## Contents
+/// - `entities`: The kinds of entities to recover.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct ResyncStorage {
+    pub entities: Vec<StorageEntity>,
+}
+
+/////////////////////////////////////////////////////////////////////
+// Mensajes del detector de fallas
+/////////////////////////////////////////////////////////////////////
+
+/// Message carrying a packet received from the failure detector of another coordinator.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct GossipReceived {
+    /// The packet received.
+    pub packet: GossipPacket,
+}
+
+/// Message sent by the failure detector to the coordinator manager when the liveness of a
+/// coordinator changes.
+#[derive(Message, Debug, Clone)]
+#[rtype(result = "()")]
+pub struct MemberStateChanged {
+    /// ID of the coordinator.
+    pub coordinator_id: String,
+    /// Its new state.
+    pub state: MemberState,
+}
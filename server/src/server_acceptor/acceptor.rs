@@ -1,22 +1,73 @@
 use crate::messages::internal_messages::{RegisterConnection, RegisterConnectionWithCoordinator};
+use crate::server_acceptor::metrics::AcceptorMetrics;
 use crate::server_actors::coordinator::Coordinator;
 use actix::prelude::*;
 use colored::Color;
+use common::constants::{
+    ACCEPT_BACKLOG, CONNECTION_REFUSED_RETRY_AFTER, MAX_CONNECTIONS, SLOW_ACCEPT_THRESHOLD,
+};
 use common::logger::Logger;
+use common::messages::shared_messages::{NetworkMessage, RetryLater};
+use common::network::codec::{WireSettings, encode_frames};
 use common::network::communicator::Communicator;
-use common::network::peer_types::PeerType;
+use common::network::peer_types::{PeerIdentity, PeerType};
+use common::network::stream::{Listener, PeerStream};
 use std::net::SocketAddr;
-use tokio::io::AsyncReadExt;
-use tokio::net::TcpListener;
-use tokio::net::TcpStream;
+use std::time::Instant;
+use tokio::io::AsyncWriteExt;
+
+/// Environment variable with the maximum number of connections of users a server keeps
+/// open at the same time, [`MAX_CONNECTIONS`] by default. Connections of other coordinators
+/// are never refused.
+pub const MAX_CONNECTIONS_ENV_VAR: &str = "MAX_CONNECTIONS";
+
+/// Environment variable with the number of connections waiting to be accepted that the
+/// system keeps for a server, [`ACCEPT_BACKLOG`] by default.
+pub const ACCEPT_BACKLOG_ENV_VAR: &str = "ACCEPT_BACKLOG";
+
+/// Returns the maximum number of connections of users selected by [`MAX_CONNECTIONS_ENV_VAR`].
+pub fn max_connections_from_env() -> usize {
+    match std::env::var(MAX_CONNECTIONS_ENV_VAR) {
+        Ok(value) => match value.trim().parse::<usize>() {
+            Ok(max) if max > 0 => max,
+            _ => {
+                eprintln!(
+                    "[Acceptor] Ignoring invalid {}: '{}'",
+                    MAX_CONNECTIONS_ENV_VAR, value
+                );
+                MAX_CONNECTIONS
+            }
+        },
+        Err(_) => MAX_CONNECTIONS,
+    }
+}
+
+/// Returns the listen backlog selected by [`ACCEPT_BACKLOG_ENV_VAR`].
+pub fn backlog_from_env() -> u32 {
+    match std::env::var(ACCEPT_BACKLOG_ENV_VAR) {
+        Ok(value) => match value.trim().parse::<u32>() {
+            Ok(backlog) if backlog > 0 => backlog,
+            _ => {
+                eprintln!(
+                    "[Acceptor] Ignoring invalid {}: '{}'",
+                    ACCEPT_BACKLOG_ENV_VAR, value
+                );
+                ACCEPT_BACKLOG
+            }
+        },
+        Err(_) => ACCEPT_BACKLOG,
+    }
+}
 
 /// The `Acceptor` actor listens for incoming TCP connections and registers them
 /// with the appropriate coordinator actor based on the peer type.
 ///
 /// ## Responsibilities
 /// - Binds to a specified address and listens for incoming connections.
-/// - Reads the peer type and remote address from each connection.
+/// - Reads the identity (peer type and user ID) and remote address from each connection.
 /// - Wraps each connection in a [`Communicator`] and registers it with the coordinator.
+/// - Counts the accepted, refused and open connections of each peer type in its
+///   [`AcceptorMetrics`], and refuses users beyond [`MAX_CONNECTIONS_ENV_VAR`].
 pub struct Acceptor {
     /// The address to bind and listen for incoming connections.
     addr: SocketAddr,
@@ -24,6 +75,12 @@ pub struct Acceptor {
     coordinator_address: Addr<Coordinator>,
     /// Logger for acceptor events.
     logger: Logger,
+    /// Counters of the accepted connections.
+    metrics: AcceptorMetrics,
+    /// Connections of users kept open at the same time.
+    max_connections: usize,
+    /// Connections waiting to be accepted that the system keeps.
+    backlog: u32,
 }
 
 impl Acceptor {
@@ -37,8 +94,46 @@ impl Acceptor {
             addr,
             coordinator_address,
             logger: Logger::new("ACCEPTOR", Color::BrightBlack),
+            metrics: AcceptorMetrics::default(),
+            max_connections: max_connections_from_env(),
+            backlog: backlog_from_env(),
         }
     }
+
+    /// Counts the connections in `metrics` instead of in counters of its own, to share them.
+    ///
+    /// ## Arguments
+    /// * `metrics` - The counters of the accepted connections.
+    pub fn with_metrics(mut self, metrics: AcceptorMetrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Refuses the connection of a user, asking it to come back after
+    /// [`CONNECTION_REFUSED_RETRY_AFTER`], and closes it.
+    fn refuse(&self, mut stream: PeerStream, remote_addr: SocketAddr, user_id: &str) {
+        self.logger.warn(format!(
+            "Refusing connection from {} ({}): limit of {} connections of users reached",
+            user_id, remote_addr, self.max_connections
+        ));
+        let message = NetworkMessage::RetryLater(RetryLater {
+            origin_addr: remote_addr,
+            retry_after_millis: CONNECTION_REFUSED_RETRY_AFTER.as_millis() as u64,
+        });
+        let logger = self.logger.clone();
+        actix::spawn(async move {
+            // Antes del handshake, el otro extremo lee el formato por defecto
+            if let Ok(frames) = encode_frames(WireSettings::default(), &message) {
+                for frame in frames {
+                    if let Err(e) = stream.write_all(&frame).await {
+                        logger.info(format!("Error refusing {}: {}", remote_addr, e));
+                        return;
+                    }
+                }
+            }
+            let _ = stream.shutdown().await;
+        });
+    }
 }
 
 impl Actor for Acceptor {
@@ -48,45 +143,42 @@ impl Actor for Acceptor {
     /// to accept and process incoming TCP connections.
     fn started(&mut self, ctx: &mut Self::Context) {
         let addr = self.addr;
+        let backlog = self.backlog;
 
         let acceptor_addr = ctx.address();
         let logger = self.logger.clone();
 
         ctx.spawn(
             async move {
-                match TcpListener::bind(addr).await {
-                    Ok(listener) => {
+                match Listener::bind_with_backlog(addr, backlog).await {
+                    Ok(mut listener) => {
                         logger.info(format!("Server Acceptor started on {}", addr));
 
                         loop {
                             match listener.accept().await {
                                 Ok((mut stream, remote_addr)) => {
-                                    let mut peer_type_byte = [0u8; 1];
-
-                                    match stream.read_exact(&mut peer_type_byte).await {
-                                        Ok(_) => {
-                                            if let Some(peer_type) =
-                                                PeerType::from_u8(peer_type_byte[0])
-                                            {
+                                    let accepted_at = Instant::now();
+                                    let acceptor_addr = acceptor_addr.clone();
+                                    let logger = logger.clone();
+                                    // Un peer lento en identificarse no demora a los demás
+                                    actix::spawn(async move {
+                                        match PeerIdentity::read_from(&mut stream).await {
+                                            Ok(identity) => {
                                                 acceptor_addr.do_send(HandleConnection {
                                                     stream,
                                                     remote_addr,
-                                                    peer_type,
+                                                    identity,
+                                                    accepted_at,
                                                 });
-                                            } else {
+                                            }
+                                            Err(e) => {
                                                 logger.info(format!(
-                                                    "Received unsupported peer type byte from {}",
-                                                    remote_addr
+                                                    "Error while reading identity from {}: {}",
+                                                    remote_addr, e
                                                 ));
                                             }
                                         }
-                                        Err(e) => {
-                                            logger.info(format!(
-                                                "Error while reading peer type from {}: {}",
-                                                remote_addr, e
-                                            ));
-                                        }
-                                    }
+                                    });
                                 }
                                 Err(e) => {
                                     logger.info(format!("Error accepting connection: {}", e));
@@ -107,13 +199,15 @@ impl Actor for Acceptor {
 /// Internal message used to handle a new TCP connection.
 ///
 /// ## Purpose
-/// Contains the TCP stream, remote address, and peer type for the new connection.
+/// Contains the TCP stream, remote address, and identity of the new connection, and when it
+/// was accepted.
 #[derive(Message)]
 #[rtype(result = "()")]
 struct HandleConnection {
-    stream: TcpStream,
+    stream: PeerStream,
     remote_addr: SocketAddr,
-    peer_type: PeerType,
+    identity: PeerIdentity,
+    accepted_at: Instant,
 }
 
 impl Handler<HandleConnection> for Acceptor {
@@ -123,34 +217,61 @@ impl Handler<HandleConnection> for Acceptor {
     /// and registering it with the coordinator actor.
     ///
     /// - For `CoordinatorType` peers, registers with `RegisterConnectionWithCoordinator`.
-    /// - For `ClientType`, `RestaurantType`, and `DeliveryType` peers, registers with `RegisterConnection`.
+    /// - For `ClientType`, `RestaurantType`, and `DeliveryType` peers, registers with `RegisterConnection`,
+    ///   unless too many users are connected, in which case the connection is refused.
     /// - Logs unsupported peer types.
     fn handle(&mut self, msg: HandleConnection, _: &mut Context<Self>) {
         let HandleConnection {
             stream,
             remote_addr,
-            peer_type,
+            identity: PeerIdentity { peer_type, user_id },
+            accepted_at,
         } = msg;
 
+        let elapsed = accepted_at.elapsed();
+        if elapsed > SLOW_ACCEPT_THRESHOLD {
+            self.logger.warn(format!(
+                "Slow accept: {} ({}) took {:?} to identify itself",
+                user_id, remote_addr, elapsed
+            ));
+        }
+
         match peer_type {
             PeerType::CoordinatorType => {
-                self.logger
-                    .info("Received connection from Coordinator. Registering...");
+                self.logger.info(format!(
+                    "Received connection from Coordinator {}. Registering...",
+                    user_id
+                ));
                 let communicator =
                     Communicator::new(stream, self.coordinator_address.clone(), peer_type);
+                if let Some(sender) = &communicator.sender {
+                    self.metrics.accepted(peer_type, (**sender).clone());
+                }
                 self.coordinator_address
                     .do_send(RegisterConnectionWithCoordinator {
                         remote_addr,
-                        communicator,
+                        communicator: Box::new(communicator),
                     });
             }
             PeerType::ClientType | PeerType::RestaurantType | PeerType::DeliveryType => {
-                self.logger
-                    .info("Received connection from Client/Restaurant/Delivery. Registering...");
+                if self.metrics.active_users() >= self.max_connections {
+                    self.metrics.rejected(peer_type);
+                    self.refuse(stream, remote_addr, &user_id);
+                    return;
+                }
+                self.logger.info(format!(
+                    "Received connection from {} ({:?}). Registering...",
+                    user_id, peer_type
+                ));
+                // El coordinador sabe de qué conexión llega cada mensaje del usuario
                 let communicator =
-                    Communicator::new(stream, self.coordinator_address.clone(), peer_type);
+                    Communicator::new_tagged(stream, self.coordinator_address.clone(), peer_type);
+                if let Some(sender) = &communicator.sender {
+                    self.metrics.accepted(peer_type, (**sender).clone());
+                }
                 self.coordinator_address.do_send(RegisterConnection {
                     client_addr: remote_addr,
+                    user_id,
                     communicator,
                 });
             }
@@ -0,0 +1,117 @@
+use actix::Addr;
+use common::health::AcceptStats;
+use common::network::peer_types::PeerType;
+use common::network::tcp_sender::TCPSender;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Connections accepted from one kind of peer.
+#[derive(Default)]
+struct PeerConnections {
+    /// Connections accepted.
+    accepted: u64,
+    /// Connections refused.
+    rejected: u64,
+    /// Senders of the accepted connections, which stop when their connection closes.
+    open: Vec<Addr<TCPSender>>,
+}
+
+impl PeerConnections {
+    /// Forgets the connections that closed and returns how many are still open.
+    fn active(&mut self) -> usize {
+        self.open.retain(|sender| sender.connected());
+        self.open.len()
+    }
+}
+
+/// Counters of the connections accepted by the [`Acceptor`](super::acceptor::Acceptor),
+/// shared with the coordinator that reports them in its health endpoint.
+#[derive(Clone, Default)]
+pub struct AcceptorMetrics {
+    peers: Arc<Mutex<BTreeMap<String, PeerConnections>>>,
+}
+
+impl fmt::Debug for AcceptorMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.snapshot()).finish()
+    }
+}
+
+impl AcceptorMetrics {
+    /// Records a connection accepted from `peer_type`, open while `sender` runs.
+    pub fn accepted(&self, peer_type: PeerType, sender: Addr<TCPSender>) {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        let peer = peers.entry(format!("{:?}", peer_type)).or_default();
+        peer.accepted += 1;
+        peer.open.push(sender);
+    }
+
+    /// Records a connection refused from `peer_type`.
+    pub fn rejected(&self, peer_type: PeerType) {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        peers
+            .entry(format!("{:?}", peer_type))
+            .or_default()
+            .rejected += 1;
+    }
+
+    /// Returns how many accepted connections of users are still open.
+    pub fn active_users(&self) -> usize {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        peers
+            .iter_mut()
+            .filter(|(name, _)| *name != &format!("{:?}", PeerType::CoordinatorType))
+            .map(|(_, peer)| peer.active())
+            .sum()
+    }
+
+    /// Returns the current counters, by kind of peer.
+    pub fn snapshot(&self) -> BTreeMap<String, AcceptStats> {
+        let mut peers = self.peers.lock().unwrap_or_else(|e| e.into_inner());
+        peers
+            .iter_mut()
+            .map(|(name, peer)| {
+                (
+                    name.clone(),
+                    AcceptStats {
+                        accepted: peer.accepted,
+                        rejected: peer.rejected,
+                        active: peer.active(),
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix::{AsyncContext, Context};
+
+    #[test]
+    fn counts_the_connections_until_they_close() {
+        let metrics = AcceptorMetrics::default();
+        let client = Context::<TCPSender>::new();
+        let coordinator = Context::<TCPSender>::new();
+        metrics.accepted(PeerType::ClientType, client.address());
+        metrics.accepted(PeerType::CoordinatorType, coordinator.address());
+        metrics.rejected(PeerType::ClientType);
+
+        assert_eq!(metrics.active_users(), 1);
+        assert_eq!(
+            metrics.snapshot()["ClientType"],
+            AcceptStats {
+                accepted: 1,
+                rejected: 1,
+                active: 1,
+            }
+        );
+
+        drop(client);
+        assert_eq!(metrics.active_users(), 0);
+        assert_eq!(metrics.snapshot()["ClientType"].active, 0);
+        assert_eq!(metrics.snapshot()["CoordinatorType"].active, 1);
+    }
+}
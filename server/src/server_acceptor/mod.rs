@@ -1 +1,2 @@
 pub mod acceptor;
+pub mod metrics;
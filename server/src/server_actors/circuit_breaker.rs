@@ -0,0 +1,162 @@
+use common::constants::{PAYMENT_GATEWAY_RETRY_BASE, PAYMENT_GATEWAY_RETRY_MAX};
+use std::fmt;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// State of the connection to a remote service, as seen by a [`CircuitBreaker`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The service is reachable: messages are sent right away.
+    Closed,
+    /// The service is unreachable: messages are queued until the next attempt.
+    Open,
+    /// An attempt to reach the service is in progress.
+    HalfOpen,
+}
+
+impl fmt::Display for CircuitState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            CircuitState::Closed => "closed",
+            CircuitState::Open => "open",
+            CircuitState::HalfOpen => "half-open",
+        };
+        write!(f, "{}", state)
+    }
+}
+
+/// Circuit breaker for the connection to a remote service.
+///
+/// Each failed attempt to reach the service opens the circuit, and the next attempt is
+/// delayed with exponential backoff: [`PAYMENT_GATEWAY_RETRY_BASE`] after the first failure,
+/// doubling with each consecutive failure up to [`PAYMENT_GATEWAY_RETRY_MAX`]. A successful
+/// attempt closes the circuit and resets the backoff.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    /// Current state.
+    state: CircuitState,
+    /// Failures since the circuit was last closed.
+    failures: u32,
+    /// Delay after the first failure.
+    base: Duration,
+    /// Maximum delay between attempts.
+    max: Duration,
+    /// When the next attempt is due, while open.
+    retry_at: Option<Instant>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(PAYMENT_GATEWAY_RETRY_BASE, PAYMENT_GATEWAY_RETRY_MAX)
+    }
+}
+
+impl CircuitBreaker {
+    /// Creates a closed circuit breaker.
+    ///
+    /// ## Arguments
+    /// * `base` - Delay before the first retry.
+    /// * `max` - Maximum delay between retries.
+    pub fn new(base: Duration, max: Duration) -> Self {
+        Self {
+            state: CircuitState::Closed,
+            failures: 0,
+            base,
+            max,
+            retry_at: None,
+        }
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    /// Returns the failures since the circuit was last closed.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Returns whether messages can be sent to the service right away.
+    pub fn is_closed(&self) -> bool {
+        self.state == CircuitState::Closed
+    }
+
+    /// Records that the service was reached, closing the circuit.
+    pub fn record_success(&mut self) {
+        self.state = CircuitState::Closed;
+        self.failures = 0;
+        self.retry_at = None;
+    }
+
+    /// Records that the service could not be reached or was lost, opening the circuit.
+    ///
+    /// ## Returns
+    /// How long to wait before the next attempt.
+    pub fn record_failure(&mut self, now: Instant) -> Duration {
+        self.failures = self.failures.saturating_add(1);
+        // 2^(fallas - 1) veces la espera base, sin pasarse del máximo
+        let exponent = (self.failures - 1).min(31);
+        let delay = self
+            .base
+            .checked_mul(1 << exponent)
+            .map_or(self.max, |delay| delay.min(self.max));
+        self.state = CircuitState::Open;
+        self.retry_at = Some(now + delay);
+        delay
+    }
+
+    /// Starts an attempt to reach the service, if one is due.
+    ///
+    /// ## Returns
+    /// Whether the attempt can be made. It can not while the circuit is closed, while
+    /// another attempt is in progress, or before the backoff elapsed.
+    pub fn try_attempt(&mut self, now: Instant) -> bool {
+        match (self.state, self.retry_at) {
+            (CircuitState::Open, Some(retry_at)) if now >= retry_at => {
+                self.state = CircuitState::HalfOpen;
+                self.retry_at = None;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_maximum() {
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new(Duration::from_secs(1), Duration::from_secs(5));
+        let delays: Vec<u64> = (0..5)
+            .map(|_| breaker.record_failure(now).as_secs())
+            .collect();
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        breaker.record_success();
+        assert!(breaker.is_closed());
+        assert_eq!(breaker.record_failure(now), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn attempts_only_once_the_backoff_elapsed() {
+        let now = Instant::now();
+        let mut breaker = CircuitBreaker::new(Duration::from_secs(1), Duration::from_secs(5));
+        assert!(!breaker.try_attempt(now));
+
+        let delay = breaker.record_failure(now);
+        assert!(!breaker.try_attempt(now));
+        assert!(breaker.try_attempt(now + delay));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        // Ya hay un intento en curso
+        assert!(!breaker.try_attempt(now + delay));
+
+        breaker.record_failure(now + delay);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(breaker.failures(), 2);
+    }
+}
@@ -1,44 +1,120 @@
 use actix::prelude::*;
 use colored::Color;
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     net::SocketAddr,
+    path::PathBuf,
     process,
-    time::Duration,
+    sync::Arc,
 };
-use tokio::net::TcpStream;
+use tokio::time::Instant;
+use tracing::info_span;
 
 use crate::{
+    dashboard,
     messages::internal_messages::{
-        ReapUser, ReconnectUser, RegisterConnection, RegisterConnectionWithCoordinator,
-        SetActorsAddresses,
+        CheckIntegrity, DeliveryOffersExpired, GetCompactionStatus, GetConnectedPeers,
+        GetConnectedUsers, GetDeadLetterStats, GetFollowerProgress, GetKitchenLoads, GetLeader,
+        GetPeerCounts, GetRingState, HandOffLeadership, QuorumWrite, ReapUser, ReassignOrder,
+        ReconnectUser, RegisterConnection, RegisterConnectionWithCoordinator, ReplicaSynced,
+        RetryDeliverySearch, SetActorsAddresses, SetLeaderEpoch,
     },
+    server_acceptor::metrics::AcceptorMetrics,
     server_actors::{
-        coordinator_manager::CoordinatorManager,
+        coordinator_error::CoordinatorError,
+        coordinator_manager::{CoordinatorManager, WriteAckMode},
+        dead_letters::DeadLetterQueue,
+        election::ElectionKind,
+        hash_ring::{self, HashRing},
+        offer_scheduler::OfferScheduler,
+        order_reaper::OrderReaper,
+        rate_limiter::{RateDecision, RateLimitKey, RateLimiter},
         reaper::{self, Reaper},
         services::{
-            nearby_delivery::NearbyDeliveryService, nearby_restaurants::NearbyRestaurantsService,
-            orders_services::OrderService,
+            cluster_snapshot::{ClusterSnapshotService, ConnectedPeer},
+            event_publisher::{self, EventPublisher, EventSinkKind},
+            nearby_delivery::NearbyDeliveryService,
+            nearby_restaurants::NearbyRestaurantsService,
+            notification_service::NotificationService,
+            order_service_pool::{self, OrderServicePool},
+            promotion_service::PromotionService,
         },
         storage::Storage,
+        storage_backend::{EntityTables, StorageBackendKind},
+        storage_log::StorageLogFile,
+        storage_view::StorageReadView,
+        user_collector::{self, UserCollector},
     },
 };
 use common::{
     bimap::BiMap,
-    constants::BASE_PORT,
+    constants::{
+        DEAD_LETTER_PURGE_INTERVAL, DRAIN_GRACE_PERIOD, EVENT_SUBSCRIBERS_PORT_OFFSET,
+        REPLICA_MAX_STALENESS, RETRY_LATER_WHILE_ELECTING, WS_NOTIFICATIONS_PORT_OFFSET,
+    },
+    health::{self, GetHealth, HealthReport},
     logger::Logger,
     messages::{
-        CancelOrder, DeliverThisOrder, OrderFinalized, UpdateOrderStatus, coordinator_messages::*,
-        internal_messages::*, shared_messages::*,
+        CancelMyOrder, CancelOrder, CancellationCharged, DeliverThisOrder, GetMyEarnings,
+        OrderFinalized, RequestNearbyDelivery, RequestOrderStatus, StillLookingForDelivery,
+        UpdateOrderStatus,
+        coordinator_messages::*,
+        coordinatormanager_messages::{ForwardToLeader, ForwardToUser, ProxyUser},
+        internal_messages::*,
+        shared_messages::*,
+    },
+    network::{
+        communicator::Communicator,
+        connections::connect_to_all,
+        peer_types::{PeerIdentity, PeerType},
+        stream::PeerStream,
+        tcp_sender::TCPSender,
+        transport::{PeerTransport, TransportKind, open_transport, serve_transport},
     },
-    network::{communicator::Communicator, connections::connect_to_all, peer_types::PeerType},
     types::{
         delivery_status::DeliveryStatus,
-        dtos::{ClientDTO, DeliveryDTO, OrderDTO, RestaurantDTO, UserDTO},
+        dtos::{
+            ClientDTO, DeliveryDTO, ElectionRecord, OfferDetails, OrderDTO, RestaurantDTO, UserDTO,
+        },
+        hlc::HlcTimestamp,
+        kitchen_load::KitchenLoad,
+        order_event::{OrderEvent, OrderEventKind},
         order_status::OrderStatus,
+        tenant::TenantId,
     },
 };
 
+/// Environment variable used to let users stay connected to a follower, which passes their
+/// messages to the leader and the answers back, instead of sending them to the leader.
+pub const USER_PROXY_ENV_VAR: &str = "USER_PROXY";
+
+/// Returns whether [`USER_PROXY_ENV_VAR`] enables user proxying.
+pub fn user_proxy_enabled() -> bool {
+    std::env::var(USER_PROXY_ENV_VAR).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "on"
+        )
+    })
+}
+
+/// The follower a user is connected to, as known by the leader.
+#[derive(Debug, Clone)]
+pub struct UserRoute {
+    /// The ID of the follower.
+    pub coordinator_id: String,
+    /// Whether the user is a client, a restaurant or a delivery.
+    pub peer_type: PeerType,
+}
+
+/// How a coordinator reaches a user.
+enum UserLink {
+    /// Over its own connection with the user.
+    Direct(Arc<Addr<TCPSender>>),
+    /// Through the follower with this ID.
+    Follower(String),
+}
+
 /// The `Coordinator` actor orchestrates the main logic of the distributed system,
 /// managing user connections, order processing, communication with other coordinators,
 /// and coordination with services such as storage, order management, and nearby services.
@@ -49,6 +125,14 @@ use common::{
 /// - Coordinates order assignment, delivery, and cancellation.
 /// - Manages communication with the `CoordinatorManager` for leader election and storage updates.
 /// - Interfaces with the `OrderService`, `NearbyRestaurantsService`, and `NearbyDeliveryService`.
+/// - Mirrors order updates to the `NotificationService` for WebSocket subscribers.
+/// - Talks to the other coordinators over TCP or gRPC, as selected by [`TransportKind`].
+/// - Rate limits the requests of each user, disconnecting the ones that keep flooding.
+/// - Keeps the messages of disconnected users until they reconnect, in a [`DeadLetterQueue`].
+/// - With [`USER_PROXY_ENV_VAR`], keeps users connected to a follower and passes their
+///   messages to the leader and back.
+/// - With [`USER_SHARDING_ENV_VAR`](hash_ring::USER_SHARDING_ENV_VAR), sends each user to
+///   the coordinator chosen by its [`HashRing`].
 #[derive(Debug)]
 pub struct Coordinator {
     /// Unique identifier for this coordinator.
@@ -57,30 +141,77 @@ pub struct Coordinator {
     pub ring_nodes: HashMap<String, SocketAddr>,
     /// Socket address of this coordinator.
     pub my_addr: SocketAddr,
+    /// Address this coordinator listens on, which differs from `my_addr` if it is not
+    /// reachable at the address it binds to, e.g. inside a container.
+    pub bind_addr: SocketAddr,
     /// Current coordinator's address (leader).
     pub current_coordinator: Option<SocketAddr>,
+    /// Epoch of the current leader.
+    pub epoch: u64,
     /// Bi-directional map of user addresses and user IDs.
     pub user_addresses: BiMap<SocketAddr, String>,
     /// Map of remote addresses to their communicators.
     pub communicators: HashMap<SocketAddr, Communicator<Coordinator>>,
     /// Address of the storage actor.
     pub storage: Option<Addr<Storage>>,
-    /// Address of the order service actor.
-    pub order_service: Option<Addr<OrderService>>,
+    /// The order service actors, each one processing a share of the orders.
+    pub order_service: Option<OrderServicePool>,
     /// Address of the nearby restaurants service actor.
     pub nearby_restaurant_service: Option<Addr<NearbyRestaurantsService>>,
     /// Address of the nearby delivery service actor.
     pub nearby_delivery_service: Option<Addr<NearbyDeliveryService>>,
+    /// Address of the WebSocket notification service actor.
+    pub notification_service: Option<Addr<NotificationService>>,
     /// Reaper for removing inactive users
     pub reaper: Option<Addr<Reaper>>,
+    /// Reaper for cancelling or retrying stuck orders
+    pub order_reaper: Option<Addr<OrderReaper>>,
+    /// Collector of the clients and deliveries that stopped coming
+    pub user_collector: Option<Addr<UserCollector>>,
+    /// Address of the cluster snapshot service actor, if the dashboard is enabled.
+    pub cluster_snapshot_service: Option<Addr<ClusterSnapshotService>>,
     /// Logger for coordinator events.
     pub logger: Logger,
     /// Address of the coordinator manager actor.
     pub coordinator_manager: Option<Addr<CoordinatorManager>>,
+    /// Transport used to talk to the other coordinators.
+    pub transport: TransportKind,
+    /// When storage writes are acknowledged to clients.
+    pub write_ack: WriteAckMode,
+    /// The leader election algorithm.
+    pub election: ElectionKind,
+    /// Directory where the storage log is persisted, if persistence is enabled.
+    pub storage_log_dir: Option<PathBuf>,
+    /// Where the storage keeps the clients, restaurants, deliveries and orders.
+    pub storage_backend: StorageBackendKind,
     /// Pending TCP streams for ring connections.
-    pub pending_streams: HashMap<SocketAddr, TcpStream>,
+    pub pending_streams: HashMap<SocketAddr, PeerStream>,
     /// Timers for order assignment timeouts.
     pub order_timers: HashMap<u64, SpawnHandle>,
+    /// Limits the orders offered to the deliveries at the same time.
+    pub offer_scheduler: OfferScheduler,
+    /// Last time the local storage caught up with the ring.
+    pub last_replica_sync: Option<Instant>,
+    /// Rate limiter for the requests of the users connected to this coordinator.
+    pub rate_limiter: RateLimiter,
+    /// Messages that could not be delivered to their user, kept until it reconnects.
+    pub dead_letters: DeadLetterQueue,
+    /// Whether users stay connected to this coordinator while it is a follower.
+    pub proxy_users: bool,
+    /// The coordinator each user belongs to, if users are sharded across the coordinators.
+    pub user_shards: Option<HashRing>,
+    /// On the leader, the follower each user connected to another coordinator is reached
+    /// through.
+    pub user_routes: HashMap<String, UserRoute>,
+    /// On a follower, the users whose registration it forwarded to the leader.
+    pub proxied_users: HashMap<String, PeerType>,
+    /// Last load reported by the kitchen of each restaurant, by restaurant ID.
+    pub kitchen_loads: HashMap<String, KitchenLoad>,
+    /// Whether the coordinator is being drained before a restart, sending its users to
+    /// another coordinator.
+    pub draining: bool,
+    /// Counters of the connections accepted by the acceptor, reported by the health endpoint.
+    pub acceptor_metrics: AcceptorMetrics,
 }
 
 impl Coordinator {
@@ -90,127 +221,435 @@ impl Coordinator {
     /// ## Arguments
     /// * `srv_addr` - The socket address of this coordinator.
     /// * `ring_nodes` - Map of all ring node IDs to their addresses.
-    pub async fn new(srv_addr: SocketAddr, ring_nodes: HashMap<String, SocketAddr>) -> Self {
+    /// * `transport` - Transport used to talk to the other coordinators.
+    /// * `write_ack` - When storage writes are acknowledged to clients.
+    /// * `election` - The leader election algorithm.
+    /// * `storage_log_dir` - Directory where the storage log is persisted, or `None` to keep
+    ///   the storage only in memory.
+    pub async fn new(
+        srv_addr: SocketAddr,
+        ring_nodes: HashMap<String, SocketAddr>,
+        transport: TransportKind,
+        write_ack: WriteAckMode,
+        election: ElectionKind,
+        storage_log_dir: Option<PathBuf>,
+    ) -> Self {
         // Inicializar el coordinador con la dirección del servidor y los nodos del anillo
         // y un logger compartido.
         let ring_nodes_vec: Vec<SocketAddr> = ring_nodes.values().cloned().collect();
-
-        let pending_streams: HashMap<SocketAddr, TcpStream> =
-            connect_to_all(ring_nodes_vec, PeerType::CoordinatorType).await;
-
-        if pending_streams.is_empty() {
+        // El nombre en el anillo no depende de los puertos elegidos para el cluster
+        let id = ring_nodes
+            .iter()
+            .find(|(_, addr)| **addr == srv_addr)
+            .map(|(name, _)| name.clone())
+            .unwrap_or_else(|| format!("server_{}", srv_addr.port()));
+        let identity = PeerIdentity::new(PeerType::CoordinatorType, id.clone());
+
+        // Con gRPC los transportes se abren al iniciar el actor, sin streams previos
+        let pending_streams: HashMap<SocketAddr, PeerStream> = match transport {
+            TransportKind::Tcp => connect_to_all(ring_nodes_vec, &identity).await,
+            TransportKind::Grpc => HashMap::new(),
+        };
+
+        if transport == TransportKind::Tcp && pending_streams.is_empty() {
             println!("No connections established.");
         }
 
+        // Repartir los usuarios requiere que los seguidores los atiendan
+        let user_shards = hash_ring::is_enabled().then(|| HashRing::new(ring_nodes.keys()));
+
         Self {
-            id: format!("server_{}", srv_addr.port() - BASE_PORT),
+            id,
             ring_nodes,
             my_addr: srv_addr,
+            bind_addr: srv_addr,
             current_coordinator: None,
+            epoch: 0,
             user_addresses: BiMap::new(),
             logger: Logger::new("COORDINATOR", Color::Black),
             coordinator_manager: None,
             communicators: HashMap::new(),
+            transport,
+            write_ack,
+            election,
+            storage_log_dir,
+            storage_backend: StorageBackendKind::from_env(),
             pending_streams,
-            order_service: Some(OrderService::new().await.start()),
+            order_service: Some(
+                OrderServicePool::start(&identity, order_service_pool::shards_from_env()).await,
+            ),
             nearby_restaurant_service: None,
             nearby_delivery_service: None,
+            notification_service: None,
             reaper: None,
+            order_reaper: None,
+            user_collector: None,
+            cluster_snapshot_service: None,
             storage: None,
             order_timers: HashMap::new(),
+            offer_scheduler: OfferScheduler::from_env(),
+            last_replica_sync: None,
+            rate_limiter: RateLimiter::default(),
+            dead_letters: DeadLetterQueue::default(),
+            proxy_users: user_proxy_enabled() || user_shards.is_some(),
+            user_shards,
+            user_routes: HashMap::new(),
+            proxied_users: HashMap::new(),
+            kitchen_loads: HashMap::new(),
+            draining: false,
+            acceptor_metrics: AcceptorMetrics::default(),
         }
     }
 
     /// Sends a [`NetworkMessage`] to a user by their user ID.
     ///
+    /// If the user is not connected, the message is kept in the dead-letter queue and sent
+    /// when the user registers again.
+    ///
     /// ## Arguments
     /// * `user_id` - The user ID to send the message to.
     /// * `message` - The [`NetworkMessage`] to send.
-    pub fn send_network_message(&self, user_id: String, message: NetworkMessage) {
-        if let Some(user_addr) = self.user_addresses.get_by_value(&user_id).cloned() {
-            if let Some(communicator) = self.communicators.get(&user_addr) {
-                if let Some(sender) = &communicator.sender {
-                    sender.do_send(message);
+    pub fn send_network_message(&mut self, user_id: String, message: NetworkMessage) {
+        match self.user_link(&user_id) {
+            Ok(link) => self.send_through(&link, &user_id, message),
+            Err(reason) => {
+                self.logger.info(format!(
+                    "{}, keeping the message until {} reconnects",
+                    reason, user_id
+                ));
+                self.dead_letters.push(&user_id, message, Instant::now());
+            }
+        }
+    }
+
+    /// Returns the sender of the connection with a user.
+    ///
+    /// ## Returns
+    /// The sender, or why the user cannot be reached.
+    fn user_sender(&self, user_id: &str) -> Result<Arc<Addr<TCPSender>>, CoordinatorError> {
+        let user_addr = self
+            .user_addresses
+            .get_by_value(&user_id.to_string())
+            .ok_or_else(|| CoordinatorError::UserNotFound(user_id.to_string()))?;
+        let communicator = self
+            .communicators
+            .get(user_addr)
+            .ok_or_else(|| CoordinatorError::CommunicatorNotFound(user_id.to_string()))?;
+        communicator
+            .sender
+            .clone()
+            .ok_or_else(|| CoordinatorError::SenderNotFound(user_id.to_string()))
+    }
+
+    /// Returns how to reach a user: over its connection with this coordinator or, on the
+    /// leader, through the follower it is connected to.
+    ///
+    /// ## Returns
+    /// The link, or why the user cannot be reached.
+    fn user_link(&self, user_id: &str) -> Result<UserLink, CoordinatorError> {
+        match self.user_sender(user_id) {
+            Ok(sender) => Ok(UserLink::Direct(sender)),
+            Err(reason) => match self.user_routes.get(user_id) {
+                Some(route) if !self.is_follower() => {
+                    Ok(UserLink::Follower(route.coordinator_id.clone()))
+                }
+                _ => Err(reason),
+            },
+        }
+    }
+
+    /// Sends a message to a user over `link`.
+    ///
+    /// ## Arguments
+    /// * `link` - How to reach the user.
+    /// * `user_id` - The user to send the message to.
+    /// * `message` - The [`NetworkMessage`] to send.
+    fn send_through(&self, link: &UserLink, user_id: &str, message: NetworkMessage) {
+        match link {
+            UserLink::Direct(sender) => sender.do_send(message),
+            UserLink::Follower(coordinator_id) => {
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(ForwardToUser {
+                        coordinator_id: coordinator_id.clone(),
+                        user_id: user_id.to_string(),
+                        message: Box::new(message),
+                    });
                 } else {
-                    self.logger.info(format!("No sender found for {}", user_id));
+                    self.logger.info("CoordinatorManager not initialized yet.");
                 }
-            } else {
-                self.logger
-                    .info(format!("Communicator not found for {}", user_id));
             }
+        }
+    }
+
+    /// Sends a user the messages kept for it while it was disconnected.
+    ///
+    /// ## Arguments
+    /// * `user_id` - The user that registered again.
+    fn redeliver_dead_letters(&mut self, user_id: &str) {
+        let Ok(link) = self.user_link(user_id) else {
+            return;
+        };
+        let messages = self.dead_letters.take(user_id, Instant::now());
+        if messages.is_empty() {
+            return;
+        }
+        self.logger.info(format!(
+            "Redelivering {} kept messages to {}",
+            messages.len(),
+            user_id
+        ));
+        for message in messages {
+            self.send_through(&link, user_id, message);
+        }
+    }
+
+    /// Sends a user an update kept in its outbox.
+    ///
+    /// If the user is not connected the update is not kept in the dead-letter queue: the
+    /// outbox already has it and replays it when the user registers again.
+    ///
+    /// ## Arguments
+    /// * `update` - The [`NotifyOrderUpdated`] to send.
+    fn send_order_update(&self, update: NotifyOrderUpdated) {
+        match self.user_link(&update.peer_id) {
+            Ok(link) => {
+                let peer_id = update.peer_id.clone();
+                self.send_through(&link, &peer_id, NetworkMessage::NotifyOrderUpdated(update));
+            }
+            Err(reason) => self.logger.info(format!(
+                "{}, update {} kept in the outbox",
+                reason, update.update_id
+            )),
+        }
+    }
+
+    /// Sends a user that registered again the order updates it did not acknowledge, oldest
+    /// first.
+    ///
+    /// Only the leader replays them: the connections with the followers are only used for
+    /// queries.
+    ///
+    /// ## Arguments
+    /// * `user_id` - The user that registered again.
+    /// * `ctx` - The actor context.
+    fn replay_order_updates(&self, user_id: String, ctx: &mut Context<Self>) {
+        if self.is_follower() {
+            return;
+        }
+        let Some(storage) = self.storage.clone() else {
+            return;
+        };
+        storage
+            .send(GetPendingOrderUpdates { user_id })
+            .into_actor(self)
+            .map(|res, act, _ctx| {
+                let updates = res.unwrap_or_default();
+                if let Some(first) = updates.first() {
+                    act.logger.info(format!(
+                        "Replaying {} missed order updates to {}",
+                        updates.len(),
+                        first.peer_id
+                    ));
+                }
+                for update in updates {
+                    act.send_order_update(update);
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Resumes the delivery assignments accepted in an older epoch and not finished yet,
+    /// telling the restaurant of each order which delivery won it.
+    ///
+    /// Restaurants not connected yet are told when they register.
+    ///
+    /// ## Arguments
+    /// * `restaurant_id` - Only resume the orders of this restaurant, or every order if `None`.
+    /// * `ctx` - The actor context.
+    fn resume_pending_assignments(&self, restaurant_id: Option<String>, ctx: &mut Context<Self>) {
+        let Some(storage) = self.storage.clone() else {
+            self.logger
+                .error("Storage not initialized, cannot resume assignments.");
+            return;
+        };
+        storage
+            .send(GetPendingAssignments {
+                restaurant_id,
+                before_epoch: self.epoch,
+            })
+            .into_actor(self)
+            .map(|res, act, ctx| {
+                for assignment in res.unwrap_or_default() {
+                    act.logger.info(format!(
+                        "Resuming assignment of order {} to delivery {}",
+                        assignment.order.order_id, assignment.delivery_info.delivery_id
+                    ));
+                    act.handle(assignment, ctx);
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Asks the [`NearbyDeliveryService`] for the deliveries near the restaurant of an order.
+    ///
+    /// ## Arguments
+    /// * `order` - The order ready for delivery.
+    /// * `restaurant` - The restaurant that prepared it.
+    /// * `radius` - How far from the restaurant to search, or `None` for the default radius.
+    fn request_nearby_delivery(
+        &self,
+        order: OrderDTO,
+        restaurant: RestaurantDTO,
+        radius: Option<f32>,
+    ) {
+        if let Some(service) = &self.nearby_delivery_service {
+            service.do_send(RequestNearbyDelivery {
+                order,
+                restaurant_info: restaurant.info(),
+                radius,
+            });
         } else {
-            self.logger.info(format!("User ID {} not found", user_id));
+            self.logger
+                .warn("NearbyDeliveryService not initialized yet.");
+        }
+    }
+
+    /// Listens on `bind_addr` instead of the address the other coordinators and the users
+    /// reach this one at.
+    ///
+    /// ## Arguments
+    /// * `bind_addr` - The address to listen on, e.g. `0.0.0.0` on the same port.
+    pub fn with_bind_addr(mut self, bind_addr: SocketAddr) -> Self {
+        self.bind_addr = bind_addr;
+        self
+    }
+
+    /// Reports the connections counted by the acceptor in `acceptor_metrics`.
+    ///
+    /// ## Arguments
+    /// * `acceptor_metrics` - The counters shared with the [`Acceptor`](crate::server_acceptor::acceptor::Acceptor).
+    pub fn with_acceptor_metrics(mut self, acceptor_metrics: AcceptorMetrics) -> Self {
+        self.acceptor_metrics = acceptor_metrics;
+        self
+    }
+
+    /// Returns the address to listen on for the endpoint `offset` ports above this
+    /// coordinator.
+    fn listen_addr(&self, offset: u16) -> SocketAddr {
+        let mut addr = self.bind_addr;
+        addr.set_port(self.bind_addr.port() + offset);
+        addr
+    }
+
+    /// Starts the publisher of the order lifecycle events, if a sink was chosen in
+    /// [`EVENT_SINK_ENV_VAR`](event_publisher::EVENT_SINK_ENV_VAR).
+    fn start_event_publisher(&self) -> Option<Addr<EventPublisher>> {
+        let kind = EventSinkKind::from_env()?;
+        let subscribers_addr = self.listen_addr(EVENT_SUBSCRIBERS_PORT_OFFSET);
+        match event_publisher::open_sink(&kind, subscribers_addr, EventPublisher::logger()) {
+            Ok(sink) => {
+                self.logger
+                    .info(format!("Publishing order events to {:?}", kind));
+                Some(EventPublisher::new(sink).start())
+            }
+            Err(e) => {
+                self.logger.error(format!(
+                    "Error opening the {:?} event sink, not publishing order events: {}",
+                    kind, e
+                ));
+                None
+            }
         }
     }
 
     /// Broadcasts delivery offers to all available delivery agents for a given order,
-    /// and starts a timer to cancel the order if not accepted in time.
+    /// and starts a timer to tell the `OrderService` if not accepted in time, so it offers
+    /// the order again or cancels it.
     ///
     /// ## Arguments
     /// * `order` - The [`OrderDTO`] to be delivered.
-    /// * `deliveries` - List of [`DeliveryDTO`]s representing available delivery agents.
+    /// * `deliveries` - The available delivery agents, each with the [`OfferDetails`] of the
+    ///   order for it.
     /// * `ctx` - The actor context.
     pub fn broadcast_deliveries(
         &mut self,
         order: OrderDTO,
-        deliveries: Vec<DeliveryDTO>,
+        deliveries: Vec<(DeliveryDTO, OfferDetails)>,
         ctx: &mut Context<Self>,
     ) {
-        for delivery in deliveries {
-            if let Some(delivery_addr) = self
-                .user_addresses
-                .get_by_value(&delivery.delivery_id)
-                .cloned()
-            {
-                if let Some(communicator) = self.communicators.get(&delivery_addr) {
-                    if let Some(sender) = &communicator.sender {
-                        sender.do_send(NetworkMessage::NewOfferToDeliver(NewOfferToDeliver {
-                            order: order.clone(),
-                        }));
-                    } else {
-                        self.logger
-                            .info(format!("No sender found for {}", delivery.delivery_id));
-                    }
-                } else {
-                    self.logger.info(format!(
-                        "Communicator not found for {}",
-                        delivery.delivery_id
-                    ));
-                }
-            } else {
-                self.logger
-                    .info(format!("User ID {} not found", delivery.delivery_id));
+        for (delivery, details) in deliveries {
+            match self.user_link(&delivery.delivery_id) {
+                Ok(link) => self.send_through(
+                    &link,
+                    &delivery.delivery_id,
+                    NetworkMessage::NewOfferToDeliver(NewOfferToDeliver {
+                        order: order.clone(),
+                        details: Some(details),
+                    }),
+                ),
+                Err(reason) => self.logger.info(reason.to_string()),
             }
         }
 
         // Iniciar timer para la orden
         let order_id = order.order_id;
-        let timer_duration = Duration::from_secs(6); // Por ejemplo, 30 segundos
+        // Los pedidos express tienen menos tiempo para conseguir un delivery
+        let timer_duration = order.priority.delivery_offer_timeout();
 
-        let handle = ctx.run_later(timer_duration, move |actor, _ctx| {
+        let handle = ctx.run_later(timer_duration, move |actor, ctx| {
             actor.logger.warn(format!(
                 "Order {} timed out, no delivery accepted.",
                 order_id
             ));
-            _ctx.address().do_send(CancelOrder {
-                order: OrderDTO {
-                    order_id,
-                    client_id: order.client_id.clone(),
-                    dish_name: order.dish_name.clone(),
-                    restaurant_id: order.restaurant_id.clone(),
-                    status: OrderStatus::Cancelled,
-                    delivery_id: None,
-                    client_position: order.client_position,
-                    expected_delivery_time: 0,
-                    time_stamp: std::time::SystemTime::now(),
-                },
-            });
             actor.order_timers.remove(&order_id);
+            actor.close_offers(order_id, ctx);
+            // El OrderService decide si se vuelve a ofrecer o se cancela
+            if let Some(order_service) = &actor.order_service {
+                order_service
+                    .for_order(order_id)
+                    .do_send(DeliveryOffersExpired { order });
+            } else {
+                actor.logger.info("OrderService not initialized yet.");
+            }
         });
 
         self.order_timers.insert(order_id, handle);
     }
 
+    /// Broadcasts the offers of an order if fewer than the allowed orders are being offered,
+    /// or leaves them waiting in the [`OfferScheduler`] otherwise.
+    ///
+    /// ## Arguments
+    /// * `offers` - The order and the deliveries to offer it to.
+    /// * `ctx` - The actor context.
+    fn schedule_offers(&mut self, offers: NearbyDeliveries, ctx: &mut Context<Self>) {
+        let order_id = offers.order.order_id;
+        match self.offer_scheduler.offer(offers) {
+            Some(offers) => self.broadcast_deliveries(offers.order, offers.deliveries, ctx),
+            None => self.logger.info(format!(
+                "Too many orders being offered, order {} waits ({} waiting)",
+                order_id,
+                self.offer_scheduler.queued_count()
+            )),
+        }
+    }
+
+    /// Stops counting the order `order_id` as offered, or takes it out of the queue, and
+    /// broadcasts the offers of the oldest waiting order if there is room for it.
+    ///
+    /// ## Arguments
+    /// * `order_id` - The ID of the order taken, expired or cancelled.
+    /// * `ctx` - The actor context.
+    fn close_offers(&mut self, order_id: u64, ctx: &mut Context<Self>) {
+        if let Some(next) = self.offer_scheduler.close(order_id) {
+            self.logger.info(format!(
+                "Offering waiting order {} to the deliveries",
+                next.order.order_id
+            ));
+            self.broadcast_deliveries(next.order, next.deliveries, ctx);
+        }
+    }
+
     /// Handles the acceptance of an order by a delivery agent, cancelling the assignment timer.
     ///
     /// ## Arguments
@@ -222,16 +661,964 @@ impl Coordinator {
             self.logger
                 .info(format!("Order {} accepted, timer cancelled.", order_id));
         }
+        self.close_offers(order_id, ctx);
+    }
+
+    /// Returns `true` if another coordinator is the current leader.
+    fn is_follower(&self) -> bool {
+        matches!(self.current_coordinator, Some(leader) if leader != self.my_addr)
+    }
+
+    /// Returns `true` if read-only queries can be answered from the local storage: always on
+    /// the leader, and on a follower only if its replica synced within [`REPLICA_MAX_STALENESS`].
+    fn can_serve_reads(&self) -> bool {
+        !self.is_follower()
+            || self
+                .last_replica_sync
+                .is_some_and(|synced| synced.elapsed() <= REPLICA_MAX_STALENESS)
+    }
+
+    /// Returns who `message`, received over the user connection `remote_addr`, is charged to
+    /// by the rate limiter, or `None` if it is not rate limited.
+    ///
+    /// Only user requests are limited. They are charged to the user registered on the
+    /// connection or, while there is none, to the connection, whatever user ID the request
+    /// carries. Requests forwarded by a follower do not arrive over a user connection, so
+    /// they are not charged twice.
+    fn rate_limit_key(
+        &self,
+        remote_addr: SocketAddr,
+        message: &NetworkMessage,
+    ) -> Option<RateLimitKey> {
+        if !matches!(
+            message,
+            NetworkMessage::WhoIsLeader(_)
+                | NetworkMessage::RegisterUser(_)
+                | NetworkMessage::RequestThisOrder(_)
+                | NetworkMessage::RequestNearbyRestaurants(_)
+                | NetworkMessage::RequestOrderStatus(_)
+                | NetworkMessage::CancelMyOrder(_)
+                | NetworkMessage::GetMyEarnings(_)
+        ) {
+            return None;
+        }
+        Some(match self.user_addresses.get_by_key(&remote_addr) {
+            Some(user_id) => RateLimitKey::User(user_id.clone()),
+            None => RateLimitKey::Peer(remote_addr),
+        })
+    }
+
+    /// Charges a request received over the connection `addr` to `key` and tells whether it
+    /// can be handled.
+    ///
+    /// A throttled sender is asked to retry later, and an abusive one is disconnected.
+    fn admit(&mut self, key: RateLimitKey, addr: SocketAddr, ctx: &mut Context<Self>) -> bool {
+        let decision = self.rate_limiter.check(key.clone(), Instant::now());
+        match decision {
+            RateDecision::Allowed => return true,
+            RateDecision::Throttled => {
+                self.logger.warn(format!(
+                    "Rate limit exceeded by {:?}, dropping request",
+                    key
+                ));
+                if let Some(sender) = self
+                    .communicators
+                    .get(&addr)
+                    .and_then(|communicator| communicator.sender.as_ref())
+                {
+                    sender.do_send(NetworkMessage::RetryLater(RetryLater {
+                        origin_addr: self.my_addr,
+                        retry_after_millis: self.rate_limiter.retry_after(&key).as_millis() as u64,
+                    }));
+                }
+            }
+            RateDecision::Abusive => {
+                self.logger
+                    .warn(format!("{:?} keeps flooding, disconnecting it", key));
+                self.rate_limiter.forget(&key);
+                if let Some(communicator) = self.communicators.get_mut(&addr) {
+                    communicator.shutdown();
+                }
+                self.handle(
+                    NetworkMessage::ConnectionClosed(ConnectionClosed { remote_addr: addr }),
+                    ctx,
+                );
+            }
+        }
+        false
+    }
+
+    /// Hands a user message over to the leader through the `CoordinatorManager`.
+    ///
+    /// ## Arguments
+    /// * `message` - The [`NetworkMessage`] to forward.
+    fn forward_to_leader(&self, message: NetworkMessage) {
+        if let Some(coordinator_manager) = &self.coordinator_manager {
+            coordinator_manager.do_send(ForwardToLeader {
+                coordinator_id: self.id.clone(),
+                message: Box::new(message),
+            });
+        } else {
+            self.logger.info("CoordinatorManager not initialized yet.");
+        }
+    }
+
+    /// Registers on a follower a user connected to it, and forwards the registration to the
+    /// leader, which answers through this coordinator.
+    ///
+    /// ## Arguments
+    /// * `msg` - The [`RegisterUser`] sent by the user.
+    fn forward_registration(&mut self, msg: RegisterUser) {
+        let Some(peer_type) = self
+            .communicators
+            .get(&msg.origin_addr)
+            .map(|communicator| communicator.peer_type)
+        else {
+            return;
+        };
+        self.user_addresses
+            .insert(msg.origin_addr, msg.user_id.clone());
+        self.proxied_users.insert(msg.user_id.clone(), peer_type);
+        self.logger.info(format!(
+            "Proxying {} ({:?}) to the leader",
+            msg.user_id, peer_type
+        ));
+        self.forward_to_leader(NetworkMessage::ProxyUser(ProxyUser {
+            coordinator_id: self.id.clone(),
+            user_id: msg.user_id.clone(),
+            peer_type,
+            connected: true,
+        }));
+        self.forward_to_leader(NetworkMessage::RegisterUser(msg));
+    }
+
+    /// Registers a user whose identity was checked, answering with the state it had if it was
+    /// registered before.
+    ///
+    /// ## Arguments
+    /// * `msg_data` - The [`RegisterUser`] sent by the user.
+    /// * `ctx` - The actor context.
+    fn register_user(&mut self, msg_data: RegisterUser, ctx: &mut Context<Self>) {
+        let user_id = msg_data.user_id.clone();
+
+        // El usuario puede estar conectado a un seguidor que reenvió su registro
+        let local_peer_type = self
+            .communicators
+            .get(&msg_data.origin_addr)
+            .map(|communicator| communicator.peer_type);
+        if local_peer_type.is_some() {
+            self.user_routes.remove(&user_id);
+        }
+        let peer_type =
+            local_peer_type.or_else(|| self.user_routes.get(&user_id).map(|route| route.peer_type));
+
+        if let Some(peer_type) = peer_type {
+            match peer_type {
+                PeerType::ClientType => {
+                    let storage = self.storage.clone();
+                    let client_id_clone = user_id.clone();
+                    let logger = self.logger.clone();
+                    ctx.spawn(
+                        async move {
+                            match storage
+                                .as_ref()
+                                .unwrap()
+                                .send(GetClient {
+                                    client_id: client_id_clone.clone(),
+                                })
+                                .await
+                            {
+                                Ok(user_dto_opt) => {
+                                    if let Some(client_dto) = user_dto_opt {
+                                        NetworkMessage::RecoveredInfo(UserDTO::Client(client_dto))
+                                    } else {
+                                        storage.as_ref().unwrap().do_send(AddClient {
+                                            client: ClientDTO {
+                                                client_position: msg_data.position,
+                                                client_id: client_id_clone.clone(),
+                                                client_order: None,
+                                                tenant_id: msg_data.tenant_id.clone(),
+                                                time_stamp: HlcTimestamp::now(),
+                                            },
+                                        });
+                                        NetworkMessage::NoRecoveredInfo
+                                    }
+                                }
+                                Err(e) => {
+                                    logger.error(format!("Error retrieving client info: {}", e));
+                                    storage.as_ref().unwrap().do_send(AddClient {
+                                        client: ClientDTO {
+                                            client_position: msg_data.position,
+                                            client_id: client_id_clone.clone(),
+                                            client_order: None,
+                                            tenant_id: msg_data.tenant_id.clone(),
+                                            time_stamp: HlcTimestamp::now(),
+                                        },
+                                    });
+                                    NetworkMessage::NoRecoveredInfo
+                                }
+                            }
+                        }
+                        .into_actor(self)
+                        .map(move |network_message, actor, ctx| {
+                            actor.send_network_message(user_id.clone(), network_message);
+                            actor.redeliver_dead_letters(&user_id);
+                            actor.replay_order_updates(user_id.clone(), ctx);
+                        }),
+                    );
+                }
+                PeerType::RestaurantType => {
+                    let storage = self.storage.clone();
+                    let restaurant_id_clone = user_id.clone();
+                    let logger = self.logger.clone();
+                    ctx.spawn(
+                        async move {
+                            match storage
+                                .as_ref()
+                                .unwrap()
+                                .send(GetRestaurant {
+                                    restaurant_id: restaurant_id_clone.clone(),
+                                })
+                                .await
+                            {
+                                Ok(user_dto_opt) => {
+                                    if let Some(mut restaurant_dto) = user_dto_opt {
+                                        // Las etiquetas con las que arrancó reemplazan a las guardadas
+                                        if restaurant_dto.tags != msg_data.tags {
+                                            storage.as_ref().unwrap().do_send(SetRestaurantTags {
+                                                restaurant_id: restaurant_id_clone.clone(),
+                                                tags: msg_data.tags.clone(),
+                                                time_stamp: None,
+                                            });
+                                            restaurant_dto.tags = msg_data.tags.clone();
+                                        }
+                                        NetworkMessage::RecoveredInfo(UserDTO::Restaurant(
+                                            restaurant_dto,
+                                        ))
+                                    } else {
+                                        storage.as_ref().unwrap().do_send(AddRestaurant {
+                                            restaurant: RestaurantDTO {
+                                                restaurant_position: msg_data.position,
+                                                restaurant_id: restaurant_id_clone.clone(),
+                                                authorized_orders: HashSet::new(),
+                                                pending_orders: HashSet::new(),
+                                                time_stamp: HlcTimestamp::now(),
+                                                cancellation_fees: Vec::new(),
+                                                closed: false,
+                                                tags: msg_data.tags.clone(),
+                                                tenant_id: msg_data.tenant_id.clone(),
+                                            },
+                                        });
+                                        NetworkMessage::NoRecoveredInfo
+                                    }
+                                }
+                                Err(e) => {
+                                    logger
+                                        .error(format!("Error retrieving restaurant info: {}", e));
+                                    storage.as_ref().unwrap().do_send(AddRestaurant {
+                                        restaurant: RestaurantDTO {
+                                            restaurant_position: msg_data.position,
+                                            restaurant_id: restaurant_id_clone.clone(),
+                                            authorized_orders: HashSet::new(),
+                                            pending_orders: HashSet::new(),
+                                            time_stamp: HlcTimestamp::now(),
+                                            cancellation_fees: Vec::new(),
+                                            closed: false,
+                                            tags: msg_data.tags.clone(),
+                                            tenant_id: msg_data.tenant_id.clone(),
+                                        },
+                                    });
+                                    NetworkMessage::NoRecoveredInfo
+                                }
+                            }
+                        }
+                        .into_actor(self)
+                        .map(move |network_message, actor, ctx| {
+                            actor.send_network_message(user_id.clone(), network_message);
+                            actor.redeliver_dead_letters(&user_id);
+                            actor.replay_order_updates(user_id.clone(), ctx);
+                            // Se le avisa qué delivery ganó los pedidos que quedaron
+                            // sin confirmar en un líder anterior
+                            if actor.current_coordinator == Some(actor.my_addr) {
+                                actor.resume_pending_assignments(Some(user_id), ctx);
+                            }
+                        }),
+                    );
+                }
+                PeerType::DeliveryType => {
+                    let storage = self.storage.clone();
+                    let delivery_id_clone = user_id.clone();
+                    let logger = self.logger.clone();
+                    ctx.spawn(
+                        async move {
+                            match storage
+                                .as_ref()
+                                .unwrap()
+                                .send(GetDelivery {
+                                    delivery_id: delivery_id_clone.clone(),
+                                })
+                                .await
+                            {
+                                Ok(user_dto_opt) => {
+                                    if let Some(delivery_dto) = user_dto_opt {
+                                        let delivery_dto = DeliveryDTO {
+                                            delivery_position: msg_data.position,
+                                            delivery_id: delivery_id_clone.clone(),
+                                            current_client_id: delivery_dto.current_client_id,
+                                            current_order: delivery_dto.current_order,
+                                            status: delivery_dto.status,
+                                            vehicle: msg_data
+                                                .vehicle
+                                                .unwrap_or(delivery_dto.vehicle),
+                                            tenant_id: delivery_dto.tenant_id,
+                                            time_stamp: HlcTimestamp::now(),
+                                        };
+                                        storage.as_ref().unwrap().do_send(AddDelivery {
+                                            delivery: delivery_dto.clone(),
+                                        });
+                                        NetworkMessage::RecoveredInfo(UserDTO::Delivery(
+                                            delivery_dto,
+                                        ))
+                                    } else {
+                                        storage.as_ref().unwrap().do_send(AddDelivery {
+                                            delivery: DeliveryDTO {
+                                                delivery_position: msg_data.position,
+                                                delivery_id: delivery_id_clone.clone(),
+                                                current_client_id: None,
+                                                current_order: None,
+                                                status: DeliveryStatus::Available,
+                                                vehicle: msg_data.vehicle.unwrap_or_default(),
+                                                tenant_id: msg_data.tenant_id.clone(),
+                                                time_stamp: HlcTimestamp::now(),
+                                            },
+                                        });
+                                        NetworkMessage::NoRecoveredInfo
+                                    }
+                                }
+                                Err(e) => {
+                                    logger.error(format!("Error retrieving delivery info: {}", e));
+                                    if let Some(storage_addr) = storage.as_ref() {
+                                        storage_addr.do_send(AddDelivery {
+                                            delivery: DeliveryDTO {
+                                                delivery_position: msg_data.position,
+                                                delivery_id: delivery_id_clone.clone(),
+                                                current_client_id: None,
+                                                current_order: None,
+                                                status: DeliveryStatus::Available,
+                                                vehicle: msg_data.vehicle.unwrap_or_default(),
+                                                tenant_id: msg_data.tenant_id.clone(),
+                                                time_stamp: HlcTimestamp::now(),
+                                            },
+                                        });
+                                    }
+
+                                    NetworkMessage::NoRecoveredInfo
+                                }
+                            }
+                        }
+                        .into_actor(self)
+                        .map(move |network_message, actor, ctx| {
+                            actor.send_network_message(user_id.clone(), network_message);
+                            actor.redeliver_dead_letters(&user_id);
+                            actor.replay_order_updates(user_id.clone(), ctx);
+                        }),
+                    );
+                }
+                _ => {
+                    self.logger.info(format!(
+                        "Received RegisterUser from non-client type: {:?}",
+                        peer_type
+                    ));
+                }
+            }
+
+            // Actualiza el user_id asociado a la dirección de origen
+            if local_peer_type.is_some() {
+                self.user_addresses
+                    .insert(msg_data.origin_addr, msg_data.user_id.clone());
+                self.logger.info(format!(
+                    "User address mapping actualizado: {} -> {}",
+                    msg_data.origin_addr, msg_data.user_id
+                ));
+            }
+        } else {
+            self.logger.info(format!(
+                "Communicator not found for {}",
+                msg_data.origin_addr
+            ));
+        }
+    }
+
+    /// Rejects the registration of a user with an ID that was claimed with another secret.
+    ///
+    /// ## Arguments
+    /// * `msg` - The [`RegisterUser`] sent by the user.
+    fn reject_identity(&mut self, msg: RegisterUser) {
+        self.logger.warn(format!(
+            "Rejecting the registration of {} from {}: the ID belongs to another user",
+            msg.user_id, msg.origin_addr
+        ));
+        let rejection = NetworkMessage::IdentityRejected(IdentityRejected {
+            user_id: msg.user_id.clone(),
+        });
+        match self
+            .communicators
+            .get(&msg.origin_addr)
+            .and_then(|communicator| communicator.sender.as_ref())
+        {
+            Some(sender) => sender.do_send(rejection),
+            // Llegó a través de un seguidor, que se lo reenvía
+            None => self.send_network_message(msg.user_id, rejection),
+        }
+    }
+
+    /// Tells a new leader about every user connected to this follower, including the ones
+    /// that registered while this coordinator was the leader.
+    fn announce_proxied_users(&mut self) {
+        for addr in self.user_addresses.keys() {
+            let user = self.user_addresses.get_by_key(addr).cloned();
+            let peer_type = self
+                .communicators
+                .get(addr)
+                .map(|communicator| communicator.peer_type);
+            if let (Some(user), Some(peer_type)) = (user, peer_type)
+                && matches!(
+                    peer_type,
+                    PeerType::ClientType | PeerType::RestaurantType | PeerType::DeliveryType
+                )
+            {
+                self.proxied_users.insert(user, peer_type);
+            }
+        }
+        for (user_id, peer_type) in &self.proxied_users {
+            self.forward_to_leader(NetworkMessage::ProxyUser(ProxyUser {
+                coordinator_id: self.id.clone(),
+                user_id: user_id.clone(),
+                peer_type: *peer_type,
+                connected: true,
+            }));
+        }
+    }
+
+    /// Tells a user which coordinator it belongs to according to [`Coordinator::user_shards`],
+    /// among the coordinators this one can reach.
+    ///
+    /// ## Arguments
+    /// * `msg` - The [`WhoIsLeader`] sent by the user.
+    /// * `ctx` - The actor context.
+    fn send_user_home(&mut self, msg: WhoIsLeader, ctx: &mut Context<Self>) {
+        let Some(coordinator_manager) = self.coordinator_manager.clone() else {
+            self.logger.info("CoordinatorManager not initialized yet.");
+            return;
+        };
+        coordinator_manager
+            .send(GetRingState)
+            .into_actor(self)
+            .map(move |res, actor, _ctx| {
+                let connected: HashSet<String> = res
+                    .map(|ring| {
+                        ring.coordinators
+                            .into_iter()
+                            .filter(|coordinator| coordinator.connected)
+                            .map(|coordinator| coordinator.id)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                // Si no se sabe a quién le corresponde, se queda en este coordinador
+                let home = actor
+                    .user_shards
+                    .as_ref()
+                    .and_then(|shards| {
+                        shards.home(&msg.user_id, |id| id == actor.id || connected.contains(id))
+                    })
+                    .and_then(|id| actor.ring_nodes.get(id))
+                    .copied()
+                    .unwrap_or(actor.my_addr);
+                actor.logger.info(format!(
+                    "{} belongs to the coordinator at {}",
+                    msg.user_id, home
+                ));
+                if let Some(sender) = actor
+                    .communicators
+                    .get(&msg.origin_addr)
+                    .and_then(|communicator| communicator.sender.as_ref())
+                {
+                    sender.do_send(NetworkMessage::LeaderIs(LeaderIs { coord_addr: home }));
+                } else {
+                    actor
+                        .logger
+                        .info(format!("No sender found for {}", msg.origin_addr));
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Acts on a user leaving on a clean shutdown, without waiting for the reaper: a delivery
+    /// is marked offline and its orders are reassigned, a restaurant is closed, and the order
+    /// of a client is cancelled if the kitchen did not start it yet. An order already started
+    /// is kept until the client comes back.
+    ///
+    /// ## Arguments
+    /// * `msg` - The [`DeregisterUser`] sent by the user.
+    /// * `ctx` - The actor context.
+    fn deregister_user(&mut self, msg: DeregisterUser, ctx: &mut Context<Self>) {
+        let Some(storage) = self.storage.clone() else {
+            self.logger.info("Storage not initialized yet.");
+            return;
+        };
+        self.logger
+            .info(format!("{} is leaving the system", msg.user_id));
+        storage.do_send(SetUserOffline {
+            user_id: msg.user_id.clone(),
+            time_stamp: None,
+        });
+        let user_id = msg.user_id;
+        async move {
+            let deliveries = storage
+                .send(GetDeliveryOrders {
+                    delivery_id: user_id.clone(),
+                })
+                .await
+                .unwrap_or_default();
+            let client_order = fetch_client_order(storage, user_id.clone()).await;
+            (user_id, deliveries, client_order)
+        }
+        .into_actor(self)
+        .map(|(user_id, deliveries, client_order), act, ctx| {
+            for order in deliveries {
+                ctx.address().do_send(ReassignOrder {
+                    order_id: order.order_id,
+                    delivery_id: user_id.clone(),
+                });
+            }
+            let Some(order) = client_order else {
+                return;
+            };
+            // Sólo se cancela lo que se puede devolver entero; lo demás espera al cliente
+            if !order.status.is_cancellable() || order.status.cancellation_fee_percent() > 0 {
+                act.logger.info(format!(
+                    "Keeping order {} of {} while it is {:?}",
+                    order.order_id, user_id, order.status
+                ));
+                return;
+            }
+            if let Some(order_service) = &act.order_service {
+                order_service
+                    .for_order(order.order_id)
+                    .do_send(CancelMyOrder {
+                        client_id: user_id,
+                        order_id: order.order_id,
+                        waive_fee: false,
+                    });
+            } else {
+                act.logger.info("OrderService not initialized yet.");
+            }
+        })
+        .spawn(ctx);
+    }
+
+    /// Records on the leader that a user connected to, or disconnected from, a follower.
+    ///
+    /// ## Arguments
+    /// * `msg` - The [`ProxyUser`] sent by the follower.
+    fn handle_proxy_user(&mut self, msg: ProxyUser) {
+        if msg.connected {
+            self.logger.info(format!(
+                "{} is connected through {}",
+                msg.user_id, msg.coordinator_id
+            ));
+            self.user_routes.insert(
+                msg.user_id.clone(),
+                UserRoute {
+                    coordinator_id: msg.coordinator_id,
+                    peer_type: msg.peer_type,
+                },
+            );
+            self.redeliver_dead_letters(&msg.user_id);
+            return;
+        }
+        // Si el usuario ya se conectó por otro lado, no se lo da por perdido
+        if self
+            .user_routes
+            .get(&msg.user_id)
+            .is_none_or(|route| route.coordinator_id != msg.coordinator_id)
+        {
+            return;
+        }
+        self.user_routes.remove(&msg.user_id);
+        self.logger.info(format!(
+            "{} disconnected from {}",
+            msg.user_id, msg.coordinator_id
+        ));
+        if let Some(reaper) = &self.reaper {
+            reaper.do_send(ReapUser {
+                user_id: msg.user_id,
+            });
+        } else {
+            self.logger
+                .error("Reaper not initialized, cannot reap user.");
+        }
+    }
+
+    /// Answers a [`RequestOrderStatus`] from the local storage, with the last state of the
+    /// order if it finished recently, or with an [`OrderNotFound`] if the client has no such
+    /// order.
+    ///
+    /// ## Arguments
+    /// * `msg` - The query.
+    /// * `ctx` - The actor context.
+    fn handle_order_status_request(&mut self, msg: RequestOrderStatus, ctx: &mut Context<Self>) {
+        let Some(storage) = self.storage.clone() else {
+            self.logger.info("Storage not initialized yet.");
+            return;
+        };
+        storage
+            .send(GetLastKnownOrder {
+                order_id: msg.order_id,
+            })
+            .into_actor(self)
+            .map(move |res, actor, _ctx| match res {
+                Ok(Some(order)) if order.client_id == msg.client_id => {
+                    actor.send_network_message(
+                        msg.client_id.clone(),
+                        NetworkMessage::NotifyOrderUpdated(NotifyOrderUpdated {
+                            peer_id: msg.client_id,
+                            order,
+                            update_id: 0,
+                            request_id: 0,
+                            decline: None,
+                        }),
+                    );
+                }
+                Ok(_) => {
+                    actor.logger.warn(format!(
+                        "Order {} not found for client {}",
+                        msg.order_id, msg.client_id
+                    ));
+                    actor.send_network_message(
+                        msg.client_id.clone(),
+                        NetworkMessage::OrderNotFound(OrderNotFound {
+                            client_id: msg.client_id,
+                            order_id: msg.order_id,
+                        }),
+                    );
+                }
+                Err(e) => {
+                    actor
+                        .logger
+                        .error(format!("Error retrieving order {}: {}", msg.order_id, e));
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Answers a [`GetMyEarnings`] from the local storage.
+    ///
+    /// ## Arguments
+    /// * `msg` - The query.
+    /// * `ctx` - The actor context.
+    fn handle_earnings_request(&mut self, msg: GetMyEarnings, ctx: &mut Context<Self>) {
+        let Some(storage) = self.storage.clone() else {
+            self.logger.info("Storage not initialized yet.");
+            return;
+        };
+        storage
+            .send(GetDeliveryEarnings {
+                delivery_id: msg.delivery_id.clone(),
+            })
+            .into_actor(self)
+            .map(move |res, actor, _ctx| match res {
+                Ok(report) => {
+                    actor.send_network_message(
+                        msg.delivery_id,
+                        NetworkMessage::MyEarnings(MyEarnings { report }),
+                    );
+                }
+                Err(e) => {
+                    actor.logger.error(format!(
+                        "Error retrieving the earnings of {}: {}",
+                        msg.delivery_id, e
+                    ));
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Answers a [`RequestElectionHistory`] from the local storage.
+    ///
+    /// ## Arguments
+    /// * `msg` - The query.
+    /// * `ctx` - The actor context.
+    fn handle_election_history_request(
+        &mut self,
+        msg: RequestElectionHistory,
+        ctx: &mut Context<Self>,
+    ) {
+        let Some(storage) = self.storage.clone() else {
+            self.logger.info("Storage not initialized yet.");
+            return;
+        };
+        storage
+            .send(GetElectionHistory)
+            .into_actor(self)
+            .map(move |res, actor, _ctx| match res {
+                Ok(elections) => {
+                    if let Some(sender) = actor
+                        .communicators
+                        .get(&msg.origin_addr)
+                        .and_then(|communicator| communicator.sender.as_ref())
+                    {
+                        sender.do_send(NetworkMessage::ElectionHistory(ElectionHistory {
+                            coordinator_id: actor.id.clone(),
+                            epoch: actor.epoch,
+                            elections,
+                        }));
+                    }
+                }
+                Err(e) => {
+                    actor
+                        .logger
+                        .error(format!("Error retrieving the election history: {}", e));
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Answers a [`VerifyStorage`] with the references between the entries of the local
+    /// storage that do not hold.
+    ///
+    /// ## Arguments
+    /// * `msg` - The query.
+    /// * `ctx` - The actor context.
+    fn handle_verify_storage_request(&mut self, msg: VerifyStorage, ctx: &mut Context<Self>) {
+        let Some(storage) = self.storage.clone() else {
+            self.logger.info("Storage not initialized yet.");
+            return;
+        };
+        storage
+            .send(CheckIntegrity)
+            .into_actor(self)
+            .map(move |res, actor, _ctx| match res {
+                Ok(report) => {
+                    if let Some(sender) = actor
+                        .communicators
+                        .get(&msg.origin_addr)
+                        .and_then(|communicator| communicator.sender.as_ref())
+                    {
+                        sender.do_send(NetworkMessage::StorageReport(report));
+                    }
+                }
+                Err(e) => {
+                    actor
+                        .logger
+                        .error(format!("Error verifying the storage: {}", e));
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Drains this coordinator before a planned restart, answering a [`DrainCoordinator`].
+    ///
+    /// Users that ask for the leader are sent to another coordinator from now on. The leader
+    /// hands the leadership off first; then every connected user is told where the leader is
+    /// and the coordinator stops after [`DRAIN_GRACE_PERIOD`]. If no coordinator can take
+    /// over, the drain is cancelled.
+    ///
+    /// ## Arguments
+    /// * `msg` - The request.
+    /// * `ctx` - The actor context.
+    fn handle_drain_request(&mut self, msg: DrainCoordinator, ctx: &mut Context<Self>) {
+        if self.draining {
+            self.logger.warn("Drain already in progress");
+            return;
+        }
+        let Some(coordinator_manager) = self.coordinator_manager.clone() else {
+            self.logger.info("CoordinatorManager not initialized yet.");
+            return;
+        };
+        self.logger
+            .warn(format!("Draining requested by {}", msg.origin_addr));
+        self.draining = true;
+        coordinator_manager
+            .send(HandOffLeadership { successor: None })
+            .into_actor(self)
+            .map(move |res, actor, ctx| {
+                let leader = res.ok().flatten();
+                if let Some(sender) = actor
+                    .communicators
+                    .get(&msg.origin_addr)
+                    .and_then(|communicator| communicator.sender.as_ref())
+                {
+                    sender.do_send(NetworkMessage::CoordinatorDrained(CoordinatorDrained {
+                        coordinator_id: actor.id.clone(),
+                        leader,
+                    }));
+                }
+                match leader {
+                    Some(leader) => actor.finish_drain(leader, ctx),
+                    None => {
+                        actor
+                            .logger
+                            .error("No coordinator can take over, cancelling the drain");
+                        actor.draining = false;
+                    }
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Asks the leader to hand the leadership off to the coordinator in the request, answering
+    /// a [`TransferLeadership`] of the admin, or of a follower that forwarded it.
+    ///
+    /// ## Arguments
+    /// * `msg` - The request.
+    /// * `ctx` - The actor context.
+    fn handle_transfer_request(&mut self, msg: TransferLeadership, ctx: &mut Context<Self>) {
+        let Some(coordinator_manager) = self.coordinator_manager.clone() else {
+            self.logger.info("CoordinatorManager not initialized yet.");
+            return;
+        };
+        self.logger.warn(format!(
+            "Transfer of the leadership to {} requested by {}",
+            msg.target, msg.origin_addr
+        ));
+        coordinator_manager
+            .send(HandOffLeadership {
+                successor: Some(msg.target.clone()),
+            })
+            .into_actor(self)
+            .map(move |res, actor, _ctx| {
+                let leader = res.ok().flatten();
+                if leader.is_none() {
+                    actor.logger.error(format!(
+                        "Could not transfer the leadership to {}",
+                        msg.target
+                    ));
+                }
+                // Si lo reenvió un seguidor, no hay a quién responderle
+                if let Some(sender) = actor
+                    .communicators
+                    .get(&msg.origin_addr)
+                    .and_then(|communicator| communicator.sender.as_ref())
+                {
+                    sender.do_send(NetworkMessage::LeadershipTransferred(
+                        LeadershipTransferred {
+                            target: msg.target,
+                            leader,
+                        },
+                    ));
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Sends every connected user to the leader at `leader` and stops the coordinator after
+    /// [`DRAIN_GRACE_PERIOD`].
+    fn finish_drain(&mut self, leader: SocketAddr, ctx: &mut Context<Self>) {
+        self.logger.warn(format!(
+            "Sending {} users to the leader at {}",
+            self.communicators.len(),
+            leader
+        ));
+        for (addr, communicator) in &self.communicators {
+            // Los demás coordinadores se enteran del líder por el CoordinatorManager
+            if self
+                .user_addresses
+                .get_by_key(addr)
+                .is_some_and(|user_id| user_id.starts_with("server_"))
+            {
+                continue;
+            }
+            if let Some(sender) = &communicator.sender {
+                sender.do_send(NetworkMessage::LeaderIs(LeaderIs { coord_addr: leader }));
+            }
+        }
+        ctx.run_later(DRAIN_GRACE_PERIOD, |actor, ctx| {
+            actor.logger.warn("Coordinator drained, shutting down");
+            ctx.stop();
+        });
+    }
+
+    /// Returns the coordinator the users of a draining coordinator are sent to: the leader,
+    /// or the first other coordinator of the ring while this one still leads.
+    fn drain_target(&self) -> Option<SocketAddr> {
+        if let Some(leader) = self
+            .current_coordinator
+            .filter(|leader| *leader != self.my_addr)
+        {
+            return Some(leader);
+        }
+        let mut ids: Vec<&String> = self
+            .ring_nodes
+            .keys()
+            .filter(|id| **id != self.id)
+            .collect();
+        ids.sort();
+        ids.first().and_then(|id| self.ring_nodes.get(*id)).copied()
     }
 }
 
+/// Returns `true` for the messages that users send to change the state of the system.
+///
+/// On a follower these are forwarded to the leader instead of being handled locally.
+fn is_user_mutation(message: &NetworkMessage) -> bool {
+    matches!(
+        message,
+        NetworkMessage::RequestThisOrder(_)
+            | NetworkMessage::CancelMyOrder(_)
+            | NetworkMessage::AckOrderUpdate(_)
+            | NetworkMessage::IAmAvailable(_)
+            | NetworkMessage::AcceptedOrder(_)
+            | NetworkMessage::OrderDelivered(_)
+            | NetworkMessage::IAmDelivering(_)
+            | NetworkMessage::UpdateOrderStatus(_)
+            | NetworkMessage::CancelOrder(_)
+            | NetworkMessage::RequestNearbyDelivery(_)
+            | NetworkMessage::DeliverThisOrder(_)
+            | NetworkMessage::DeliveryAccepted(_)
+            | NetworkMessage::RestaurantAvailability(_)
+            | NetworkMessage::DeregisterUser(_)
+    )
+}
+
 impl Actor for Coordinator {
     type Context = Context<Self>;
 
     /// Initializes storage, services, and coordinator manager when the actor starts.
     fn started(&mut self, ctx: &mut Self::Context) {
         // Inicializar el servicio de almacenamiento
-        let storage = Storage::new(ctx.address());
+        let log_file = self.storage_log_dir.as_deref().and_then(|dir| {
+            StorageLogFile::open(dir, &self.id)
+                .inspect_err(|e| {
+                    self.logger
+                        .error(format!("Error opening the storage log in {:?}: {}", dir, e))
+                })
+                .ok()
+        });
+        let tables = EntityTables::open(
+            self.storage_backend,
+            self.storage_log_dir.as_deref(),
+            &self.id,
+        )
+        .unwrap_or_else(|e| {
+            self.logger.error(format!(
+                "Error opening the {:?} storage backend, keeping the storage in memory: {}",
+                self.storage_backend, e
+            ));
+            EntityTables::default()
+        });
+        // Los servicios de búsqueda leen la vista sin pasar por el Storage
+        let storage_view = StorageReadView::default();
+        let mut storage = Storage::new(
+            ctx.address(),
+            self.id.clone(),
+            log_file,
+            storage_view.clone(),
+        )
+        .with_tables(tables);
+        if let Some(event_publisher) = self.start_event_publisher() {
+            storage = storage.with_event_publisher(event_publisher);
+        }
         let storage_address = storage.start();
         self.storage = Some(storage_address.clone());
 
@@ -241,18 +1628,70 @@ impl Actor for Coordinator {
             self.ring_nodes.clone(),
             ctx.address(),
             storage_address.clone(),
-        );
-        // self.order_service.start();
-
+            self.transport,
+            self.write_ack,
+        )
+        .with_election(self.election)
+        .with_bind_addr(self.bind_addr);
         self.coordinator_manager = Some(coordinator_manager.start());
         self.logger.info("Coordinator started.");
 
-        let reaper = reaper::Reaper::new(storage_address.clone());
+        let reaper = reaper::Reaper::new(
+            storage_address.clone(),
+            ctx.address(),
+            reaper::grace_period_from_env(),
+        );
         self.reaper = Some(reaper.start());
         self.logger.info("Reaper started.");
 
+        if let Some(order_service) = &self.order_service {
+            let order_reaper = OrderReaper::new(
+                self.my_addr,
+                storage_address.clone(),
+                ctx.address(),
+                order_service.clone(),
+            );
+            self.order_reaper = Some(order_reaper.start());
+            self.logger.info("Order Reaper started.");
+        }
+
+        let user_collector = UserCollector::new(
+            self.my_addr,
+            storage_address.clone(),
+            ctx.address(),
+            user_collector::user_ttl_from_env(),
+        );
+        self.user_collector = Some(user_collector.start());
+        self.logger.info("User Collector started.");
+
+        let mut transports: Vec<(SocketAddr, Box<dyn PeerTransport>)> = Vec::new();
         for (addr, stream) in self.pending_streams.drain() {
             let communicator = Communicator::new(stream, ctx.address(), PeerType::CoordinatorType);
+            transports.push((addr, Box::new(communicator)));
+        }
+        if self.transport == TransportKind::Grpc {
+            for addr in self.ring_nodes.values() {
+                if *addr != self.my_addr
+                    && let Some(transport) = open_transport(self.transport, self.my_addr, *addr)
+                {
+                    transports.push((*addr, transport));
+                }
+            }
+            let bind_addr = self.bind_addr;
+            let recipient = ctx.address().recipient();
+            let logger = self.logger.clone();
+            ctx.spawn(
+                async move {
+                    if let Err(e) = serve_transport(TransportKind::Grpc, bind_addr, recipient).await
+                    {
+                        logger.error(format!("gRPC transport stopped: {}", e));
+                    }
+                }
+                .into_actor(self),
+            );
+        }
+
+        for (addr, communicator) in transports {
             // le paso los coordinadores que hay al CoordinatorManager
             if let Some(coordinator_manager) = &self.coordinator_manager {
                 coordinator_manager.do_send(RegisterConnectionWithCoordinator {
@@ -275,20 +1714,90 @@ impl Actor for Coordinator {
 
         // Inicializar el servicio de restaurantes cercanos
         let nearby_restaurant_service =
-            NearbyRestaurantsService::new(storage_address.clone(), ctx.address());
+            NearbyRestaurantsService::new(storage_view.clone(), ctx.address());
         self.nearby_restaurant_service = Some(nearby_restaurant_service.start());
         // Inicializar el servicio de delivery cercanos
         let nearby_delivery_service =
-            NearbyDeliveryService::new(storage_address.clone(), ctx.address());
+            NearbyDeliveryService::new(storage_address.clone(), storage_view, ctx.address());
         self.nearby_delivery_service = Some(nearby_delivery_service.start());
+        // Inicializar el servicio de notificaciones por WebSocket
+        let notifications_addr = self.listen_addr(WS_NOTIFICATIONS_PORT_OFFSET);
+        self.notification_service = Some(NotificationService::new(notifications_addr).start());
+        // Inicializar el endpoint de salud, para saber cuándo el nodo está listo
+        if let Some(health_addr) = health::addr_from_env(self.bind_addr) {
+            let coordinator = ctx.address();
+            let logger = self.logger.clone();
+            actix::spawn(async move {
+                let report = move || {
+                    let coordinator = coordinator.clone();
+                    async move { coordinator.send(GetHealth).await.unwrap_or_default() }
+                };
+                match health::serve(health_addr, report).await {
+                    Ok(()) => {
+                        logger.info(format!("Health endpoint at http://{}/health", health_addr))
+                    }
+                    Err(e) => logger.warn(format!(
+                        "Cannot serve the health endpoint at {}: {}",
+                        health_addr, e
+                    )),
+                }
+            });
+        }
+        // Inicializar el dashboard, que sólo se sirve mientras este nodo sea el líder
+        if dashboard::is_enabled()
+            && let Some(coordinator_manager) = &self.coordinator_manager
+        {
+            let service = ClusterSnapshotService::new(
+                self.id.clone(),
+                self.my_addr,
+                storage_address.clone(),
+                ctx.address(),
+                coordinator_manager.clone(),
+                self.order_reaper.clone(),
+            )
+            .with_bind_addr(self.bind_addr);
+            self.cluster_snapshot_service = Some(service.start());
+        }
+        // Inicializar el TUI, que reemplaza los logs en la terminal
+        #[cfg(feature = "tui")]
+        if crate::tui::is_enabled()
+            && let Some(coordinator_manager) = &self.coordinator_manager
+        {
+            crate::tui::ServerTui::new(
+                self.id.clone(),
+                self.my_addr,
+                storage_address.clone(),
+                ctx.address(),
+                coordinator_manager.clone(),
+            )
+            .start();
+        }
 
         if let Some(order_service) = &self.order_service {
-            order_service.do_send(SetActorsAddresses {
+            // Inicializar el servicio de códigos promocionales, que usan todos los shards
+            let promotion_service = PromotionService::new(storage_address.clone()).start();
+            order_service.broadcast(SetActorsAddresses {
                 coordinator_addr: ctx.address(),
                 storage_addr: storage_address,
+                promotion_addr: promotion_service,
             });
+            self.logger.info(format!(
+                "Order Service started with {} shards.",
+                order_service.len()
+            ));
         }
 
+        // Descartar periódicamente los mensajes de usuarios que no se reconectaron
+        ctx.run_interval(DEAD_LETTER_PURGE_INTERVAL, |act, _ctx| {
+            let expired = act.dead_letters.purge_expired(Instant::now());
+            if expired > 0 {
+                act.logger.warn(format!(
+                    "Discarded {} messages of users that did not reconnect",
+                    expired
+                ));
+            }
+        });
+
         self.logger.info("Services initialized.");
     }
 }
@@ -317,9 +1826,11 @@ impl Handler<RegisterConnection> for Coordinator {
         self.communicators.insert(msg.client_addr, msg.communicator);
 
         self.user_addresses
-            .insert(msg.client_addr, "UNKNOWN_USER".to_string());
-        self.logger
-            .info(format!("Registered connection from {} ", msg.client_addr));
+            .insert(msg.client_addr, msg.user_id.clone());
+        self.logger.info(format!(
+            "Registered connection from {} as {}",
+            msg.client_addr, msg.user_id
+        ));
     }
 }
 
@@ -334,23 +1845,186 @@ impl Handler<NearbyRestaurants> for Coordinator {
     }
 }
 
-/// Handles broadcasting available deliveries to delivery agents.
+/// Handles broadcasting available deliveries to delivery agents, once the [`OfferScheduler`]
+/// has room for the order.
 impl Handler<NearbyDeliveries> for Coordinator {
     type Result = ();
 
     fn handle(&mut self, msg: NearbyDeliveries, ctx: &mut Self::Context) -> Self::Result {
-        // Buscar el comunicador del cliente
-        self.broadcast_deliveries(msg.order, msg.deliveries, ctx);
+        // Si hay demasiados pedidos ofrecidos, éste espera su turno
+        self.schedule_offers(msg, ctx);
     }
 }
 
 /// Handles notifications of order updates to peers.
+///
+/// The update is kept in the outbox of the peer in the `Storage` before it is sent, so the
+/// peer gets it when it registers again if it does not acknowledge it.
 impl Handler<NotifyOrderUpdated> for Coordinator {
     type Result = ();
 
-    fn handle(&mut self, msg: NotifyOrderUpdated, _ctx: &mut Self::Context) -> Self::Result {
-        let peer_id = msg.peer_id.clone();
-        self.send_network_message(peer_id, NetworkMessage::NotifyOrderUpdated(msg));
+    fn handle(&mut self, msg: NotifyOrderUpdated, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(notification_service) = &self.notification_service {
+            notification_service.do_send(msg.clone());
+        }
+        let Some(storage) = self.storage.clone() else {
+            let peer_id = msg.peer_id.clone();
+            self.send_network_message(peer_id, NetworkMessage::NotifyOrderUpdated(msg));
+            return;
+        };
+        storage
+            .send(QueueOrderUpdate {
+                update: msg.clone(),
+            })
+            .into_actor(self)
+            .map(move |res, act, _ctx| match res {
+                Ok(update) => act.send_order_update(update),
+                Err(e) => {
+                    act.logger.error(format!(
+                        "Error keeping the update of order {}: {}",
+                        msg.order.order_id, e
+                    ));
+                    let peer_id = msg.peer_id.clone();
+                    act.send_network_message(peer_id, NetworkMessage::NotifyOrderUpdated(msg));
+                }
+            })
+            .spawn(ctx);
+    }
+}
+
+/// Handles the reassignment of an order whose delivery stopped responding.
+///
+/// If the order is still on its way with that delivery, or waiting for it, it goes back to
+/// `ReadyForDelivery`, the client and the restaurant are notified and the deliveries near the
+/// restaurant are offered the order again.
+impl Handler<ReassignOrder> for Coordinator {
+    type Result = ResponseActFuture<Self, Result<(), CoordinatorError>>;
+
+    fn handle(&mut self, msg: ReassignOrder, _ctx: &mut Self::Context) -> Self::Result {
+        let Some(storage) = self.storage.clone() else {
+            self.logger
+                .error("Storage not initialized, cannot reassign order.");
+            return Box::pin(fut::ready(Err(CoordinatorError::NotInitialized("Storage"))));
+        };
+        let order_id = msg.order_id;
+        Box::pin(
+            fetch_order_and_restaurant(storage, order_id)
+                .into_actor(self)
+                .map(move |found, act, _ctx| {
+                    let (mut order, restaurant) = match found {
+                        Ok(found) => found,
+                        Err(e) => {
+                            act.logger
+                                .warn(format!("Cannot reassign order {}: {}", order_id, e));
+                            return Err(e);
+                        }
+                    };
+                    // Si el delivery llegó a entregarlo o el pedido ya cambió de manos, no hay nada que hacer
+                    let reassignable = match order.status {
+                        OrderStatus::Delivering => {
+                            order.delivery_id.as_deref() == Some(msg.delivery_id.as_str())
+                        }
+                        OrderStatus::ReadyForDelivery => true,
+                        _ => false,
+                    };
+                    if !reassignable {
+                        return Ok(());
+                    }
+                    act.logger.warn(format!(
+                        "Delivery {} stopped responding, reassigning order {}",
+                        msg.delivery_id, order_id
+                    ));
+                    order.status = OrderStatus::ReadyForDelivery;
+                    order.delivery_id = None;
+                    if let Some(storage) = &act.storage {
+                        storage.do_send(RemoveAcceptedDeliveries { order_id });
+                    }
+                    act.send_network_message(
+                        order.restaurant_id.clone(),
+                        NetworkMessage::UpdateOrderStatus(UpdateOrderStatus {
+                            order: order.clone(),
+                            reason: None,
+                            kitchen_load: None,
+                        }),
+                    );
+                    if let Some(order_service) = &act.order_service {
+                        order_service
+                            .for_order(order.order_id)
+                            .do_send(SetOrderStatus {
+                                order: order.clone(),
+                                order_status: OrderStatus::ReadyForDelivery,
+                                // El pedido vuelve a buscar delivery porque el anterior lo abandonó
+                                event: Some(
+                                    OrderEvent::new(OrderEventKind::StatusChanged, &order, &act.id)
+                                        .with_actor("coordinator"),
+                                ),
+                            });
+                    }
+                    act.request_nearby_delivery(order, restaurant, None);
+                    Ok(())
+                }),
+        )
+    }
+}
+
+/// Handles a new delivery search for an order that no delivery took.
+///
+/// The search is skipped if the order is no longer ready for delivery or its offers are still
+/// waiting for an answer or for their turn to be broadcast.
+impl Handler<RetryDeliverySearch> for Coordinator {
+    type Result = ResponseActFuture<Self, Result<(), CoordinatorError>>;
+
+    fn handle(&mut self, msg: RetryDeliverySearch, _ctx: &mut Self::Context) -> Self::Result {
+        let Some(storage) = self.storage.clone() else {
+            self.logger
+                .error("Storage not initialized, cannot retry delivery search.");
+            return Box::pin(fut::ready(Err(CoordinatorError::NotInitialized("Storage"))));
+        };
+        let order_id = msg.order_id;
+        Box::pin(
+            fetch_order_and_restaurant(storage, order_id)
+                .into_actor(self)
+                .map(move |found, act, _ctx| {
+                    let (order, restaurant) = match found {
+                        Ok(found) => found,
+                        Err(e) => {
+                            act.logger.warn(format!(
+                                "Cannot retry delivery search for order {}: {}",
+                                order_id, e
+                            ));
+                            return Err(e);
+                        }
+                    };
+                    if order.status != OrderStatus::ReadyForDelivery
+                        || act.order_timers.contains_key(&order_id)
+                        || act.offer_scheduler.is_queued(order_id)
+                    {
+                        return Ok(());
+                    }
+                    act.request_nearby_delivery(order, restaurant, msg.radius);
+                    Ok(())
+                }),
+        )
+    }
+}
+
+/// Handles telling a client that its order is being offered to the deliveries again.
+impl Handler<StillLookingForDelivery> for Coordinator {
+    type Result = ();
+
+    fn handle(&mut self, msg: StillLookingForDelivery, _ctx: &mut Self::Context) -> Self::Result {
+        let client_id = msg.order.client_id.clone();
+        self.send_network_message(client_id, NetworkMessage::StillLookingForDelivery(msg));
+    }
+}
+
+/// Handles telling a client how much of its cancelled order is refunded.
+impl Handler<CancellationCharged> for Coordinator {
+    type Result = ();
+
+    fn handle(&mut self, msg: CancellationCharged, _ctx: &mut Self::Context) -> Self::Result {
+        let client_id = msg.client_id.clone();
+        self.send_network_message(client_id, NetworkMessage::CancellationCharged(msg));
     }
 }
 
@@ -375,6 +2049,17 @@ impl Handler<DeliveryNoNeeded> for Coordinator {
     }
 }
 
+/// Handles the rejection of a status sent by a restaurant or delivery, telling it how the
+/// order really is.
+impl Handler<StatusRejected> for Coordinator {
+    type Result = ();
+
+    fn handle(&mut self, msg: StatusRejected, _ctx: &mut Self::Context) -> Self::Result {
+        let peer_id = msg.peer_id.clone();
+        self.send_network_message(peer_id, NetworkMessage::StatusRejected(msg));
+    }
+}
+
 /// Handles instructions to deliver a specific order.
 impl Handler<DeliverThisOrder> for Coordinator {
     type Result = ();
@@ -397,7 +2082,7 @@ impl Handler<DeliverThisOrder> for Coordinator {
 /// Handles queries about the current leader in the system.
 impl Handler<WhoIsLeader> for Coordinator {
     type Result = ();
-    fn handle(&mut self, msg: WhoIsLeader, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: WhoIsLeader, ctx: &mut Self::Context) -> Self::Result {
         self.logger
             .info(format!("Received WhoIsLeader from {}", msg.origin_addr));
         self.logger.info(format!(
@@ -406,27 +2091,48 @@ impl Handler<WhoIsLeader> for Coordinator {
         ));
         let user_address = msg.origin_addr;
 
-        // Si el origen está en user_addresses, actualizar el ID del usuario
-        if let Some(_user_id) = self.user_addresses.get_by_key(&user_address) {
-            self.user_addresses
-                .insert(user_address, msg.user_id.clone());
+        // El ID ya llegó al conectarse; si no coincide, nos quedamos con el más reciente
+        if self.user_addresses.get_by_key(&user_address) != Some(&msg.user_id) {
             self.logger.info(format!(
-                "User ID for {} is {}",
-                user_address,
-                msg.user_id.clone()
+                "User ID for {} is now {}",
+                user_address, msg.user_id
             ));
-        } else {
-            self.logger.info(format!(
-                "No user ID found for {}. Adding as UNKNOWN_USER.",
-                user_address
-            ));
-            // Si no está, lo actualizamos o lo agregamos
             self.user_addresses
                 .insert(user_address, msg.user_id.clone());
         }
 
+        // Un coordinador que se drena no toma usuarios nuevos: los manda a otro
+        if self.draining
+            && !msg.user_id.starts_with("server_")
+            && let Some(target) = self.drain_target()
+        {
+            if let Some(sender) = self
+                .communicators
+                .get(&user_address)
+                .and_then(|communicator| communicator.sender.as_ref())
+            {
+                sender.do_send(NetworkMessage::LeaderIs(LeaderIs { coord_addr: target }));
+            }
+            return;
+        }
+
+        // Con los usuarios repartidos, se le indica el coordinador que le corresponde
+        if self.current_coordinator.is_some()
+            && self.user_shards.is_some()
+            && !msg.user_id.starts_with("server_")
+        {
+            self.send_user_home(msg, ctx);
+            return;
+        }
+
         //  Si hay un coordinador actual, se lo notificamos al cliente
-        if let Some(addr) = self.current_coordinator {
+        if let Some(leader) = self.current_coordinator {
+            // Con el proxy activo el usuario se queda conectado a este coordinador
+            let addr = if self.proxy_users && !msg.user_id.starts_with("server_") {
+                self.my_addr
+            } else {
+                leader
+            };
             if let Some(sender) = &self.communicators[&msg.origin_addr].sender {
                 println!("Sending Leader Is to {}", msg.origin_addr);
                 sender.do_send(NetworkMessage::LeaderIs(LeaderIs { coord_addr: (addr) }));
@@ -434,39 +2140,235 @@ impl Handler<WhoIsLeader> for Coordinator {
                 self.logger
                     .info(format!("No sender found for {}", msg.origin_addr));
             }
-        } else {
-            // Si no hay coordinador actual, le preguntamos al CoordinatorManager
-            self.logger
-                .info("No current coordinator available. Check Server Election implementation.");
+        } else {
+            // Si no hay coordinador actual, le preguntamos al CoordinatorManager
+            self.logger
+                .info("No current coordinator available. Check Server Election implementation.");
+
+            // coordintor no tiene lider asociado ->  enviar WhoIsLeader al CoordinatorManager
+            if let Some(coordinator_manager) = &self.coordinator_manager {
+                coordinator_manager.do_send(msg);
+            } else {
+                self.logger
+                    .info("CoordinatorManager not initialized yet, cannot handle WhoIsLeader.");
+            }
+        }
+    }
+}
+
+/// Handles updates about the current leader's ID.
+impl Handler<LeaderIdIs> for Coordinator {
+    type Result = ();
+
+    fn handle(&mut self, msg: LeaderIdIs, ctx: &mut Self::Context) -> Self::Result {
+        self.logger.info(format!(
+            "Received LeaderIdIs with leader ID {} (epoch {})",
+            msg.leader_id, msg.epoch
+        ));
+        if let Some(leader_addr) = self.ring_nodes.get(&msg.leader_id).copied() {
+            let leader_changed = self.current_coordinator != Some(leader_addr);
+            self.current_coordinator = Some(leader_addr);
+            if leader_changed {
+                self.user_routes.clear();
+                if self.is_follower() && self.proxy_users {
+                    self.announce_proxied_users();
+                } else {
+                    self.proxied_users.clear();
+                }
+            }
+            let new_epoch = msg.epoch > self.epoch;
+            self.epoch = self.epoch.max(msg.epoch);
+            if let Some(order_service) = &self.order_service {
+                order_service.broadcast(SetLeaderEpoch {
+                    epoch: self.epoch,
+                    leader: leader_addr == self.my_addr,
+                });
+            }
+            // Un nuevo líder retoma las asignaciones que decidieron los líderes anteriores
+            if new_epoch && leader_addr == self.my_addr {
+                // El líder guarda la elección en el storage, que la replica a los seguidores
+                if let Some(storage) = &self.storage {
+                    storage.do_send(RecordElection {
+                        record: ElectionRecord::now(&msg.leader_id, self.epoch),
+                    });
+                }
+                self.resume_pending_assignments(None, ctx);
+            }
+        } else {
+            self.logger.info(format!(
+                "Leader ID {} not found in ring nodes.",
+                msg.leader_id
+            ));
+        }
+    }
+}
+
+/// Handles notifications that the local storage caught up with the ring.
+impl Handler<ReplicaSynced> for Coordinator {
+    type Result = ();
+
+    fn handle(&mut self, _msg: ReplicaSynced, _ctx: &mut Self::Context) -> Self::Result {
+        self.last_replica_sync = Some(Instant::now());
+    }
+}
+
+/// Handles requests for the address of the current leader.
+impl Handler<GetLeader> for Coordinator {
+    type Result = Option<SocketAddr>;
+
+    fn handle(&mut self, _msg: GetLeader, _ctx: &mut Self::Context) -> Self::Result {
+        self.current_coordinator
+    }
+}
+
+/// Handles requests for the users connected through this coordinator.
+impl Handler<GetConnectedUsers> for Coordinator {
+    type Result = MessageResult<GetConnectedUsers>;
+
+    fn handle(&mut self, _msg: GetConnectedUsers, _ctx: &mut Self::Context) -> Self::Result {
+        let mut users: Vec<String> = self.user_addresses.values().cloned().collect();
+        users.extend(self.user_routes.keys().cloned());
+        MessageResult(users)
+    }
+}
+
+/// Handles requests for the timeline of an order, asking the `Storage` for it.
+impl Handler<GetOrderTimeline> for Coordinator {
+    type Result = ResponseFuture<Vec<OrderEvent>>;
+
+    fn handle(&mut self, msg: GetOrderTimeline, _ctx: &mut Self::Context) -> Self::Result {
+        let storage = self.storage.clone();
+        Box::pin(async move {
+            match storage {
+                Some(storage) => storage.send(msg).await.unwrap_or_default(),
+                None => Vec::new(),
+            }
+        })
+    }
+}
 
-            // coordintor no tiene lider asociado ->  enviar WhoIsLeader al CoordinatorManager
-            if let Some(coordinator_manager) = &self.coordinator_manager {
-                coordinator_manager.do_send(msg);
-            } else {
-                self.logger
-                    .info("CoordinatorManager not initialized yet, cannot handle WhoIsLeader.");
+/// Handles requests for the order updates a user did not acknowledge, asking the `Storage`
+/// for them.
+impl Handler<GetPendingOrderUpdates> for Coordinator {
+    type Result = ResponseFuture<Vec<NotifyOrderUpdated>>;
+
+    fn handle(&mut self, msg: GetPendingOrderUpdates, _ctx: &mut Self::Context) -> Self::Result {
+        let storage = self.storage.clone();
+        Box::pin(async move {
+            match storage {
+                Some(storage) => storage.send(msg).await.unwrap_or_default(),
+                None => Vec::new(),
             }
-        }
+        })
     }
 }
 
-/// Handles updates about the current leader's ID.
-impl Handler<LeaderIdIs> for Coordinator {
-    type Result = ();
+/// Handles requests for the users connected to this coordinator, used by the dashboard.
+impl Handler<GetConnectedPeers> for Coordinator {
+    type Result = MessageResult<GetConnectedPeers>;
+
+    fn handle(&mut self, _msg: GetConnectedPeers, _ctx: &mut Self::Context) -> Self::Result {
+        let mut peers: Vec<ConnectedPeer> = self
+            .communicators
+            .iter()
+            .filter(|(_, communicator)| communicator.peer_type != PeerType::CoordinatorType)
+            .map(|(addr, communicator)| ConnectedPeer {
+                user_id: self.user_addresses.get_by_key(addr).cloned(),
+                addr: *addr,
+                peer_type: format!("{:?}", communicator.peer_type),
+                outbound: communicator.outbound_stats(),
+            })
+            .collect();
+        peers.sort_by(|a, b| a.user_id.cmp(&b.user_id));
+        MessageResult(peers)
+    }
+}
 
-    fn handle(&mut self, msg: LeaderIdIs, _ctx: &mut Self::Context) -> Self::Result {
-        self.logger.info(format!(
-            "Received LeaderIdIs with leader ID {}",
-            msg.leader_id
-        ));
-        if let Some(leader_addr) = self.ring_nodes.get(&msg.leader_id) {
-            self.current_coordinator = Some(*leader_addr);
-        } else {
-            self.logger.info(format!(
-                "Leader ID {} not found in ring nodes.",
-                msg.leader_id
-            ));
+/// Handles requests for the number of users connected by type, used by the TUI.
+impl Handler<GetPeerCounts> for Coordinator {
+    type Result = MessageResult<GetPeerCounts>;
+
+    fn handle(&mut self, _msg: GetPeerCounts, _ctx: &mut Self::Context) -> Self::Result {
+        let mut counts = BTreeMap::new();
+        for communicator in self.communicators.values() {
+            *counts
+                .entry(format!("{:?}", communicator.peer_type))
+                .or_insert(0) += 1;
         }
+        MessageResult(counts)
+    }
+}
+
+/// Handles requests for the health of this coordinator, answered by its health endpoint.
+///
+/// A leader is always ready, and a follower only while its replica is fresh enough to serve
+/// reads. A coordinator that knows no leader is not ready.
+impl Handler<GetHealth> for Coordinator {
+    type Result = ResponseActFuture<Self, HealthReport>;
+
+    fn handle(&mut self, _msg: GetHealth, ctx: &mut Self::Context) -> Self::Result {
+        let connections = self.handle(GetPeerCounts, ctx).0;
+        let role = match self.current_coordinator {
+            Some(leader) if leader == self.my_addr => "leader",
+            Some(_) => "follower",
+            None => "electing",
+        };
+        let report = HealthReport {
+            service: "server".to_string(),
+            node_id: self.id.clone(),
+            ready: self.current_coordinator.is_some() && self.can_serve_reads(),
+            role: role.to_string(),
+            replica_age_millis: self
+                .is_follower()
+                .then_some(self.last_replica_sync)
+                .flatten()
+                .map(|synced| synced.elapsed().as_millis() as u64),
+            connections: connections.into_iter().collect(),
+            accepted: self.acceptor_metrics.snapshot(),
+            ..HealthReport::default()
+        };
+        let storage = self.storage.clone();
+        let coordinator_manager = (role == "leader")
+            .then(|| self.coordinator_manager.clone())
+            .flatten();
+        Box::pin(
+            async move {
+                let mut report = report;
+                if let Some(storage) = storage
+                    && let Ok(status) = storage.send(GetCompactionStatus).await
+                {
+                    report.log_index = Some(status.next_log_id.saturating_sub(1));
+                }
+                if let (Some(manager), Some(log_index)) = (coordinator_manager, report.log_index)
+                    && let Ok(followers) = manager.send(GetFollowerProgress).await
+                {
+                    report.log_lag = followers
+                        .iter()
+                        .map(|follower| log_index.saturating_sub(follower.applied_index))
+                        .max();
+                }
+                report
+            }
+            .into_actor(self),
+        )
+    }
+}
+
+/// Handles requests for the counters of the dead-letter queue, used by the dashboard.
+impl Handler<GetDeadLetterStats> for Coordinator {
+    type Result = MessageResult<GetDeadLetterStats>;
+
+    fn handle(&mut self, _msg: GetDeadLetterStats, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.dead_letters.stats())
+    }
+}
+
+/// Handles requests for the last load reported by each kitchen, used by the dashboard.
+impl Handler<GetKitchenLoads> for Coordinator {
+    type Result = MessageResult<GetKitchenLoads>;
+
+    fn handle(&mut self, _msg: GetKitchenLoads, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.kitchen_loads.clone())
     }
 }
 
@@ -478,6 +2380,7 @@ impl Handler<RetryLater> for Coordinator {
         if let Some(sender) = &self.communicators[&msg.origin_addr].sender {
             sender.do_send(NetworkMessage::RetryLater(RetryLater {
                 origin_addr: self.my_addr,
+                retry_after_millis: msg.retry_after_millis,
             }));
         } else {
             self.logger
@@ -511,29 +2414,63 @@ impl Handler<OrderFinalized> for Coordinator {
     }
 }
 
+/// Handles writes that must reach a quorum of coordinators, forwarding them to the
+/// `CoordinatorManager`.
+impl Handler<QuorumWrite> for Coordinator {
+    type Result = ResponseFuture<Result<(), CoordinatorError>>;
+
+    fn handle(&mut self, msg: QuorumWrite, _ctx: &mut Self::Context) -> Self::Result {
+        let coordinator_manager = self.coordinator_manager.clone();
+        Box::pin(async move {
+            match coordinator_manager {
+                Some(coordinator_manager) => coordinator_manager.send(msg).await?,
+                None => Err(CoordinatorError::NotInitialized("CoordinatorManager")),
+            }
+        })
+    }
+}
+
 /// Handles order cancellation requests.
 impl Handler<CancelOrder> for Coordinator {
     type Result = ();
 
-    fn handle(&mut self, msg: CancelOrder, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: CancelOrder, ctx: &mut Self::Context) -> Self::Result {
         self.logger.info(format!(
             "Received cancel order request for order ID: {}, status was: {:?}",
             msg.order.order_id, msg.order.status
         ));
+        // Ya no hace falta esperar a que un delivery acepte el pedido
+        if let Some(handle) = self.order_timers.remove(&msg.order.order_id) {
+            ctx.cancel_future(handle);
+        }
+        self.close_offers(msg.order.order_id, ctx);
         // Si el pedido esta en estado "ReadyForDelivery", le aviso al restaurante que no hay delivery (cancelando el pedido)
         if msg.order.status == OrderStatus::ReadyForDelivery {
             let restaurant_id = msg.order.restaurant_id.clone();
             self.send_network_message(restaurant_id, NetworkMessage::CancelOrder(msg.clone()));
         }
         // En cualquier otro estado, tambien le aviso al cliente
-        if msg.order.status == OrderStatus::Cancelled {
+        if matches!(
+            msg.order.status,
+            OrderStatus::Cancelled | OrderStatus::OutOfStock
+        ) {
             self.logger.info(format!(
                 "Cancelling order {} for client {}",
                 msg.order.order_id, msg.order.client_id
             ));
-            self.storage.as_ref().unwrap().do_send(RemoveOrder {
-                order: msg.order.clone(),
+            // Si lo rechazó el restaurante, la línea de tiempo registra el motivo
+            let event = msg.reason.map(|reason| {
+                OrderEvent::new(OrderEventKind::Removed, &msg.order, &self.id).with_reason(reason)
             });
+            let remove = RemoveOrder {
+                order: msg.order.clone(),
+                event,
+            };
+            // El OrderService olvida el pedido antes de pasarle la baja al Storage
+            match &self.order_service {
+                Some(order_service) => order_service.for_order(msg.order.order_id).do_send(remove),
+                None => self.storage.as_ref().unwrap().do_send(remove),
+            }
             self.send_network_message(
                 msg.order.restaurant_id.clone(),
                 NetworkMessage::CancelOrder(msg.clone()),
@@ -544,17 +2481,40 @@ impl Handler<CancelOrder> for Coordinator {
     }
 }
 
+/// Handles the messages received over the connections of users, charging the requests to the
+/// rate limiter before handling them as any other [`NetworkMessage`].
+impl Handler<ReceivedFrom> for Coordinator {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReceivedFrom, ctx: &mut Self::Context) -> Self::Result {
+        if let Some(key) = self.rate_limit_key(msg.remote_addr, &msg.message)
+            && !self.admit(key, msg.remote_addr, ctx)
+        {
+            return;
+        }
+        self.handle(msg.message, ctx);
+    }
+}
+
 /// Handles all incoming [`NetworkMessage`]s, dispatching them to the appropriate service or handler.
 impl Handler<NetworkMessage> for Coordinator {
     type Result = ();
     fn handle(&mut self, msg: NetworkMessage, ctx: &mut Self::Context) -> Self::Result {
-        //println!("Received NetworkMessage: {:?}", msg);
+        let _span =
+            info_span!("coordinator", msg = msg.kind(), order_id = msg.order_id()).entered();
+        tracing::trace!(?msg, "received");
+        // Los seguidores sólo responden consultas: las mutaciones las resuelve el líder
+        if self.is_follower() && is_user_mutation(&msg) {
+            self.forward_to_leader(msg);
+            return;
+        }
         match msg {
             // All Users messages
             NetworkMessage::WhoIsLeader(msg_data) => {
                 if self.current_coordinator.is_none() {
                     ctx.address().do_send(RetryLater {
                         origin_addr: msg_data.origin_addr,
+                        retry_after_millis: RETRY_LATER_WHILE_ELECTING.as_millis() as u64,
                     });
                     return;
                 }
@@ -576,17 +2536,7 @@ impl Handler<NetworkMessage> for Coordinator {
             }
             NetworkMessage::LeaderIdIs(msg_data) => {
                 self.logger.info("Received LeaderIdIs message");
-                // Informar al CoordinatorManager sobre el nuevo líder
-
-                if let Some(leader_addr) = self.ring_nodes.get(&msg_data.leader_id) {
-                    self.current_coordinator = Some(*leader_addr);
-                } else {
-                    self.logger.info(format!(
-                        "Leader ID {} not found in ring nodes.",
-                        msg_data.leader_id
-                    ));
-                }
-
+                // El CoordinatorManager valida la época y nos informa el nuevo líder
                 if let Some(coordinator_manager) = &self.coordinator_manager {
                     coordinator_manager.do_send(msg_data);
                 } else {
@@ -605,230 +2555,63 @@ impl Handler<NetworkMessage> for Coordinator {
                         .error("Reaper not initialized, cannot reap user.");
                 }
 
-                if let Some(communicator) = self.communicators.get(&msg_data.origin_addr) {
-                    match communicator.peer_type {
-                        PeerType::ClientType => {
-                            let storage = self.storage.clone();
-                            let client_id_clone = user_id.clone();
-                            let logger = self.logger.clone();
-                            ctx.spawn(
-                                async move {
-                                    match storage
-                                        .as_ref()
-                                        .unwrap()
-                                        .send(GetClient {
-                                            client_id: client_id_clone.clone(),
-                                        })
-                                        .await
-                                    {
-                                        Ok(user_dto_opt) => {
-                                            if let Some(client_dto) = user_dto_opt {
-                                                NetworkMessage::RecoveredInfo(UserDTO::Client(
-                                                    client_dto,
-                                                ))
-                                            } else {
-                                                storage.as_ref().unwrap().do_send(AddClient {
-                                                    client: ClientDTO {
-                                                        client_position: msg_data.position,
-                                                        client_id: client_id_clone.clone(),
-                                                        client_order: None,
-                                                        time_stamp: std::time::SystemTime::now(),
-                                                    },
-                                                });
-                                                NetworkMessage::NoRecoveredInfo
-                                            }
-                                        }
-                                        Err(e) => {
-                                            logger.error(format!(
-                                                "Error retrieving client info: {}",
-                                                e
-                                            ));
-                                            storage.as_ref().unwrap().do_send(AddClient {
-                                                client: ClientDTO {
-                                                    client_position: msg_data.position,
-                                                    client_id: client_id_clone.clone(),
-                                                    client_order: None,
-                                                    time_stamp: std::time::SystemTime::now(),
-                                                },
-                                            });
-                                            NetworkMessage::NoRecoveredInfo
-                                        }
-                                    }
-                                }
-                                .into_actor(self)
-                                .map(
-                                    move |network_message, actor, _ctx| {
-                                        actor
-                                            .send_network_message(user_id.clone(), network_message);
-                                    },
-                                ),
-                            );
-                        }
-                        PeerType::RestaurantType => {
-                            let storage = self.storage.clone();
-                            let restaurant_id_clone = user_id.clone();
-                            let logger = self.logger.clone();
-                            ctx.spawn(
-                                async move {
-                                    match storage
-                                        .as_ref()
-                                        .unwrap()
-                                        .send(GetRestaurant {
-                                            restaurant_id: restaurant_id_clone.clone(),
-                                        })
-                                        .await
-                                    {
-                                        Ok(user_dto_opt) => {
-                                            if let Some(restaurant_dto) = user_dto_opt {
-                                                NetworkMessage::RecoveredInfo(UserDTO::Restaurant(
-                                                    restaurant_dto,
-                                                ))
-                                            } else {
-                                                storage.as_ref().unwrap().do_send(AddRestaurant {
-                                                    restaurant: RestaurantDTO {
-                                                        restaurant_position: msg_data.position,
-                                                        restaurant_id: restaurant_id_clone.clone(),
-                                                        authorized_orders: HashSet::new(),
-                                                        pending_orders: HashSet::new(),
-                                                        time_stamp: std::time::SystemTime::now(),
-                                                    },
-                                                });
-                                                NetworkMessage::NoRecoveredInfo
-                                            }
-                                        }
-                                        Err(e) => {
-                                            logger.error(format!(
-                                                "Error retrieving restaurant info: {}",
-                                                e
-                                            ));
-                                            storage.as_ref().unwrap().do_send(AddRestaurant {
-                                                restaurant: RestaurantDTO {
-                                                    restaurant_position: msg_data.position,
-                                                    restaurant_id: restaurant_id_clone.clone(),
-                                                    authorized_orders: HashSet::new(),
-                                                    pending_orders: HashSet::new(),
-                                                    time_stamp: std::time::SystemTime::now(),
-                                                },
-                                            });
-                                            NetworkMessage::NoRecoveredInfo
-                                        }
-                                    }
-                                }
-                                .into_actor(self)
-                                .map(
-                                    move |network_message, actor, _ctx| {
-                                        actor
-                                            .send_network_message(user_id.clone(), network_message);
-                                    },
-                                ),
-                            );
-                        }
-                        PeerType::DeliveryType => {
-                            let storage = self.storage.clone();
-                            let delivery_id_clone = user_id.clone();
-                            let logger = self.logger.clone();
-                            ctx.spawn(
-                                async move {
-                                    match storage
-                                        .as_ref()
-                                        .unwrap()
-                                        .send(GetDelivery {
-                                            delivery_id: delivery_id_clone.clone(),
-                                        })
-                                        .await
-                                    {
-                                        Ok(user_dto_opt) => {
-                                            if let Some(delivery_dto) = user_dto_opt {
-                                                let delivery_dto = DeliveryDTO {
-                                                    delivery_position: msg_data.position,
-                                                    delivery_id: delivery_id_clone.clone(),
-                                                    current_client_id: delivery_dto
-                                                        .current_client_id,
-                                                    current_order: delivery_dto.current_order,
-                                                    status: delivery_dto.status,
-                                                    time_stamp: std::time::SystemTime::now(),
-                                                };
-                                                storage.as_ref().unwrap().do_send(AddDelivery {
-                                                    delivery: delivery_dto.clone(),
-                                                });
-                                                NetworkMessage::RecoveredInfo(UserDTO::Delivery(
-                                                    delivery_dto,
-                                                ))
-                                            } else {
-                                                storage.as_ref().unwrap().do_send(AddDelivery {
-                                                    delivery: DeliveryDTO {
-                                                        delivery_position: msg_data.position,
-                                                        delivery_id: delivery_id_clone.clone(),
-                                                        current_client_id: None,
-                                                        current_order: None,
-                                                        status: DeliveryStatus::Available,
-                                                        time_stamp: std::time::SystemTime::now(),
-                                                    },
-                                                });
-                                                NetworkMessage::NoRecoveredInfo
-                                            }
-                                        }
-                                        Err(e) => {
-                                            logger.error(format!(
-                                                "Error retrieving delivery info: {}",
-                                                e
-                                            ));
-                                            if let Some(storage_addr) = storage.as_ref() {
-                                                storage_addr.do_send(AddDelivery {
-                                                    delivery: DeliveryDTO {
-                                                        delivery_position: msg_data.position,
-                                                        delivery_id: delivery_id_clone.clone(),
-                                                        current_client_id: None,
-                                                        current_order: None,
-                                                        status: DeliveryStatus::Available,
-                                                        time_stamp: std::time::SystemTime::now(),
-                                                    },
-                                                });
-                                            }
-
-                                            NetworkMessage::NoRecoveredInfo
-                                        }
-                                    }
-                                }
-                                .into_actor(self)
-                                .map(
-                                    move |network_message, actor, _ctx| {
-                                        actor
-                                            .send_network_message(user_id.clone(), network_message);
-                                    },
-                                ),
-                            );
-                        }
-                        _ => {
-                            self.logger.info(format!(
-                                "Received RegisterUser from non-client type: {:?}",
-                                communicator.peer_type
-                            ));
-                        }
-                    }
-
-                    // Actualiza el user_id asociado a la dirección de origen
-                    self.user_addresses
-                        .insert(msg_data.origin_addr, msg_data.user_id.clone());
-                    self.logger.info(format!(
-                        "User address mapping actualizado: {} -> {}",
-                        msg_data.origin_addr, msg_data.user_id
-                    ));
-                } else {
-                    self.logger.info(format!(
-                        "Communicator not found for {}",
-                        msg_data.origin_addr
-                    ));
+                if self.is_follower() && self.communicators.contains_key(&msg_data.origin_addr) {
+                    self.forward_registration(msg_data);
+                    return;
                 }
 
-                // TODO: Intentar recuperar información del usuario desde storage
-                // self.send_network_message(client_id, NetworkMessage::NoRecoveredInfo);
+                // Sólo quien tiene el secreto con el que se registró el ID puede volver a usarlo
+                let Some(storage) = self.storage.clone() else {
+                    self.logger
+                        .error("Storage not initialized, cannot register user.");
+                    return;
+                };
+                storage
+                    .send(ClaimUserId {
+                        user_id: msg_data.user_id.clone(),
+                        secret: msg_data.secret.clone(),
+                        tenant_id: msg_data.tenant_id.clone(),
+                        secret_hash: None,
+                    })
+                    .into_actor(self)
+                    .map(move |res, act, ctx| match res {
+                        Ok(true) => act.register_user(msg_data, ctx),
+                        Ok(false) => act.reject_identity(msg_data),
+                        Err(e) => act.logger.error(format!(
+                            "Error checking the identity of {}: {}",
+                            msg_data.user_id, e
+                        )),
+                    })
+                    // Los mensajes siguientes del usuario esperan a que quede registrado
+                    .wait(ctx);
             }
 
             // Client messages
             NetworkMessage::RequestThisOrder(msg_data) => {
+                if self.order_service.is_none() {
+                    self.logger.info("OrderService not initialized yet.");
+                    return;
+                }
+                // El ID y el marketplace los decide el OrderService, no el cliente
+                if let Some(order_service) = &self.order_service {
+                    order_service
+                        .for_request(msg_data.request_id)
+                        .do_send(msg_data);
+                }
+            }
+            NetworkMessage::AckOrderUpdate(msg_data) => {
+                if let Some(storage) = &self.storage {
+                    storage.do_send(AckOrderUpdates {
+                        user_id: msg_data.client_id,
+                        update_id: msg_data.update_id,
+                    });
+                } else {
+                    self.logger.info("Storage not initialized yet.");
+                }
+            }
+            NetworkMessage::CancelMyOrder(msg_data) => {
                 if let Some(order_service) = &self.order_service {
-                    order_service.do_send(msg_data);
+                    order_service.for_order(msg_data.order_id).do_send(msg_data);
                 } else {
                     self.logger.info("OrderService not initialized yet.");
                 }
@@ -845,16 +2628,47 @@ impl Handler<NetworkMessage> for Coordinator {
                 self.logger
                     .info("Received OrderFinalized message, not implemented yet");
             }
-            NetworkMessage::RequestNearbyRestaurants(msg_data) => {
-                if let Some(service) = &self.nearby_restaurant_service {
-                    service.do_send(msg_data);
+            NetworkMessage::RequestNearbyRestaurants(mut msg_data) => {
+                if !self.can_serve_reads() {
+                    self.forward_to_leader(NetworkMessage::RequestNearbyRestaurants(msg_data));
+                } else if let (Some(service), Some(storage)) =
+                    (self.nearby_restaurant_service.clone(), self.storage.clone())
+                {
+                    // El marketplace lo decide el registro guardado, no lo que diga el cliente
+                    storage
+                        .send(GetClient {
+                            client_id: msg_data.client.client_id.clone(),
+                        })
+                        .into_actor(self)
+                        .map(move |res, _act, _ctx| {
+                            msg_data.client.tenant_id = match res {
+                                Ok(Some(client)) => client.tenant_id,
+                                _ => TenantId::default(),
+                            };
+                            service.do_send(msg_data);
+                        })
+                        .spawn(ctx);
                 } else {
                     self.logger
                         .info("NearbyRestaurantsService not initialized yet.");
                 }
             }
+            NetworkMessage::RequestOrderStatus(msg_data) => {
+                if !self.can_serve_reads() {
+                    self.forward_to_leader(NetworkMessage::RequestOrderStatus(msg_data));
+                } else {
+                    self.handle_order_status_request(msg_data, ctx);
+                }
+            }
 
             // Delivery messages
+            NetworkMessage::GetMyEarnings(msg_data) => {
+                if !self.can_serve_reads() {
+                    self.forward_to_leader(NetworkMessage::GetMyEarnings(msg_data));
+                } else {
+                    self.handle_earnings_request(msg_data, ctx);
+                }
+            }
             NetworkMessage::IAmAvailable(_msg_data) => {
                 self.logger
                     .info("Received IAmAvailable message, not implemented yet");
@@ -864,25 +2678,35 @@ impl Handler<NetworkMessage> for Coordinator {
                     .info("Received AcceptOrder message, not implemented yet");
                 self.handle_order_accepted(msg_data.order.order_id, ctx);
                 if let Some(order_service) = &self.order_service {
-                    order_service.do_send(msg_data);
+                    order_service
+                        .for_order(msg_data.order.order_id)
+                        .do_send(msg_data);
                 } else {
                     self.logger.info("OrderService not initialized yet.");
                 }
             }
             NetworkMessage::OrderDelivered(msg_data) => {
                 if let Some(order_service) = &self.order_service {
-                    order_service.do_send(UpdateOrderStatus {
-                        order: msg_data.order.clone(),
-                    });
+                    order_service
+                        .for_order(msg_data.order.order_id)
+                        .do_send(UpdateOrderStatus {
+                            order: msg_data.order.clone(),
+                            reason: None,
+                            kitchen_load: None,
+                        });
                 } else {
                     self.logger.info("OrderService not initialized yet.");
                 }
             }
             NetworkMessage::IAmDelivering(msg_data) => {
                 if let Some(order_service) = &self.order_service {
-                    order_service.do_send(UpdateOrderStatus {
-                        order: msg_data.order.clone(),
-                    });
+                    order_service
+                        .for_order(msg_data.order.order_id)
+                        .do_send(UpdateOrderStatus {
+                            order: msg_data.order.clone(),
+                            reason: None,
+                            kitchen_load: None,
+                        });
                 } else {
                     self.logger.info("OrderService not initialized yet.");
                 }
@@ -890,8 +2714,14 @@ impl Handler<NetworkMessage> for Coordinator {
 
             // Restaurant messages
             NetworkMessage::UpdateOrderStatus(msg_data) => {
+                if let Some(load) = msg_data.kitchen_load {
+                    self.kitchen_loads
+                        .insert(msg_data.order.restaurant_id.clone(), load);
+                }
                 if let Some(order_service) = &self.order_service {
-                    order_service.do_send(msg_data);
+                    order_service
+                        .for_order(msg_data.order.order_id)
+                        .do_send(msg_data);
                 } else {
                     self.logger.info("OrderService not initialized yet.");
                 }
@@ -911,19 +2741,43 @@ impl Handler<NetworkMessage> for Coordinator {
             }
             NetworkMessage::DeliverThisOrder(msg_data) => {
                 if let Some(order_service) = &self.order_service {
-                    order_service.do_send(msg_data);
+                    order_service
+                        .for_order(msg_data.order.order_id)
+                        .do_send(msg_data);
                 } else {
                     self.logger.info("OrderService not initialized yet.");
                 }
             }
             NetworkMessage::DeliveryAccepted(msg_data) => {
                 if let Some(order_service) = &self.order_service {
-                    order_service.do_send(msg_data);
+                    order_service
+                        .for_order(msg_data.order.order_id)
+                        .do_send(msg_data);
                 } else {
                     self.logger.info("OrderService not initialized yet.");
                 }
             }
 
+            NetworkMessage::DeregisterUser(msg_data) => {
+                self.deregister_user(msg_data, ctx);
+            }
+            NetworkMessage::RestaurantAvailability(msg_data) => {
+                self.logger.info(format!(
+                    "Restaurant {} is now {}",
+                    msg_data.restaurant_id,
+                    if msg_data.open { "open" } else { "closed" }
+                ));
+                if let Some(storage) = &self.storage {
+                    storage.do_send(SetRestaurantOpen {
+                        restaurant_id: msg_data.restaurant_id,
+                        open: msg_data.open,
+                        time_stamp: None,
+                    });
+                } else {
+                    self.logger.info("Storage not initialized yet.");
+                }
+            }
+
             // CoordinatorManager messages
             NetworkMessage::RequestNewStorageUpdates(msg_data) => {
                 if let Some(coordinator_manager) = &self.coordinator_manager {
@@ -947,6 +2801,28 @@ impl Handler<NetworkMessage> for Coordinator {
                     self.logger.info("CoordinatorManager not initialized yet.");
                 }
             }
+            NetworkMessage::RequestStorageSubset(msg_data) => {
+                self.logger.info("Received RequestStorageSubset message");
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg_data);
+                } else {
+                    self.logger.info("CoordinatorManager not initialized yet.");
+                }
+            }
+            NetworkMessage::AntiEntropyDigest(msg_data) => {
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg_data);
+                } else {
+                    self.logger.info("CoordinatorManager not initialized yet.");
+                }
+            }
+            NetworkMessage::AntiEntropyRepair(msg_data) => {
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg_data);
+                } else {
+                    self.logger.info("CoordinatorManager not initialized yet.");
+                }
+            }
             NetworkMessage::StorageSnapshot(msg_data) => {
                 self.logger.info("Received StorageSnapshot message");
                 if let Some(coordinator_manager) = &self.coordinator_manager {
@@ -955,10 +2831,61 @@ impl Handler<NetworkMessage> for Coordinator {
                     self.logger.info("CoordinatorManager not initialized yet.");
                 }
             }
+            NetworkMessage::SnapshotChunk(msg_data) => {
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg_data);
+                } else {
+                    self.logger.info("CoordinatorManager not initialized yet.");
+                }
+            }
+            NetworkMessage::ForwardToLeader(msg_data) => {
+                if self.is_follower() {
+                    // El líder cambió mientras viajaba el mensaje
+                    self.forward_to_leader(*msg_data.message);
+                } else {
+                    ctx.address().do_send(*msg_data.message);
+                }
+            }
+            NetworkMessage::ProxyUser(msg_data) => {
+                self.handle_proxy_user(msg_data);
+            }
+            NetworkMessage::ForwardToUser(msg_data) => {
+                self.send_network_message(msg_data.user_id, *msg_data.message);
+            }
+            NetworkMessage::ReplicateWrite(msg_data) => {
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg_data);
+                } else {
+                    self.logger.info("CoordinatorManager not initialized yet.");
+                }
+            }
+            NetworkMessage::WriteAck(msg_data) => {
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg_data);
+                } else {
+                    self.logger.info("CoordinatorManager not initialized yet.");
+                }
+            }
+            NetworkMessage::PrepareLeadership(msg_data) => {
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg_data);
+                } else {
+                    self.logger.info("CoordinatorManager not initialized yet.");
+                }
+            }
             NetworkMessage::RecoverStorageOperations(_msg_data) => {
                 self.logger
                     .info("Received RecoverStorageOperations message");
             }
+            NetworkMessage::BullyElection(msg) => {
+                self.logger
+                    .info(format!("Received BullyElection message: {:?}", msg));
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg);
+                } else {
+                    self.logger.info("CoordinatorManager not initialized yet.");
+                }
+            }
             NetworkMessage::LeaderElection(msg) => {
                 self.logger.info(format!(
                     "Received LeaderElection message from {} with candidates {:?}",
@@ -993,30 +2920,59 @@ impl Handler<NetworkMessage> for Coordinator {
                 self.logger
                     .info(format!("Connection closed for {}", msg_data.remote_addr));
                 let remote_addr = msg_data.remote_addr;
+                self.rate_limiter.forget(&RateLimitKey::Peer(remote_addr));
 
-                let user = self
-                    .user_addresses
-                    .get_by_key(&remote_addr)
-                    .cloned()
-                    .unwrap_or_else(|| "UNKNOWN_USER".to_string());
-
-                if let Some(reaper_addr) = &self.reaper {
-                    reaper_addr.do_send(ReapUser {
+                // Las conexiones de coordinadores no tienen un usuario asociado
+                let user = self.user_addresses.get_by_key(&remote_addr).cloned();
+                if let Some(user) = &user {
+                    self.rate_limiter.forget(&RateLimitKey::User(user.clone()));
+                }
+                let proxied = user
+                    .as_ref()
+                    .and_then(|user| self.proxied_users.remove(user));
+                if let Some(user) = &user
+                    && let Some(peer_type) = proxied
+                    && self.is_follower()
+                {
+                    // Al usuario lo da por perdido el líder, que es quien tiene su estado
+                    self.forward_to_leader(NetworkMessage::ProxyUser(ProxyUser {
+                        coordinator_id: self.id.clone(),
                         user_id: user.clone(),
-                    });
-                    self.logger.info(format!(
-                        "Reaping user {} due to disconnection from {}",
-                        user, remote_addr
-                    ));
-                } else {
-                    self.logger
-                        .error("Reaper not initialized, cannot reap user.");
+                        peer_type,
+                        connected: false,
+                    }));
+                } else if let Some(user) = &user {
+                    if let Some(reaper_addr) = &self.reaper {
+                        reaper_addr.do_send(ReapUser {
+                            user_id: user.clone(),
+                        });
+                        self.logger.info(format!(
+                            "Reaping user {} due to disconnection from {}",
+                            user, remote_addr
+                        ));
+                    } else {
+                        self.logger
+                            .error("Reaper not initialized, cannot reap user.");
+                    }
                 }
 
                 // Si el remote_addr está en self.communicators, lo eliminamos
-                if let Some(_communicator) = self.communicators.get(&remote_addr) {
-                    self.communicators.remove(&remote_addr);
+                if let Some(communicator) = self.communicators.remove(&remote_addr) {
                     self.user_addresses.remove_by_key(&remote_addr);
+                    let unacknowledged = communicator.take_unacknowledged();
+                    if let Some(user) = &user
+                        && !unacknowledged.is_empty()
+                    {
+                        // Lo que el usuario no confirmó se le reenvía cuando se reconecte
+                        self.logger.info(format!(
+                            "Keeping {} unacknowledged messages until {} reconnects",
+                            unacknowledged.len(),
+                            user
+                        ));
+                        for message in unacknowledged {
+                            self.dead_letters.push(user, message, Instant::now());
+                        }
+                    }
 
                     self.logger
                         .info(format!("Removed communicator for {}", remote_addr));
@@ -1034,6 +2990,42 @@ impl Handler<NetworkMessage> for Coordinator {
                 }
             }
 
+            NetworkMessage::IncompatibleVersion(msg_data) => {
+                // La conexión se cierra y llega un ConnectionClosed a continuación
+                self.logger.warn(format!(
+                    "Rejected connection with {}: protocol v{} not in supported range {:?}",
+                    msg_data.remote_addr, msg_data.received, msg_data.supported
+                ));
+            }
+
+            NetworkMessage::RequestElectionHistory(msg_data) => {
+                self.handle_election_history_request(msg_data, ctx);
+            }
+
+            NetworkMessage::VerifyStorage(msg_data) => {
+                self.handle_verify_storage_request(msg_data, ctx);
+            }
+
+            NetworkMessage::DrainCoordinator(msg_data) => {
+                self.handle_drain_request(msg_data, ctx);
+            }
+
+            NetworkMessage::TransferLeadership(msg_data) => {
+                self.handle_transfer_request(msg_data, ctx);
+            }
+
+            NetworkMessage::InjectFaults(msg_data) => {
+                self.logger
+                    .warn(format!("Injecting network faults: {:?}", msg_data.config));
+                for communicator in self.communicators.values() {
+                    communicator.set_faults(msg_data.config.clone());
+                }
+                // Las conexiones con los demás coordinadores las maneja el CoordinatorManager
+                if let Some(coordinator_manager) = &self.coordinator_manager {
+                    coordinator_manager.do_send(msg_data);
+                }
+            }
+
             _ => {
                 self.logger.info(format!(
                     "NetworkMessage descartado/no implementado: {:?}",
@@ -1044,9 +3036,218 @@ impl Handler<NetworkMessage> for Coordinator {
     }
 }
 
+/// Fetches an order and the restaurant that prepares it from the storage.
+///
+/// ## Returns
+/// [`CoordinatorError::OrderNotFound`] if either of them is not in the storage, or
+/// [`CoordinatorError::Mailbox`] if the storage did not answer.
+async fn fetch_order_and_restaurant(
+    storage: Addr<Storage>,
+    order_id: u64,
+) -> Result<(OrderDTO, RestaurantDTO), CoordinatorError> {
+    let order = storage
+        .send(GetOrder { order_id })
+        .await?
+        .ok_or(CoordinatorError::OrderNotFound(order_id))?;
+    let restaurant = storage
+        .send(GetRestaurant {
+            restaurant_id: order.restaurant_id.clone(),
+        })
+        .await?
+        .ok_or(CoordinatorError::OrderNotFound(order_id))?;
+    Ok((order, restaurant))
+}
+
+/// Looks up the order `client_id` has in progress, if any.
+async fn fetch_client_order(storage: Addr<Storage>, client_id: String) -> Option<OrderDTO> {
+    let client = storage.send(GetClient { client_id }).await.ok().flatten()?;
+    let order_id = client.client_order?.order_id;
+    storage.send(GetOrder { order_id }).await.ok().flatten()
+}
+
 impl Drop for Coordinator {
     fn drop(&mut self) {
         actix::System::current().stop();
         process::exit(0);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server_actors::coordinator_manager::tests::{
+        Outboxes, addr_of, new_manager, sent_to,
+    };
+    use std::mem::ManuallyDrop;
+    use std::time::Duration;
+
+    /// Returns the coordinator `server_1` of a ring of two, following `server_0`, and the
+    /// messages its manager sends to the leader.
+    ///
+    /// Dropping a `Coordinator` ends the process, so it is never dropped.
+    async fn follower() -> (
+        ManuallyDrop<Coordinator>,
+        Addr<CoordinatorManager>,
+        Outboxes,
+    ) {
+        let ring_nodes = HashMap::from([
+            ("server_0".to_string(), addr_of(0)),
+            ("server_1".to_string(), addr_of(1)),
+        ]);
+        let mut coordinator = ManuallyDrop::new(
+            Coordinator::new(
+                addr_of(1),
+                ring_nodes,
+                TransportKind::Grpc,
+                WriteAckMode::Leader,
+                ElectionKind::default(),
+                None,
+            )
+            .await,
+        );
+        let (mut manager, outboxes) = new_manager(1, 2);
+        manager.coordinator_actual = Some(addr_of(0));
+        let manager = manager.start();
+        coordinator.current_coordinator = Some(addr_of(0));
+        coordinator.coordinator_manager = Some(manager.clone());
+        (coordinator, manager, outboxes)
+    }
+
+    /// Runs to completion the future a handler of `coordinator` answered with.
+    async fn run<T>(
+        coordinator: &mut Coordinator,
+        mut answer: ResponseActFuture<Coordinator, T>,
+    ) -> T {
+        let mut ctx = Context::new();
+        std::future::poll_fn(|task| answer.as_mut().poll(coordinator, &mut ctx, task)).await
+    }
+
+    fn order_status_request() -> NetworkMessage {
+        NetworkMessage::RequestOrderStatus(RequestOrderStatus {
+            client_id: "client_0".to_string(),
+            order_id: 7,
+        })
+    }
+
+    #[actix_rt::test]
+    async fn only_a_follower_that_synced_recently_serves_reads() {
+        let (mut coordinator, _manager, _outboxes) = follower().await;
+        assert!(!coordinator.can_serve_reads());
+
+        coordinator.last_replica_sync = Some(Instant::now());
+        assert!(coordinator.can_serve_reads());
+
+        let stale = Instant::now() - REPLICA_MAX_STALENESS - Duration::from_secs(1);
+        coordinator.last_replica_sync = Some(stale);
+        assert!(!coordinator.can_serve_reads());
+
+        // El líder siempre responde desde su storage
+        coordinator.current_coordinator = Some(coordinator.my_addr);
+        assert!(coordinator.can_serve_reads());
+    }
+
+    #[actix_rt::test]
+    async fn a_synced_follower_answers_the_order_status_itself() {
+        let (mut coordinator, manager, outboxes) = follower().await;
+        coordinator.last_replica_sync = Some(Instant::now());
+
+        coordinator.handle(order_status_request(), &mut Context::new());
+        manager.send(GetRingState).await.unwrap();
+        assert!(sent_to(&outboxes, "server_0").is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn a_stale_follower_forwards_the_order_status_to_the_leader() {
+        let (mut coordinator, manager, outboxes) = follower().await;
+
+        coordinator.handle(order_status_request(), &mut Context::new());
+        // El manager procesa los mensajes en orden: al responder ya reenvió la consulta
+        manager.send(GetRingState).await.unwrap();
+        match sent_to(&outboxes, "server_0").as_slice() {
+            [NetworkMessage::ForwardToLeader(forward)] => {
+                assert_eq!(forward.coordinator_id, "server_1");
+                assert!(matches!(
+                    *forward.message,
+                    NetworkMessage::RequestOrderStatus(RequestOrderStatus { order_id: 7, .. })
+                ));
+            }
+            other => panic!("Unexpected messages: {:?}", other),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn requests_are_charged_to_their_connection_whatever_user_they_claim() {
+        let (mut coordinator, manager, outboxes) = follower().await;
+        coordinator.rate_limiter = RateLimiter::new(1, 0.001, 10);
+        let user_addr = addr_of(5);
+        coordinator
+            .user_addresses
+            .insert(user_addr, "client_0".to_string());
+
+        for client_id in ["client_0", "client_9", "server_0"] {
+            let request = NetworkMessage::RequestOrderStatus(RequestOrderStatus {
+                client_id: client_id.to_string(),
+                order_id: 7,
+            });
+            coordinator.handle(
+                ReceivedFrom {
+                    remote_addr: user_addr,
+                    message: request,
+                },
+                &mut Context::new(),
+            );
+        }
+        manager.send(GetRingState).await.unwrap();
+        // Sólo la primera pasa: las otras se cobran a la misma conexión
+        assert_eq!(sent_to(&outboxes, "server_0").len(), 1);
+        let session = RateLimitKey::User("client_0".to_string());
+        assert!(coordinator.rate_limiter.retry_after(&session) > Duration::ZERO);
+
+        coordinator.handle(
+            NetworkMessage::ConnectionClosed(ConnectionClosed {
+                remote_addr: user_addr,
+            }),
+            &mut Context::new(),
+        );
+        assert_eq!(
+            coordinator.rate_limiter.retry_after(&session),
+            Duration::ZERO
+        );
+    }
+
+    #[actix_rt::test]
+    async fn a_missing_order_cannot_be_reassigned_nor_searched_again() {
+        let (mut coordinator, _manager, _outboxes) = follower().await;
+        let reassign = || ReassignOrder {
+            order_id: 7,
+            delivery_id: "delivery_0".to_string(),
+        };
+        let answer = coordinator.handle(reassign(), &mut Context::new());
+        assert_eq!(
+            run(&mut coordinator, answer).await,
+            Err(CoordinatorError::NotInitialized("Storage"))
+        );
+
+        let storage = Storage::new(
+            Context::<Coordinator>::new().address(),
+            "server_1".to_string(),
+            None,
+            StorageReadView::default(),
+        );
+        coordinator.storage = Some(storage.start());
+        let answer = coordinator.handle(reassign(), &mut Context::new());
+        assert_eq!(
+            run(&mut coordinator, answer).await,
+            Err(CoordinatorError::OrderNotFound(7))
+        );
+        let retry = RetryDeliverySearch {
+            order_id: 7,
+            radius: None,
+        };
+        let answer = coordinator.handle(retry, &mut Context::new());
+        assert_eq!(
+            run(&mut coordinator, answer).await,
+            Err(CoordinatorError::OrderNotFound(7))
+        );
+    }
+}
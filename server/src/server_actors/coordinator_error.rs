@@ -0,0 +1,83 @@
+use actix::MailboxError;
+use std::fmt;
+
+/// Why the [`Coordinator`](super::coordinator::Coordinator) could not do what another actor
+/// asked for, returned so the caller can retry, cancel or re-route the work instead of losing
+/// it.
+///
+/// Only the internal messages whose callers act on their failures return it: `QuorumWrite`,
+/// `ReassignOrder` and `RetryDeliverySearch`. The handlers of network messages still log
+/// their failures, since the peer that sent them waits for no answer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoordinatorError {
+    /// The actor with this name has not been started or its address was not set yet.
+    NotInitialized(&'static str),
+    /// No user with this ID is registered.
+    UserNotFound(String),
+    /// The user with this ID is registered but its connection is gone.
+    CommunicatorNotFound(String),
+    /// The connection with the user with this ID cannot send messages.
+    SenderNotFound(String),
+    /// The order with this ID, or its restaurant, is not stored.
+    OrderNotFound(u64),
+    /// The write was not confirmed by a quorum of coordinators in time.
+    QuorumNotReached,
+    /// The storage ignored the write, so there is nothing to replicate.
+    WriteIgnored,
+    /// The message could not be delivered to an actor, or it stopped before answering.
+    Mailbox(String),
+}
+
+impl fmt::Display for CoordinatorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoordinatorError::NotInitialized(actor) => write!(f, "{} not initialized yet", actor),
+            CoordinatorError::UserNotFound(user_id) => write!(f, "User ID {} not found", user_id),
+            CoordinatorError::CommunicatorNotFound(user_id) => {
+                write!(f, "Communicator not found for {}", user_id)
+            }
+            CoordinatorError::SenderNotFound(user_id) => {
+                write!(f, "No sender found for {}", user_id)
+            }
+            CoordinatorError::OrderNotFound(order_id) => {
+                write!(f, "Order {} or its restaurant not found", order_id)
+            }
+            CoordinatorError::QuorumNotReached => {
+                write!(f, "Write not confirmed by a quorum of coordinators")
+            }
+            CoordinatorError::WriteIgnored => write!(f, "Write ignored by the storage"),
+            CoordinatorError::Mailbox(e) => write!(f, "Mailbox error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CoordinatorError {}
+
+impl From<MailboxError> for CoordinatorError {
+    fn from(e: MailboxError) -> Self {
+        CoordinatorError::Mailbox(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mailbox_errors_become_coordinator_errors() {
+        let error: CoordinatorError = MailboxError::Closed.into();
+        assert!(matches!(error, CoordinatorError::Mailbox(_)));
+    }
+
+    #[test]
+    fn describes_the_user_that_cannot_be_reached() {
+        assert_eq!(
+            CoordinatorError::UserNotFound("ana".to_string()).to_string(),
+            "User ID ana not found"
+        );
+        assert_eq!(
+            CoordinatorError::SenderNotFound("ana".to_string()).to_string(),
+            "No sender found for ana"
+        );
+    }
+}
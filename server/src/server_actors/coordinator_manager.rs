@@ -1,23 +1,103 @@
 use crate::messages::internal_messages::{
-    GetAllStorage, GetLogsFromIndex, GetMinLogIndex, RegisterConnectionWithCoordinator,
+    ApplyReplicatedWrite, CompactLog, GetAllStorage, GetCompactionStatus, GetFollowerProgress,
+    GetLogsFromIndex, GetRingState, GetStorageDigest, HandOffLeadership, IsRestoredFromLog,
+    MemberStateChanged, MergeSnapshot, QuorumWrite, RegisterConnectionWithCoordinator,
+    ReplicaSynced, ResyncStorage,
 };
 use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::coordinator_error::CoordinatorError;
+use crate::server_actors::election::{
+    ElectionAction, ElectionKind, ElectionMessage, ElectionStrategy, ElectionView,
+};
+use crate::server_actors::gossip::{self, FailureDetector, MemberState};
+use crate::server_actors::services::cluster_snapshot::{
+    CoordinatorStatus, FollowerProgress, RingState,
+};
 use crate::server_actors::storage::Storage;
 use actix::prelude::*;
 use colored::Color;
 use common::bimap::BiMap;
 use common::constants::{
-    INTERVAL_HEARTBEAT, INTERVAL_STORAGE, TIMEOUT_HEARTBEAT, TIMEOUT_LEADER_RESPONSE,
+    ANTI_ENTROPY_INTERVAL, INTERVAL_HEARTBEAT, INTERVAL_STORAGE, LEADERSHIP_HANDOFF_TIMEOUT,
+    LOG_COMPACTION_FOLLOWER_TIMEOUT, LOG_COMPACTION_INTERVAL, MAX_RECENT_ELECTIONS,
+    QUORUM_WRITE_TIMEOUT, SNAPSHOT_CACHE_TTL, SNAPSHOT_CHUNK_SIZE, SNAPSHOT_CHUNK_TIMEOUT,
+    TIMEOUT_HEARTBEAT, TIMEOUT_LEADER_RESPONSE,
 };
 use common::logger::Logger;
 use common::messages::coordinatormanager_messages::{
-    CheckPongTimeout, LeaderElection, Ping, Pong, RequestAllStorage, RequestNewStorageUpdates,
-    StorageSnapshot, StorageUpdates,
+    AntiEntropyDigest, AntiEntropyRepair, BullyElection, CheckPongTimeout, ForwardToLeader,
+    ForwardToUser, LeaderElection, Ping, Pong, PrepareLeadership, ReplicateWrite,
+    RequestAllStorage, RequestNewStorageUpdates, RequestStorageSubset, SnapshotChunk,
+    StorageSnapshot, StorageUpdates, WriteAck,
+};
+use common::messages::shared_messages::{
+    ConnectionClosed, InjectFaults, NetworkMessage, TransferLeadership,
 };
-use common::messages::shared_messages::{ConnectionClosed, NetworkMessage};
 use common::messages::{ApplyStorageUpdates, LeaderIdIs, StartRunning, WhoIsLeader};
-use common::network::communicator::Communicator;
+use common::network::transport::{PeerTransport, TransportKind, open_transport};
+use common::types::dtos::{ElectionRecord, Snapshot};
+use common::types::storage_entity::StorageEntity;
+use rand::seq::SliceRandom;
+use std::collections::{HashSet, VecDeque};
 use std::{collections::HashMap, net::SocketAddr};
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+/// Environment variable used to select when storage writes are acknowledged.
+pub const WRITE_ACK_ENV_VAR: &str = "WRITE_ACK_MODE";
+
+/// When a write is acknowledged to the client that caused it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WriteAckMode {
+    /// As soon as the leader applies it; followers catch up lazily (the default).
+    #[default]
+    Leader,
+    /// Once a majority of the coordinators (more than half) hold it.
+    Quorum,
+}
+
+impl WriteAckMode {
+    /// Reads the mode from [`WRITE_ACK_ENV_VAR`] (`leader` or `quorum`), defaulting to `leader`.
+    pub fn from_env() -> Self {
+        match std::env::var(WRITE_ACK_ENV_VAR) {
+            Ok(value) if value.eq_ignore_ascii_case("quorum") => WriteAckMode::Quorum,
+            _ => WriteAckMode::Leader,
+        }
+    }
+}
+
+/// A write waiting for the confirmation of a quorum of coordinators.
+#[derive(Debug)]
+struct PendingWrite {
+    /// IDs of the coordinators that confirmed the write, including this one.
+    acks: HashSet<String>,
+    /// Number of confirmations needed.
+    needed: usize,
+    /// Completes the [`QuorumWrite`] request.
+    responder: oneshot::Sender<bool>,
+}
+
+/// A handoff of the leadership waiting for the successor to apply the whole log.
+#[derive(Debug)]
+struct PendingHandoff {
+    /// ID of the coordinator that takes over.
+    successor: String,
+    /// Last index of the log of this coordinator, which the successor must apply.
+    last_index: u64,
+    /// Completes the [`HandOffLeadership`] request.
+    responder: oneshot::Sender<Option<SocketAddr>>,
+}
+
+/// A storage snapshot being received in chunks from the leader.
+#[derive(Debug)]
+struct IncomingSnapshot {
+    /// ID of the snapshot being received.
+    snapshot_id: u64,
+    /// Number of chunks of the snapshot.
+    total: u32,
+    /// Chunks received so far, in order.
+    chunks: Vec<String>,
+}
 
 /// The `CoordinatorManager` actor is responsible for leader election, heartbeat monitoring,
 /// and distributed storage synchronization among coordinator nodes in the system.
@@ -37,11 +117,15 @@ pub struct CoordinatorManager {
     /// Current coordinator node (leader).
     pub coordinator_actual: Option<SocketAddr>,
     /// Map of coordinator node addresses to their communicators.
-    pub coord_communicators: HashMap<SocketAddr, Communicator<Coordinator>>,
+    pub coord_communicators: HashMap<SocketAddr, Box<dyn PeerTransport>>,
+    /// Transport used to talk to the other coordinators.
+    pub transport: TransportKind,
     /// Bi-directional map of coordinator addresses and their IDs.
     pub coord_addresses: BiMap<SocketAddr, String>,
     /// Socket address of this server.
     pub my_socket_addr: SocketAddr,
+    /// Address this server listens on, if not `my_socket_addr`.
+    pub bind_addr: SocketAddr,
     /// Logger for coordinator manager events
     pub logger: Logger,
     /// Address of the local `Coordinator` actor.
@@ -60,6 +144,45 @@ pub struct CoordinatorManager {
     get_storage_updates_timer: Option<actix::SpawnHandle>,
     /// waiting for leader response
     waiting_for_leader: Option<actix::SpawnHandle>,
+    /// Serialized snapshots sent to each coordinator, kept to resume interrupted transfers.
+    outgoing_snapshots: HashMap<String, (u64, Vec<String>)>,
+    /// Snapshot currently being received from the leader.
+    incoming_snapshot: Option<IncomingSnapshot>,
+    /// Kinds of entities asked for in the snapshot requested from the leader.
+    snapshot_entities: Vec<StorageEntity>,
+    /// Timer handle for re-requesting a stalled snapshot transfer.
+    snapshot_stall_timer: Option<actix::SpawnHandle>,
+    /// When storage writes are acknowledged to clients.
+    pub write_ack: WriteAckMode,
+    /// ID of the next replicated write.
+    next_write_id: u64,
+    /// Writes waiting for a quorum, by write ID.
+    pending_writes: HashMap<u64, PendingWrite>,
+    /// The most recent leader changes, oldest first.
+    elections: VecDeque<ElectionRecord>,
+    /// Highest leader epoch known. Storage messages of an older epoch come from a stale
+    /// leader and are rejected.
+    epoch: u64,
+    /// Last log index applied by each coordinator that requests updates from this one, and
+    /// when it was reported.
+    follower_progress: HashMap<String, (u64, Instant)>,
+    /// Address of the gossip failure detector, if enabled.
+    failure_detector: Option<Addr<FailureDetector>>,
+    /// IDs of the coordinators the failure detector declared dead. They are left out of
+    /// the ring until they answer again.
+    dead_coordinators: HashSet<String>,
+    /// The leader election algorithm.
+    election: Box<dyn ElectionStrategy>,
+    /// Timer handle of the election strategy.
+    election_timer: Option<actix::SpawnHandle>,
+    /// Leadership handoff in progress, on the leader.
+    handoff: Option<PendingHandoff>,
+    /// On a follower, the coordinator a transfer of the leadership was requested for, and
+    /// where to answer once it is announced.
+    awaited_leader: Option<(String, oneshot::Sender<Option<SocketAddr>>)>,
+    /// Coordinator to pull the log from instead of the previous one of the ring, while the
+    /// leadership is handed off to this one.
+    pull_from: Option<SocketAddr>,
 }
 
 impl Actor for CoordinatorManager {
@@ -75,12 +198,16 @@ impl CoordinatorManager {
     /// * `ring_nodes` - Map of all ring node IDs to their addresses.
     /// * `coordinator_addr` - Address of the local `Coordinator` actor.
     /// * `storage` - Address of the `Storage` actor.
+    /// * `transport` - Transport used to talk to the other coordinators.
+    /// * `write_ack` - When storage writes are acknowledged to clients.
     pub fn new(
         id: String,
         my_coordinator_addr: SocketAddr,
         ring_nodes: HashMap<String, SocketAddr>,
         coordinator_addr: Addr<Coordinator>,
         storage: Addr<Storage>,
+        transport: TransportKind,
+        write_ack: WriteAckMode,
     ) -> Self {
         let mut coord_addresses = BiMap::new();
         for (id, addr) in ring_nodes.iter() {
@@ -90,9 +217,11 @@ impl CoordinatorManager {
         Self {
             id,
             my_socket_addr: my_coordinator_addr,
+            bind_addr: my_coordinator_addr,
             ring_nodes,
             coordinator_actual: None,
             coord_communicators: HashMap::new(),
+            transport,
             coord_addresses,
             //heartbeat_timestamps: HashMap::new(),
             logger: Logger::new("COORDINATOR_MANAGER", Color::BrightCyan),
@@ -104,34 +233,167 @@ impl CoordinatorManager {
             waiting_pong_timer: None,
             get_storage_updates_timer: None,
             waiting_for_leader: None,
+            outgoing_snapshots: HashMap::new(),
+            incoming_snapshot: None,
+            snapshot_entities: StorageEntity::ALL.to_vec(),
+            snapshot_stall_timer: None,
+            write_ack,
+            next_write_id: 0,
+            pending_writes: HashMap::new(),
+            elections: VecDeque::new(),
+            epoch: 0,
+            follower_progress: HashMap::new(),
+            failure_detector: None,
+            dead_coordinators: HashSet::new(),
+            election: ElectionKind::default().strategy(),
+            election_timer: None,
+            handoff: None,
+            awaited_leader: None,
+            pull_from: None,
+        }
+    }
+
+    /// Replaces the default ring election.
+    ///
+    /// ## Arguments
+    /// * `election` - The leader election algorithm.
+    pub fn with_election(mut self, election: ElectionKind) -> Self {
+        self.election = election.strategy();
+        self
+    }
+
+    /// Listens on `bind_addr` instead of the address the other coordinators reach this one
+    /// at.
+    ///
+    /// ## Arguments
+    /// * `bind_addr` - The address the coordinator listens on.
+    pub fn with_bind_addr(mut self, bind_addr: SocketAddr) -> Self {
+        self.bind_addr = bind_addr;
+        self
+    }
+
+    /// Remembers that `leader_id` became the leader of the current epoch, keeping the last
+    /// [`MAX_RECENT_ELECTIONS`] changes.
+    fn record_election(&mut self, leader_id: &str) {
+        if self
+            .elections
+            .back()
+            .is_some_and(|last| last.leader_id == leader_id && last.epoch == self.epoch)
+        {
+            return;
         }
+        if self.elections.len() == MAX_RECENT_ELECTIONS {
+            self.elections.pop_front();
+        }
+        self.elections
+            .push_back(ElectionRecord::now(leader_id, self.epoch));
+    }
+
+    /// Returns the epoch `leader_id` leads if it is elected now: the smallest one above every
+    /// epoch known that belongs to it.
+    ///
+    /// Each coordinator owns the epochs congruent to its position in the ring, so two
+    /// partitions electing different leaders can never issue the same epoch.
+    fn next_epoch(&self, leader_id: &str) -> u64 {
+        let mut ids: Vec<&String> = self.ring_nodes.keys().collect();
+        ids.sort();
+        let ring_size = ids.len().max(1) as u64;
+        let position = ids.iter().position(|id| *id == leader_id).unwrap_or(0) as u64;
+        (self.epoch / ring_size + 1) * ring_size + position
+    }
+
+    /// Makes this coordinator the leader of a new epoch and announces it to the ring.
+    fn proclaim_myself_leader(&mut self) {
+        self.epoch = self.next_epoch(&self.id);
+        self.coordinator_actual = Some(self.my_socket_addr);
+        self.broadcast_leader_is();
     }
 
     /// Starts a new leader election process among the ring nodes.
-    pub fn start_leader_election(&mut self) {
+    pub fn start_leader_election(&mut self, ctx: &mut Context<Self>) {
         self.election_in_progress = true;
-        let election = NetworkMessage::LeaderElection(LeaderElection {
-            initiator: self.id.clone(),
-            candidates: vec![self.id.clone()],
-        });
+        self.logger.info("Starting election...");
+        let view = self.election_view();
+        let actions = self.election.start(&view);
+        self.run_election_actions(actions, ctx);
+    }
 
-        if let Some(next) = self.find_next_in_ring() {
-            if let Err(err) = self.send_network_message(next, election) {
-                self.logger
-                    .error(format!("Failed to send message to {}: {}", next, err));
-            }
+    /// Returns what the election strategy needs to know of the ring.
+    fn election_view(&self) -> ElectionView {
+        let mut members: Vec<String> = self
+            .ring_nodes
+            .keys()
+            .filter(|id| {
+                **id == self.id
+                    || (!self.dead_coordinators.contains(*id)
+                        && self
+                            .coord_addresses
+                            .get_by_value(id)
+                            .is_some_and(|addr| self.coord_communicators.contains_key(addr)))
+            })
+            .cloned()
+            .collect();
+        members.sort();
+        ElectionView {
+            id: self.id.clone(),
+            members,
+            epoch: self.epoch,
+        }
+    }
 
-            self.logger
-                .info(format!("Starting election. Sent to {}", next));
-        } else {
-            self.logger.warn(
-                "There is no next node in the ring to start the election, I proclaim myself leader",
-            );
-            // Si no hay siguiente nodo, me autoproclamo líder
-            self.coordinator_actual = Some(self.my_socket_addr);
-            self.election_in_progress = false;
-            // Broadcast a todos los nodos que soy el líder
-            self.broadcast_leader_is();
+    /// Carries out the actions asked by the election strategy.
+    fn run_election_actions(&mut self, actions: Vec<ElectionAction>, ctx: &mut Context<Self>) {
+        for action in actions {
+            match action {
+                ElectionAction::Send { to, message } => {
+                    let Some(addr) = self.coord_addresses.get_by_value(&to).copied() else {
+                        self.logger
+                            .warn(format!("No address found for {} to send the election", to));
+                        continue;
+                    };
+                    if let Err(err) = self.send_network_message(addr, *message) {
+                        self.logger
+                            .error(format!("Failed to send election to {}: {}", to, err));
+                    }
+                }
+                ElectionAction::Elected { leader, epoch } => {
+                    self.election_in_progress = false;
+                    if let Some(handle) = self.election_timer.take() {
+                        ctx.cancel_future(handle);
+                    }
+                    self.election.leader_announced();
+                    self.epoch = self.epoch.max(epoch);
+                    self.epoch = self.next_epoch(&leader);
+                    self.logger.info(format!(
+                        "Election completed. New leader: {} (epoch {})",
+                        leader, self.epoch
+                    ));
+                    let leader_addr = if leader == self.id {
+                        Some(self.my_socket_addr)
+                    } else {
+                        self.coord_addresses.get_by_value(&leader).copied()
+                    };
+                    if let Some(leader_addr) = leader_addr {
+                        self.coordinator_actual = Some(leader_addr);
+                        self.broadcast_leader_is();
+                    } else {
+                        self.logger
+                            .warn(format!("No address found for new leader: {}", leader));
+                    }
+                }
+                ElectionAction::Timer(delay) => {
+                    if let Some(handle) = self.election_timer.take() {
+                        ctx.cancel_future(handle);
+                    }
+                    let handle = ctx.run_later(delay, |act, ctx| {
+                        act.election_timer = None;
+                        let view = act.election_view();
+                        let actions = act.election.timeout(&view);
+                        act.run_election_actions(actions, ctx);
+                    });
+                    self.election_timer = Some(handle);
+                }
+            }
         }
     }
 
@@ -155,30 +417,34 @@ impl CoordinatorManager {
                 return;
             }
 
-            // ¡Calcular el nodo anterior en cada tick!
-            let previous_node_addr_opt = act.find_previous_in_ring();
+            // ¡Calcular el nodo anterior en cada tick! Si vamos a ser líderes, leemos del líder
+            let previous_node_addr_opt = act
+                .pull_from
+                .filter(|addr| act.coord_communicators.contains_key(addr))
+                .or_else(|| act.find_previous_in_ring());
             if previous_node_addr_opt.is_none() {
                 return;
             }
             let previous_node_addr = previous_node_addr_opt.unwrap();
 
-            // Enviar GetMinLogIndex al storage y esperar la respuesta
+            // Pedir los índices del log al storage y esperar la respuesta
             storage_addr
-                .send(GetMinLogIndex)
+                .send(GetCompactionStatus)
                 .into_actor(act)
                 .then(move |res, act, _ctx| {
                     match res {
-                        Ok(min_log_index) => {
+                        Ok(status) => {
                             act.logger.info(format!(
                                 "Received minLogIndex from storage: {}",
-                                min_log_index
+                                status.min_log_index
                             ));
                             if let Err(e) = act.send_network_message(
                                 previous_node_addr,
                                 NetworkMessage::RequestNewStorageUpdates(
                                     RequestNewStorageUpdates {
                                         coordinator_id: act.id.clone(),
-                                        start_index: min_log_index,
+                                        start_index: status.min_log_index,
+                                        applied_index: Some(status.next_log_id.saturating_sub(1)),
                                     },
                                 ),
                             ) {
@@ -199,41 +465,129 @@ impl CoordinatorManager {
         self.get_storage_updates_timer = Some(handler);
     }
 
-    /// Finds the next node in the ring for message passing.
-    fn find_next_in_ring(&self) -> Option<SocketAddr> {
-        // Obtener claves de comunicadores conectados (sin incluirme)
-        let mut nodes_ids = self.ring_nodes.keys().cloned().collect::<Vec<_>>();
-        nodes_ids.sort();
+    /// Starts the periodic compaction of the storage log.
+    ///
+    /// Entries are dropped once every coordinator that requested updates from this one in
+    /// the last [`LOG_COMPACTION_FOLLOWER_TIMEOUT`] applied them. A coordinator silent for
+    /// longer does not hold the log back: this one sends it a snapshot when it returns.
+    fn start_log_compaction(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(LOG_COMPACTION_INTERVAL, |act, _ctx| {
+            if let Some(up_to) = act.compaction_point(Instant::now()) {
+                act.storage.do_send(CompactLog { up_to });
+            }
+        });
+    }
+
+    /// Forgets the progress of the coordinators silent for longer than
+    /// [`LOG_COMPACTION_FOLLOWER_TIMEOUT`] and returns the first log index some of the rest
+    /// still needs.
+    ///
+    /// ## Returns
+    /// `None` if no coordinator reported its progress recently, so nothing is compacted.
+    fn compaction_point(&mut self, now: Instant) -> Option<u64> {
+        self.follower_progress.retain(|_, (_, reported_at)| {
+            now.duration_since(*reported_at) < LOG_COMPACTION_FOLLOWER_TIMEOUT
+        });
+        self.follower_progress
+            .values()
+            .map(|(applied_index, _)| applied_index + 1)
+            .min()
+    }
 
-        let nodes = nodes_ids
-            .iter()
-            .filter_map(|id| {
-                self.coord_addresses
-                    .get_by_value(id)
-                    .cloned()
-                    .and_then(|addr| {
-                        if self.coord_communicators.contains_key(&addr) || id == &self.id {
-                            Some(addr)
-                        } else {
-                            self.logger.warn(format!(
-                                "Address {} for ID {} not in coord_communicators",
-                                addr, id
-                            ));
-                            None
-                        }
-                    })
-            })
-            .collect::<Vec<_>>();
+    /// Starts comparing periodically the storage of this follower with the one of another
+    /// random follower, to repair divergences that the chain of the ring would not notice.
+    fn start_anti_entropy(&mut self, ctx: &mut Context<Self>) {
+        ctx.run_interval(ANTI_ENTROPY_INTERVAL, |act, ctx| {
+            act.send_digest_to_random_follower(ctx);
+        });
+    }
 
-        if nodes.len() < 2 {
-            // si soy el unico nodo o no hay nodos, no hay siguiente
-            return None;
+    /// Sends the digest of the local storage to another random follower.
+    fn send_digest_to_random_follower(&mut self, ctx: &mut Context<Self>) {
+        if self.election_in_progress
+            || self
+                .coordinator_actual
+                .is_none_or(|leader| leader == self.my_socket_addr)
+        {
+            return;
         }
+        let followers: Vec<SocketAddr> = self
+            .coord_communicators
+            .keys()
+            .filter(|addr| Some(**addr) != self.coordinator_actual && **addr != self.my_socket_addr)
+            .filter(|addr| {
+                self.coord_addresses
+                    .get_by_key(addr)
+                    .is_some_and(|id| !self.dead_coordinators.contains(id))
+            })
+            .copied()
+            .collect();
+        let Some(&follower) = followers.choose(&mut rand::thread_rng()) else {
+            return;
+        };
+        self.storage
+            .send(GetStorageDigest)
+            .into_actor(self)
+            .map(move |res, act, _ctx| match res {
+                Ok(digest) => {
+                    let message = NetworkMessage::AntiEntropyDigest(AntiEntropyDigest {
+                        coordinator_id: act.id.clone(),
+                        epoch: act.epoch,
+                        digest,
+                    });
+                    if let Err(e) = act.send_network_message(follower, message) {
+                        act.logger
+                            .warn(format!("Error sending AntiEntropyDigest: {}", e));
+                    }
+                }
+                Err(e) => {
+                    act.logger
+                        .warn(format!("Error obtaining the storage digest: {:?}", e));
+                }
+            })
+            .spawn(ctx);
+    }
 
-        // Buscar el siguiente en el anillo
-        let idx = nodes.iter().position(|x| *x == self.my_socket_addr)?;
-        let next = nodes.get((idx + 1) % nodes.len())?;
-        Some(*next)
+    /// Sends to `coordinator_id` the local entries of the `entities` kinds, asking for its own
+    /// if `reply`.
+    fn send_repair(
+        &mut self,
+        coordinator_id: String,
+        entities: Vec<StorageEntity>,
+        reply: bool,
+        ctx: &mut Context<Self>,
+    ) {
+        let Some(remote_addr) = self.coord_addresses.get_by_value(&coordinator_id).cloned() else {
+            self.logger.warn(format!(
+                "No address found for coordinator: {}",
+                coordinator_id
+            ));
+            return;
+        };
+        // Se pide después de aplicar lo recibido: el storage atiende los mensajes en orden
+        self.storage
+            .send(GetAllStorage)
+            .into_actor(self)
+            .map(move |res, act, _ctx| match res {
+                Ok(snapshot) => {
+                    let message = NetworkMessage::AntiEntropyRepair(AntiEntropyRepair {
+                        coordinator_id: act.id.clone(),
+                        epoch: act.epoch,
+                        snapshot: Box::new(snapshot.subset(&entities)),
+                        entities,
+                        reply,
+                    });
+                    if let Err(e) = act.send_network_message(remote_addr, message) {
+                        act.logger
+                            .warn(format!("Error sending AntiEntropyRepair: {}", e));
+                    }
+                }
+                Err(e) => {
+                    act.logger
+                        .warn(format!("Error obtaining storage snapshot: {:?}", e));
+                }
+            })
+            .spawn(ctx);
     }
 
     /// Finds the previous node in the ring for storage updates.
@@ -249,6 +603,9 @@ impl CoordinatorManager {
         for offset in 1..=ordered_ids.len() {
             let idx = (my_index + ordered_ids.len() - offset) % ordered_ids.len();
             let prev_id = &ordered_ids[idx];
+            if self.dead_coordinators.contains(prev_id) {
+                continue;
+            }
 
             // Obtener su SocketAddr
             if let Some(addr) = self.coord_addresses.get_by_value(prev_id) {
@@ -296,12 +653,12 @@ impl CoordinatorManager {
                     act.coord_communicators.remove(&leader);
                     act.coord_addresses.remove_by_key(&leader);
 
-                    act.start_leader_election();
+                    act.start_leader_election(ctx);
                 } else {
                     let local_addr = act
                         .coord_communicators
                         .get(&leader)
-                        .map(|c| c.local_address)
+                        .map(|c| c.local_address())
                         .unwrap_or(act.my_socket_addr);
 
                     let result = act.send_network_message(
@@ -334,7 +691,7 @@ impl CoordinatorManager {
                             //act.heartbeat_timestamps.remove(&leader);
 
                             act.pong_pending = false;
-                            act.start_leader_election();
+                            act.start_leader_election(ctx);
                         }
                     }
                 }
@@ -342,7 +699,7 @@ impl CoordinatorManager {
                 act.logger
                     .info("No current leader known, starting election...");
                 act.election_in_progress = true;
-                act.start_leader_election();
+                act.start_leader_election(ctx);
             }
         });
     }
@@ -354,29 +711,18 @@ impl CoordinatorManager {
         message: NetworkMessage,
     ) -> Result<(), String> {
         if let Some(communicator) = self.coord_communicators.get(&target) {
-            if let Some(sender) = &communicator.sender {
-                self.logger
-                    .info(format!("Sending message to {}: {:?}", target, message));
-                match sender.try_send(message) {
-                    Ok(_) => Ok(()),
-                    Err(e) => {
-                        let err_msg = format!("Failed to send to {}: {:?}", target, e);
-
-                        // Eliminar nodo por fallo de envío
-                        self.coord_communicators.remove(&target);
-                        self.coord_addresses.remove_by_key(&target);
-                        Err(err_msg)
-                    }
+            self.logger
+                .info(format!("Sending message to {}: {:?}", target, message));
+            match communicator.send(message) {
+                Ok(_) => Ok(()),
+                Err(err_msg) => {
+                    self.logger.error(&err_msg);
+
+                    // Eliminar nodo por fallo de envío
+                    self.coord_communicators.remove(&target);
+                    self.coord_addresses.remove_by_key(&target);
+                    Err(err_msg)
                 }
-            } else {
-                let err_msg = format!("Sender not initialized in communicator for {}", target);
-                self.logger.error(&err_msg);
-
-                // También lo removemos, por estar mal configurado
-                self.coord_communicators.remove(&target);
-                self.coord_addresses.remove_by_key(&target);
-
-                Err(err_msg)
             }
         } else {
             let err_msg = format!("No communicator found for {}", target);
@@ -391,11 +737,11 @@ impl CoordinatorManager {
     /// Broadcasts a [`NetworkMessage`] to all connected coordinator nodes.
     fn broadcast_network_message(&mut self, message: NetworkMessage) {
         for addr in self.coord_communicators.keys().copied().collect::<Vec<_>>() {
-            if addr != self.my_socket_addr {
-                if let Err(err) = self.send_network_message(addr, message.clone()) {
-                    self.logger
-                        .error(format!("Failed to send message to {}: {}", addr, err));
-                }
+            if addr != self.my_socket_addr
+                && let Err(err) = self.send_network_message(addr, message.clone())
+            {
+                self.logger
+                    .error(format!("Failed to send message to {}: {}", addr, err));
             }
         }
     }
@@ -403,14 +749,19 @@ impl CoordinatorManager {
     /// Broadcasts the current leader's identity to all nodes.
     fn broadcast_leader_is(&mut self) {
         if let Some(leader) = self.coordinator_actual {
-            if let Some(leader_id) = self.coord_addresses.get_by_key(&leader) {
+            if let Some(leader_id) = self.coord_addresses.get_by_key(&leader).cloned() {
+                self.record_election(&leader_id);
                 let message = NetworkMessage::LeaderIdIs(LeaderIdIs {
-                    leader_id: leader_id.to_string(),
+                    leader_id: leader_id.clone(),
+                    epoch: self.epoch,
                 });
-                self.logger
-                    .info(format!("Broadcasting new leader: {}", leader));
+                self.logger.info(format!(
+                    "Broadcasting new leader: {} (epoch {})",
+                    leader, self.epoch
+                ));
                 self.coordinator_addr.do_send(LeaderIdIs {
-                    leader_id: leader_id.to_string(),
+                    leader_id,
+                    epoch: self.epoch,
                 });
                 self.broadcast_network_message(message);
             } else {
@@ -437,7 +788,7 @@ impl CoordinatorManager {
             }
 
             if let Some(communicator) = self.coord_communicators.get(&addr) {
-                let local_addr = communicator.local_address;
+                let local_addr = communicator.local_address();
                 let message = NetworkMessage::WhoIsLeader(WhoIsLeader {
                     origin_addr: local_addr,
                     user_id: self.id.clone(),
@@ -469,47 +820,22 @@ impl CoordinatorManager {
 
     /// Asks all nodes for the current leader and waits for a response.
     fn ask_for_leader(&mut self, ctx: &mut Context<Self>) {
-        let id = self.id.clone();
         match self.broadcast_who_is_leader() {
             Ok(_) => {
                 // Esperamos X segundos para ver si alguien responde
                 let handler =
-                    ctx.run_later(TIMEOUT_LEADER_RESPONSE, move |actor: &mut Self, _ctx| {
+                    ctx.run_later(TIMEOUT_LEADER_RESPONSE, move |actor: &mut Self, ctx| {
                         if actor.coordinator_actual.is_none() {
                             actor.logger.info(
                                 "Asked all nodes for leader. No responses. Becoming leader...",
                             );
-                            actor.coordinator_actual = Some(actor.my_socket_addr);
-                            //actor.coordinator_addr.do_send(LeaderIdIs { leader_id: id });
-                            actor.broadcast_leader_is();
+                            actor.proclaim_myself_leader();
                         } else {
                             actor.logger.info(format!(
                                 "Leader response received before timeout: {:?}",
                                 actor.coordinator_actual
                             ));
-                            // Nos conectamos por primera vez al lider y solicitamos todo el Storage
-                            if let Some(addr) = actor.coordinator_actual {
-                                // logea que enviaste
-                                actor.logger.info(format!(
-                                    "Requesting all storage from leader at {}",
-                                    addr
-                                ));
-                                if let Err(e) = actor.send_network_message(
-                                    addr,
-                                    NetworkMessage::RequestAllStorage(RequestAllStorage {
-                                        coordinator_id: id.clone(),
-                                    }),
-                                ) {
-                                    actor.logger.warn(format!(
-                                        "Error al enviar RequestAllStorage al líder: {}",
-                                        e
-                                    ));
-                                }
-                            } else {
-                                actor
-                                    .logger
-                                    .warn("No coordinator address found to request storage.");
-                            }
+                            actor.catch_up_with_leader(ctx);
                         }
                     });
                 self.waiting_for_leader = Some(handler);
@@ -520,8 +846,133 @@ impl CoordinatorManager {
                     reason
                 ));
                 // Nos autoproclamamos líder directamente
-                self.coordinator_actual = Some(self.my_socket_addr);
-                self.broadcast_leader_is();
+                self.proclaim_myself_leader();
+            }
+        }
+    }
+
+    /// Catches up with the leader after joining the ring.
+    ///
+    /// A storage rebuilt from its persisted log only needs the updates it missed, which the
+    /// periodic storage updates bring; otherwise the whole storage is requested.
+    fn catch_up_with_leader(&mut self, ctx: &mut Context<Self>) {
+        self.storage
+            .send(IsRestoredFromLog)
+            .into_actor(self)
+            .map(|restored, act, ctx| {
+                if let Ok(true) = restored {
+                    act.logger
+                        .info("Storage restored from its log, catching up with updates");
+                } else {
+                    // Nos conectamos por primera vez al lider y solicitamos todo el Storage
+                    act.incoming_snapshot = None;
+                    act.snapshot_entities = StorageEntity::ALL.to_vec();
+                    act.request_storage_snapshot(ctx);
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Requests the storage snapshot from the leader, resuming the transfer in progress if any.
+    /// Only the kinds of entities in `snapshot_entities` are asked for.
+    fn request_storage_snapshot(&mut self, ctx: &mut Context<Self>) {
+        let Some(leader) = self.coordinator_actual else {
+            self.logger
+                .warn("No coordinator address found to request storage.");
+            return;
+        };
+        if leader == self.my_socket_addr {
+            // Si pasamos a ser líder, nuestro storage es la referencia
+            self.incoming_snapshot = None;
+            return;
+        }
+        let (snapshot_id, from_seq) = match &self.incoming_snapshot {
+            Some(incoming) => (Some(incoming.snapshot_id), incoming.chunks.len() as u32),
+            None => (None, 0),
+        };
+        let request = if StorageEntity::ALL
+            .iter()
+            .all(|entity| self.snapshot_entities.contains(entity))
+        {
+            self.logger.info(format!(
+                "Requesting all storage from leader at {} (from chunk {})",
+                leader, from_seq
+            ));
+            NetworkMessage::RequestAllStorage(RequestAllStorage {
+                coordinator_id: self.id.clone(),
+                snapshot_id,
+                from_seq,
+            })
+        } else {
+            self.logger.info(format!(
+                "Requesting {:?} from leader at {} (from chunk {})",
+                self.snapshot_entities, leader, from_seq
+            ));
+            NetworkMessage::RequestStorageSubset(RequestStorageSubset {
+                coordinator_id: self.id.clone(),
+                entities: self.snapshot_entities.clone(),
+                snapshot_id,
+                from_seq,
+            })
+        };
+        if let Err(e) = self.send_network_message(leader, request) {
+            self.logger
+                .warn(format!("Error al pedir el storage al líder: {}", e));
+        }
+        self.arm_snapshot_timer(ctx);
+    }
+
+    /// (Re)starts the timer that re-requests the snapshot if no chunk arrives in time.
+    fn arm_snapshot_timer(&mut self, ctx: &mut Context<Self>) {
+        if let Some(handle) = self.snapshot_stall_timer.take() {
+            ctx.cancel_future(handle);
+        }
+        let handle = ctx.run_later(SNAPSHOT_CHUNK_TIMEOUT, |act, ctx| {
+            act.snapshot_stall_timer = None;
+            act.logger
+                .warn("Storage snapshot transfer stalled, requesting it again");
+            act.request_storage_snapshot(ctx);
+        });
+        self.snapshot_stall_timer = Some(handle);
+    }
+
+    /// Sends the chunks of the snapshot cached for `coordinator_id`, starting at `from_seq`.
+    fn send_snapshot_chunks(
+        &mut self,
+        remote_addr: SocketAddr,
+        coordinator_id: &str,
+        from_seq: u32,
+    ) {
+        let Some((snapshot_id, chunks)) = self.outgoing_snapshots.get(coordinator_id) else {
+            return;
+        };
+        let snapshot_id = *snapshot_id;
+        let total = chunks.len() as u32;
+        let pending: Vec<SnapshotChunk> = chunks
+            .iter()
+            .enumerate()
+            .skip(from_seq as usize)
+            .map(|(seq, payload)| SnapshotChunk {
+                snapshot_id,
+                seq: seq as u32,
+                total,
+                payload: payload.clone(),
+                coordinator_id: self.id.clone(),
+                epoch: self.epoch,
+            })
+            .collect();
+
+        self.logger.info(format!(
+            "Sending snapshot {} to {}: chunks {}..{}",
+            snapshot_id, coordinator_id, from_seq, total
+        ));
+        for chunk in pending {
+            if let Err(e) =
+                self.send_network_message(remote_addr, NetworkMessage::SnapshotChunk(chunk))
+            {
+                self.logger
+                    .error(format!("Error sending SnapshotChunk: {}", e));
+                return;
             }
         }
     }
@@ -535,23 +986,34 @@ impl CoordinatorManager {
         // Insertar la dirección del socket en el mapa de direcciones de coordinadores
         self.coord_addresses
             .insert(msg.origin_addr, msg.user_id.clone());
+        // Con gRPC no hay aceptador: si el nodo volvió, se abre de nuevo el transporte
+        if !self.coord_communicators.contains_key(&msg.origin_addr)
+            && self
+                .ring_nodes
+                .values()
+                .any(|addr| *addr == msg.origin_addr)
+            && let Some(transport) =
+                open_transport(self.transport, self.my_socket_addr, msg.origin_addr)
+        {
+            self.coord_communicators.insert(msg.origin_addr, transport);
+        }
         // Si ya tengo un coordinador actual, responder con su ID
         if let Some(leader) = self.coordinator_actual {
             if let Some(leader_id) = self.coord_addresses.get_by_key(&leader) {
                 let response = NetworkMessage::LeaderIdIs(LeaderIdIs {
                     leader_id: leader_id.to_string(),
+                    epoch: self.epoch,
                 });
                 if let Some(registered_remote_addr) =
                     self.coord_addresses.get_by_value(&msg.user_id)
                 {
                     if let Some(communicator) = self.coord_communicators.get(registered_remote_addr)
                     {
-                        if let Some(sender) = &communicator.sender {
-                            sender.do_send(response);
-                            self.logger
-                                .info(format!("Sent LeaderIdIs to {}", msg.origin_addr));
-                        } else {
-                            self.logger.warn("Sender not initialized");
+                        match communicator.send(response) {
+                            Ok(_) => self
+                                .logger
+                                .info(format!("Sent LeaderIdIs to {}", msg.origin_addr)),
+                            Err(e) => self.logger.warn(e),
                         }
                     } else {
                         self.logger
@@ -573,9 +1035,37 @@ impl CoordinatorManager {
     }
 
     /// Handles an incoming `LeaderIdIs` message.
-    fn handle_leader_is(&mut self, msg: LeaderIdIs, _ctx: &mut Context<Self>) {
+    ///
+    /// Announcements of an older epoch are ignored. If the announced leader replaces this
+    /// coordinator, it steps down.
+    fn handle_leader_is(&mut self, msg: LeaderIdIs, ctx: &mut Context<Self>) {
+        if msg.epoch < self.epoch {
+            self.logger.warn(format!(
+                "Ignoring LeaderIdIs for {} of stale epoch {} (current epoch {})",
+                msg.leader_id, msg.epoch, self.epoch
+            ));
+            self.fence_stale_coordinator(&msg.leader_id);
+            return;
+        }
+        if msg.epoch > self.epoch {
+            // El traspaso terminó (o lo ganó otro): volvemos a leer del anterior del anillo
+            self.pull_from = None;
+        }
+        self.epoch = msg.epoch;
         self.election_in_progress = false;
         self.waiting_for_leader = None;
+        if let Some(handle) = self.election_timer.take() {
+            ctx.cancel_future(handle);
+        }
+        self.election.leader_announced();
+        if self
+            .awaited_leader
+            .as_ref()
+            .is_some_and(|(target, _)| *target == msg.leader_id)
+            && let Some((target, responder)) = self.awaited_leader.take()
+        {
+            let _ = responder.send(self.ring_nodes.get(&target).copied());
+        }
         if self.coordinator_actual.is_none() {
             if let Some(leader_addr) = self.coord_addresses.get_by_value(&msg.leader_id) {
                 self.logger.info(format!(
@@ -583,6 +1073,7 @@ impl CoordinatorManager {
                     leader_addr, msg.leader_id
                 ));
                 self.coordinator_actual = Some(*leader_addr);
+                self.record_election(&msg.leader_id);
             } else {
                 self.logger.info(format!(
                     "Received LeaderIdIs from {}, but no address found for it",
@@ -592,20 +1083,229 @@ impl CoordinatorManager {
 
             self.coordinator_addr.do_send(LeaderIdIs {
                 leader_id: msg.leader_id.clone(),
+                epoch: msg.epoch,
             });
-            self.logger
-                .info(format!("Updated local coordinator to {}", msg.leader_id));
+            self.logger.info(format!(
+                "Updated local coordinator to {} (epoch {})",
+                msg.leader_id, msg.epoch
+            ));
         } else if let Some(registered_remote_addr) =
             self.coord_addresses.get_by_value(&msg.leader_id)
+            && self.coordinator_actual != Some(*registered_remote_addr)
+        {
+            self.logger.warn(format!(
+                "I overwrote my coordinator because LeaderIdIs arrived. Local: {:?}, Received: {} (epoch {})",
+                self.coordinator_actual, *registered_remote_addr, msg.epoch
+            ));
+            let was_leader = self.coordinator_actual == Some(self.my_socket_addr);
+            self.coordinator_actual = Some(*registered_remote_addr);
+            self.record_election(&msg.leader_id);
+            self.coordinator_addr.do_send(LeaderIdIs {
+                leader_id: msg.leader_id.clone(),
+                epoch: msg.epoch,
+            });
+            if was_leader {
+                self.step_down(ctx);
+            }
+        }
+    }
+
+    /// Stops acting as the leader after another coordinator was elected in a newer epoch.
+    ///
+    /// Writes still waiting for a quorum fail, and the storage is synchronized again from
+    /// the snapshot of the new leader.
+    fn step_down(&mut self, ctx: &mut Context<Self>) {
+        self.logger.warn(format!(
+            "Stepping down, {:?} is the leader of epoch {}",
+            self.coordinator_actual, self.epoch
+        ));
+        for (_, pending) in self.pending_writes.drain() {
+            let _ = pending.responder.send(false);
+        }
+        self.incoming_snapshot = None;
+        self.snapshot_entities = StorageEntity::ALL.to_vec();
+        self.request_storage_snapshot(ctx);
+    }
+
+    /// Checks the epoch of a storage message sent by `coordinator_id`.
+    ///
+    /// ## Returns
+    /// `false` if the message belongs to an older epoch and must be rejected. If it belongs
+    /// to a newer one, this coordinator missed an election and asks the sender who the
+    /// leader is.
+    fn check_epoch(&mut self, coordinator_id: &str, epoch: u64) -> bool {
+        if epoch < self.epoch {
+            self.logger.warn(format!(
+                "Rejecting storage message from {} of stale epoch {} (current epoch {})",
+                coordinator_id, epoch, self.epoch
+            ));
+            self.fence_stale_coordinator(coordinator_id);
+            return false;
+        }
+        if epoch > self.epoch
+            && let Some(remote_addr) = self
+                .coord_addresses
+                .get_by_value(&coordinator_id.to_string())
+                .cloned()
+            && let Some(communicator) = self.coord_communicators.get(&remote_addr)
         {
-            if self.coordinator_actual != Some(*registered_remote_addr) {
+            self.logger.warn(format!(
+                "{} knows a newer epoch ({} > {}), asking it for the leader",
+                coordinator_id, epoch, self.epoch
+            ));
+            let message = NetworkMessage::WhoIsLeader(WhoIsLeader {
+                origin_addr: communicator.local_address(),
+                user_id: self.id.clone(),
+            });
+            if let Err(e) = self.send_network_message(remote_addr, message) {
                 self.logger.warn(format!(
-                    "I overwrote my coordinator because LeaderIdIs arrived. Local: {:?}, Received: {}",
-                    self.coordinator_actual, *registered_remote_addr
+                    "Error sending WhoIsLeader to {}: {}",
+                    remote_addr, e
                 ));
-                self.coordinator_actual = Some(*registered_remote_addr);
             }
         }
+        true
+    }
+
+    /// Returns whether `coordinator_id` is another coordinator that is connected and alive,
+    /// which the leadership can be handed off to.
+    fn can_take_over(&self, coordinator_id: &str) -> bool {
+        coordinator_id != self.id
+            && !self.dead_coordinators.contains(coordinator_id)
+            && self
+                .coord_addresses
+                .get_by_value(&coordinator_id.to_string())
+                .is_some_and(|addr| self.coord_communicators.contains_key(addr))
+    }
+
+    /// Returns the connected follower that applied most of the log of this coordinator, the
+    /// one a draining leader hands the leadership off to.
+    fn handoff_successor(&self) -> Option<String> {
+        most_up_to_date(&self.follower_progress, |id| self.can_take_over(id))
+    }
+
+    /// Asks the leader to hand the leadership off to `target`, and answers through
+    /// `responder` once `target` is announced as the leader, or with `None` if it is not
+    /// after [`LEADERSHIP_HANDOFF_TIMEOUT`].
+    fn request_transfer_from_leader(
+        &mut self,
+        target: String,
+        responder: oneshot::Sender<Option<SocketAddr>>,
+        ctx: &mut Context<Self>,
+    ) {
+        let Some(leader) = self.coordinator_actual else {
+            self.logger
+                .warn("No leader known to transfer the leadership from");
+            let _ = responder.send(None);
+            return;
+        };
+        let Some(origin_addr) = self
+            .coord_communicators
+            .get(&leader)
+            .map(|communicator| communicator.local_address())
+        else {
+            self.logger
+                .warn(format!("No connection with the leader at {}", leader));
+            let _ = responder.send(None);
+            return;
+        };
+        self.logger.info(format!(
+            "Asking the leader at {} to transfer the leadership to {}",
+            leader, target
+        ));
+        if let Err(e) = self.send_network_message(
+            leader,
+            NetworkMessage::TransferLeadership(TransferLeadership {
+                origin_addr,
+                target: target.clone(),
+            }),
+        ) {
+            self.logger
+                .warn(format!("Error sending TransferLeadership: {}", e));
+            let _ = responder.send(None);
+            return;
+        }
+        self.awaited_leader = Some((target, responder));
+        // El líder espera a su sucesor, y después se propaga el anuncio
+        ctx.run_later(
+            LEADERSHIP_HANDOFF_TIMEOUT + TIMEOUT_LEADER_RESPONSE,
+            |act, _ctx| {
+                if let Some((target, responder)) = act.awaited_leader.take() {
+                    act.logger.warn(format!(
+                        "{} was not announced as the leader in time",
+                        target
+                    ));
+                    let _ = responder.send(None);
+                }
+            },
+        );
+    }
+
+    /// Completes the handoff in progress once its successor applied the whole log: the
+    /// successor becomes the leader of a new epoch and the writes still waiting for a quorum
+    /// fail.
+    fn check_handoff(&mut self) {
+        let Some(handoff) = &self.handoff else {
+            return;
+        };
+        let caught_up = self
+            .follower_progress
+            .get(&handoff.successor)
+            .is_some_and(|(applied_index, _)| *applied_index >= handoff.last_index);
+        if !caught_up {
+            return;
+        }
+        let Some(handoff) = self.handoff.take() else {
+            return;
+        };
+        let successor_addr = self
+            .coord_addresses
+            .get_by_value(&handoff.successor)
+            .copied();
+        self.logger.warn(format!(
+            "Handing the leadership off to {}, which applied the log up to {}",
+            handoff.successor, handoff.last_index
+        ));
+        self.epoch = self.next_epoch(&handoff.successor);
+        self.coordinator_actual = successor_addr;
+        self.broadcast_leader_is();
+        for (_, pending) in self.pending_writes.drain() {
+            let _ = pending.responder.send(false);
+        }
+        let _ = handoff
+            .responder
+            .send(self.ring_nodes.get(&handoff.successor).copied());
+    }
+
+    /// Tells `coordinator_id`, which is behind on the leader epoch, who the current leader
+    /// is, so that it steps down if it still acts as the leader.
+    fn fence_stale_coordinator(&mut self, coordinator_id: &str) {
+        let Some(leader_id) = self
+            .coordinator_actual
+            .and_then(|leader| self.coord_addresses.get_by_key(&leader).cloned())
+        else {
+            return;
+        };
+        let Some(remote_addr) = self
+            .coord_addresses
+            .get_by_value(&coordinator_id.to_string())
+            .cloned()
+        else {
+            return;
+        };
+        if remote_addr == self.my_socket_addr {
+            return;
+        }
+        let message = NetworkMessage::LeaderIdIs(LeaderIdIs {
+            leader_id,
+            epoch: self.epoch,
+        });
+        if let Err(e) = self.send_network_message(remote_addr, message) {
+            self.logger.warn(format!(
+                "Error sending LeaderIdIs to stale coordinator {}: {}",
+                coordinator_id, e
+            ));
+        }
     }
 }
 
@@ -633,6 +1333,20 @@ impl Handler<StartRunning> for CoordinatorManager {
         self.start_heartbeat_checker(ctx);
         // Iniciar el chequeo de actualizaciones de Storage
         self.start_storage_updates_checker(ctx);
+        // Iniciar la compactación periódica del log
+        self.start_log_compaction(ctx);
+        // Iniciar la comparación del storage con otros seguidores
+        self.start_anti_entropy(ctx);
+        // Iniciar el detector de fallas de todo el anillo
+        if gossip::is_enabled() {
+            let detector = FailureDetector::new(
+                self.id.clone(),
+                self.bind_addr,
+                &self.ring_nodes,
+                ctx.address(),
+            );
+            self.failure_detector = Some(detector.start());
+        }
     }
 }
 
@@ -645,7 +1359,25 @@ impl Handler<RequestAllStorage> for CoordinatorManager {
             "Received RequestAllStorage from {}",
             msg.coordinator_id
         ));
-        let id = msg.coordinator_id.clone();
+        self.handle(
+            RequestStorageSubset {
+                coordinator_id: msg.coordinator_id,
+                entities: StorageEntity::ALL.to_vec(),
+                snapshot_id: msg.snapshot_id,
+                from_seq: msg.from_seq,
+            },
+            ctx,
+        );
+    }
+}
+
+/// Handles requests for some kinds of entities of the storage from another node, sent as a
+/// snapshot in chunks like a full one.
+impl Handler<RequestStorageSubset> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: RequestStorageSubset, ctx: &mut Context<Self>) {
+        let id = msg.coordinator_id.clone();
 
         if let Some(remote_addr) = self.coord_addresses.get_by_value(&id) {
             if !self.coord_communicators.contains_key(remote_addr) {
@@ -668,21 +1400,42 @@ impl Handler<RequestAllStorage> for CoordinatorManager {
             .cloned()
             .unwrap();
 
+        // Si el pedido reanuda una transferencia que todavía tenemos, seguimos desde ahí
+        if let Some(snapshot_id) = msg.snapshot_id
+            && let Some((cached_id, _)) = self.outgoing_snapshots.get(&id)
+            && *cached_id == snapshot_id
+        {
+            self.send_snapshot_chunks(remote_addr, &id, msg.from_seq);
+            return;
+        }
+
         storage_addr
             .send(GetAllStorage)
             .into_actor(self)
-            .then(move |res, act, _ctx| {
+            .then(move |res, act, ctx| {
                 match res {
-                    Ok(snapshot) => {
-                        act.send_network_message(
-                            remote_addr,
-                            NetworkMessage::StorageSnapshot(StorageSnapshot { snapshot }),
-                        )
-                        .unwrap_or_else(|e| {
+                    Ok(snapshot) => match serde_json::to_string(&snapshot.subset(&msg.entities)) {
+                        Ok(serialized) => {
+                            let snapshot_id = rand::random::<u64>();
+                            act.outgoing_snapshots
+                                .insert(id.clone(), (snapshot_id, split_in_chunks(&serialized)));
+                            act.send_snapshot_chunks(remote_addr, &id, 0);
+                            // Se descarta pasado un tiempo si nadie la reanudó
+                            ctx.run_later(SNAPSHOT_CACHE_TTL, move |act, _ctx| {
+                                if act
+                                    .outgoing_snapshots
+                                    .get(&id)
+                                    .is_some_and(|(cached_id, _)| *cached_id == snapshot_id)
+                                {
+                                    act.outgoing_snapshots.remove(&id);
+                                }
+                            });
+                        }
+                        Err(e) => {
                             act.logger
-                                .error(format!("Error sending StorageSnapshot: {}", e))
-                        });
-                    }
+                                .error(format!("Error serializing storage snapshot: {}", e));
+                        }
+                    },
                     Err(e) => {
                         act.logger
                             .warn(format!("Error obtaining storage snapshot: {:?}", e));
@@ -719,7 +1472,7 @@ impl Handler<LeaderIdIs> for CoordinatorManager {
 impl Handler<CheckPongTimeout> for CoordinatorManager {
     type Result = ();
 
-    fn handle(&mut self, _msg: CheckPongTimeout, _ctx: &mut Self::Context) {
+    fn handle(&mut self, _msg: CheckPongTimeout, ctx: &mut Self::Context) {
         if self.pong_pending && self.pong_leader_addr == self.coordinator_actual {
             self.logger
                 .warn("Timeout waiting for Pong. Starting election...");
@@ -732,7 +1485,7 @@ impl Handler<CheckPongTimeout> for CoordinatorManager {
             }
 
             self.coordinator_actual = None;
-            self.start_leader_election();
+            self.start_leader_election(ctx);
         } else {
             //self.logger.info("⚠️ CheckPongTimeout ignorado: ya no esperamos Pong del coordinador actual.");
         }
@@ -769,47 +1522,68 @@ impl Handler<Pong> for CoordinatorManager {
     }
 }
 
+/// Handles changes in the liveness of a coordinator reported by the failure detector.
+///
+/// A dead coordinator is skipped when passing the election and when asking for storage
+/// updates, and if it was the leader a new election starts without waiting for the
+/// heartbeat to time out.
+impl Handler<MemberStateChanged> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: MemberStateChanged, ctx: &mut Self::Context) {
+        if msg.state != MemberState::Dead {
+            self.dead_coordinators.remove(&msg.coordinator_id);
+            return;
+        }
+        self.logger.warn(format!(
+            "Failure detector declared {} dead",
+            msg.coordinator_id
+        ));
+        self.dead_coordinators.insert(msg.coordinator_id.clone());
+
+        let leader_id = self
+            .coordinator_actual
+            .and_then(|leader| self.coord_addresses.get_by_key(&leader));
+        if leader_id != Some(&msg.coordinator_id) || self.election_in_progress {
+            return;
+        }
+        self.logger.warn("Leader down. Starting election...");
+        if let Some(handle) = self.waiting_pong_timer.take() {
+            ctx.cancel_future(handle);
+        }
+        self.pong_pending = false;
+        self.pong_leader_addr = None;
+        self.coordinator_actual = None;
+        self.election_in_progress = true;
+        self.start_leader_election(ctx);
+    }
+}
+
 /// Handles leader election messages from other nodes.
 impl Handler<LeaderElection> for CoordinatorManager {
     type Result = ();
 
-    fn handle(&mut self, msg: LeaderElection, _ctx: &mut Self::Context) {
-        let mut candidates = msg.candidates.clone();
-
-        if msg.initiator == self.id {
-            // Completó el ciclo
-            self.election_in_progress = false;
-            let new_leader = candidates.iter().min().unwrap().clone();
-            self.logger
-                .info(format!("Election completed. New leader: {}", new_leader));
+    fn handle(&mut self, msg: LeaderElection, ctx: &mut Self::Context) {
+        let view = self.election_view();
+        let actions = self.election.handle(ElectionMessage::Ring(msg), &view);
+        self.run_election_actions(actions, ctx);
+    }
+}
 
-            if let Some(leader_addr) = self.coord_addresses.get_by_value(&new_leader) {
-                self.coordinator_actual = Some(*leader_addr);
-                self.broadcast_leader_is();
-            } else {
-                self.logger
-                    .warn(format!("No address found for new leader: {}", new_leader));
-            }
-        } else {
-            // Sumarme como candidato
-            candidates.push(self.id.clone());
-            if let Some(next) = self.find_next_in_ring() {
-                if let Err(err) = self.send_network_message(
-                    next,
-                    NetworkMessage::LeaderElection(LeaderElection {
-                        initiator: msg.initiator,
-                        candidates,
-                    }),
-                ) {
-                    self.logger.error(format!(
-                        "Failed to send LeaderElection to {}: {}",
-                        next, err
-                    ));
-                }
+/// Handles bully election messages from other nodes.
+impl Handler<BullyElection> for CoordinatorManager {
+    type Result = ();
 
-                self.logger.info(format!("Passing election to {}", next));
-            }
+    fn handle(&mut self, msg: BullyElection, ctx: &mut Self::Context) {
+        // Un menor me desafía: me sumo a la elección
+        if let BullyElection::Election { from, .. } = &msg
+            && *from > self.id
+        {
+            self.election_in_progress = true;
         }
+        let view = self.election_view();
+        let actions = self.election.handle(ElectionMessage::Bully(msg), &view);
+        self.run_election_actions(actions, ctx);
     }
 }
 
@@ -818,6 +1592,9 @@ impl Handler<StorageUpdates> for CoordinatorManager {
     type Result = ();
 
     fn handle(&mut self, msg: StorageUpdates, _ctx: &mut Context<Self>) {
+        if !self.check_epoch(&msg.coordinator_id, msg.epoch) {
+            return;
+        }
         self.logger.info(format!(
             "Received {} storage updates, forwarding to storage...",
             msg.updates.len()
@@ -830,6 +1607,7 @@ impl Handler<StorageUpdates> for CoordinatorManager {
             is_leader: self.coordinator_actual == Some(self.my_socket_addr),
             updates: updates_vec,
         });
+        self.coordinator_addr.do_send(ReplicaSynced);
     }
 }
 
@@ -838,7 +1616,540 @@ impl Handler<StorageSnapshot> for CoordinatorManager {
     type Result = ();
 
     fn handle(&mut self, msg: StorageSnapshot, _ctx: &mut Context<Self>) {
+        if !self.check_epoch(&msg.coordinator_id, msg.epoch) {
+            return;
+        }
         self.storage.do_send(msg);
+        self.coordinator_addr.do_send(ReplicaSynced);
+    }
+}
+
+/// Handles user messages that a follower hands over to the leader.
+impl Handler<ForwardToLeader> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: ForwardToLeader, _ctx: &mut Context<Self>) {
+        match self.coordinator_actual {
+            Some(leader) if leader != self.my_socket_addr => {
+                if let Err(e) =
+                    self.send_network_message(leader, NetworkMessage::ForwardToLeader(msg))
+                {
+                    self.logger
+                        .warn(format!("Error forwarding message to the leader: {}", e));
+                }
+            }
+            _ => {
+                // Sin líder conocido (o si somos el líder) no hay a quién reenviarlo
+                self.logger.warn(format!(
+                    "Cannot forward message from {} to the leader: {:?}",
+                    msg.coordinator_id, self.coordinator_actual
+                ));
+            }
+        }
+    }
+}
+
+/// Handles messages that the leader hands over to a user connected to a follower.
+impl Handler<ForwardToUser> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: ForwardToUser, _ctx: &mut Context<Self>) {
+        let Some(follower) = self.ring_nodes.get(&msg.coordinator_id).copied() else {
+            self.logger.warn(format!(
+                "Cannot forward message to {}: unknown coordinator {}",
+                msg.user_id, msg.coordinator_id
+            ));
+            return;
+        };
+        if let Err(e) = self.send_network_message(follower, NetworkMessage::ForwardToUser(msg)) {
+            self.logger
+                .warn(format!("Error forwarding message to a follower: {}", e));
+        }
+    }
+}
+
+/// Handles requests to inject network faults on the connections with other coordinators.
+impl Handler<InjectFaults> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: InjectFaults, _ctx: &mut Context<Self>) {
+        for transport in self.coord_communicators.values() {
+            transport.set_faults(msg.config.clone());
+        }
+    }
+}
+
+/// Handles requests for the state of the ring, used by the dashboard.
+impl Handler<GetRingState> for CoordinatorManager {
+    type Result = MessageResult<GetRingState>;
+
+    fn handle(&mut self, _msg: GetRingState, _ctx: &mut Context<Self>) -> Self::Result {
+        let mut coordinators: Vec<CoordinatorStatus> =
+            self.ring_nodes
+                .iter()
+                .map(|(id, addr)| CoordinatorStatus {
+                    id: id.clone(),
+                    addr: *addr,
+                    // Las conexiones entrantes quedan registradas con la dirección remota
+                    connected: *id == self.id
+                        || (!self.dead_coordinators.contains(id)
+                            && self.coord_addresses.get_by_value(id).is_some_and(|remote| {
+                                self.coord_communicators.contains_key(remote)
+                            })),
+                })
+                .collect();
+        coordinators.sort_by(|a, b| a.id.cmp(&b.id));
+
+        MessageResult(RingState {
+            leader: self.coordinator_actual,
+            epoch: self.epoch,
+            coordinators,
+            elections: self.elections.iter().cloned().collect(),
+        })
+    }
+}
+
+/// Handles requests for the progress of the coordinators that pull the log from this one,
+/// used by the TUI.
+impl Handler<GetFollowerProgress> for CoordinatorManager {
+    type Result = MessageResult<GetFollowerProgress>;
+
+    fn handle(&mut self, _msg: GetFollowerProgress, _ctx: &mut Context<Self>) -> Self::Result {
+        let mut progress: Vec<FollowerProgress> = self
+            .follower_progress
+            .iter()
+            .map(|(id, (applied_index, reported_at))| FollowerProgress {
+                coordinator_id: id.clone(),
+                applied_index: *applied_index,
+                reported_ago: reported_at.elapsed(),
+            })
+            .collect();
+        progress.sort_by(|a, b| a.coordinator_id.cmp(&b.coordinator_id));
+        MessageResult(progress)
+    }
+}
+
+/// Handles the digest of another follower, answering with the local entries of the kinds in
+/// which both storages diverged.
+///
+/// Digests are only compared if both followers applied the log up to the same index:
+/// otherwise they differ just because one of them is behind.
+impl Handler<AntiEntropyDigest> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: AntiEntropyDigest, ctx: &mut Context<Self>) {
+        if !self.check_epoch(&msg.coordinator_id, msg.epoch)
+            || self.coordinator_actual == Some(self.my_socket_addr)
+        {
+            return;
+        }
+        self.storage
+            .send(GetStorageDigest)
+            .into_actor(self)
+            .map(move |res, act, ctx| {
+                let digest = match res {
+                    Ok(digest) => digest,
+                    Err(e) => {
+                        act.logger
+                            .warn(format!("Error obtaining the storage digest: {:?}", e));
+                        return;
+                    }
+                };
+                if digest.next_log_id != msg.digest.next_log_id {
+                    return;
+                }
+                let entities = digest.differing(&msg.digest);
+                if entities.is_empty() {
+                    return;
+                }
+                act.logger.warn(format!(
+                    "Storage diverged from {} in {:?}, exchanging their entries",
+                    msg.coordinator_id, entities
+                ));
+                act.send_repair(msg.coordinator_id, entities, true, ctx);
+            })
+            .spawn(ctx);
+    }
+}
+
+/// Handles the entries sent by a follower whose storage diverged from this one, merging them
+/// and answering with the local ones if asked to.
+impl Handler<AntiEntropyRepair> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: AntiEntropyRepair, ctx: &mut Context<Self>) {
+        if !self.check_epoch(&msg.coordinator_id, msg.epoch)
+            || self.coordinator_actual == Some(self.my_socket_addr)
+        {
+            return;
+        }
+        self.logger.info(format!(
+            "Merging {:?} from {}",
+            msg.entities, msg.coordinator_id
+        ));
+        self.storage.do_send(MergeSnapshot {
+            snapshot: msg.snapshot,
+        });
+        if msg.reply {
+            self.send_repair(msg.coordinator_id, msg.entities, false, ctx);
+        }
+    }
+}
+
+/// Handles requests to recover some kinds of entities from the leader.
+///
+/// A transfer already in progress is kept if it brings them; otherwise it is restarted asking
+/// also for them.
+impl Handler<ResyncStorage> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: ResyncStorage, ctx: &mut Context<Self>) {
+        if self.coordinator_actual == Some(self.my_socket_addr) {
+            self.logger
+                .info("The leader's storage is the reference, nothing to resync");
+            return;
+        }
+        if self.incoming_snapshot.is_none() {
+            self.snapshot_entities.clear();
+        } else if msg
+            .entities
+            .iter()
+            .all(|entity| self.snapshot_entities.contains(entity))
+        {
+            return;
+        }
+        for entity in msg.entities {
+            if !self.snapshot_entities.contains(&entity) {
+                self.snapshot_entities.push(entity);
+            }
+        }
+        self.incoming_snapshot = None;
+        self.request_storage_snapshot(ctx);
+    }
+}
+
+/// Handles requests to hand the leadership off, before draining the coordinator or to
+/// transfer it to a given coordinator.
+///
+/// The successor is asked to pull the log from the leader ([`PrepareLeadership`]), and the
+/// leader waits up to [`LEADERSHIP_HANDOFF_TIMEOUT`] for it to apply the whole log, so that no
+/// acknowledged write is lost with the leadership.
+impl Handler<HandOffLeadership> for CoordinatorManager {
+    type Result = ResponseActFuture<Self, Option<SocketAddr>>;
+
+    fn handle(&mut self, msg: HandOffLeadership, ctx: &mut Context<Self>) -> Self::Result {
+        if msg.successor.as_ref() == Some(&self.id) {
+            return Box::pin(fut::ready(Some(self.my_socket_addr)));
+        }
+        if self.coordinator_actual != Some(self.my_socket_addr) {
+            if let Some(target) = msg.successor {
+                let (tx, rx) = oneshot::channel();
+                self.request_transfer_from_leader(target, tx, ctx);
+                return Box::pin(fut::wrap_future(async move { rx.await.ok().flatten() }));
+            }
+            let leader = self
+                .coordinator_actual
+                .and_then(|leader| self.coord_addresses.get_by_key(&leader))
+                .and_then(|id| self.ring_nodes.get(id))
+                .copied();
+            return Box::pin(fut::ready(leader));
+        }
+        if self.handoff.is_some() {
+            self.logger
+                .warn("A leadership handoff is already in progress");
+            return Box::pin(fut::ready(None));
+        }
+        let successor = match msg.successor {
+            Some(target) if self.can_take_over(&target) => Some(target),
+            Some(target) => {
+                self.logger
+                    .warn(format!("{} cannot take over the leadership", target));
+                None
+            }
+            None => self.handoff_successor(),
+        };
+        let Some(successor) = successor else {
+            self.logger
+                .warn("No follower to hand the leadership off to");
+            return Box::pin(fut::ready(None));
+        };
+        if let Some(remote_addr) = self.coord_addresses.get_by_value(&successor).copied()
+            && let Err(e) = self.send_network_message(
+                remote_addr,
+                NetworkMessage::PrepareLeadership(PrepareLeadership {
+                    coordinator_id: self.id.clone(),
+                    epoch: self.epoch,
+                }),
+            )
+        {
+            self.logger
+                .warn(format!("Error sending PrepareLeadership: {}", e));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        Box::pin(
+            self.storage
+                .send(GetCompactionStatus)
+                .into_actor(self)
+                .then(move |res, act, ctx| {
+                    match res {
+                        Ok(status) => {
+                            let last_index = status.next_log_id.saturating_sub(1);
+                            act.logger.info(format!(
+                                "Waiting for {} to apply the log up to {} before handing off",
+                                successor, last_index
+                            ));
+                            act.handoff = Some(PendingHandoff {
+                                successor,
+                                last_index,
+                                responder: tx,
+                            });
+                            act.check_handoff();
+                            ctx.run_later(LEADERSHIP_HANDOFF_TIMEOUT, |act, _ctx| {
+                                if let Some(handoff) = act.handoff.take() {
+                                    act.logger.warn(format!(
+                                        "{} did not apply the log up to {} in time, keeping the leadership",
+                                        handoff.successor, handoff.last_index
+                                    ));
+                                    let _ = handoff.responder.send(None);
+                                }
+                            });
+                        }
+                        Err(e) => {
+                            act.logger
+                                .error(format!("Error retrieving the log index: {}", e));
+                            let _ = tx.send(None);
+                        }
+                    }
+                    fut::wrap_future(async move { rx.await.ok().flatten() })
+                }),
+        )
+    }
+}
+
+/// Handles requests of the leader to pull the log from it, before it hands the leadership off
+/// to this coordinator.
+impl Handler<PrepareLeadership> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: PrepareLeadership, _ctx: &mut Context<Self>) {
+        if !self.check_epoch(&msg.coordinator_id, msg.epoch) {
+            return;
+        }
+        let Some(leader_addr) = self
+            .coord_addresses
+            .get_by_value(&msg.coordinator_id)
+            .copied()
+        else {
+            self.logger.warn(format!(
+                "No address found for coordinator: {}",
+                msg.coordinator_id
+            ));
+            return;
+        };
+        self.logger.info(format!(
+            "{} hands the leadership off to this coordinator, pulling the log from it",
+            msg.coordinator_id
+        ));
+        self.pull_from = Some(leader_addr);
+    }
+}
+
+/// Handles incoming storage snapshot chunks from the leader.
+///
+/// Chunks are accepted in order; duplicates are ignored and gaps are recovered by the stall
+/// timer, which resumes the transfer from the first missing chunk.
+impl Handler<SnapshotChunk> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: SnapshotChunk, ctx: &mut Context<Self>) {
+        if !self.check_epoch(&msg.coordinator_id, msg.epoch) {
+            return;
+        }
+        let same_snapshot = self
+            .incoming_snapshot
+            .as_ref()
+            .is_some_and(|incoming| incoming.snapshot_id == msg.snapshot_id);
+        if !same_snapshot {
+            if msg.seq != 0 {
+                self.logger.warn(format!(
+                    "Ignoring chunk {} of unknown snapshot {}",
+                    msg.seq, msg.snapshot_id
+                ));
+                return;
+            }
+            self.logger.info(format!(
+                "Receiving snapshot {} in {} chunks",
+                msg.snapshot_id, msg.total
+            ));
+            self.incoming_snapshot = Some(IncomingSnapshot {
+                snapshot_id: msg.snapshot_id,
+                total: msg.total,
+                chunks: Vec::with_capacity(msg.total as usize),
+            });
+        }
+        let Some(incoming) = self.incoming_snapshot.as_mut() else {
+            return;
+        };
+        if msg.seq as usize != incoming.chunks.len() {
+            // Duplicado o fuera de orden: el timer pide lo que falte
+            return;
+        }
+        incoming.chunks.push(msg.payload);
+        if (incoming.chunks.len() as u32) < incoming.total {
+            self.arm_snapshot_timer(ctx);
+            return;
+        }
+
+        if let Some(handle) = self.snapshot_stall_timer.take() {
+            ctx.cancel_future(handle);
+        }
+        if let Some(incoming) = self.incoming_snapshot.take() {
+            match serde_json::from_str::<Snapshot>(&incoming.chunks.concat()) {
+                Ok(snapshot) => {
+                    self.logger
+                        .info(format!("Snapshot {} received", incoming.snapshot_id));
+                    self.snapshot_entities = StorageEntity::ALL.to_vec();
+                    self.storage.do_send(StorageSnapshot {
+                        snapshot: Box::new(snapshot),
+                        coordinator_id: msg.coordinator_id,
+                        epoch: msg.epoch,
+                    });
+                    self.coordinator_addr.do_send(ReplicaSynced);
+                }
+                Err(e) => {
+                    self.logger
+                        .error(format!("Error deserializing storage snapshot: {}", e));
+                    self.request_storage_snapshot(ctx);
+                }
+            }
+        }
+    }
+}
+
+/// Handles writes that must reach a quorum before being acknowledged.
+///
+/// Replicates the write to every connected coordinator and answers once a majority of the
+/// ring (more than half, counting this node) holds it, or with
+/// [`CoordinatorError::QuorumNotReached`] after [`QUORUM_WRITE_TIMEOUT`]. In
+/// [`WriteAckMode::Leader`] it answers right away.
+impl Handler<QuorumWrite> for CoordinatorManager {
+    type Result = ResponseFuture<Result<(), CoordinatorError>>;
+
+    fn handle(&mut self, msg: QuorumWrite, ctx: &mut Context<Self>) -> Self::Result {
+        let needed = self.ring_nodes.len() / 2 + 1;
+        if self.write_ack == WriteAckMode::Leader || needed <= 1 {
+            return Box::pin(async { Ok(()) });
+        }
+
+        let write_id = self.next_write_id;
+        self.next_write_id += 1;
+        self.broadcast_network_message(NetworkMessage::ReplicateWrite(ReplicateWrite {
+            write_id,
+            coordinator_id: self.id.clone(),
+            index: msg.write.index,
+            update: msg.write.update,
+            epoch: self.epoch,
+        }));
+
+        let (tx, rx) = oneshot::channel();
+        self.pending_writes.insert(
+            write_id,
+            PendingWrite {
+                acks: HashSet::from([self.id.clone()]),
+                needed,
+                responder: tx,
+            },
+        );
+        ctx.run_later(QUORUM_WRITE_TIMEOUT, move |act, _ctx| {
+            if let Some(pending) = act.pending_writes.remove(&write_id) {
+                act.logger.warn(format!(
+                    "Write {} confirmed by {} of {} coordinators before the timeout",
+                    write_id,
+                    pending.acks.len(),
+                    pending.needed
+                ));
+                let _ = pending.responder.send(false);
+            }
+        });
+
+        Box::pin(async move {
+            match rx.await {
+                Ok(true) => Ok(()),
+                _ => Err(CoordinatorError::QuorumNotReached),
+            }
+        })
+    }
+}
+
+/// Handles writes replicated by the leader, applying them at the index they have in its log
+/// and confirming them.
+impl Handler<ReplicateWrite> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: ReplicateWrite, ctx: &mut Context<Self>) {
+        // Sin WriteAck, la escritura de un líder obsoleto no alcanza el quórum
+        if !self.check_epoch(&msg.coordinator_id, msg.epoch) {
+            return;
+        }
+        let Some(leader_addr) = self
+            .coord_addresses
+            .get_by_value(&msg.coordinator_id)
+            .cloned()
+        else {
+            self.logger.warn(format!(
+                "No address found for coordinator: {}",
+                msg.coordinator_id
+            ));
+            return;
+        };
+        let write_id = msg.write_id;
+
+        self.storage
+            .send(ApplyReplicatedWrite {
+                index: msg.index,
+                update: msg.update,
+            })
+            .into_actor(self)
+            .then(move |res, act, _ctx| {
+                match res {
+                    Ok(()) => {
+                        if let Err(e) = act.send_network_message(
+                            leader_addr,
+                            NetworkMessage::WriteAck(WriteAck {
+                                write_id,
+                                coordinator_id: act.id.clone(),
+                            }),
+                        ) {
+                            act.logger.warn(format!("Error sending WriteAck: {}", e));
+                        }
+                    }
+                    Err(e) => {
+                        act.logger
+                            .warn(format!("Error applying replicated write: {:?}", e));
+                    }
+                }
+                fut::ready(())
+            })
+            .spawn(ctx);
+    }
+}
+
+/// Handles confirmations of replicated writes, completing them once the quorum is reached.
+impl Handler<WriteAck> for CoordinatorManager {
+    type Result = ();
+
+    fn handle(&mut self, msg: WriteAck, _ctx: &mut Context<Self>) {
+        let Some(pending) = self.pending_writes.get_mut(&msg.write_id) else {
+            // Ya se alcanzó el quórum o venció el timeout
+            return;
+        };
+        pending.acks.insert(msg.coordinator_id);
+        if pending.acks.len() >= pending.needed
+            && let Some(pending) = self.pending_writes.remove(&msg.write_id)
+        {
+            let _ = pending.responder.send(true);
+        }
     }
 }
 
@@ -846,7 +2157,7 @@ impl Handler<StorageSnapshot> for CoordinatorManager {
 impl Handler<RequestNewStorageUpdates> for CoordinatorManager {
     type Result = ();
 
-    fn handle(&mut self, msg: RequestNewStorageUpdates, _ctx: &mut Context<Self>) {
+    fn handle(&mut self, msg: RequestNewStorageUpdates, ctx: &mut Context<Self>) {
         // lo buscamos en el bimap de direcciones y luego en comunicadores. Si no esta en comunicadores, no lo tenemos conectado
         let id = msg.coordinator_id.clone();
 
@@ -868,42 +2179,78 @@ impl Handler<RequestNewStorageUpdates> for CoordinatorManager {
             "Received request for Storage updates from node {}",
             msg.start_index
         ));
+        if let Some(applied_index) = msg.applied_index {
+            self.follower_progress
+                .insert(id.clone(), (applied_index, Instant::now()));
+            self.check_handoff();
+        }
 
         // Enviar las actualizaciones de Storage al nodo que lo solicitó
-        self.storage
-            .send(GetLogsFromIndex {
-                index: msg.start_index,
-            })
-            .into_actor(self)
-            .then(move |res, act, _ctx| {
-                match res {
-                    Ok(updates) => {
-                        // Aquí deberíamos enviar las actualizaciones desde min_log_index hasta el final
-                        // Por simplicidad, enviamos un mensaje vacío
-
-                        let remote_addr = act
-                            .coord_addresses
-                            .get_by_value(&msg.coordinator_id)
-                            .cloned()
-                            .unwrap();
-
-                        act.send_network_message(
-                            remote_addr,
-                            NetworkMessage::StorageUpdates(StorageUpdates { updates }),
-                        )
-                        .unwrap_or_else(|e| {
-                            act.logger
-                                .error(format!("Error sending StorageUpdates: {}", e))
-                        });
+        let storage = self.storage.clone();
+        let start_index = msg.start_index;
+        async move {
+            let status = storage.send(GetCompactionStatus).await?;
+            let updates = storage
+                .send(GetLogsFromIndex { index: start_index })
+                .await?;
+            Ok::<_, MailboxError>((status, updates))
+        }
+        .into_actor(self)
+        .then(move |res, act, ctx| {
+            match res {
+                Ok((status, updates)) => {
+                    // Si le faltan entradas que ya compactamos, sólo puede ponerse al día con un
+                    // snapshot, sea este nodo el líder o el anterior del anillo
+                    if msg
+                        .applied_index
+                        .is_some_and(|applied_index| applied_index + 1 < status.confirmed_up_to)
+                    {
+                        if act.outgoing_snapshots.contains_key(&msg.coordinator_id) {
+                            // Ya le estamos enviando uno
+                            return fut::ready(());
+                        }
+                        act.logger.warn(format!(
+                            "Coordinator {} is behind the compacted log, sending it a snapshot",
+                            msg.coordinator_id
+                        ));
+                        act.handle(
+                            RequestAllStorage {
+                                coordinator_id: msg.coordinator_id,
+                                snapshot_id: None,
+                                from_seq: 0,
+                            },
+                            ctx,
+                        );
+                        return fut::ready(());
                     }
-                    Err(e) => {
+
+                    let remote_addr = act
+                        .coord_addresses
+                        .get_by_value(&msg.coordinator_id)
+                        .cloned()
+                        .unwrap();
+
+                    act.send_network_message(
+                        remote_addr,
+                        NetworkMessage::StorageUpdates(StorageUpdates {
+                            updates,
+                            coordinator_id: act.id.clone(),
+                            epoch: act.epoch,
+                        }),
+                    )
+                    .unwrap_or_else(|e| {
                         act.logger
-                            .warn(format!("Error obtaining minLogIndex from storage: {:?}", e));
-                    }
+                            .error(format!("Error sending StorageUpdates: {}", e))
+                    });
                 }
-                fut::ready(())
-            })
-            .spawn(_ctx);
+                Err(e) => {
+                    act.logger
+                        .warn(format!("Error obtaining minLogIndex from storage: {:?}", e));
+                }
+            }
+            fut::ready(())
+        })
+        .spawn(ctx);
     }
 }
 
@@ -957,9 +2304,552 @@ impl Handler<ConnectionClosed> for CoordinatorManager {
                 self.pong_leader_addr = None;
             }
 
-            self.start_leader_election();
+            self.start_leader_election(ctx);
         }
 
         //self.heartbeat_timestamps.remove(&msg.addr);
     }
 }
+
+/// Splits a serialized snapshot in chunks of at most [`SNAPSHOT_CHUNK_SIZE`] bytes,
+/// respecting UTF-8 character boundaries.
+/// Returns the ID of the coordinator with the highest applied index in `progress` among the
+/// `eligible` ones, the lowest ID on a tie.
+fn most_up_to_date(
+    progress: &HashMap<String, (u64, Instant)>,
+    eligible: impl Fn(&str) -> bool,
+) -> Option<String> {
+    progress
+        .iter()
+        .filter(|(id, _)| eligible(id))
+        .max_by(|(a_id, (a_index, _)), (b_id, (b_index, _))| {
+            a_index.cmp(b_index).then_with(|| b_id.cmp(a_id))
+        })
+        .map(|(id, _)| id.clone())
+}
+
+fn split_in_chunks(serialized: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = serialized;
+    while !rest.is_empty() {
+        let mut end = SNAPSHOT_CHUNK_SIZE.min(rest.len());
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (chunk, tail) = rest.split_at(end);
+        chunks.push(chunk.to_string());
+        rest = tail;
+    }
+    if chunks.is_empty() {
+        chunks.push(String::new());
+    }
+    chunks
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::messages::internal_messages::LoggedWrite;
+    use crate::server_actors::storage_view::StorageReadView;
+    use common::messages::internal_messages::{AddClient, RemoveClient, StorageLogMessage};
+    use common::types::dtos::ClientDTO;
+    use common::types::hlc::HlcTimestamp;
+    use common::types::tenant::TenantId;
+    use std::sync::{Arc, Mutex};
+
+    /// Messages sent to each coordinator of the ring, by ID.
+    pub(crate) type Outboxes = HashMap<String, Arc<Mutex<Vec<NetworkMessage>>>>;
+
+    /// A connection that keeps the messages sent instead of delivering them.
+    #[derive(Debug)]
+    struct RecordingTransport {
+        local_address: SocketAddr,
+        peer_address: SocketAddr,
+        sent: Arc<Mutex<Vec<NetworkMessage>>>,
+    }
+
+    impl PeerTransport for RecordingTransport {
+        fn local_address(&self) -> SocketAddr {
+            self.local_address
+        }
+
+        fn peer_address(&self) -> SocketAddr {
+            self.peer_address
+        }
+
+        fn send(&self, message: NetworkMessage) -> Result<(), String> {
+            self.sent.lock().unwrap().push(message);
+            Ok(())
+        }
+
+        fn shutdown(&mut self) {}
+    }
+
+    /// Returns the address of `server_{index}`.
+    pub(crate) fn addr_of(index: usize) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], 9000 + index as u16))
+    }
+
+    /// Returns the manager of `server_{me}` in a ring of `size` coordinators, connected to
+    /// the rest through [`RecordingTransport`]s. Its coordinator and storage are never
+    /// started.
+    pub(crate) fn new_manager(me: usize, size: usize) -> (CoordinatorManager, Outboxes) {
+        let ring_nodes: HashMap<String, SocketAddr> = (0..size)
+            .map(|index| (format!("server_{}", index), addr_of(index)))
+            .collect();
+        let mut manager = CoordinatorManager::new(
+            format!("server_{}", me),
+            addr_of(me),
+            ring_nodes,
+            Context::<Coordinator>::new().address(),
+            Context::<Storage>::new().address(),
+            TransportKind::Tcp,
+            WriteAckMode::Leader,
+        );
+        let mut outboxes = HashMap::new();
+        for index in (0..size).filter(|index| *index != me) {
+            let sent = Arc::new(Mutex::new(Vec::new()));
+            manager.coord_communicators.insert(
+                addr_of(index),
+                Box::new(RecordingTransport {
+                    local_address: addr_of(me),
+                    peer_address: addr_of(index),
+                    sent: sent.clone(),
+                }),
+            );
+            outboxes.insert(format!("server_{}", index), sent);
+        }
+        (manager, outboxes)
+    }
+
+    /// Takes the messages sent to `coordinator_id` so far.
+    pub(crate) fn sent_to(outboxes: &Outboxes, coordinator_id: &str) -> Vec<NetworkMessage> {
+        std::mem::take(&mut *outboxes[coordinator_id].lock().unwrap())
+    }
+
+    /// Returns an empty snapshot serialized as JSON.
+    fn serialized_snapshot() -> String {
+        let mut storage = Storage::new(
+            Context::<Coordinator>::new().address(),
+            "server_0".to_string(),
+            None,
+            StorageReadView::default(),
+        );
+        let MessageResult(snapshot) = storage.handle(GetAllStorage, &mut Context::new());
+        serde_json::to_string(&snapshot).unwrap()
+    }
+
+    fn chunk(snapshot_id: u64, seq: usize, chunks: &[String]) -> SnapshotChunk {
+        SnapshotChunk {
+            snapshot_id,
+            seq: seq as u32,
+            total: chunks.len() as u32,
+            payload: chunks[seq].clone(),
+            coordinator_id: "server_0".to_string(),
+            epoch: 0,
+        }
+    }
+
+    /// Returns a write logged by the leader at `index`.
+    fn logged_write(index: u64) -> LoggedWrite {
+        LoggedWrite {
+            index,
+            update: StorageLogMessage::RemoveClient(RemoveClient {
+                client_id: "client_0".to_string(),
+                time_stamp: None,
+            }),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn a_write_is_acknowledged_once_a_quorum_holds_it() {
+        let (mut manager, outboxes) = new_manager(0, 3);
+        manager.write_ack = WriteAckMode::Quorum;
+        let mut ctx = Context::new();
+
+        let result = manager.handle(
+            QuorumWrite {
+                write: logged_write(42),
+            },
+            &mut ctx,
+        );
+        // Los seguidores reciben la escritura con el índice que tiene en el log del líder
+        let mut write_id = None;
+        for follower in ["server_1", "server_2"] {
+            match sent_to(&outboxes, follower).as_slice() {
+                [NetworkMessage::ReplicateWrite(write)] => {
+                    assert_eq!(write.index, 42);
+                    write_id = Some(write.write_id);
+                }
+                other => panic!("Unexpected messages: {:?}", other),
+            }
+        }
+
+        manager.handle(
+            WriteAck {
+                write_id: write_id.unwrap(),
+                coordinator_id: "server_2".to_string(),
+            },
+            &mut ctx,
+        );
+        assert_eq!(result.await, Ok(()));
+        assert!(manager.pending_writes.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn half_of_an_even_ring_is_not_a_quorum() {
+        let (mut manager, outboxes) = new_manager(0, 4);
+        manager.write_ack = WriteAckMode::Quorum;
+        let mut ctx = Context::new();
+
+        let result = manager.handle(
+            QuorumWrite {
+                write: logged_write(42),
+            },
+            &mut ctx,
+        );
+        let write_id = match sent_to(&outboxes, "server_1").as_slice() {
+            [NetworkMessage::ReplicateWrite(write)] => write.write_id,
+            other => panic!("Unexpected messages: {:?}", other),
+        };
+        let ack = |coordinator_id: &str| WriteAck {
+            write_id,
+            coordinator_id: coordinator_id.to_string(),
+        };
+
+        // Con el líder, dos de cuatro todavía no son mayoría
+        manager.handle(ack("server_1"), &mut ctx);
+        assert!(manager.pending_writes.contains_key(&write_id));
+        manager.handle(ack("server_3"), &mut ctx);
+        assert_eq!(result.await, Ok(()));
+        assert!(manager.pending_writes.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn a_write_without_a_quorum_fails_after_the_timeout() {
+        let (mut manager, _outboxes) = new_manager(0, 3);
+        manager.write_ack = WriteAckMode::Quorum;
+        let manager = manager.start();
+
+        let result = manager
+            .send(QuorumWrite {
+                write: logged_write(42),
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, Err(CoordinatorError::QuorumNotReached));
+    }
+
+    #[test]
+    fn chunks_respect_the_size_and_char_boundaries() {
+        assert_eq!(split_in_chunks(""), vec![String::new()]);
+
+        let exact = "a".repeat(2 * SNAPSHOT_CHUNK_SIZE);
+        let chunks = split_in_chunks(&exact);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|c| c.len() == SNAPSHOT_CHUNK_SIZE));
+
+        // La "ñ" ocupa dos bytes y quedaría partida en el límite del primer chunk
+        let text = format!("{}ñ{}", "a".repeat(SNAPSHOT_CHUNK_SIZE - 1), "b".repeat(10));
+        let chunks = split_in_chunks(&text);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].len(), SNAPSHOT_CHUNK_SIZE - 1);
+        assert!(chunks[1].starts_with('ñ'));
+        assert_eq!(chunks.concat(), text);
+    }
+
+    #[actix_rt::test]
+    async fn out_of_order_and_duplicated_chunks_are_ignored() {
+        let (mut manager, _) = new_manager(1, 2);
+        let mut ctx = Context::new();
+        let serialized = serialized_snapshot();
+        let third = serialized.len() / 3;
+        let chunks = vec![
+            serialized[..third].to_string(),
+            serialized[third..2 * third].to_string(),
+            serialized[2 * third..].to_string(),
+        ];
+
+        // Sin el primer chunk no se sabe nada de la snapshot
+        manager.handle(chunk(7, 1, &chunks), &mut ctx);
+        assert!(manager.incoming_snapshot.is_none());
+
+        manager.handle(chunk(7, 0, &chunks), &mut ctx);
+        manager.handle(chunk(7, 2, &chunks), &mut ctx);
+        manager.handle(chunk(7, 0, &chunks), &mut ctx);
+        let incoming = manager.incoming_snapshot.as_ref().unwrap();
+        assert_eq!(incoming.chunks, chunks[..1]);
+
+        manager.handle(chunk(7, 1, &chunks), &mut ctx);
+        manager.handle(chunk(7, 2, &chunks), &mut ctx);
+        assert!(manager.incoming_snapshot.is_none());
+        assert!(manager.snapshot_stall_timer.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn a_dropped_transfer_resumes_from_the_first_missing_chunk() {
+        let (mut follower, follower_outboxes) = new_manager(1, 2);
+        let (mut leader, leader_outboxes) = new_manager(0, 2);
+        let mut ctx = Context::new();
+        let chunks: Vec<String> = serialized_snapshot()
+            .chars()
+            .map(|c| c.to_string())
+            .collect();
+        leader
+            .outgoing_snapshots
+            .insert("server_1".to_string(), (7, chunks.clone()));
+
+        follower.coordinator_actual = Some(addr_of(0));
+        follower.handle(chunk(7, 0, &chunks), &mut ctx);
+        follower.handle(chunk(7, 1, &chunks), &mut ctx);
+        // Se pierde el chunk 2 y el timer vuelve a pedir la snapshot
+        follower.handle(chunk(7, 3, &chunks), &mut ctx);
+        follower.request_storage_snapshot(&mut ctx);
+
+        let from_seq = match sent_to(&follower_outboxes, "server_0").as_slice() {
+            [NetworkMessage::RequestAllStorage(request)] => {
+                assert_eq!(request.snapshot_id, Some(7));
+                request.from_seq
+            }
+            other => panic!("Unexpected messages: {:?}", other),
+        };
+        assert_eq!(from_seq, 2);
+
+        leader.send_snapshot_chunks(addr_of(1), "server_1", from_seq);
+        let resent = sent_to(&leader_outboxes, "server_1");
+        assert_eq!(resent.len(), chunks.len() - 2);
+        for message in resent {
+            let NetworkMessage::SnapshotChunk(chunk) = message else {
+                panic!("Unexpected message: {:?}", message);
+            };
+            follower.handle(chunk, &mut ctx);
+        }
+        assert!(follower.incoming_snapshot.is_none());
+        // La snapshot se pudo leer: no se vuelve a pedir
+        assert!(sent_to(&follower_outboxes, "server_0").is_empty());
+    }
+
+    #[test]
+    fn hands_off_to_the_most_up_to_date_follower() {
+        let now = Instant::now();
+        let progress = HashMap::from([
+            ("server_1".to_string(), (40, now)),
+            ("server_2".to_string(), (42, now)),
+            ("server_3".to_string(), (42, now)),
+        ]);
+
+        assert_eq!(
+            most_up_to_date(&progress, |_| true),
+            Some("server_2".to_string())
+        );
+        assert_eq!(
+            most_up_to_date(&progress, |id| id == "server_1"),
+            Some("server_1".to_string())
+        );
+        assert_eq!(most_up_to_date(&progress, |_| false), None);
+    }
+
+    #[test]
+    fn epochs_stay_distinct_across_ring_positions() {
+        let (mut manager, _outboxes) = new_manager(0, 3);
+        for epoch in [0, 1, 5, 6, 41] {
+            manager.epoch = epoch;
+            let epochs: Vec<u64> = (0..3)
+                .map(|index| manager.next_epoch(&format!("server_{}", index)))
+                .collect();
+            for (position, next) in epochs.iter().enumerate() {
+                assert!(*next > epoch, "{} after {}", next, epoch);
+                // Cada coordinador es dueño de las epochs congruentes con su posición
+                assert_eq!(*next % 3, position as u64);
+            }
+            let distinct: HashSet<&u64> = epochs.iter().collect();
+            assert_eq!(distinct.len(), 3, "{:?} after {}", epochs, epoch);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn a_stale_leader_is_fenced_and_steps_down() {
+        let mut ctx = Context::new();
+        // server_2 ganó la epoch 5 mientras server_0 seguía creyéndose líder de la 3
+        let (mut follower, follower_outboxes) = new_manager(1, 3);
+        follower.epoch = 5;
+        follower.coordinator_actual = Some(addr_of(2));
+        let (mut stale, stale_outboxes) = new_manager(0, 3);
+        stale.epoch = 3;
+        stale.coordinator_actual = Some(addr_of(0));
+        let (responder, pending) = oneshot::channel();
+        stale.pending_writes.insert(
+            1,
+            PendingWrite {
+                acks: HashSet::from(["server_0".to_string()]),
+                needed: 2,
+                responder,
+            },
+        );
+
+        assert!(!follower.check_epoch("server_0", 3));
+        let fence = match sent_to(&follower_outboxes, "server_0").as_slice() {
+            [NetworkMessage::LeaderIdIs(fence)] => fence.clone(),
+            other => panic!("Unexpected messages: {:?}", other),
+        };
+        assert_eq!(fence.leader_id, "server_2");
+        assert_eq!(fence.epoch, 5);
+
+        stale.handle(fence, &mut ctx);
+        assert_eq!(stale.epoch, 5);
+        assert_eq!(stale.coordinator_actual, Some(addr_of(2)));
+        // Las escrituras pendientes fallan y el storage se vuelve a pedir al nuevo líder
+        assert!(stale.pending_writes.is_empty());
+        assert_eq!(pending.await, Ok(false));
+        assert!(matches!(
+            sent_to(&stale_outboxes, "server_2").as_slice(),
+            [NetworkMessage::RequestAllStorage(_)]
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn a_higher_epoch_is_adopted() {
+        let mut ctx = Context::new();
+        let (mut manager, outboxes) = new_manager(1, 3);
+        manager.epoch = 3;
+        manager.coordinator_actual = Some(addr_of(0));
+
+        // Un mensaje de una epoch más nueva se acepta, pero se pregunta quién es el líder
+        assert!(manager.check_epoch("server_2", 5));
+        assert!(matches!(
+            sent_to(&outboxes, "server_2").as_slice(),
+            [NetworkMessage::WhoIsLeader(_)]
+        ));
+        assert_eq!(manager.epoch, 3);
+
+        manager.handle(
+            LeaderIdIs {
+                leader_id: "server_2".to_string(),
+                epoch: 5,
+            },
+            &mut ctx,
+        );
+        assert_eq!(manager.epoch, 5);
+        assert_eq!(manager.coordinator_actual, Some(addr_of(2)));
+
+        // Un anuncio de la epoch anterior ya no cambia al líder
+        manager.handle(
+            LeaderIdIs {
+                leader_id: "server_0".to_string(),
+                epoch: 3,
+            },
+            &mut ctx,
+        );
+        assert_eq!(manager.epoch, 5);
+        assert_eq!(manager.coordinator_actual, Some(addr_of(2)));
+        assert!(
+            sent_to(&outboxes, "server_0")
+                .iter()
+                .any(|message| matches!(
+                    message,
+                    NetworkMessage::LeaderIdIs(LeaderIdIs { epoch: 5, .. })
+                ))
+        );
+    }
+
+    /// Waits until something is sent to `coordinator_id` and takes it.
+    async fn wait_for_messages(outboxes: &Outboxes, coordinator_id: &str) -> Vec<NetworkMessage> {
+        for _ in 0..100 {
+            let sent = sent_to(outboxes, coordinator_id);
+            if !sent.is_empty() {
+                return sent;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        Vec::new()
+    }
+
+    #[test]
+    fn compacts_only_what_every_recent_follower_applied() {
+        let (mut manager, _outboxes) = new_manager(0, 3);
+        let now = Instant::now();
+        // Nadie informó su progreso: no se compacta nada
+        assert_eq!(manager.compaction_point(now), None);
+
+        manager.follower_progress = HashMap::from([
+            ("server_1".to_string(), (5, now)),
+            ("server_2".to_string(), (2, now)),
+        ]);
+        assert_eq!(manager.compaction_point(now), Some(3));
+
+        // Un seguidor en silencio deja de frenar la compactación
+        let later = now + LOG_COMPACTION_FOLLOWER_TIMEOUT;
+        manager
+            .follower_progress
+            .insert("server_1".to_string(), (5, later));
+        assert_eq!(manager.compaction_point(later), Some(6));
+        assert!(!manager.follower_progress.contains_key("server_2"));
+    }
+
+    #[actix_rt::test]
+    async fn a_requester_behind_the_compacted_log_gets_a_snapshot() {
+        let storage = Storage::new(
+            Context::<Coordinator>::new().address(),
+            "server_1".to_string(),
+            None,
+            StorageReadView::default(),
+        )
+        .start();
+        for index in 1..=5 {
+            storage
+                .send(StorageLogMessage::AddClient(AddClient {
+                    client: ClientDTO {
+                        client_position: (0.0, 0.0),
+                        client_id: format!("client_{}", index),
+                        client_order: None,
+                        time_stamp: HlcTimestamp::default(),
+                        tenant_id: TenantId::default(),
+                    },
+                }))
+                .await
+                .unwrap();
+        }
+        storage.send(CompactLog { up_to: 4 }).await.unwrap();
+
+        // server_1 no es el líder, pero server_2 le pide actualizaciones por ser el anterior
+        let (mut manager, outboxes) = new_manager(1, 3);
+        manager.storage = storage;
+        manager.coordinator_actual = Some(addr_of(0));
+        let manager = manager.start();
+
+        manager
+            .send(RequestNewStorageUpdates {
+                coordinator_id: "server_2".to_string(),
+                start_index: 4,
+                applied_index: Some(3),
+            })
+            .await
+            .unwrap();
+        match wait_for_messages(&outboxes, "server_2").await.as_slice() {
+            [NetworkMessage::StorageUpdates(updates)] => {
+                let mut indices: Vec<u64> = updates.updates.keys().copied().collect();
+                indices.sort_unstable();
+                assert_eq!(indices, [4, 5]);
+            }
+            other => panic!("Unexpected messages: {:?}", other),
+        }
+
+        // Le faltan entradas que ya se compactaron: sólo un snapshot lo pone al día
+        manager
+            .send(RequestNewStorageUpdates {
+                coordinator_id: "server_2".to_string(),
+                start_index: 2,
+                applied_index: Some(1),
+            })
+            .await
+            .unwrap();
+        let sent = wait_for_messages(&outboxes, "server_2").await;
+        assert!(!sent.is_empty());
+        assert!(
+            sent.iter()
+                .all(|message| matches!(message, NetworkMessage::SnapshotChunk(_))),
+            "Unexpected messages: {:?}",
+            sent
+        );
+    }
+}
@@ -0,0 +1,174 @@
+use common::constants::{DEAD_LETTER_MAX_PER_USER, DEAD_LETTER_TTL};
+use common::messages::shared_messages::NetworkMessage;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Counters of the messages kept by a [`DeadLetterQueue`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DeadLetterStats {
+    /// Messages waiting for their user to reconnect.
+    pub queued: u64,
+    /// Messages that could not be delivered and were kept.
+    pub retained: u64,
+    /// Kept messages delivered once their user reconnected.
+    pub redelivered: u64,
+    /// Kept messages discarded because their user did not reconnect in time.
+    pub expired: u64,
+    /// Kept messages discarded because their user had too many waiting.
+    pub dropped: u64,
+}
+
+/// A message that could not be delivered to its user.
+#[derive(Debug)]
+struct DeadLetter {
+    /// The message.
+    message: NetworkMessage,
+    /// When the message stops being worth delivering.
+    expires_at: Instant,
+}
+
+/// Messages that could not be delivered because their user was disconnected, kept until
+/// the user reconnects.
+///
+/// Each message is kept for [`DEAD_LETTER_TTL`], and at most [`DEAD_LETTER_MAX_PER_USER`]
+/// messages are kept per user: the oldest ones are dropped first.
+#[derive(Debug)]
+pub struct DeadLetterQueue {
+    /// The waiting messages of each user, oldest first.
+    letters: HashMap<String, VecDeque<DeadLetter>>,
+    /// How long each message is kept.
+    ttl: Duration,
+    /// Maximum messages kept per user.
+    max_per_user: usize,
+    /// Counters of the queue.
+    stats: DeadLetterStats,
+}
+
+impl Default for DeadLetterQueue {
+    fn default() -> Self {
+        Self::new(DEAD_LETTER_TTL, DEAD_LETTER_MAX_PER_USER)
+    }
+}
+
+impl DeadLetterQueue {
+    /// Creates an empty queue.
+    ///
+    /// ## Arguments
+    /// * `ttl` - How long each message is kept.
+    /// * `max_per_user` - Maximum messages kept per user.
+    pub fn new(ttl: Duration, max_per_user: usize) -> Self {
+        Self {
+            letters: HashMap::new(),
+            ttl,
+            max_per_user,
+            stats: DeadLetterStats::default(),
+        }
+    }
+
+    /// Returns the counters of the queue.
+    pub fn stats(&self) -> DeadLetterStats {
+        DeadLetterStats {
+            queued: self
+                .letters
+                .values()
+                .map(|letters| letters.len() as u64)
+                .sum(),
+            ..self.stats
+        }
+    }
+
+    /// Keeps a message that could not be delivered to `user_id`.
+    pub fn push(&mut self, user_id: &str, message: NetworkMessage, now: Instant) {
+        let letters = self.letters.entry(user_id.to_string()).or_default();
+        if letters.len() >= self.max_per_user {
+            letters.pop_front();
+            self.stats.dropped += 1;
+        }
+        letters.push_back(DeadLetter {
+            message,
+            expires_at: now + self.ttl,
+        });
+        self.stats.retained += 1;
+    }
+
+    /// Takes the messages kept for `user_id` that did not expire yet, oldest first.
+    pub fn take(&mut self, user_id: &str, now: Instant) -> Vec<NetworkMessage> {
+        let Some(letters) = self.letters.remove(user_id) else {
+            return Vec::new();
+        };
+        let (alive, expired): (Vec<DeadLetter>, Vec<DeadLetter>) = letters
+            .into_iter()
+            .partition(|letter| letter.expires_at > now);
+        self.stats.expired += expired.len() as u64;
+        self.stats.redelivered += alive.len() as u64;
+        alive.into_iter().map(|letter| letter.message).collect()
+    }
+
+    /// Discards the expired messages of every user.
+    ///
+    /// ## Returns
+    /// How many messages were discarded.
+    pub fn purge_expired(&mut self, now: Instant) -> u64 {
+        let mut purged = 0;
+        self.letters.retain(|_, letters| {
+            let before = letters.len();
+            letters.retain(|letter| letter.expires_at > now);
+            purged += (before - letters.len()) as u64;
+            !letters.is_empty()
+        });
+        self.stats.expired += purged;
+        purged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::messages::shared_messages::RetryLater;
+
+    fn retry_later() -> NetworkMessage {
+        NetworkMessage::RetryLater(RetryLater {
+            origin_addr: "127.0.0.1:8080".parse().unwrap(),
+            retry_after_millis: 0,
+        })
+    }
+
+    #[test]
+    fn redelivers_the_messages_kept_for_a_user_in_order() {
+        let now = Instant::now();
+        let mut queue = DeadLetterQueue::new(Duration::from_secs(10), 2);
+        queue.push("ana", NetworkMessage::NoRecoveredInfo, now);
+        queue.push("ana", retry_later(), now);
+        queue.push("ana", NetworkMessage::NoRecoveredInfo, now);
+
+        let messages = queue.take("ana", now);
+        assert_eq!(messages.len(), 2);
+        assert!(matches!(messages[0], NetworkMessage::RetryLater(_)));
+        assert!(queue.take("ana", now).is_empty());
+
+        let stats = queue.stats();
+        assert_eq!(stats.retained, 3);
+        assert_eq!(stats.dropped, 1);
+        assert_eq!(stats.redelivered, 2);
+        assert_eq!(stats.queued, 0);
+    }
+
+    #[test]
+    fn discards_the_messages_of_users_that_do_not_reconnect() {
+        let now = Instant::now();
+        let mut queue = DeadLetterQueue::new(Duration::from_secs(10), 5);
+        queue.push("ana", NetworkMessage::NoRecoveredInfo, now);
+        queue.push(
+            "bob",
+            NetworkMessage::NoRecoveredInfo,
+            now + Duration::from_secs(5),
+        );
+
+        assert_eq!(queue.purge_expired(now + Duration::from_secs(10)), 1);
+        assert_eq!(queue.stats().queued, 1);
+        assert!(queue.take("bob", now + Duration::from_secs(20)).is_empty());
+        assert_eq!(queue.stats().expired, 2);
+    }
+}
@@ -0,0 +1,412 @@
+use common::constants::{ELECTION_ANNOUNCEMENT_TIMEOUT, ELECTION_ANSWER_TIMEOUT};
+use common::messages::coordinatormanager_messages::{BullyElection, LeaderElection};
+use common::messages::shared_messages::NetworkMessage;
+use std::fmt;
+use std::time::Duration;
+
+/// Environment variable used to select the leader election algorithm.
+pub const ELECTION_ENV_VAR: &str = "LEADER_ELECTION";
+
+/// The leader election algorithm of the coordinators.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ElectionKind {
+    /// The election goes around the ring collecting candidates (the default).
+    #[default]
+    Ring,
+    /// Each coordinator challenges the ones senior to it, and the most senior alive wins.
+    Bully,
+}
+
+impl ElectionKind {
+    /// Reads the algorithm from [`ELECTION_ENV_VAR`] (`ring` or `bully`), defaulting to `ring`.
+    pub fn from_env() -> Self {
+        match std::env::var(ELECTION_ENV_VAR) {
+            Ok(value) if value.eq_ignore_ascii_case("bully") => ElectionKind::Bully,
+            Ok(value) if !value.eq_ignore_ascii_case("ring") => {
+                eprintln!(
+                    "[Election] Ignoring invalid {}: {}",
+                    ELECTION_ENV_VAR, value
+                );
+                ElectionKind::Ring
+            }
+            _ => ElectionKind::Ring,
+        }
+    }
+
+    /// Creates a strategy running this algorithm.
+    pub fn strategy(self) -> Box<dyn ElectionStrategy> {
+        match self {
+            ElectionKind::Ring => Box::new(RingStrategy),
+            ElectionKind::Bully => Box::new(BullyStrategy::default()),
+        }
+    }
+}
+
+/// What a coordinator knows of the ring when an election event happens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ElectionView {
+    /// ID of this coordinator.
+    pub id: String,
+    /// IDs of the coordinators reachable from this one, this one included, sorted.
+    pub members: Vec<String>,
+    /// Highest leader epoch known.
+    pub epoch: u64,
+}
+
+/// A message of any of the election algorithms.
+#[derive(Debug, Clone)]
+pub enum ElectionMessage {
+    /// A message of the ring election.
+    Ring(LeaderElection),
+    /// A message of the bully election.
+    Bully(BullyElection),
+}
+
+/// What an [`ElectionStrategy`] asks the `CoordinatorManager` to do.
+#[derive(Debug, Clone)]
+pub enum ElectionAction {
+    /// Send `message` to the coordinator `to`.
+    Send {
+        to: String,
+        message: Box<NetworkMessage>,
+    },
+    /// The election is over: announce `leader` as the leader of the epoch after `epoch`.
+    Elected { leader: String, epoch: u64 },
+    /// Call [`ElectionStrategy::timeout`] after the delay, replacing any earlier timer.
+    Timer(Duration),
+}
+
+/// A leader election algorithm.
+///
+/// The strategy only decides what to do; the `CoordinatorManager` sends the messages,
+/// runs the timers and announces the leader elected with `LeaderIdIs`.
+pub trait ElectionStrategy: fmt::Debug {
+    /// Starts an election, after the leader was lost or none is known.
+    fn start(&mut self, view: &ElectionView) -> Vec<ElectionAction>;
+
+    /// Handles a message of the election sent by another coordinator.
+    fn handle(&mut self, message: ElectionMessage, view: &ElectionView) -> Vec<ElectionAction>;
+
+    /// Handles the expiration of the last [`ElectionAction::Timer`] asked for.
+    fn timeout(&mut self, _view: &ElectionView) -> Vec<ElectionAction> {
+        Vec::new()
+    }
+
+    /// Called when a leader is announced, ending the election in progress.
+    fn leader_announced(&mut self) {}
+}
+
+/// Ring election: the election goes around the ring of reachable coordinators collecting
+/// their IDs, and when it is back at its initiator the lowest ID is the leader.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RingStrategy;
+
+impl RingStrategy {
+    /// Returns the coordinator after this one in the ring, if any other is reachable.
+    fn next_in_ring(view: &ElectionView) -> Option<String> {
+        if view.members.len() < 2 {
+            return None;
+        }
+        let index = view.members.iter().position(|id| *id == view.id)?;
+        view.members.get((index + 1) % view.members.len()).cloned()
+    }
+}
+
+impl ElectionStrategy for RingStrategy {
+    fn start(&mut self, view: &ElectionView) -> Vec<ElectionAction> {
+        let Some(next) = Self::next_in_ring(view) else {
+            // Si no hay siguiente nodo, me autoproclamo líder
+            return vec![ElectionAction::Elected {
+                leader: view.id.clone(),
+                epoch: view.epoch,
+            }];
+        };
+        vec![ElectionAction::Send {
+            to: next,
+            message: Box::new(NetworkMessage::LeaderElection(LeaderElection {
+                initiator: view.id.clone(),
+                candidates: vec![view.id.clone()],
+                epoch: view.epoch,
+            })),
+        }]
+    }
+
+    fn handle(&mut self, message: ElectionMessage, view: &ElectionView) -> Vec<ElectionAction> {
+        let ElectionMessage::Ring(mut election) = message else {
+            return Vec::new();
+        };
+        if election.initiator == view.id {
+            // Completó el ciclo
+            let Some(leader) = election.candidates.iter().min().cloned() else {
+                return Vec::new();
+            };
+            return vec![ElectionAction::Elected {
+                leader,
+                epoch: election.epoch,
+            }];
+        }
+        // Sumarme como candidato
+        election.candidates.push(view.id.clone());
+        election.epoch = election.epoch.max(view.epoch);
+        Self::next_in_ring(view)
+            .map(|next| ElectionAction::Send {
+                to: next,
+                message: Box::new(NetworkMessage::LeaderElection(election)),
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Phase of a bully election in progress.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BullyPhase {
+    /// Waiting for a senior coordinator to answer the challenge.
+    WaitingAnswers,
+    /// A senior coordinator answered: waiting for it to announce the leader.
+    WaitingLeader,
+}
+
+/// Bully election: a coordinator challenges every reachable coordinator with a lower ID,
+/// and becomes the leader if none answers in [`ELECTION_ANSWER_TIMEOUT`]. A coordinator
+/// challenged by a junior one answers and starts its own election, so the lowest ID alive
+/// wins, as with the ring. If the winner does not announce itself in
+/// [`ELECTION_ANNOUNCEMENT_TIMEOUT`], the election starts over.
+#[derive(Debug, Clone, Default)]
+pub struct BullyStrategy {
+    /// Phase of the election in progress, if any.
+    phase: Option<BullyPhase>,
+    /// Highest leader epoch known by the coordinators that took part in the election.
+    epoch: u64,
+}
+
+impl ElectionStrategy for BullyStrategy {
+    fn start(&mut self, view: &ElectionView) -> Vec<ElectionAction> {
+        self.epoch = self.epoch.max(view.epoch);
+        let seniors: Vec<&String> = view.members.iter().filter(|id| **id < view.id).collect();
+        if seniors.is_empty() {
+            self.phase = None;
+            return vec![ElectionAction::Elected {
+                leader: view.id.clone(),
+                epoch: self.epoch,
+            }];
+        }
+        self.phase = Some(BullyPhase::WaitingAnswers);
+        let mut actions: Vec<ElectionAction> = seniors
+            .into_iter()
+            .map(|senior| ElectionAction::Send {
+                to: senior.clone(),
+                message: Box::new(NetworkMessage::BullyElection(BullyElection::Election {
+                    from: view.id.clone(),
+                    epoch: self.epoch,
+                })),
+            })
+            .collect();
+        actions.push(ElectionAction::Timer(ELECTION_ANSWER_TIMEOUT));
+        actions
+    }
+
+    fn handle(&mut self, message: ElectionMessage, view: &ElectionView) -> Vec<ElectionAction> {
+        let ElectionMessage::Bully(election) = message else {
+            return Vec::new();
+        };
+        match election {
+            BullyElection::Election { from, epoch } => {
+                self.epoch = self.epoch.max(epoch);
+                if from <= view.id {
+                    return Vec::new();
+                }
+                // Le aviso al menor que sigo vivo y me hago cargo de la elección
+                let mut actions = vec![ElectionAction::Send {
+                    to: from,
+                    message: Box::new(NetworkMessage::BullyElection(BullyElection::Alive {
+                        from: view.id.clone(),
+                    })),
+                }];
+                if self.phase.is_none() {
+                    actions.extend(self.start(view));
+                }
+                actions
+            }
+            BullyElection::Alive { from } => {
+                if from >= view.id || self.phase != Some(BullyPhase::WaitingAnswers) {
+                    return Vec::new();
+                }
+                self.phase = Some(BullyPhase::WaitingLeader);
+                vec![ElectionAction::Timer(ELECTION_ANNOUNCEMENT_TIMEOUT)]
+            }
+        }
+    }
+
+    fn timeout(&mut self, view: &ElectionView) -> Vec<ElectionAction> {
+        match self.phase.take() {
+            // Ningún mayor respondió: el líder soy yo
+            Some(BullyPhase::WaitingAnswers) => vec![ElectionAction::Elected {
+                leader: view.id.clone(),
+                epoch: self.epoch.max(view.epoch),
+            }],
+            // El mayor que respondió no se anunció: empezar de nuevo
+            Some(BullyPhase::WaitingLeader) => self.start(view),
+            None => Vec::new(),
+        }
+    }
+
+    fn leader_announced(&mut self) {
+        self.phase = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+    /// A ring of coordinators running a strategy, with a perfect network: messages are
+    /// delivered in order, and the timers only expire once no message is in flight.
+    struct Ring {
+        strategies: BTreeMap<String, Box<dyn ElectionStrategy>>,
+        alive: BTreeSet<String>,
+        epochs: BTreeMap<String, u64>,
+        leaders: BTreeMap<String, String>,
+        in_flight: VecDeque<(String, ElectionMessage)>,
+        timers: BTreeSet<String>,
+        elected: Vec<String>,
+    }
+
+    impl Ring {
+        fn new(kind: ElectionKind, size: usize, dead: &[usize]) -> Self {
+            let ids: Vec<String> = (0..size).map(|i| format!("server_{}", i)).collect();
+            Self {
+                strategies: ids.iter().map(|id| (id.clone(), kind.strategy())).collect(),
+                alive: ids
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, _)| !dead.contains(i))
+                    .map(|(_, id)| id.clone())
+                    .collect(),
+                epochs: ids.iter().map(|id| (id.clone(), 0)).collect(),
+                leaders: BTreeMap::new(),
+                in_flight: VecDeque::new(),
+                timers: BTreeSet::new(),
+                elected: Vec::new(),
+            }
+        }
+
+        fn view(&self, id: &str) -> ElectionView {
+            ElectionView {
+                id: id.to_string(),
+                members: self.alive.iter().cloned().collect(),
+                epoch: self.epochs[id],
+            }
+        }
+
+        fn run(&mut self, id: &str, actions: Vec<ElectionAction>) {
+            for action in actions {
+                match action {
+                    ElectionAction::Send { to, message } => {
+                        let message = match *message {
+                            NetworkMessage::LeaderElection(msg) => ElectionMessage::Ring(msg),
+                            NetworkMessage::BullyElection(msg) => ElectionMessage::Bully(msg),
+                            other => panic!("Unexpected message {:?}", other),
+                        };
+                        if self.alive.contains(&to) {
+                            self.in_flight.push_back((to, message));
+                        }
+                    }
+                    ElectionAction::Elected { leader, epoch } => {
+                        self.elected.push(leader.clone());
+                        // El anuncio del líder llega a todos
+                        for member in self.alive.clone() {
+                            self.epochs.insert(member.clone(), epoch + 1);
+                            self.leaders.insert(member.clone(), leader.clone());
+                            self.timers.remove(&member);
+                            self.strategies.get_mut(&member).unwrap().leader_announced();
+                        }
+                    }
+                    ElectionAction::Timer(_) => {
+                        self.timers.insert(id.to_string());
+                    }
+                }
+            }
+        }
+
+        fn elect(&mut self, initiators: &[usize]) {
+            for i in initiators {
+                let id = format!("server_{}", i);
+                let view = self.view(&id);
+                let actions = self.strategies.get_mut(&id).unwrap().start(&view);
+                self.run(&id, actions);
+            }
+            loop {
+                if let Some((to, message)) = self.in_flight.pop_front() {
+                    let view = self.view(&to);
+                    let actions = self.strategies.get_mut(&to).unwrap().handle(message, &view);
+                    self.run(&to, actions);
+                } else if let Some(id) = self.timers.pop_first() {
+                    let view = self.view(&id);
+                    let actions = self.strategies.get_mut(&id).unwrap().timeout(&view);
+                    self.run(&id, actions);
+                } else {
+                    break;
+                }
+            }
+        }
+
+        /// Asserts that every coordinator alive follows the same leader, the lowest ID alive.
+        fn assert_one_leader(&self) {
+            let leaders: BTreeSet<&String> = self
+                .alive
+                .iter()
+                .map(|id| self.leaders.get(id).expect("A coordinator has no leader"))
+                .collect();
+            assert_eq!(leaders.len(), 1, "Leaders: {:?}", leaders);
+            assert_eq!(leaders.first(), self.alive.first().as_ref());
+        }
+    }
+
+    const KINDS: [ElectionKind; 2] = [ElectionKind::Ring, ElectionKind::Bully];
+
+    #[test]
+    fn exactly_one_leader_emerges() {
+        for kind in KINDS {
+            let mut ring = Ring::new(kind, 4, &[]);
+            ring.elect(&[3]);
+            ring.assert_one_leader();
+        }
+    }
+
+    #[test]
+    fn exactly_one_leader_emerges_when_the_leader_is_dead() {
+        for kind in KINDS {
+            let mut ring = Ring::new(kind, 4, &[0]);
+            ring.elect(&[2]);
+            ring.assert_one_leader();
+            assert_eq!(ring.leaders["server_3"], "server_1", "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn exactly_one_leader_emerges_from_concurrent_elections() {
+        for kind in KINDS {
+            let mut ring = Ring::new(kind, 4, &[1]);
+            ring.elect(&[0, 2, 3]);
+            ring.assert_one_leader();
+            assert!(
+                ring.elected.iter().all(|leader| leader == "server_0"),
+                "{:?} elected {:?}",
+                kind,
+                ring.elected
+            );
+        }
+    }
+
+    #[test]
+    fn a_lone_coordinator_elects_itself() {
+        for kind in KINDS {
+            let mut ring = Ring::new(kind, 3, &[0, 1]);
+            ring.elect(&[2]);
+            ring.assert_one_leader();
+            assert_eq!(ring.elected, vec!["server_2".to_string()]);
+        }
+    }
+}
@@ -0,0 +1,643 @@
+use crate::messages::internal_messages::{GossipReceived, MemberStateChanged};
+use crate::server_actors::coordinator_manager::CoordinatorManager;
+use actix::prelude::*;
+use colored::Color;
+use common::constants::{
+    GOSSIP_ACK_TIMEOUT, GOSSIP_INDIRECT_PROBES, GOSSIP_INTERVAL, GOSSIP_PORT_OFFSET,
+    GOSSIP_RETRANSMISSIONS, GOSSIP_SUSPICION_TIMEOUT,
+};
+use common::logger::Logger;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+
+/// Environment variable that enables the gossip failure detector (`1`, `true` or `on`).
+pub const GOSSIP_ENV_VAR: &str = "GOSSIP_FAILURE_DETECTOR";
+
+/// Returns whether [`GOSSIP_ENV_VAR`] enables the gossip failure detector.
+pub fn is_enabled() -> bool {
+    std::env::var(GOSSIP_ENV_VAR).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "on"
+        )
+    })
+}
+
+/// Returns the UDP address the failure detector of the coordinator at `addr` listens on.
+pub fn gossip_addr(addr: SocketAddr) -> SocketAddr {
+    let mut gossip_addr = addr;
+    gossip_addr.set_port(addr.port() + GOSSIP_PORT_OFFSET);
+    gossip_addr
+}
+
+/// Liveness of a coordinator, as seen by the failure detector.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemberState {
+    /// It answers the probes.
+    Alive,
+    /// It did not answer a probe, directly nor through other coordinators.
+    Suspect,
+    /// It stayed suspect for [`GOSSIP_SUSPICION_TIMEOUT`].
+    Dead,
+}
+
+/// A change in the liveness of a coordinator, spread piggybacked on the probes.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MemberUpdate {
+    /// ID of the coordinator.
+    pub id: String,
+    /// Its new state.
+    pub state: MemberState,
+    /// Incarnation the state refers to. Only the coordinator itself increases it, to refute
+    /// that it is suspect or dead.
+    pub incarnation: u64,
+}
+
+impl MemberUpdate {
+    /// Returns whether the update replaces the state `state` of incarnation `incarnation`.
+    fn overrides(&self, state: MemberState, incarnation: u64) -> bool {
+        match (self.state, state) {
+            (MemberState::Alive, _) => self.incarnation > incarnation,
+            (MemberState::Suspect, MemberState::Alive) => self.incarnation >= incarnation,
+            (MemberState::Suspect, _) => self.incarnation > incarnation,
+            (MemberState::Dead, MemberState::Dead) => false,
+            (MemberState::Dead, _) => self.incarnation >= incarnation,
+        }
+    }
+}
+
+/// What a coordinator knows about another one.
+#[derive(Debug, Clone)]
+struct Member {
+    /// Current state.
+    state: MemberState,
+    /// Incarnation of the state.
+    incarnation: u64,
+    /// When it became suspect, while it is.
+    suspected_at: Option<Instant>,
+}
+
+/// The liveness of every coordinator of the ring, following the SWIM protocol.
+///
+/// Each change is spread [`GOSSIP_RETRANSMISSIONS`] times piggybacked on the probes, so
+/// every coordinator learns it without extra messages. A coordinator that learns it is
+/// suspect or dead refutes it by increasing its incarnation.
+#[derive(Debug, Clone)]
+pub struct Membership {
+    /// ID of this coordinator.
+    id: String,
+    /// Incarnation of this coordinator.
+    incarnation: u64,
+    /// The other coordinators, by ID.
+    members: BTreeMap<String, Member>,
+    /// Position of the next coordinator to probe, in ID order.
+    next_probe: usize,
+    /// Updates still to be spread, with the times each has left.
+    updates: Vec<(MemberUpdate, u32)>,
+}
+
+impl Membership {
+    /// Creates a membership where every other coordinator is alive.
+    ///
+    /// ## Arguments
+    /// * `id` - The ID of this coordinator.
+    /// * `members` - The IDs of the other coordinators.
+    pub fn new(id: String, members: impl IntoIterator<Item = String>) -> Self {
+        let members = members
+            .into_iter()
+            .filter(|member| *member != id)
+            .map(|member| {
+                (
+                    member,
+                    Member {
+                        state: MemberState::Alive,
+                        incarnation: 0,
+                        suspected_at: None,
+                    },
+                )
+            })
+            .collect();
+        Self {
+            id,
+            incarnation: 0,
+            members,
+            next_probe: 0,
+            updates: Vec::new(),
+        }
+    }
+
+    /// Returns the incarnation of this coordinator.
+    pub fn incarnation(&self) -> u64 {
+        self.incarnation
+    }
+
+    /// Returns the state of the coordinator `id`, if it is a member.
+    pub fn state(&self, id: &str) -> Option<MemberState> {
+        self.members.get(id).map(|member| member.state)
+    }
+
+    /// Applies an update received from another coordinator.
+    ///
+    /// ## Returns
+    /// The new state of the coordinator, if it changed.
+    pub fn apply(&mut self, update: MemberUpdate, now: Instant) -> Option<MemberState> {
+        if update.id == self.id {
+            // Me dan por sospechoso o caído: lo desmiento con una encarnación nueva
+            if update.state != MemberState::Alive && update.incarnation >= self.incarnation {
+                self.incarnation = update.incarnation + 1;
+                self.spread(MemberUpdate {
+                    id: self.id.clone(),
+                    state: MemberState::Alive,
+                    incarnation: self.incarnation,
+                });
+            }
+            return None;
+        }
+        let member = self.members.get_mut(&update.id)?;
+        if !update.overrides(member.state, member.incarnation) {
+            return None;
+        }
+        let changed = member.state != update.state;
+        member.incarnation = update.incarnation;
+        member.state = update.state;
+        member.suspected_at = (update.state == MemberState::Suspect).then_some(now);
+        self.spread(update.clone());
+        changed.then_some(update.state)
+    }
+
+    /// Marks the coordinator `id` as suspect, after it did not answer a probe.
+    ///
+    /// ## Returns
+    /// Whether it was alive until now.
+    pub fn suspect(&mut self, id: &str, now: Instant) -> bool {
+        let Some(member) = self.members.get(id) else {
+            return false;
+        };
+        if member.state != MemberState::Alive {
+            return false;
+        }
+        let update = MemberUpdate {
+            id: id.to_string(),
+            state: MemberState::Suspect,
+            incarnation: member.incarnation,
+        };
+        self.apply(update, now).is_some()
+    }
+
+    /// Marks as dead the coordinators suspect for longer than `timeout`.
+    ///
+    /// ## Returns
+    /// The IDs of the coordinators marked as dead.
+    pub fn expire_suspects(&mut self, now: Instant, timeout: Duration) -> Vec<String> {
+        let expired: Vec<MemberUpdate> = self
+            .members
+            .iter()
+            .filter(|(_, member)| {
+                member
+                    .suspected_at
+                    .is_some_and(|since| now.duration_since(since) >= timeout)
+            })
+            .map(|(id, member)| MemberUpdate {
+                id: id.clone(),
+                state: MemberState::Dead,
+                incarnation: member.incarnation,
+            })
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|update| {
+                let id = update.id.clone();
+                self.apply(update, now).map(|_| id)
+            })
+            .collect()
+    }
+
+    /// Returns the next coordinator to probe, going through all of them in turn.
+    ///
+    /// The dead ones are probed too, to notice when they come back.
+    pub fn next_probe_target(&mut self) -> Option<String> {
+        if self.members.is_empty() {
+            return None;
+        }
+        let index = self.next_probe % self.members.len();
+        self.next_probe = index + 1;
+        self.members.keys().nth(index).cloned()
+    }
+
+    /// Returns up to `count` coordinators, other than `target` and not dead, to probe
+    /// `target` on behalf of this one.
+    pub fn helpers(&self, target: &str, count: usize) -> Vec<String> {
+        let candidates: Vec<&String> = self
+            .members
+            .iter()
+            .filter(|(id, member)| *id != target && member.state != MemberState::Dead)
+            .map(|(id, _)| id)
+            .collect();
+        candidates
+            .choose_multiple(&mut rand::thread_rng(), count)
+            .map(|id| (*id).clone())
+            .collect()
+    }
+
+    /// Returns the updates to piggyback on the next message, counting it as one of the
+    /// times each is spread.
+    pub fn piggyback(&mut self) -> Vec<MemberUpdate> {
+        let updates = self
+            .updates
+            .iter()
+            .map(|(update, _)| update.clone())
+            .collect();
+        for (_, left) in self.updates.iter_mut() {
+            *left -= 1;
+        }
+        self.updates.retain(|(_, left)| *left > 0);
+        updates
+    }
+
+    /// Queues `update` to be spread, replacing the older update of the same coordinator.
+    fn spread(&mut self, update: MemberUpdate) {
+        self.updates.retain(|(queued, _)| queued.id != update.id);
+        self.updates.push((update, GOSSIP_RETRANSMISSIONS));
+    }
+}
+
+/// Kind of a [`GossipPacket`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum GossipKind {
+    /// Asks the receiver to answer with an [`GossipKind::Ack`] of the same sequence number.
+    Ping { seq: u64 },
+    /// Asks the receiver to probe `target` and forward its answer.
+    PingReq { seq: u64, target: String },
+    /// Answers the probe `seq`.
+    Ack { seq: u64 },
+}
+
+/// A datagram exchanged between the failure detectors.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct GossipPacket {
+    /// ID of the coordinator that sent it.
+    pub from: String,
+    /// Incarnation of the coordinator that sent it.
+    pub incarnation: u64,
+    /// What the packet asks or answers.
+    pub kind: GossipKind,
+    /// Changes in the liveness of the coordinators, piggybacked.
+    pub updates: Vec<MemberUpdate>,
+}
+
+/// A probe waiting for its [`GossipKind::Ack`].
+#[derive(Debug, Clone)]
+struct Probe {
+    /// ID of the coordinator probed.
+    target: String,
+    /// Coordinator that asked for the probe and the sequence number of its own probe, if
+    /// the probe is made on behalf of another coordinator.
+    requester: Option<(String, u64)>,
+}
+
+/// The `FailureDetector` actor tracks the liveness of every coordinator of the ring with
+/// SWIM-style gossip over UDP.
+///
+/// ## Responsibilities:
+/// - Every [`GOSSIP_INTERVAL`], probes one coordinator with a `Ping`, going through all of
+///   them in turn.
+/// - If it does not answer in [`GOSSIP_ACK_TIMEOUT`], asks [`GOSSIP_INDIRECT_PROBES`] other
+///   coordinators to probe it, so a single bad link does not mark it as suspect.
+/// - Marks it as suspect if no answer arrives before the next probe, and as dead after
+///   [`GOSSIP_SUSPICION_TIMEOUT`] without a refutation.
+/// - Reports every change of state to the `CoordinatorManager`.
+pub struct FailureDetector {
+    /// ID of this coordinator.
+    pub id: String,
+    /// Address the UDP socket binds to.
+    pub addr: SocketAddr,
+    /// UDP address of each other coordinator, by ID.
+    pub peers: HashMap<String, SocketAddr>,
+    /// Liveness of the coordinators.
+    pub membership: Membership,
+    /// Address of the `CoordinatorManager` actor to report changes to.
+    pub coordinator_manager: Addr<CoordinatorManager>,
+    /// The UDP socket, once bound.
+    socket: Option<Arc<UdpSocket>>,
+    /// Sequence number of the next probe.
+    next_seq: u64,
+    /// Sequence number of the probe of the current period.
+    current_probe: Option<u64>,
+    /// Probes waiting for an answer, by sequence number.
+    probes: HashMap<u64, Probe>,
+    /// Logger instance for events.
+    pub logger: Logger,
+}
+
+impl FailureDetector {
+    /// Creates a new `FailureDetector`.
+    ///
+    /// ## Arguments
+    /// * `id` - The ID of this coordinator.
+    /// * `my_addr` - The address this coordinator listens on.
+    /// * `ring_nodes` - The address of every coordinator of the ring, by ID.
+    /// * `coordinator_manager` - The address of the `CoordinatorManager` actor.
+    pub fn new(
+        id: String,
+        my_addr: SocketAddr,
+        ring_nodes: &HashMap<String, SocketAddr>,
+        coordinator_manager: Addr<CoordinatorManager>,
+    ) -> Self {
+        let peers: HashMap<String, SocketAddr> = ring_nodes
+            .iter()
+            .filter(|(peer_id, _)| **peer_id != id)
+            .map(|(peer_id, addr)| (peer_id.clone(), gossip_addr(*addr)))
+            .collect();
+        Self {
+            membership: Membership::new(id.clone(), peers.keys().cloned()),
+            id,
+            addr: gossip_addr(my_addr),
+            peers,
+            coordinator_manager,
+            socket: None,
+            next_seq: 0,
+            current_probe: None,
+            probes: HashMap::new(),
+            logger: Logger::new("Failure Detector", Color::BrightMagenta),
+        }
+    }
+
+    /// Sends a packet of kind `kind` to the coordinator `to`, with the pending updates.
+    fn send(&mut self, to: &str, kind: GossipKind) {
+        let (Some(socket), Some(addr)) = (&self.socket, self.peers.get(to)) else {
+            return;
+        };
+        let packet = GossipPacket {
+            from: self.id.clone(),
+            incarnation: self.membership.incarnation(),
+            kind,
+            updates: self.membership.piggyback(),
+        };
+        let Ok(bytes) = serde_json::to_vec(&packet) else {
+            return;
+        };
+        // UDP: si no se puede enviar, se da por perdido como cualquier otro datagrama
+        if let Err(e) = socket.try_send_to(&bytes, *addr) {
+            self.logger
+                .warn(format!("Error sending gossip to {}: {}", to, e));
+        }
+    }
+
+    /// Returns a new sequence number.
+    fn new_seq(&mut self) -> u64 {
+        self.next_seq += 1;
+        self.next_seq
+    }
+
+    /// Reports a change of state of the coordinator `id` to the `CoordinatorManager`.
+    fn report(&self, id: &str, state: MemberState) {
+        self.logger
+            .info(format!("Coordinator {} is now {:?}", id, state));
+        self.coordinator_manager.do_send(MemberStateChanged {
+            coordinator_id: id.to_string(),
+            state,
+        });
+    }
+
+    /// Runs a period of the protocol: settles the probe of the previous period, expires
+    /// the suspects and probes the next coordinator.
+    fn run_period(&mut self, ctx: &mut Context<Self>) {
+        let now = Instant::now();
+        if let Some(seq) = self.current_probe.take()
+            && let Some(probe) = self.probes.get(&seq)
+        {
+            let target = probe.target.clone();
+            if self.membership.suspect(&target, now) {
+                self.report(&target, MemberState::Suspect);
+            }
+        }
+        // Las sondas tienen un período para ser respondidas
+        self.probes.clear();
+        for id in self
+            .membership
+            .expire_suspects(now, GOSSIP_SUSPICION_TIMEOUT)
+        {
+            self.report(&id, MemberState::Dead);
+        }
+
+        let Some(target) = self.membership.next_probe_target() else {
+            return;
+        };
+        let seq = self.new_seq();
+        self.probes.insert(
+            seq,
+            Probe {
+                target: target.clone(),
+                requester: None,
+            },
+        );
+        self.current_probe = Some(seq);
+        self.send(&target, GossipKind::Ping { seq });
+
+        ctx.run_later(GOSSIP_ACK_TIMEOUT, move |act, _ctx| {
+            if !act.probes.contains_key(&seq) {
+                return;
+            }
+            for helper in act.membership.helpers(&target, GOSSIP_INDIRECT_PROBES) {
+                act.send(
+                    &helper,
+                    GossipKind::PingReq {
+                        seq,
+                        target: target.clone(),
+                    },
+                );
+            }
+        });
+    }
+}
+
+impl Actor for FailureDetector {
+    type Context = Context<Self>;
+
+    /// Binds the UDP socket, spawns the task that receives the packets and starts probing.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let addr = self.addr;
+        let detector = ctx.address();
+        let logger = self.logger.clone();
+
+        async move { UdpSocket::bind(addr).await.map(Arc::new) }
+            .into_actor(self)
+            .map(move |result, act, ctx| {
+                let socket = match result {
+                    Ok(socket) => socket,
+                    Err(e) => {
+                        logger.error(format!("Error binding to {}: {}", addr, e));
+                        ctx.stop();
+                        return;
+                    }
+                };
+                logger.info(format!("Gossiping on udp://{}", addr));
+                act.socket = Some(socket.clone());
+
+                tokio::spawn(async move {
+                    let mut buffer = vec![0u8; 64 * 1024];
+                    loop {
+                        match socket.recv_from(&mut buffer).await {
+                            Ok((len, _)) => {
+                                match serde_json::from_slice::<GossipPacket>(&buffer[..len]) {
+                                    Ok(packet) => detector.do_send(GossipReceived { packet }),
+                                    Err(e) => logger.warn(format!("Invalid gossip packet: {}", e)),
+                                }
+                            }
+                            Err(e) => logger.warn(format!("Error receiving gossip: {}", e)),
+                        }
+                    }
+                });
+                ctx.run_interval(GOSSIP_INTERVAL, |act, ctx| act.run_period(ctx));
+            })
+            .wait(ctx);
+    }
+}
+
+/// Handles a packet received from the failure detector of another coordinator.
+impl Handler<GossipReceived> for FailureDetector {
+    type Result = ();
+
+    fn handle(&mut self, msg: GossipReceived, _ctx: &mut Self::Context) {
+        let packet = msg.packet;
+        let now = Instant::now();
+        // Recibir algo de un coordinador es prueba de que está vivo
+        let sender = MemberUpdate {
+            id: packet.from.clone(),
+            state: MemberState::Alive,
+            incarnation: packet.incarnation,
+        };
+        for update in std::iter::once(sender).chain(packet.updates) {
+            let id = update.id.clone();
+            if let Some(state) = self.membership.apply(update, now) {
+                self.report(&id, state);
+            }
+        }
+
+        match packet.kind {
+            GossipKind::Ping { seq } => self.send(&packet.from, GossipKind::Ack { seq }),
+            GossipKind::PingReq { seq, target } => {
+                let own_seq = self.new_seq();
+                self.probes.insert(
+                    own_seq,
+                    Probe {
+                        target: target.clone(),
+                        requester: Some((packet.from, seq)),
+                    },
+                );
+                self.send(&target, GossipKind::Ping { seq: own_seq });
+            }
+            GossipKind::Ack { seq } => {
+                if let Some(Probe {
+                    requester: Some((requester, requester_seq)),
+                    ..
+                }) = self.probes.remove(&seq)
+                {
+                    self.send(&requester, GossipKind::Ack { seq: requester_seq });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(id: &str, state: MemberState, incarnation: u64) -> MemberUpdate {
+        MemberUpdate {
+            id: id.to_string(),
+            state,
+            incarnation,
+        }
+    }
+
+    fn membership() -> Membership {
+        Membership::new(
+            "server_0".to_string(),
+            ["server_0", "server_1", "server_2"].map(String::from),
+        )
+    }
+
+    #[test]
+    fn suspects_and_then_declares_dead_a_silent_member() {
+        let mut membership = membership();
+        let start = Instant::now();
+        assert!(membership.suspect("server_1", start));
+        assert!(!membership.suspect("server_1", start));
+        assert_eq!(membership.state("server_1"), Some(MemberState::Suspect));
+        assert!(
+            membership
+                .expire_suspects(start + Duration::from_secs(1), Duration::from_secs(3))
+                .is_empty()
+        );
+        assert_eq!(
+            membership.expire_suspects(start + Duration::from_secs(3), Duration::from_secs(3)),
+            vec!["server_1".to_string()]
+        );
+        assert_eq!(membership.state("server_1"), Some(MemberState::Dead));
+        // Los caídos no ayudan a sondear
+        assert_eq!(membership.helpers("server_2", 2), Vec::<String>::new());
+    }
+
+    #[test]
+    fn only_a_newer_incarnation_refutes_a_suspicion() {
+        let mut membership = membership();
+        let now = Instant::now();
+        membership.suspect("server_1", now);
+        assert_eq!(
+            membership.apply(update("server_1", MemberState::Alive, 0), now),
+            None
+        );
+        assert_eq!(
+            membership.apply(update("server_1", MemberState::Alive, 1), now),
+            Some(MemberState::Alive)
+        );
+        // Una sospecha vieja no pisa la refutación
+        assert_eq!(
+            membership.apply(update("server_1", MemberState::Suspect, 0), now),
+            None
+        );
+        assert_eq!(
+            membership.apply(update("server_1", MemberState::Dead, 1), now),
+            Some(MemberState::Dead)
+        );
+        // Vuelve a unirse con una encarnación nueva
+        assert_eq!(
+            membership.apply(update("server_1", MemberState::Alive, 2), now),
+            Some(MemberState::Alive)
+        );
+    }
+
+    #[test]
+    fn refutes_the_rumors_about_itself_and_spreads_them() {
+        let mut membership = membership();
+        let now = Instant::now();
+        membership.apply(update("server_0", MemberState::Dead, 0), now);
+        assert_eq!(membership.incarnation(), 1);
+        let gossip = membership.piggyback();
+        assert_eq!(gossip, vec![update("server_0", MemberState::Alive, 1)]);
+
+        for _ in 1..GOSSIP_RETRANSMISSIONS {
+            assert_eq!(membership.piggyback(), gossip);
+        }
+        assert!(membership.piggyback().is_empty());
+    }
+
+    #[test]
+    fn probes_every_member_in_turn() {
+        let mut membership = membership();
+        let targets: Vec<String> = (0..4)
+            .filter_map(|_| membership.next_probe_target())
+            .collect();
+        assert_eq!(
+            targets,
+            vec!["server_1", "server_2", "server_1", "server_2"]
+        );
+    }
+}
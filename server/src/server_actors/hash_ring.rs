@@ -0,0 +1,120 @@
+use common::constants::USER_SHARD_VIRTUAL_NODES;
+use std::collections::BTreeMap;
+
+/// Environment variable used to spread users across the coordinators by their ID instead
+/// of connecting all of them to the leader.
+pub const USER_SHARDING_ENV_VAR: &str = "USER_SHARDING";
+
+/// Returns whether [`USER_SHARDING_ENV_VAR`] enables user sharding.
+pub fn is_enabled() -> bool {
+    std::env::var(USER_SHARDING_ENV_VAR).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "on"
+        )
+    })
+}
+
+/// Assigns each user to a coordinator by consistent hashing of its ID.
+///
+/// Every coordinator is placed on the ring [`USER_SHARD_VIRTUAL_NODES`] times, and a user
+/// belongs to the first coordinator found clockwise from the hash of its ID. When a
+/// coordinator goes down only its users move, to the next coordinators of the ring, and
+/// they come back once it is up again.
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    /// The coordinator IDs, by the position of each of their points on the ring.
+    points: BTreeMap<u64, String>,
+}
+
+impl HashRing {
+    /// Creates a ring with the given coordinators.
+    ///
+    /// ## Arguments
+    /// * `coordinator_ids` - The IDs of every coordinator of the cluster.
+    pub fn new<'a>(coordinator_ids: impl IntoIterator<Item = &'a String>) -> Self {
+        Self::with_virtual_nodes(coordinator_ids, USER_SHARD_VIRTUAL_NODES)
+    }
+
+    /// Creates a ring placing each coordinator `virtual_nodes` times.
+    fn with_virtual_nodes<'a>(
+        coordinator_ids: impl IntoIterator<Item = &'a String>,
+        virtual_nodes: usize,
+    ) -> Self {
+        let mut points = BTreeMap::new();
+        for id in coordinator_ids {
+            for replica in 0..virtual_nodes.max(1) {
+                points.insert(hash(&format!("{}#{}", id, replica)), id.clone());
+            }
+        }
+        Self { points }
+    }
+
+    /// Returns the coordinator a user belongs to among the ones that are up.
+    ///
+    /// ## Arguments
+    /// * `user_id` - The ID of the user.
+    /// * `is_up` - Whether a coordinator, by its ID, can take users.
+    ///
+    /// ## Returns
+    /// The ID of the coordinator, or `None` if none is up.
+    pub fn home(&self, user_id: &str, is_up: impl Fn(&str) -> bool) -> Option<&str> {
+        let start = hash(user_id);
+        self.points
+            .range(start..)
+            .chain(self.points.range(..start))
+            .map(|(_, id)| id.as_str())
+            .find(|id| is_up(id))
+    }
+}
+
+/// FNV-1a hash of `key`, the same in every process and Rust version.
+fn hash(key: &str) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    let hash = key.bytes().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+    });
+    // Mezcla final para que IDs parecidos queden lejos en el anillo
+    let hash = (hash ^ (hash >> 33)).wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^ (hash >> 33)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn ring() -> HashRing {
+        let ids: Vec<String> = (0..4).map(|n| format!("server_{}", n)).collect();
+        HashRing::new(&ids)
+    }
+
+    #[test]
+    fn spreads_the_users_across_the_coordinators() {
+        let ring = ring();
+        let mut users_per_coordinator: HashMap<&str, usize> = HashMap::new();
+        for n in 0..4000 {
+            let home = ring.home(&format!("client_{}", n), |_| true).unwrap();
+            *users_per_coordinator.entry(home).or_default() += 1;
+        }
+        assert_eq!(users_per_coordinator.len(), 4);
+        assert!(users_per_coordinator.values().all(|&users| users > 600));
+    }
+
+    #[test]
+    fn only_moves_the_users_of_a_coordinator_that_is_down() {
+        let ring = ring();
+        for n in 0..1000 {
+            let user = format!("delivery_{}", n);
+            let home = ring.home(&user, |_| true).unwrap();
+            let without_server_2 = ring.home(&user, |id| id != "server_2").unwrap();
+            if home == "server_2" {
+                assert_ne!(without_server_2, "server_2");
+            } else {
+                assert_eq!(without_server_2, home);
+            }
+        }
+        assert_eq!(ring.home("client_1", |_| false), None);
+    }
+}
@@ -1,5 +1,18 @@
+pub mod circuit_breaker;
 pub mod coordinator;
+pub mod coordinator_error;
 pub mod coordinator_manager;
+pub mod dead_letters;
+pub mod election;
+pub mod gossip;
+pub mod hash_ring;
+pub mod offer_scheduler;
+pub mod order_reaper;
+pub mod rate_limiter;
 pub mod reaper;
 pub mod services;
 pub mod storage;
+pub mod storage_backend;
+pub mod storage_log;
+pub mod storage_view;
+pub mod user_collector;
@@ -0,0 +1,173 @@
+use common::constants::MAX_OPEN_DELIVERY_OFFERS;
+use common::messages::coordinator_messages::NearbyDeliveries;
+use std::collections::{BTreeMap, HashSet};
+
+/// Environment variable with the number of orders that can be offered to the deliveries at
+/// the same time.
+pub const MAX_OPEN_OFFERS_ENV_VAR: &str = "MAX_OPEN_OFFERS";
+
+/// Reads the number of open offers from [`MAX_OPEN_OFFERS_ENV_VAR`], or
+/// [`MAX_OPEN_DELIVERY_OFFERS`] if it is not set or is not a positive number.
+pub fn max_open_from_env() -> usize {
+    match std::env::var(MAX_OPEN_OFFERS_ENV_VAR) {
+        Ok(value) => match value.trim().parse() {
+            Ok(max_open) if max_open > 0 => max_open,
+            _ => {
+                eprintln!(
+                    "[Coordinator] Ignoring invalid {}: '{}'",
+                    MAX_OPEN_OFFERS_ENV_VAR, value
+                );
+                MAX_OPEN_DELIVERY_OFFERS
+            }
+        },
+        Err(_) => MAX_OPEN_DELIVERY_OFFERS,
+    }
+}
+
+/// Limits how many orders are offered to the deliveries at the same time.
+///
+/// An order is open from the moment its offers are broadcast until a delivery takes it, its
+/// offers expire or it is cancelled. The orders found while the limit is reached wait in a
+/// queue, and the oldest one (the lowest ID, as the leader assigns increasing IDs) is offered
+/// as soon as another one is closed.
+#[derive(Debug, Clone)]
+pub struct OfferScheduler {
+    /// Maximum number of open orders.
+    max_open: usize,
+    /// IDs of the open orders.
+    open: HashSet<u64>,
+    /// Deliveries found for each waiting order, by order ID.
+    queued: BTreeMap<u64, NearbyDeliveries>,
+}
+
+impl Default for OfferScheduler {
+    fn default() -> Self {
+        Self::new(MAX_OPEN_DELIVERY_OFFERS)
+    }
+}
+
+impl OfferScheduler {
+    /// Creates a scheduler allowing `max_open` open orders, at least one.
+    pub fn new(max_open: usize) -> Self {
+        Self {
+            max_open: max_open.max(1),
+            open: HashSet::new(),
+            queued: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a scheduler with the limit of [`max_open_from_env`].
+    pub fn from_env() -> Self {
+        Self::new(max_open_from_env())
+    }
+
+    /// Schedules the offers of an order.
+    ///
+    /// ## Returns
+    /// The offers, if they can be broadcast now, or `None` if they were queued.
+    pub fn offer(&mut self, offers: NearbyDeliveries) -> Option<NearbyDeliveries> {
+        let order_id = offers.order.order_id;
+        if self.open.contains(&order_id) || self.open.len() < self.max_open {
+            self.queued.remove(&order_id);
+            self.open.insert(order_id);
+            return Some(offers);
+        }
+        // Si el pedido ya esperaba, se queda con los deliveries más recientes
+        self.queued.insert(order_id, offers);
+        None
+    }
+
+    /// Closes the order `order_id`, or takes it out of the queue.
+    ///
+    /// ## Returns
+    /// The offers of the oldest waiting order, if closing this one made room for it.
+    pub fn close(&mut self, order_id: u64) -> Option<NearbyDeliveries> {
+        self.queued.remove(&order_id);
+        if !self.open.remove(&order_id) || self.open.len() >= self.max_open {
+            return None;
+        }
+        let (next_id, offers) = self.queued.pop_first()?;
+        self.open.insert(next_id);
+        Some(offers)
+    }
+
+    /// Returns whether the order `order_id` is waiting to be offered.
+    pub fn is_queued(&self, order_id: u64) -> bool {
+        self.queued.contains_key(&order_id)
+    }
+
+    /// Returns the number of orders offered right now.
+    pub fn open_count(&self) -> usize {
+        self.open.len()
+    }
+
+    /// Returns the number of orders waiting to be offered.
+    pub fn queued_count(&self) -> usize {
+        self.queued.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::dtos::OrderDTO;
+    use common::types::hlc::HlcTimestamp;
+    use common::types::order_priority::OrderPriority;
+    use common::types::order_status::OrderStatus;
+    use common::types::tenant::TenantId;
+
+    fn offers(order_id: u64) -> NearbyDeliveries {
+        NearbyDeliveries {
+            order: OrderDTO {
+                order_id,
+                client_id: "client_1".to_string(),
+                restaurant_id: "resto_1".to_string(),
+                items: Vec::new(),
+                status: OrderStatus::ReadyForDelivery,
+                delivery_id: None,
+                time_stamp: HlcTimestamp::default(),
+                client_position: (0.0, 0.0),
+                expected_delivery_time: 0,
+                expected_preparation_time: 0,
+                priority: OrderPriority::Standard,
+                discount: 0,
+                tenant_id: TenantId::default(),
+            },
+            deliveries: Vec::new(),
+        }
+    }
+
+    fn id(offers: Option<NearbyDeliveries>) -> Option<u64> {
+        offers.map(|offers| offers.order.order_id)
+    }
+
+    #[test]
+    fn queues_the_orders_over_the_limit_and_offers_the_oldest_first() {
+        let mut scheduler = OfferScheduler::new(2);
+        assert_eq!(id(scheduler.offer(offers(10))), Some(10));
+        assert_eq!(id(scheduler.offer(offers(11))), Some(11));
+        assert_eq!(id(scheduler.offer(offers(14))), None);
+        assert_eq!(id(scheduler.offer(offers(12))), None);
+        assert_eq!((scheduler.open_count(), scheduler.queued_count()), (2, 2));
+        assert!(scheduler.is_queued(14));
+
+        // Volver a ofrecer un pedido abierto no ocupa otro lugar
+        assert_eq!(id(scheduler.offer(offers(11))), Some(11));
+        assert_eq!(id(scheduler.close(10)), Some(12));
+        assert_eq!(id(scheduler.close(11)), Some(14));
+        assert_eq!(id(scheduler.close(12)), None);
+        assert_eq!((scheduler.open_count(), scheduler.queued_count()), (1, 0));
+    }
+
+    #[test]
+    fn closing_a_waiting_order_takes_it_out_of_the_queue() {
+        let mut scheduler = OfferScheduler::new(1);
+        scheduler.offer(offers(1));
+        scheduler.offer(offers(2));
+        scheduler.offer(offers(3));
+        assert_eq!(id(scheduler.close(2)), None);
+        assert!(!scheduler.is_queued(2));
+        assert_eq!(id(scheduler.close(7)), None);
+        assert_eq!(id(scheduler.close(1)), Some(3));
+    }
+}
@@ -0,0 +1,310 @@
+use crate::messages::internal_messages::{
+    GetAllStorage, GetLeader, GetOrderReaperStats, RetryDeliverySearch,
+};
+use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::services::order_service_pool::OrderServicePool;
+use crate::server_actors::storage::Storage;
+use actix::prelude::*;
+use colored::Color;
+use common::constants::{
+    ORDER_REAPER_INTERVAL, ORDER_REAPER_MAX_RETRIES, STUCK_DELIVERY_TIMEOUT, STUCK_KITCHEN_TIMEOUT,
+    STUCK_PAYMENT_TIMEOUT,
+};
+use common::logger::Logger;
+use common::messages::CancelMyOrder;
+use common::types::dtos::OrderDTO;
+use common::types::order_status::OrderStatus;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// What to do with an order stuck in the same status for too long.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StuckAction {
+    /// Cancel the order and refund the client.
+    Cancel,
+    /// Search for a delivery again.
+    RetryDelivery,
+}
+
+/// Counters of the stuck orders found by the [`OrderReaper`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct OrderReaperStats {
+    /// Scans of the storage made while leader.
+    pub scans: u64,
+    /// Orders found stuck.
+    pub stuck: u64,
+    /// Stuck orders cancelled.
+    pub cancelled: u64,
+    /// Delivery searches retried for stuck orders.
+    pub retried: u64,
+}
+
+/// Returns how long an order can stay in `status` before it is considered stuck, or `None`
+/// if the status is final or handled elsewhere.
+fn stuck_timeout(status: &OrderStatus) -> Option<Duration> {
+    match status {
+        OrderStatus::Requested | OrderStatus::Authorized => Some(STUCK_PAYMENT_TIMEOUT),
+        OrderStatus::Pending | OrderStatus::Preparing => Some(STUCK_KITCHEN_TIMEOUT),
+        OrderStatus::ReadyForDelivery => Some(STUCK_DELIVERY_TIMEOUT),
+        // Los pedidos en camino se reasignan si se pierde su delivery
+        _ => None,
+    }
+}
+
+/// An order seen in a scan.
+#[derive(Debug, Clone)]
+struct Tracked {
+    /// Status of the order in the last scan.
+    status: OrderStatus,
+    /// When the order was first seen in that status, or last acted on.
+    since: Instant,
+    /// Delivery searches retried in this status.
+    retries: u32,
+}
+
+/// Tracks how long each order stays in the same status across scans of the storage.
+#[derive(Debug, Default)]
+pub struct StuckOrderTracker {
+    /// The orders seen in the last scan.
+    seen: HashMap<u64, Tracked>,
+}
+
+impl StuckOrderTracker {
+    /// Records the orders currently in storage.
+    ///
+    /// ## Returns
+    /// The orders stuck in their status for too long, with what to do with each. Orders
+    /// waiting for a delivery are retried up to [`ORDER_REAPER_MAX_RETRIES`] times before being
+    /// cancelled. The timer of an order starts again after acting on it.
+    pub fn scan<'a>(
+        &mut self,
+        orders: impl IntoIterator<Item = &'a OrderDTO>,
+        now: Instant,
+    ) -> Vec<(OrderDTO, StuckAction)> {
+        let mut seen = HashMap::new();
+        let mut stuck = Vec::new();
+        for order in orders {
+            let Some(timeout) = stuck_timeout(&order.status) else {
+                continue;
+            };
+            let mut tracked = match self.seen.remove(&order.order_id) {
+                Some(tracked) if tracked.status == order.status => tracked,
+                _ => Tracked {
+                    status: order.status.clone(),
+                    since: now,
+                    retries: 0,
+                },
+            };
+            if now.duration_since(tracked.since) >= timeout {
+                let action = if order.status == OrderStatus::ReadyForDelivery
+                    && tracked.retries < ORDER_REAPER_MAX_RETRIES
+                {
+                    tracked.retries += 1;
+                    StuckAction::RetryDelivery
+                } else {
+                    StuckAction::Cancel
+                };
+                tracked.since = now;
+                stuck.push((order.clone(), action));
+            }
+            seen.insert(order.order_id, tracked);
+        }
+        // Los pedidos que ya no están o terminaron se olvidan
+        self.seen = seen;
+        stuck
+    }
+
+    /// Forgets every order, so that timers start again.
+    pub fn clear(&mut self) {
+        self.seen.clear();
+    }
+}
+
+/// The `OrderReaper` actor periodically scans the storage of the leader for orders stuck in
+/// a non-final status, and cancels or retries them.
+///
+/// ## Responsibilities
+/// - Cancels orders whose payment or preparation never finished.
+/// - Searches again for a delivery for orders ready to be delivered that none took.
+/// - Counts the stuck orders found, cancelled and retried.
+///
+/// Only the leader acts on stuck orders: followers keep their timers empty, so a new leader
+/// gives every order the full timeout.
+pub struct OrderReaper {
+    /// The address of this coordinator.
+    my_addr: SocketAddr,
+    /// The storage scanned for stuck orders.
+    storage: Addr<Storage>,
+    /// The coordinator that retries delivery searches and knows the leader.
+    coordinator: Addr<Coordinator>,
+    /// The service that cancels orders and refunds them.
+    order_service: OrderServicePool,
+    /// How long each order has been in its status.
+    tracker: StuckOrderTracker,
+    /// Counters of the stuck orders.
+    stats: OrderReaperStats,
+    /// Logger for order reaper events.
+    logger: Logger,
+}
+
+impl OrderReaper {
+    /// Creates a new `OrderReaper`.
+    ///
+    /// ## Arguments
+    /// * `my_addr` - The address of this coordinator.
+    /// * `storage` - The storage scanned for stuck orders.
+    /// * `coordinator` - The coordinator that retries delivery searches.
+    /// * `order_service` - The service that cancels orders.
+    pub fn new(
+        my_addr: SocketAddr,
+        storage: Addr<Storage>,
+        coordinator: Addr<Coordinator>,
+        order_service: OrderServicePool,
+    ) -> Self {
+        Self {
+            my_addr,
+            storage,
+            coordinator,
+            order_service,
+            tracker: StuckOrderTracker::default(),
+            stats: OrderReaperStats::default(),
+            logger: Logger::new("Order Reaper", Color::BrightRed),
+        }
+    }
+
+    /// Scans the storage if this coordinator is the leader.
+    fn scan(&mut self, ctx: &mut Context<Self>) {
+        let coordinator = self.coordinator.clone();
+        let storage = self.storage.clone();
+        let my_addr = self.my_addr;
+        async move {
+            if coordinator.send(GetLeader).await.ok().flatten() != Some(my_addr) {
+                return None;
+            }
+            storage.send(GetAllStorage).await.ok()
+        }
+        .into_actor(self)
+        .map(|snapshot, act, _ctx| {
+            let Some(snapshot) = snapshot else {
+                act.tracker.clear();
+                return;
+            };
+            act.stats.scans += 1;
+            let stuck = act.tracker.scan(snapshot.orders.values(), Instant::now());
+            for (order, action) in stuck {
+                act.stats.stuck += 1;
+                act.act_on(order, action);
+            }
+        })
+        .spawn(ctx);
+    }
+
+    /// Cancels or retries a stuck order.
+    fn act_on(&mut self, order: OrderDTO, action: StuckAction) {
+        match action {
+            StuckAction::Cancel => {
+                self.stats.cancelled += 1;
+                self.logger.warn(format!(
+                    "Order {} stuck in {:?}, cancelling it",
+                    order.order_id, order.status
+                ));
+                self.order_service
+                    .for_order(order.order_id)
+                    .do_send(CancelMyOrder {
+                        client_id: order.client_id,
+                        order_id: order.order_id,
+                        // El pedido se trabó en el sistema, no por culpa del cliente
+                        waive_fee: true,
+                    });
+            }
+            StuckAction::RetryDelivery => {
+                self.stats.retried += 1;
+                self.logger.warn(format!(
+                    "Order {} still has no delivery, searching again",
+                    order.order_id
+                ));
+                self.coordinator.do_send(RetryDeliverySearch {
+                    order_id: order.order_id,
+                    radius: None,
+                });
+            }
+        }
+        self.logger
+            .info(format!("Stuck orders so far: {:?}", self.stats));
+    }
+}
+
+impl Actor for OrderReaper {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(ORDER_REAPER_INTERVAL, |act, ctx| act.scan(ctx));
+    }
+}
+
+/// Handles requests for the counters of stuck orders.
+impl Handler<GetOrderReaperStats> for OrderReaper {
+    type Result = MessageResult<GetOrderReaperStats>;
+
+    fn handle(&mut self, _msg: GetOrderReaperStats, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.stats)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::dtos::OrderItem;
+
+    fn order(order_id: u64, status: OrderStatus) -> OrderDTO {
+        OrderDTO {
+            status,
+            ..OrderDTO::new(
+                order_id,
+                "client",
+                "restaurant",
+                vec![OrderItem::new("Pizza", 1)],
+            )
+        }
+    }
+
+    #[test]
+    fn cancels_orders_that_stay_in_the_same_status_too_long() {
+        let start = Instant::now();
+        let mut tracker = StuckOrderTracker::default();
+        let orders = [
+            order(1, OrderStatus::Pending),
+            order(2, OrderStatus::Delivering),
+        ];
+        assert!(tracker.scan(&orders, start).is_empty());
+
+        // Un cambio de estado reinicia el tiempo
+        let moved = [order(1, OrderStatus::Preparing)];
+        let later = start + STUCK_KITCHEN_TIMEOUT;
+        assert!(tracker.scan(&moved, later).is_empty());
+
+        let stuck = tracker.scan(&moved, later + STUCK_KITCHEN_TIMEOUT);
+        assert_eq!(stuck.len(), 1);
+        assert_eq!(stuck[0].0.order_id, 1);
+        assert_eq!(stuck[0].1, StuckAction::Cancel);
+    }
+
+    #[test]
+    fn retries_the_delivery_search_before_cancelling() {
+        let mut now = Instant::now();
+        let mut tracker = StuckOrderTracker::default();
+        let orders = [order(1, OrderStatus::ReadyForDelivery)];
+        tracker.scan(&orders, now);
+        let mut actions = Vec::new();
+        for _ in 0..=ORDER_REAPER_MAX_RETRIES {
+            now += STUCK_DELIVERY_TIMEOUT;
+            actions.extend(tracker.scan(&orders, now).into_iter().map(|(_, a)| a));
+        }
+        let mut expected = vec![StuckAction::RetryDelivery; ORDER_REAPER_MAX_RETRIES as usize];
+        expected.push(StuckAction::Cancel);
+        assert_eq!(actions, expected);
+    }
+}
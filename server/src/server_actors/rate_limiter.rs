@@ -0,0 +1,165 @@
+use common::constants::{RATE_LIMIT_BURST, RATE_LIMIT_MAX_STRIKES, RATE_LIMIT_PER_SECOND};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Who a message is charged to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// A connection whose user is not known yet.
+    Peer(SocketAddr),
+    /// A registered user, whatever connection it uses.
+    User(String),
+}
+
+/// What to do with a message after charging it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateDecision {
+    /// The message can be handled.
+    Allowed,
+    /// The message must be dropped and the sender asked to retry later.
+    Throttled,
+    /// The sender kept flooding after being throttled and must be disconnected.
+    Abusive,
+}
+
+/// A token bucket: each message takes a token, and tokens come back at a fixed rate.
+#[derive(Debug, Clone)]
+struct Bucket {
+    /// Tokens left.
+    tokens: f64,
+    /// Last time tokens were added.
+    last_refill: Instant,
+    /// Messages throttled since the bucket was last full.
+    strikes: u32,
+}
+
+/// Token-bucket rate limiter for the messages users send to a coordinator.
+///
+/// Each key can send [`RATE_LIMIT_BURST`] messages at once and then
+/// [`RATE_LIMIT_PER_SECOND`] per second. A key throttled [`RATE_LIMIT_MAX_STRIKES`] times
+/// without letting its bucket fill up again is considered abusive.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    /// Maximum number of tokens of a bucket.
+    burst: f64,
+    /// Tokens added to a bucket per second.
+    per_second: f64,
+    /// Throttled messages after which a key is abusive.
+    max_strikes: u32,
+    /// Bucket of each key.
+    buckets: HashMap<RateLimitKey, Bucket>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new(
+            RATE_LIMIT_BURST,
+            RATE_LIMIT_PER_SECOND,
+            RATE_LIMIT_MAX_STRIKES,
+        )
+    }
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter.
+    ///
+    /// ## Arguments
+    /// * `burst` - Messages a key can send at once.
+    /// * `per_second` - Messages a key can send per second after the burst.
+    /// * `max_strikes` - Throttled messages after which a key is abusive.
+    pub fn new(burst: u32, per_second: f64, max_strikes: u32) -> Self {
+        Self {
+            burst: burst as f64,
+            per_second,
+            max_strikes,
+            buckets: HashMap::new(),
+        }
+    }
+
+    /// Charges a message to `key`.
+    pub fn check(&mut self, key: RateLimitKey, now: Instant) -> RateDecision {
+        let bucket = self.buckets.entry(key).or_insert(Bucket {
+            tokens: self.burst,
+            last_refill: now,
+            strikes: 0,
+        });
+        let elapsed = now.saturating_duration_since(bucket.last_refill);
+        bucket.tokens = (bucket.tokens + elapsed.as_secs_f64() * self.per_second).min(self.burst);
+        bucket.last_refill = now;
+        if bucket.tokens >= self.burst {
+            bucket.strikes = 0;
+        }
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            RateDecision::Allowed
+        } else {
+            bucket.strikes += 1;
+            if bucket.strikes >= self.max_strikes {
+                RateDecision::Abusive
+            } else {
+                RateDecision::Throttled
+            }
+        }
+    }
+
+    /// Returns how long `key` has to wait for its next token, as of its last message.
+    pub fn retry_after(&self, key: &RateLimitKey) -> Duration {
+        match self.buckets.get(key) {
+            Some(bucket) if bucket.tokens < 1.0 && self.per_second > 0.0 => {
+                Duration::from_secs_f64((1.0 - bucket.tokens) / self.per_second)
+            }
+            _ => Duration::ZERO,
+        }
+    }
+
+    /// Forgets `key`, e.g. once its connection is closed.
+    pub fn forget(&mut self, key: &RateLimitKey) {
+        self.buckets.remove(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn user(id: &str) -> RateLimitKey {
+        RateLimitKey::User(id.to_string())
+    }
+
+    #[test]
+    fn throttles_after_the_burst_until_tokens_come_back() {
+        let mut limiter = RateLimiter::new(2, 1.0, 10);
+        let now = Instant::now();
+        assert_eq!(limiter.check(user("a"), now), RateDecision::Allowed);
+        assert_eq!(limiter.check(user("a"), now), RateDecision::Allowed);
+        assert_eq!(limiter.check(user("a"), now), RateDecision::Throttled);
+        // Cada clave tiene su propio balde
+        assert_eq!(limiter.check(user("b"), now), RateDecision::Allowed);
+
+        assert_eq!(limiter.retry_after(&user("a")), Duration::from_secs(1));
+        assert_eq!(limiter.retry_after(&user("b")), Duration::ZERO);
+
+        let later = now + Duration::from_secs(1);
+        assert_eq!(limiter.check(user("a"), later), RateDecision::Allowed);
+        assert_eq!(limiter.check(user("a"), later), RateDecision::Throttled);
+    }
+
+    #[test]
+    fn flags_keys_that_keep_flooding() {
+        let mut limiter = RateLimiter::new(1, 1.0, 3);
+        let now = Instant::now();
+        assert_eq!(limiter.check(user("a"), now), RateDecision::Allowed);
+        assert_eq!(limiter.check(user("a"), now), RateDecision::Throttled);
+        assert_eq!(limiter.check(user("a"), now), RateDecision::Throttled);
+        assert_eq!(limiter.check(user("a"), now), RateDecision::Abusive);
+
+        // Un balde lleno otra vez perdona los intentos anteriores
+        let later = now + Duration::from_secs(5);
+        assert_eq!(limiter.check(user("a"), later), RateDecision::Allowed);
+        assert_eq!(limiter.check(user("a"), later), RateDecision::Throttled);
+    }
+}
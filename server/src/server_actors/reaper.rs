@@ -1,26 +1,37 @@
-use crate::messages::internal_messages::{ReapUser, ReconnectUser};
+use crate::messages::internal_messages::{ReapUser, ReassignOrder, ReconnectUser};
+use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::coordinator_error::CoordinatorError;
 use crate::server_actors::storage::Storage;
 use actix::SpawnHandle;
 use actix::prelude::*;
-use common::constants::REAP_TIMEOUT;
-use common::messages::internal_messages::RemoveUser;
+use common::constants::{DELIVERY_GRACE_PERIOD, REAP_TIMEOUT};
+use common::messages::internal_messages::{GetDeliveryOrders, RemoveUser};
 use std::collections::HashMap;
+use std::time::Duration;
 
 /// The `Reaper` actor is responsible for managing user reaping operations.
 /// It handles the reaping of users after a specified timeout and manages user reconnections.
 ///
 /// ## Responsibilities
 /// - Reaps users after a timeout by sending a message to the `Storage` actor.
+/// - Asks the `Coordinator` to reassign the orders of a delivery that did not reconnect
+///   within the grace period.
 /// - Cancels the reaping timer when a user reconnects.
 ///
 /// ## Fields
 /// - `users_timer`: A map that associates user IDs with their respective timer handles.
 /// - `storage_addr`: The address of the `Storage` actor to which messages are sent
+/// - `coordinator_addr`: The address of the `Coordinator` that reassigns orders.
+/// - `grace_period`: How long a disconnected delivery keeps its orders.
 pub struct Reaper {
     /// A map of user IDs to their associated timer handles.
     pub users_timer: HashMap<String, SpawnHandle>,
     /// The address of the storage actor to send messages to.
     pub storage_addr: Addr<Storage>,
+    /// The address of the coordinator that reassigns the orders of disconnected deliveries.
+    pub coordinator_addr: Addr<Coordinator>,
+    /// How long a disconnected delivery keeps its orders, at most [`REAP_TIMEOUT`].
+    pub grace_period: Duration,
 }
 
 impl Reaper {
@@ -28,34 +39,104 @@ impl Reaper {
     ///
     /// ## Parameters
     /// - `storage_addr`: The address of the `Storage` actor to send messages to.
-    pub fn new(storage_addr: Addr<Storage>) -> Self {
+    /// - `coordinator_addr`: The address of the `Coordinator` that reassigns orders.
+    /// - `grace_period`: How long a disconnected delivery keeps its orders.
+    pub fn new(
+        storage_addr: Addr<Storage>,
+        coordinator_addr: Addr<Coordinator>,
+        grace_period: Duration,
+    ) -> Self {
         Reaper {
             users_timer: HashMap::new(),
             storage_addr,
+            coordinator_addr,
+            // Los pedidos se reasignan antes de borrar al delivery
+            grace_period: grace_period.min(REAP_TIMEOUT),
         }
     }
 }
 
+/// Environment variable with the seconds a disconnected delivery keeps its orders before they
+/// are reassigned.
+pub const GRACE_PERIOD_ENV_VAR: &str = "DELIVERY_GRACE_PERIOD";
+
+/// Reads the grace period from [`GRACE_PERIOD_ENV_VAR`], or [`DELIVERY_GRACE_PERIOD`] if it is
+/// not set or is invalid.
+pub fn grace_period_from_env() -> Duration {
+    match std::env::var(GRACE_PERIOD_ENV_VAR) {
+        Ok(value) => value
+            .trim()
+            .parse()
+            .map(Duration::from_secs)
+            .unwrap_or_else(|_| {
+                eprintln!(
+                    "[Reaper] Ignoring invalid {}: '{}'",
+                    GRACE_PERIOD_ENV_VAR, value
+                );
+                DELIVERY_GRACE_PERIOD
+            }),
+        Err(_) => DELIVERY_GRACE_PERIOD,
+    }
+}
+
 impl Actor for Reaper {
     type Context = Context<Self>;
 }
 
 /// Handles the `ReapUser` messages.
 /// This handler sets a timer to reap a user after a specified timeout.
-/// When the timer expires, it sends a message to the `Storage` actor to remove the user
-/// and removes the user from the timer map.
+/// After the grace period, the orders the user was carrying or about to carry as a delivery
+/// are handed to the `Coordinator` to be reassigned. When the timer expires, it sends a
+/// message to the `Storage` actor to remove the user and removes the user from the timer map.
 impl Handler<ReapUser> for Reaper {
     type Result = ();
 
     fn handle(&mut self, msg: ReapUser, ctx: &mut Self::Context) -> Self::Result {
         let user_id = msg.user_id.clone();
-        let handle = ctx.run_later(REAP_TIMEOUT, move |act, _ctx| {
-            // Notify the storage actor to reap the user
-            act.storage_addr.do_send(RemoveUser {
-                user_id: user_id.clone(),
+        let handle = ctx.run_later(self.grace_period, move |act, ctx| {
+            let storage = act.storage_addr.clone();
+            let coordinator = act.coordinator_addr.clone();
+            let delivery_id = user_id.clone();
+            async move {
+                let orders = storage
+                    .send(GetDeliveryOrders {
+                        delivery_id: delivery_id.clone(),
+                    })
+                    .await
+                    .unwrap_or_default();
+                for order in orders {
+                    let order_id = order.order_id;
+                    let result = coordinator
+                        .send(ReassignOrder {
+                            order_id,
+                            delivery_id: delivery_id.clone(),
+                        })
+                        .await
+                        .unwrap_or_else(|e| Err(e.into()));
+                    match result {
+                        // Si el pedido ya no existe no hay nada que reasignar
+                        Ok(()) | Err(CoordinatorError::OrderNotFound(_)) => {}
+                        Err(e) => {
+                            eprintln!("[Reaper] Cannot reassign order {}: {}", order_id, e);
+                        }
+                    }
+                }
+            }
+            .into_actor(act)
+            .spawn(ctx);
+
+            // El resto del tiempo se sigue esperando a que se reconecte antes de borrarlo
+            let remaining = REAP_TIMEOUT.saturating_sub(act.grace_period);
+            let reaped_id = user_id.clone();
+            let handle = ctx.run_later(remaining, move |act, _ctx| {
+                // Notify the storage actor to reap the user
+                act.storage_addr.do_send(RemoveUser {
+                    user_id: reaped_id.clone(),
+                });
+                // Remove the user from the timer map
+                act.users_timer.remove(&reaped_id);
             });
-            // Remove the user from the timer map
-            act.users_timer.remove(&user_id);
+            act.users_timer.insert(user_id, handle);
         });
         // Store the handle in the timer map
         self.users_timer.insert(msg.user_id, handle);
@@ -0,0 +1,306 @@
+use crate::dashboard;
+use crate::messages::internal_messages::{
+    GetAllStorage, GetClusterSnapshot, GetCompactionStatus, GetConnectedPeers, GetDeadLetterStats,
+    GetKitchenLoads, GetLeader, GetOrderReaperStats, GetRingState,
+};
+use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::coordinator_manager::CoordinatorManager;
+use crate::server_actors::dead_letters::DeadLetterStats;
+use crate::server_actors::order_reaper::{OrderReaper, OrderReaperStats};
+use crate::server_actors::storage::{CompactionStatus, Storage};
+use actix::prelude::*;
+use actix_web::dev::ServerHandle;
+use colored::Color;
+use common::constants::{DASHBOARD_LEADER_CHECK_INTERVAL, DASHBOARD_PORT_OFFSET};
+use common::logger::Logger;
+use common::messages::internal_messages::GetOrderTimeline;
+use common::network::outbound::OutboundStats;
+use common::types::dtos::{DeliveryDTO, ElectionRecord, OrderDTO};
+use common::types::kitchen_load::KitchenLoad;
+use common::types::order_event::OrderEvent;
+use common::types::restaurant_info::RestaurantInfo;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Whether a coordinator of the ring is reachable from this one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoordinatorStatus {
+    /// ID of the coordinator.
+    pub id: String,
+    /// Address of the coordinator.
+    pub addr: SocketAddr,
+    /// Whether there is an open connection with it (always `true` for this coordinator).
+    pub connected: bool,
+}
+
+/// The state of the ring as seen by a `CoordinatorManager`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RingState {
+    /// Address of the current leader, if any.
+    pub leader: Option<SocketAddr>,
+    /// Epoch of the current leader.
+    pub epoch: u64,
+    /// Every coordinator of the ring, sorted by ID.
+    pub coordinators: Vec<CoordinatorStatus>,
+    /// The most recent leader changes, oldest first.
+    pub elections: Vec<ElectionRecord>,
+}
+
+/// How far a coordinator that pulls the storage log from this one has applied it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowerProgress {
+    /// ID of the coordinator.
+    pub coordinator_id: String,
+    /// Index of the last log entry it applied.
+    pub applied_index: u64,
+    /// Time since it last reported its progress.
+    pub reported_ago: Duration,
+}
+
+/// A user connected to a coordinator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectedPeer {
+    /// ID announced by the user, if known.
+    pub user_id: Option<String>,
+    /// Address of the connection.
+    pub addr: SocketAddr,
+    /// Type of the peer (client, restaurant, delivery, ...).
+    pub peer_type: String,
+    /// Depth and drops of the queue of messages sent to the peer.
+    pub outbound: OutboundStats,
+}
+
+/// Everything the dashboard shows about the cluster.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterSnapshot {
+    /// ID of the coordinator that took the snapshot.
+    pub node_id: String,
+    /// The state of the ring.
+    pub ring: Option<RingState>,
+    /// Users connected to this coordinator.
+    pub peers: Vec<ConnectedPeer>,
+    /// Every order in storage, most recently updated first.
+    pub orders: Vec<OrderDTO>,
+    /// Every delivery in storage, with its position and status.
+    pub deliveries: Vec<DeliveryDTO>,
+    /// Every restaurant in storage, with its position.
+    pub restaurants: Vec<RestaurantInfo>,
+    /// Stuck orders found by the order reaper of this coordinator.
+    pub stuck_orders: OrderReaperStats,
+    /// State of the storage log of this coordinator and its compactions.
+    pub log_compaction: CompactionStatus,
+    /// Messages this coordinator could not deliver to its users.
+    #[serde(default)]
+    pub dead_letters: DeadLetterStats,
+    /// Last load reported by the kitchen of each restaurant, by restaurant ID.
+    #[serde(default)]
+    pub kitchen_loads: HashMap<String, KitchenLoad>,
+}
+
+/// The `ClusterSnapshotService` actor aggregates the state of the cluster for the web
+/// dashboard, and serves the dashboard while this coordinator is the leader.
+///
+/// ## Responsibilities
+/// - Builds [`ClusterSnapshot`]s from the `Storage`, the `CoordinatorManager` and the
+///   `Coordinator`.
+/// - Looks up the timeline of an order in the `Storage`.
+/// - Periodically checks who the leader is, starting the dashboard when this coordinator
+///   becomes the leader and stopping it when it stops being so.
+pub struct ClusterSnapshotService {
+    /// ID of this coordinator.
+    pub node_id: String,
+    /// Address of this coordinator.
+    pub my_addr: SocketAddr,
+    /// Address this coordinator listens on. The dashboard listens on its port plus
+    /// [`DASHBOARD_PORT_OFFSET`].
+    pub bind_addr: SocketAddr,
+    /// The address of the Storage actor.
+    pub storage_addr: Addr<Storage>,
+    /// The address of the Coordinator actor.
+    pub coordinator_addr: Addr<Coordinator>,
+    /// The address of the CoordinatorManager actor.
+    pub coordinator_manager_addr: Addr<CoordinatorManager>,
+    /// The address of the OrderReaper actor, if started.
+    pub order_reaper_addr: Option<Addr<OrderReaper>>,
+    /// Handle of the running dashboard, if any.
+    dashboard: Option<ServerHandle>,
+    /// Logger for service events.
+    pub logger: Logger,
+}
+
+impl ClusterSnapshotService {
+    /// Creates a new `ClusterSnapshotService`.
+    ///
+    /// ## Arguments
+    /// * `node_id` - ID of this coordinator.
+    /// * `my_addr` - Address of this coordinator. The dashboard listens on its port plus
+    ///   [`DASHBOARD_PORT_OFFSET`], unless another address to listen on is given with
+    ///   [`ClusterSnapshotService::with_bind_addr`].
+    /// * `storage_addr` - The address of the Storage actor.
+    /// * `coordinator_addr` - The address of the Coordinator actor.
+    /// * `coordinator_manager_addr` - The address of the CoordinatorManager actor.
+    /// * `order_reaper_addr` - The address of the OrderReaper actor, if started.
+    pub fn new(
+        node_id: String,
+        my_addr: SocketAddr,
+        storage_addr: Addr<Storage>,
+        coordinator_addr: Addr<Coordinator>,
+        coordinator_manager_addr: Addr<CoordinatorManager>,
+        order_reaper_addr: Option<Addr<OrderReaper>>,
+    ) -> Self {
+        Self {
+            node_id,
+            my_addr,
+            bind_addr: my_addr,
+            storage_addr,
+            coordinator_addr,
+            coordinator_manager_addr,
+            order_reaper_addr,
+            dashboard: None,
+            logger: Logger::new("Cluster Snapshot Service", Color::BrightMagenta),
+        }
+    }
+
+    /// Serves the dashboard from `bind_addr` instead of the address of this coordinator.
+    pub fn with_bind_addr(mut self, bind_addr: SocketAddr) -> Self {
+        self.bind_addr = bind_addr;
+        self
+    }
+
+    /// Starts or stops the dashboard depending on whether this coordinator is the leader.
+    fn check_leadership(&mut self, ctx: &mut Context<Self>) {
+        self.coordinator_addr
+            .send(GetLeader)
+            .into_actor(self)
+            .map(|result, act, ctx| {
+                let is_leader = matches!(result, Ok(Some(leader)) if leader == act.my_addr);
+                match act.dashboard.take() {
+                    None if is_leader => act.start_dashboard(ctx),
+                    Some(handle) if !is_leader => {
+                        act.logger
+                            .info("No longer the leader, stopping the dashboard.");
+                        actix::spawn(async move { handle.stop(true).await });
+                    }
+                    handle => act.dashboard = handle,
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Starts serving the dashboard.
+    fn start_dashboard(&mut self, ctx: &mut Context<Self>) {
+        let mut addr = self.bind_addr;
+        addr.set_port(self.bind_addr.port() + DASHBOARD_PORT_OFFSET);
+        match dashboard::serve(addr, ctx.address()) {
+            Ok(server) => {
+                self.logger
+                    .info(format!("Dashboard available on http://{}", addr));
+                self.dashboard = Some(server.handle());
+                actix::spawn(async move {
+                    let _ = server.await;
+                });
+            }
+            Err(e) => {
+                self.logger
+                    .error(format!("Error starting the dashboard on {}: {}", addr, e));
+            }
+        }
+    }
+}
+
+impl Actor for ClusterSnapshotService {
+    type Context = Context<Self>;
+
+    /// Starts checking periodically whether this coordinator is the leader.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(DASHBOARD_LEADER_CHECK_INTERVAL, |act, ctx| {
+            act.check_leadership(ctx);
+        });
+    }
+
+    /// Stops the dashboard, if it is running.
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        if let Some(handle) = self.dashboard.take() {
+            actix::spawn(async move { handle.stop(false).await });
+        }
+    }
+}
+
+/// Handles requests for a [`ClusterSnapshot`].
+impl Handler<GetClusterSnapshot> for ClusterSnapshotService {
+    type Result = ResponseFuture<ClusterSnapshot>;
+
+    fn handle(&mut self, _msg: GetClusterSnapshot, _ctx: &mut Self::Context) -> Self::Result {
+        let node_id = self.node_id.clone();
+        let storage_addr = self.storage_addr.clone();
+        let coordinator_addr = self.coordinator_addr.clone();
+        let coordinator_manager_addr = self.coordinator_manager_addr.clone();
+        let order_reaper_addr = self.order_reaper_addr.clone();
+
+        Box::pin(async move {
+            let ring = coordinator_manager_addr.send(GetRingState).await.ok();
+            let peers = coordinator_addr
+                .send(GetConnectedPeers)
+                .await
+                .unwrap_or_default();
+            let (mut orders, deliveries, restaurants) = match storage_addr.send(GetAllStorage).await
+            {
+                Ok(snapshot) => (
+                    snapshot.orders.into_values().collect::<Vec<_>>(),
+                    snapshot.deliverys.into_values().collect::<Vec<_>>(),
+                    snapshot
+                        .restaurants
+                        .into_values()
+                        .map(|restaurant| restaurant.info())
+                        .collect::<Vec<_>>(),
+                ),
+                Err(_) => (Vec::new(), Vec::new(), Vec::new()),
+            };
+            orders.sort_by_key(|order| std::cmp::Reverse(order.time_stamp));
+            let stuck_orders = match order_reaper_addr {
+                Some(order_reaper) => order_reaper
+                    .send(GetOrderReaperStats)
+                    .await
+                    .unwrap_or_default(),
+                None => OrderReaperStats::default(),
+            };
+            let log_compaction = storage_addr
+                .send(GetCompactionStatus)
+                .await
+                .unwrap_or_default();
+            let dead_letters = coordinator_addr
+                .send(GetDeadLetterStats)
+                .await
+                .unwrap_or_default();
+            let kitchen_loads = coordinator_addr
+                .send(GetKitchenLoads)
+                .await
+                .unwrap_or_default();
+
+            ClusterSnapshot {
+                node_id,
+                ring,
+                peers,
+                orders,
+                deliveries,
+                restaurants,
+                stuck_orders,
+                log_compaction,
+                dead_letters,
+                kitchen_loads,
+            }
+        })
+    }
+}
+
+/// Handles requests for the timeline of an order, asking the `Storage` for it.
+impl Handler<GetOrderTimeline> for ClusterSnapshotService {
+    type Result = ResponseFuture<Vec<OrderEvent>>;
+
+    fn handle(&mut self, msg: GetOrderTimeline, _ctx: &mut Self::Context) -> Self::Result {
+        let storage_addr = self.storage_addr.clone();
+        Box::pin(async move { storage_addr.send(msg).await.unwrap_or_default() })
+    }
+}
@@ -0,0 +1,425 @@
+use crate::messages::internal_messages::PublishOrderEvent;
+use actix::prelude::*;
+use colored::Color;
+use common::logger::Logger;
+use common::network::stream::Listener;
+use common::types::dtos::OrderDTO;
+use common::types::hlc::HlcTimestamp;
+use common::types::order_event::{OrderEvent, OrderEventKind};
+use common::types::order_status::OrderStatus;
+use common::types::rejection_reason::RejectionReason;
+use common::types::tenant::TenantId;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+/// Environment variable used to select where the order lifecycle events are published.
+pub const EVENT_SINK_ENV_VAR: &str = "EVENT_SINK";
+
+/// Where the [`EventPublisher`] sends the order lifecycle events.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventSinkKind {
+    /// One JSON line per event on the standard output.
+    Stdout,
+    /// One JSON line per event appended to a file.
+    File(PathBuf),
+    /// One JSON line per event to every TCP subscriber connected to the coordinator.
+    Tcp,
+}
+
+impl EventSinkKind {
+    /// Parses a sink: `stdout`, `file:<path>` or `tcp`.
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("stdout") {
+            Some(EventSinkKind::Stdout)
+        } else if value.eq_ignore_ascii_case("tcp") {
+            Some(EventSinkKind::Tcp)
+        } else {
+            value
+                .strip_prefix("file:")
+                .map(str::trim)
+                .filter(|path| !path.is_empty())
+                .map(|path| EventSinkKind::File(PathBuf::from(path)))
+        }
+    }
+
+    /// Reads the sink from [`EVENT_SINK_ENV_VAR`], or `None` if it is not set or is invalid,
+    /// in which case the events are not published.
+    pub fn from_env() -> Option<Self> {
+        let value = std::env::var(EVENT_SINK_ENV_VAR).ok()?;
+        let kind = Self::parse(&value);
+        if kind.is_none() {
+            eprintln!(
+                "[Event Publisher] Ignoring invalid {}: '{}'",
+                EVENT_SINK_ENV_VAR, value
+            );
+        }
+        kind
+    }
+}
+
+/// A destination of the order lifecycle events.
+///
+/// ## Purpose
+/// Lets the [`EventPublisher`] send the events without knowing who consumes them: it only
+/// hands over each event serialized as a line of JSON.
+pub trait EventSink {
+    /// Sends `line`, the JSON of an event without the trailing newline.
+    fn publish(&mut self, line: &str) -> io::Result<()>;
+}
+
+/// An [`EventSink`] that prints the events on the standard output.
+pub struct StdoutSink;
+
+impl EventSink for StdoutSink {
+    fn publish(&mut self, line: &str) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        writeln!(stdout, "{}", line)?;
+        stdout.flush()
+    }
+}
+
+/// An [`EventSink`] that appends the events to a file.
+pub struct FileSink {
+    file: File,
+}
+
+impl FileSink {
+    /// Opens `path` to append the events, creating it if it does not exist.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self { file })
+    }
+}
+
+impl EventSink for FileSink {
+    fn publish(&mut self, line: &str) -> io::Result<()> {
+        // Una sola escritura por línea, así no se mezclan con las de otro coordinador
+        self.file.write_all(format!("{}\n", line).as_bytes())
+    }
+}
+
+/// An [`EventSink`] that sends the events to the TCP subscribers connected to it.
+///
+/// A subscriber only has to connect: from then on it receives one line of JSON per event,
+/// until it closes the connection. The events published before it connected are not sent.
+pub struct TcpSink {
+    /// Channels to the tasks that write to each subscriber.
+    subscribers: Arc<Mutex<Vec<UnboundedSender<String>>>>,
+}
+
+impl TcpSink {
+    /// Listens for subscribers on `addr`. Must be called from the actix runtime.
+    pub fn listen(addr: SocketAddr, logger: Logger) -> Self {
+        let subscribers = Arc::new(Mutex::new(Vec::new()));
+        actix::spawn(accept_subscribers(addr, subscribers.clone(), logger));
+        Self { subscribers }
+    }
+}
+
+impl EventSink for TcpSink {
+    fn publish(&mut self, line: &str) -> io::Result<()> {
+        let mut subscribers = self
+            .subscribers
+            .lock()
+            .map_err(|_| io::Error::other("subscribers lock poisoned"))?;
+        // Los suscriptores que se desconectaron ya no reciben el canal
+        subscribers.retain(|subscriber| subscriber.send(format!("{}\n", line)).is_ok());
+        Ok(())
+    }
+}
+
+/// Accepts the subscribers of a [`TcpSink`], spawning a task per subscriber that writes the
+/// events to its socket until the subscriber closes it.
+async fn accept_subscribers(
+    addr: SocketAddr,
+    subscribers: Arc<Mutex<Vec<UnboundedSender<String>>>>,
+    logger: Logger,
+) {
+    let mut listener = match Listener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            logger.error(format!("Error binding to {}: {}", addr, e));
+            return;
+        }
+    };
+    logger.info(format!("Order events for TCP subscribers on {}", addr));
+    loop {
+        match listener.accept().await {
+            Ok((mut stream, remote_addr)) => {
+                let (tx, mut rx) = unbounded_channel::<String>();
+                if let Ok(mut subscribers) = subscribers.lock() {
+                    subscribers.push(tx);
+                }
+                logger.info(format!("New event subscriber {}", remote_addr));
+                tokio::spawn(async move {
+                    while let Some(line) = rx.recv().await {
+                        if stream.write_all(line.as_bytes()).await.is_err() {
+                            break;
+                        }
+                    }
+                });
+            }
+            Err(e) => {
+                logger.warn(format!("Error accepting connection: {}", e));
+            }
+        }
+    }
+}
+
+/// Opens the sink `kind`. The TCP subscribers connect to `subscribers_addr`.
+pub fn open_sink(
+    kind: &EventSinkKind,
+    subscribers_addr: SocketAddr,
+    logger: Logger,
+) -> io::Result<Box<dyn EventSink>> {
+    Ok(match kind {
+        EventSinkKind::Stdout => Box::new(StdoutSink),
+        EventSinkKind::File(path) => Box::new(FileSink::open(path)?),
+        EventSinkKind::Tcp => Box::new(TcpSink::listen(subscribers_addr, logger)),
+    })
+}
+
+/// A step of the lifecycle of an order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LifecycleStage {
+    /// The order was stored.
+    Created,
+    /// The payment gateway authorized the order.
+    Authorized,
+    /// The restaurant started cooking the order.
+    Preparing,
+    /// A delivery picked the order up.
+    Delivering,
+    /// The delivery handed the order to the client.
+    Delivered,
+    /// The order was removed before it was delivered.
+    Cancelled,
+}
+
+impl LifecycleStage {
+    /// Returns the steps of the lifecycle the event of the timeline of an order stands for,
+    /// oldest first.
+    ///
+    /// An order is only stored once the payment gateway authorized it, so its creation is
+    /// followed by its authorization. An order removed before it was delivered was cancelled,
+    /// by the client, the restaurant or the server.
+    pub fn of(event: &OrderEvent) -> Vec<Self> {
+        match (event.kind, &event.status) {
+            (OrderEventKind::Created, _) => {
+                vec![LifecycleStage::Created, LifecycleStage::Authorized]
+            }
+            (OrderEventKind::StatusChanged, OrderStatus::Preparing) => {
+                vec![LifecycleStage::Preparing]
+            }
+            (OrderEventKind::StatusChanged, OrderStatus::Delivering) => {
+                vec![LifecycleStage::Delivering]
+            }
+            (OrderEventKind::Removed, OrderStatus::Delivered) => vec![LifecycleStage::Delivered],
+            (OrderEventKind::Removed, _) => vec![LifecycleStage::Cancelled],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A step of the lifecycle of an order, as sent to the external consumers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LifecycleEvent {
+    /// The step of the lifecycle.
+    pub stage: LifecycleStage,
+    /// ID of the order.
+    pub order_id: u64,
+    /// The tenant of the order.
+    pub tenant_id: TenantId,
+    /// The client that placed the order.
+    pub client_id: String,
+    /// The restaurant of the order.
+    pub restaurant_id: String,
+    /// The delivery of the order, if any.
+    pub delivery_id: Option<String>,
+    /// The status of the order after the change.
+    pub status: OrderStatus,
+    /// Price of the order, in cents.
+    pub price: u64,
+    /// Who caused the change (e.g. `restaurant resto_1`).
+    pub actor: String,
+    /// ID of the coordinator that applied the change.
+    pub node: String,
+    /// When the change was applied.
+    pub timestamp: HlcTimestamp,
+    /// Why the restaurant rejected the order, if it did.
+    pub reason: Option<RejectionReason>,
+}
+
+impl LifecycleEvent {
+    /// Returns the lifecycle events of `event`, recorded in the timeline of `order`.
+    pub fn from_order_event(order: &OrderDTO, event: &OrderEvent) -> Vec<Self> {
+        LifecycleStage::of(event)
+            .into_iter()
+            .map(|stage| LifecycleEvent {
+                stage,
+                order_id: order.order_id,
+                tenant_id: order.tenant_id.clone(),
+                client_id: order.client_id.clone(),
+                restaurant_id: order.restaurant_id.clone(),
+                delivery_id: event.delivery_id.clone(),
+                status: event.status.clone(),
+                price: order.price(),
+                actor: event.actor.clone(),
+                node: event.node.clone(),
+                timestamp: event.timestamp,
+                reason: event.reason,
+            })
+            .collect()
+    }
+}
+
+/// The `EventPublisher` actor publishes the lifecycle events of the orders (created,
+/// authorized, preparing, delivering, delivered and cancelled) to external consumers.
+///
+/// ## Responsibilities
+/// - Receives from the `Storage` the events of the changes this coordinator applied first,
+///   which are the ones applied by the leader.
+/// - Sends the events that are steps of the lifecycle of an order to an [`EventSink`], as
+///   lines of JSON.
+///
+/// Each event is applied first by a single coordinator, so consumers get it once even if
+/// they listen to every coordinator.
+pub struct EventPublisher {
+    /// Where the events are sent.
+    sink: Box<dyn EventSink>,
+    /// Logger for publisher events.
+    logger: Logger,
+}
+
+impl EventPublisher {
+    /// Creates a new `EventPublisher`.
+    ///
+    /// ## Arguments
+    /// * `sink` - Where the events are sent.
+    pub fn new(sink: Box<dyn EventSink>) -> Self {
+        Self {
+            sink,
+            logger: Self::logger(),
+        }
+    }
+
+    /// Returns the logger of the publisher, also used by its sinks.
+    pub fn logger() -> Logger {
+        Logger::new("Event Publisher", Color::BrightGreen)
+    }
+}
+
+impl Actor for EventPublisher {
+    type Context = Context<Self>;
+}
+
+/// Handles [`PublishOrderEvent`] messages.
+///
+/// Sends the lifecycle events of the order event to the sink, ignoring the other events.
+impl Handler<PublishOrderEvent> for EventPublisher {
+    type Result = ();
+
+    fn handle(&mut self, msg: PublishOrderEvent, _ctx: &mut Self::Context) -> Self::Result {
+        for event in LifecycleEvent::from_order_event(&msg.order, &msg.event) {
+            let line = match serde_json::to_string(&event) {
+                Ok(line) => line,
+                Err(e) => {
+                    self.logger
+                        .error(format!("Error serializing order event: {}", e));
+                    return;
+                }
+            };
+            if let Err(e) = self.sink.publish(&line) {
+                self.logger.warn(format!(
+                    "Error publishing event {:?} of order {}: {}",
+                    event.stage, event.order_id, e
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::dtos::OrderItem;
+    use common::types::order_priority::OrderPriority;
+
+    #[test]
+    fn sinks_are_parsed_from_their_names() {
+        assert_eq!(
+            EventSinkKind::parse(" STDOUT "),
+            Some(EventSinkKind::Stdout)
+        );
+        assert_eq!(EventSinkKind::parse("tcp"), Some(EventSinkKind::Tcp));
+        assert_eq!(
+            EventSinkKind::parse("file: events.jsonl"),
+            Some(EventSinkKind::File(PathBuf::from("events.jsonl")))
+        );
+        assert_eq!(EventSinkKind::parse("file:"), None);
+        assert_eq!(EventSinkKind::parse("kafka"), None);
+    }
+
+    #[test]
+    fn only_the_steps_of_the_lifecycle_are_published() {
+        let mut order = OrderDTO {
+            order_id: 7,
+            client_id: "client_1".to_string(),
+            restaurant_id: "resto_1".to_string(),
+            items: vec![OrderItem::new("Pizza", 1)],
+            status: OrderStatus::Authorized,
+            delivery_id: None,
+            client_position: (0.0, 0.0),
+            expected_delivery_time: 0,
+            expected_preparation_time: 0,
+            priority: OrderPriority::Standard,
+            discount: 0,
+            time_stamp: HlcTimestamp::default(),
+            tenant_id: TenantId::new("staging"),
+        };
+        let stages = |kind, order: &OrderDTO| {
+            LifecycleEvent::from_order_event(order, &OrderEvent::new(kind, order, "server_0"))
+                .into_iter()
+                .map(|event| event.stage)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            stages(OrderEventKind::Created, &order),
+            [LifecycleStage::Created, LifecycleStage::Authorized]
+        );
+        order.status = OrderStatus::Pending;
+        assert!(stages(OrderEventKind::StatusChanged, &order).is_empty());
+        order.status = OrderStatus::Delivering;
+        assert_eq!(
+            stages(OrderEventKind::StatusChanged, &order),
+            [LifecycleStage::Delivering]
+        );
+        assert!(stages(OrderEventKind::DeliveryAssigned, &order).is_empty());
+        order.status = OrderStatus::Delivered;
+        assert_eq!(
+            stages(OrderEventKind::Removed, &order),
+            [LifecycleStage::Delivered]
+        );
+        order.status = OrderStatus::Cancelled;
+        let cancelled = LifecycleEvent::from_order_event(
+            &order,
+            &OrderEvent::new(OrderEventKind::Removed, &order, "server_0"),
+        );
+        assert_eq!(cancelled[0].stage, LifecycleStage::Cancelled);
+        assert_eq!(cancelled[0].tenant_id.as_str(), "staging");
+        assert!(
+            serde_json::to_string(&cancelled[0])
+                .unwrap()
+                .contains("\"stage\":\"cancelled\"")
+        );
+    }
+}
@@ -1,3 +1,8 @@
+pub mod cluster_snapshot;
+pub mod event_publisher;
 pub mod nearby_delivery;
 pub mod nearby_restaurants;
+pub mod notification_service;
+pub mod order_service_pool;
 pub mod orders_services;
+pub mod promotion_service;
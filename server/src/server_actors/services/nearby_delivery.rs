@@ -1,28 +1,38 @@
 use crate::server_actors::coordinator::Coordinator;
 use crate::server_actors::storage::Storage;
+use crate::server_actors::storage_view::StorageReadView;
 use actix::prelude::*;
 use colored::Color;
 use common::constants::NEARBY_RADIUS;
 use common::logger::Logger;
 use common::messages::CancelOrder;
 use common::messages::coordinator_messages::NearbyDeliveries;
-use common::messages::internal_messages::{GetDeliveries, RemoveOrder};
+use common::messages::internal_messages::RemoveOrder;
 use common::messages::restaurant_messages::RequestNearbyDelivery;
-use common::types::dtos::DeliveryDTO;
-use common::utils::calculate_distance;
+use common::types::dtos::{DeliveryDTO, OfferDetails, OrderDTO};
+use common::types::vehicle::VehicleType;
+use common::types::zone::DeliveryZones;
 
 /// The `NearbyDeliveryService` Actor is responsible for tracking nearby delivery users suitable
 /// for a given restaurant's order based on their geographical position.
 ///
 /// ## Responsibilities
-/// - Fetches available deliveries from the storage.
-/// - Filters deliveries based on proximity to the restaurant's position.
-/// - Sends the filtered list of nearby deliveries to the coordinator.
+/// - Reads the deliveries near the restaurant's position from the storage read view.
+/// - Leaves out the deliveries of other tenants than the order's, the ones whose zones do not
+///   cover both the restaurant and the client, and the ones whose vehicle cannot carry the
+///   order.
+/// - Computes the distances and the payout of the order for each delivery.
+/// - Offers an order first to the deliveries whose vehicle suits the length of their trip.
+/// - Sends the filtered list of nearby deliveries, with their offers, to the coordinator.
 pub struct NearbyDeliveryService {
     /// The address of the Coordinator actor to send messages to.
     pub coordinator_address: Addr<Coordinator>,
-    /// The address of the Storage actor to fetch deliveries from.
+    /// The address of the Storage actor, to remove the orders nobody can deliver.
     pub storage_address: Addr<Storage>,
+    /// The read view of the storage to fetch deliveries from.
+    pub storage_view: StorageReadView,
+    /// Zones served by each delivery.
+    pub zones: DeliveryZones,
     /// Logger instance for events
     pub logger: Logger,
 }
@@ -32,38 +42,72 @@ impl NearbyDeliveryService {
     ///
     /// ## Arguments
     /// * `storage_address` - The address of the Storage actor.
+    /// * `storage_view` - The read view of the storage.
     /// * `coordinator_address` - The address of the Coordinator actor.
-    pub fn new(storage_address: Addr<Storage>, coordinator_address: Addr<Coordinator>) -> Self {
+    pub fn new(
+        storage_address: Addr<Storage>,
+        storage_view: StorageReadView,
+        coordinator_address: Addr<Coordinator>,
+    ) -> Self {
         let logger = Logger::new("Nearby Delivery Service", Color::Green);
         NearbyDeliveryService {
             coordinator_address,
             storage_address,
+            storage_view,
+            zones: DeliveryZones::from_env(),
             logger,
         }
     }
 
-    /// Filters the available deliveries to find those that are within a specified radius
-    /// from the restaurant's position.
-    ///
-    /// ## Arguments
-    /// * `available_deliveries` - A vector of `DeliveryDTO` containing all available deliveries.
-    /// * `restaurant_pos` - A tuple representing the restaurant's geographical position (latitude, longitude).
-    ///
-    /// ## Returns
-    /// A vector of `DeliveryDTO` containing only those deliveries that are within the specified radius.
-    fn get_nearby_deliveries(
+    /// Returns the deliveries of the tenant of `order` whose zones cover its route from
+    /// `restaurant`, and whose vehicle carries it.
+    fn can_deliver(
         &self,
-        available_deliveries: Vec<DeliveryDTO>,
-        restaurant_pos: (f32, f32),
+        deliveries: Vec<DeliveryDTO>,
+        order: &OrderDTO,
+        restaurant: (f32, f32),
     ) -> Vec<DeliveryDTO> {
-        available_deliveries
+        deliveries
             .into_iter()
             .filter(|delivery| {
-                let distance = calculate_distance(delivery.delivery_position, restaurant_pos);
-                distance <= NEARBY_RADIUS
+                delivery.tenant_id == order.tenant_id
+                    && delivery.vehicle.carries(order.units())
+                    && self.zones.covers_route(
+                        &delivery.delivery_id,
+                        restaurant,
+                        order.client_position,
+                    )
+            })
+            .collect()
+    }
+
+    /// Pairs each delivery with the [`OfferDetails`] of `order` from its position.
+    fn with_offers(
+        deliveries: Vec<DeliveryDTO>,
+        order: &OrderDTO,
+        restaurant: (f32, f32),
+    ) -> Vec<(DeliveryDTO, OfferDetails)> {
+        deliveries
+            .into_iter()
+            .map(|delivery| {
+                let details = OfferDetails::new(order, delivery.delivery_position, restaurant);
+                (delivery, details)
             })
             .collect()
     }
+
+    /// Keeps the offers to the deliveries whose vehicle is the one suited for their trip, if
+    /// any, and all of them otherwise.
+    fn suited(offers: Vec<(DeliveryDTO, OfferDetails)>) -> Vec<(DeliveryDTO, OfferDetails)> {
+        let suits = |(delivery, details): &(DeliveryDTO, OfferDetails)| {
+            delivery.vehicle == VehicleType::for_trip(details.trip_distance())
+        };
+        if offers.iter().any(suits) {
+            offers.into_iter().filter(suits).collect()
+        } else {
+            offers
+        }
+    }
 }
 
 impl Actor for NearbyDeliveryService {
@@ -73,67 +117,66 @@ impl Actor for NearbyDeliveryService {
 impl Handler<RequestNearbyDelivery> for NearbyDeliveryService {
     type Result = ();
 
-    /// Handles the `RequestNearbyDelivery` message by fetching deliveries from storage,
-    /// filtering them based on proximity to the restaurant's position, and sending the results
-    /// to the Coordinator actor.
-    fn handle(&mut self, msg: RequestNearbyDelivery, ctx: &mut Context<Self>) {
-        let coordinator_addr = self.coordinator_address.clone();
-        let logger = self.logger.clone();
-        let get_nearby_deliveries = NearbyDeliveryService::get_nearby_deliveries;
-        let storage_addr = self.storage_address.clone();
+    /// Handles the `RequestNearbyDelivery` message by reading the deliveries near the
+    /// restaurant, or every delivery if none is near, keeping the ones whose zones cover the
+    /// route of the order and whose vehicle carries it, and sending the result to the
+    /// Coordinator actor. The first time the order is offered, only the deliveries with a
+    /// vehicle suited to their trip get it if there are any; the next offers go to all.
+    fn handle(&mut self, msg: RequestNearbyDelivery, _ctx: &mut Context<Self>) {
         let restaurant = msg.restaurant_info.position;
+        let radius = msg.radius.unwrap_or(NEARBY_RADIUS);
         let order = msg.order;
+        let first_offer = msg.radius.is_none();
+        let offers = |deliveries| {
+            let offers = Self::with_offers(deliveries, &order, restaurant);
+            if first_offer {
+                Self::suited(offers)
+            } else {
+                offers
+            }
+        };
         self.logger.info(format!(
             "Requesting nearby deliveries for order: {:?} at restaurant position: {:?}",
             order.order_id, restaurant
         ));
-        self.storage_address
-            .send(GetDeliveries)
-            .into_actor(self)
-            .map(move |res, act, _ctx| match res {
-                Ok(deliveries) => {
-                    if deliveries.is_empty() {
-                        logger.warn("Retrived  no deliveries from storage.");
-                        coordinator_addr.do_send(CancelOrder {
-                            order: order.clone(),
-                        });
-                        storage_addr.do_send(RemoveOrder { order });
-                    } else {
-                        logger.info(format!(
-                            "Retrieved {} deliveries from storage.",
-                            deliveries.len()
-                        ));
-                        let nearby: Vec<DeliveryDTO> =
-                            get_nearby_deliveries(act, deliveries.clone(), restaurant);
-                        if nearby.is_empty() {
-                            logger.warn(
-                                "No nearby deliveries found for the order. Sending all deliveries.",
-                            );
-                            coordinator_addr.do_send(NearbyDeliveries {
-                                order,
-                                deliveries: deliveries.clone(),
-                            });
-                        } else {
-                            logger.info(format!(
-                                "Found {} nearby deliveries for order: {:?}",
-                                nearby.len(),
-                                order.order_id
-                            ));
-                            coordinator_addr.do_send(NearbyDeliveries {
-                                order,
-                                deliveries: nearby,
-                            });
-                        }
-                    }
-                }
-                Err(_) => {
-                    logger.error("Error retrieving deliveries from storage.");
-                    coordinator_addr.do_send(NearbyDeliveries {
-                        order,
-                        deliveries: Vec::new(),
-                    });
-                }
-            })
-            .wait(ctx);
+
+        // Sólo se le ofrece el pedido a quienes cubren el restaurante y el cliente, y lo
+        // pueden llevar en su vehículo
+        let nearby = self.can_deliver(
+            self.storage_view.nearby_deliveries(restaurant, radius),
+            &order,
+            restaurant,
+        );
+        if !nearby.is_empty() {
+            self.logger.info(format!(
+                "Found {} nearby deliveries for order: {:?}",
+                nearby.len(),
+                order.order_id
+            ));
+            self.coordinator_address.do_send(NearbyDeliveries {
+                deliveries: offers(nearby),
+                order,
+            });
+            return;
+        }
+
+        // Sin deliveries cerca, se le ofrece el pedido a todos
+        let deliveries = self.can_deliver(self.storage_view.deliveries(), &order, restaurant);
+        if deliveries.is_empty() {
+            self.logger.warn("Retrived  no deliveries from storage.");
+            self.coordinator_address.do_send(CancelOrder {
+                order: order.clone(),
+                reason: None,
+            });
+            self.storage_address
+                .do_send(RemoveOrder { order, event: None });
+            return;
+        }
+        self.logger
+            .warn("No nearby deliveries found for the order. Sending all deliveries.");
+        self.coordinator_address.do_send(NearbyDeliveries {
+            deliveries: offers(deliveries),
+            order,
+        });
     }
 }
@@ -1,30 +1,39 @@
 use crate::server_actors::coordinator::Coordinator;
-use crate::server_actors::storage::Storage;
+use crate::server_actors::storage_view::StorageReadView;
 use actix::prelude::*;
 use colored::Color;
-use common::constants::NEARBY_RADIUS;
+use common::constants::{MAX_NEARBY_RADIUS, NEARBY_RADIUS};
 use common::logger::Logger;
 use common::messages::CancelOrder;
 use common::messages::NearbyRestaurants;
 use common::messages::RequestNearbyRestaurants;
-use common::messages::internal_messages::GetAllRestaurantsInfo;
-use common::types::dtos::OrderDTO;
+use common::types::dtos::{ClientDTO, OrderDTO};
+use common::types::hlc::HlcTimestamp;
+use common::types::order_priority::OrderPriority;
 use common::types::order_status::OrderStatus;
+use common::types::restaurant_filter::RestaurantFilter;
 use common::types::restaurant_info::RestaurantInfo;
+use common::types::tenant::TenantId;
 use common::utils::calculate_distance;
+use std::collections::HashMap;
 
 /// The `NearbyRestaurantsService` actor is responsible for handling requests
-/// for nearby restaurants based on the client's location. It retrieves
-/// available restaurants from the storage, filters them based on proximity,
-/// and sends the results back to the coordinator.
+/// for nearby restaurants based on the client's location. It reads
+/// the restaurants near the client from the storage read view and sends the results back to
+/// the coordinator.
 ///
 /// ## Responsibilities:
-/// - Retrieve all available restaurants from the storage.
-/// - Filter restaurants based on the client's location and a predefined radius.
+/// - Retrieve the restaurants within the radius asked by the client, or a predefined one, from
+///   the storage.
+/// - Leave out the restaurants of other tenants than the client's.
+/// - Leave out the restaurants outside their opening hours, unless the client asks for them.
+/// - Search the restaurants by name or tag and sort them as the client asks.
+/// - Fall back to every restaurant if none is near and the client did not ask for a radius.
+/// - Tell the client how far each restaurant is, and which are closed.
 /// - Send the list of nearby restaurants back to the coordinator.
 pub struct NearbyRestaurantsService {
-    /// The address of the Storage actor to fetch restaurants from.
-    pub storage_addr: Addr<Storage>,
+    /// The read view of the storage to fetch restaurants from.
+    pub storage_view: StorageReadView,
     /// The address of the Coordinator actor to send messages to.
     pub coordinator_addr: Addr<Coordinator>,
     /// Logger instance for events
@@ -35,41 +44,71 @@ impl NearbyRestaurantsService {
     /// Creates a new instance of `NearbyRestaurantService`.
     ///
     /// ## Arguments
-    /// * `storage_address` - The address of the Storage actor.
+    /// * `storage_view` - The read view of the storage.
     /// * `coordinator_address` - The address of the Coordinator actor.
-    pub fn new(storage_addr: Addr<Storage>, coordinator_addr: Addr<Coordinator>) -> Self {
+    pub fn new(storage_view: StorageReadView, coordinator_addr: Addr<Coordinator>) -> Self {
         let logger = Logger::new("Nearby Restaurants Service", Color::Green);
         NearbyRestaurantsService {
-            storage_addr,
+            storage_view,
             coordinator_addr,
             logger,
         }
     }
 
-    /// Filters the list of available restaurants to find those within a specified radius
-    /// from the client's location.
-    ///
-    /// ## Arguments
-    /// * `available_restaurants` - A vector of `RestaurantInfo` containing all available restaurants.
-    /// * `location` - A tuple representing the client's location as (latitude, longitude).
-    ///
-    /// ## Returns
-    /// A vector of `RestaurantInfo` containing only the restaurants that are within the specified radius.
-    fn get_nearby_restaurants(
+    /// Leaves out the `restaurants` of other tenants than `tenant_id` and the ones that the
+    /// client did not ask for with its `filter`.
+    fn listed_restaurants(
         &self,
-        available_restaurants: Vec<RestaurantInfo>,
-        location: (f32, f32),
+        mut restaurants: Vec<RestaurantInfo>,
+        tenant_id: &TenantId,
+        filter: &RestaurantFilter,
     ) -> Vec<RestaurantInfo> {
-        available_restaurants
-            .into_iter()
-            .filter(|restaurant| {
-                let distance = calculate_distance(restaurant.position, location);
-                distance <= NEARBY_RADIUS
-            })
-            .collect()
+        restaurants.retain(|restaurant| {
+            restaurant.tenant_id == *tenant_id
+                && (filter.include_closed || self.storage_view.is_restaurant_open(&restaurant.id))
+                && filter.matches(restaurant)
+        });
+        restaurants
+    }
+
+    /// Returns the answer to the client at `location`, listing the `restaurants` in the order
+    /// asked with its `filter`.
+    fn answer(
+        &self,
+        client: ClientDTO,
+        location: (f32, f32),
+        restaurants: Vec<RestaurantInfo>,
+        filter: &RestaurantFilter,
+    ) -> NearbyRestaurants {
+        let distances = distances_from(location, &restaurants);
+        let restaurants = filter.apply(restaurants, &distances);
+        let closed = restaurants
+            .iter()
+            .filter(|restaurant| !self.storage_view.is_restaurant_open(&restaurant.id))
+            .map(|restaurant| restaurant.id.clone())
+            .collect();
+        NearbyRestaurants {
+            client,
+            restaurants,
+            distances,
+            closed,
+        }
     }
 }
 
+/// Returns the distance from `location` to each restaurant, by restaurant ID.
+fn distances_from(location: (f32, f32), restaurants: &[RestaurantInfo]) -> HashMap<String, f32> {
+    restaurants
+        .iter()
+        .map(|restaurant| {
+            (
+                restaurant.id.clone(),
+                calculate_distance(restaurant.position, location),
+            )
+        })
+        .collect()
+}
+
 impl Actor for NearbyRestaurantsService {
     type Context = Context<Self>;
 }
@@ -77,73 +116,76 @@ impl Actor for NearbyRestaurantsService {
 impl Handler<RequestNearbyRestaurants> for NearbyRestaurantsService {
     type Result = ();
 
-    /// Handles the `RequestNearbyRestaurants` message by retrieving all restaurants from storage,
-    /// filtering them based on the client's location, and sending the results back to the coordinator.
-    fn handle(&mut self, msg: RequestNearbyRestaurants, ctx: &mut Self::Context) -> Self::Result {
-        let storage_addr = self.storage_addr.clone();
-        let coordinator_addr = self.coordinator_addr.clone();
-        let logger = self.logger.clone();
+    /// Handles the `RequestNearbyRestaurants` message by retrieving the restaurants near the
+    /// client from storage and sending the results back to the coordinator.
+    fn handle(&mut self, msg: RequestNearbyRestaurants, _ctx: &mut Self::Context) -> Self::Result {
         let client = msg.client.clone();
         let location = msg.client.client_position;
-        let get_nearby_restaurants = NearbyRestaurantsService::get_nearby_restaurants;
 
         let order_dummy_cancelled = OrderDTO {
             order_id: 0,
             client_id: msg.client.client_id,
-            dish_name: "None".to_string(),
+            items: Vec::new(),
             restaurant_id: "None".to_string(),
             status: OrderStatus::Cancelled,
             delivery_id: None,
             client_position: msg.client.client_position,
             expected_delivery_time: 0,
-            time_stamp: std::time::SystemTime::now(),
+            expected_preparation_time: 0,
+            priority: OrderPriority::Standard,
+            discount: 0,
+            tenant_id: msg.client.tenant_id.clone(),
+            time_stamp: HlcTimestamp::now(),
         };
 
-        storage_addr
-            .send(GetAllRestaurantsInfo)
-            .into_actor(self)
-            .map(move |res, act, _ctx| match res {
-                Ok(restaurants) => {
-                    if restaurants.is_empty() {
-                        logger.warn("Retrieved no restaurants from storage.");
-                        coordinator_addr.do_send(CancelOrder {
-                            order: order_dummy_cancelled,
-                        });
-                    } else {
-                        logger.info(format!(
-                            "Retrieved {} restaurants from storage.",
-                            restaurants.len()
-                        ));
-                        let nearby: Vec<RestaurantInfo> =
-                            get_nearby_restaurants(act, restaurants.clone(), location);
+        // El radio pedido por el cliente se respeta, hasta el máximo permitido
+        let radius = msg
+            .radius
+            .map(|radius| radius.clamp(0.0, MAX_NEARBY_RADIUS));
+
+        let filter = msg.filter;
+        let tenant_id = client.tenant_id.clone();
 
-                        if nearby.is_empty() {
-                            logger.warn("No nearby restaurants found.");
-                            coordinator_addr.do_send(NearbyRestaurants {
-                                client,
-                                restaurants: restaurants.clone(),
-                            });
-                        } else {
-                            logger.info(format!(
-                                "Found {} nearby restaurants for client at position: {:?}",
-                                nearby.len(),
-                                location
-                            ));
-                            coordinator_addr.do_send(NearbyRestaurants {
-                                client,
-                                restaurants: nearby,
-                            });
-                        }
-                    }
-                }
-                Err(_) => {
-                    logger.error("Error retrieving restaurants from storage.");
-                    coordinator_addr.do_send(NearbyRestaurants {
-                        client,
-                        restaurants: Vec::new(),
-                    });
-                }
-            })
-            .wait(ctx);
+        let nearby = self.listed_restaurants(
+            self.storage_view
+                .nearby_restaurants(location, radius.unwrap_or(NEARBY_RADIUS)),
+            &tenant_id,
+            &filter,
+        );
+        if !nearby.is_empty() || radius.is_some() {
+            self.logger.info(format!(
+                "Found {} nearby restaurants for client at position: {:?}",
+                nearby.len(),
+                location
+            ));
+            self.coordinator_addr
+                .do_send(self.answer(client, location, nearby, &filter));
+            return;
+        }
+
+        // Sin restaurantes cerca, se le ofrecen todos
+        let restaurants =
+            self.listed_restaurants(self.storage_view.restaurants(), &tenant_id, &filter);
+        if restaurants.is_empty() && (filter.query.is_some() || filter.tag.is_some()) {
+            // Ninguno coincide con la búsqueda: se le responde la lista vacía
+            self.logger.info(format!(
+                "No restaurants match the search {:?} with tag {:?}",
+                filter.query, filter.tag
+            ));
+            self.coordinator_addr
+                .do_send(self.answer(client, location, restaurants, &filter));
+            return;
+        }
+        if restaurants.is_empty() {
+            self.logger.warn("Retrieved no restaurants from storage.");
+            self.coordinator_addr.do_send(CancelOrder {
+                order: order_dummy_cancelled,
+                reason: None,
+            });
+            return;
+        }
+        self.logger.warn("No nearby restaurants found.");
+        self.coordinator_addr
+            .do_send(self.answer(client, location, restaurants, &filter));
     }
 }
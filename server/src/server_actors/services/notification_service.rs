@@ -0,0 +1,335 @@
+use crate::messages::internal_messages::{SubscribeNotifications, UnsubscribeNotifications};
+use actix::prelude::*;
+use colored::Color;
+use common::logger::Logger;
+use common::messages::NotifyOrderUpdated;
+use common::network::stream::{Listener, PeerStream};
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio_tungstenite::accept_async;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// The `NotificationService` actor pushes order status updates to external consumers
+/// over WebSocket.
+///
+/// ## Responsibilities:
+/// - Listens for WebSocket connections; the first text frame names the client to follow.
+/// - Keeps track of the subscribers of each client.
+/// - Forwards every [`NotifyOrderUpdated`] emitted by the `OrderService` to the subscribers
+///   of the order's client, serialized as JSON.
+pub struct NotificationService {
+    /// The address the WebSocket listener binds to.
+    pub addr: SocketAddr,
+    /// Subscribers of each client, keyed by client ID and subscriber ID.
+    pub subscribers: HashMap<String, HashMap<u64, UnboundedSender<String>>>,
+    /// Logger instance for events.
+    pub logger: Logger,
+}
+
+impl NotificationService {
+    /// Creates a new `NotificationService`.
+    ///
+    /// ## Arguments
+    /// * `addr` - The socket address the WebSocket listener binds to.
+    pub fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            subscribers: HashMap::new(),
+            logger: Logger::new("Notification Service", Color::BrightCyan),
+        }
+    }
+}
+
+impl Actor for NotificationService {
+    type Context = Context<Self>;
+
+    /// Binds the WebSocket listener and spawns a task per incoming connection.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        let addr = self.addr;
+        let service = ctx.address();
+        let logger = self.logger.clone();
+
+        ctx.spawn(
+            async move {
+                let mut listener = match Listener::bind(addr).await {
+                    Ok(listener) => listener,
+                    Err(e) => {
+                        logger.error(format!("Error binding to {}: {}", addr, e));
+                        return;
+                    }
+                };
+                logger.info(format!("WebSocket notifications on ws://{}", addr));
+
+                let mut next_subscriber_id: u64 = 0;
+                loop {
+                    match listener.accept().await {
+                        Ok((stream, remote_addr)) => {
+                            next_subscriber_id += 1;
+                            tokio::spawn(serve_subscriber(
+                                stream,
+                                remote_addr,
+                                next_subscriber_id,
+                                service.clone(),
+                                logger.clone(),
+                            ));
+                        }
+                        Err(e) => {
+                            logger.warn(format!("Error accepting connection: {}", e));
+                        }
+                    }
+                }
+            }
+            .into_actor(self),
+        );
+    }
+}
+
+/// Performs the WebSocket handshake, subscribes to the client named in the first frame
+/// and pumps the updates into the socket until either side closes it.
+async fn serve_subscriber(
+    stream: PeerStream,
+    remote_addr: SocketAddr,
+    subscriber_id: u64,
+    service: Addr<NotificationService>,
+    logger: Logger,
+) {
+    let mut ws_stream = match accept_async(stream).await {
+        Ok(ws_stream) => ws_stream,
+        Err(e) => {
+            logger.warn(format!(
+                "WebSocket handshake with {} failed: {}",
+                remote_addr, e
+            ));
+            return;
+        }
+    };
+    // El primer frame de texto indica el cliente al que se suscribe
+    let client_id = match ws_stream.next().await {
+        Some(Ok(WsMessage::Text(client_id))) if !client_id.trim().is_empty() => {
+            client_id.trim().to_string()
+        }
+        _ => {
+            logger.warn(format!("{} did not provide a client ID", remote_addr));
+            return;
+        }
+    };
+
+    let (tx, mut rx) = unbounded_channel();
+    service.do_send(SubscribeNotifications {
+        client_id: client_id.clone(),
+        subscriber_id,
+        sender: tx,
+    });
+
+    let (mut sink, mut incoming) = ws_stream.split();
+    loop {
+        tokio::select! {
+            update = rx.recv() => match update {
+                Some(payload) => {
+                    if sink.send(WsMessage::Text(payload)).await.is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            },
+            frame = incoming.next() => match frame {
+                Some(Ok(WsMessage::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    service.do_send(UnsubscribeNotifications {
+        client_id,
+        subscriber_id,
+    });
+}
+
+/// Handles [`SubscribeNotifications`] messages.
+///
+/// Registers a new subscriber for the given client.
+impl Handler<SubscribeNotifications> for NotificationService {
+    type Result = ();
+
+    fn handle(&mut self, msg: SubscribeNotifications, _ctx: &mut Self::Context) -> Self::Result {
+        self.logger.info(format!(
+            "New subscriber {} for client {}",
+            msg.subscriber_id, msg.client_id
+        ));
+        self.subscribers
+            .entry(msg.client_id)
+            .or_default()
+            .insert(msg.subscriber_id, msg.sender);
+    }
+}
+
+/// Handles [`UnsubscribeNotifications`] messages.
+///
+/// Removes a subscriber, dropping the client entry once it has none left.
+impl Handler<UnsubscribeNotifications> for NotificationService {
+    type Result = ();
+
+    fn handle(&mut self, msg: UnsubscribeNotifications, _ctx: &mut Self::Context) -> Self::Result {
+        if let Some(subscribers) = self.subscribers.get_mut(&msg.client_id) {
+            subscribers.remove(&msg.subscriber_id);
+            if subscribers.is_empty() {
+                self.subscribers.remove(&msg.client_id);
+            }
+        }
+    }
+}
+
+/// Handles [`NotifyOrderUpdated`] messages.
+///
+/// Pushes the updated order to every subscriber of the order's client.
+impl Handler<NotifyOrderUpdated> for NotificationService {
+    type Result = ();
+
+    fn handle(&mut self, msg: NotifyOrderUpdated, _ctx: &mut Self::Context) -> Self::Result {
+        let Some(subscribers) = self.subscribers.get_mut(&msg.order.client_id) else {
+            return;
+        };
+        let payload = match serde_json::to_string(&msg.order) {
+            Ok(payload) => payload,
+            Err(e) => {
+                self.logger
+                    .error(format!("Error serializing order update: {}", e));
+                return;
+            }
+        };
+        subscribers.retain(|_, sender| sender.send(payload.clone()).is_ok());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::dtos::{OrderDTO, OrderItem};
+    use common::types::hlc::HlcTimestamp;
+    use common::types::order_priority::OrderPriority;
+    use common::types::order_status::OrderStatus;
+    use common::types::tenant::TenantId;
+    use std::time::Duration;
+    use tokio::sync::mpsc::error::TryRecvError;
+    use tokio_tungstenite::connect_async;
+
+    fn update(client_id: &str, status: OrderStatus) -> NotifyOrderUpdated {
+        NotifyOrderUpdated {
+            peer_id: client_id.to_string(),
+            order: OrderDTO {
+                order_id: 7,
+                client_id: client_id.to_string(),
+                restaurant_id: "resto_1".to_string(),
+                items: vec![OrderItem::new("Pizza", 1)],
+                status,
+                delivery_id: None,
+                client_position: (0.0, 0.0),
+                expected_delivery_time: 0,
+                expected_preparation_time: 0,
+                priority: OrderPriority::Standard,
+                discount: 0,
+                time_stamp: HlcTimestamp::default(),
+                tenant_id: TenantId::default(),
+            },
+            update_id: 0,
+            request_id: 0,
+            decline: None,
+        }
+    }
+
+    fn subscribe(
+        service: &mut NotificationService,
+        ctx: &mut Context<NotificationService>,
+        client_id: &str,
+        subscriber_id: u64,
+    ) -> tokio::sync::mpsc::UnboundedReceiver<String> {
+        let (sender, receiver) = unbounded_channel();
+        service.handle(
+            SubscribeNotifications {
+                client_id: client_id.to_string(),
+                subscriber_id,
+                sender,
+            },
+            ctx,
+        );
+        receiver
+    }
+
+    #[actix_rt::test]
+    async fn updates_reach_only_the_subscribers_of_the_client() {
+        let mut service = NotificationService::new("127.0.0.1:0".parse().unwrap());
+        let mut ctx = Context::new();
+        let mut first = subscribe(&mut service, &mut ctx, "client_1", 1);
+        let mut second = subscribe(&mut service, &mut ctx, "client_1", 2);
+        let mut other = subscribe(&mut service, &mut ctx, "client_2", 3);
+
+        service.handle(update("client_1", OrderStatus::Preparing), &mut ctx);
+
+        for receiver in [&mut first, &mut second] {
+            let order: OrderDTO = serde_json::from_str(&receiver.try_recv().unwrap()).unwrap();
+            assert_eq!(order.status, OrderStatus::Preparing);
+        }
+        assert_eq!(other.try_recv(), Err(TryRecvError::Empty));
+    }
+
+    #[actix_rt::test]
+    async fn gone_subscribers_are_forgotten() {
+        let mut service = NotificationService::new("127.0.0.1:0".parse().unwrap());
+        let mut ctx = Context::new();
+        let closed = subscribe(&mut service, &mut ctx, "client_1", 1);
+        let _open = subscribe(&mut service, &mut ctx, "client_1", 2);
+        let _leaving = subscribe(&mut service, &mut ctx, "client_2", 3);
+
+        // Un receptor cerrado se descarta en el próximo envío
+        drop(closed);
+        service.handle(update("client_1", OrderStatus::Preparing), &mut ctx);
+        assert_eq!(service.subscribers["client_1"].len(), 1);
+
+        service.handle(
+            UnsubscribeNotifications {
+                client_id: "client_2".to_string(),
+                subscriber_id: 3,
+            },
+            &mut ctx,
+        );
+        assert!(!service.subscribers.contains_key("client_2"));
+    }
+
+    #[actix_rt::test]
+    async fn pushes_updates_over_websocket() {
+        let addr = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let service = NotificationService::new(addr).start();
+
+        let mut ws_stream = None;
+        for _ in 0..50 {
+            if let Ok((stream, _)) = connect_async(format!("ws://{}", addr)).await {
+                ws_stream = Some(stream);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let mut ws_stream = ws_stream.expect("The listener did not start");
+        ws_stream
+            .send(WsMessage::Text("client_1".to_string()))
+            .await
+            .unwrap();
+
+        // La suscripción se registra en paralelo, así que se reenvía hasta que llegue
+        let payload = loop {
+            service.do_send(update("client_1", OrderStatus::Delivering));
+            match tokio::time::timeout(Duration::from_millis(50), ws_stream.next()).await {
+                Ok(Some(Ok(WsMessage::Text(payload)))) => break payload,
+                Ok(other) => panic!("Unexpected frame: {:?}", other),
+                Err(_) => continue,
+            }
+        };
+        let order: OrderDTO = serde_json::from_str(&payload).unwrap();
+        assert_eq!(order.client_id, "client_1");
+        assert_eq!(order.status, OrderStatus::Delivering);
+    }
+}
@@ -0,0 +1,110 @@
+use crate::server_actors::services::orders_services::OrderService;
+use actix::prelude::*;
+use common::constants::ORDER_SERVICE_SHARDS;
+use common::network::peer_types::PeerIdentity;
+
+/// Environment variable with the number of `OrderService` actors that process orders in
+/// parallel.
+pub const ORDER_SERVICE_SHARDS_ENV_VAR: &str = "ORDER_SERVICE_SHARDS";
+
+/// Reads the number of shards from [`ORDER_SERVICE_SHARDS_ENV_VAR`], or
+/// [`ORDER_SERVICE_SHARDS`] if it is not set or is not a positive number.
+pub fn shards_from_env() -> usize {
+    match std::env::var(ORDER_SERVICE_SHARDS_ENV_VAR) {
+        Ok(value) => match value.trim().parse() {
+            Ok(shards) if shards > 0 => shards,
+            _ => {
+                eprintln!(
+                    "[OrderService] Ignoring invalid {}: '{}'",
+                    ORDER_SERVICE_SHARDS_ENV_VAR, value
+                );
+                ORDER_SERVICE_SHARDS
+            }
+        },
+        Err(_) => ORDER_SERVICE_SHARDS,
+    }
+}
+
+/// Returns the shard, out of `shards`, that processes the order `order_id`.
+pub(crate) fn shard_of(order_id: u64, shards: usize) -> usize {
+    // Los IDs de pedidos consecutivos caen en shards distintos
+    (order_id.wrapping_mul(0x9e37_79b9_7f4a_7c15) % shards as u64) as usize
+}
+
+/// A pool of [`OrderService`] actors, each one processing a share of the orders.
+///
+/// Every message about an order goes to the same shard, so the messages of each order are
+/// still processed in order, while the authorizations, status updates and assignments of
+/// different orders are processed in parallel. Each shard has its own connection to the
+/// PaymentGateway, so the results of its payments come back to it.
+#[derive(Debug, Clone)]
+pub struct OrderServicePool {
+    /// The shards of the pool.
+    shards: Vec<Addr<OrderService>>,
+}
+
+impl OrderServicePool {
+    /// Starts a pool of `shards` [`OrderService`] actors.
+    ///
+    /// ## Arguments
+    /// * `identity` - The identity of the coordinator, announced to the PaymentGateway.
+    /// * `shards` - The number of actors, at least one.
+    pub async fn start(identity: &PeerIdentity, shards: usize) -> Self {
+        let shards = shards.max(1);
+        let mut pool = Vec::with_capacity(shards);
+        for shard in 0..shards {
+            pool.push(OrderService::new(identity, shard, shards).await.start());
+        }
+        Self { shards: pool }
+    }
+
+    /// Returns the number of shards of the pool.
+    pub fn len(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Returns whether the pool has no shards, which never happens once started.
+    pub fn is_empty(&self) -> bool {
+        self.shards.is_empty()
+    }
+
+    /// Returns the shard that processes the order `order_id`.
+    pub fn for_order(&self, order_id: u64) -> &Addr<OrderService> {
+        &self.shards[shard_of(order_id, self.shards.len())]
+    }
+
+    /// Returns the shard that processes the order request `request_id`, so that a resent
+    /// request reaches the same shard as its first copy.
+    pub fn for_request(&self, request_id: u64) -> &Addr<OrderService> {
+        &self.shards[shard_of(request_id, self.shards.len())]
+    }
+
+    /// Sends a message to every shard.
+    pub fn broadcast<M>(&self, msg: M)
+    where
+        M: Message + Send + Clone + 'static,
+        M::Result: Send,
+        OrderService: Handler<M>,
+    {
+        for shard in &self.shards {
+            shard.do_send(msg.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spreads_consecutive_orders_across_the_shards() {
+        let mut orders_per_shard = [0; 4];
+        for order_id in 1..=400 {
+            let shard = shard_of(order_id, 4);
+            assert_eq!(shard, shard_of(order_id, 4));
+            orders_per_shard[shard] += 1;
+        }
+        assert!(orders_per_shard.iter().all(|&orders| orders > 60));
+        assert_eq!(shard_of(12345, 1), 0);
+    }
+}
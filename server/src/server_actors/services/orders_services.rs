@@ -1,39 +1,181 @@
 use crate::messages::internal_messages::{
-    AddOrderAccepted, FinishDeliveryAssignment, SetActorsAddresses,
+    AddOrderAccepted, AppendWrite, ApplyPromoCode, DeliveryOffersExpired, FinishDeliveryAssignment,
+    LoggedWrite, QuorumWrite, RetryDeliverySearch, SetActorsAddresses, SetLeaderEpoch,
 };
+use crate::server_actors::circuit_breaker::CircuitBreaker;
 use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::coordinator_error::CoordinatorError;
+use crate::server_actors::services::order_service_pool::shard_of;
+use crate::server_actors::services::promotion_service::PromotionService;
 use crate::server_actors::storage::Storage;
 use actix::prelude::*;
 use colored::Color;
+use common::constants::{ORDER_PRICE_CENTS, UNASSIGNED_ORDER_ID};
 use common::logger::Logger;
 use common::messages::internal_messages::{
-    AddAuthorizedOrderToRestaurant, AddOrder, AddPendingOrderToRestaurant,
-    RemoveAuthorizedOrderToRestaurant, RemoveOrder, RemovePendingOrderToRestaurant,
-    SetCurrentOrderToDelivery, SetDeliveryToOrder, SetOrderExpectedTime, SetOrderStatus,
+    AddAuthorizedOrderToRestaurant, AddCancellationFee, AddDeliveryEarning, AddOrder,
+    AddOutboxPayment, AddPendingOrderToRestaurant, AddPromoRedemption, ClaimOrderRequest,
+    CompleteOutboxPayment, GetAcceptedDelivery, GetClient, GetOrder, GetOrderTimeline,
+    GetPendingPayments, GetRestaurant, RemoveAcceptedDeliveries, RemoveAuthorizedOrderToRestaurant,
+    RemoveOrder, RemovePendingOrderToRestaurant, SetCurrentOrderToDelivery, SetDeliveryToOrder,
+    SetEarningPaidOut, SetOrderExpectedTime, SetOrderPreparationTime, SetOrderStatus,
+    StorageLogMessage,
 };
 use common::messages::{
-    AcceptedOrder, BillPayment, DeliverThisOrder, DeliveryAccepted, DeliveryAvailable,
-    DeliveryNoNeeded, NotifyOrderUpdated, OrderFinalized, RequestAuthorization, RequestThisOrder,
-    UpdateOrderStatus,
+    AcceptedOrder, BillPayment, CancelMyOrder, CancelOrder, CancellationCharged, DeliverThisOrder,
+    DeliveryAccepted, DeliveryAvailable, DeliveryNoNeeded, NotifyOrderUpdated, OrderFinalized,
+    PayOutDelivery, PaymentCompleted, RefundPayment, RequestAuthorization, RequestThisOrder,
+    StatusRejected, StillLookingForDelivery, UpdateOrderStatus,
 };
-use common::network::connections::connect_some;
-use common::types::dtos::OrderDTO;
+use common::network::connections::{connect_one, connect_some};
+use common::network::stream::PeerStream;
+use common::types::decline_reason::DeclineReason;
+use common::types::dtos::{CancellationFee, OrderDTO, OrderRequest, PromoRedemption};
+use common::types::hlc::HlcTimestamp;
+use common::types::order_event::{OrderEvent, OrderEventKind};
+use common::types::tenant::TenantId;
+use common::types::zone::DeliveryZones;
 use common::{
-    constants::{PAYMENT_GATEWAY_PORT, SERVER_IP_ADDRESS},
+    constants::{
+        DELIVERY_REOFFER_ATTEMPTS, DELIVERY_REOFFER_BACKOFF, MAX_NEARBY_RADIUS, NEARBY_RADIUS,
+        PAYMENT_GATEWAY_HEALTH_INTERVAL, PAYMENT_GATEWAY_HEALTH_TIMEOUT,
+        PAYMENT_GATEWAY_MAX_PENDING, PAYMENT_GATEWAY_PORT, SERVER_IP_ADDRESS,
+    },
+    messages::coordinatormanager_messages::Ping,
     messages::{AuthorizationResult, NetworkMessage, NewOrder},
-    network::{communicator::Communicator, peer_types::PeerType},
+    network::{
+        communicator::Communicator,
+        peer_types::{PeerIdentity, PeerType},
+    },
     types::order_status::OrderStatus,
 };
-use std::{collections::HashMap, net::SocketAddr};
-use tokio::net::TcpStream;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    net::SocketAddr,
+    time::Duration,
+};
+use tokio::time::Instant;
+use tracing::info_span;
+
+/// Environment variable with the comma-separated addresses of the payment gateways, primary
+/// first. Only the gateway at [`PAYMENT_GATEWAY_PORT`] is used if it is not set.
+pub const PAYMENT_GATEWAYS_ENV_VAR: &str = "PAYMENT_GATEWAYS";
+
+/// Reads the addresses of the payment gateways from [`PAYMENT_GATEWAYS_ENV_VAR`], skipping the
+/// invalid ones.
+pub fn payment_gateways_from_env() -> Vec<SocketAddr> {
+    let gateways: Vec<SocketAddr> = std::env::var(PAYMENT_GATEWAYS_ENV_VAR)
+        .map(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|addr| !addr.is_empty())
+                .filter_map(|addr| match addr.parse() {
+                    Ok(addr) => Some(addr),
+                    Err(_) => {
+                        eprintln!(
+                            "[OrderService] Ignoring invalid payment gateway in {}: '{}'",
+                            PAYMENT_GATEWAYS_ENV_VAR, addr
+                        );
+                        None
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if gateways.is_empty() {
+        let default = format!("{}:{}", SERVER_IP_ADDRESS, PAYMENT_GATEWAY_PORT)
+            .parse::<SocketAddr>()
+            .expect("Failed to parse server address");
+        return vec![default];
+    }
+    gateways
+}
+
+/// Returns how long to wait before offering an order again for the `attempt`-th time, and
+/// how far from the restaurant to look for deliveries, in blocks.
+///
+/// Both double with each attempt, the radius up to [`MAX_NEARBY_RADIUS`].
+fn reoffer_schedule(attempt: u32) -> (Duration, f32) {
+    let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+    let wait = DELIVERY_REOFFER_BACKOFF.saturating_mul(factor);
+    let radius = (NEARBY_RADIUS * 2.0 * factor as f32).min(MAX_NEARBY_RADIUS);
+    (wait, radius)
+}
+
+/// A request for the PaymentGateway, kept until it can be sent.
+#[derive(Debug, Clone)]
+enum PaymentRequest {
+    /// Authorize the payment of a new order.
+    Authorize(OrderDTO),
+    /// Bill a delivered order.
+    Bill(OrderDTO),
+    /// Refund a cancelled order, keeping `fee` cents as its cancellation fee.
+    Refund { order: OrderDTO, fee: u64 },
+    /// Pay a delivery what it earned for an order.
+    PayOut {
+        delivery_id: String,
+        order_id: u64,
+        amount: u64,
+    },
+}
+
+impl PaymentRequest {
+    /// Builds the message sent to the PaymentGateway from `origin_address`.
+    fn into_message(self, origin_address: SocketAddr) -> NetworkMessage {
+        match self {
+            PaymentRequest::Authorize(order) => {
+                NetworkMessage::RequestAuthorization(RequestAuthorization {
+                    origin_address,
+                    order,
+                })
+            }
+            PaymentRequest::Bill(order) => NetworkMessage::BillPayment(BillPayment {
+                origin_address,
+                order,
+            }),
+            PaymentRequest::Refund { order, fee } => NetworkMessage::RefundPayment(RefundPayment {
+                origin_address,
+                order,
+                fee,
+            }),
+            PaymentRequest::PayOut {
+                delivery_id,
+                order_id,
+                amount,
+            } => NetworkMessage::PayOutDelivery(PayOutDelivery {
+                origin_address,
+                delivery_id,
+                order_id,
+                amount,
+            }),
+        }
+    }
+}
 
 /// The `OrderService` actor is responsible for managing orders in the system.
 ///
 /// ## Responsibilities
-/// - Receives and processes order requests from clients.
+/// - Receives and processes order requests from clients, giving each order an ID that falls
+///   on its own shard. A resent request gets the ID of its first copy, claimed in Storage.
+/// - Takes off the price the discount of the promo code of the order, as validated by the
+///   `PromotionService`, and records the use of the code once the order is authorized.
 /// - Coordinates payment authorization with the PaymentGateway.
 /// - Updates order status and notifies the Coordinator and Storage actors.
-/// - Handles delivery assignments and order finalization.
+/// - Waits for new orders and status changes to reach a quorum of coordinators before
+///   notifying the client, when quorum writes are enabled.
+/// - Handles delivery assignments and order finalization. A delivery acceptance is replicated
+///   before it is confirmed, so that a new leader never lets a second delivery win the order.
+/// - Cancels orders at the request of their clients, refunding the payment.
+/// - Records what the delivery of each finalized order earned and has the PaymentGateway pay
+///   it out.
+/// - Keeps the bill of each finalized order in the replicated payment outbox until the
+///   PaymentGateway confirms it, sending the pending bills of its orders again when it
+///   reconnects to a gateway or its coordinator becomes the leader.
+/// - Health-checks the PaymentGateway and fails over to the next configured gateway when it
+///   stops answering, sending again the authorizations it did not answer.
+/// - Reconnects to the PaymentGateways with exponential backoff when none is reachable,
+///   queuing the payment requests until one is reachable again.
 /// - Maintains mappings between clients, restaurants, and their orders.
 pub struct OrderService {
     /// Tracks the status of each order by order ID.
@@ -44,33 +186,73 @@ pub struct OrderService {
     pub restaurants_orders: HashMap<String, Vec<u64>>,
     /// List of pending order IDs.
     pub pending_orders: Vec<u64>,
+    /// IDs of the orders cancelled by their clients and not removed yet, whose late status
+    /// updates are ignored.
+    pub cancelled_orders: HashSet<u64>,
+    /// Times each order that no delivery took was offered again, by order ID.
+    pub delivery_reoffers: HashMap<u64, u32>,
+    /// Epoch of the current leader, recorded with each delivery acceptance.
+    pub epoch: u64,
+    /// Whether the coordinator of this service is the leader, the only one that bills.
+    leading: bool,
+    /// Index of this service in its pool, out of `shards`. It only bills its own orders.
+    shard: usize,
+    /// Number of services in the pool.
+    shards: usize,
+    /// Last order ID given by this service.
+    last_order_id: u64,
     /// Address of the Coordinator actor.
     pub coordinator_address: Option<Addr<Coordinator>>,
     /// Address of the Storage actor.
     pub storage_address: Option<Addr<Storage>>,
+    /// Address of the PromotionService actor.
+    pub promotion_address: Option<Addr<PromotionService>>,
+    /// Uses of promo codes to record once their order is authorized, by order ID.
+    pending_redemptions: HashMap<u64, PromoRedemption>,
     /// Logger for order service events.
     pub logger: Logger,
     /// Communicator for interacting with the PaymentGateway.
     pub payment_gateway_address: Option<Communicator<OrderService>>,
     /// Pending TCP stream for PaymentGateway connection.
-    pub pending_stream: Option<TcpStream>,
+    pub pending_stream: Option<PeerStream>,
+    /// Addresses of the PaymentGateways, primary first.
+    payment_gateways: Vec<SocketAddr>,
+    /// Index in `payment_gateways` of the gateway in use, or to try first.
+    active_gateway: usize,
+    /// Last time the gateway in use sent a message.
+    last_gateway_answer: Instant,
+    /// Orders sent to the gateway in use for authorization and not answered yet, by order ID.
+    awaiting_authorization: HashMap<u64, OrderDTO>,
+    /// IDs of the orders billed through the gateway in use and not confirmed yet.
+    awaiting_bills: HashSet<u64>,
+    /// Identity announced to the PaymentGateway when reconnecting.
+    identity: PeerIdentity,
+    /// State of the connection to the PaymentGateway.
+    payment_circuit: CircuitBreaker,
+    /// Payment requests waiting for the PaymentGateway to be reachable, oldest first.
+    pending_payments: VecDeque<PaymentRequest>,
+    /// Zones served by each restaurant, used to reject orders from clients outside them.
+    zones: DeliveryZones,
 }
 
 impl OrderService {
     /// Asynchronously creates a new `OrderService` instance and attempts to connect to the PaymentGateway.
-    pub async fn new() -> Self {
+    ///
+    /// ## Arguments
+    /// * `identity` - The identity of the coordinator, announced to the PaymentGateway.
+    /// * `shard` - The index of the service in its pool.
+    /// * `shards` - The number of services in the pool.
+    pub async fn new(identity: &PeerIdentity, shard: usize, shards: usize) -> Self {
         let logger = Logger::new("Order Service", Color::Green);
 
-        let payment_gateway_address = format!("{}:{}", SERVER_IP_ADDRESS, PAYMENT_GATEWAY_PORT)
-            .parse::<SocketAddr>()
-            .expect("Failed to parse server address");
+        let payment_gateways = payment_gateways_from_env();
         println!(
-            "Trying to connect to Payment Gateway: {}",
-            payment_gateway_address
+            "Trying to connect to Payment Gateways: {:?}",
+            payment_gateways
         );
 
-        let pending_stream =
-            connect_some(vec![payment_gateway_address], PeerType::CoordinatorType).await;
+        // Se prueban en orden, así que se usa el primario si está disponible
+        let pending_stream = connect_some(payment_gateways.clone(), identity).await;
 
         if pending_stream.is_none() {
             logger.error("Failed to connect to PaymentGateway");
@@ -82,56 +264,749 @@ impl OrderService {
             clients_orders: HashMap::new(),
             restaurants_orders: HashMap::new(),
             pending_orders: Vec::new(),
+            cancelled_orders: HashSet::new(),
+            delivery_reoffers: HashMap::new(),
+            epoch: 0,
+            leading: false,
+            shard,
+            shards: shards.max(1),
+            last_order_id: UNASSIGNED_ORDER_ID,
             coordinator_address: None,
             storage_address: None,
+            promotion_address: None,
+            pending_redemptions: HashMap::new(),
             logger,
             payment_gateway_address: None,
             pending_stream,
+            payment_gateways,
+            active_gateway: 0,
+            last_gateway_answer: Instant::now(),
+            awaiting_authorization: HashMap::new(),
+            awaiting_bills: HashSet::new(),
+            identity: identity.clone(),
+            payment_circuit: CircuitBreaker::default(),
+            pending_payments: VecDeque::new(),
+            zones: DeliveryZones::from_env(),
+        }
+    }
+
+    /// Sends a request to the PaymentGateway, or queues it until the gateway is reachable.
+    ///
+    /// If the queue is full, new orders are rejected as unauthorized instead of waiting,
+    /// and refunds and payouts are dropped. Bills stay in the payment outbox, and are sent
+    /// again once the gateway is reachable.
+    fn send_to_payment_gateway(&mut self, request: PaymentRequest) {
+        if self.payment_circuit.is_closed()
+            && let Some(communicator) = self.payment_gateway_address.as_ref()
+            && let Some(sender) = communicator.sender.as_ref()
+        {
+            sender.do_send(request.clone().into_message(communicator.local_address));
+            match request {
+                PaymentRequest::Authorize(order) => {
+                    self.awaiting_authorization.insert(order.order_id, order);
+                }
+                PaymentRequest::Bill(order) => {
+                    self.awaiting_bills.insert(order.order_id);
+                }
+                _ => {}
+            }
+            return;
+        }
+        if self.pending_payments.len() < PAYMENT_GATEWAY_MAX_PENDING {
+            self.pending_payments.push_back(request);
+            return;
+        }
+        match request {
+            PaymentRequest::Authorize(mut order) => {
+                self.logger.warn(format!(
+                    "PaymentGateway unreachable and too many pending payments, rejecting order {}",
+                    order.order_id
+                ));
+                order.status = OrderStatus::Unauthorized;
+                if let Some(coordinator) = self.coordinator_address.clone() {
+                    self.handle_unauthorized_order(
+                        &order,
+                        Some(DeclineReason::GatewayTimeout),
+                        coordinator,
+                    );
+                }
+            }
+            PaymentRequest::Bill(order) => {
+                self.logger.warn(format!(
+                    "PaymentGateway unreachable and too many pending payments, keeping the bill of order {} in the outbox",
+                    order.order_id
+                ));
+            }
+            PaymentRequest::Refund { order, .. } => {
+                self.logger.error(format!(
+                    "PaymentGateway unreachable and too many pending payments, dropping payment of order {}",
+                    order.order_id
+                ));
+            }
+            PaymentRequest::PayOut {
+                delivery_id,
+                order_id,
+                ..
+            } => {
+                self.logger.error(format!(
+                    "PaymentGateway unreachable and too many pending payments, dropping payout of order {} to {}",
+                    order_id, delivery_id
+                ));
+            }
+        }
+    }
+
+    /// Sends the queued payment requests, oldest first, once the PaymentGateway is reachable.
+    fn flush_pending_payments(&mut self) {
+        if self.pending_payments.is_empty() {
+            return;
+        }
+        self.logger.info(format!(
+            "Sending {} pending payment requests to the PaymentGateway",
+            self.pending_payments.len()
+        ));
+        for request in std::mem::take(&mut self.pending_payments) {
+            self.send_to_payment_gateway(request);
+        }
+    }
+
+    /// Starts using the gateway connected through `stream`, and sends it the queued requests.
+    fn use_payment_gateway(&mut self, stream: PeerStream, ctx: &mut Context<Self>) {
+        let communicator = Communicator::new(stream, ctx.address(), PeerType::CoordinatorType);
+        if let Some(index) = self
+            .payment_gateways
+            .iter()
+            .position(|gateway| *gateway == communicator.peer_address)
+        {
+            self.active_gateway = index;
+        }
+        self.logger.info(format!(
+            "Using PaymentGateway {}",
+            communicator.peer_address
+        ));
+        self.payment_gateway_address = Some(communicator);
+        self.last_gateway_answer = Instant::now();
+        self.payment_circuit.record_success();
+        self.flush_pending_payments();
+        self.replay_payment_outbox(ctx);
+    }
+
+    /// Returns whether this service processes the order `order_id`.
+    fn owns(&self, order_id: u64) -> bool {
+        shard_of(order_id, self.shards) == self.shard
+    }
+
+    /// Bills again the orders of this service whose bills in the payment outbox the
+    /// PaymentGateway did not confirm, unless they are queued or were already sent to the
+    /// gateway in use. Only the leader bills.
+    fn replay_payment_outbox(&self, ctx: &mut Context<Self>) {
+        if !self.leading {
+            return;
+        }
+        let Some(storage) = self.storage_address.clone() else {
+            self.logger.error("Storage address not set");
+            return;
+        };
+        storage
+            .send(GetPendingPayments)
+            .into_actor(self)
+            .map(|res, act, _ctx| {
+                let orders = match res {
+                    Ok(orders) => orders,
+                    Err(e) => {
+                        act.logger
+                            .error(format!("Error retrieving the payment outbox: {}", e));
+                        return;
+                    }
+                };
+                let queued: HashSet<u64> = act
+                    .pending_payments
+                    .iter()
+                    .filter_map(|request| match request {
+                        PaymentRequest::Bill(order) => Some(order.order_id),
+                        _ => None,
+                    })
+                    .collect();
+                let unsent: Vec<OrderDTO> = orders
+                    .into_iter()
+                    .filter(|order| act.owns(order.order_id))
+                    .filter(|order| {
+                        !queued.contains(&order.order_id)
+                            && !act.awaiting_bills.contains(&order.order_id)
+                    })
+                    .collect();
+                if unsent.is_empty() {
+                    return;
+                }
+                act.logger.info(format!(
+                    "Sending again {} bills not confirmed by the PaymentGateway",
+                    unsent.len()
+                ));
+                for order in unsent {
+                    act.send_to_payment_gateway(PaymentRequest::Bill(order));
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Sends a health check to the gateway in use, or fails over if it stopped answering.
+    fn check_payment_gateway(&mut self, ctx: &mut Context<Self>) {
+        let Some(communicator) = self.payment_gateway_address.as_ref() else {
+            return;
+        };
+        if Instant::now().duration_since(self.last_gateway_answer) > PAYMENT_GATEWAY_HEALTH_TIMEOUT
+        {
+            self.logger.warn(format!(
+                "PaymentGateway {} stopped answering, failing over",
+                communicator.peer_address
+            ));
+            self.payment_gateway_unreachable(ctx);
+            return;
+        }
+        if let Some(sender) = communicator.sender.as_ref() {
+            sender.do_send(NetworkMessage::Ping(Ping {
+                from: communicator.local_address,
+            }));
+        }
+    }
+
+    /// Opens the circuit to the PaymentGateway and schedules a reconnection with backoff,
+    /// starting with the next configured gateway.
+    ///
+    /// The authorizations the lost gateway did not answer are queued again, and the bills it
+    /// did not confirm are sent again from the payment outbox after reconnecting. The
+    /// gateways answer a repeated request with their first result, so they are never
+    /// charged twice.
+    fn payment_gateway_unreachable(&mut self, ctx: &mut Context<Self>) {
+        self.payment_gateway_address = None;
+        self.active_gateway = (self.active_gateway + 1) % self.payment_gateways.len();
+        for (_, order) in self.awaiting_authorization.drain() {
+            self.pending_payments
+                .push_front(PaymentRequest::Authorize(order));
+        }
+        self.awaiting_bills.clear();
+        let delay = self.payment_circuit.record_failure(Instant::now());
+        self.logger.warn(format!(
+            "PaymentGateway unreachable ({} failures, circuit {}), retrying in {:?}",
+            self.payment_circuit.failures(),
+            self.payment_circuit.state(),
+            delay
+        ));
+        ctx.run_later(delay, |act, ctx| act.reconnect_payment_gateway(ctx));
+    }
+
+    /// Tries to connect to the PaymentGateways again, if the backoff elapsed, starting with
+    /// the one after the gateway lost.
+    fn reconnect_payment_gateway(&mut self, ctx: &mut Context<Self>) {
+        if !self.payment_circuit.try_attempt(Instant::now()) {
+            return;
+        }
+        let mut gateways = self.payment_gateways.clone();
+        gateways.rotate_left(self.active_gateway);
+        let identity = self.identity.clone();
+        async move {
+            for addr in gateways {
+                if let Some(stream) = connect_one(addr, &identity).await {
+                    return Some(stream);
+                }
+            }
+            None
         }
+        .into_actor(self)
+        .map(|stream, act, ctx| match stream {
+            Some(stream) => act.use_payment_gateway(stream, ctx),
+            None => act.payment_gateway_unreachable(ctx),
+        })
+        .spawn(ctx);
     }
 
-    /// Handles an unauthorized order by notifying the Coordinator.
+    /// Handles an unauthorized order by notifying the Coordinator, with the reason the payment
+    /// was declined for the client.
     ///
     /// ## Arguments
     /// * `order` - The unauthorized [`OrderDTO`].
+    /// * `decline` - Why the payment was declined, if known.
     /// * `coordinator` - The address of the Coordinator actor.
-    fn handle_unauthorized_order(&mut self, order: &OrderDTO, coordinator: Addr<Coordinator>) {
-        self.logger.warn(format!(
-            "Order {} unauthorized, notifying Coordinator",
-            order.order_id
-        ));
+    fn handle_unauthorized_order(
+        &mut self,
+        order: &OrderDTO,
+        decline: Option<DeclineReason>,
+        coordinator: Addr<Coordinator>,
+    ) {
+        match decline {
+            Some(reason) => self.logger.warn(format!(
+                "Order {} unauthorized ({}), notifying Coordinator",
+                order.order_id, reason
+            )),
+            None => self.logger.warn(format!(
+                "Order {} unauthorized, notifying Coordinator",
+                order.order_id
+            )),
+        }
+        // El cliente puede volver a usar el código en otro pedido
+        self.pending_redemptions.remove(&order.order_id);
         coordinator.do_send(NotifyOrderUpdated {
             peer_id: order.client_id.clone(),
             order: order.clone(),
+            update_id: 0,
+            request_id: 0,
+            decline,
         });
     }
 
-    /// Handles an authorized order by storing it and notifying the Coordinator.
+    /// Handles an authorized order by storing it and notifying the Coordinator once the
+    /// write is replicated.
     ///
     /// ## Arguments
     /// * `order` - The authorized [`OrderDTO`].
     /// * `coordinator` - The address of the Coordinator actor.
-    fn handle_authorized_order(&mut self, order: &OrderDTO, coordinator: Addr<Coordinator>) {
+    /// * `ctx` - The actor context.
+    fn handle_authorized_order(
+        &mut self,
+        order: &OrderDTO,
+        coordinator: Addr<Coordinator>,
+        ctx: &mut Context<Self>,
+    ) {
         self.logger.info(format!(
             "Order {} authorized, notifying Coordinator",
             order.order_id
         ));
-        if let Some(addr) = self.storage_address.as_ref() {
-            addr.do_send(AddOrder {
-                order: order.clone(),
+        self.orders.insert(order.order_id, OrderStatus::Authorized);
+        if let Some(redemption) = self.pending_redemptions.remove(&order.order_id) {
+            self.send_to_storage(AddPromoRedemption {
+                redemption,
+                time_stamp: None,
             });
-        } else {
-            self.logger.error("Storage address not set");
         }
-        coordinator.do_send(NewOrder {
+        let add_order = AddOrder {
             order: order.clone(),
+            event: Some(OrderEvent::new(
+                OrderEventKind::Created,
+                order,
+                &self.identity.user_id,
+            )),
+        };
+        let order = order.clone();
+        self.write_then(
+            StorageLogMessage::AddOrder(add_order),
+            ctx,
+            move |act, result| {
+                if let Err(e) = result {
+                    act.logger.warn(format!(
+                        "Order {} not confirmed, not offering it yet: {}",
+                        order.order_id, e
+                    ));
+                    return;
+                }
+                coordinator.do_send(NewOrder {
+                    order: order.clone(),
+                });
+                coordinator.do_send(NotifyOrderUpdated {
+                    peer_id: order.client_id.clone(),
+                    order,
+                    update_id: 0,
+                    request_id: 0,
+                    decline: None,
+                });
+            },
+        );
+    }
+
+    /// Applies a status change sent by a restaurant or delivery, once it is known to be valid
+    /// from the current status of the order.
+    fn apply_status_update(&mut self, msg: UpdateOrderStatus, ctx: &mut Context<Self>) {
+        self.orders
+            .insert(msg.order.order_id, msg.order.status.clone());
+        match msg.order.status {
+            OrderStatus::Pending => {
+                ctx.address().do_send(SetOrderPreparationTime {
+                    order_id: msg.order.order_id,
+                    preparation_time: msg.order.expected_preparation_time,
+                    time_stamp: None,
+                });
+                ctx.address().do_send(AddPendingOrderToRestaurant {
+                    order: msg.order.clone(),
+                    restaurant_id: msg.order.restaurant_id.clone(),
+                    time_stamp: None,
+                });
+                ctx.address().do_send(SetOrderStatus {
+                    order: msg.order.clone(),
+                    order_status: OrderStatus::Pending,
+                    event: None,
+                });
+            }
+            OrderStatus::Cancelled => {
+                let event = msg.reason.map(|reason| {
+                    OrderEvent::new(OrderEventKind::Removed, &msg.order, &self.identity.user_id)
+                        .with_reason(reason)
+                });
+                ctx.address().do_send(RemoveOrder {
+                    order: msg.order.clone(),
+                    event,
+                });
+            }
+            OrderStatus::Preparing => {
+                ctx.address().do_send(SetOrderPreparationTime {
+                    order_id: msg.order.order_id,
+                    preparation_time: msg.order.expected_preparation_time,
+                    time_stamp: None,
+                });
+                ctx.address().do_send(SetOrderStatus {
+                    order: msg.order.clone(),
+                    order_status: OrderStatus::Preparing,
+                    event: None,
+                });
+                ctx.address().do_send(RemovePendingOrderToRestaurant {
+                    order: msg.order.clone(),
+                    restaurant_id: msg.order.restaurant_id.clone(),
+                    time_stamp: None,
+                });
+                ctx.address().do_send(AddAuthorizedOrderToRestaurant {
+                    order: msg.order.clone(),
+                    restaurant_id: msg.order.restaurant_id.clone(),
+                    time_stamp: None,
+                });
+            }
+            OrderStatus::ReadyForDelivery => {
+                ctx.address().do_send(SetOrderStatus {
+                    order: msg.order.clone(),
+                    order_status: OrderStatus::ReadyForDelivery,
+                    event: None,
+                });
+            }
+            OrderStatus::Delivering => {
+                let delivery_id_opt = msg.order.delivery_id.clone();
+                if delivery_id_opt.is_none() {
+                    self.logger.error(format!(
+                        "Order {} is Delivering but has no delivery_id",
+                        msg.order.order_id
+                    ));
+                    return;
+                }
+                ctx.address().do_send(SetOrderExpectedTime {
+                    order_id: msg.order.order_id,
+                    expected_time: msg.order.expected_delivery_time,
+                    time_stamp: None,
+                });
+
+                self.send_to_coordinator(NotifyOrderUpdated {
+                    peer_id: msg.order.client_id.clone(),
+                    order: msg.order.clone(),
+                    update_id: 0,
+                    request_id: 0,
+                    decline: None,
+                });
+            }
+            OrderStatus::Delivered => {
+                ctx.address().do_send(OrderFinalized {
+                    order: msg.order.clone(),
+                });
+            }
+            _ => {
+                self.logger.error(format!(
+                    "Unexpected order status {:?} for order {}",
+                    msg.order.status, msg.order.order_id
+                ));
+            }
+        }
+    }
+
+    /// Rejects a status change the order cannot make from its `current` state, replying with
+    /// the stored order to the peer that sent it.
+    fn reject_status_update(&mut self, current: OrderDTO, msg: UpdateOrderStatus) {
+        self.orders.insert(current.order_id, current.status.clone());
+        // Los estados de la entrega los manda el delivery; el resto, el restaurante
+        let peer_id = match (&msg.order.status, &msg.order.delivery_id) {
+            (OrderStatus::Delivering | OrderStatus::Delivered, Some(delivery_id)) => {
+                delivery_id.clone()
+            }
+            _ => msg.order.restaurant_id.clone(),
+        };
+        self.logger.warn(format!(
+            "Rejected status {:?} from {} for order {}, which is {:?}",
+            msg.order.status, peer_id, current.order_id, current.status
+        ));
+        self.send_to_coordinator(StatusRejected {
+            peer_id,
+            order: current,
+            rejected_status: msg.order.status,
         });
-        coordinator.do_send(NotifyOrderUpdated {
-            peer_id: order.client_id.clone(),
+    }
+
+    /// Cancels an order at the request of its client, if it has not left the restaurant yet.
+    ///
+    /// The payment is refunded except for the [`CancellationFee`] of the stage the order is
+    /// at, which is credited to the restaurant. The delivery agent that accepted the order (if
+    /// any) is released, and the Coordinator removes the order and notifies the restaurant and
+    /// the client, who also gets the breakdown of the refund. If the order can no longer be
+    /// cancelled the client is told its current status.
+    ///
+    /// ## Arguments
+    /// * `order` - The order, as stored.
+    /// * `waive_fee` - Whether the whole price is refunded regardless of the stage.
+    /// * `storage` - The address of the Storage actor.
+    /// * `ctx` - The actor context.
+    fn cancel_order(
+        &mut self,
+        order: OrderDTO,
+        waive_fee: bool,
+        storage: Addr<Storage>,
+        ctx: &mut Context<Self>,
+    ) {
+        if !order.status.is_cancellable() {
+            self.logger.warn(format!(
+                "Order {} cannot be cancelled, it is {:?}",
+                order.order_id, order.status
+            ));
+            self.send_to_coordinator(NotifyOrderUpdated {
+                peer_id: order.client_id.clone(),
+                order,
+                update_id: 0,
+                request_id: 0,
+                decline: None,
+            });
+            return;
+        }
+        self.logger.info(format!(
+            "Client {} cancelled order {}",
+            order.client_id, order.order_id
+        ));
+        self.cancelled_orders.insert(order.order_id);
+        self.delivery_reoffers.remove(&order.order_id);
+        // La comisión depende de la etapa en la que estaba el pedido antes de cancelarlo
+        let fee = CancellationFee::for_order(&order, waive_fee);
+        let mut cancelled = order;
+        cancelled.status = OrderStatus::Cancelled;
+        self.request_refund(&cancelled, fee.fee);
+        if fee.fee > 0 {
+            self.logger.info(format!(
+                "Order {} cancelled while {}, {} cents kept for {}",
+                cancelled.order_id, fee.stage, fee.fee, cancelled.restaurant_id
+            ));
+            self.send_to_storage(AddCancellationFee {
+                restaurant_id: cancelled.restaurant_id.clone(),
+                fee: fee.clone(),
+                time_stamp: None,
+            });
+        }
+        self.send_to_coordinator(CancellationCharged {
+            client_id: cancelled.client_id.clone(),
+            fee,
+        });
+
+        storage
+            .send(GetAcceptedDelivery {
+                order_id: cancelled.order_id,
+            })
+            .into_actor(self)
+            .map(move |res, act, _ctx| {
+                // Si un delivery ya había aceptado el pedido, se lo libera
+                if let Ok(Some(delivery)) = res {
+                    act.send_to_storage(RemoveAcceptedDeliveries {
+                        order_id: cancelled.order_id,
+                    });
+                    act.send_to_coordinator(DeliveryNoNeeded {
+                        order: cancelled.clone(),
+                        delivery_info: delivery,
+                    });
+                }
+                // El Coordinator elimina la orden y avisa al restaurante y al cliente
+                act.send_to_coordinator(CancelOrder {
+                    order: cancelled,
+                    reason: None,
+                });
+            })
+            .spawn(ctx);
+    }
+
+    /// Records what the delivery of a finalized order earned and asks the PaymentGateway to
+    /// pay it out, only the first time the order is finalized.
+    ///
+    /// ## Arguments
+    /// * `order` - The finalized order.
+    /// * `ctx` - The actor context.
+    fn record_delivery_earning(&mut self, order: &OrderDTO, ctx: &mut Context<Self>) {
+        let Some(delivery_id) = order.delivery_id.clone() else {
+            self.logger.warn(format!(
+                "Order {} was finalized without a delivery, nobody earns it",
+                order.order_id
+            ));
+            return;
+        };
+        let Some(storage) = self.storage_address.clone() else {
+            self.logger.error("Storage address not set");
+            return;
+        };
+        storage
+            .send(AddDeliveryEarning {
+                delivery_id: delivery_id.clone(),
+                order: order.clone(),
+                earning: None,
+            })
+            .into_actor(self)
+            .map(move |res, act, _ctx| {
+                if let Ok(Some(earning)) = res {
+                    act.send_to_payment_gateway(PaymentRequest::PayOut {
+                        delivery_id,
+                        order_id: earning.order_id,
+                        amount: earning.total(),
+                    });
+                }
+            })
+            .spawn(ctx);
+    }
+
+    /// Asks the PaymentGateway to refund the payment of a cancelled order, keeping `fee`
+    /// cents of it.
+    fn request_refund(&mut self, order: &OrderDTO, fee: u64) {
+        self.send_to_payment_gateway(PaymentRequest::Refund {
             order: order.clone(),
+            fee,
         });
     }
 
+    /// Applies `update` to the local Storage and replicates it like
+    /// [`replicate_then`](Self::replicate_then).
+    fn write_then<F>(&mut self, update: StorageLogMessage, ctx: &mut Context<Self>, on_commit: F)
+    where
+        F: FnOnce(&mut Self, Result<(), CoordinatorError>) + 'static,
+    {
+        let Some(storage) = self.storage_address.clone() else {
+            on_commit(self, Err(CoordinatorError::NotInitialized("Storage")));
+            return;
+        };
+        storage
+            .send(AppendWrite { update })
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(Some(write)) => act.replicate_then(write, ctx, on_commit),
+                    Ok(None) => on_commit(act, Err(CoordinatorError::WriteIgnored)),
+                    Err(e) => on_commit(act, Err(e.into())),
+                }
+                fut::ready(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Replicates a write already applied to the local Storage and runs `on_commit` once
+    /// a quorum of coordinators holds it (right away unless quorum writes are enabled).
+    ///
+    /// If the quorum is not reached in time `on_commit` receives the error instead: the
+    /// write stays in the log of this coordinator, but it may be lost if it stops leading.
+    fn replicate_then<F>(&mut self, write: LoggedWrite, ctx: &mut Context<Self>, on_commit: F)
+    where
+        F: FnOnce(&mut Self, Result<(), CoordinatorError>) + 'static,
+    {
+        let Some(coordinator) = self.coordinator_address.clone() else {
+            on_commit(self, Err(CoordinatorError::NotInitialized("Coordinator")));
+            return;
+        };
+        coordinator
+            .send(QuorumWrite { write })
+            .into_actor(self)
+            .then(move |res, act, _ctx| {
+                on_commit(act, res.unwrap_or_else(|e| Err(e.into())));
+                fut::ready(())
+            })
+            .spawn(ctx);
+    }
+
+    /// Asks the Coordinator to offer `order` again to the deliveries within `radius`.
+    ///
+    /// If the order is gone its attempts are forgotten. If the Coordinator cannot search
+    /// now, the attempt counts as expired, so the order is offered again later or cancelled.
+    fn retry_delivery_search(&self, order: OrderDTO, radius: f32, ctx: &mut Context<Self>) {
+        let Some(coordinator) = self.coordinator_address.clone() else {
+            self.logger.error("Coordinator address not set");
+            return;
+        };
+        let order_id = order.order_id;
+        coordinator
+            .send(RetryDeliverySearch {
+                order_id,
+                radius: Some(radius),
+            })
+            .into_actor(self)
+            .map(
+                move |res, act, ctx| match res.unwrap_or_else(|e| Err(e.into())) {
+                    Ok(()) => {}
+                    Err(CoordinatorError::OrderNotFound(_)) => {
+                        act.logger
+                            .info(format!("Order {} is gone, no longer offering it", order_id));
+                        act.delivery_reoffers.remove(&order_id);
+                    }
+                    Err(e) => {
+                        act.logger
+                            .warn(format!("Cannot offer order {} again: {}", order_id, e));
+                        ctx.address().do_send(DeliveryOffersExpired { order });
+                    }
+                },
+            )
+            .spawn(ctx);
+    }
+
+    /// Finalizes an order billed by the PaymentGateway, only the first time its payment is
+    /// confirmed: the gateway confirms a repeated bill again, and the bills of the outbox are
+    /// sent again after failovers and leader changes.
+    fn complete_payment(&mut self, payment: PaymentCompleted, ctx: &mut Context<Self>) {
+        let Some(storage) = self.storage_address.clone() else {
+            self.logger.error("Storage address not set");
+            return;
+        };
+        let order_id = payment.order.order_id;
+        storage
+            .send(CompleteOutboxPayment {
+                order: payment.order.clone(),
+                time_stamp: None,
+            })
+            .into_actor(self)
+            .map(move |res, act, _ctx| {
+                match res {
+                    Ok(true) => {}
+                    Ok(false) => {
+                        act.logger.info(format!(
+                            "Ignoring repeated payment confirmation for order {}",
+                            order_id
+                        ));
+                        return;
+                    }
+                    Err(e) => {
+                        act.logger.error(format!(
+                            "Error completing the payment of order {}: {}",
+                            order_id, e
+                        ));
+                        return;
+                    }
+                }
+                act.logger.info(format!(
+                    "Payment of {} cents completed for order {}",
+                    payment.amount, order_id
+                ));
+                act.delivery_reoffers.remove(&order_id);
+                act.orders.remove(&order_id);
+                act.cancelled_orders.remove(&order_id);
+                // Como se terminó la entrega, se elimina la orden del Storage
+                act.send_to_storage(RemoveOrder {
+                    order: payment.order.clone(),
+                    event: None,
+                });
+                // Notificar al  Coordinator para que informe al restaurante
+                act.send_to_coordinator(OrderFinalized {
+                    order: payment.order.clone(),
+                });
+                // Notificar al  Coordinator para que informe al cliente
+                act.send_to_coordinator(NotifyOrderUpdated {
+                    peer_id: payment.order.client_id.clone(),
+                    order: payment.order,
+                    update_id: 0,
+                    request_id: 0,
+                    decline: None,
+                });
+            })
+            .spawn(ctx);
+    }
+
     /// Sends a message to the Storage actor if its address is set.
     fn send_to_storage<T>(&self, msg: T)
     where
@@ -161,18 +1036,252 @@ impl OrderService {
             self.logger.error("Storage address not set");
         }
     }
+
+    /// Rejects `order` with `status` before charging it, telling its client.
+    fn reject_order(&self, mut order: OrderDTO, status: OrderStatus) {
+        order.status = status;
+        self.send_to_coordinator(NotifyOrderUpdated {
+            peer_id: order.client_id.clone(),
+            order,
+            update_id: 0,
+            request_id: 0,
+            decline: None,
+        });
+    }
+
+    /// Returns a new order ID that falls on the shard of this service, so that every later
+    /// message about the order reaches it.
+    ///
+    /// IDs follow the hybrid clock, which moves past every change replicated from previous
+    /// leaders, so they grow over time and across leader changes, like UUIDv7s.
+    fn next_order_id(&mut self) -> u64 {
+        let mut order_id = HlcTimestamp::now().as_u64().max(self.last_order_id + 1);
+        while shard_of(order_id, self.shards) != self.shard {
+            order_id += 1;
+        }
+        self.last_order_id = order_id;
+        order_id
+    }
+
+    /// Tells the client of a new order which ID it got, and checks the order before charging
+    /// it: it is rejected if the restaurant does not serve the zone of the client, belongs to
+    /// another marketplace or is closed.
+    fn place_order(
+        &mut self,
+        order: OrderDTO,
+        request_id: u64,
+        code: Option<String>,
+        ctx: &mut Context<Self>,
+    ) {
+        self.logger.info(format!(
+            "Order {} placed by client {}",
+            order.order_id, order.client_id
+        ));
+        // La primera actualización le dice al cliente qué ID tiene su pedido
+        let mut requested = order.clone();
+        requested.status = OrderStatus::Requested;
+        self.send_to_coordinator(NotifyOrderUpdated {
+            peer_id: requested.client_id.clone(),
+            order: requested,
+            update_id: 0,
+            request_id,
+            decline: None,
+        });
+        // El restaurante no atiende la zona del cliente: se rechaza antes de cobrar
+        if !self
+            .zones
+            .covers(&order.restaurant_id, order.client_position)
+        {
+            self.logger.warn(format!(
+                "Client {} at {:?} is out of the zones of restaurant {}, rejecting order {}",
+                order.client_id, order.client_position, order.restaurant_id, order.order_id
+            ));
+            self.reject_order(order, OrderStatus::OutOfZone);
+            return;
+        }
+        let Some(storage) = self.storage_address.clone() else {
+            self.authorize_with_promo_code(order, code, ctx);
+            return;
+        };
+        storage
+            .send(GetRestaurant {
+                restaurant_id: order.restaurant_id.clone(),
+            })
+            .into_actor(self)
+            .map(move |res, act, ctx| {
+                // Un restaurante de otro marketplace no atiende al cliente
+                if let Ok(Some(restaurant)) = &res
+                    && restaurant.tenant_id != order.tenant_id
+                {
+                    act.logger.warn(format!(
+                        "Restaurant {} belongs to tenant {}, not to {}, rejecting order {}",
+                        order.restaurant_id, restaurant.tenant_id, order.tenant_id, order.order_id
+                    ));
+                    act.reject_order(order, OrderStatus::OtherTenant);
+                    return;
+                }
+                // El restaurante está fuera de su horario: se rechaza antes de cobrar
+                if let Ok(Some(restaurant)) = res
+                    && restaurant.closed
+                {
+                    act.logger.warn(format!(
+                        "Restaurant {} is closed, rejecting order {}",
+                        order.restaurant_id, order.order_id
+                    ));
+                    act.reject_order(order, OrderStatus::RestaurantClosed);
+                    return;
+                }
+                act.authorize_with_promo_code(order, code, ctx);
+            })
+            .spawn(ctx);
+    }
+
+    /// Answers a resent order request with the order `order_id` placed by its first copy,
+    /// as stored, or as just requested if it was not stored yet.
+    fn answer_resent_order(
+        &mut self,
+        order: OrderDTO,
+        order_id: u64,
+        request_id: u64,
+        storage: Addr<Storage>,
+        ctx: &mut Context<Self>,
+    ) {
+        self.logger.info(format!(
+            "Request {} of client {} was already placed as order {}",
+            request_id, order.client_id, order_id
+        ));
+        storage
+            .send(GetOrder { order_id })
+            .into_actor(self)
+            .map(move |res, act, _ctx| {
+                let order = match res {
+                    Ok(Some(stored)) => stored,
+                    _ => OrderDTO {
+                        order_id,
+                        status: OrderStatus::Requested,
+                        ..order
+                    },
+                };
+                act.send_to_coordinator(NotifyOrderUpdated {
+                    peer_id: order.client_id.clone(),
+                    order,
+                    update_id: 0,
+                    request_id,
+                    decline: None,
+                });
+            })
+            .spawn(ctx);
+    }
+
+    /// Stores an authorized order, unless its timeline shows it was already stored, by this
+    /// or a previous leader, when the gateway answered a repeated authorization.
+    fn authorize_once(
+        &mut self,
+        order: OrderDTO,
+        coordinator: Addr<Coordinator>,
+        ctx: &mut Context<Self>,
+    ) {
+        let Some(storage) = self.storage_address.clone() else {
+            self.handle_authorized_order(&order, coordinator, ctx);
+            return;
+        };
+        storage
+            .send(GetOrderTimeline {
+                order_id: order.order_id,
+            })
+            .into_actor(self)
+            .map(move |res, act, ctx| {
+                let stored = res.is_ok_and(|timeline| {
+                    timeline
+                        .iter()
+                        .any(|event| event.kind == OrderEventKind::Created)
+                });
+                if stored {
+                    act.logger.warn(format!(
+                        "Order {} was already authorized, ignoring repeated result",
+                        order.order_id
+                    ));
+                    return;
+                }
+                act.handle_authorized_order(&order, coordinator, ctx);
+            })
+            // Los resultados siguientes esperan, así ven la orden ya guardada
+            .wait(ctx);
+    }
+
+    /// Applies the promo `code` to `order`, if any, and asks the PaymentGateway to authorize
+    /// it. Orders whose code cannot be used are rejected with the
+    /// [`OrderStatus::InvalidPromoCode`] status.
+    fn authorize_with_promo_code(
+        &mut self,
+        mut order: OrderDTO,
+        code: Option<String>,
+        ctx: &mut Context<Self>,
+    ) {
+        let (Some(code), Some(promotion)) = (code, self.promotion_address.clone()) else {
+            // Notifica al PaymentGateway para que procese el pago
+            self.send_to_payment_gateway(PaymentRequest::Authorize(order));
+            return;
+        };
+        promotion
+            .send(ApplyPromoCode {
+                code: code.clone(),
+                order: order.clone(),
+            })
+            .into_actor(self)
+            .map(move |res, act, _ctx| {
+                let rejection = match res {
+                    Ok(Ok(redemption)) => {
+                        act.logger.info(format!(
+                            "Promo code {} takes {} cents off order {}",
+                            redemption.code, redemption.discount, order.order_id
+                        ));
+                        order.discount = redemption.discount;
+                        act.pending_redemptions.insert(order.order_id, redemption);
+                        act.send_to_payment_gateway(PaymentRequest::Authorize(order));
+                        return;
+                    }
+                    Ok(Err(rejection)) => rejection.to_string(),
+                    Err(e) => e.to_string(),
+                };
+                act.logger.warn(format!(
+                    "Rejecting order {} with promo code {}: {}",
+                    order.order_id, code, rejection
+                ));
+                act.reject_order(order, OrderStatus::InvalidPromoCode);
+            })
+            .spawn(ctx);
+    }
 }
 
 impl Actor for OrderService {
     type Context = Context<Self>;
 
-    /// Initializes the PaymentGateway communicator when the actor starts.
+    /// Initializes the PaymentGateway communicator when the actor starts, or schedules a
+    /// reconnection if no gateway was reachable, and starts the health checks.
     fn started(&mut self, ctx: &mut Self::Context) {
         if let Some(stream) = self.pending_stream.take() {
-            let communicator = Communicator::new(stream, ctx.address(), PeerType::CoordinatorType);
-            self.payment_gateway_address = Some(communicator);
+            self.use_payment_gateway(stream, ctx);
         } else {
-            self.logger.error("Failed to connect to PaymentGateway");
+            self.payment_gateway_unreachable(ctx);
+        }
+        ctx.run_interval(PAYMENT_GATEWAY_HEALTH_INTERVAL, |act, ctx| {
+            act.check_payment_gateway(ctx)
+        });
+    }
+}
+
+/// Handles the start of a new leader epoch. When its coordinator becomes the leader, the
+/// service bills again the orders the previous leader did not get confirmed.
+impl Handler<SetLeaderEpoch> for OrderService {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetLeaderEpoch, ctx: &mut Self::Context) -> Self::Result {
+        self.epoch = msg.epoch;
+        let became_leader = msg.leader && !self.leading;
+        self.leading = msg.leader;
+        if became_leader {
+            self.replay_payment_outbox(ctx);
         }
     }
 }
@@ -184,47 +1293,190 @@ impl Handler<SetActorsAddresses> for OrderService {
     fn handle(&mut self, msg: SetActorsAddresses, _ctx: &mut Self::Context) -> Self::Result {
         self.coordinator_address = Some(msg.coordinator_addr);
         self.storage_address = Some(msg.storage_addr);
+        self.promotion_address = Some(msg.promotion_addr);
     }
 }
 
-/// Handles order requests from clients by forwarding them to the PaymentGateway for authorization.
+/// Handles order requests from clients by forwarding them to the PaymentGateway for
+/// authorization, or queuing them while the gateway is unreachable.
+///
+/// The order belongs to the marketplace the client registered in, as stored, whatever the
+/// request says. Orders from clients outside the zones of the restaurant are rejected with
+/// the [`OrderStatus::OutOfZone`] status, orders to a restaurant of another marketplace with
+/// the [`OrderStatus::OtherTenant`] status, orders to a restaurant outside its opening hours
+/// with the [`OrderStatus::RestaurantClosed`] status, and orders with a promo code that
+/// cannot be used with the [`OrderStatus::InvalidPromoCode`] status.
 impl Handler<RequestThisOrder> for OrderService {
     type Result = ();
 
-    fn handle(&mut self, msg: RequestThisOrder, _ctx: &mut Self::Context) -> Self::Result {
-        // Notifica al PaymentGateway para que procese el pago
-        if let Some(communicator) = self.payment_gateway_address.as_ref() {
-            if let Some(sender) = communicator.sender.as_ref() {
-                let socket_addr = communicator.local_address;
-                let auth_message = NetworkMessage::RequestAuthorization(RequestAuthorization {
-                    origin_address: socket_addr,
-                    order: msg.order,
-                });
-                sender.do_send(auth_message);
-            } else {
-                self.logger
-                    .error("PaymentGateway Communicator sender not initialized");
+    fn handle(&mut self, msg: RequestThisOrder, ctx: &mut Self::Context) -> Self::Result {
+        let mut order = msg.order;
+        order.order_id = self.next_order_id();
+        order.tenant_id = TenantId::default();
+        // El descuento y los precios los decide el servidor, no el cliente
+        order.discount = 0;
+        for item in &mut order.items {
+            item.unit_price = ORDER_PRICE_CENTS;
+        }
+        let code = msg.promo_code.filter(|code| !code.trim().is_empty());
+        let request_id = msg.request_id;
+        let Some(storage) = self.storage_address.clone() else {
+            self.place_order(order, request_id, code, ctx);
+            return;
+        };
+        let get_client = storage.send(GetClient {
+            client_id: order.client_id.clone(),
+        });
+        // Un pedido reenviado conserva el ID que recibió la primera vez, aunque lo haya
+        // recibido de otro líder
+        let claim = (request_id != 0).then(|| {
+            storage.send(ClaimOrderRequest {
+                client_id: order.client_id.clone(),
+                request: OrderRequest {
+                    request_id,
+                    order_id: order.order_id,
+                },
+            })
+        });
+        async move {
+            let client = get_client.await;
+            let claimed = match claim {
+                Some(claim) => Some(claim.await),
+                None => None,
+            };
+            (client, claimed)
+        }
+        .into_actor(self)
+        .map(move |(client, claimed), act, ctx| {
+            // El marketplace lo decide el registro del cliente, no lo que diga el pedido
+            if let Ok(Some(client)) = client {
+                order.tenant_id = client.tenant_id;
             }
-        } else {
-            self.logger
-                .error("PaymentGateway Communicator not initialized");
+            match claimed {
+                Some(Ok(order_id)) if order_id != order.order_id => {
+                    act.answer_resent_order(order, order_id, request_id, storage, ctx);
+                }
+                Some(Err(e)) => {
+                    act.logger.error(format!(
+                        "Error claiming request {} of client {}: {}",
+                        request_id, order.client_id, e
+                    ));
+                    act.place_order(order, request_id, code, ctx);
+                }
+                _ => act.place_order(order, request_id, code, ctx),
+            }
+        })
+        .spawn(ctx);
+    }
+}
+
+/// Handles order cancellations requested by clients, checking the stored status of the order.
+impl Handler<CancelMyOrder> for OrderService {
+    type Result = ();
+
+    fn handle(&mut self, msg: CancelMyOrder, ctx: &mut Self::Context) -> Self::Result {
+        let Some(storage) = self.storage_address.clone() else {
+            self.logger.error("Storage address not set");
+            return;
+        };
+        storage
+            .send(GetOrder {
+                order_id: msg.order_id,
+            })
+            .into_actor(self)
+            .map(move |res, act, ctx| match res {
+                Ok(Some(order)) if order.client_id == msg.client_id => {
+                    act.cancel_order(order, msg.waive_fee, storage, ctx);
+                }
+                Ok(_) => {
+                    act.logger.warn(format!(
+                        "Order {} not found for client {}, cannot cancel it",
+                        msg.order_id, msg.client_id
+                    ));
+                }
+                Err(e) => {
+                    act.logger
+                        .error(format!("Error retrieving order {}: {}", msg.order_id, e));
+                }
+            })
+            .spawn(ctx);
+    }
+}
+
+/// Handles an order that no delivery accepted in time by offering it again, after an
+/// increasing wait and to the deliveries in a wider radius, and telling the client. After
+/// [`DELIVERY_REOFFER_ATTEMPTS`] attempts the order is cancelled.
+impl Handler<DeliveryOffersExpired> for OrderService {
+    type Result = ();
+
+    fn handle(&mut self, msg: DeliveryOffersExpired, ctx: &mut Self::Context) -> Self::Result {
+        let order = msg.order;
+        let order_id = order.order_id;
+        let attempts = self.delivery_reoffers.entry(order_id).or_default();
+        *attempts += 1;
+        let attempt = *attempts;
+        if attempt > DELIVERY_REOFFER_ATTEMPTS {
+            self.delivery_reoffers.remove(&order_id);
+            self.logger.warn(format!(
+                "No delivery took order {} after {} attempts, cancelling it",
+                order_id, DELIVERY_REOFFER_ATTEMPTS
+            ));
+            self.send_to_coordinator(CancelOrder {
+                order: OrderDTO {
+                    status: OrderStatus::Cancelled,
+                    delivery_id: None,
+                    expected_delivery_time: 0,
+                    expected_preparation_time: 0,
+                    time_stamp: HlcTimestamp::now(),
+                    ..order
+                },
+                reason: None,
+            });
+            return;
         }
+        let (wait, radius) = reoffer_schedule(attempt);
+        self.logger.info(format!(
+            "Offering order {} again in {:?} within {} blocks (attempt {} of {})",
+            order_id, wait, radius, attempt, DELIVERY_REOFFER_ATTEMPTS
+        ));
+        self.send_to_coordinator(StillLookingForDelivery {
+            order: order.clone(),
+            attempt,
+            max_attempts: DELIVERY_REOFFER_ATTEMPTS,
+        });
+        ctx.run_later(wait, move |act, ctx| {
+            act.retry_delivery_search(order, radius, ctx);
+        });
     }
 }
 
 /// Handles payment authorization results and updates the order accordingly.
+///
+/// A result is only applied once: results of orders not waiting for one, and authorizations
+/// of orders already stored (even if they already finished), are ignored.
 impl Handler<AuthorizationResult> for OrderService {
     type Result = ();
 
-    fn handle(&mut self, msg: AuthorizationResult, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, msg: AuthorizationResult, ctx: &mut Self::Context) -> Self::Result {
         let order = msg.result;
+        if self
+            .awaiting_authorization
+            .remove(&order.order_id)
+            .is_none()
+        {
+            self.logger.warn(format!(
+                "Ignoring repeated authorization result for order {}",
+                order.order_id
+            ));
+            return;
+        }
         if let Some(coordinator) = &self.coordinator_address {
             match order.status {
                 OrderStatus::Authorized => {
-                    self.handle_authorized_order(&order, coordinator.clone());
+                    self.authorize_once(order, coordinator.clone(), ctx);
                 }
                 OrderStatus::Unauthorized => {
-                    self.handle_unauthorized_order(&order, coordinator.clone());
+                    self.handle_unauthorized_order(&order, msg.decline, coordinator.clone());
                 }
                 _ => {
                     self.logger.error(format!(
@@ -244,29 +1496,52 @@ impl Handler<NetworkMessage> for OrderService {
     type Result = ();
 
     fn handle(&mut self, msg: NetworkMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _span =
+            info_span!("order_service", msg = msg.kind(), order_id = msg.order_id()).entered();
+        // Cualquier mensaje del gateway cuenta como respuesta al chequeo de salud
+        if !matches!(msg, NetworkMessage::ConnectionClosed(_)) {
+            self.last_gateway_answer = Instant::now();
+        }
         match msg {
+            NetworkMessage::Pong(_) => {}
             NetworkMessage::AuthorizationResult(result) => {
                 ctx.address().do_send(result);
             }
             NetworkMessage::PaymentCompleted(payment) => {
+                self.awaiting_bills.remove(&payment.order.order_id);
+                self.complete_payment(payment, ctx);
+            }
+            NetworkMessage::PaymentRefunded(refund) => {
                 self.logger.info(format!(
-                    "Payment completed for order {}",
-                    payment.order.order_id
+                    "Payment refunded for order {}",
+                    refund.order.order_id
                 ));
-                // Como se terminó la entrega, se elimina la orden del Storage
-                self.send_to_storage(RemoveOrder {
-                    order: payment.order.clone(),
-                });
-                // Notificar al  Coordinator para que informe al restaurante
-                self.send_to_coordinator(OrderFinalized {
-                    order: payment.order.clone(),
-                });
-                // Notificar al  Coordinator para que informe al cliente
-                self.send_to_coordinator(NotifyOrderUpdated {
-                    peer_id: payment.order.client_id.clone(),
-                    order: payment.order.clone(),
+            }
+            NetworkMessage::DeliveryPaidOut(payout) => {
+                self.logger.info(format!(
+                    "Paid {} cents to delivery {} for order {}",
+                    payout.amount, payout.delivery_id, payout.order_id
+                ));
+                self.send_to_storage(SetEarningPaidOut {
+                    delivery_id: payout.delivery_id,
+                    order_id: payout.order_id,
+                    time_stamp: None,
                 });
             }
+            NetworkMessage::ConnectionClosed(closed)
+                if self
+                    .payment_gateway_address
+                    .as_ref()
+                    .is_some_and(|gateway| gateway.peer_address == closed.remote_addr) =>
+            {
+                self.logger.error(format!(
+                    "Lost connection to PaymentGateway {}",
+                    closed.remote_addr
+                ));
+                self.payment_gateway_unreachable(ctx);
+            }
+            // De una conexión con un gateway que ya se dejó de usar
+            NetworkMessage::ConnectionClosed(_) => {}
             _ => {
                 self.logger.error(format!(
                     "Unhandled NetworkMessage in OrderService: {:?}",
@@ -278,77 +1553,55 @@ impl Handler<NetworkMessage> for OrderService {
 }
 
 /// Handles updates to the status of an order and coordinates changes with Storage and Coordinator.
+///
+/// Only the changes the order can make from its current status are applied (see
+/// [`OrderStatus::can_become`]); the peer that sent any other one is told how the order is.
 impl Handler<UpdateOrderStatus> for OrderService {
     type Result = ();
 
     fn handle(&mut self, msg: UpdateOrderStatus, ctx: &mut Self::Context) -> Self::Result {
-        match msg.order.status {
-            OrderStatus::Pending => {
-                ctx.address().do_send(AddPendingOrderToRestaurant {
-                    order: msg.order.clone(),
-                    restaurant_id: msg.order.restaurant_id.clone(),
-                });
-                ctx.address().do_send(SetOrderStatus {
-                    order: msg.order.clone(),
-                    order_status: OrderStatus::Pending,
-                });
-            }
-            OrderStatus::Cancelled => {
-                ctx.address().do_send(RemoveOrder {
-                    order: msg.order.clone(),
-                });
-            }
-            OrderStatus::Preparing => {
-                ctx.address().do_send(SetOrderStatus {
-                    order: msg.order.clone(),
-                    order_status: OrderStatus::Preparing,
-                });
-                ctx.address().do_send(RemovePendingOrderToRestaurant {
-                    order: msg.order.clone(),
-                    restaurant_id: msg.order.restaurant_id.clone(),
-                });
-                ctx.address().do_send(AddAuthorizedOrderToRestaurant {
-                    order: msg.order.clone(),
-                    restaurant_id: msg.order.restaurant_id.clone(),
-                });
-            }
-            OrderStatus::ReadyForDelivery => {
-                ctx.address().do_send(SetOrderStatus {
-                    order: msg.order.clone(),
-                    order_status: OrderStatus::ReadyForDelivery,
-                });
-            }
-            OrderStatus::Delivering => {
-                let delivery_id_opt = msg.order.delivery_id.clone();
-                if delivery_id_opt.is_none() {
-                    self.logger.error(format!(
-                        "Order {} is Delivering but has no delivery_id",
-                        msg.order.order_id
-                    ));
-                    return;
-                }
-                ctx.address().do_send(SetOrderExpectedTime {
-                    order_id: msg.order.order_id,
-                    expected_time: msg.order.expected_delivery_time,
-                });
-
-                self.send_to_coordinator(NotifyOrderUpdated {
-                    peer_id: msg.order.client_id.clone(),
-                    order: msg.order.clone(),
-                });
-            }
-            OrderStatus::Delivered => {
-                ctx.address().do_send(OrderFinalized {
-                    order: msg.order.clone(),
-                });
-            }
-            _ => {
-                self.logger.error(format!(
-                    "Unexpected order status {:?} for order {}",
-                    msg.order.status, msg.order.order_id
-                ));
-            }
+        // El cliente canceló el pedido: el restaurante o el delivery todavía no se enteraron
+        if self.cancelled_orders.contains(&msg.order.order_id) {
+            self.logger.info(format!(
+                "Ignoring status {:?} of cancelled order {}",
+                msg.order.status, msg.order.order_id
+            ));
+            return;
         }
+        let order_id = msg.order.order_id;
+        // Con el último estado conocido se validan al instante los cambios que llegan seguidos
+        if self
+            .orders
+            .get(&order_id)
+            .is_some_and(|current| current.can_become(&msg.order.status))
+        {
+            self.apply_status_update(msg, ctx);
+            return;
+        }
+        // Si no se conoce el pedido o el cambio no parece válido, decide el Storage
+        let Some(storage) = self.storage_address.clone() else {
+            self.logger.error("Storage address not set");
+            return;
+        };
+        storage
+            .send(GetOrder { order_id })
+            .into_actor(self)
+            .map(move |res, act, ctx| match res {
+                Ok(Some(current)) if current.status.can_become(&msg.order.status) => {
+                    act.apply_status_update(msg, ctx);
+                }
+                Ok(Some(current)) => act.reject_status_update(current, msg),
+                Ok(None) => act.logger.warn(format!(
+                    "Ignoring status {:?} of unknown order {}",
+                    msg.order.status, order_id
+                )),
+                Err(e) => act.logger.error(format!(
+                    "Error retrieving order {} to check its status: {}",
+                    order_id, e
+                )),
+            })
+            // Los cambios siguientes esperan, para validarse en orden
+            .wait(ctx);
     }
 }
 
@@ -366,6 +1619,9 @@ impl Handler<DeliveryAccepted> for OrderService {
             coordinator.do_send(NotifyOrderUpdated {
                 peer_id: msg.order.client_id.clone(),
                 order: msg.order.clone(),
+                update_id: 0,
+                request_id: 0,
+                decline: None,
             });
         } else {
             self.logger.error("Coordinator address not set");
@@ -382,25 +1638,73 @@ impl Handler<AcceptedOrder> for OrderService {
             "Resending AcceptOrder to Storage: {:?}",
             msg.order.order_id
         ));
+        let Some(storage) = self.storage_address.clone() else {
+            self.logger.error("Storage address not set");
+            return;
+        };
         let message = AddOrderAccepted {
             order: msg.order.clone(),
             delivery: msg.delivery_info.clone(),
             addr: ctx.address().clone(),
+            epoch: self.epoch,
         };
-        self.send_to_storage(message);
+        // Si el Storage rechaza la aceptación, él mismo avisa con un DeliveryNoNeeded
+        storage
+            .send(message)
+            .into_actor(self)
+            .then(move |res, act, ctx| {
+                match res {
+                    Ok(Some(accepted)) => act.confirm_acceptance(
+                        DeliveryAvailable {
+                            order: msg.order,
+                            delivery_info: msg.delivery_info,
+                        },
+                        accepted,
+                        ctx,
+                    ),
+                    Ok(None) => {}
+                    Err(e) => act.logger.error(format!(
+                        "Error accepting order {}: {}",
+                        msg.order.order_id, e
+                    )),
+                }
+                fut::ready(())
+            })
+            .spawn(ctx);
     }
 }
 
-/// Handles notifications that a delivery agent is available for an order.
-impl Handler<DeliveryAvailable> for OrderService {
-    type Result = ();
-
-    fn handle(&mut self, msg: DeliveryAvailable, _ctx: &mut Self::Context) -> Self::Result {
+impl OrderService {
+    /// Confirms that the delivery of `msg` is available for its order once the acceptance
+    /// `accepted`, logged by the Storage, is replicated. Otherwise the delivery is released.
+    fn confirm_acceptance(
+        &mut self,
+        msg: DeliveryAvailable,
+        accepted: LoggedWrite,
+        ctx: &mut Context<Self>,
+    ) {
         self.logger.info(format!(
             "Delivery {} is available",
             msg.delivery_info.delivery_id
         ));
-        self.send_to_coordinator(msg.clone());
+        // La aceptación se confirma recién cuando sobrevive a un cambio de líder
+        self.replicate_then(accepted, ctx, move |act, result| {
+            if result.is_ok() {
+                act.send_to_coordinator(msg);
+                return;
+            }
+            act.logger.warn(format!(
+                "Acceptance of order {} by {} not replicated, releasing the delivery",
+                msg.order.order_id, msg.delivery_info.delivery_id
+            ));
+            act.send_to_storage(RemoveAcceptedDeliveries {
+                order_id: msg.order.order_id,
+            });
+            act.send_to_coordinator(DeliveryNoNeeded {
+                order: msg.order,
+                delivery_info: msg.delivery_info,
+            });
+        });
     }
 }
 
@@ -425,25 +1729,17 @@ impl Handler<OrderFinalized> for OrderService {
     fn handle(&mut self, msg: OrderFinalized, ctx: &mut Self::Context) -> Self::Result {
         self.logger
             .info(format!("Finalizing order: {:?}", msg.order.order_id));
-        if let Some(communicator) = self.payment_gateway_address.as_ref() {
-            let socket_addr = communicator.local_address;
-            if let Some(sender) = communicator.sender.as_ref() {
-                let auth_message = NetworkMessage::BillPayment(BillPayment {
-                    origin_address: socket_addr,
-                    order: msg.order.clone(),
-                });
-                sender.do_send(auth_message);
-            } else {
-                self.logger
-                    .error("PaymentGateway Communicator sender not initialized");
-            }
-        } else {
-            self.logger
-                .error("PaymentGateway Communicator not initialized");
-        }
+        // El cobro queda en el outbox hasta que el gateway lo confirme
+        self.send_to_storage(AddOutboxPayment {
+            order: msg.order.clone(),
+            time_stamp: None,
+        });
+        self.send_to_payment_gateway(PaymentRequest::Bill(msg.order.clone()));
+        self.record_delivery_earning(&msg.order, ctx);
         // eliminar datos asociados a la orden -> eliminar el cliente, si está en el restaurant y si está en el delivery
         ctx.address().do_send(RemoveOrder {
             order: msg.order.clone(),
+            event: None,
         });
     }
 }
@@ -457,16 +1753,30 @@ impl Handler<DeliverThisOrder> for OrderService {
             "Finishing delivery assignment for order {}",
             msg.order.order_id
         ));
-        // Notificar al Coordinator para que informe al cliente
-        self.send_to_storage(FinishDeliveryAssignment {
-            order: msg.order.clone(),
-            addr: ctx.address().clone(),
-        });
-        self.send_to_coordinator(msg.clone());
+        let Some(storage) = self.storage_address.clone() else {
+            self.logger.error("Storage address not set");
+            return;
+        };
+        storage
+            .send(FinishDeliveryAssignment {
+                order: msg.order.clone(),
+                addr: ctx.address().clone(),
+            })
+            .into_actor(self)
+            .map(move |res, act, _ctx| {
+                // Notificar al Coordinator para que informe al delivery, sólo una vez
+                if matches!(res, Ok(true)) {
+                    act.send_to_coordinator(msg);
+                }
+            })
+            .spawn(ctx);
     }
 }
 
 /// Handles requests to remove an order from the system.
+///
+/// Once the order is removed from the Storage its late status updates are ignored as those
+/// of an unknown order, so it is no longer kept among the cancelled ones.
 impl Handler<RemoveOrder> for OrderService {
     type Result = ();
 
@@ -475,6 +1785,8 @@ impl Handler<RemoveOrder> for OrderService {
             "Resending Remove Order to Storage: {:?}",
             msg.order.order_id
         ));
+        self.orders.remove(&msg.order.order_id);
+        self.cancelled_orders.remove(&msg.order.order_id);
         self.send_to_storage(msg.clone());
     }
 }
@@ -568,22 +1880,46 @@ impl Handler<SetCurrentOrderToDelivery> for OrderService {
 impl Handler<SetOrderStatus> for OrderService {
     type Result = ();
 
-    fn handle(&mut self, msg: SetOrderStatus, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: SetOrderStatus, ctx: &mut Self::Context) -> Self::Result {
         self.logger.info(format!(
             "Sending SetOrderStatus to Storage: order {} -> status {:?}",
             msg.order.order_id.clone(),
             msg.order_status
         ));
-        if let Some(addr) = self.storage_address.as_ref() {
-            addr.do_send(msg.clone());
-        } else {
-            self.logger.error("Storage address not set");
+        self.orders
+            .insert(msg.order.order_id, msg.order_status.clone());
+        // El evento viaja con la escritura, así todos los coordinadores registran el mismo
+        if msg.event.is_none() {
+            let mut changed = msg.order.clone();
+            changed.status = msg.order_status.clone();
+            msg.event = Some(OrderEvent::new(
+                OrderEventKind::StatusChanged,
+                &changed,
+                &self.identity.user_id,
+            ));
         }
-        // Notificar al Coordinator para que informe al cliente
-        self.send_to_coordinator(NotifyOrderUpdated {
-            peer_id: msg.order.client_id.clone(),
-            order: msg.order.clone(),
-        });
+        // Notificar al Coordinator para que informe al cliente una vez replicado
+        let order = msg.order.clone();
+        self.write_then(
+            StorageLogMessage::SetOrderStatus(msg),
+            ctx,
+            move |act, result| {
+                if let Err(e) = result {
+                    act.logger.warn(format!(
+                        "Status of order {} not confirmed, not notifying it: {}",
+                        order.order_id, e
+                    ));
+                    return;
+                }
+                act.send_to_coordinator(NotifyOrderUpdated {
+                    peer_id: order.client_id.clone(),
+                    order,
+                    update_id: 0,
+                    request_id: 0,
+                    decline: None,
+                });
+            },
+        );
     }
 }
 
@@ -613,3 +1949,243 @@ impl Handler<SetOrderExpectedTime> for OrderService {
         self.send_to_storage(msg);
     }
 }
+
+impl Handler<SetOrderPreparationTime> for OrderService {
+    type Result = ();
+
+    fn handle(&mut self, msg: SetOrderPreparationTime, _ctx: &mut Self::Context) -> Self::Result {
+        self.logger.info(format!(
+            "Sending SetOrderPreparationTime to Storage: order {} -> preparation time {:?}",
+            msg.order_id, msg.preparation_time
+        ));
+        self.send_to_storage(msg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server_actors::storage_view::StorageReadView;
+    use common::messages::internal_messages::{AddClient, AddRestaurant};
+    use common::network::memory;
+    use common::types::dtos::{ClientDTO, OrderItem, RestaurantDTO};
+    use common::types::order_priority::OrderPriority;
+    use std::sync::{Arc, Mutex};
+
+    /// A PaymentGateway that keeps every message it receives.
+    struct Gateway {
+        received: Arc<Mutex<Vec<NetworkMessage>>>,
+    }
+
+    impl Actor for Gateway {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<NetworkMessage> for Gateway {
+        type Result = ();
+
+        fn handle(&mut self, msg: NetworkMessage, _ctx: &mut Context<Self>) {
+            self.received.lock().unwrap().push(msg);
+        }
+    }
+
+    fn new_order() -> OrderDTO {
+        OrderDTO {
+            order_id: UNASSIGNED_ORDER_ID,
+            items: vec![OrderItem::new("Pizza", 1)],
+            client_id: "client_1".to_string(),
+            restaurant_id: "restaurant_1".to_string(),
+            delivery_id: None,
+            status: OrderStatus::Requested,
+            client_position: (0.0, 0.0),
+            expected_delivery_time: 0,
+            expected_preparation_time: 0,
+            priority: OrderPriority::Standard,
+            discount: 0,
+            time_stamp: HlcTimestamp::default(),
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    /// Returns the orders the gateway was asked to authorize, once it was asked for at least
+    /// one.
+    async fn wait_for_authorizations(received: &Arc<Mutex<Vec<NetworkMessage>>>) -> Vec<OrderDTO> {
+        let authorizations = || -> Vec<OrderDTO> {
+            received
+                .lock()
+                .unwrap()
+                .iter()
+                .filter_map(|message| match message {
+                    NetworkMessage::RequestAuthorization(msg) => Some(msg.order.clone()),
+                    _ => None,
+                })
+                .collect()
+        };
+        for _ in 0..100 {
+            if !authorizations().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        // Se espera un poco más por si llega una segunda autorización
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        authorizations()
+    }
+
+    /// Starts a service connected to an in-memory PaymentGateway and to an empty storage, and
+    /// returns them with the messages the gateway receives.
+    async fn start_service() -> (
+        Addr<OrderService>,
+        Addr<Storage>,
+        Arc<Mutex<Vec<NetworkMessage>>>,
+        Communicator<Gateway>,
+    ) {
+        memory::enable();
+        let mut listener = memory::bind(payment_gateways_from_env()[0]).unwrap();
+        let identity = PeerIdentity::new(PeerType::CoordinatorType, "server_0");
+        let mut service = OrderService::new(&identity, 0, 1).await;
+        let mut stream = listener.accept().await;
+        PeerIdentity::read_from(&mut stream).await.unwrap();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let gateway = Gateway {
+            received: received.clone(),
+        }
+        .start();
+        let gateway = Communicator::new(
+            PeerStream::Memory(stream),
+            gateway,
+            PeerType::CoordinatorType,
+        );
+        let storage = Storage::new(
+            Context::<Coordinator>::new().address(),
+            "server_0".to_string(),
+            None,
+            StorageReadView::default(),
+        )
+        .start();
+        service.storage_address = Some(storage.clone());
+        (service.start(), storage, received, gateway)
+    }
+
+    fn restaurant(restaurant_id: &str, tenant_id: TenantId) -> AddRestaurant {
+        AddRestaurant {
+            restaurant: RestaurantDTO {
+                restaurant_position: (0.0, 0.0),
+                restaurant_id: restaurant_id.to_string(),
+                authorized_orders: Default::default(),
+                pending_orders: Default::default(),
+                cancellation_fees: Vec::new(),
+                closed: false,
+                tags: Vec::new(),
+                tenant_id,
+                time_stamp: HlcTimestamp::default(),
+            },
+        }
+    }
+
+    #[actix_rt::test]
+    async fn gives_order_ids_that_fall_on_its_own_shard() {
+        let identity = PeerIdentity::new(PeerType::CoordinatorType, "server_0");
+        let mut service = OrderService::new(&identity, 2, 4).await;
+        let mut last = UNASSIGNED_ORDER_ID;
+        for _ in 0..20 {
+            let order_id = service.next_order_id();
+            assert!(order_id > last);
+            assert!(service.owns(order_id));
+            last = order_id;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn a_resent_order_request_is_placed_only_once() {
+        let (service, _storage, received, _gateway) = start_service().await;
+
+        let request = || RequestThisOrder {
+            order: new_order(),
+            promo_code: None,
+            request_id: 7,
+        };
+        service.do_send(request());
+        service.do_send(request());
+        assert_eq!(wait_for_authorizations(&received).await.len(), 1);
+        memory::disable();
+    }
+
+    #[actix_rt::test]
+    async fn an_order_belongs_to_the_tenant_the_client_registered_in() {
+        let (service, storage, received, _gateway) = start_service().await;
+        let staging = TenantId::new("staging");
+        storage
+            .send(AddClient {
+                client: ClientDTO {
+                    client_position: (0.0, 0.0),
+                    client_id: "client_1".to_string(),
+                    client_order: None,
+                    tenant_id: staging.clone(),
+                    time_stamp: HlcTimestamp::default(),
+                },
+            })
+            .await
+            .unwrap();
+        storage
+            .send(restaurant("restaurant_1", staging.clone()))
+            .await
+            .unwrap();
+        storage
+            .send(restaurant("restaurant_2", TenantId::default()))
+            .await
+            .unwrap();
+
+        // El pedido dice ser del marketplace por defecto, pero vale el registro del cliente
+        let request = |restaurant_id: &str, request_id| RequestThisOrder {
+            order: OrderDTO {
+                restaurant_id: restaurant_id.to_string(),
+                ..new_order()
+            },
+            promo_code: None,
+            request_id,
+        };
+        service.do_send(request("restaurant_2", 1));
+        service.do_send(request("restaurant_1", 2));
+        let authorized = wait_for_authorizations(&received).await;
+        assert_eq!(authorized.len(), 1);
+        assert_eq!(authorized[0].restaurant_id, "restaurant_1");
+        assert_eq!(authorized[0].tenant_id, staging);
+        memory::disable();
+    }
+
+    #[actix_rt::test]
+    async fn a_removed_order_is_no_longer_kept_as_cancelled() {
+        let identity = PeerIdentity::new(PeerType::CoordinatorType, "server_0");
+        let mut service = OrderService::new(&identity, 0, 1).await;
+        service.orders.insert(7, OrderStatus::Preparing);
+        service.cancelled_orders.insert(7);
+
+        service.handle(
+            RemoveOrder {
+                order: OrderDTO {
+                    order_id: 7,
+                    status: OrderStatus::Cancelled,
+                    ..new_order()
+                },
+                event: None,
+            },
+            &mut Context::new(),
+        );
+        assert!(service.orders.is_empty());
+        assert!(service.cancelled_orders.is_empty());
+    }
+
+    #[test]
+    fn waits_longer_and_searches_farther_with_each_attempt() {
+        assert_eq!(
+            reoffer_schedule(1),
+            (DELIVERY_REOFFER_BACKOFF, NEARBY_RADIUS * 2.0)
+        );
+        let (second_wait, second_radius) = reoffer_schedule(2);
+        assert_eq!(second_wait, DELIVERY_REOFFER_BACKOFF * 2);
+        assert!(second_radius <= MAX_NEARBY_RADIUS);
+        assert_eq!(reoffer_schedule(3).0, DELIVERY_REOFFER_BACKOFF * 4);
+        assert_eq!(reoffer_schedule(40).1, MAX_NEARBY_RADIUS);
+    }
+}
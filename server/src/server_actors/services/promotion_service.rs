@@ -0,0 +1,95 @@
+use crate::messages::internal_messages::ApplyPromoCode;
+use crate::server_actors::storage::Storage;
+use actix::prelude::*;
+use colored::Color;
+use common::logger::Logger;
+use common::messages::internal_messages::GetPromoRedemptions;
+use common::types::dtos::PromoRedemption;
+use common::types::hlc::HlcTimestamp;
+use common::types::promotion::{PromoRejection, Promotions};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The `PromotionService` actor validates the promo codes the clients use in their orders.
+///
+/// ## Responsibilities
+/// - Keeps the promo codes configured in [`PROMO_CODES`](common::types::promotion::PROMO_CODES_ENV_VAR).
+/// - Checks that a code exists, has not expired and applies to the restaurant of the order.
+/// - Asks the storage whether the client already used the code, since each client can use
+///   a code only once.
+/// - Computes the discount, which the order service takes off the price before asking the
+///   payment gateway to authorize it.
+pub struct PromotionService {
+    /// The promo codes that can be used.
+    pub promotions: Promotions,
+    /// The address of the Storage actor, to check the uses of the codes.
+    pub storage_address: Addr<Storage>,
+    /// Logger instance for events.
+    pub logger: Logger,
+}
+
+impl PromotionService {
+    /// Creates a new `PromotionService` with the promo codes configured in the environment.
+    ///
+    /// ## Arguments
+    /// * `storage_address` - The address of the Storage actor.
+    pub fn new(storage_address: Addr<Storage>) -> Self {
+        Self {
+            promotions: Promotions::from_env(),
+            storage_address,
+            logger: Logger::new("Promotion Service", Color::BrightGreen),
+        }
+    }
+}
+
+impl Actor for PromotionService {
+    type Context = Context<Self>;
+
+    fn started(&mut self, _ctx: &mut Self::Context) {
+        if !self.promotions.is_empty() {
+            self.logger
+                .info(format!("{} promo codes available", self.promotions.len()));
+        }
+    }
+}
+
+/// Handles applying a promo code to a new order.
+///
+/// The same order can apply the code again, so that a retried request is not rejected.
+impl Handler<ApplyPromoCode> for PromotionService {
+    type Result = ResponseFuture<Result<PromoRedemption, PromoRejection>>;
+
+    fn handle(&mut self, msg: ApplyPromoCode, _ctx: &mut Self::Context) -> Self::Result {
+        let Some(promo) = self.promotions.get(&msg.code) else {
+            return Box::pin(async { Err(PromoRejection::Unknown) });
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        let discount = match promo.discount_for(&msg.order, now) {
+            Ok(discount) => discount,
+            Err(rejection) => return Box::pin(async { Err(rejection) }),
+        };
+        let code = promo.code.clone();
+        let storage = self.storage_address.clone();
+        Box::pin(async move {
+            let order = msg.order;
+            let redemptions = storage
+                .send(GetPromoRedemptions { code: code.clone() })
+                .await
+                .unwrap_or_default();
+            if redemptions.iter().any(|redemption| {
+                redemption.client_id == order.client_id && redemption.order_id != order.order_id
+            }) {
+                return Err(PromoRejection::AlreadyRedeemed);
+            }
+            Ok(PromoRedemption {
+                code,
+                client_id: order.client_id,
+                order_id: order.order_id,
+                discount,
+                time_stamp: HlcTimestamp::default(),
+            })
+        })
+    }
+}
@@ -1,58 +1,161 @@
 use crate::messages::internal_messages::{
-    AddOrderAccepted, FinishDeliveryAssignment, GetAllStorage, GetLogsFromIndex, GetMinLogIndex,
+    AddOrderAccepted, AppendWrite, ApplyReplicatedWrite, CheckIntegrity, CompactLog,
+    FinishDeliveryAssignment, GetAllStorage, GetCompactionStatus, GetInFlightOrders,
+    GetLogsFromIndex, GetMinLogIndex, GetStorageDigest, IsRestoredFromLog, LoggedWrite,
+    MergeSnapshot, PublishOrderEvent,
 };
 use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::services::event_publisher::EventPublisher;
+use crate::server_actors::storage_backend::{EntityTable, EntityTables};
+use crate::server_actors::storage_log::{LogRecord, StorageLogFile};
+use crate::server_actors::storage_view::StorageReadView;
 use actix::prelude::*;
 use colored::Color;
 use common::bimap::BiMap;
+use common::constants::{
+    LOG_COMPACTION_MIN_ENTRIES, MAX_COMPLETED_PAYMENTS, MAX_ORDER_REQUESTS_PER_CLIENT,
+    MAX_PENDING_ORDER_UPDATES, MAX_REMOVED_ORDER_TIMELINES, MAX_STORED_ELECTIONS, MAX_TOMBSTONES,
+};
 use common::logger::Logger;
 use common::messages::coordinatormanager_messages::StorageSnapshot;
 use common::messages::internal_messages::{
-    AddAuthorizedOrderToRestaurant, AddClient, AddDelivery, AddOrder, AddPendingOrderToRestaurant,
-    AddRestaurant, ApplyStorageUpdates, GetAllAvailableDeliveries, GetAllRestaurantsInfo,
-    GetClient, GetDeliveries, GetDelivery, GetOrder, GetRestaurant, GetRestaurants,
-    InsertAcceptedDelivery, RemoveAcceptedDeliveries, RemoveAuthorizedOrderToRestaurant,
+    AckOrderUpdates, AddAuthorizedOrderToRestaurant, AddCancellationFee, AddClient, AddDelivery,
+    AddDeliveryEarning, AddOrder, AddOutboxPayment, AddPendingOrderToRestaurant,
+    AddPromoRedemption, AddRestaurant, ApplyStorageUpdates, ClaimOrderRequest, ClaimUserId,
+    CompleteOutboxPayment, GetAcceptedDelivery, GetAllAvailableDeliveries, GetAllRestaurantsInfo,
+    GetClient, GetDeliveries, GetDelivery, GetDeliveryEarnings, GetDeliveryOrders,
+    GetElectionHistory, GetLastKnownOrder, GetNearbyDeliveries, GetNearbyRestaurants, GetOrder,
+    GetOrderTimeline, GetPendingAssignments, GetPendingOrderUpdates, GetPendingPayments,
+    GetPromoRedemptions, GetRestaurant, GetRestaurants, GetStaleUsers, InsertAcceptedDelivery,
+    QueueOrderUpdate, RecordElection, RemoveAcceptedDeliveries, RemoveAuthorizedOrderToRestaurant,
     RemoveClient, RemoveDelivery, RemoveOrder, RemovePendingOrderToRestaurant, RemoveRestaurant,
     RemoveUser, SetCurrentClientToDelivery, SetCurrentOrderToDelivery, SetDeliveryPosition,
-    SetDeliveryStatus, SetDeliveryToOrder, SetOrderExpectedTime, SetOrderStatus, StorageLogMessage,
+    SetDeliveryStatus, SetDeliveryToOrder, SetEarningPaidOut, SetOrderExpectedTime,
+    SetOrderPreparationTime, SetOrderStatus, SetRestaurantOpen, SetRestaurantTags, SetUserOffline,
+    StorageLogMessage, TouchUsers,
 };
-use common::messages::{DeliveryAvailable, DeliveryNoNeeded};
+use common::messages::shared_messages::StorageReport;
+use common::messages::{DeliveryAvailable, DeliveryNoNeeded, NotifyOrderUpdated};
+use common::types::order_event::{OrderEvent, OrderEventKind};
 use common::types::order_status::OrderStatus;
 use common::types::{
-    dtos::{ClientDTO, DeliveryDTO, OrderDTO, RestaurantDTO, Snapshot},
-    restaurant_info::RestaurantInfo,
+    delivery_status::DeliveryStatus,
+    dtos::{
+        ClientDTO, DeliveryDTO, EarningDTO, EarningsReport, ElectionRecord, OrderDTO, OrderRequest,
+        OutboxPayment, PromoRedemption, RestaurantDTO, Snapshot, Tombstones,
+    },
+    hlc::HlcTimestamp,
+    secret_hash::SecretHash,
+    storage_entity::{EntityDigest, StorageDigest, StorageEntity},
+    storage_violation::StorageViolation,
+    tenant::TenantId,
 };
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
+use common::utils::calculate_distance;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::info_span;
+
+/// State of the storage log and its compactions.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct CompactionStatus {
+    /// Index of the oldest entry kept in the log.
+    pub min_log_index: u64,
+    /// Index of the next entry of the log.
+    pub next_log_id: u64,
+    /// Entries kept in memory to replicate to other coordinators.
+    pub entries: usize,
+    /// Operations appended to the persisted log since it was last replaced by a snapshot.
+    pub persisted_since_snapshot: u64,
+    /// Compactions that dropped entries or wrote a snapshot.
+    pub compactions: u64,
+    /// Entries dropped by every compaction.
+    pub dropped_entries: u64,
+    /// The first index still needed by some follower in the last compaction.
+    pub confirmed_up_to: u64,
+    /// When the log was last compacted, in milliseconds since the Unix epoch.
+    pub last_compaction_millis: Option<u64>,
+}
 
 /// The `Storage` actor is responsible for maintaining and updating all persistent state in the system,
 /// including clients, restaurants, deliveries, orders, and the storage log.
 ///
 /// # Responsibilities
-/// - Stores and manages all entities (clients, restaurants, deliveries, orders).
+/// - Stores and manages all entities (clients, restaurants, deliveries, orders), in memory or
+///   on disk depending on the [`StorageBackend`](crate::server_actors::storage_backend::StorageBackend).
 /// - Handles log-based persistence and synchronization for distributed recovery.
+/// - Persists every operation to a [`StorageLogFile`] and replays it at boot.
 /// - Applies and logs all state-changing operations.
 /// - Provides snapshots and log segments for recovery and replication.
+/// - Keeps the timeline of every order ([`OrderEvent`]s), including the recently removed ones.
+/// - Keeps the order updates sent to each user until the user acknowledges them.
+/// - Keeps what each delivery earned for the orders it delivered, and whether it was paid out.
+/// - Keeps the bills sent to the payment gateway until the gateway confirms them.
+/// - Keeps when each client and delivery was last seen, to find the ones that left.
 /// - Coordinates with the `Coordinator` actor for system-wide updates.
 pub struct Storage {
     /// Dictionary with information about clients.
-    pub clients: HashMap<String, ClientDTO>,
+    pub clients: EntityTable<String, ClientDTO>,
     /// Dictionary with information about restaurants.
-    pub restaurants: HashMap<String, RestaurantDTO>,
+    pub restaurants: EntityTable<String, RestaurantDTO>,
     /// Dictionary with information about deliveries.
-    pub deliverys: HashMap<String, DeliveryDTO>,
+    pub deliverys: EntityTable<String, DeliveryDTO>,
     /// Dictionary of orders.
-    pub orders: HashMap<u64, OrderDTO>,
+    pub orders: EntityTable<u64, OrderDTO>,
+    /// Copy of the restaurants and deliveries read by the nearby services.
+    read_view: StorageReadView,
     /// Deliveries that have accepted orders.
     pub accepted_deliveries: BiMap<u64, String>,
+    /// Leader epoch in which each accepted delivery was decided.
+    pub accepted_epochs: HashMap<u64, u64>,
+    /// Timeline of each order, oldest event first.
+    pub order_events: HashMap<u64, Vec<OrderEvent>>,
+    /// Removed orders whose timeline is still kept, oldest removal first.
+    removed_timelines: VecDeque<u64>,
+    /// Last state of the removed orders whose timeline is still kept, stamped when they were
+    /// removed.
+    pub removed_orders: HashMap<u64, OrderDTO>,
+    /// When the recently removed clients, restaurants, deliveries and orders were removed.
+    pub tombstones: Tombstones,
+    /// Order updates not acknowledged yet by each user, oldest first.
+    pub order_updates: HashMap<String, VecDeque<NotifyOrderUpdated>>,
+    /// ID of the next order update.
+    pub next_order_update_id: u64,
+    /// Earnings of each delivery, oldest first.
+    pub delivery_earnings: HashMap<String, Vec<EarningDTO>>,
+    /// Most recent leader changes, oldest epoch first.
+    pub elections: VecDeque<ElectionRecord>,
+    /// Uses of each promo code, oldest first.
+    pub promo_redemptions: HashMap<String, Vec<PromoRedemption>>,
+    /// Secret each user ID was claimed with, by user ID.
+    pub user_secrets: HashMap<String, SecretHash>,
+    /// Bills sent to the payment gateway, by order ID.
+    pub payment_outbox: HashMap<u64, OutboxPayment>,
+    /// Most recent order requests of each client, lowest order ID first.
+    pub order_requests: HashMap<String, Vec<OrderRequest>>,
+    /// When each client and delivery registered or was last seen connected, by user ID.
+    pub last_seen: HashMap<String, HlcTimestamp>,
     /// List of storage log updates.
     pub storage_updates: HashMap<u64, StorageLogMessage>,
     /// Index of the next log entry.
     pub next_log_id: u64,
+    /// Index the update being applied is logged at, when it was replicated by the leader.
+    replicated_index: Option<u64>,
     /// Index of the minimum persistent operation in the log.
     pub min_persistent_log_index: u64,
     /// Address of the associated `Coordinator`.
     pub coordinator: Addr<Coordinator>,
+    /// ID of the coordinator, recorded in the events it applies first.
+    pub node_id: String,
+    /// File where every operation is persisted, if persistence is enabled.
+    log_file: Option<StorageLogFile>,
+    /// Publisher of the lifecycle of the orders this coordinator changes first, if any.
+    event_publisher: Option<Addr<EventPublisher>>,
+    /// Whether the state was rebuilt from the persisted log at boot.
+    restored_from_log: bool,
+    /// Counters of the log compactions.
+    compaction: CompactionStatus,
     /// Logger for storage events.
     pub logger: Logger,
 }
@@ -62,71 +165,805 @@ impl Storage {
     ///
     /// # Arguments
     /// * `coordinator` - The address of the `Coordinator` actor.
-    pub fn new(coordinator: Addr<Coordinator>) -> Self {
+    /// * `node_id` - The ID of the coordinator.
+    /// * `log_file` - File where operations are persisted, replayed when the actor starts.
+    /// * `read_view` - The view kept up to date with the restaurants and deliveries.
+    pub fn new(
+        coordinator: Addr<Coordinator>,
+        node_id: String,
+        log_file: Option<StorageLogFile>,
+        read_view: StorageReadView,
+    ) -> Self {
         Self {
-            clients: HashMap::new(),
-            restaurants: HashMap::new(),
-            deliverys: HashMap::new(),
-            orders: HashMap::new(),
+            clients: EntityTable::default(),
+            restaurants: EntityTable::default(),
+            deliverys: EntityTable::default(),
+            orders: EntityTable::default(),
+            read_view,
             accepted_deliveries: BiMap::new(),
+            accepted_epochs: HashMap::new(),
+            order_events: HashMap::new(),
+            removed_timelines: VecDeque::new(),
+            removed_orders: HashMap::new(),
+            tombstones: Tombstones::default(),
+            order_updates: HashMap::new(),
+            next_order_update_id: 1,
+            delivery_earnings: HashMap::new(),
+            elections: VecDeque::new(),
+            promo_redemptions: HashMap::new(),
+            user_secrets: HashMap::new(),
+            payment_outbox: HashMap::new(),
+            order_requests: HashMap::new(),
+            last_seen: HashMap::new(),
             storage_updates: HashMap::new(),
             next_log_id: 1,
+            replicated_index: None,
             min_persistent_log_index: 0,
             coordinator,
+            node_id,
+            log_file,
+            event_publisher: None,
+            restored_from_log: false,
+            compaction: CompactionStatus::default(),
             logger: Logger::new("Storage", Color::White),
         }
     }
+
+    /// Hands the events of the orders this coordinator changes first to `event_publisher`.
+    pub fn with_event_publisher(mut self, event_publisher: Addr<EventPublisher>) -> Self {
+        self.event_publisher = Some(event_publisher);
+        self
+    }
+
+    /// Keeps the clients, restaurants, deliveries and orders in `tables` instead of in
+    /// memory.
+    ///
+    /// # Arguments
+    /// * `tables` - The tables opened in the chosen backend.
+    pub fn with_tables(mut self, tables: EntityTables) -> Self {
+        self.clients = tables.clients;
+        self.restaurants = tables.restaurants;
+        self.deliverys = tables.deliverys;
+        self.orders = tables.orders;
+        self
+    }
     /// Adds a new log entry to the storage log and increments the log index.
     ///
+    /// The entry takes the next index, or the index the leader gave it if it is being
+    /// applied with [`Storage::apply_at`].
+    ///
     /// # Arguments
     /// * `log_message` - The [`StorageLogMessage`] to add.
     fn add_to_log(&mut self, log_message: StorageLogMessage) {
-        self.storage_updates.insert(self.next_log_id, log_message);
-        self.next_log_id += 1;
+        let log_id = self.replicated_index.take().unwrap_or(self.next_log_id);
+        let _span = info_span!(
+            "storage.append",
+            msg = log_message.kind(),
+            order_id = log_message.order_id(),
+            log_index = log_id
+        )
+        .entered();
+        if let Some(log_file) = self.log_file.as_mut() {
+            match log_file.append(log_id, &log_message) {
+                Ok(()) => self.compaction.persisted_since_snapshot += 1,
+                Err(e) => self
+                    .logger
+                    .error(format!("Error persisting log entry {}: {}", log_id, e)),
+            }
+        }
+        self.storage_updates.insert(log_id, log_message);
+        self.next_log_id = self.next_log_id.max(log_id + 1);
+    }
+
+    /// Applies `update`, replicated by the leader, logging it at `index` like the leader
+    /// did. An update whose index is already in the log was applied before and is skipped.
+    ///
+    /// # Arguments
+    /// * `index` - The index of the update in the log of the leader.
+    /// * `update` - The [`StorageLogMessage`] to apply.
+    fn apply_at(&mut self, index: u64, update: StorageLogMessage, ctx: &mut Context<Self>) {
+        if self.storage_updates.contains_key(&index) {
+            return;
+        }
+        self.replicated_index = Some(index);
+        self.handle(update, ctx);
+        // Si la operación se ignoró, el índice no debe quedar para la siguiente
+        self.replicated_index = None;
+    }
+
+    /// Returns the entry logged at `index`, if any.
+    fn logged_at(&self, index: u64) -> Option<LoggedWrite> {
+        self.storage_updates.get(&index).map(|update| LoggedWrite {
+            index,
+            update: update.clone(),
+        })
+    }
+
+    /// Returns a [`Snapshot`] of the whole storage state.
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            clients: self.clients.to_map(),
+            restaurants: self.restaurants.to_map(),
+            deliverys: self.deliverys.to_map(),
+            orders: self.orders.to_map(),
+            accepted_deliveries: self.accepted_deliveries.clone(),
+            accepted_epochs: self.accepted_epochs.clone(),
+            order_events: self.order_events.clone(),
+            removed_orders: self.removed_orders.clone(),
+            tombstones: self.tombstones.clone(),
+            order_updates: self
+                .order_updates
+                .iter()
+                .map(|(user_id, updates)| (user_id.clone(), updates.iter().cloned().collect()))
+                .collect(),
+            next_order_update_id: self.next_order_update_id,
+            delivery_earnings: self.delivery_earnings.clone(),
+            elections: self.elections.iter().cloned().collect(),
+            promo_redemptions: self.promo_redemptions.clone(),
+            user_secrets: self.user_secrets.clone(),
+            payment_outbox: self.payment_outbox.clone(),
+            order_requests: self.order_requests.clone(),
+            last_seen: self.last_seen.clone(),
+            next_log_id: self.next_log_id,
+            min_persistent_log_index: self.min_persistent_log_index,
+        }
+    }
+
+    /// Merges the entries of `snapshot` into the storage state and continues the log from
+    /// its indices.
+    fn apply_snapshot(&mut self, snapshot: Snapshot) {
+        let next_log_id = snapshot.next_log_id;
+        let min_persistent_log_index = snapshot.min_persistent_log_index;
+        self.merge_snapshot(snapshot);
+        self.next_log_id = next_log_id;
+        self.min_persistent_log_index = min_persistent_log_index;
+    }
+
+    /// Merges the entries of `snapshot` into the storage state, keeping the indices of the
+    /// log.
+    ///
+    /// Two coordinators may act as leader at the same time for a while, so an entry of the
+    /// snapshot only replaces the local one if it changed later, according to their
+    /// `time_stamp` (last writer wins). An entry removed locally is only brought back if the
+    /// snapshot changed it after the removal, while a local entry is kept even if the snapshot
+    /// removed it, since the removal arrives with the log. Accepted deliveries are kept from
+    /// the newest leader epoch, and the timelines, the order updates, the earnings and the
+    /// payment outbox are merged entry by entry.
+    fn merge_snapshot(&mut self, snapshot: Snapshot) {
+        let tombstones = &self.tombstones;
+        for (client_id, client) in snapshot.clients {
+            let local = self
+                .clients
+                .get(&client_id)
+                .map(|client| client.time_stamp)
+                .or_else(|| tombstones.clients.get(&client_id).copied());
+            if is_newer(client.time_stamp, local) {
+                self.clients.insert(client_id, client);
+            }
+        }
+        for (restaurant_id, restaurant) in snapshot.restaurants {
+            let local = self
+                .restaurants
+                .get(&restaurant_id)
+                .map(|restaurant| restaurant.time_stamp)
+                .or_else(|| tombstones.restaurants.get(&restaurant_id).copied());
+            if is_newer(restaurant.time_stamp, local) {
+                self.read_view.put_restaurant(restaurant.info());
+                self.read_view
+                    .set_restaurant_open(&restaurant_id, !restaurant.closed);
+                self.restaurants.insert(restaurant_id, restaurant);
+            }
+        }
+        for (delivery_id, delivery) in snapshot.deliverys {
+            let local = self
+                .deliverys
+                .get(&delivery_id)
+                .map(|delivery| delivery.time_stamp)
+                .or_else(|| tombstones.deliverys.get(&delivery_id).copied());
+            if is_newer(delivery.time_stamp, local) {
+                self.deliverys.insert(delivery_id, delivery);
+            }
+        }
+        for (order_id, order) in snapshot.orders {
+            let local = self
+                .orders
+                .get(&order_id)
+                .map(|order| order.time_stamp)
+                .or_else(|| tombstones.orders.get(&order_id).copied());
+            if is_newer(order.time_stamp, local) {
+                self.orders.insert(order_id, order);
+            }
+        }
+        self.refresh_order_copies();
+        for (order_id, delivery_id) in snapshot.accepted_deliveries {
+            let epoch = snapshot
+                .accepted_epochs
+                .get(&order_id)
+                .copied()
+                .unwrap_or_default();
+            // La aceptación de un líder obsoleto no pisa la de uno más nuevo
+            if self
+                .accepted_epochs
+                .get(&order_id)
+                .is_none_or(|local| epoch >= *local)
+            {
+                self.accept_delivery(order_id, delivery_id, epoch);
+            }
+        }
+        for (order_id, events) in snapshot.order_events {
+            // La línea de tiempo de un pedido borrado que ya se descartó no se vuelve a armar
+            if !self.orders.contains_key(&order_id)
+                && !self.order_events.contains_key(&order_id)
+                && self.tombstones.orders.contains_key(&order_id)
+            {
+                continue;
+            }
+            for event in events {
+                self.record_event(order_id, event);
+            }
+            if let Some(timeline) = self.order_events.get_mut(&order_id) {
+                timeline.sort_by_key(|event| event.timestamp);
+            }
+        }
+        // Las líneas de tiempo de pedidos que ya no están se descartan de la más vieja a la
+        // más nueva
+        let mut removed: Vec<(HlcTimestamp, u64)> = self
+            .order_events
+            .iter()
+            .filter(|(order_id, _)| !self.orders.contains_key(order_id))
+            .map(|(order_id, events)| {
+                let last = events
+                    .last()
+                    .map(|event| event.timestamp)
+                    .unwrap_or_default();
+                (last, *order_id)
+            })
+            .collect();
+        removed.sort_unstable();
+        // Los próximos cambios quedan después de todos los del snapshot
+        if let Some(latest) = self
+            .order_events
+            .values()
+            .filter_map(|events| events.last())
+            .map(|event| event.timestamp)
+            .max()
+        {
+            HlcTimestamp::observe(latest);
+        }
+        self.removed_timelines = removed.into_iter().map(|(_, order_id)| order_id).collect();
+        for (order_id, order) in snapshot.removed_orders {
+            let local = self
+                .removed_orders
+                .get(&order_id)
+                .map(|order| order.time_stamp);
+            if is_newer(order.time_stamp, local) {
+                self.removed_orders.insert(order_id, order);
+            }
+        }
+        let removed_timelines = &self.removed_timelines;
+        self.removed_orders
+            .retain(|order_id, _| removed_timelines.contains(order_id));
+        let tombstones = snapshot.tombstones;
+        merge_tombstones(&mut self.tombstones.clients, tombstones.clients);
+        merge_tombstones(&mut self.tombstones.restaurants, tombstones.restaurants);
+        merge_tombstones(&mut self.tombstones.deliverys, tombstones.deliverys);
+        merge_tombstones(&mut self.tombstones.orders, tombstones.orders);
+        for (user_id, updates) in snapshot.order_updates {
+            let outbox = self.order_updates.entry(user_id).or_default();
+            for update in updates {
+                if !outbox
+                    .iter()
+                    .any(|local| local.update_id == update.update_id)
+                {
+                    outbox.push_back(update);
+                }
+            }
+            outbox
+                .make_contiguous()
+                .sort_by_key(|update| update.update_id);
+            while outbox.len() > MAX_PENDING_ORDER_UPDATES {
+                outbox.pop_front();
+            }
+        }
+        self.next_order_update_id = self.next_order_update_id.max(snapshot.next_order_update_id);
+        for (delivery_id, earnings) in snapshot.delivery_earnings {
+            let local = self.delivery_earnings.entry(delivery_id).or_default();
+            for earning in earnings {
+                match local
+                    .iter_mut()
+                    .find(|local| local.order_id == earning.order_id)
+                {
+                    Some(local) if is_newer(earning.time_stamp, Some(local.time_stamp)) => {
+                        *local = earning;
+                    }
+                    Some(_) => {}
+                    None => local.push(earning),
+                }
+            }
+            local.sort_by_key(|earning| earning.time_stamp);
+        }
+        for record in snapshot.elections {
+            self.remember_election(record);
+        }
+        for (code, redemptions) in snapshot.promo_redemptions {
+            for redemption in redemptions {
+                self.remember_redemption(&code, redemption);
+            }
+        }
+        // Si dos líderes aceptaron el mismo ID, vale el secreto conocido primero
+        for (user_id, secret) in snapshot.user_secrets {
+            self.user_secrets.entry(user_id).or_insert(secret);
+        }
+        for (order_id, payment) in snapshot.payment_outbox {
+            let local = self
+                .payment_outbox
+                .get(&order_id)
+                .map(|payment| payment.time_stamp);
+            if is_newer(payment.time_stamp, local) {
+                self.payment_outbox.insert(order_id, payment);
+            }
+        }
+        self.drop_old_completed_payments();
+        for (client_id, requests) in snapshot.order_requests {
+            for request in requests {
+                self.remember_order_request(&client_id, request);
+            }
+        }
+        for (user_id, seen) in snapshot.last_seen {
+            self.see_user(user_id, seen);
+        }
+    }
+
+    /// Returns the references between the entries that do not hold, sorted by order.
+    pub fn violations(&self) -> Vec<StorageViolation> {
+        let mut violations = Vec::new();
+        let mut orders = self.orders.values();
+        orders.sort_by_key(|order| order.order_id);
+        for order in &orders {
+            let order_id = order.order_id;
+            if !self.clients.contains_key(&order.client_id) {
+                violations.push(StorageViolation::MissingClient {
+                    order_id,
+                    client_id: order.client_id.clone(),
+                });
+            }
+            match self.restaurants.get(&order.restaurant_id) {
+                Some(restaurant) => {
+                    let holds = |orders: &HashSet<OrderDTO>| {
+                        orders.iter().any(|other| other.order_id == order_id)
+                    };
+                    if holds(&restaurant.pending_orders) && holds(&restaurant.authorized_orders) {
+                        violations.push(StorageViolation::PendingAndAuthorized {
+                            order_id,
+                            restaurant_id: order.restaurant_id.clone(),
+                        });
+                    }
+                }
+                None => violations.push(StorageViolation::MissingRestaurant {
+                    order_id,
+                    restaurant_id: order.restaurant_id.clone(),
+                }),
+            }
+            if let Some(delivery_id) = &order.delivery_id
+                && !self.deliverys.contains_key(delivery_id)
+            {
+                violations.push(StorageViolation::MissingDelivery {
+                    order_id,
+                    delivery_id: delivery_id.clone(),
+                });
+            }
+        }
+        let mut accepted: Vec<(&u64, &String)> = self
+            .accepted_deliveries
+            .keys()
+            .filter_map(|order_id| Some((order_id, self.accepted_deliveries.get_by_key(order_id)?)))
+            .collect();
+        accepted.sort();
+        for (order_id, delivery_id) in accepted {
+            match self.orders.get(order_id) {
+                Some(order) if order.delivery_id.as_ref() != Some(delivery_id) => {
+                    violations.push(StorageViolation::AcceptedByAnotherDelivery {
+                        order_id: *order_id,
+                        accepted_by: delivery_id.clone(),
+                        assigned_to: order.delivery_id.clone(),
+                    });
+                }
+                Some(_) => {}
+                None => violations.push(StorageViolation::AcceptedMissingOrder {
+                    order_id: *order_id,
+                    delivery_id: delivery_id.clone(),
+                }),
+            }
+        }
+        violations
+    }
+
+    /// Returns the digest of the entries of each kind, from their IDs and `time_stamp`s.
+    fn digest(&self) -> StorageDigest {
+        let mut clients = EntityDigest::default();
+        self.clients
+            .for_each(|client_id, client| clients.add((client_id, client.time_stamp)));
+        let mut restaurants = EntityDigest::default();
+        self.restaurants.for_each(|restaurant_id, restaurant| {
+            restaurants.add((restaurant_id, restaurant.time_stamp))
+        });
+        let mut deliveries = EntityDigest::default();
+        self.deliverys
+            .for_each(|delivery_id, delivery| deliveries.add((delivery_id, delivery.time_stamp)));
+        for (delivery_id, earnings) in &self.delivery_earnings {
+            for earning in earnings {
+                deliveries.add((delivery_id, earning.order_id, earning.time_stamp));
+            }
+        }
+        let mut orders = EntityDigest::default();
+        self.orders
+            .for_each(|order_id, order| orders.add((order_id, order.time_stamp)));
+        for order_id in self.accepted_deliveries.keys() {
+            orders.add((
+                order_id,
+                self.accepted_deliveries.get_by_key(order_id),
+                self.accepted_epochs.get(order_id),
+            ));
+        }
+        for (order_id, payment) in &self.payment_outbox {
+            orders.add((order_id, payment.completed, payment.time_stamp));
+        }
+        StorageDigest {
+            next_log_id: self.next_log_id,
+            entities: HashMap::from([
+                (StorageEntity::Clients, clients),
+                (StorageEntity::Restaurants, restaurants),
+                (StorageEntity::Deliveries, deliveries),
+                (StorageEntity::Orders, orders),
+            ]),
+        }
+    }
+
+    /// Adds `record` to the election history, sorted by epoch, keeping the last
+    /// [`MAX_STORED_ELECTIONS`] changes.
+    ///
+    /// ## Returns
+    /// `false` if a change of the same epoch was already stored.
+    fn remember_election(&mut self, record: ElectionRecord) -> bool {
+        if self
+            .elections
+            .iter()
+            .any(|stored| stored.epoch == record.epoch)
+        {
+            return false;
+        }
+        let position = self
+            .elections
+            .partition_point(|stored| stored.epoch < record.epoch);
+        self.elections.insert(position, record);
+        if self.elections.len() > MAX_STORED_ELECTIONS {
+            self.elections.pop_front();
+        }
+        true
+    }
+
+    /// Adds `redemption` to the uses of the promo code `code`, oldest first.
+    ///
+    /// ## Returns
+    /// `false` if the client had already used the code.
+    fn remember_redemption(&mut self, code: &str, redemption: PromoRedemption) -> bool {
+        let redemptions = self.promo_redemptions.entry(code.to_string()).or_default();
+        if redemptions
+            .iter()
+            .any(|stored| stored.client_id == redemption.client_id)
+        {
+            return false;
+        }
+        let position =
+            redemptions.partition_point(|stored| stored.time_stamp <= redemption.time_stamp);
+        redemptions.insert(position, redemption);
+        true
+    }
+
+    /// Drops the oldest completed bills of the payment outbox, keeping the last
+    /// [`MAX_COMPLETED_PAYMENTS`] to recognize repeated confirmations of the gateway.
+    fn drop_old_completed_payments(&mut self) {
+        let mut completed: Vec<(HlcTimestamp, u64)> = self
+            .payment_outbox
+            .iter()
+            .filter(|(_, payment)| payment.completed)
+            .map(|(order_id, payment)| (payment.time_stamp, *order_id))
+            .collect();
+        if completed.len() <= MAX_COMPLETED_PAYMENTS {
+            return;
+        }
+        completed.sort_unstable();
+        let excess = completed.len() - MAX_COMPLETED_PAYMENTS;
+        for (_, order_id) in &completed[..excess] {
+            self.payment_outbox.remove(order_id);
+        }
+    }
+
+    /// Records the order ID assigned to a request of `client_id`, unless the request already
+    /// has one, and returns the ID the request keeps. Only the most recent requests of each
+    /// client are kept.
+    fn remember_order_request(&mut self, client_id: &str, request: OrderRequest) -> u64 {
+        let requests = self
+            .order_requests
+            .entry(client_id.to_string())
+            .or_default();
+        if let Some(kept) = requests
+            .iter()
+            .find(|kept| kept.request_id == request.request_id)
+        {
+            return kept.order_id;
+        }
+        let position = requests.partition_point(|kept| kept.order_id <= request.order_id);
+        requests.insert(position, request);
+        if requests.len() > MAX_ORDER_REQUESTS_PER_CLIENT {
+            let excess = requests.len() - MAX_ORDER_REQUESTS_PER_CLIENT;
+            requests.drain(..excess);
+        }
+        request.order_id
+    }
+
+    /// Replaces the copies of the orders kept by clients, restaurants and deliveries with
+    /// the stored orders, after some orders were replaced by those of a snapshot.
+    fn refresh_order_copies(&mut self) {
+        let orders = &self.orders;
+        let stored = |order: &OrderDTO| orders.get(&order.order_id);
+        self.clients.update_all(|client| {
+            if let Some(order) = client.client_order.as_ref().and_then(stored) {
+                client.client_order = Some(order);
+            }
+        });
+        self.deliverys.update_all(|delivery| {
+            if let Some(order) = delivery.current_order.as_ref().and_then(stored) {
+                delivery.current_order = Some(order);
+            }
+        });
+        self.publish_deliveries();
+        self.restaurants.update_all(|restaurant| {
+            for set in [
+                &mut restaurant.authorized_orders,
+                &mut restaurant.pending_orders,
+            ] {
+                *set = set
+                    .iter()
+                    .map(|order| stored(order).unwrap_or_else(|| order.clone()))
+                    .collect();
+            }
+        });
+    }
+
+    /// Copies every delivery to the [`StorageReadView`], after changing many of them.
+    fn publish_deliveries(&self) {
+        self.read_view.replace_deliveries(self.deliverys.values());
+    }
+
+    /// Records that `delivery_id` accepted the order `order_id` in the leader epoch `epoch`.
+    ///
+    /// A delivery accepts a single order at a time, so it leaves the order it had accepted
+    /// before, if any.
+    fn accept_delivery(&mut self, order_id: u64, delivery_id: String, epoch: u64) {
+        if let Some(previous) = self.accepted_deliveries.get_by_value(&delivery_id)
+            && *previous != order_id
+        {
+            self.accepted_epochs.remove(previous);
+        }
+        self.accepted_deliveries.insert(order_id, delivery_id);
+        self.accepted_epochs.insert(order_id, epoch);
+    }
+
+    /// Returns `event`, the event of a change replicated from the coordinator that applied
+    /// it first, or a new event of the change if this coordinator is the first.
+    ///
+    /// The clock of the process moves past a replicated event, so the changes this
+    /// coordinator applies first afterwards are stamped after it.
+    fn stamp_event(
+        &self,
+        event: Option<OrderEvent>,
+        kind: OrderEventKind,
+        order: &OrderDTO,
+    ) -> OrderEvent {
+        match event {
+            Some(event) => {
+                HlcTimestamp::observe(event.timestamp);
+                event
+            }
+            None => OrderEvent::new(kind, order, &self.node_id),
+        }
+    }
+
+    /// Returns `time_stamp`, the stamp of a change replicated from the coordinator that
+    /// applied it first, or a new stamp if this coordinator is the first.
+    fn stamp_change(&self, time_stamp: Option<HlcTimestamp>) -> HlcTimestamp {
+        match time_stamp {
+            Some(time_stamp) => {
+                HlcTimestamp::observe(time_stamp);
+                time_stamp
+            }
+            None => HlcTimestamp::now(),
+        }
+    }
+
+    /// Returns the tenant of the client, restaurant or delivery `user_id`, if it is stored.
+    fn tenant_of(&self, user_id: &str) -> Option<TenantId> {
+        let user_id = user_id.to_string();
+        self.clients
+            .get(&user_id)
+            .map(|client| client.tenant_id)
+            .or_else(|| {
+                self.restaurants
+                    .get(&user_id)
+                    .map(|restaurant| restaurant.tenant_id)
+            })
+            .or_else(|| {
+                self.deliverys
+                    .get(&user_id)
+                    .map(|delivery| delivery.tenant_id)
+            })
+    }
+
+    /// Records that `user_id` was seen at `time_stamp`, unless it was seen later.
+    fn see_user(&mut self, user_id: String, time_stamp: HlcTimestamp) {
+        let seen = self.last_seen.entry(user_id).or_insert(time_stamp);
+        *seen = (*seen).max(time_stamp);
+    }
+
+    /// Returns whether `user_id` is the client or the delivery of an order not finished yet.
+    fn has_active_orders(&self, user_id: &str) -> bool {
+        let mut active = false;
+        self.orders.for_each(|order_id, order| {
+            let accepted = self.accepted_deliveries.get_by_key(order_id);
+            active |= !order.status.is_final()
+                && (order.client_id == user_id
+                    || order.delivery_id.as_deref() == Some(user_id)
+                    || accepted.is_some_and(|delivery_id| delivery_id == user_id));
+        });
+        active
+            || self
+                .clients
+                .get(&user_id.to_string())
+                .is_some_and(|client| {
+                    client
+                        .client_order
+                        .is_some_and(|order| !order.status.is_final())
+                })
+            || self
+                .deliverys
+                .get(&user_id.to_string())
+                .is_some_and(|delivery| delivery.current_order.is_some())
+    }
+
+    /// Returns the clients and deliveries last seen before `seen_before` that have no active
+    /// orders, sorted by ID. A user never seen counts as seen when it last changed.
+    pub fn stale_users(&self, seen_before: HlcTimestamp) -> Vec<String> {
+        let mut candidates = Vec::new();
+        self.clients.for_each(|client_id, client| {
+            candidates.push((client_id.clone(), client.time_stamp));
+        });
+        self.deliverys.for_each(|delivery_id, delivery| {
+            candidates.push((delivery_id.clone(), delivery.time_stamp));
+        });
+        let mut stale: Vec<String> = candidates
+            .into_iter()
+            .filter(|(user_id, changed)| {
+                let seen = self.last_seen.get(user_id).copied().unwrap_or(*changed);
+                seen < seen_before && !self.has_active_orders(user_id)
+            })
+            .map(|(user_id, _)| user_id)
+            .collect();
+        stale.sort();
+        stale
+    }
+
+    /// Appends `event` to the timeline of `order_id`.
+    ///
+    /// A change can be applied twice (by a quorum write and by the replicated log), so an
+    /// event already in the timeline is not added again. Returns whether it was added.
+    fn record_event(&mut self, order_id: u64, event: OrderEvent) -> bool {
+        let timeline = self.order_events.entry(order_id).or_default();
+        if timeline.contains(&event) {
+            return false;
+        }
+        timeline.push(event);
+        true
+    }
+
+    /// Appends `event` to the timeline of `order` and, if this coordinator applied the change
+    /// first, hands it to the event publisher.
+    fn record_order_event(&mut self, order: &OrderDTO, event: OrderEvent) {
+        let applied_here = event.node == self.node_id;
+        if self.record_event(order.order_id, event.clone())
+            && applied_here
+            && let Some(event_publisher) = &self.event_publisher
+        {
+            event_publisher.do_send(PublishOrderEvent {
+                order: order.clone(),
+                event,
+            });
+        }
+    }
+
+    /// Keeps the timeline and the last state of a removed order, dropping the oldest ones
+    /// beyond [`MAX_REMOVED_ORDER_TIMELINES`].
+    fn keep_removed_order(&mut self, order: OrderDTO) {
+        let order_id = order.order_id;
+        self.removed_orders.insert(order_id, order);
+        if self.removed_timelines.contains(&order_id) {
+            return;
+        }
+        self.removed_timelines.push_back(order_id);
+        while self.removed_timelines.len() > MAX_REMOVED_ORDER_TIMELINES {
+            if let Some(oldest) = self.removed_timelines.pop_front() {
+                self.order_events.remove(&oldest);
+                self.removed_orders.remove(&oldest);
+            }
+        }
+    }
+
+    /// Replaces the persisted log with a snapshot of the current state.
+    fn compact_log(&mut self) {
+        let snapshot = self.snapshot();
+        if let Some(log_file) = self.log_file.as_mut() {
+            match log_file.truncate(&snapshot) {
+                Ok(()) => self.compaction.persisted_since_snapshot = 0,
+                Err(e) => self
+                    .logger
+                    .error(format!("Error compacting the persisted log: {}", e)),
+            }
+        }
     }
 
     fn update_associated_order(&mut self, order: &OrderDTO) {
         // chequemos si la orden existe en el storage
-        if let Some(order) = self.orders.get_mut(&order.order_id) {
-            // Actualizamos la orden en el storage
-            *order = order.clone();
+        if let Some(order) = self.orders.get(&order.order_id) {
             let order_clone = order.clone();
             // nos fijamos si el cliente existe
             // y actualizamos la orden asociada al cliente
-            if let Some(client) = self.clients.get_mut(&order.client_id) {
+            let client = self.clients.update(&order.client_id, |client| {
                 client.client_order = Some(order.clone());
-            } else {
+                client.time_stamp = client.time_stamp.max(order.time_stamp);
+            });
+            if client.is_none() {
                 self.logger
                     .error(format!("Client not found for order: {}", order.client_id));
             }
             // nos fijamos si el delivery existe
             // y actualizamos la orden asociada al delivery
             if let Some(delivery_id) = &order.delivery_id {
-                if let Some(delivery) = self.deliverys.get_mut(delivery_id) {
+                let delivery = self.deliverys.update(delivery_id, |delivery| {
                     delivery.current_order = Some(order.clone());
+                    delivery.time_stamp = delivery.time_stamp.max(order.time_stamp);
+                    delivery.clone()
+                });
+                if let Some(delivery) = delivery {
+                    self.read_view.put_delivery(delivery);
                 } else {
                     self.logger
                         .error(format!("Delivery not found for order: {}", order.order_id));
                 }
             }
             // nos fijamos si el restaurant existe
-            if let Some(restaurant) = self.restaurants.get_mut(&order.restaurant_id) {
+            let restaurant = self.restaurants.update(&order.restaurant_id, |restaurant| {
+                restaurant.time_stamp = restaurant.time_stamp.max(order.time_stamp);
                 // nos fijamos si la orden está en authorized_orders o en pending_orders
-                if restaurant.authorized_orders.remove(order) {
+                if restaurant.authorized_orders.remove(&order) {
                     restaurant.authorized_orders.insert(order_clone);
-                } else if restaurant.pending_orders.remove(order) {
+                    true
+                } else if restaurant.pending_orders.remove(&order) {
                     restaurant.pending_orders.insert(order_clone);
+                    true
                 } else {
-                    self.logger.error(format!(
-                        "Order not found in restaurant orders: {}",
-                        order.order_id
-                    ));
+                    false
                 }
-            } else {
-                self.logger.error(format!(
+            });
+            match restaurant {
+                Some(true) => {}
+                Some(false) => self.logger.error(format!(
+                    "Order not found in restaurant orders: {}",
+                    order.order_id
+                )),
+                None => self.logger.error(format!(
                     "Restaurant not found for order: {}",
                     order.restaurant_id
-                ));
+                )),
             }
         } else {
             self.logger
@@ -135,8 +972,93 @@ impl Storage {
     }
 }
 
+/// Records that the entity `id` was removed at `removed_at`, dropping the oldest tombstones
+/// beyond [`MAX_TOMBSTONES`].
+fn bury<K: Hash + Eq + Ord + Clone>(
+    tombstones: &mut HashMap<K, HlcTimestamp>,
+    id: K,
+    removed_at: HlcTimestamp,
+) {
+    merge_tombstones(tombstones, HashMap::from([(id, removed_at)]));
+}
+
+/// Adds the `incoming` tombstones to the local ones, keeping the latest removal of each
+/// entity and dropping the oldest tombstones beyond [`MAX_TOMBSTONES`].
+fn merge_tombstones<K: Hash + Eq + Ord + Clone>(
+    tombstones: &mut HashMap<K, HlcTimestamp>,
+    incoming: HashMap<K, HlcTimestamp>,
+) {
+    for (id, removed_at) in incoming {
+        let local = tombstones.entry(id).or_insert(removed_at);
+        *local = (*local).max(removed_at);
+    }
+    if tombstones.len() > MAX_TOMBSTONES {
+        let mut oldest: Vec<(HlcTimestamp, K)> = tombstones
+            .iter()
+            .map(|(id, removed_at)| (*removed_at, id.clone()))
+            .collect();
+        oldest.sort_unstable();
+        let excess = tombstones.len() - MAX_TOMBSTONES;
+        for (_, id) in oldest.into_iter().take(excess) {
+            tombstones.remove(&id);
+        }
+    }
+}
+
+/// Returns whether an entry of a snapshot stamped at `incoming` replaces the local entry,
+/// stamped at `local` (`None` if there is no local entry).
+fn is_newer(incoming: HlcTimestamp, local: Option<HlcTimestamp>) -> bool {
+    local.is_none_or(|local| incoming > local)
+}
+
 impl Actor for Storage {
     type Context = Context<Self>;
+
+    /// Rebuilds the state from the persisted log, if any, and compacts the log into a
+    /// snapshot of the result. Each entry is logged again at its persisted index.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        // Mientras se reproduce el log, las operaciones no se vuelven a persistir ni publicar
+        let Some(log_file) = self.log_file.take() else {
+            return;
+        };
+        let event_publisher = self.event_publisher.take();
+        match log_file.read_records() {
+            Ok(records) if !records.is_empty() => {
+                self.logger.info(format!(
+                    "Replaying {} records from {}",
+                    records.len(),
+                    log_file.path().display()
+                ));
+                for record in records {
+                    match record {
+                        LogRecord::Snapshot(snapshot) => self.apply_snapshot(snapshot),
+                        LogRecord::Entry { index, update } => self.apply_at(index, update, ctx),
+                    }
+                }
+                self.restored_from_log = true;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                self.logger.error(format!(
+                    "Error reading the persisted log {}: {}",
+                    log_file.path().display(),
+                    e
+                ));
+            }
+        }
+        self.log_file = Some(log_file);
+        self.event_publisher = event_publisher;
+        self.compact_log();
+    }
+}
+
+/// Handles queries about whether the state was rebuilt from the persisted log.
+impl Handler<IsRestoredFromLog> for Storage {
+    type Result = bool;
+
+    fn handle(&mut self, _msg: IsRestoredFromLog, _ctx: &mut Self::Context) -> Self::Result {
+        self.restored_from_log
+    }
 }
 
 /// Handles requests for the minimum log index currently stored.
@@ -163,6 +1085,53 @@ impl Handler<GetLogsFromIndex> for Storage {
     }
 }
 
+/// Handles requests to compact the log up to the index every follower confirmed.
+impl Handler<CompactLog> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, msg: CompactLog, _ctx: &mut Self::Context) -> Self::Result {
+        let up_to = msg.up_to.min(self.next_log_id);
+        let before = self.storage_updates.len();
+        self.storage_updates.retain(|id, _| *id >= up_to);
+        let dropped = (before - self.storage_updates.len()) as u64;
+        self.min_persistent_log_index = self.min_persistent_log_index.max(up_to);
+
+        // El snapshot reemplaza al archivo sólo si creció lo suficiente desde el anterior
+        let write_snapshot = self.compaction.persisted_since_snapshot >= LOG_COMPACTION_MIN_ENTRIES;
+        if dropped == 0 && !write_snapshot {
+            return;
+        }
+        if write_snapshot {
+            self.compact_log();
+        }
+        self.compaction.compactions += 1;
+        self.compaction.dropped_entries += dropped;
+        self.compaction.confirmed_up_to = up_to;
+        self.compaction.last_compaction_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .ok();
+        self.logger.info(format!(
+            "Log compacted up to {}: {} entries dropped, snapshot written: {}",
+            up_to, dropped, write_snapshot
+        ));
+    }
+}
+
+/// Handles requests for the state of the log and its compactions.
+impl Handler<GetCompactionStatus> for Storage {
+    type Result = MessageResult<GetCompactionStatus>;
+
+    fn handle(&mut self, _msg: GetCompactionStatus, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(CompactionStatus {
+            min_log_index: self.min_persistent_log_index,
+            next_log_id: self.next_log_id,
+            entries: self.storage_updates.len(),
+            ..self.compaction.clone()
+        })
+    }
+}
+
 /// Applies a batch of storage updates, updating the log and state accordingly.
 impl Handler<ApplyStorageUpdates> for Storage {
     type Result = ();
@@ -192,90 +1161,159 @@ impl Handler<ApplyStorageUpdates> for Storage {
             }
 
             // 2. Aplica y guarda en su registro las operaciones que están en el mensaje recibido pero NO en el registro.
-            for (id, update) in incoming_updates {
-                match self.storage_updates.entry(id) {
-                    Entry::Occupied(_) => {}
-                    Entry::Vacant(entry) => {
-                        // Aplica la operación
-                        ctx.address().do_send(update.clone());
-
-                        // Guarda la operación en el registro
-                        entry.insert(update);
-                    }
-                }
+            let mut missing: Vec<(u64, StorageLogMessage)> = incoming_updates
+                .into_iter()
+                .filter(|(id, _)| !self.storage_updates.contains_key(id))
+                .collect();
+            missing.sort_unstable_by_key(|(id, _)| *id);
+            for (id, update) in missing {
+                // Se registra con el índice del líder, para que el siguiente del anillo reciba
+                // la misma operación con ese índice
+                self.apply_at(id, update, ctx);
             }
         }
     }
 }
 
+/// Handles writes of the order service, returning where they were logged so that they can
+/// be replicated.
+impl Handler<AppendWrite> for Storage {
+    type Result = Option<LoggedWrite>;
+
+    fn handle(&mut self, msg: AppendWrite, ctx: &mut Self::Context) -> Self::Result {
+        let index = self.next_log_id;
+        self.handle(msg.update, ctx);
+        self.logged_at(index)
+    }
+}
+
+/// Handles writes replicated by the leader, logging them at the index they have there.
+impl Handler<ApplyReplicatedWrite> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, msg: ApplyReplicatedWrite, ctx: &mut Self::Context) -> Self::Result {
+        self.apply_at(msg.index, msg.update, ctx);
+    }
+}
+
 /// Applies a single storage log message by dispatching it to the appropriate handler.
 impl Handler<StorageLogMessage> for Storage {
     type Result = ();
 
     fn handle(&mut self, msg: StorageLogMessage, ctx: &mut Self::Context) -> Self::Result {
+        let _span =
+            info_span!("storage.apply", msg = msg.kind(), order_id = msg.order_id()).entered();
         match msg {
             StorageLogMessage::AddClient(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::AddRestaurant(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::AddDelivery(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::AddOrder(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::InsertAcceptedDelivery(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::AddAuthorizedOrderToRestaurant(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::AddPendingOrderToRestaurant(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::RemoveAcceptedDeliveries(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::RemoveClient(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::RemoveRestaurant(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::RemoveDelivery(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::RemoveAuthorizedOrderToRestaurant(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::RemovePendingOrderToRestaurant(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::RemoveOrder(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::SetDeliveryPosition(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::SetCurrentClientToDelivery(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::SetCurrentOrderToDelivery(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::SetDeliveryStatus(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::SetDeliveryToOrder(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::SetOrderStatus(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
             }
             StorageLogMessage::SetOrderExpectedTime(msg) => {
-                ctx.address().do_send(msg);
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::SetOrderPreparationTime(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::QueueOrderUpdate(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::AckOrderUpdates(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::AddDeliveryEarning(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::SetEarningPaidOut(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::AddCancellationFee(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::AddPromoRedemption(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::SetRestaurantOpen(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::SetRestaurantTags(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::SetUserOffline(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::TouchUsers(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::ClaimUserId(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::ClaimOrderRequest(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::RecordElection(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::AddOutboxPayment(msg) => {
+                self.handle(msg, ctx);
+            }
+            StorageLogMessage::CompleteOutboxPayment(msg) => {
+                self.handle(msg, ctx);
             }
         }
     }
@@ -287,45 +1325,62 @@ impl Handler<GetAllStorage> for Storage {
 
     fn handle(&mut self, _msg: GetAllStorage, _ctx: &mut Self::Context) -> Self::Result {
         // Enviar toda la información del storage al coordinator.
-        let snapshot = Snapshot {
-            clients: self.clients.clone(),
-            restaurants: self.restaurants.clone(),
-            deliverys: self.deliverys.clone(),
-            orders: self.orders.clone(),
-            accepted_deliveries: self.accepted_deliveries.clone(),
-            next_log_id: self.next_log_id,
-            min_persistent_log_index: self.min_persistent_log_index,
-        };
+        let snapshot = self.snapshot();
         self.logger.info("Snapshot sent to coordinator manager.");
         MessageResult(snapshot)
     }
 }
 
-/// Updates the storage state from a received snapshot.
-impl Handler<StorageSnapshot> for Storage {
+/// Handles requests to verify the references between the entries of the storage.
+impl Handler<CheckIntegrity> for Storage {
+    type Result = MessageResult<CheckIntegrity>;
+
+    fn handle(&mut self, _msg: CheckIntegrity, _ctx: &mut Self::Context) -> Self::Result {
+        let violations = self.violations();
+        if !violations.is_empty() {
+            self.logger.warn(format!(
+                "Storage integrity check found {} violations",
+                violations.len()
+            ));
+        }
+        MessageResult(StorageReport {
+            coordinator_id: self.node_id.clone(),
+            next_log_id: self.next_log_id,
+            violations,
+        })
+    }
+}
+
+/// Handles requests for the digest of the storage, compared with the one of another follower.
+impl Handler<GetStorageDigest> for Storage {
+    type Result = MessageResult<GetStorageDigest>;
+
+    fn handle(&mut self, _msg: GetStorageDigest, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.digest())
+    }
+}
+
+/// Merges the entries of another follower into the storage, keeping the indices of the log.
+impl Handler<MergeSnapshot> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: StorageSnapshot, _ctx: &mut Self::Context) -> Self::Result {
-        // Por cada elemento que viene en el snapshot, lo piso en el storage.
-        let snapshot = msg.snapshot.clone();
+    fn handle(&mut self, msg: MergeSnapshot, _ctx: &mut Self::Context) -> Self::Result {
+        self.merge_snapshot(*msg.snapshot);
+        // Las entradas combinadas no están en el log: se persiste el estado completo
+        self.compact_log();
+        self.logger
+            .info("Storage merged with the entries of another coordinator.");
+    }
+}
 
-        for (client_id, client) in snapshot.clients {
-            self.clients.insert(client_id, client);
-        }
-        for (restaurant_id, restaurant) in snapshot.restaurants {
-            self.restaurants.insert(restaurant_id, restaurant);
-        }
-        for (delivery_id, delivery) in snapshot.deliverys {
-            self.deliverys.insert(delivery_id, delivery);
-        }
-        for (order_id, order) in snapshot.orders {
-            self.orders.insert(order_id, order);
-        }
-        for (order_id, delivery_id) in snapshot.accepted_deliveries {
-            self.accepted_deliveries.insert(order_id, delivery_id);
-        }
-        self.next_log_id = snapshot.next_log_id;
-        self.min_persistent_log_index = snapshot.min_persistent_log_index;
+/// Updates the storage state from a received snapshot.
+impl Handler<StorageSnapshot> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, msg: StorageSnapshot, _ctx: &mut Self::Context) -> Self::Result {
+        self.apply_snapshot(*msg.snapshot);
+        // El log persistido arranca de nuevo desde el snapshot
+        self.compact_log();
         self.logger
             .info("Storage snapshot updated from coordinator.");
     }
@@ -341,6 +1396,8 @@ impl Handler<AddClient> for Storage {
         self.logger
             .info(format!("Client added: {}", msg.client.client_id));
         self.add_to_log(StorageLogMessage::AddClient(msg.clone()));
+        self.see_user(msg.client.client_id.clone(), msg.client.time_stamp);
+        self.tombstones.clients.remove(&msg.client.client_id);
         self.clients
             .insert(msg.client.client_id.clone(), msg.client.clone());
     }
@@ -356,6 +1413,12 @@ impl Handler<AddRestaurant> for Storage {
             msg.restaurant.restaurant_id
         ));
         self.add_to_log(StorageLogMessage::AddRestaurant(msg.clone()));
+        self.read_view.put_restaurant(msg.restaurant.info());
+        self.read_view
+            .set_restaurant_open(&msg.restaurant.restaurant_id, !msg.restaurant.closed);
+        self.tombstones
+            .restaurants
+            .remove(&msg.restaurant.restaurant_id);
         self.restaurants
             .insert(msg.restaurant.restaurant_id.clone(), msg.restaurant.clone());
     }
@@ -369,6 +1432,9 @@ impl Handler<AddDelivery> for Storage {
         self.logger
             .info(format!("Delivery added: {}", msg.delivery.delivery_id));
         self.add_to_log(StorageLogMessage::AddDelivery(msg.clone()));
+        self.see_user(msg.delivery.delivery_id.clone(), msg.delivery.time_stamp);
+        self.read_view.put_delivery(msg.delivery.clone());
+        self.tombstones.deliverys.remove(&msg.delivery.delivery_id);
         self.deliverys
             .insert(msg.delivery.delivery_id.clone(), msg.delivery.clone());
     }
@@ -378,14 +1444,21 @@ impl Handler<AddDelivery> for Storage {
 impl Handler<AddOrder> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: AddOrder, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: AddOrder, _ctx: &mut Self::Context) -> Self::Result {
         self.logger
             .info(format!("Order added: {}", msg.order.order_id));
+        let event = self.stamp_event(msg.event.take(), OrderEventKind::Created, &msg.order);
+        msg.order.time_stamp = event.timestamp;
+        msg.event = Some(event.clone());
         self.add_to_log(StorageLogMessage::AddOrder(msg.clone()));
+        self.tombstones.orders.remove(&msg.order.order_id);
         self.orders.insert(msg.order.order_id, msg.order.clone());
-        if let Some(client) = self.clients.get_mut(&msg.order.client_id) {
+        self.record_order_event(&msg.order, event);
+        let client = self.clients.update(&msg.order.client_id, |client| {
             client.client_order = Some(msg.order.clone());
-        } else {
+            client.time_stamp = client.time_stamp.max(msg.order.time_stamp);
+        });
+        if client.is_none() {
             self.logger.error(format!(
                 "Client not found for order: {}",
                 msg.order.client_id
@@ -396,10 +1469,10 @@ impl Handler<AddOrder> for Storage {
 
 /// Handles adding an accepted delivery for an order.
 impl Handler<AddOrderAccepted> for Storage {
-    type Result = ();
+    type Result = Option<LoggedWrite>;
 
     fn handle(&mut self, msg: AddOrderAccepted, ctx: &mut Self::Context) -> Self::Result {
-        if let Some(order) = self.orders.get_mut(&msg.order.order_id) {
+        if let Some(order) = self.orders.get(&msg.order.order_id) {
             if order.status != OrderStatus::ReadyForDelivery {
                 msg.addr.do_send(DeliveryNoNeeded {
                     order: msg.order.clone(),
@@ -447,19 +1520,25 @@ impl Handler<AddOrderAccepted> for Storage {
                 ));
 
                 // Agrego la orden y el delivery a accepted_deliveries.
+                let index = self.next_log_id;
                 self.handle(
                     InsertAcceptedDelivery {
                         order_id: msg.order.order_id,
                         delivery_id: msg.delivery.delivery_id.clone(),
+                        epoch: msg.epoch,
                     },
                     ctx,
                 );
 
-                // Reenviar el mensaje al address contenida en el mensaje
-                msg.addr.do_send(DeliveryAvailable {
-                    order: msg.order.clone(),
-                    delivery_info: msg.delivery.clone(),
-                });
+                // El OrderService replica la aceptación antes de confirmarla
+                let accepted = self.logged_at(index);
+                if accepted.is_none() {
+                    msg.addr.do_send(DeliveryNoNeeded {
+                        order: msg.order,
+                        delivery_info: msg.delivery,
+                    });
+                }
+                return accepted;
             }
         } else {
             self.logger.error(format!(
@@ -472,6 +1551,7 @@ impl Handler<AddOrderAccepted> for Storage {
                 delivery_info: msg.delivery,
             });
         }
+        None
     }
 }
 
@@ -480,9 +1560,40 @@ impl Handler<InsertAcceptedDelivery> for Storage {
     type Result = ();
 
     fn handle(&mut self, msg: InsertAcceptedDelivery, _ctx: &mut Self::Context) -> Self::Result {
+        // Un líder obsoleto no puede pisar la decisión de un líder más nuevo
+        if let Some(epoch) = self.accepted_epochs.get(&msg.order_id)
+            && *epoch > msg.epoch
+            && self.accepted_deliveries.get_by_key(&msg.order_id) != Some(&msg.delivery_id)
+        {
+            self.logger.warn(format!(
+                "Ignoring acceptance of order {} by {} from stale epoch {} (accepted in epoch {})",
+                msg.order_id, msg.delivery_id, msg.epoch, epoch
+            ));
+            return;
+        }
+        // Sólo se acepta una orden que existe y que no tiene otro delivery asignado
+        match self.orders.get(&msg.order_id) {
+            None => {
+                self.logger
+                    .warn(format!("Order not found: {}", msg.order_id));
+                return;
+            }
+            Some(order)
+                if order
+                    .delivery_id
+                    .as_ref()
+                    .is_some_and(|delivery_id| *delivery_id != msg.delivery_id) =>
+            {
+                self.logger.warn(format!(
+                    "Ignoring acceptance of order {} by {}: it is assigned to another delivery",
+                    msg.order_id, msg.delivery_id
+                ));
+                return;
+            }
+            Some(_) => {}
+        }
         self.add_to_log(StorageLogMessage::InsertAcceptedDelivery(msg.clone()));
-        self.accepted_deliveries
-            .insert(msg.order_id, msg.delivery_id.clone());
+        self.accept_delivery(msg.order_id, msg.delivery_id, msg.epoch);
     }
 }
 
@@ -492,19 +1603,24 @@ impl Handler<AddAuthorizedOrderToRestaurant> for Storage {
 
     fn handle(
         &mut self,
-        msg: AddAuthorizedOrderToRestaurant,
+        mut msg: AddAuthorizedOrderToRestaurant,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::AddAuthorizedOrderToRestaurant(
             msg.clone(),
         ));
-        if let Some(restaurant) = self.restaurants.get_mut(&msg.restaurant_id) {
+        if let Some(mut restaurant) = self.restaurants.get(&msg.restaurant_id) {
+            restaurant.time_stamp = restaurant.time_stamp.max(time_stamp);
             if let Some(order) = self.orders.get(&msg.order.order_id) {
                 restaurant.authorized_orders.insert(order.clone());
             } else {
                 self.logger
                     .warn(format!("Order not found: {}", msg.order.order_id));
             }
+            self.restaurants
+                .insert(msg.restaurant_id.clone(), restaurant);
         } else {
             self.logger.error(format!(
                 "Restaurant not found for order: {}",
@@ -520,19 +1636,24 @@ impl Handler<AddPendingOrderToRestaurant> for Storage {
 
     fn handle(
         &mut self,
-        msg: AddPendingOrderToRestaurant,
+        mut msg: AddPendingOrderToRestaurant,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::AddPendingOrderToRestaurant(msg.clone()));
-        if let Some(restaurant) = self.restaurants.get_mut(&msg.restaurant_id) {
+        if let Some(mut restaurant) = self.restaurants.get(&msg.restaurant_id) {
+            restaurant.time_stamp = restaurant.time_stamp.max(time_stamp);
             if let Some(order) = self.orders.get(&msg.order.order_id) {
                 // TODO: Ver si hay que eliminar la orden de authorized_orders acá
-                restaurant.authorized_orders.remove(order);
+                restaurant.authorized_orders.remove(&order);
                 restaurant.pending_orders.insert(order.clone());
             } else {
                 self.logger
                     .warn(format!("Order not found: {}", msg.order.order_id));
             }
+            self.restaurants
+                .insert(msg.restaurant_id.clone(), restaurant);
         } else {
             self.logger.error(format!(
                 "Restaurant not found for order: {}",
@@ -544,50 +1665,62 @@ impl Handler<AddPendingOrderToRestaurant> for Storage {
 
 /// Handles the completion of a delivery assignment, removing accepted deliveries and notifying the address.
 impl Handler<FinishDeliveryAssignment> for Storage {
-    type Result = ();
+    type Result = bool;
 
     fn handle(&mut self, msg: FinishDeliveryAssignment, ctx: &mut Self::Context) -> Self::Result {
-        if let Some(delivery_id) = msg.order.delivery_id.clone() {
-            self.handle(
-                SetDeliveryToOrder {
-                    order: msg.order.clone(),
-                    delivery_id: delivery_id.clone(),
-                },
-                ctx,
-            );
-            self.handle(
-                SetOrderStatus {
-                    order: msg.order.clone(),
-                    order_status: OrderStatus::Delivering,
-                },
-                ctx,
-            );
-
-            self.handle(
-                SetCurrentOrderToDelivery {
-                    delivery_id: delivery_id.clone(),
-                    order: msg.order.clone(),
-                },
-                ctx,
-            );
-            self.handle(
-                SetCurrentClientToDelivery {
-                    delivery_id: delivery_id.clone(),
-                    client_id: msg.order.client_id.clone(),
-                },
-                ctx,
-            );
-            self.handle(
-                RemovePendingOrderToRestaurant {
-                    order: msg.order.clone(),
-                    restaurant_id: msg.order.restaurant_id.clone(),
-                },
-                ctx,
-            );
-        } else {
+        let Some(delivery_id) = msg.order.delivery_id.clone() else {
             self.logger.error("No delivery ID found in order.");
-            return;
+            return false;
+        };
+        // Sólo se confirma al delivery que aceptó el pedido, una única vez
+        if self.accepted_deliveries.get_by_key(&msg.order.order_id) != Some(&delivery_id) {
+            self.logger.warn(format!(
+                "Delivery {} did not accept order {}, or it was already assigned",
+                delivery_id, msg.order.order_id
+            ));
+            return false;
         }
+        self.handle(
+            SetDeliveryToOrder {
+                order: msg.order.clone(),
+                delivery_id: delivery_id.clone(),
+                event: None,
+            },
+            ctx,
+        );
+        self.handle(
+            SetOrderStatus {
+                order: msg.order.clone(),
+                order_status: OrderStatus::Delivering,
+                event: None,
+            },
+            ctx,
+        );
+
+        self.handle(
+            SetCurrentOrderToDelivery {
+                delivery_id: delivery_id.clone(),
+                order: msg.order.clone(),
+                time_stamp: None,
+            },
+            ctx,
+        );
+        self.handle(
+            SetCurrentClientToDelivery {
+                delivery_id: delivery_id.clone(),
+                client_id: msg.order.client_id.clone(),
+                time_stamp: None,
+            },
+            ctx,
+        );
+        self.handle(
+            RemovePendingOrderToRestaurant {
+                order: msg.order.clone(),
+                restaurant_id: msg.order.restaurant_id.clone(),
+                time_stamp: None,
+            },
+            ctx,
+        );
 
         self.handle(
             RemoveAcceptedDeliveries {
@@ -619,6 +1752,7 @@ impl Handler<FinishDeliveryAssignment> for Storage {
         //         delivery_info: delivery.clone(),
         //     });
         // }
+        true
     }
 }
 
@@ -629,6 +1763,7 @@ impl Handler<RemoveAcceptedDeliveries> for Storage {
     fn handle(&mut self, msg: RemoveAcceptedDeliveries, _ctx: &mut Self::Context) -> Self::Result {
         self.add_to_log(StorageLogMessage::RemoveAcceptedDeliveries(msg.clone()));
         self.accepted_deliveries.remove_by_key(&msg.order_id);
+        self.accepted_epochs.remove(&msg.order_id);
     }
 }
 
@@ -636,35 +1771,640 @@ impl Handler<RemoveAcceptedDeliveries> for Storage {
 impl Handler<GetClient> for Storage {
     type Result = MessageResult<GetClient>;
 
-    fn handle(&mut self, msg: GetClient, _ctx: &mut Self::Context) -> Self::Result {
-        MessageResult(self.clients.get(&msg.client_id).cloned())
+    fn handle(&mut self, msg: GetClient, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.clients.get(&msg.client_id))
+    }
+}
+
+/// Handles requests to get a restaurant by ID.
+impl Handler<GetRestaurant> for Storage {
+    type Result = MessageResult<GetRestaurant>;
+
+    fn handle(&mut self, msg: GetRestaurant, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.restaurants.get(&msg.restaurant_id))
+    }
+}
+
+/// Handles requests to get a delivery by ID.
+impl Handler<GetDelivery> for Storage {
+    type Result = MessageResult<GetDelivery>;
+
+    fn handle(&mut self, msg: GetDelivery, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.deliverys.get(&msg.delivery_id))
+    }
+}
+
+/// Handles requests to get an order by ID.
+impl Handler<GetOrder> for Storage {
+    type Result = MessageResult<GetOrder>;
+
+    fn handle(&mut self, msg: GetOrder, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.orders.get(&msg.order_id))
+    }
+}
+
+/// Handles requests for an order that may have been removed recently.
+impl Handler<GetLastKnownOrder> for Storage {
+    type Result = MessageResult<GetLastKnownOrder>;
+
+    fn handle(&mut self, msg: GetLastKnownOrder, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.orders
+                .get(&msg.order_id)
+                .or_else(|| self.removed_orders.get(&msg.order_id).cloned()),
+        )
+    }
+}
+
+/// Handles requests for the timeline of an order.
+impl Handler<GetOrderTimeline> for Storage {
+    type Result = MessageResult<GetOrderTimeline>;
+
+    fn handle(&mut self, msg: GetOrderTimeline, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.order_events
+                .get(&msg.order_id)
+                .cloned()
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// Handles requests for the orders that are not finished yet.
+impl Handler<GetInFlightOrders> for Storage {
+    type Result = MessageResult<GetInFlightOrders>;
+
+    fn handle(&mut self, _msg: GetInFlightOrders, _ctx: &mut Self::Context) -> Self::Result {
+        let mut orders: Vec<OrderDTO> = self
+            .orders
+            .values()
+            .into_iter()
+            .filter(|order| {
+                !matches!(
+                    order.status,
+                    OrderStatus::Delivered
+                        | OrderStatus::Cancelled
+                        | OrderStatus::Unauthorized
+                        | OrderStatus::OutOfStock
+                        | OrderStatus::OutOfZone
+                        | OrderStatus::InvalidPromoCode
+                        | OrderStatus::RestaurantClosed
+                        | OrderStatus::OtherTenant
+                )
+            })
+            .collect();
+        orders.sort_by_key(|order| order.time_stamp);
+        MessageResult(orders)
+    }
+}
+
+/// Handles keeping an order update in the outbox of its user and logs the operation.
+///
+/// The update gets a new ID unless it comes with the one set by the leader. Beyond
+/// [`MAX_PENDING_ORDER_UPDATES`] the oldest updates of the user are dropped.
+impl Handler<QueueOrderUpdate> for Storage {
+    type Result = MessageResult<QueueOrderUpdate>;
+
+    fn handle(&mut self, mut msg: QueueOrderUpdate, _ctx: &mut Self::Context) -> Self::Result {
+        if msg.update.update_id == 0 {
+            msg.update.update_id = self.next_order_update_id;
+        }
+        self.next_order_update_id = self.next_order_update_id.max(msg.update.update_id + 1);
+        self.add_to_log(StorageLogMessage::QueueOrderUpdate(msg.clone()));
+
+        let outbox = self
+            .order_updates
+            .entry(msg.update.peer_id.clone())
+            .or_default();
+        if !outbox
+            .iter()
+            .any(|update| update.update_id == msg.update.update_id)
+        {
+            outbox.push_back(msg.update.clone());
+        }
+        while outbox.len() > MAX_PENDING_ORDER_UPDATES {
+            outbox.pop_front();
+        }
+        MessageResult(msg.update)
+    }
+}
+
+/// Handles dropping the order updates a user acknowledged and logs the operation.
+impl Handler<AckOrderUpdates> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, msg: AckOrderUpdates, _ctx: &mut Self::Context) -> Self::Result {
+        self.add_to_log(StorageLogMessage::AckOrderUpdates(msg.clone()));
+        if let Some(outbox) = self.order_updates.get_mut(&msg.user_id) {
+            outbox.retain(|update| update.update_id > msg.update_id);
+            if outbox.is_empty() {
+                self.order_updates.remove(&msg.user_id);
+            }
+        }
+    }
+}
+
+/// Handles requests for the order updates a user has not acknowledged yet.
+impl Handler<GetPendingOrderUpdates> for Storage {
+    type Result = MessageResult<GetPendingOrderUpdates>;
+
+    fn handle(&mut self, msg: GetPendingOrderUpdates, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.order_updates
+                .get(&msg.user_id)
+                .map(|outbox| outbox.iter().cloned().collect())
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// Handles recording what a delivery agent earned for an order and logs the operation.
+///
+/// The earning is computed from the distance between the restaurant and the client unless
+/// it comes with the one computed by the leader. The earning of an order is only recorded
+/// once.
+impl Handler<AddDeliveryEarning> for Storage {
+    type Result = MessageResult<AddDeliveryEarning>;
+
+    fn handle(&mut self, mut msg: AddDeliveryEarning, _ctx: &mut Self::Context) -> Self::Result {
+        let earning = match msg.earning.take() {
+            Some(earning) => {
+                HlcTimestamp::observe(earning.time_stamp);
+                earning
+            }
+            None => {
+                // Si el restaurante ya no está, sólo se paga la tarifa base
+                let from = self
+                    .restaurants
+                    .get(&msg.order.restaurant_id)
+                    .map_or(msg.order.client_position, |restaurant| {
+                        restaurant.restaurant_position
+                    });
+                EarningDTO::for_distance(
+                    msg.order.order_id,
+                    calculate_distance(from, msg.order.client_position),
+                    HlcTimestamp::now(),
+                )
+            }
+        };
+        // Una ganancia repetida no se vuelve a registrar en el log
+        if self
+            .delivery_earnings
+            .get(&msg.delivery_id)
+            .is_some_and(|earnings| {
+                earnings
+                    .iter()
+                    .any(|recorded| recorded.order_id == earning.order_id)
+            })
+        {
+            return MessageResult(None);
+        }
+        msg.earning = Some(earning.clone());
+        self.add_to_log(StorageLogMessage::AddDeliveryEarning(msg.clone()));
+        self.delivery_earnings
+            .entry(msg.delivery_id)
+            .or_default()
+            .push(earning.clone());
+        MessageResult(Some(earning))
+    }
+}
+
+/// Handles marking the earning of a delivery agent for an order as paid out and logs the
+/// operation.
+impl Handler<SetEarningPaidOut> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: SetEarningPaidOut, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::SetEarningPaidOut(msg.clone()));
+        if let Some(earning) =
+            self.delivery_earnings
+                .get_mut(&msg.delivery_id)
+                .and_then(|earnings| {
+                    earnings
+                        .iter_mut()
+                        .find(|earning| earning.order_id == msg.order_id)
+                })
+        {
+            earning.paid_out = true;
+            earning.time_stamp = earning.time_stamp.max(time_stamp);
+        } else {
+            self.logger.warn(format!(
+                "Earning of order {} not found for delivery {}",
+                msg.order_id, msg.delivery_id
+            ));
+        }
+    }
+}
+
+/// Handles crediting a restaurant with the fee of an order cancelled by its client and logs
+/// the operation.
+impl Handler<AddCancellationFee> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: AddCancellationFee, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::AddCancellationFee(msg.clone()));
+        let restaurant = self.restaurants.update(&msg.restaurant_id, |restaurant| {
+            restaurant.time_stamp = restaurant.time_stamp.max(time_stamp);
+            // La comisión de un pedido se acredita una sola vez
+            if !restaurant
+                .cancellation_fees
+                .iter()
+                .any(|fee| fee.order_id == msg.fee.order_id)
+            {
+                restaurant.cancellation_fees.push(msg.fee);
+            }
+        });
+        if restaurant.is_none() {
+            self.logger.error(format!(
+                "Restaurant not found for cancellation fee: {}",
+                msg.restaurant_id
+            ));
+        }
+    }
+}
+
+/// Handles recording whether a restaurant is within its opening hours and logs the operation.
+impl Handler<SetRestaurantOpen> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: SetRestaurantOpen, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::SetRestaurantOpen(msg.clone()));
+        let Some(mut restaurant) = self.restaurants.get(&msg.restaurant_id) else {
+            self.logger.error(format!(
+                "Restaurant not found to set its opening: {}",
+                msg.restaurant_id
+            ));
+            return;
+        };
+        restaurant.time_stamp = restaurant.time_stamp.max(time_stamp);
+        restaurant.closed = !msg.open;
+        self.restaurants
+            .insert(msg.restaurant_id.clone(), restaurant);
+        self.read_view
+            .set_restaurant_open(&msg.restaurant_id, msg.open);
+    }
+}
+
+/// Handles recording the tags of a restaurant and logs the operation.
+impl Handler<SetRestaurantTags> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: SetRestaurantTags, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::SetRestaurantTags(msg.clone()));
+        let Some(mut restaurant) = self.restaurants.get(&msg.restaurant_id) else {
+            self.logger.error(format!(
+                "Restaurant not found to set its tags: {}",
+                msg.restaurant_id
+            ));
+            return;
+        };
+        restaurant.time_stamp = restaurant.time_stamp.max(time_stamp);
+        restaurant.tags = msg.tags;
+        self.read_view.put_restaurant(restaurant.info());
+        self.restaurants.insert(msg.restaurant_id, restaurant);
+    }
+}
+
+/// Handles recording that a user left on a clean shutdown and logs the operation: a delivery
+/// is marked offline and forgets its current order, and a restaurant is closed.
+impl Handler<SetUserOffline> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: SetUserOffline, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::SetUserOffline(msg.clone()));
+        if let Some(mut delivery) = self.deliverys.get(&msg.user_id) {
+            delivery.time_stamp = delivery.time_stamp.max(time_stamp);
+            delivery.status = DeliveryStatus::Offline;
+            // Sus pedidos se reasignan a otros deliveries
+            delivery.current_order = None;
+            delivery.current_client_id = None;
+            self.read_view.put_delivery(delivery.clone());
+            self.deliverys.insert(msg.user_id.clone(), delivery);
+        } else if let Some(mut restaurant) = self.restaurants.get(&msg.user_id) {
+            restaurant.time_stamp = restaurant.time_stamp.max(time_stamp);
+            restaurant.closed = true;
+            self.read_view.set_restaurant_open(&msg.user_id, false);
+            self.restaurants.insert(msg.user_id.clone(), restaurant);
+        }
+        // Los clientes no cambian: sus pedidos los cancela el OrderService
+        self.logger.info(format!("User {} is offline", msg.user_id));
+    }
+}
+
+/// Handles recording that some users are still connected and logs the operation. Only the
+/// clients and deliveries in storage are recorded.
+impl Handler<TouchUsers> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: TouchUsers, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::TouchUsers(msg.clone()));
+        for user_id in msg.user_ids {
+            if self.clients.contains_key(&user_id) || self.deliverys.contains_key(&user_id) {
+                self.see_user(user_id, time_stamp);
+            }
+        }
+    }
+}
+
+/// Handles requests for the clients and deliveries not seen for a while.
+impl Handler<GetStaleUsers> for Storage {
+    type Result = MessageResult<GetStaleUsers>;
+
+    fn handle(&mut self, msg: GetStaleUsers, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.stale_users(msg.seen_before))
+    }
+}
+
+/// Handles claims of user IDs: a salted hash of the first secret an ID is registered with is
+/// logged, and only that secret can be used with the ID from then on.
+impl Handler<ClaimUserId> for Storage {
+    type Result = bool;
+
+    fn handle(&mut self, mut msg: ClaimUserId, _ctx: &mut Self::Context) -> Self::Result {
+        // Un ID pertenece al marketplace en el que se registró primero
+        if let Some(tenant_id) = self.tenant_of(&msg.user_id)
+            && tenant_id != msg.tenant_id
+        {
+            self.logger.warn(format!(
+                "User ID {} belongs to tenant {}, not to {}",
+                msg.user_id, tenant_id, msg.tenant_id
+            ));
+            return false;
+        }
+        if let Some(kept) = self.user_secrets.get(&msg.user_id) {
+            return match (&msg.secret, &msg.secret_hash) {
+                (Some(secret), _) => kept.matches(secret),
+                // Un reclamo ya logueado que vuelve a aplicarse
+                (None, Some(hash)) => hash == kept,
+                (None, None) => false,
+            };
+        }
+        // Los reclamos replicados o repetidos del log ya traen el hash con su salt
+        let hash = match (msg.secret.take(), msg.secret_hash.take()) {
+            (_, Some(hash)) => hash,
+            (Some(secret), None) => SecretHash::new(&secret),
+            // Los IDs sin secreto los puede usar cualquiera, como antes
+            (None, None) => return true,
+        };
+        msg.secret_hash = Some(hash.clone());
+        self.add_to_log(StorageLogMessage::ClaimUserId(msg.clone()));
+        self.user_secrets.insert(msg.user_id.clone(), hash);
+        self.logger.info(format!("User ID {} claimed", msg.user_id));
+        true
+    }
+}
+
+/// Handles claims of order IDs for client requests: the first order ID a request is claimed
+/// with is logged, and resends of the request get that same ID back.
+impl Handler<ClaimOrderRequest> for Storage {
+    type Result = u64;
+
+    fn handle(&mut self, msg: ClaimOrderRequest, _ctx: &mut Self::Context) -> Self::Result {
+        let kept = self
+            .order_requests
+            .get(&msg.client_id)
+            .and_then(|requests| {
+                requests
+                    .iter()
+                    .find(|kept| kept.request_id == msg.request.request_id)
+            });
+        if let Some(kept) = kept {
+            return kept.order_id;
+        }
+        self.add_to_log(StorageLogMessage::ClaimOrderRequest(msg.clone()));
+        self.remember_order_request(&msg.client_id, msg.request)
+    }
+}
+
+/// Handles recording that a client used a promo code and logs the operation.
+impl Handler<AddPromoRedemption> for Storage {
+    type Result = bool;
+
+    fn handle(&mut self, mut msg: AddPromoRedemption, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        msg.redemption.time_stamp = time_stamp;
+        self.add_to_log(StorageLogMessage::AddPromoRedemption(msg.clone()));
+        let code = msg.redemption.code.clone();
+        let recorded = self.remember_redemption(&code, msg.redemption);
+        if !recorded {
+            self.logger.warn(format!(
+                "Promo code {} was already used by the client",
+                code
+            ));
+        }
+        recorded
+    }
+}
+
+/// Handles requests for the uses of a promo code.
+impl Handler<GetPromoRedemptions> for Storage {
+    type Result = MessageResult<GetPromoRedemptions>;
+
+    fn handle(&mut self, msg: GetPromoRedemptions, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.promo_redemptions
+                .get(&msg.code)
+                .cloned()
+                .unwrap_or_default(),
+        )
+    }
+}
+
+/// Handles keeping the bill of a finalized order in the payment outbox and logs the
+/// operation. The bill of an order already in the outbox is kept as it is.
+impl Handler<AddOutboxPayment> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, mut msg: AddOutboxPayment, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::AddOutboxPayment(msg.clone()));
+        self.payment_outbox
+            .entry(msg.order.order_id)
+            .or_insert(OutboxPayment {
+                order: msg.order,
+                completed: false,
+                time_stamp,
+            });
+    }
+}
+
+/// Handles marking the bill of an order in the payment outbox as completed and logs the
+/// operation.
+///
+/// Answers whether the payment was completed now, so a repeated confirmation of the
+/// gateway does not finalize the order twice. Repeated confirmations, and those of orders
+/// not in the outbox, are not logged.
+impl Handler<CompleteOutboxPayment> for Storage {
+    type Result = bool;
+
+    fn handle(&mut self, mut msg: CompleteOutboxPayment, _ctx: &mut Self::Context) -> Self::Result {
+        let order_id = msg.order.order_id;
+        match self.payment_outbox.get(&order_id) {
+            Some(payment) if payment.completed => return false,
+            Some(_) => {}
+            // Ya se completó y se descartó, o nunca se cobró: no hay nada que finalizar
+            None => {
+                self.logger.warn(format!(
+                    "Payment of order {} completed without being in the outbox, ignoring it",
+                    order_id
+                ));
+                return false;
+            }
+        }
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::CompleteOutboxPayment(msg));
+        if let Some(payment) = self.payment_outbox.get_mut(&order_id) {
+            payment.completed = true;
+            payment.time_stamp = payment.time_stamp.max(time_stamp);
+        }
+        self.drop_old_completed_payments();
+        true
+    }
+}
+
+/// Handles requests for the bills of the payment outbox not completed yet.
+impl Handler<GetPendingPayments> for Storage {
+    type Result = MessageResult<GetPendingPayments>;
+
+    fn handle(&mut self, _msg: GetPendingPayments, _ctx: &mut Self::Context) -> Self::Result {
+        let mut pending: Vec<&OutboxPayment> = self
+            .payment_outbox
+            .values()
+            .filter(|payment| !payment.completed)
+            .collect();
+        pending.sort_by_key(|payment| payment.time_stamp);
+        MessageResult(
+            pending
+                .into_iter()
+                .map(|payment| payment.order.clone())
+                .collect(),
+        )
+    }
+}
+
+/// Handles recording a leader change in the election history and logs the operation.
+impl Handler<RecordElection> for Storage {
+    type Result = ();
+
+    fn handle(&mut self, msg: RecordElection, _ctx: &mut Self::Context) -> Self::Result {
+        self.add_to_log(StorageLogMessage::RecordElection(msg.clone()));
+        let RecordElection { record } = msg;
+        let (leader_id, epoch) = (record.leader_id.clone(), record.epoch);
+        if self.remember_election(record) {
+            self.logger.info(format!(
+                "Election recorded: {} leads epoch {}",
+                leader_id, epoch
+            ));
+        }
+    }
+}
+
+/// Handles requests for the election history.
+impl Handler<GetElectionHistory> for Storage {
+    type Result = MessageResult<GetElectionHistory>;
+
+    fn handle(&mut self, _msg: GetElectionHistory, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.elections.iter().cloned().collect())
+    }
+}
+
+/// Handles requests for the earnings of a delivery agent.
+impl Handler<GetDeliveryEarnings> for Storage {
+    type Result = MessageResult<GetDeliveryEarnings>;
+
+    fn handle(&mut self, msg: GetDeliveryEarnings, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(EarningsReport {
+            earnings: self
+                .delivery_earnings
+                .get(&msg.delivery_id)
+                .cloned()
+                .unwrap_or_default(),
+            delivery_id: msg.delivery_id,
+        })
     }
 }
 
-/// Handles requests to get a restaurant by ID.
-impl Handler<GetRestaurant> for Storage {
-    type Result = MessageResult<GetRestaurant>;
+/// Handles requests to get the orders a delivery agent is delivering or was accepted for.
+impl Handler<GetDeliveryOrders> for Storage {
+    type Result = MessageResult<GetDeliveryOrders>;
 
-    fn handle(&mut self, msg: GetRestaurant, _ctx: &mut Self::Context) -> Self::Result {
-        MessageResult(self.restaurants.get(&msg.restaurant_id).cloned())
+    fn handle(&mut self, msg: GetDeliveryOrders, _ctx: &mut Self::Context) -> Self::Result {
+        let accepted = self.accepted_deliveries.get_by_value(&msg.delivery_id);
+        let orders = self
+            .orders
+            .values()
+            .into_iter()
+            .filter(|order| {
+                Some(&order.order_id) == accepted
+                    || (order.status == OrderStatus::Delivering
+                        && order.delivery_id.as_ref() == Some(&msg.delivery_id))
+            })
+            .collect();
+        MessageResult(orders)
     }
 }
 
-/// Handles requests to get a delivery by ID.
-impl Handler<GetDelivery> for Storage {
-    type Result = MessageResult<GetDelivery>;
-
-    fn handle(&mut self, msg: GetDelivery, _ctx: &mut Self::Context) -> Self::Result {
-        MessageResult(self.deliverys.get(&msg.delivery_id).cloned())
+/// Handles requests to get the acceptances decided in older epochs whose assignment was not
+/// finished.
+impl Handler<GetPendingAssignments> for Storage {
+    type Result = MessageResult<GetPendingAssignments>;
+
+    fn handle(&mut self, msg: GetPendingAssignments, _ctx: &mut Self::Context) -> Self::Result {
+        let pending = self
+            .accepted_deliveries
+            .keys()
+            .filter(|order_id| {
+                self.accepted_epochs
+                    .get(order_id)
+                    .copied()
+                    .unwrap_or_default()
+                    < msg.before_epoch
+            })
+            .filter_map(|order_id| {
+                let order = self.orders.get(order_id)?;
+                let delivery_id = self.accepted_deliveries.get_by_key(order_id)?;
+                let delivery = self.deliverys.get(delivery_id)?;
+                let restaurant_matches = msg
+                    .restaurant_id
+                    .as_ref()
+                    .is_none_or(|restaurant_id| *restaurant_id == order.restaurant_id);
+                (order.status == OrderStatus::ReadyForDelivery && restaurant_matches).then_some(
+                    DeliveryAvailable {
+                        order,
+                        delivery_info: delivery,
+                    },
+                )
+            })
+            .collect();
+        MessageResult(pending)
     }
 }
 
-/// Handles requests to get an order by ID.
-impl Handler<GetOrder> for Storage {
-    type Result = MessageResult<GetOrder>;
+/// Handles requests to get the delivery agent that accepted an order.
+impl Handler<GetAcceptedDelivery> for Storage {
+    type Result = MessageResult<GetAcceptedDelivery>;
 
-    fn handle(&mut self, msg: GetOrder, _ctx: &mut Self::Context) -> Self::Result {
-        MessageResult(self.orders.get(&msg.order_id).cloned())
+    fn handle(&mut self, msg: GetAcceptedDelivery, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(
+            self.accepted_deliveries
+                .get_by_key(&msg.order_id)
+                .and_then(|delivery_id| self.deliverys.get(delivery_id)),
+        )
     }
 }
 
@@ -678,6 +2418,7 @@ impl Handler<RemoveUser> for Storage {
             self.handle(
                 RemoveClient {
                     client_id: msg.user_id,
+                    time_stamp: None,
                 },
                 ctx,
             );
@@ -685,6 +2426,7 @@ impl Handler<RemoveUser> for Storage {
             self.handle(
                 RemoveRestaurant {
                     restaurant_id: msg.user_id,
+                    time_stamp: None,
                 },
                 ctx,
             );
@@ -692,6 +2434,7 @@ impl Handler<RemoveUser> for Storage {
             self.handle(
                 RemoveDelivery {
                     delivery_id: msg.user_id,
+                    time_stamp: None,
                 },
                 ctx,
             );
@@ -705,11 +2448,20 @@ impl Handler<RemoveUser> for Storage {
 impl Handler<RemoveClient> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: RemoveClient, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: RemoveClient, _ctx: &mut Self::Context) -> Self::Result {
         self.logger
             .info(format!("Client removed: {}", msg.client_id));
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::RemoveClient(msg.clone()));
         self.clients.remove(&msg.client_id);
+        bury(
+            &mut self.tombstones.clients,
+            msg.client_id.clone(),
+            time_stamp,
+        );
+        self.order_updates.remove(&msg.client_id);
+        self.last_seen.remove(&msg.client_id);
     }
 }
 
@@ -717,10 +2469,18 @@ impl Handler<RemoveClient> for Storage {
 impl Handler<RemoveRestaurant> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: RemoveRestaurant, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: RemoveRestaurant, _ctx: &mut Self::Context) -> Self::Result {
         self.logger
             .info(format!("Restaurant removed: {}", msg.restaurant_id));
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.restaurants.remove(&msg.restaurant_id);
+        bury(
+            &mut self.tombstones.restaurants,
+            msg.restaurant_id.clone(),
+            time_stamp,
+        );
+        self.read_view.remove_restaurant(&msg.restaurant_id);
         self.add_to_log(StorageLogMessage::RemoveRestaurant(msg.clone()));
         // TODO: ver como hacer cascade con las órdenes asociadas a este restaurante.
     }
@@ -730,11 +2490,20 @@ impl Handler<RemoveRestaurant> for Storage {
 impl Handler<RemoveDelivery> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: RemoveDelivery, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: RemoveDelivery, _ctx: &mut Self::Context) -> Self::Result {
         self.logger
             .info(format!("Delivery removed: {}", msg.delivery_id));
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::RemoveDelivery(msg.clone()));
         self.deliverys.remove(&msg.delivery_id);
+        bury(
+            &mut self.tombstones.deliverys,
+            msg.delivery_id.clone(),
+            time_stamp,
+        );
+        self.read_view.remove_delivery(&msg.delivery_id);
+        self.last_seen.remove(&msg.delivery_id);
     }
 }
 
@@ -744,19 +2513,24 @@ impl Handler<RemoveAuthorizedOrderToRestaurant> for Storage {
 
     fn handle(
         &mut self,
-        msg: RemoveAuthorizedOrderToRestaurant,
+        mut msg: RemoveAuthorizedOrderToRestaurant,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::RemoveAuthorizedOrderToRestaurant(
             msg.clone(),
         ));
-        if let Some(restaurant) = self.restaurants.get_mut(&msg.restaurant_id) {
+        if let Some(mut restaurant) = self.restaurants.get(&msg.restaurant_id) {
+            restaurant.time_stamp = restaurant.time_stamp.max(time_stamp);
             if let Some(order) = self.orders.get(&msg.order.order_id) {
-                restaurant.authorized_orders.remove(order);
+                restaurant.authorized_orders.remove(&order);
             } else {
                 self.logger
                     .warn(format!("Order not found: {}", msg.order.order_id));
             }
+            self.restaurants
+                .insert(msg.restaurant_id.clone(), restaurant);
         } else {
             self.logger.error(format!(
                 "Restaurant not found for order: {}",
@@ -772,19 +2546,24 @@ impl Handler<RemovePendingOrderToRestaurant> for Storage {
 
     fn handle(
         &mut self,
-        msg: RemovePendingOrderToRestaurant,
+        mut msg: RemovePendingOrderToRestaurant,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::RemovePendingOrderToRestaurant(
             msg.clone(),
         ));
-        if let Some(restaurant) = self.restaurants.get_mut(&msg.restaurant_id) {
+        if let Some(mut restaurant) = self.restaurants.get(&msg.restaurant_id) {
+            restaurant.time_stamp = restaurant.time_stamp.max(time_stamp);
             if let Some(order) = self.orders.get(&msg.order.order_id) {
-                restaurant.pending_orders.remove(order);
+                restaurant.pending_orders.remove(&order);
             } else {
                 self.logger
                     .warn(format!("Order not found: {}", msg.order.order_id));
             }
+            self.restaurants
+                .insert(msg.restaurant_id.clone(), restaurant);
         } else {
             self.logger.error(format!(
                 "Restaurant not found for order: {}",
@@ -798,23 +2577,36 @@ impl Handler<RemovePendingOrderToRestaurant> for Storage {
 impl Handler<RemoveOrder> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: RemoveOrder, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: RemoveOrder, _ctx: &mut Self::Context) -> Self::Result {
         self.logger
             .info(format!("Order removed: {}", msg.order.order_id));
+        let event = self.stamp_event(msg.event.take(), OrderEventKind::Removed, &msg.order);
+        let removed_at = event.timestamp;
+        msg.event = Some(event.clone());
         self.add_to_log(StorageLogMessage::RemoveOrder(msg.clone()));
         if let Some(order) = self.orders.remove(&msg.order.order_id) {
+            bury(&mut self.tombstones.orders, order.order_id, removed_at);
+            self.record_order_event(&order, event);
+            let mut last_state = order.clone();
+            last_state.status = msg.order.status;
+            last_state.time_stamp = removed_at;
+            self.keep_removed_order(last_state);
             // Limpiar la orden del cliente
-            if let Some(client) = self.clients.get_mut(&order.client_id) {
+            if let Some(mut client) = self.clients.get(&order.client_id) {
                 client.client_order = None;
+                self.clients.insert(order.client_id.clone(), client);
             } else {
                 self.logger
                     .warn(format!("Client not found for order: {}", order.client_id));
             }
 
             // Limpiar la orden de los pedidos del restaurante
-            if let Some(restaurant) = self.restaurants.get_mut(&order.restaurant_id) {
+            if let Some(mut restaurant) = self.restaurants.get(&order.restaurant_id) {
                 restaurant.pending_orders.remove(&order);
                 restaurant.authorized_orders.remove(&order);
+                restaurant.time_stamp = restaurant.time_stamp.max(removed_at);
+                self.restaurants
+                    .insert(order.restaurant_id.clone(), restaurant);
             } else {
                 self.logger.warn(format!(
                     "Restaurant not found for order: {}",
@@ -823,19 +2615,34 @@ impl Handler<RemoveOrder> for Storage {
             }
 
             // Limpiar la orden del delivery si corresponde
-            if let Some(delivery_id) = &order.delivery_id {
-                if let Some(delivery) = self.deliverys.get_mut(delivery_id) {
-                    if let Some(current_order) = &delivery.current_order {
-                        if current_order.order_id == order.order_id {
-                            delivery.current_order = None;
-                        }
-                    }
-                } else {
-                    self.logger
-                        .warn(format!("Delivery not found for order: {}", delivery_id));
+            if let Some(delivery_id) = &order.delivery_id
+                && !self.deliverys.contains_key(delivery_id)
+            {
+                self.logger
+                    .warn(format!("Delivery not found for order: {}", delivery_id));
+            }
+            // La orden puede estar en un delivery que todavía no figura en la orden
+            for (delivery_id, mut delivery) in self.deliverys.entries() {
+                if delivery
+                    .current_order
+                    .as_ref()
+                    .is_some_and(|current_order| current_order.order_id == order.order_id)
+                {
+                    delivery.current_order = None;
+                    delivery.time_stamp = delivery.time_stamp.max(removed_at);
+                    self.read_view.put_delivery(delivery.clone());
+                    self.deliverys.insert(delivery_id, delivery);
                 }
             }
+            // Una aceptación pendiente de la orden ya no tiene a quién asignarse
+            self.accepted_deliveries.remove_by_key(&order.order_id);
+            self.accepted_epochs.remove(&order.order_id);
             self.clients.remove(&msg.order.client_id);
+            bury(
+                &mut self.tombstones.clients,
+                msg.order.client_id.clone(),
+                removed_at,
+            );
         } else {
             self.logger
                 .warn(format!("Order not found: {}", msg.order.order_id));
@@ -849,12 +2656,17 @@ impl Handler<RemoveOrder> for Storage {
 impl Handler<SetDeliveryPosition> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: SetDeliveryPosition, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: SetDeliveryPosition, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::SetDeliveryPosition(msg.clone()));
-        if let Some(delivery) = self.deliverys.get_mut(&msg.delivery_id) {
+        if let Some(mut delivery) = self.deliverys.get(&msg.delivery_id) {
+            delivery.time_stamp = delivery.time_stamp.max(time_stamp);
             delivery.delivery_position = msg.position;
+            self.read_view.put_delivery(delivery.clone());
             self.logger
                 .info(format!("Delivery position updated: {}", msg.delivery_id));
+            self.deliverys.insert(msg.delivery_id.clone(), delivery);
         } else {
             self.logger
                 .warn(format!("Delivery not found: {}", msg.delivery_id));
@@ -868,16 +2680,21 @@ impl Handler<SetCurrentClientToDelivery> for Storage {
 
     fn handle(
         &mut self,
-        msg: SetCurrentClientToDelivery,
+        mut msg: SetCurrentClientToDelivery,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::SetCurrentClientToDelivery(msg.clone()));
-        if let Some(delivery) = self.deliverys.get_mut(&msg.delivery_id) {
+        if let Some(mut delivery) = self.deliverys.get(&msg.delivery_id) {
+            delivery.time_stamp = delivery.time_stamp.max(time_stamp);
             delivery.current_client_id = Some(msg.client_id.clone());
+            self.read_view.put_delivery(delivery.clone());
             self.logger.info(format!(
                 "Current client set for delivery: {}",
                 msg.delivery_id
             ));
+            self.deliverys.insert(msg.delivery_id.clone(), delivery);
         } else {
             self.logger
                 .warn(format!("Delivery not found: {}", msg.delivery_id));
@@ -889,12 +2706,20 @@ impl Handler<SetCurrentClientToDelivery> for Storage {
 impl Handler<SetCurrentOrderToDelivery> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: SetCurrentOrderToDelivery, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(
+        &mut self,
+        mut msg: SetCurrentOrderToDelivery,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::SetCurrentOrderToDelivery(msg.clone()));
-        if let Some(delivery) = self.deliverys.get_mut(&msg.delivery_id) {
+        if let Some(mut delivery) = self.deliverys.get(&msg.delivery_id) {
+            delivery.time_stamp = delivery.time_stamp.max(time_stamp);
             // Obtener la orden del id pasado en el mensaje
             if let Some(order) = self.orders.get(&msg.order.order_id) {
                 delivery.current_order = Some(order.clone());
+                self.read_view.put_delivery(delivery.clone());
                 self.logger.info(format!(
                     "Current order set for delivery: {}",
                     msg.delivery_id
@@ -903,6 +2728,7 @@ impl Handler<SetCurrentOrderToDelivery> for Storage {
                 self.logger
                     .warn(format!("Order not found: {}", msg.order.order_id));
             }
+            self.deliverys.insert(msg.delivery_id.clone(), delivery);
         } else {
             self.logger
                 .warn(format!("Delivery not found: {}", msg.delivery_id));
@@ -914,12 +2740,17 @@ impl Handler<SetCurrentOrderToDelivery> for Storage {
 impl Handler<SetDeliveryStatus> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: SetDeliveryStatus, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: SetDeliveryStatus, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::SetDeliveryStatus(msg.clone()));
-        if let Some(delivery) = self.deliverys.get_mut(&msg.delivery_id) {
+        if let Some(mut delivery) = self.deliverys.get(&msg.delivery_id) {
+            delivery.time_stamp = delivery.time_stamp.max(time_stamp);
             delivery.status = msg.delivery_status;
+            self.read_view.put_delivery(delivery.clone());
             self.logger
                 .info(format!("Delivery status updated: {}", msg.delivery_id));
+            self.deliverys.insert(msg.delivery_id.clone(), delivery);
         } else {
             self.logger
                 .warn(format!("Delivery not found: {}", msg.delivery_id));
@@ -931,12 +2762,27 @@ impl Handler<SetDeliveryStatus> for Storage {
 impl Handler<SetDeliveryToOrder> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: SetDeliveryToOrder, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: SetDeliveryToOrder, _ctx: &mut Self::Context) -> Self::Result {
+        let mut changed = msg.order.clone();
+        changed.delivery_id = Some(msg.delivery_id.clone());
+        let event = self.stamp_event(msg.event.take(), OrderEventKind::DeliveryAssigned, &changed);
+        msg.event = Some(event.clone());
         self.add_to_log(StorageLogMessage::SetDeliveryToOrder(msg.clone()));
-        if let Some(order) = self.orders.get_mut(&msg.order.order_id) {
+        if let Some(mut order) = self.orders.get(&msg.order.order_id) {
             order.delivery_id = Some(msg.delivery_id.clone());
-            let order_clone = order.clone();
-            self.update_associated_order(&order_clone);
+            order.time_stamp = order.time_stamp.max(event.timestamp);
+            self.orders.insert(msg.order.order_id, order.clone());
+            self.update_associated_order(&order);
+            self.record_order_event(&order, event);
+            // La aceptación de otro delivery queda descartada
+            if self
+                .accepted_deliveries
+                .get_by_key(&msg.order.order_id)
+                .is_some_and(|accepted| *accepted != msg.delivery_id)
+            {
+                self.accepted_deliveries.remove_by_key(&msg.order.order_id);
+                self.accepted_epochs.remove(&msg.order.order_id);
+            }
         } else {
             self.logger
                 .warn(format!("Order not found: {}", msg.order.order_id));
@@ -948,12 +2794,18 @@ impl Handler<SetDeliveryToOrder> for Storage {
 impl Handler<SetOrderStatus> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: SetOrderStatus, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: SetOrderStatus, _ctx: &mut Self::Context) -> Self::Result {
+        let mut changed = msg.order.clone();
+        changed.status = msg.order_status.clone();
+        let event = self.stamp_event(msg.event.take(), OrderEventKind::StatusChanged, &changed);
+        msg.event = Some(event.clone());
         self.add_to_log(StorageLogMessage::SetOrderStatus(msg.clone()));
-        if let Some(order) = self.orders.get_mut(&msg.order.order_id) {
+        if let Some(mut order) = self.orders.get(&msg.order.order_id) {
             order.status = msg.order_status.clone();
-            let order_clone = order.clone();
-            self.update_associated_order(&order_clone);
+            order.time_stamp = order.time_stamp.max(event.timestamp);
+            self.orders.insert(msg.order.order_id, order.clone());
+            self.update_associated_order(&order);
+            self.record_order_event(&order, event);
         } else {
             self.logger
                 .warn(format!("Order not found: {}", msg.order.order_id));
@@ -964,12 +2816,38 @@ impl Handler<SetOrderStatus> for Storage {
 impl Handler<SetOrderExpectedTime> for Storage {
     type Result = ();
 
-    fn handle(&mut self, msg: SetOrderExpectedTime, _ctx: &mut Self::Context) -> Self::Result {
+    fn handle(&mut self, mut msg: SetOrderExpectedTime, _ctx: &mut Self::Context) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
         self.add_to_log(StorageLogMessage::SetOrderExpectedTime(msg.clone()));
-        if let Some(order) = self.orders.get_mut(&msg.order_id) {
+        if let Some(mut order) = self.orders.get(&msg.order_id) {
             order.expected_delivery_time = msg.expected_time;
-            let order_clone = order.clone();
-            self.update_associated_order(&order_clone);
+            order.time_stamp = order.time_stamp.max(time_stamp);
+            self.orders.insert(msg.order_id, order.clone());
+            self.update_associated_order(&order);
+        } else {
+            self.logger
+                .warn(format!("Order not found: {}", msg.order_id));
+        }
+    }
+}
+
+impl Handler<SetOrderPreparationTime> for Storage {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        mut msg: SetOrderPreparationTime,
+        _ctx: &mut Self::Context,
+    ) -> Self::Result {
+        let time_stamp = self.stamp_change(msg.time_stamp.take());
+        msg.time_stamp = Some(time_stamp);
+        self.add_to_log(StorageLogMessage::SetOrderPreparationTime(msg.clone()));
+        if let Some(mut order) = self.orders.get(&msg.order_id) {
+            order.expected_preparation_time = msg.preparation_time;
+            order.time_stamp = order.time_stamp.max(time_stamp);
+            self.orders.insert(msg.order_id, order.clone());
+            self.update_associated_order(&order);
         } else {
             self.logger
                 .warn(format!("Order not found: {}", msg.order_id));
@@ -982,7 +2860,7 @@ impl Handler<GetRestaurants> for Storage {
     type Result = MessageResult<GetRestaurants>;
 
     fn handle(&mut self, _msg: GetRestaurants, _ctx: &mut Self::Context) -> Self::Result {
-        let restaurants: Vec<RestaurantDTO> = self.restaurants.values().cloned().collect();
+        let restaurants: Vec<RestaurantDTO> = self.restaurants.values();
         MessageResult(restaurants)
     }
 }
@@ -992,15 +2870,25 @@ impl Handler<GetAllRestaurantsInfo> for Storage {
     type Result = MessageResult<GetAllRestaurantsInfo>;
 
     fn handle(&mut self, _msg: GetAllRestaurantsInfo, _ctx: &mut Self::Context) -> Self::Result {
-        let restaurants: Vec<RestaurantInfo> = self
-            .restaurants
-            .values()
-            .map(|r| RestaurantInfo {
-                id: r.restaurant_id.clone(),
-                position: r.restaurant_position,
-            })
-            .collect();
-        MessageResult(restaurants)
+        MessageResult(self.read_view.restaurants())
+    }
+}
+
+/// Handles requests for the restaurants near a position.
+impl Handler<GetNearbyRestaurants> for Storage {
+    type Result = MessageResult<GetNearbyRestaurants>;
+
+    fn handle(&mut self, msg: GetNearbyRestaurants, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.read_view.nearby_restaurants(msg.position, msg.radius))
+    }
+}
+
+/// Handles requests for the deliveries near a position.
+impl Handler<GetNearbyDeliveries> for Storage {
+    type Result = MessageResult<GetNearbyDeliveries>;
+
+    fn handle(&mut self, msg: GetNearbyDeliveries, _ctx: &mut Self::Context) -> Self::Result {
+        MessageResult(self.read_view.nearby_deliveries(msg.position, msg.radius))
     }
 }
 
@@ -1009,8 +2897,7 @@ impl Handler<GetDeliveries> for Storage {
     type Result = MessageResult<GetDeliveries>;
 
     fn handle(&mut self, _msg: GetDeliveries, _ctx: &mut Self::Context) -> Self::Result {
-        let deliveries: Vec<DeliveryDTO> = self.deliverys.values().cloned().collect();
-        MessageResult(deliveries)
+        MessageResult(self.read_view.deliveries())
     }
 }
 
@@ -1023,12 +2910,1077 @@ impl Handler<GetAllAvailableDeliveries> for Storage {
         _msg: GetAllAvailableDeliveries,
         _ctx: &mut Self::Context,
     ) -> Self::Result {
-        let available_deliveries: Vec<DeliveryDTO> = self
-            .deliverys
-            .values()
-            .filter(|d| d.status == common::types::delivery_status::DeliveryStatus::Available)
-            .cloned()
+        MessageResult(self.read_view.available_deliveries())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::dtos::{CancellationFee, OrderItem};
+    use common::types::storage_entity::StorageEntity;
+    use common::types::tenant::TenantId;
+    use common::types::vehicle::VehicleType;
+    use proptest::prelude::*;
+
+    const CLIENTS: [&str; 3] = ["client_0", "client_1", "client_2"];
+    const RESTAURANTS: [&str; 2] = ["restaurant_0", "restaurant_1"];
+    const DELIVERIES: [&str; 3] = ["delivery_0", "delivery_1", "delivery_2"];
+    const ORDERS: u64 = 4;
+    const ORDER_STATUSES: [OrderStatus; 4] = [
+        OrderStatus::Authorized,
+        OrderStatus::Preparing,
+        OrderStatus::ReadyForDelivery,
+        OrderStatus::Delivering,
+    ];
+    const DELIVERY_STATUSES: [DeliveryStatus; 4] = [
+        DeliveryStatus::Available,
+        DeliveryStatus::WaitingConfirmation,
+        DeliveryStatus::Delivering,
+        DeliveryStatus::Reconnecting,
+    ];
+
+    /// Returns a storage whose coordinator is never started: the log messages do not
+    /// reach it.
+    fn new_storage() -> (Storage, Context<Storage>) {
+        let coordinator = Context::<Coordinator>::new().address();
+        (
+            Storage::new(
+                coordinator,
+                "server_0".to_string(),
+                None,
+                StorageReadView::default(),
+            ),
+            Context::new(),
+        )
+    }
+
+    /// Returns the order `order_id`, always placed by the same client at the same
+    /// restaurant.
+    fn order(order_id: u64) -> OrderDTO {
+        OrderDTO::new(
+            order_id,
+            CLIENTS[order_id as usize % CLIENTS.len()],
+            RESTAURANTS[order_id as usize % RESTAURANTS.len()],
+            vec![OrderItem::new("Pizza", 1)],
+        )
+    }
+
+    /// Builds the log message `kind` out of the IDs drawn by the strategy.
+    fn log_message(
+        kind: u8,
+        client: usize,
+        restaurant: usize,
+        delivery: usize,
+        order_id: u64,
+        n: u64,
+    ) -> StorageLogMessage {
+        let client_id = CLIENTS[client].to_string();
+        let restaurant_id = RESTAURANTS[restaurant].to_string();
+        let delivery_id = DELIVERIES[delivery].to_string();
+        let order = order(order_id);
+        match kind {
+            0 => StorageLogMessage::AddClient(AddClient {
+                client: ClientDTO {
+                    client_position: (0.0, 0.0),
+                    client_id,
+                    client_order: None,
+                    time_stamp: HlcTimestamp::default(),
+                    tenant_id: TenantId::default(),
+                },
+            }),
+            1 => StorageLogMessage::AddRestaurant(AddRestaurant {
+                restaurant: RestaurantDTO {
+                    restaurant_position: (0.0, 0.0),
+                    restaurant_id,
+                    authorized_orders: Default::default(),
+                    pending_orders: Default::default(),
+                    time_stamp: HlcTimestamp::default(),
+                    cancellation_fees: Vec::new(),
+                    closed: false,
+                    tags: vec![format!("tag_{}", n % 3)],
+                    tenant_id: TenantId::default(),
+                },
+            }),
+            2 => StorageLogMessage::AddDelivery(AddDelivery {
+                delivery: DeliveryDTO {
+                    delivery_position: (0.0, 0.0),
+                    delivery_id,
+                    current_client_id: None,
+                    current_order: None,
+                    status: DeliveryStatus::Available,
+                    vehicle: VehicleType::default(),
+                    time_stamp: HlcTimestamp::default(),
+                    tenant_id: TenantId::default(),
+                },
+            }),
+            3 => StorageLogMessage::RemoveClient(RemoveClient {
+                client_id,
+                time_stamp: None,
+            }),
+            4 => StorageLogMessage::RemoveRestaurant(RemoveRestaurant {
+                restaurant_id,
+                time_stamp: None,
+            }),
+            5 => StorageLogMessage::RemoveDelivery(RemoveDelivery {
+                delivery_id,
+                time_stamp: None,
+            }),
+            6 => StorageLogMessage::SetDeliveryPosition(SetDeliveryPosition {
+                delivery_id,
+                position: (n as f32, n as f32),
+                time_stamp: None,
+            }),
+            7 => StorageLogMessage::SetCurrentClientToDelivery(SetCurrentClientToDelivery {
+                delivery_id,
+                client_id,
+                time_stamp: None,
+            }),
+            8 => StorageLogMessage::SetDeliveryStatus(SetDeliveryStatus {
+                delivery_id,
+                delivery_status: DELIVERY_STATUSES[n as usize],
+                time_stamp: None,
+            }),
+            9 => StorageLogMessage::AddOrder(AddOrder { order, event: None }),
+            10 => StorageLogMessage::RemoveOrder(RemoveOrder { order, event: None }),
+            11 => {
+                StorageLogMessage::AddAuthorizedOrderToRestaurant(AddAuthorizedOrderToRestaurant {
+                    restaurant_id: order.restaurant_id.clone(),
+                    order,
+                    time_stamp: None,
+                })
+            }
+            12 => StorageLogMessage::AddPendingOrderToRestaurant(AddPendingOrderToRestaurant {
+                restaurant_id: order.restaurant_id.clone(),
+                order,
+                time_stamp: None,
+            }),
+            13 => StorageLogMessage::RemoveAuthorizedOrderToRestaurant(
+                RemoveAuthorizedOrderToRestaurant {
+                    restaurant_id: order.restaurant_id.clone(),
+                    order,
+                    time_stamp: None,
+                },
+            ),
+            14 => {
+                StorageLogMessage::RemovePendingOrderToRestaurant(RemovePendingOrderToRestaurant {
+                    restaurant_id: order.restaurant_id.clone(),
+                    order,
+                    time_stamp: None,
+                })
+            }
+            15 => StorageLogMessage::SetCurrentOrderToDelivery(SetCurrentOrderToDelivery {
+                delivery_id,
+                order,
+                time_stamp: None,
+            }),
+            16 => StorageLogMessage::SetDeliveryToOrder(SetDeliveryToOrder {
+                order,
+                delivery_id,
+                event: None,
+            }),
+            17 => StorageLogMessage::SetOrderStatus(SetOrderStatus {
+                order,
+                order_status: ORDER_STATUSES[n as usize].clone(),
+                event: None,
+            }),
+            18 => StorageLogMessage::SetOrderExpectedTime(SetOrderExpectedTime {
+                order_id,
+                expected_time: n * 1000,
+                time_stamp: None,
+            }),
+            19 => StorageLogMessage::SetOrderPreparationTime(SetOrderPreparationTime {
+                order_id,
+                preparation_time: n * 1000,
+                time_stamp: None,
+            }),
+            20 => StorageLogMessage::InsertAcceptedDelivery(InsertAcceptedDelivery {
+                order_id,
+                delivery_id,
+                epoch: n,
+            }),
+            21 => {
+                StorageLogMessage::RemoveAcceptedDeliveries(RemoveAcceptedDeliveries { order_id })
+            }
+            22 => StorageLogMessage::QueueOrderUpdate(QueueOrderUpdate {
+                update: NotifyOrderUpdated {
+                    peer_id: order.client_id.clone(),
+                    order,
+                    update_id: 0,
+                    request_id: 0,
+                    decline: None,
+                },
+            }),
+            23 => StorageLogMessage::AddDeliveryEarning(AddDeliveryEarning {
+                delivery_id,
+                order,
+                earning: None,
+            }),
+            24 => StorageLogMessage::SetEarningPaidOut(SetEarningPaidOut {
+                delivery_id,
+                order_id,
+                time_stamp: None,
+            }),
+            25 => StorageLogMessage::RecordElection(RecordElection {
+                record: ElectionRecord {
+                    leader_id: format!("server_{}", n),
+                    epoch: n,
+                    at_millis: n * 1000,
+                },
+            }),
+            26 => StorageLogMessage::AddCancellationFee(AddCancellationFee {
+                restaurant_id,
+                fee: CancellationFee {
+                    order_id,
+                    stage: OrderStatus::Preparing,
+                    price: 1000,
+                    fee: 500,
+                },
+                time_stamp: None,
+            }),
+            27 => StorageLogMessage::AddPromoRedemption(AddPromoRedemption {
+                redemption: PromoRedemption {
+                    code: format!("PROMO{}", n),
+                    client_id,
+                    order_id,
+                    discount: 100,
+                    time_stamp: HlcTimestamp::default(),
+                },
+                time_stamp: None,
+            }),
+            28 => StorageLogMessage::SetRestaurantOpen(SetRestaurantOpen {
+                restaurant_id,
+                open: n.is_multiple_of(2),
+                time_stamp: None,
+            }),
+            29 => StorageLogMessage::SetUserOffline(SetUserOffline {
+                user_id: if n.is_multiple_of(2) {
+                    delivery_id
+                } else {
+                    restaurant_id
+                },
+                time_stamp: None,
+            }),
+            // Como quedan en el log: con el hash y sin el secreto
+            30 => StorageLogMessage::ClaimUserId(ClaimUserId {
+                user_id: client_id,
+                secret: None,
+                tenant_id: TenantId::default(),
+                secret_hash: Some(SecretHash {
+                    salt: format!("salt_{}", n),
+                    hash: format!("hash_{}", n),
+                }),
+            }),
+            31 => StorageLogMessage::SetRestaurantTags(SetRestaurantTags {
+                restaurant_id,
+                tags: vec![format!("tag_{}", n % 3)],
+                time_stamp: None,
+            }),
+            32 => StorageLogMessage::AddOutboxPayment(AddOutboxPayment {
+                order,
+                time_stamp: None,
+            }),
+            33 => StorageLogMessage::CompleteOutboxPayment(CompleteOutboxPayment {
+                order,
+                time_stamp: None,
+            }),
+            34 => StorageLogMessage::TouchUsers(TouchUsers {
+                user_ids: vec![client_id, delivery_id],
+                time_stamp: None,
+            }),
+            35 => StorageLogMessage::ClaimOrderRequest(ClaimOrderRequest {
+                client_id,
+                request: OrderRequest {
+                    request_id: n + 1,
+                    order_id,
+                },
+            }),
+            _ => StorageLogMessage::AckOrderUpdates(AckOrderUpdates {
+                user_id: client_id,
+                update_id: n,
+            }),
+        }
+    }
+
+    fn storage_messages() -> impl Strategy<Value = Vec<StorageLogMessage>> {
+        let message = (
+            0..37u8,
+            0..CLIENTS.len(),
+            0..RESTAURANTS.len(),
+            0..DELIVERIES.len(),
+            1..=ORDERS,
+            0..4u64,
+        )
+            .prop_map(|(kind, client, restaurant, delivery, order_id, n)| {
+                log_message(kind, client, restaurant, delivery, order_id, n)
+            });
+        prop::collection::vec(message, 0..80)
+    }
+
+    /// Applies `messages` in order.
+    fn apply(
+        storage: &mut Storage,
+        ctx: &mut Context<Storage>,
+        messages: impl IntoIterator<Item = StorageLogMessage>,
+    ) {
+        for message in messages {
+            storage.handle(message, ctx);
+        }
+    }
+
+    /// Returns the log of `storage`, oldest first, as it is replicated to the followers.
+    fn logged(storage: &Storage) -> Vec<StorageLogMessage> {
+        let mut entries: Vec<_> = storage.storage_updates.iter().collect();
+        entries.sort_unstable_by_key(|(index, _)| **index);
+        entries
+            .into_iter()
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+
+    /// Returns the state of `storage` in a form that can be compared, leaving out the
+    /// index of its log.
+    fn state(storage: &Storage) -> serde_json::Value {
+        let mut state = serde_json::to_value(storage.snapshot()).unwrap();
+        state["next_log_id"] = serde_json::Value::Null;
+        // Los conjuntos de órdenes no tienen un orden fijo
+        if let Some(restaurants) = state["restaurants"].as_object_mut() {
+            for restaurant in restaurants.values_mut() {
+                for set in ["authorized_orders", "pending_orders"] {
+                    if let Some(orders) = restaurant[set].as_array_mut() {
+                        orders.sort_unstable_by_key(|order| order["order_id"].as_u64());
+                    }
+                }
+            }
+        }
+        state
+    }
+
+    /// Checks that every order referenced by a user or by an accepted delivery exists,
+    /// and that no delivery accepted an order assigned to another one.
+    fn check_invariants(storage: &Storage) -> Result<(), TestCaseError> {
+        for client in storage.clients.values() {
+            if let Some(order) = &client.client_order {
+                prop_assert!(
+                    storage.orders.contains_key(&order.order_id),
+                    "Client {} references the missing order {}",
+                    client.client_id,
+                    order.order_id
+                );
+            }
+        }
+        for restaurant in storage.restaurants.values() {
+            for order in restaurant
+                .authorized_orders
+                .iter()
+                .chain(&restaurant.pending_orders)
+            {
+                prop_assert!(
+                    storage.orders.contains_key(&order.order_id),
+                    "Restaurant {} references the missing order {}",
+                    restaurant.restaurant_id,
+                    order.order_id
+                );
+            }
+        }
+        for delivery in storage.deliverys.values() {
+            if let Some(order) = &delivery.current_order {
+                prop_assert!(
+                    storage.orders.contains_key(&order.order_id),
+                    "Delivery {} references the missing order {}",
+                    delivery.delivery_id,
+                    order.order_id
+                );
+            }
+        }
+        for order_id in storage.accepted_deliveries.keys() {
+            let accepted = storage.accepted_deliveries.get_by_key(order_id);
+            let Some(order) = storage.orders.get(order_id) else {
+                return Err(TestCaseError::fail(format!(
+                    "The missing order {} was accepted by {:?}",
+                    order_id, accepted
+                )));
+            };
+            prop_assert!(
+                order.delivery_id.is_none() || order.delivery_id.as_ref() == accepted,
+                "Order {} is assigned to {:?} but was accepted by {:?}",
+                order_id,
+                order.delivery_id,
+                accepted
+            );
+        }
+        for order_id in storage.accepted_epochs.keys() {
+            prop_assert!(
+                storage.accepted_deliveries.contains_key(order_id),
+                "Order {} keeps the epoch of an acceptance it no longer has",
+                order_id
+            );
+        }
+        Ok(())
+    }
+
+    fn set_delivery_status(status: DeliveryStatus) -> StorageLogMessage {
+        StorageLogMessage::SetDeliveryStatus(SetDeliveryStatus {
+            delivery_id: DELIVERIES[0].to_string(),
+            delivery_status: status,
+            time_stamp: None,
+        })
+    }
+
+    #[test]
+    fn keeps_the_entries_changed_last_when_merging_snapshots() {
+        let (mut first, mut first_ctx) = new_storage();
+        // Un cliente, un delivery y un pedido del cliente
+        apply(
+            &mut first,
+            &mut first_ctx,
+            [
+                log_message(0, 1, 0, 0, 1, 0),
+                log_message(2, 0, 0, 0, 1, 0),
+                log_message(9, 0, 0, 0, 1, 0),
+            ],
+        );
+        let (mut second, mut second_ctx) = new_storage();
+        apply(&mut second, &mut second_ctx, logged(&first));
+
+        // Los dos actúan como líder a la vez y cambian las mismas entradas
+        first.handle(
+            set_delivery_status(DeliveryStatus::Reconnecting),
+            &mut first_ctx,
+        );
+        second.handle(
+            set_delivery_status(DeliveryStatus::Delivering),
+            &mut second_ctx,
+        );
+        first.handle(log_message(17, 0, 0, 0, 1, 1), &mut first_ctx);
+        let first_snapshot = first.snapshot();
+        first.apply_snapshot(second.snapshot());
+        second.apply_snapshot(first_snapshot);
+
+        for storage in [&first, &second] {
+            let delivery = storage.deliverys.get(&DELIVERIES[0].to_string()).unwrap();
+            assert_eq!(delivery.status, DeliveryStatus::Delivering);
+            assert_eq!(
+                storage.orders.get(&1).unwrap().status,
+                OrderStatus::Preparing
+            );
+            let client = storage.clients.get(&CLIENTS[1].to_string()).unwrap();
+            assert_eq!(
+                client.client_order.map(|order| order.status),
+                Some(OrderStatus::Preparing)
+            );
+        }
+        assert_eq!(state(&first), state(&second));
+    }
+
+    #[test]
+    fn a_removed_order_is_still_known_by_its_last_state() {
+        let (mut storage, mut ctx) = new_storage();
+        apply(
+            &mut storage,
+            &mut ctx,
+            [log_message(0, 1, 0, 0, 1, 0), log_message(9, 0, 0, 0, 1, 0)],
+        );
+        let mut cancelled = order(1);
+        cancelled.status = OrderStatus::Cancelled;
+        storage.handle(
+            RemoveOrder {
+                order: cancelled,
+                event: None,
+            },
+            &mut ctx,
+        );
+
+        assert!(storage.orders.get(&1).is_none());
+        let last_state = |storage: &mut Storage, ctx: &mut Context<Storage>| {
+            storage
+                .handle(GetLastKnownOrder { order_id: 1 }, ctx)
+                .0
+                .map(|order| order.status)
+        };
+        assert_eq!(
+            last_state(&mut storage, &mut ctx),
+            Some(OrderStatus::Cancelled)
+        );
+
+        // Un coordinador que se sincroniza con un snapshot también lo conoce
+        let (mut follower, mut follower_ctx) = new_storage();
+        follower.apply_snapshot(storage.snapshot());
+        assert_eq!(
+            last_state(&mut follower, &mut follower_ctx),
+            Some(OrderStatus::Cancelled)
+        );
+    }
+
+    #[test]
+    fn an_older_snapshot_does_not_bring_back_removed_entries() {
+        let (mut storage, mut ctx) = new_storage();
+        apply(
+            &mut storage,
+            &mut ctx,
+            [
+                log_message(0, 1, 0, 0, 1, 0),
+                log_message(2, 0, 0, 0, 1, 0),
+                log_message(9, 0, 0, 0, 1, 0),
+            ],
+        );
+        let before_removals = storage.snapshot();
+        apply(
+            &mut storage,
+            &mut ctx,
+            [
+                log_message(10, 0, 0, 0, 1, 0),
+                log_message(5, 0, 0, 0, 1, 0),
+            ],
+        );
+        // Como si la línea de tiempo del pedido ya se hubiera descartado
+        storage.order_events.remove(&1);
+        storage.removed_orders.remove(&1);
+
+        storage.merge_snapshot(before_removals);
+        assert!(storage.orders.get(&1).is_none());
+        assert!(storage.clients.get(&CLIENTS[1].to_string()).is_none());
+        assert!(storage.deliverys.get(&DELIVERIES[0].to_string()).is_none());
+        assert!(!storage.order_events.contains_key(&1));
+
+        // Las bajas también llegan a quien se sincroniza con un snapshot
+        let (mut follower, _) = new_storage();
+        follower.apply_snapshot(storage.snapshot());
+        assert_eq!(follower.tombstones, storage.tombstones);
+    }
+
+    #[test]
+    fn merging_snapshots_keeps_the_order_updates_of_both() {
+        let queue = |update_id: u64| QueueOrderUpdate {
+            update: NotifyOrderUpdated {
+                peer_id: CLIENTS[1].to_string(),
+                order: order(1),
+                update_id,
+                request_id: 0,
+                decline: None,
+            },
+        };
+        let (mut first, mut first_ctx) = new_storage();
+        first.handle(queue(1), &mut first_ctx);
+        let (mut second, mut second_ctx) = new_storage();
+        second.handle(queue(2), &mut second_ctx);
+        second.handle(queue(1), &mut second_ctx);
+
+        first.merge_snapshot(second.snapshot());
+        let update_ids: Vec<u64> = first.order_updates[CLIENTS[1]]
+            .iter()
+            .map(|update| update.update_id)
+            .collect();
+        assert_eq!(update_ids, [1, 2]);
+        assert_eq!(first.next_order_update_id, 3);
+    }
+
+    #[test]
+    fn a_partial_snapshot_only_brings_the_entities_asked_for() {
+        let (mut leader, mut leader_ctx) = new_storage();
+        apply(
+            &mut leader,
+            &mut leader_ctx,
+            [
+                log_message(0, 1, 0, 0, 1, 0),
+                log_message(2, 0, 0, 0, 1, 0),
+                log_message(9, 0, 0, 0, 1, 0),
+            ],
+        );
+        let (mut follower, _) = new_storage();
+        follower.apply_snapshot(leader.snapshot().subset(&[StorageEntity::Orders]));
+
+        assert!(follower.orders.contains_key(&1));
+        assert!(follower.clients.is_empty());
+        assert!(follower.deliverys.is_empty());
+        assert_eq!(follower.next_log_id, leader.next_log_id);
+    }
+
+    #[test]
+    fn followers_that_diverged_converge_by_exchanging_what_differs() {
+        let (mut first, mut first_ctx) = new_storage();
+        apply(
+            &mut first,
+            &mut first_ctx,
+            [
+                log_message(0, 1, 0, 0, 1, 0),
+                log_message(2, 0, 0, 0, 1, 0),
+                log_message(9, 0, 0, 0, 1, 0),
+            ],
+        );
+        let (mut second, mut second_ctx) = new_storage();
+        apply(&mut second, &mut second_ctx, logged(&first));
+        assert_eq!(first.digest(), second.digest());
+
+        // Un cambio que sólo le llegó al primero
+        first.handle(
+            set_delivery_status(DeliveryStatus::Reconnecting),
+            &mut first_ctx,
+        );
+        second.next_log_id = first.next_log_id;
+        let differing = first.digest().differing(&second.digest());
+        assert_eq!(differing, [StorageEntity::Deliveries]);
+
+        let first_entries = first.snapshot().subset(&differing);
+        first.merge_snapshot(second.snapshot().subset(&differing));
+        second.merge_snapshot(first_entries);
+        assert_eq!(first.digest(), second.digest());
+        assert_eq!(
+            second
+                .deliverys
+                .get(&DELIVERIES[0].to_string())
+                .unwrap()
+                .status,
+            DeliveryStatus::Reconnecting
+        );
+    }
+
+    #[test]
+    fn the_payment_outbox_keeps_the_bills_not_confirmed_after_a_leader_change() {
+        let (mut leader, mut leader_ctx) = new_storage();
+        apply(
+            &mut leader,
+            &mut leader_ctx,
+            [
+                log_message(32, 0, 0, 0, 1, 0),
+                log_message(32, 0, 0, 0, 2, 0),
+                log_message(33, 0, 0, 0, 1, 0),
+                // Un cobro repetido no vuelve a quedar pendiente
+                log_message(32, 0, 0, 0, 1, 0),
+            ],
+        );
+        let (mut follower, mut follower_ctx) = new_storage();
+        apply(&mut follower, &mut follower_ctx, logged(&leader));
+
+        let MessageResult(pending) = follower.handle(GetPendingPayments, &mut follower_ctx);
+        assert_eq!(
+            pending
+                .iter()
+                .map(|order| order.order_id)
+                .collect::<Vec<_>>(),
+            [2]
+        );
+        assert_eq!(state(&leader), state(&follower));
+    }
+
+    #[test]
+    fn a_payment_is_completed_only_once() {
+        let (mut storage, mut ctx) = new_storage();
+        apply(&mut storage, &mut ctx, [log_message(32, 0, 0, 0, 1, 0)]);
+        let confirm = |order_id| CompleteOutboxPayment {
+            order: order(order_id),
+            time_stamp: None,
+        };
+
+        // El gateway confirma otra vez un cobro repetido: no se finaliza ni se loguea dos veces
+        assert!(storage.handle(confirm(1), &mut ctx));
+        assert!(!storage.handle(confirm(1), &mut ctx));
+        assert_eq!(storage.storage_updates.len(), 2);
+
+        // Un pedido que no está en el outbox no queda completado
+        assert!(!storage.handle(confirm(2), &mut ctx));
+        assert!(!storage.payment_outbox.contains_key(&2));
+        assert_eq!(storage.storage_updates.len(), 2);
+    }
+
+    #[test]
+    fn only_the_most_recent_completed_payments_are_kept() {
+        let (mut storage, mut ctx) = new_storage();
+        let orders = 1..=MAX_COMPLETED_PAYMENTS as u64 + 1;
+        for order_id in orders.clone() {
+            apply(
+                &mut storage,
+                &mut ctx,
+                [log_message(32, 0, 0, 0, order_id, 0)],
+            );
+        }
+        for order_id in orders {
+            assert!(storage.handle(
+                CompleteOutboxPayment {
+                    order: order(order_id),
+                    time_stamp: None,
+                },
+                &mut ctx
+            ));
+        }
+        assert_eq!(storage.payment_outbox.len(), MAX_COMPLETED_PAYMENTS);
+        assert!(!storage.payment_outbox.contains_key(&1));
+        // Una confirmación tardía del cobro descartado tampoco lo finaliza otra vez
+        assert!(!storage.handle(
+            CompleteOutboxPayment {
+                order: order(1),
+                time_stamp: None,
+            },
+            &mut ctx
+        ));
+    }
+
+    #[test]
+    fn only_the_users_not_seen_for_a_while_and_without_active_orders_are_stale() {
+        let (mut leader, mut leader_ctx) = new_storage();
+        apply(
+            &mut leader,
+            &mut leader_ctx,
+            [
+                log_message(0, 0, 0, 0, 1, 0),
+                log_message(0, 1, 0, 0, 1, 0),
+                log_message(0, 2, 0, 0, 1, 0),
+                log_message(2, 0, 0, 0, 1, 0),
+                log_message(2, 0, 0, 1, 1, 0),
+                // El pedido 1 es de client_1, que no se puede desalojar mientras siga activo
+                log_message(9, 0, 0, 0, 1, 0),
+                log_message(34, 2, 0, 1, 1, 0),
+            ],
+        );
+        let (mut follower, mut follower_ctx) = new_storage();
+        apply(&mut follower, &mut follower_ctx, logged(&leader));
+
+        // Los usuarios registrados con la hora por defecto no se vieron desde entonces
+        let seen_before = HlcTimestamp {
+            physical_millis: 1,
+            logical: 0,
+        };
+        assert_eq!(leader.stale_users(seen_before), ["client_0", "delivery_0"]);
+        assert_eq!(
+            follower.stale_users(seen_before),
+            ["client_0", "delivery_0"]
+        );
+        assert_eq!(state(&leader), state(&follower));
+
+        leader.handle(
+            RemoveUser {
+                user_id: "client_0".to_string(),
+            },
+            &mut leader_ctx,
+        );
+        assert!(!leader.last_seen.contains_key("client_0"));
+        assert_eq!(leader.stale_users(seen_before), ["delivery_0"]);
+    }
+
+    #[test]
+    fn replicated_writes_keep_the_index_of_the_leader() {
+        let (mut leader, mut leader_ctx) = new_storage();
+        apply(
+            &mut leader,
+            &mut leader_ctx,
+            [log_message(0, 0, 0, 0, 1, 0), log_message(0, 1, 0, 0, 1, 0)],
+        );
+        let write = leader
+            .handle(
+                AppendWrite {
+                    update: log_message(2, 0, 0, 0, 1, 0),
+                },
+                &mut leader_ctx,
+            )
+            .unwrap();
+        assert_eq!(write.index, 3);
+
+        // El seguidor todavía no recibió las dos primeras escrituras
+        let (mut follower, mut follower_ctx) = new_storage();
+        for _ in 0..2 {
+            follower.handle(
+                ApplyReplicatedWrite {
+                    index: write.index,
+                    update: write.update.clone(),
+                },
+                &mut follower_ctx,
+            );
+        }
+        assert_eq!(follower.storage_updates.len(), 1);
+        assert_eq!(
+            follower.storage_updates.get(&write.index).map(|m| m.kind()),
+            Some(write.update.kind())
+        );
+        assert_eq!(follower.next_log_id, write.index + 1);
+
+        // Las que faltaban llegan después con sus índices y no pisan la replicada
+        let missing: Vec<(u64, StorageLogMessage)> = leader
+            .storage_updates
+            .iter()
+            .map(|(index, update)| (*index, update.clone()))
             .collect();
-        MessageResult(available_deliveries)
+        follower.handle(
+            ApplyStorageUpdates {
+                updates: missing,
+                is_leader: false,
+            },
+            &mut follower_ctx,
+        );
+        assert_eq!(logged(&follower).len(), 3);
+        assert_eq!(state(&follower), state(&leader));
+        assert_eq!(follower.next_log_id, leader.next_log_id);
+    }
+
+    #[test]
+    fn updates_applied_at_an_index_do_not_move_the_next_local_one() {
+        let (mut leader, mut leader_ctx) = new_storage();
+        apply(
+            &mut leader,
+            &mut leader_ctx,
+            [
+                log_message(0, 0, 0, 0, 1, 0),
+                log_message(2, 0, 0, 0, 1, 0),
+                log_message(23, 0, 0, 0, 1, 0),
+            ],
+        );
+        let earning = leader.storage_updates[&3].clone();
+
+        // Llegan las dos últimas, con un hueco antes
+        let (mut follower, mut follower_ctx) = new_storage();
+        follower.apply_at(2, leader.storage_updates[&2].clone(), &mut follower_ctx);
+        follower.apply_at(3, earning.clone(), &mut follower_ctx);
+        assert_eq!(follower.next_log_id, 4);
+
+        // Una ganancia repetida se ignora y su índice no queda para la siguiente escritura
+        follower.apply_at(9, earning, &mut follower_ctx);
+        assert!(!follower.storage_updates.contains_key(&9));
+        follower.handle(log_message(0, 1, 0, 0, 1, 0), &mut follower_ctx);
+        let mut indices: Vec<u64> = follower.storage_updates.keys().copied().collect();
+        indices.sort_unstable();
+        assert_eq!(indices, [2, 3, 4]);
+    }
+
+    #[test]
+    fn compaction_drops_the_entries_every_follower_applied() {
+        let (mut storage, mut ctx) = new_storage();
+        apply(
+            &mut storage,
+            &mut ctx,
+            (0..3).map(|client| log_message(0, client, 0, 0, 1, 0)),
+        );
+
+        storage.handle(CompactLog { up_to: 3 }, &mut ctx);
+        assert_eq!(storage.storage_updates.keys().collect::<Vec<_>>(), [&3]);
+        assert_eq!(storage.min_persistent_log_index, 3);
+        assert_eq!(storage.compaction.confirmed_up_to, 3);
+        assert_eq!(storage.compaction.dropped_entries, 2);
+
+        // Nunca se compacta más allá de lo que se escribió
+        storage.handle(CompactLog { up_to: 100 }, &mut ctx);
+        assert!(storage.storage_updates.is_empty());
+        assert_eq!(storage.min_persistent_log_index, storage.next_log_id);
+        assert_eq!(storage.compaction.compactions, 2);
+    }
+
+    #[test]
+    fn replaying_the_log_keeps_the_persisted_indexes() {
+        let dir = std::env::temp_dir().join(format!("storage_replay_{}", rand::random::<u64>()));
+        let mut log_file = StorageLogFile::open(&dir, "server_0").unwrap();
+        log_file.append(5, &log_message(0, 0, 0, 0, 1, 0)).unwrap();
+        log_file.append(8, &log_message(2, 0, 0, 0, 1, 0)).unwrap();
+        drop(log_file);
+
+        let mut storage = Storage::new(
+            Context::<Coordinator>::new().address(),
+            "server_0".to_string(),
+            Some(StorageLogFile::open(&dir, "server_0").unwrap()),
+            StorageReadView::default(),
+        );
+        let mut ctx = Context::new();
+        storage.started(&mut ctx);
+        assert!(storage.restored_from_log);
+        let mut indices: Vec<u64> = storage.storage_updates.keys().copied().collect();
+        indices.sort_unstable();
+        assert_eq!(indices, [5, 8]);
+        // La próxima escritura sigue después del último índice persistido
+        assert_eq!(storage.next_log_id, 9);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_repeated_earning_is_not_logged_again() {
+        let (mut storage, mut ctx) = new_storage();
+        apply(
+            &mut storage,
+            &mut ctx,
+            [log_message(0, 0, 0, 0, 1, 0), log_message(2, 0, 0, 0, 1, 0)],
+        );
+        let earning = || AddDeliveryEarning {
+            delivery_id: DELIVERIES[0].to_string(),
+            order: order(1),
+            earning: None,
+        };
+
+        assert!(storage.handle(earning(), &mut ctx).0.is_some());
+        assert!(storage.handle(earning(), &mut ctx).0.is_none());
+        assert_eq!(storage.delivery_earnings[DELIVERIES[0]].len(), 1);
+        assert_eq!(storage.storage_updates.len(), 3);
+    }
+
+    #[test]
+    fn a_user_id_cannot_be_claimed_from_another_tenant() {
+        let (mut storage, mut ctx) = new_storage();
+        apply(&mut storage, &mut ctx, [log_message(0, 0, 0, 0, 1, 0)]);
+        let claim = |tenant_id: TenantId| ClaimUserId {
+            user_id: CLIENTS[0].to_string(),
+            secret: Some("secret".to_string()),
+            tenant_id,
+            secret_hash: None,
+        };
+
+        assert!(!storage.handle(claim(TenantId::new("staging")), &mut ctx));
+        assert!(!storage.user_secrets.contains_key(CLIENTS[0]));
+        assert!(storage.handle(claim(TenantId::default()), &mut ctx));
+    }
+
+    #[test]
+    fn user_secrets_are_only_kept_hashed() {
+        let (mut storage, mut ctx) = new_storage();
+        let claim = |secret: &str| ClaimUserId {
+            user_id: CLIENTS[0].to_string(),
+            secret: Some(secret.to_string()),
+            tenant_id: TenantId::default(),
+            secret_hash: None,
+        };
+
+        assert!(storage.handle(claim("s3cret"), &mut ctx));
+        assert!(storage.handle(claim("s3cret"), &mut ctx));
+        assert!(!storage.handle(claim("other"), &mut ctx));
+
+        let log = serde_json::to_string(&logged(&storage)).unwrap();
+        let snapshot = serde_json::to_string(&storage.snapshot()).unwrap();
+        assert!(!log.contains("s3cret"));
+        assert!(!snapshot.contains("s3cret"));
+
+        // Al reaplicar el log queda el mismo hash, con el mismo salt
+        let (mut replica, mut replica_ctx) = new_storage();
+        apply(&mut replica, &mut replica_ctx, logged(&storage));
+        assert_eq!(replica.user_secrets, storage.user_secrets);
+        assert!(replica.handle(claim("s3cret"), &mut replica_ctx));
+    }
+
+    #[test]
+    fn a_resent_order_request_keeps_its_first_order_id() {
+        let (mut storage, mut ctx) = new_storage();
+        let claim = |request_id, order_id| ClaimOrderRequest {
+            client_id: CLIENTS[0].to_string(),
+            request: OrderRequest {
+                request_id,
+                order_id,
+            },
+        };
+
+        assert_eq!(storage.handle(claim(1, 10), &mut ctx), 10);
+        // El reenvío recibe el ID de la primera vez y no se vuelve a loguear
+        assert_eq!(storage.handle(claim(1, 11), &mut ctx), 10);
+        assert_eq!(storage.storage_updates.len(), 1);
+
+        // Sólo se recuerdan los pedidos más recientes de cada cliente
+        for request_id in 2..=MAX_ORDER_REQUESTS_PER_CLIENT as u64 + 1 {
+            storage.handle(claim(request_id, 10 + request_id), &mut ctx);
+        }
+        assert_eq!(
+            storage.order_requests[CLIENTS[0]].len(),
+            MAX_ORDER_REQUESTS_PER_CLIENT
+        );
+        assert_eq!(storage.handle(claim(1, 30), &mut ctx), 30);
+    }
+
+    #[test]
+    fn the_integrity_check_reports_the_references_that_do_not_hold() {
+        let (mut storage, mut ctx) = new_storage();
+        apply(
+            &mut storage,
+            &mut ctx,
+            [
+                log_message(0, 1, 0, 0, 1, 0),
+                log_message(1, 0, 1, 0, 1, 0),
+                log_message(9, 0, 0, 0, 1, 0),
+                log_message(9, 0, 0, 0, 2, 0),
+            ],
+        );
+        let order_2 = [
+            StorageViolation::MissingClient {
+                order_id: 2,
+                client_id: CLIENTS[2].to_string(),
+            },
+            StorageViolation::MissingRestaurant {
+                order_id: 2,
+                restaurant_id: RESTAURANTS[0].to_string(),
+            },
+        ];
+        assert_eq!(storage.violations(), order_2);
+
+        // Entradas que sólo quedarían así por un bug o un disco corrupto
+        storage.clients.remove(&CLIENTS[1].to_string());
+        storage.orders.update(&1, |order| {
+            order.delivery_id = Some(DELIVERIES[0].to_string());
+        });
+        storage
+            .accepted_deliveries
+            .insert(1, DELIVERIES[1].to_string());
+        storage
+            .accepted_deliveries
+            .insert(3, DELIVERIES[2].to_string());
+        storage
+            .restaurants
+            .update(&RESTAURANTS[1].to_string(), |restaurant| {
+                restaurant.pending_orders.insert(order(1));
+                restaurant.authorized_orders.insert(order(1));
+            });
+
+        let violations = storage.violations();
+        assert_eq!(violations[3..5], order_2);
+        assert_eq!(
+            [&violations[..3], &violations[5..]].concat(),
+            [
+                StorageViolation::MissingClient {
+                    order_id: 1,
+                    client_id: CLIENTS[1].to_string(),
+                },
+                StorageViolation::PendingAndAuthorized {
+                    order_id: 1,
+                    restaurant_id: RESTAURANTS[1].to_string(),
+                },
+                StorageViolation::MissingDelivery {
+                    order_id: 1,
+                    delivery_id: DELIVERIES[0].to_string(),
+                },
+                StorageViolation::AcceptedByAnotherDelivery {
+                    order_id: 1,
+                    accepted_by: DELIVERIES[1].to_string(),
+                    assigned_to: Some(DELIVERIES[0].to_string()),
+                },
+                StorageViolation::AcceptedMissingOrder {
+                    order_id: 3,
+                    delivery_id: DELIVERIES[2].to_string(),
+                },
+            ]
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn every_reference_stays_consistent(messages in storage_messages()) {
+            let (mut storage, mut ctx) = new_storage();
+            for message in messages {
+                storage.handle(message, &mut ctx);
+                check_invariants(&storage)?;
+            }
+        }
+
+        #[test]
+        fn replaying_the_log_rebuilds_the_same_state(messages in storage_messages()) {
+            let (mut storage, mut ctx) = new_storage();
+            apply(&mut storage, &mut ctx, messages);
+
+            let (mut replica, mut replica_ctx) = new_storage();
+            apply(&mut replica, &mut replica_ctx, logged(&storage));
+            prop_assert_eq!(state(&replica), state(&storage));
+        }
+
+        #[test]
+        fn applying_every_change_twice_changes_nothing(messages in storage_messages()) {
+            let (mut storage, mut ctx) = new_storage();
+            apply(&mut storage, &mut ctx, messages);
+
+            // Un cambio llega dos veces si lo aplica una escritura con quórum y el log
+            let twice = logged(&storage)
+                .into_iter()
+                .flat_map(|message| [message.clone(), message]);
+            let (mut replica, mut replica_ctx) = new_storage();
+            apply(&mut replica, &mut replica_ctx, twice);
+            prop_assert_eq!(state(&replica), state(&storage));
+        }
     }
 }
@@ -0,0 +1,383 @@
+use common::types::dtos::{ClientDTO, DeliveryDTO, OrderDTO, RestaurantDTO};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::io;
+use std::path::Path;
+
+/// Environment variable used to select where the `Storage` keeps its entities.
+pub const STORAGE_BACKEND_ENV_VAR: &str = "STORAGE_BACKEND";
+
+/// Where the `Storage` keeps the clients, restaurants, deliveries and orders.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StorageBackendKind {
+    /// In `HashMap`s (the default).
+    #[default]
+    Memory,
+    /// In a sled database on disk, so that large simulations are not bound by the RAM.
+    /// Requires the `sled` feature.
+    Sled,
+}
+
+impl StorageBackendKind {
+    /// Reads the backend from [`STORAGE_BACKEND_ENV_VAR`] (`memory` or `sled`), defaulting
+    /// to memory.
+    ///
+    /// Falls back to memory if sled is requested but the `sled` feature is disabled.
+    pub fn from_env() -> Self {
+        match std::env::var(STORAGE_BACKEND_ENV_VAR) {
+            Ok(value) if value.trim().eq_ignore_ascii_case("sled") => {
+                if cfg!(feature = "sled") {
+                    StorageBackendKind::Sled
+                } else {
+                    println!(
+                        "[Storage] sled backend requested but the `sled` feature is disabled, using memory"
+                    );
+                    StorageBackendKind::Memory
+                }
+            }
+            _ => StorageBackendKind::Memory,
+        }
+    }
+}
+
+/// The key of an entry of a [`StorageBackend`], which backends on disk store as bytes.
+pub trait BackendKey: Clone + Eq + Hash + Send + 'static {
+    /// Returns the bytes the key is stored as.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Returns the key stored as `bytes`, or `None` if they are not a valid key.
+    fn from_bytes(bytes: &[u8]) -> Option<Self>;
+}
+
+impl BackendKey for String {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+impl BackendKey for u64 {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_be_bytes().to_vec()
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(u64::from_be_bytes(bytes.try_into().ok()?))
+    }
+}
+
+/// Where the entries of one kind of entity are kept.
+///
+/// ## Purpose
+/// Lets the `Storage` keep its entities in memory or on disk without knowing which: it only
+/// reads, writes and visits whole entries.
+pub trait StorageBackend<K, V>: Send {
+    /// Returns a copy of the entry of `key`, if any.
+    fn get(&self, key: &K) -> Option<V>;
+
+    /// Stores `value` as the entry of `key`, replacing the previous one.
+    fn put(&mut self, key: K, value: V);
+
+    /// Removes the entry of `key`, returning it if there was one.
+    fn remove(&mut self, key: &K) -> Option<V>;
+
+    /// Calls `visit` with every entry, in no particular order.
+    fn scan(&self, visit: &mut dyn FnMut(&K, &V));
+
+    /// Returns the number of entries.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if there are no entries.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// A [`StorageBackend`] that keeps the entries in a `HashMap`.
+#[derive(Debug)]
+pub struct MemoryBackend<K, V> {
+    entries: HashMap<K, V>,
+}
+
+impl<K, V> Default for MemoryBackend<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<K: BackendKey, V: Clone + Send> StorageBackend<K, V> for MemoryBackend<K, V> {
+    fn get(&self, key: &K) -> Option<V> {
+        self.entries.get(key).cloned()
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        self.entries.insert(key, value);
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries.remove(key)
+    }
+
+    fn scan(&self, visit: &mut dyn FnMut(&K, &V)) {
+        for (key, value) in &self.entries {
+            visit(key, value);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+}
+
+/// A [`StorageBackend`] that keeps the entries as JSON in a tree of a sled database.
+#[cfg(feature = "sled")]
+pub struct SledBackend<K, V> {
+    tree: sled::Tree,
+    entries: std::marker::PhantomData<fn() -> (K, V)>,
+}
+
+#[cfg(feature = "sled")]
+impl<K, V> SledBackend<K, V> {
+    /// Opens the tree `name` of `db`, dropping the entries left by a previous run: the
+    /// storage log rebuilds them at boot.
+    pub fn open(db: &sled::Db, name: &str) -> io::Result<Self> {
+        let tree = db.open_tree(name).map_err(io::Error::other)?;
+        tree.clear().map_err(io::Error::other)?;
+        Ok(Self {
+            tree,
+            entries: std::marker::PhantomData,
+        })
+    }
+}
+
+#[cfg(feature = "sled")]
+impl<K, V> StorageBackend<K, V> for SledBackend<K, V>
+where
+    K: BackendKey,
+    V: serde::Serialize + serde::de::DeserializeOwned,
+{
+    fn get(&self, key: &K) -> Option<V> {
+        let bytes = self.tree.get(key.to_bytes()).ok()??;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    fn put(&mut self, key: K, value: V) {
+        let stored = serde_json::to_vec(&value)
+            .map_err(io::Error::other)
+            .and_then(|bytes| {
+                self.tree
+                    .insert(key.to_bytes(), bytes)
+                    .map_err(io::Error::other)
+            });
+        if let Err(e) = stored {
+            eprintln!("[Storage] Error writing to the sled backend: {}", e);
+        }
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let bytes = self.tree.remove(key.to_bytes()).ok()??;
+        serde_json::from_slice(&bytes).ok()
+    }
+
+    fn scan(&self, visit: &mut dyn FnMut(&K, &V)) {
+        for (key, bytes) in self.tree.iter().flatten() {
+            if let (Some(key), Ok(value)) = (K::from_bytes(&key), serde_json::from_slice(&bytes)) {
+                visit(&key, &value);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.tree.len()
+    }
+}
+
+/// The entries of one kind of entity, kept in a [`StorageBackend`].
+///
+/// Entries are read as copies, so changing one means writing it back: [`EntityTable::update`]
+/// does both.
+pub struct EntityTable<K, V> {
+    backend: Box<dyn StorageBackend<K, V>>,
+}
+
+impl<K: BackendKey, V: Clone + Send + 'static> Default for EntityTable<K, V> {
+    fn default() -> Self {
+        Self::new(Box::new(MemoryBackend::default()))
+    }
+}
+
+impl<K: BackendKey, V: Clone> EntityTable<K, V> {
+    /// Creates a table that keeps its entries in `backend`.
+    pub fn new(backend: Box<dyn StorageBackend<K, V>>) -> Self {
+        Self { backend }
+    }
+
+    /// Returns a copy of the entry of `key`, if any.
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.backend.get(key)
+    }
+
+    /// Returns `true` if there is an entry for `key`.
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.backend.get(key).is_some()
+    }
+
+    /// Stores `value` as the entry of `key`.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.backend.put(key, value);
+    }
+
+    /// Removes the entry of `key`, returning it if there was one.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.backend.remove(key)
+    }
+
+    /// Changes the entry of `key` with `change` and writes it back.
+    ///
+    /// ## Returns
+    /// What `change` returned, or `None` if there is no entry for `key`.
+    pub fn update<R>(&mut self, key: &K, change: impl FnOnce(&mut V) -> R) -> Option<R> {
+        let mut value = self.backend.get(key)?;
+        let result = change(&mut value);
+        self.backend.put(key.clone(), value);
+        Some(result)
+    }
+
+    /// Changes every entry with `change` and writes them back.
+    pub fn update_all(&mut self, mut change: impl FnMut(&mut V)) {
+        for (key, mut value) in self.entries() {
+            change(&mut value);
+            self.backend.put(key, value);
+        }
+    }
+
+    /// Calls `visit` with every entry, in no particular order.
+    pub fn for_each(&self, mut visit: impl FnMut(&K, &V)) {
+        self.backend.scan(&mut visit);
+    }
+
+    /// Returns a copy of every entry, in no particular order.
+    pub fn entries(&self) -> Vec<(K, V)> {
+        let mut entries = Vec::with_capacity(self.backend.len());
+        self.for_each(|key, value| entries.push((key.clone(), value.clone())));
+        entries
+    }
+
+    /// Returns a copy of every value, in no particular order.
+    pub fn values(&self) -> Vec<V> {
+        let mut values = Vec::with_capacity(self.backend.len());
+        self.for_each(|_, value| values.push(value.clone()));
+        values
+    }
+
+    /// Returns a copy of every entry, by key.
+    pub fn to_map(&self) -> HashMap<K, V> {
+        self.entries().into_iter().collect()
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.backend.len()
+    }
+
+    /// Returns `true` if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.backend.is_empty()
+    }
+}
+
+/// The tables of the entities kept by a `Storage`.
+#[derive(Default)]
+pub struct EntityTables {
+    pub clients: EntityTable<String, ClientDTO>,
+    pub restaurants: EntityTable<String, RestaurantDTO>,
+    pub deliverys: EntityTable<String, DeliveryDTO>,
+    pub orders: EntityTable<u64, OrderDTO>,
+}
+
+impl EntityTables {
+    /// Opens the tables of the coordinator `coordinator_id` in the backend `kind`.
+    ///
+    /// A sled database is kept in `dir` (the directory of the storage log) or, if it is
+    /// `None`, in a temporary directory removed when the coordinator stops.
+    pub fn open(
+        kind: StorageBackendKind,
+        dir: Option<&Path>,
+        coordinator_id: &str,
+    ) -> io::Result<Self> {
+        match kind {
+            StorageBackendKind::Memory => Ok(Self::default()),
+            #[cfg(feature = "sled")]
+            StorageBackendKind::Sled => {
+                let config = match dir {
+                    Some(dir) => {
+                        sled::Config::new().path(dir.join(format!("{}.sled", coordinator_id)))
+                    }
+                    None => sled::Config::new().temporary(true),
+                };
+                let db = config.open().map_err(io::Error::other)?;
+                Ok(Self {
+                    clients: EntityTable::new(Box::new(SledBackend::open(&db, "clients")?)),
+                    restaurants: EntityTable::new(Box::new(SledBackend::open(&db, "restaurants")?)),
+                    deliverys: EntityTable::new(Box::new(SledBackend::open(&db, "deliveries")?)),
+                    orders: EntityTable::new(Box::new(SledBackend::open(&db, "orders")?)),
+                })
+            }
+            #[cfg(not(feature = "sled"))]
+            StorageBackendKind::Sled => {
+                let _ = (dir, coordinator_id);
+                Err(io::Error::other("the `sled` feature is disabled"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_survive_the_round_trip_to_bytes() {
+        assert_eq!(u64::from_bytes(&42u64.to_bytes()), Some(42));
+        assert_eq!(u64::from_bytes(b"short"), None);
+        let key = "client_1".to_string();
+        assert_eq!(String::from_bytes(&key.to_bytes()), Some(key));
+    }
+
+    #[test]
+    fn updates_write_the_entry_back() {
+        let mut table: EntityTable<u64, String> = EntityTable::default();
+        table.insert(1, "pizza".to_string());
+        assert_eq!(table.update(&1, |dish| dish.push('s')), Some(()));
+        assert_eq!(table.update(&2, |dish| dish.push('s')), None);
+        assert_eq!(table.get(&1).as_deref(), Some("pizzas"));
+        table.update_all(|dish| dish.make_ascii_uppercase());
+        assert_eq!(table.values(), vec!["PIZZAS".to_string()]);
+        assert_eq!(table.remove(&1).as_deref(), Some("PIZZAS"));
+        assert!(table.is_empty());
+    }
+
+    #[cfg(feature = "sled")]
+    #[test]
+    fn the_sled_backend_keeps_the_entries_on_disk() {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let mut table: EntityTable<u64, String> =
+            EntityTable::new(Box::new(SledBackend::open(&db, "dishes").unwrap()));
+        table.insert(2, "empanada".to_string());
+        table.insert(1, "pizza".to_string());
+        table.update(&1, |dish| dish.push('s'));
+        assert_eq!(
+            table.entries(),
+            vec![(1, "pizzas".to_string()), (2, "empanada".to_string())]
+        );
+        // Lo que quedó de una ejecución anterior lo reconstruye el log
+        let reopened: EntityTable<u64, String> =
+            EntityTable::new(Box::new(SledBackend::open(&db, "dishes").unwrap()));
+        assert!(reopened.is_empty());
+    }
+}
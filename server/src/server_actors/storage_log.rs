@@ -0,0 +1,212 @@
+use common::constants::DEFAULT_STORAGE_LOG_DIR;
+use common::messages::internal_messages::StorageLogMessage;
+use common::types::dtos::Snapshot;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Environment variable with the directory where each coordinator persists its storage log.
+/// Persistence is disabled if it is not set or is `off`, and `on` uses
+/// [`DEFAULT_STORAGE_LOG_DIR`].
+pub const STORAGE_LOG_DIR_ENV_VAR: &str = "STORAGE_LOG_DIR";
+
+/// Returns the directory selected by [`STORAGE_LOG_DIR_ENV_VAR`], or `None` if persistence
+/// is disabled.
+pub fn dir_from_env() -> Option<PathBuf> {
+    parse_dir(std::env::var(STORAGE_LOG_DIR_ENV_VAR).ok().as_deref())
+}
+
+/// Returns the directory selected by the value of [`STORAGE_LOG_DIR_ENV_VAR`], if any.
+fn parse_dir(value: Option<&str>) -> Option<PathBuf> {
+    match value.map(str::trim) {
+        None | Some("") => None,
+        Some(value) if value.eq_ignore_ascii_case("off") => None,
+        Some(value) if value.eq_ignore_ascii_case("on") => {
+            Some(PathBuf::from(DEFAULT_STORAGE_LOG_DIR))
+        }
+        Some(value) => Some(PathBuf::from(value)),
+    }
+}
+
+/// A line of the storage log file.
+// Sólo la primera línea es un snapshot, y las líneas se leen de a una
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub enum LogRecord {
+    /// The whole storage state. Only the first line of the file can be a snapshot.
+    Snapshot(Snapshot),
+    /// An operation applied after the snapshot, with its index in the storage log.
+    Entry {
+        index: u64,
+        update: StorageLogMessage,
+    },
+}
+
+/// An append-only file with the operations applied to the `Storage` of a coordinator, one
+/// JSON [`LogRecord`] per line.
+///
+/// Replaying it at boot rebuilds the storage state, so a restarted coordinator only needs
+/// the operations it missed instead of a full snapshot.
+#[derive(Debug)]
+pub struct StorageLogFile {
+    /// Path of the file.
+    path: PathBuf,
+    /// The file, opened for appending.
+    file: File,
+}
+
+impl StorageLogFile {
+    /// Opens (or creates) the log file of `coordinator_id` inside `dir`.
+    pub fn open(dir: &Path, coordinator_id: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}.log", coordinator_id));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file })
+    }
+
+    /// Returns the path of the file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Reads every record of the file, in order.
+    ///
+    /// Reading stops at the first line that cannot be parsed: a crash in the middle of an
+    /// append leaves a truncated last line.
+    pub fn read_records(&self) -> io::Result<Vec<LogRecord>> {
+        let reader = BufReader::new(File::open(&self.path)?);
+        let mut records = Vec::new();
+        for line in reader.lines() {
+            match serde_json::from_str(&line?) {
+                Ok(record) => records.push(record),
+                Err(_) => break,
+            }
+        }
+        Ok(records)
+    }
+
+    /// Appends an operation to the file.
+    pub fn append(&mut self, index: u64, update: &StorageLogMessage) -> io::Result<()> {
+        let record = LogRecord::Entry {
+            index,
+            update: update.clone(),
+        };
+        self.write_record(&record)
+    }
+
+    /// Replaces the content of the file with `snapshot`, dropping every previous operation.
+    ///
+    /// The snapshot is written to a temporary file first, so a crash never leaves the log
+    /// empty.
+    pub fn truncate(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("log.tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        let line = serde_json::to_string(&LogRecord::Snapshot(snapshot.clone()))?;
+        writeln!(tmp, "{}", line)?;
+        tmp.sync_all()?;
+        fs::rename(&tmp_path, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+
+    /// Writes `record` as a new line.
+    fn write_record(&mut self, record: &LogRecord) -> io::Result<()> {
+        let line = serde_json::to_string(record)?;
+        writeln!(self.file, "{}", line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::bimap::BiMap;
+    use common::messages::internal_messages::RemoveClient;
+    use common::types::dtos::Tombstones;
+    use std::collections::HashMap;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("storage_log_{}_{}", name, rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn remove_client(id: &str) -> StorageLogMessage {
+        StorageLogMessage::RemoveClient(RemoveClient {
+            client_id: id.to_string(),
+            time_stamp: None,
+        })
+    }
+
+    fn empty_snapshot(next_log_id: u64) -> Snapshot {
+        Snapshot {
+            clients: HashMap::new(),
+            restaurants: HashMap::new(),
+            deliverys: HashMap::new(),
+            orders: HashMap::new(),
+            accepted_deliveries: BiMap::new(),
+            accepted_epochs: HashMap::new(),
+            order_events: HashMap::new(),
+            removed_orders: HashMap::new(),
+            tombstones: Tombstones::default(),
+            order_updates: HashMap::new(),
+            next_order_update_id: 0,
+            delivery_earnings: HashMap::new(),
+            elections: Vec::new(),
+            promo_redemptions: HashMap::new(),
+            user_secrets: HashMap::new(),
+            payment_outbox: HashMap::new(),
+            order_requests: HashMap::new(),
+            last_seen: HashMap::new(),
+            next_log_id,
+            min_persistent_log_index: 0,
+        }
+    }
+
+    #[test]
+    fn replays_appended_entries_after_the_snapshot() {
+        let dir = temp_dir("replay");
+        let mut log = StorageLogFile::open(&dir, "server_0").unwrap();
+        log.append(1, &remove_client("a")).unwrap();
+        log.truncate(&empty_snapshot(2)).unwrap();
+        log.append(2, &remove_client("b")).unwrap();
+        drop(log);
+
+        let records = StorageLogFile::open(&dir, "server_0")
+            .unwrap()
+            .read_records()
+            .unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(matches!(&records[0], LogRecord::Snapshot(s) if s.next_log_id == 2));
+        assert!(matches!(&records[1], LogRecord::Entry { index: 2, .. }));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn ignores_a_truncated_last_line() {
+        let dir = temp_dir("torn");
+        let mut log = StorageLogFile::open(&dir, "server_1").unwrap();
+        log.append(1, &remove_client("a")).unwrap();
+        write!(log.file, "{{\"Entry\":{{\"index\":2").unwrap();
+
+        let records = log.read_records().unwrap();
+        assert_eq!(records.len(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn persistence_is_opt_in() {
+        assert_eq!(parse_dir(None), None);
+        assert_eq!(parse_dir(Some(" ")), None);
+        assert_eq!(parse_dir(Some("OFF")), None);
+        assert_eq!(
+            parse_dir(Some("on")),
+            Some(PathBuf::from(DEFAULT_STORAGE_LOG_DIR))
+        );
+        assert_eq!(
+            parse_dir(Some(" /tmp/pedidos ")),
+            Some(PathBuf::from("/tmp/pedidos"))
+        );
+    }
+}
@@ -0,0 +1,237 @@
+use common::constants::NEARBY_RADIUS;
+use common::spatial_index::SpatialIndex;
+use common::types::delivery_status::DeliveryStatus;
+use common::types::dtos::DeliveryDTO;
+use common::types::restaurant_info::RestaurantInfo;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// The restaurants and deliveries of a [`StorageReadView`], indexed by position.
+#[derive(Debug)]
+struct ReadModel {
+    /// Position of each restaurant, by restaurant ID.
+    restaurants: HashMap<String, RestaurantInfo>,
+    /// IDs of the restaurants outside their opening hours.
+    closed_restaurants: HashSet<String>,
+    /// Each delivery, by delivery ID.
+    deliveries: HashMap<String, DeliveryDTO>,
+    /// Restaurant IDs indexed by position, for nearby searches.
+    restaurant_index: SpatialIndex<String>,
+    /// Delivery IDs indexed by position, for nearby searches.
+    delivery_index: SpatialIndex<String>,
+}
+
+impl Default for ReadModel {
+    fn default() -> Self {
+        Self {
+            restaurants: HashMap::new(),
+            closed_restaurants: HashSet::new(),
+            deliveries: HashMap::new(),
+            restaurant_index: SpatialIndex::new(NEARBY_RADIUS),
+            delivery_index: SpatialIndex::new(NEARBY_RADIUS),
+        }
+    }
+}
+
+/// A copy of the restaurants and deliveries of the [`Storage`](super::storage::Storage),
+/// shared with the services that search them.
+///
+/// The `Storage` updates the view every time it changes a restaurant or a delivery, and the
+/// nearby services read it directly instead of sending messages to the `Storage`, so their
+/// searches do not wait behind the log entries being applied, nor delay them.
+#[derive(Debug, Clone, Default)]
+pub struct StorageReadView {
+    /// The shared copy.
+    model: Arc<RwLock<ReadModel>>,
+}
+
+impl StorageReadView {
+    /// Locks the view for reading, even if a writer panicked while holding it.
+    fn read(&self) -> RwLockReadGuard<'_, ReadModel> {
+        self.model.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Locks the view for writing, even if a writer panicked while holding it.
+    fn write(&self) -> RwLockWriteGuard<'_, ReadModel> {
+        self.model.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Adds a restaurant to the view, or moves it.
+    pub(crate) fn put_restaurant(&self, restaurant: RestaurantInfo) {
+        let mut model = self.write();
+        model
+            .restaurant_index
+            .insert(restaurant.id.clone(), restaurant.position);
+        model.restaurants.insert(restaurant.id.clone(), restaurant);
+    }
+
+    /// Removes a restaurant from the view.
+    pub(crate) fn remove_restaurant(&self, restaurant_id: &str) {
+        let mut model = self.write();
+        model.restaurant_index.remove(&restaurant_id.to_string());
+        model.restaurants.remove(restaurant_id);
+        model.closed_restaurants.remove(restaurant_id);
+    }
+
+    /// Records whether a restaurant is within its opening hours.
+    pub(crate) fn set_restaurant_open(&self, restaurant_id: &str, open: bool) {
+        let mut model = self.write();
+        if open {
+            model.closed_restaurants.remove(restaurant_id);
+        } else {
+            model.closed_restaurants.insert(restaurant_id.to_string());
+        }
+    }
+
+    /// Adds a delivery to the view, or replaces it.
+    pub(crate) fn put_delivery(&self, delivery: DeliveryDTO) {
+        let mut model = self.write();
+        model
+            .delivery_index
+            .insert(delivery.delivery_id.clone(), delivery.delivery_position);
+        model
+            .deliveries
+            .insert(delivery.delivery_id.clone(), delivery);
+    }
+
+    /// Removes a delivery from the view.
+    pub(crate) fn remove_delivery(&self, delivery_id: &str) {
+        let mut model = self.write();
+        model.delivery_index.remove(&delivery_id.to_string());
+        model.deliveries.remove(delivery_id);
+    }
+
+    /// Replaces every delivery of the view.
+    pub(crate) fn replace_deliveries(&self, deliveries: impl IntoIterator<Item = DeliveryDTO>) {
+        let mut model = self.write();
+        model.deliveries.clear();
+        model.delivery_index = SpatialIndex::new(NEARBY_RADIUS);
+        for delivery in deliveries {
+            model
+                .delivery_index
+                .insert(delivery.delivery_id.clone(), delivery.delivery_position);
+            model
+                .deliveries
+                .insert(delivery.delivery_id.clone(), delivery);
+        }
+    }
+
+    /// Returns the restaurants at most `radius` away from `position`.
+    pub fn nearby_restaurants(&self, position: (f32, f32), radius: f32) -> Vec<RestaurantInfo> {
+        let model = self.read();
+        model
+            .restaurant_index
+            .within(position, radius)
+            .into_iter()
+            .filter_map(|restaurant_id| model.restaurants.get(restaurant_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns every restaurant.
+    pub fn restaurants(&self) -> Vec<RestaurantInfo> {
+        self.read().restaurants.values().cloned().collect()
+    }
+
+    /// Returns whether a restaurant is within its opening hours. Restaurants that never said
+    /// otherwise are open.
+    pub fn is_restaurant_open(&self, restaurant_id: &str) -> bool {
+        !self.read().closed_restaurants.contains(restaurant_id)
+    }
+
+    /// Returns the deliveries at most `radius` away from `position`.
+    pub fn nearby_deliveries(&self, position: (f32, f32), radius: f32) -> Vec<DeliveryDTO> {
+        let model = self.read();
+        model
+            .delivery_index
+            .within(position, radius)
+            .into_iter()
+            .filter_map(|delivery_id| model.deliveries.get(delivery_id))
+            .cloned()
+            .collect()
+    }
+
+    /// Returns every delivery.
+    pub fn deliveries(&self) -> Vec<DeliveryDTO> {
+        self.read().deliveries.values().cloned().collect()
+    }
+
+    /// Returns the deliveries available to take an order.
+    pub fn available_deliveries(&self) -> Vec<DeliveryDTO> {
+        self.read()
+            .deliveries
+            .values()
+            .filter(|delivery| delivery.status == DeliveryStatus::Available)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::hlc::HlcTimestamp;
+    use common::types::tenant::TenantId;
+
+    fn delivery(delivery_id: &str, position: (f32, f32), status: DeliveryStatus) -> DeliveryDTO {
+        DeliveryDTO {
+            delivery_position: position,
+            delivery_id: delivery_id.to_string(),
+            current_client_id: None,
+            current_order: None,
+            status,
+            vehicle: Default::default(),
+            time_stamp: HlcTimestamp::default(),
+            tenant_id: TenantId::default(),
+        }
+    }
+
+    #[test]
+    fn follows_the_changes_of_the_storage() {
+        let view = StorageReadView::default();
+        let reader = view.clone();
+        view.put_restaurant(RestaurantInfo {
+            id: "restaurant_1".to_string(),
+            position: (0.0, 0.0),
+            tags: Vec::new(),
+            tenant_id: TenantId::default(),
+        });
+        view.put_delivery(delivery(
+            "delivery_1",
+            (1.0, 1.0),
+            DeliveryStatus::Available,
+        ));
+        view.put_delivery(delivery(
+            "delivery_2",
+            (90.0, 90.0),
+            DeliveryStatus::Delivering,
+        ));
+        assert_eq!(reader.nearby_restaurants((2.0, 2.0), 5.0).len(), 1);
+        assert_eq!(reader.nearby_deliveries((0.0, 0.0), 5.0).len(), 1);
+        assert_eq!(reader.available_deliveries().len(), 1);
+        assert!(reader.is_restaurant_open("restaurant_1"));
+        view.set_restaurant_open("restaurant_1", false);
+        assert!(!reader.is_restaurant_open("restaurant_1"));
+
+        // El delivery se mueve lejos del restaurante
+        view.put_delivery(delivery(
+            "delivery_1",
+            (80.0, 80.0),
+            DeliveryStatus::Available,
+        ));
+        assert!(reader.nearby_deliveries((0.0, 0.0), 5.0).is_empty());
+        view.remove_restaurant("restaurant_1");
+        assert!(reader.restaurants().is_empty());
+        assert!(reader.is_restaurant_open("restaurant_1"));
+
+        view.replace_deliveries([delivery(
+            "delivery_3",
+            (0.0, 0.0),
+            DeliveryStatus::Available,
+        )]);
+        let deliveries = reader.deliveries();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].delivery_id, "delivery_3");
+        assert!(reader.nearby_deliveries((80.0, 80.0), 5.0).is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+use crate::messages::internal_messages::{GetConnectedUsers, GetLeader};
+use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::storage::Storage;
+use actix::prelude::*;
+use colored::Color;
+use common::constants::{USER_COLLECTOR_INTERVAL, USER_TTL};
+use common::logger::Logger;
+use common::messages::internal_messages::{GetStaleUsers, RemoveUser, TouchUsers};
+use common::types::hlc::HlcTimestamp;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Environment variable with how long a client or delivery can go unseen before it is
+/// evicted, in seconds.
+pub const USER_TTL_ENV_VAR: &str = "USER_TTL";
+
+/// Reads the time to live of the users from [`USER_TTL_ENV_VAR`], or [`USER_TTL`] if it is not
+/// set or is invalid.
+pub fn user_ttl_from_env() -> Duration {
+    match std::env::var(USER_TTL_ENV_VAR) {
+        Ok(value) => match value.trim().parse() {
+            Ok(secs) if secs > 0 => Duration::from_secs(secs),
+            _ => {
+                eprintln!(
+                    "[User Collector] Ignoring invalid {}: '{}'",
+                    USER_TTL_ENV_VAR, value
+                );
+                USER_TTL
+            }
+        },
+        Err(_) => USER_TTL,
+    }
+}
+
+/// Returns the time before which a user seen at `now` minus `ttl` or earlier is stale.
+fn seen_before(now: HlcTimestamp, ttl: Duration) -> HlcTimestamp {
+    HlcTimestamp {
+        physical_millis: now.physical_millis.saturating_sub(ttl.as_millis() as u64),
+        logical: 0,
+    }
+}
+
+/// The `UserCollector` actor periodically evicts from the storage of the leader the clients
+/// and deliveries that were not seen for longer than their time to live.
+///
+/// ## Responsibilities
+/// - Records, through the replicated log, that the users connected to the cluster are still
+///   there.
+/// - Evicts the clients and deliveries not seen for too long, unless they have active orders.
+///
+/// The [`Reaper`](crate::server_actors::reaper::Reaper) removes the users that disconnect from
+/// a coordinator, but its timers are lost if that coordinator stops. The collector catches the
+/// users left behind. Only the leader collects, so every coordinator evicts the same users.
+pub struct UserCollector {
+    /// The address of this coordinator.
+    my_addr: SocketAddr,
+    /// The storage the users are evicted from.
+    storage: Addr<Storage>,
+    /// The coordinator that knows the leader and the connected users.
+    coordinator: Addr<Coordinator>,
+    /// How long a user can go unseen.
+    ttl: Duration,
+    /// Users evicted so far.
+    evicted: u64,
+    /// Logger for user collector events.
+    logger: Logger,
+}
+
+impl UserCollector {
+    /// Creates a new `UserCollector`.
+    ///
+    /// ## Arguments
+    /// * `my_addr` - The address of this coordinator.
+    /// * `storage` - The storage the users are evicted from.
+    /// * `coordinator` - The coordinator that knows the leader and the connected users.
+    /// * `ttl` - How long a user can go unseen before it is evicted.
+    pub fn new(
+        my_addr: SocketAddr,
+        storage: Addr<Storage>,
+        coordinator: Addr<Coordinator>,
+        ttl: Duration,
+    ) -> Self {
+        Self {
+            my_addr,
+            storage,
+            coordinator,
+            ttl,
+            evicted: 0,
+            logger: Logger::new("User Collector", Color::BrightBlack),
+        }
+    }
+
+    /// Records the connected users and evicts the stale ones if this coordinator is the
+    /// leader.
+    fn collect(&mut self, ctx: &mut Context<Self>) {
+        let coordinator = self.coordinator.clone();
+        let storage = self.storage.clone();
+        let my_addr = self.my_addr;
+        let ttl = self.ttl;
+        async move {
+            if coordinator.send(GetLeader).await.ok().flatten() != Some(my_addr) {
+                return Vec::new();
+            }
+            let connected = coordinator
+                .send(GetConnectedUsers)
+                .await
+                .unwrap_or_default();
+            if !connected.is_empty() {
+                storage.do_send(TouchUsers {
+                    user_ids: connected,
+                    time_stamp: None,
+                });
+            }
+            storage
+                .send(GetStaleUsers {
+                    seen_before: seen_before(HlcTimestamp::now(), ttl),
+                })
+                .await
+                .unwrap_or_default()
+        }
+        .into_actor(self)
+        .map(|stale, act, _ctx| {
+            for user_id in stale {
+                act.evicted += 1;
+                act.logger.warn(format!(
+                    "{} was not seen for more than {:?}, evicting it ({} evicted so far)",
+                    user_id, act.ttl, act.evicted
+                ));
+                act.storage.do_send(RemoveUser { user_id });
+            }
+        })
+        .spawn(ctx);
+    }
+}
+
+impl Actor for UserCollector {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(USER_COLLECTOR_INTERVAL, |act, ctx| act.collect(ctx));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn users_seen_within_the_ttl_are_not_stale() {
+        let now = HlcTimestamp {
+            physical_millis: 10_000,
+            logical: 3,
+        };
+        let cutoff = seen_before(now, Duration::from_secs(4));
+        assert_eq!(cutoff.physical_millis, 6_000);
+        assert!(
+            HlcTimestamp {
+                physical_millis: 5_999,
+                logical: 9
+            } < cutoff
+        );
+        assert!(
+            HlcTimestamp {
+                physical_millis: 6_000,
+                logical: 0
+            } >= cutoff
+        );
+        assert_eq!(
+            seen_before(now, Duration::from_secs(60)),
+            HlcTimestamp::default()
+        );
+    }
+}
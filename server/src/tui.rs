@@ -0,0 +1,359 @@
+use crate::messages::internal_messages::{
+    GetCompactionStatus, GetFollowerProgress, GetInFlightOrders, GetPeerCounts, GetRingState,
+};
+use crate::server_actors::coordinator::Coordinator;
+use crate::server_actors::coordinator_manager::CoordinatorManager;
+use crate::server_actors::services::cluster_snapshot::FollowerProgress;
+use crate::server_actors::storage::Storage;
+use actix::prelude::*;
+use colored::Color;
+use common::constants::TUI_REFRESH_INTERVAL;
+use common::logger::Logger;
+use common::types::dtos::OrderDTO;
+use ratatui::DefaultTerminal;
+use ratatui::Frame;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Paragraph, Row, Table};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+/// Environment variable that shows the TUI instead of the logs (`1`, `true` or `on`). It
+/// only has effect if the server was built with the `tui` feature.
+pub const TUI_ENV_VAR: &str = "SERVER_TUI";
+
+/// Returns whether [`TUI_ENV_VAR`] enables the TUI.
+pub fn is_enabled() -> bool {
+    std::env::var(TUI_ENV_VAR).is_ok_and(|value| {
+        matches!(
+            value.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "on"
+        )
+    })
+}
+
+/// Everything the TUI shows, as last read from the actors of this coordinator.
+#[derive(Debug, Clone, Default)]
+pub struct TuiState {
+    /// ID of this coordinator.
+    pub node_id: String,
+    /// Address of this coordinator.
+    pub my_addr: Option<SocketAddr>,
+    /// Address of the current leader, if any.
+    pub leader: Option<SocketAddr>,
+    /// Epoch of the current leader.
+    pub epoch: u64,
+    /// Number of users connected to this coordinator, by peer type.
+    pub peers: BTreeMap<String, usize>,
+    /// Orders neither delivered nor cancelled, oldest first.
+    pub orders: Vec<OrderDTO>,
+    /// Index of the last entry of the local storage log.
+    pub last_log_index: u64,
+    /// How far the coordinators that pull the log from this one applied it.
+    pub followers: Vec<FollowerProgress>,
+}
+
+/// Draws `state` on the whole frame: the leader, the connected peers, the replication lag of
+/// each follower and the orders in flight.
+pub fn draw(frame: &mut Frame, state: &TuiState) {
+    let [header, middle, orders, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Length(8),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [peers, replication] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(middle);
+
+    let role = match state.leader {
+        Some(leader) if Some(leader) == state.my_addr => "leader".to_string(),
+        Some(leader) => format!("follower of {}", leader),
+        None => "no leader known".to_string(),
+    };
+    frame.render_widget(
+        Paragraph::new(format!(
+            "{} — {} (epoch {}) — log index {}",
+            state.node_id, role, state.epoch, state.last_log_index
+        ))
+        .block(Block::bordered().title(" PedidosRust server ")),
+        header,
+    );
+
+    let bold = Style::default().add_modifier(Modifier::BOLD);
+    let peer_rows = state
+        .peers
+        .iter()
+        .map(|(peer_type, count)| Row::new(vec![peer_type.replace("Type", ""), count.to_string()]));
+    frame.render_widget(
+        Table::new(peer_rows, [Constraint::Fill(1), Constraint::Length(8)])
+            .header(Row::new(vec!["Type", "Count"]).style(bold))
+            .block(Block::bordered().title(" Connected peers ")),
+        peers,
+    );
+
+    let follower_rows = state.followers.iter().map(|follower| {
+        Row::new(vec![
+            follower.coordinator_id.clone(),
+            follower.applied_index.to_string(),
+            state
+                .last_log_index
+                .saturating_sub(follower.applied_index)
+                .to_string(),
+            format!("{:.1}s ago", follower.reported_ago.as_secs_f32()),
+        ])
+    });
+    frame.render_widget(
+        Table::new(
+            follower_rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(9),
+                Constraint::Length(6),
+                Constraint::Length(11),
+            ],
+        )
+        .header(Row::new(vec!["Follower", "Applied", "Lag", "Reported"]).style(bold))
+        .block(Block::bordered().title(" Log replication ")),
+        replication,
+    );
+
+    let order_rows = state.orders.iter().map(|order| {
+        Row::new(vec![
+            order.order_id.to_string(),
+            order.client_id.clone(),
+            order.restaurant_id.clone(),
+            order.status.to_string(),
+            order.delivery_id.clone().unwrap_or_else(|| "-".to_string()),
+        ])
+    });
+    frame.render_widget(
+        Table::new(
+            order_rows,
+            [
+                Constraint::Length(20),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+                Constraint::Length(18),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(Row::new(vec!["Order", "Client", "Restaurant", "Status", "Delivery"]).style(bold))
+        .block(Block::bordered().title(format!(" Orders in flight ({}) ", state.orders.len()))),
+        orders,
+    );
+
+    frame.render_widget(
+        Paragraph::new("q: close the TUI and show the logs · Ctrl-C: stop the server"),
+        footer,
+    );
+}
+
+/// The `ServerTui` actor shows the state of this coordinator on the terminal, refreshed every
+/// [`TUI_REFRESH_INTERVAL`], while the logs are muted.
+///
+/// ## Responsibilities
+/// - Asks the `Coordinator`, the `Storage` and the `CoordinatorManager` for the connected
+///   peers, the orders in flight, the leader and the progress of the followers.
+/// - Draws them with [`draw`].
+/// - Closes on `q`, restoring the terminal and the logs, and stops the server on `Ctrl-C`.
+pub struct ServerTui {
+    /// The address of the Storage actor.
+    storage_addr: Addr<Storage>,
+    /// The address of the Coordinator actor.
+    coordinator_addr: Addr<Coordinator>,
+    /// The address of the CoordinatorManager actor.
+    coordinator_manager_addr: Addr<CoordinatorManager>,
+    /// The terminal, while the TUI is shown.
+    terminal: Option<DefaultTerminal>,
+    /// What is shown.
+    state: TuiState,
+    /// Logger for TUI events, shown once it closes.
+    logger: Logger,
+}
+
+impl ServerTui {
+    /// Creates a new `ServerTui`.
+    ///
+    /// ## Arguments
+    /// * `node_id` - ID of this coordinator.
+    /// * `my_addr` - Address of this coordinator.
+    /// * `storage_addr` - The address of the Storage actor.
+    /// * `coordinator_addr` - The address of the Coordinator actor.
+    /// * `coordinator_manager_addr` - The address of the CoordinatorManager actor.
+    pub fn new(
+        node_id: String,
+        my_addr: SocketAddr,
+        storage_addr: Addr<Storage>,
+        coordinator_addr: Addr<Coordinator>,
+        coordinator_manager_addr: Addr<CoordinatorManager>,
+    ) -> Self {
+        Self {
+            storage_addr,
+            coordinator_addr,
+            coordinator_manager_addr,
+            terminal: None,
+            state: TuiState {
+                node_id,
+                my_addr: Some(my_addr),
+                ..TuiState::default()
+            },
+            logger: Logger::new("TUI", Color::BrightCyan),
+        }
+    }
+
+    /// Handles the keys pressed since the last refresh.
+    fn handle_keys(&mut self, ctx: &mut Context<Self>) {
+        while event::poll(Duration::ZERO).unwrap_or(false) {
+            let Ok(Event::Key(key)) = event::read() else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    // En modo raw Ctrl-C no genera la señal, así que se apaga desde acá
+                    self.close();
+                    println!("Ctrl-C recibido, apagando...");
+                    std::process::exit(0);
+                }
+                KeyCode::Char('q') | KeyCode::Esc => ctx.stop(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Reads the state from the actors of this coordinator and draws it.
+    fn refresh(&mut self, ctx: &mut Context<Self>) {
+        let storage = self.storage_addr.clone();
+        let coordinator = self.coordinator_addr.clone();
+        let coordinator_manager = self.coordinator_manager_addr.clone();
+        async move {
+            let ring = coordinator_manager.send(GetRingState).await?;
+            let followers = coordinator_manager.send(GetFollowerProgress).await?;
+            let peers = coordinator.send(GetPeerCounts).await?;
+            let orders = storage.send(GetInFlightOrders).await?;
+            let log = storage.send(GetCompactionStatus).await?;
+            Ok::<_, MailboxError>((ring, followers, peers, orders, log))
+        }
+        .into_actor(self)
+        .map(|res, act, _ctx| {
+            if let Ok((ring, followers, peers, orders, log)) = res {
+                act.state.leader = ring.leader;
+                act.state.epoch = ring.epoch;
+                act.state.followers = followers;
+                act.state.peers = peers;
+                act.state.orders = orders;
+                act.state.last_log_index = log.next_log_id.saturating_sub(1);
+            }
+            if let Some(terminal) = act.terminal.as_mut()
+                && let Err(e) = terminal.draw(|frame| draw(frame, &act.state))
+            {
+                act.logger.error(format!("Error drawing the TUI: {}", e));
+            }
+        })
+        .wait(ctx);
+    }
+
+    /// Restores the terminal and the logs.
+    fn close(&mut self) {
+        if self.terminal.take().is_some() {
+            ratatui::restore();
+            Logger::set_muted(false);
+        }
+    }
+}
+
+impl Actor for ServerTui {
+    type Context = Context<Self>;
+
+    /// Takes over the terminal and starts refreshing it.
+    fn started(&mut self, ctx: &mut Self::Context) {
+        match ratatui::try_init() {
+            Ok(terminal) => {
+                Logger::set_muted(true);
+                self.terminal = Some(terminal);
+            }
+            Err(e) => {
+                self.logger
+                    .error(format!("Error starting the TUI, keeping the logs: {}", e));
+                ctx.stop();
+                return;
+            }
+        }
+        ctx.run_interval(TUI_REFRESH_INTERVAL, |act, ctx| {
+            act.handle_keys(ctx);
+            act.refresh(ctx);
+        });
+    }
+
+    /// Gives the terminal back to the logs.
+    fn stopped(&mut self, _ctx: &mut Self::Context) {
+        self.close();
+        self.logger.info("TUI closed, showing the logs.");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::types::dtos::OrderItem;
+    use common::types::order_status::OrderStatus;
+    use ratatui::Terminal;
+    use ratatui::backend::TestBackend;
+
+    #[test]
+    fn shows_the_leader_peers_lag_and_orders() {
+        let my_addr: SocketAddr = "127.0.0.1:8080".parse().unwrap();
+        let state = TuiState {
+            node_id: "server_0".to_string(),
+            my_addr: Some(my_addr),
+            leader: Some(my_addr),
+            epoch: 3,
+            peers: BTreeMap::from([("ClientType".to_string(), 2)]),
+            orders: vec![OrderDTO {
+                status: OrderStatus::Preparing,
+                ..OrderDTO::new(
+                    42,
+                    "client_1",
+                    "restaurant_1",
+                    vec![OrderItem::new("Pizza", 1)],
+                )
+            }],
+            last_log_index: 120,
+            followers: vec![FollowerProgress {
+                coordinator_id: "server_1".to_string(),
+                applied_index: 100,
+                reported_ago: Duration::from_millis(500),
+            }],
+        };
+        let mut terminal = Terminal::new(TestBackend::new(100, 24)).unwrap();
+        terminal.draw(|frame| draw(frame, &state)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let lines: Vec<String> = (0..buffer.area.height)
+            .map(|y| {
+                (0..buffer.area.width)
+                    .map(|x| buffer[(x, y)].symbol())
+                    .collect()
+            })
+            .collect();
+        let screen = lines.join("\n");
+        assert!(screen.contains("server_0 — leader (epoch 3)"));
+        assert!(screen.contains("Client"));
+        // El seguidor aplicó hasta la 100 de 120 entradas
+        let follower: Vec<&str> = lines
+            .iter()
+            .find(|line| line.contains("server_1"))
+            .unwrap()
+            .split(|c: char| c.is_whitespace() || c == '│')
+            .filter(|word| !word.is_empty())
+            .collect();
+        assert!(follower.windows(3).any(|w| w == ["server_1", "100", "20"]));
+        assert!(screen.contains("Orders in flight (1)"));
+        assert!(screen.contains("Preparing"));
+    }
+}
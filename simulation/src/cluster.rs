@@ -0,0 +1,355 @@
+use actix::prelude::*;
+use api::api_actors::session::ClientSession;
+use api::messages::internal_messages::{CancelTrackedOrder, FetchOrderStatus, SubmitOrder};
+use common::constants::{BASE_PORT, NUM_COORDINATORS, PAYMENT_GATEWAY_PORT, SERVER_IP_ADDRESS};
+use common::messages::NotifyOrderUpdated;
+use common::messages::internal_messages::{GetOrderTimeline, GetPendingOrderUpdates};
+use common::network::memory;
+use common::network::transport::TransportKind;
+use common::types::dtos::{OrderDTO, OrderItem};
+use common::types::order_event::OrderEvent;
+use common::types::order_priority::OrderPriority;
+use common::types::order_status::OrderStatus;
+use common::types::restaurant_info::RestaurantInfo;
+use common::types::tenant::TenantId;
+use common::utils::TravelModel;
+use delivery::delivery_actors::delivery::Delivery;
+use payment::payment::PaymentGateway;
+use payment::payment_acceptor::PaymentAcceptor;
+use payment::wallet::Wallets;
+use restaurant::policy::AcceptancePolicy;
+use restaurant::restaurant_actors::restaurant::Restaurant;
+use server::messages::internal_messages::GetLeader;
+use server::server_acceptor::acceptor::Acceptor;
+use server::server_actors::coordinator::Coordinator;
+use server::server_actors::coordinator_manager::WriteAckMode;
+use server::server_actors::election::ElectionKind;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::{IpAddr, SocketAddr};
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+/// Virtual time given to each node to start before the next one.
+const STARTUP_STEP: Duration = Duration::from_secs(1);
+/// Virtual time between two checks while waiting for a condition.
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Position of every simulated peer, so that they are all nearby.
+const POSITION: (f32, f32) = (5.0, 5.0);
+
+/// Size and settings of a simulated cluster.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationConfig {
+    /// Number of coordinators, at most [`NUM_COORDINATORS`].
+    pub coordinators: u16,
+    /// Number of clients, each one with its own [`ClientSession`].
+    pub clients: usize,
+    /// Number of restaurants. They accept every order.
+    pub restaurants: usize,
+    /// Number of deliveries. They accept every offer.
+    pub deliveries: usize,
+    /// When the coordinators acknowledge storage writes.
+    pub write_ack: WriteAckMode,
+    /// The leader election algorithm of the coordinators.
+    pub election: ElectionKind,
+    /// How long the trips of the deliveries take.
+    pub travel: TravelModel,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            coordinators: NUM_COORDINATORS,
+            clients: 1,
+            restaurants: 1,
+            deliveries: 1,
+            write_ack: WriteAckMode::default(),
+            election: ElectionKind::default(),
+            travel: TravelModel::default(),
+        }
+    }
+}
+
+/// Runs `test` against a cluster simulated in the current thread.
+///
+/// Every peer runs as an actor of a single Actix system and the connections go through
+/// the in-memory network of [`memory`], so no real socket is opened. The clock is virtual:
+/// it only moves forward when every actor is idle, jumping straight to the next timer, so
+/// scenarios that take minutes of cluster time run in milliseconds.
+///
+/// ## Arguments
+/// * `config` - The size of the cluster.
+/// * `test` - Receives the started [`Cluster`] and drives the scenario.
+pub fn simulate<F, Fut>(config: SimulationConfig, test: F) -> Fut::Output
+where
+    F: FnOnce(Cluster) -> Fut,
+    Fut: Future,
+{
+    assert!(
+        (1..=NUM_COORDINATORS).contains(&config.coordinators),
+        "A simulation needs between 1 and {} coordinators",
+        NUM_COORDINATORS
+    );
+    let system = System::new();
+    let output = panic::catch_unwind(AssertUnwindSafe(|| {
+        system.block_on(async move {
+            tokio::time::pause();
+            memory::enable();
+            let cluster = Cluster::start(config).await;
+            test(cluster).await
+        })
+    }));
+    memory::disable();
+    // Los actores terminan el proceso al destruirse, así que el sistema no se destruye,
+    // tampoco si el escenario falló: si no, la falla terminaría el proceso sin reportarse
+    std::mem::forget(system);
+    match output {
+        Ok(output) => output,
+        Err(failure) => panic::resume_unwind(failure),
+    }
+}
+
+/// Lets the virtual clock move forward by `duration`, running every timer due meanwhile.
+pub async fn advance(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// A running simulated cluster, used to drive and inspect a scenario.
+pub struct Cluster {
+    /// Addresses of the coordinators, by index.
+    servers: Vec<SocketAddr>,
+    /// The coordinator actors, by index.
+    coordinators: Vec<Addr<Coordinator>>,
+    /// Indexes of the coordinators that were killed.
+    killed: HashSet<usize>,
+    /// Sessions of the clients, by index.
+    sessions: Vec<Addr<ClientSession>>,
+    /// IDs of the restaurants, by index.
+    restaurant_ids: Vec<String>,
+}
+
+impl Cluster {
+    /// Starts the payment gateway, the coordinators, and then the restaurants, deliveries
+    /// and clients, giving each coordinator time to join the ring.
+    async fn start(config: SimulationConfig) -> Self {
+        let ip: IpAddr = SERVER_IP_ADDRESS.parse().expect("Invalid IP address");
+
+        let payment_gateway = PaymentGateway::new(Wallets::unlimited()).start();
+        PaymentAcceptor::new(SocketAddr::new(ip, PAYMENT_GATEWAY_PORT), payment_gateway).start();
+        advance(STARTUP_STEP).await;
+
+        let servers: Vec<SocketAddr> = (0..config.coordinators)
+            .map(|i| SocketAddr::new(ip, BASE_PORT + i))
+            .collect();
+        let ring_nodes: HashMap<String, SocketAddr> = servers
+            .iter()
+            .enumerate()
+            .map(|(i, addr)| (format!("server_{}", i), *addr))
+            .collect();
+
+        let mut coordinators = Vec::with_capacity(servers.len());
+        for addr in &servers {
+            let coordinator = Coordinator::new(
+                *addr,
+                ring_nodes.clone(),
+                TransportKind::Tcp,
+                config.write_ack,
+                config.election,
+                None,
+            )
+            .await
+            .start();
+            Acceptor::new(*addr, coordinator.clone()).start();
+            coordinators.push(coordinator);
+            advance(STARTUP_STEP).await;
+        }
+
+        let mut restaurant_ids = Vec::with_capacity(config.restaurants);
+        for i in 0..config.restaurants {
+            let id = format!("sim_restaurant_{}", i);
+            let info = RestaurantInfo {
+                id: id.clone(),
+                position: POSITION,
+                tags: Vec::new(),
+                tenant_id: TenantId::default(),
+            };
+            let policy = AcceptancePolicy {
+                probability: 1.0,
+                ..AcceptancePolicy::default()
+            };
+            Restaurant::new(info, policy, servers.clone()).await.start();
+            restaurant_ids.push(id);
+        }
+
+        for i in 0..config.deliveries {
+            Delivery::new(
+                servers.clone(),
+                format!("sim_delivery_{}", i),
+                POSITION,
+                1.0,
+            )
+            .await
+            .with_travel_model(config.travel)
+            .start();
+        }
+
+        let mut sessions = Vec::with_capacity(config.clients);
+        for i in 0..config.clients {
+            let session =
+                ClientSession::new(servers.clone(), format!("sim_client_{}", i), POSITION)
+                    .await
+                    .expect("The simulated client could not connect");
+            sessions.push(session.start());
+        }
+        advance(STARTUP_STEP).await;
+
+        Self {
+            servers,
+            coordinators,
+            killed: HashSet::new(),
+            sessions,
+            restaurant_ids,
+        }
+    }
+
+    /// Returns the addresses of the coordinators, by index.
+    pub fn servers(&self) -> &[SocketAddr] {
+        &self.servers
+    }
+
+    /// Places an order of a single dish from a client at a restaurant.
+    ///
+    /// ## Arguments
+    /// * `client` - Index of the client placing the order.
+    /// * `restaurant` - Index of the restaurant.
+    /// * `dish_name` - The dish being ordered.
+    pub async fn place_order(&self, client: usize, restaurant: usize, dish_name: &str) -> OrderDTO {
+        self.sessions[client]
+            .send(SubmitOrder {
+                restaurant_id: self.restaurant_ids[restaurant].clone(),
+                items: vec![OrderItem::new(dish_name, 1)],
+                priority: OrderPriority::Standard,
+                promo_code: None,
+            })
+            .await
+            .expect("The client session stopped")
+            .expect("The cluster did not take the order")
+    }
+
+    /// Asks the cluster to cancel an order on behalf of the client that placed it.
+    pub async fn cancel_order(&self, client: usize, order_id: u64) {
+        self.sessions[client]
+            .send(CancelTrackedOrder { order_id })
+            .await
+            .expect("The client session stopped");
+    }
+
+    /// Returns the status of an order as the cluster tells the client that placed it.
+    pub async fn order_status(&self, client: usize, order_id: u64) -> Option<OrderStatus> {
+        self.sessions[client]
+            .send(FetchOrderStatus { order_id })
+            .await
+            .expect("The client session stopped")
+            .ok()
+            .flatten()
+            .map(|order| order.status)
+    }
+
+    /// Waits, in virtual time, until the client sees the order in `status`.
+    ///
+    /// ## Returns
+    /// `false` if the order did not reach `status` within `timeout`.
+    pub async fn wait_for_status(
+        &self,
+        client: usize,
+        order_id: u64,
+        status: OrderStatus,
+        timeout: Duration,
+    ) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while tokio::time::Instant::now() < deadline {
+            if self.order_status(client, order_id).await.as_ref() == Some(&status) {
+                return true;
+            }
+            advance(POLL_INTERVAL).await;
+        }
+        false
+    }
+
+    /// Returns the timeline of an order as recorded by a coordinator.
+    ///
+    /// ## Arguments
+    /// * `coordinator` - Index of the coordinator.
+    /// * `order_id` - The ID of the order.
+    pub async fn order_timeline(&self, coordinator: usize, order_id: u64) -> Vec<OrderEvent> {
+        self.coordinators[coordinator]
+            .send(GetOrderTimeline { order_id })
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Returns the order updates a client did not acknowledge yet, as kept by a coordinator.
+    ///
+    /// ## Arguments
+    /// * `coordinator` - Index of the coordinator.
+    /// * `client` - Index of the client.
+    pub async fn pending_order_updates(
+        &self,
+        coordinator: usize,
+        client: usize,
+    ) -> Vec<NotifyOrderUpdated> {
+        self.coordinators[coordinator]
+            .send(GetPendingOrderUpdates {
+                user_id: format!("sim_client_{}", client),
+            })
+            .await
+            .unwrap_or_default()
+    }
+
+    /// Returns the leader, if every coordinator still alive agrees on one that is alive.
+    pub async fn leader(&self) -> Option<SocketAddr> {
+        let mut leaders = HashSet::new();
+        for (i, coordinator) in self.coordinators.iter().enumerate() {
+            if !self.killed.contains(&i) {
+                leaders.insert(coordinator.send(GetLeader).await.ok().flatten());
+            }
+        }
+        match leaders.into_iter().collect::<Vec<_>>()[..] {
+            [Some(leader)] if !self.is_killed(leader) => Some(leader),
+            _ => None,
+        }
+    }
+
+    /// Waits, in virtual time, until the coordinators still alive agree on a leader.
+    ///
+    /// ## Returns
+    /// The leader, or `None` if there was none within `timeout`.
+    pub async fn wait_for_leader(&self, timeout: Duration) -> Option<SocketAddr> {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while tokio::time::Instant::now() < deadline {
+            if let Some(leader) = self.leader().await {
+                return Some(leader);
+            }
+            advance(POLL_INTERVAL).await;
+        }
+        None
+    }
+
+    /// Kills the coordinator at `addr`: every connection to or from it is cut and new ones
+    /// are refused, as if its process had crashed.
+    pub fn kill_coordinator(&mut self, addr: SocketAddr) {
+        let Some(index) = self.servers.iter().position(|server| *server == addr) else {
+            return;
+        };
+        memory::isolate(addr, &format!("server_{}", index));
+        self.killed.insert(index);
+    }
+
+    /// Returns whether the coordinator at `addr` was killed.
+    fn is_killed(&self, addr: SocketAddr) -> bool {
+        self.servers
+            .iter()
+            .position(|server| *server == addr)
+            .is_some_and(|index| self.killed.contains(&index))
+    }
+}
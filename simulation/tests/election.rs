@@ -0,0 +1,47 @@
+use server::server_actors::election::ElectionKind;
+use simulation::cluster::{SimulationConfig, simulate};
+use std::time::Duration;
+
+/// The election algorithms every scenario runs with.
+const ELECTIONS: [ElectionKind; 2] = [ElectionKind::Ring, ElectionKind::Bully];
+
+fn config(election: ElectionKind) -> SimulationConfig {
+    SimulationConfig {
+        election,
+        ..SimulationConfig::default()
+    }
+}
+
+#[test]
+fn coordinators_agree_on_a_leader() {
+    for election in ELECTIONS {
+        simulate(config(election), |cluster| async move {
+            assert!(
+                cluster
+                    .wait_for_leader(Duration::from_secs(30))
+                    .await
+                    .is_some(),
+                "No leader was elected with the {:?} election",
+                election
+            );
+        });
+    }
+}
+
+#[test]
+fn new_leader_is_elected_when_the_leader_dies() {
+    for election in ELECTIONS {
+        simulate(config(election), |mut cluster| async move {
+            let leader = cluster
+                .wait_for_leader(Duration::from_secs(30))
+                .await
+                .expect("No leader was elected");
+            cluster.kill_coordinator(leader);
+            let new_leader = cluster
+                .wait_for_leader(Duration::from_secs(60))
+                .await
+                .expect("No leader was elected after the failure");
+            assert_ne!(new_leader, leader, "{:?} election", election);
+        });
+    }
+}
@@ -0,0 +1,186 @@
+use common::types::order_event::OrderEventKind;
+use common::types::order_status::OrderStatus;
+use server::server_actors::coordinator_manager::WriteAckMode;
+use simulation::cluster::{SimulationConfig, advance, simulate};
+use std::time::Duration;
+
+#[test]
+fn order_is_delivered() {
+    simulate(SimulationConfig::default(), |cluster| async move {
+        let order = cluster.place_order(0, 0, "Pizza").await;
+        assert!(
+            cluster
+                .wait_for_status(
+                    0,
+                    order.order_id,
+                    OrderStatus::Delivered,
+                    Duration::from_secs(120)
+                )
+                .await
+        );
+    });
+}
+
+#[test]
+fn every_coordinator_records_the_timeline_of_an_order() {
+    simulate(SimulationConfig::default(), |cluster| async move {
+        let order = cluster.place_order(0, 0, "Pizza").await;
+        assert!(
+            cluster
+                .wait_for_status(
+                    0,
+                    order.order_id,
+                    OrderStatus::Delivered,
+                    Duration::from_secs(120)
+                )
+                .await
+        );
+        // Se le da tiempo a la replicación de la baja del pedido
+        advance(Duration::from_secs(10)).await;
+
+        let timeline = cluster.order_timeline(0, order.order_id).await;
+        let kinds: Vec<OrderEventKind> = timeline.iter().map(|event| event.kind).collect();
+        assert_eq!(kinds.first(), Some(&OrderEventKind::Created));
+        assert!(kinds.contains(&OrderEventKind::DeliveryAssigned));
+        let last = timeline.last().expect("The timeline is empty");
+        assert_eq!(last.kind, OrderEventKind::Removed);
+        assert_eq!(last.status, OrderStatus::Delivered);
+        assert!(last.actor.starts_with("delivery"));
+        for coordinator in 1..cluster.servers().len() {
+            assert_eq!(
+                cluster.order_timeline(coordinator, order.order_id).await,
+                timeline
+            );
+        }
+    });
+}
+
+#[test]
+fn clients_acknowledge_every_order_update() {
+    simulate(SimulationConfig::default(), |cluster| async move {
+        let order = cluster.place_order(0, 0, "Pizza").await;
+        assert!(
+            cluster
+                .wait_for_status(
+                    0,
+                    order.order_id,
+                    OrderStatus::Delivered,
+                    Duration::from_secs(120)
+                )
+                .await
+        );
+        // Se le da tiempo a la replicación de las confirmaciones
+        advance(Duration::from_secs(10)).await;
+
+        for coordinator in 0..cluster.servers().len() {
+            assert!(
+                cluster
+                    .pending_order_updates(coordinator, 0)
+                    .await
+                    .is_empty()
+            );
+        }
+    });
+}
+
+#[test]
+fn order_is_delivered_after_the_leader_dies() {
+    simulate(SimulationConfig::default(), |mut cluster| async move {
+        let leader = cluster
+            .wait_for_leader(Duration::from_secs(30))
+            .await
+            .expect("No leader was elected");
+        cluster.kill_coordinator(leader);
+        cluster
+            .wait_for_leader(Duration::from_secs(60))
+            .await
+            .expect("No leader was elected after the failure");
+
+        let order = cluster.place_order(0, 0, "Empanadas").await;
+        assert!(
+            cluster
+                .wait_for_status(
+                    0,
+                    order.order_id,
+                    OrderStatus::Delivered,
+                    Duration::from_secs(120)
+                )
+                .await
+        );
+    });
+}
+
+#[test]
+fn order_is_delivered_when_the_leader_dies_with_quorum_writes() {
+    let config = SimulationConfig {
+        deliveries: 2,
+        write_ack: WriteAckMode::Quorum,
+        ..SimulationConfig::default()
+    };
+    simulate(config, |mut cluster| async move {
+        let order = cluster.place_order(0, 0, "Lomito").await;
+        assert!(
+            cluster
+                .wait_for_status(
+                    0,
+                    order.order_id,
+                    OrderStatus::Preparing,
+                    Duration::from_secs(30)
+                )
+                .await
+        );
+        let leader = cluster
+            .wait_for_leader(Duration::from_secs(30))
+            .await
+            .expect("No leader was elected");
+        cluster.kill_coordinator(leader);
+
+        assert!(
+            cluster
+                .wait_for_status(
+                    0,
+                    order.order_id,
+                    OrderStatus::Delivered,
+                    Duration::from_secs(180)
+                )
+                .await
+        );
+    });
+}
+
+#[test]
+fn order_is_cancelled_by_the_client() {
+    simulate(SimulationConfig::default(), |cluster| async move {
+        let order = cluster.place_order(0, 0, "Milanesa").await;
+        assert!(
+            cluster
+                .wait_for_status(
+                    0,
+                    order.order_id,
+                    OrderStatus::Preparing,
+                    Duration::from_secs(30)
+                )
+                .await
+        );
+        cluster.cancel_order(0, order.order_id).await;
+        assert!(
+            cluster
+                .wait_for_status(
+                    0,
+                    order.order_id,
+                    OrderStatus::Cancelled,
+                    Duration::from_secs(30)
+                )
+                .await
+        );
+    });
+}
+
+#[test]
+#[should_panic(expected = "the scenario failed")]
+fn a_failing_scenario_is_reported() {
+    simulate(SimulationConfig::default(), |cluster| async move {
+        cluster.place_order(0, 0, "Pizza").await;
+        panic!("the scenario failed");
+    });
+}